@@ -0,0 +1,213 @@
+//! Benchmarks for sync planning on synthetic libraries.
+//!
+//! Run with `cargo bench`. Library sizes span 10k–100k tracks; filesystem
+//! scans use a real directory tree under the system temp dir in which half
+//! of the planned tracks already exist.
+
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use qoget::manifest::Manifest;
+use qoget::models::{
+    Album, AlbumId, Artist, DiscNumber, PaginatedList, PurchaseInfo, PurchaseList, Service,
+    SizeMismatchPolicy, Track, TrackId, TrackNumber,
+};
+use qoget::path::{Naming, track_path};
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+
+const PLAN_SIZES: &[usize] = &[10_000, 50_000, 100_000];
+const SCAN_SIZES: &[usize] = &[10_000, 50_000];
+const TRACKS_PER_ALBUM: usize = 12;
+
+/// Synthetic purchase list with `tracks` album tracks, two discs for every
+/// tenth album, a guest performer on every fifth track, and one standalone
+/// purchase per 100 tracks that duplicates an album track (exercises dedup).
+fn synthetic_library(tracks: usize) -> PurchaseList {
+    let album_count = tracks.div_ceil(TRACKS_PER_ALBUM);
+    let albums: Vec<Album> = (0..album_count)
+        .map(|a| {
+            let artist = Artist {
+                id: a as u64,
+                name: format!("Artist {}", a / 3),
+            };
+            let media_count = if a % 10 == 0 { 2 } else { 1 };
+            let items: Vec<Track> = (0..TRACKS_PER_ALBUM)
+                .map(|t| {
+                    let performer = if t % 5 == 0 {
+                        Artist {
+                            id: 999_999,
+                            name: format!("Guest {t}"),
+                        }
+                    } else {
+                        artist.clone()
+                    };
+                    Track {
+                        id: TrackId((a * TRACKS_PER_ALBUM + t) as u64),
+                        title: format!("Song number {t} from album {a}"),
+                        version: None,
+                        track_number: TrackNumber(t as u8 + 1),
+                        media_number: DiscNumber(if media_count > 1 { t as u8 % 2 + 1 } else { 1 }),
+                        duration: 240,
+                        performer,
+                        isrc: None,
+                        purchase: PurchaseInfo::default(),
+                    }
+                })
+                .collect();
+            Album {
+                id: AlbumId(format!("album-{a}")),
+                title: format!("Album: {a} / Deluxe?"),
+                version: None,
+                artist,
+                media_count,
+                tracks_count: TRACKS_PER_ALBUM as u16,
+                upc: None,
+                release_date: None,
+                artists: Vec::new(),
+                image: None,
+                tracks: Some(PaginatedList {
+                    offset: 0,
+                    limit: TRACKS_PER_ALBUM as u64,
+                    total: TRACKS_PER_ALBUM as u64,
+                    items,
+                }),
+                purchase: PurchaseInfo::default(),
+            }
+        })
+        .collect();
+
+    let standalone: Vec<Track> = albums
+        .iter()
+        .step_by(100 / TRACKS_PER_ALBUM)
+        .filter_map(|a| a.tracks.as_ref()?.items.first().cloned())
+        .collect();
+
+    PurchaseList {
+        albums,
+        tracks: standalone,
+    }
+}
+
+/// Materialize every other planned track as a small file under `base`.
+fn synthetic_layout(purchases: &PurchaseList, base: &Path) {
+    let _ = std::fs::remove_dir_all(base);
+    for (i, task) in collect_tasks(purchases, base, ".mp3", Naming::default())
+        .iter()
+        .enumerate()
+    {
+        if i % 2 == 0 {
+            std::fs::create_dir_all(task.target_path.parent().unwrap()).unwrap();
+            std::fs::write(&task.target_path, b"x").unwrap();
+        }
+    }
+}
+
+fn bench_path_generation(c: &mut Criterion) {
+    let purchases = synthetic_library(TRACKS_PER_ALBUM);
+    let album = &purchases.albums[0];
+    let track = &album.tracks.as_ref().unwrap().items[0];
+    let base = Path::new("/music");
+
+    c.bench_function("track_path", |b| {
+        b.iter(|| {
+            track_path(
+                black_box(base),
+                black_box(album),
+                black_box(track),
+                ".mp3",
+                Naming::default(),
+            )
+        })
+    });
+}
+
+fn bench_collect_tasks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collect_tasks");
+    for &size in PLAN_SIZES {
+        let purchases = synthetic_library(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &purchases, |b, p| {
+            b.iter(|| collect_tasks(p, Path::new("/music"), ".mp3", Naming::default()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_build_sync_plan(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("build_sync_plan");
+    group.sample_size(20);
+    for &size in PLAN_SIZES {
+        let purchases = synthetic_library(size);
+        let tasks = collect_tasks(
+            &purchases,
+            Path::new("/nonexistent"),
+            ".mp3",
+            Naming::default(),
+        );
+        let existing = rt
+            .block_on(scan_existing(
+                &tasks,
+                Service::Qobuz,
+                &Manifest::default(),
+                Naming::default(),
+            ))
+            .unwrap();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &purchases, |b, p| {
+            b.iter_batched(
+                || collect_tasks(p, Path::new("/nonexistent"), ".mp3", Naming::default()),
+                |tasks| {
+                    build_sync_plan(
+                        tasks,
+                        &existing,
+                        false,
+                        SizeMismatchPolicy::Keep,
+                        Naming::default(),
+                    )
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_scan_existing(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("scan_existing");
+    group.sample_size(10);
+    for &size in SCAN_SIZES {
+        let base: PathBuf = std::env::temp_dir().join(format!("qoget-bench-{size}"));
+        let purchases = synthetic_library(size);
+        synthetic_layout(&purchases, &base);
+        let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+        let manifest = Manifest::default();
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                rt.block_on(scan_existing(
+                    &tasks,
+                    Service::Qobuz,
+                    &manifest,
+                    Naming::default(),
+                ))
+                .unwrap()
+            })
+        });
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_path_generation,
+    bench_collect_tasks,
+    bench_build_sync_plan,
+    bench_scan_existing
+);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::manifest::Manifest;
+use crate::models::{PurchaseList, Service};
+use crate::path::{Naming, album_dir};
+
+/// File name cover art is saved under, next to the album's tracks.
+pub const COVER_FILE: &str = "cover.jpg";
+
+/// Folder art names other tools write; any of them counts as having art.
+const COVER_NAMES: &[&str] = &[
+    "cover.jpg",
+    "cover.jpeg",
+    "cover.png",
+    "folder.jpg",
+    "folder.png",
+    "front.jpg",
+];
+
+/// An album directory that needs its cover fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtTask {
+    pub service: Service,
+    pub artist: String,
+    pub title: String,
+    /// Album directory, relative to the target directory.
+    pub dir: PathBuf,
+    pub url: String,
+}
+
+/// What `art backfill` found for the purchased albums.
+#[derive(Debug, Default)]
+pub struct ArtPlan {
+    pub tasks: Vec<ArtTask>,
+    /// Albums whose directory already has cover art.
+    pub present: usize,
+    /// Albums with no directory in the library yet.
+    pub not_synced: usize,
+    /// Albums on disk for which the store lists no image.
+    pub unavailable: usize,
+}
+
+/// True if `dir` holds a cover image under any of the usual names.
+pub fn has_cover(dir: &Path) -> bool {
+    COVER_NAMES.iter().any(|name| dir.join(name).is_file())
+}
+
+/// Find purchased albums that are in `target_dir` but lack cover art.
+///
+/// An album's directory is taken from the manifest when it recorded one of
+/// the album's tracks (so albums moved by `verify --update-manifest` are
+/// still found), otherwise it is the directory sync would have used.
+pub fn plan_backfill(
+    target_dir: &Path,
+    purchases: &[(Service, &PurchaseList)],
+    manifest: &Manifest,
+    naming: Naming,
+) -> ArtPlan {
+    let mut plan = ArtPlan::default();
+    let mut seen = HashSet::new();
+
+    for &(service, list) in purchases {
+        for album in &list.albums {
+            let recorded = album
+                .tracks
+                .iter()
+                .flat_map(|t| &t.items)
+                .find_map(|t| manifest.recorded_album_dir(service, t.id));
+            let dir = recorded.unwrap_or_else(|| album_dir(Path::new(""), album, naming));
+            if !seen.insert(dir.clone()) {
+                continue;
+            }
+
+            let full = target_dir.join(&dir);
+            if !full.is_dir() {
+                plan.not_synced += 1;
+            } else if has_cover(&full) {
+                plan.present += 1;
+            } else if let Some(url) = album.image.as_ref().and_then(|i| i.large.clone()) {
+                plan.tasks.push(ArtTask {
+                    service,
+                    artist: album.artist.name.clone(),
+                    title: album.title.clone(),
+                    dir,
+                    url,
+                });
+            } else {
+                plan.unavailable += 1;
+            }
+        }
+    }
+    plan
+}
+
+/// Download `url` to `dir/cover.jpg`. The image is written to a temporary
+/// file first, so an interrupted fetch never leaves a truncated cover.
+pub async fn fetch_cover(http: &reqwest::Client, url: &str, dir: &Path) -> Result<u64> {
+    let resp = http
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("fetching {url}"))?;
+    if !resp.status().is_success() {
+        bail!("{url} returned HTTP {}", resp.status());
+    }
+    let bytes = resp
+        .bytes()
+        .await
+        .with_context(|| format!("reading {url}"))?;
+    if !bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        bail!("{url} did not return a JPEG image");
+    }
+
+    let dest = dir.join(COVER_FILE);
+    let tmp = dir.join(format!(".{COVER_FILE}.part"));
+    tokio::fs::write(&tmp, &bytes)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, &dest)
+        .await
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), dest.display()))?;
+    Ok(bytes.len() as u64)
+}
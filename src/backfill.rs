@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Service;
+use crate::report::SyncReport;
+
+/// Where a target directory's backfill progress is kept.
+const STATE_FILE: &str = ".qoget/backfill.json";
+
+/// Progress of a first sync of a whole library, spread over `backfill`
+/// sessions, possibly days apart. Each session resumes from what is on disk
+/// like `sync` does; this only remembers what the sessions did and what was
+/// still to come after the last one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Backfill {
+    pub sessions: Vec<Session>,
+    /// What each service had left after its latest session, in the order
+    /// later sessions will get to it: Qobuz tracks and Bandcamp purchases,
+    /// as "Artist - Title".
+    pub backlog: BTreeMap<Service, Vec<String>>,
+}
+
+/// One `backfill` run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Unix time the session started.
+    pub started_at: u64,
+    pub duration_secs: f64,
+    /// Files downloaded.
+    pub downloaded: usize,
+    /// Items that failed and stay in the backlog.
+    pub failed: usize,
+    /// Items left for a later session by the budget.
+    pub deferred: usize,
+}
+
+impl Backfill {
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join(STATE_FILE)
+    }
+
+    /// The saved progress, or a fresh start if there is none.
+    pub fn load(target_dir: &Path) -> Result<Self> {
+        let path = Self::path(target_dir);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("parsing {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, target_dir: &Path) -> Result<()> {
+        let path = Self::path(target_dir);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(self)? + "\n";
+        std::fs::write(&tmp, json).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+
+    /// Add a finished session. The backlog of each service it synced is
+    /// replaced by what that service failed or deferred; a service that
+    /// didn't get as far as downloading keeps its old backlog.
+    pub fn record_session(&mut self, report: &SyncReport) {
+        let mut session = Session {
+            started_at: report.started_at,
+            duration_secs: report.duration_secs,
+            downloaded: 0,
+            failed: 0,
+            deferred: 0,
+        };
+        for service in &report.services {
+            let deferred: Vec<&str> = service
+                .skipped
+                .iter()
+                .filter(|s| s.reason == Some("deferred"))
+                .map(|s| s.name.as_str())
+                .collect();
+            session.downloaded += service.succeeded.len();
+            session.failed += service.failed.len();
+            session.deferred += deferred.len();
+
+            let attempted = !service.succeeded.is_empty()
+                || !service.failed.is_empty()
+                || !service.skipped.is_empty();
+            if service.error.is_some() && !attempted {
+                continue;
+            }
+            let mut backlog: Vec<String> = service.failed.iter().map(|f| f.name.clone()).collect();
+            for name in deferred {
+                if !backlog.iter().any(|b| b == name) {
+                    backlog.push(name.to_string());
+                }
+            }
+            self.backlog.insert(service.service, backlog);
+        }
+        self.sessions.push(session);
+    }
+
+    /// Files downloaded over all sessions.
+    pub fn downloaded(&self) -> usize {
+        self.sessions.iter().map(|s| s.downloaded).sum()
+    }
+
+    /// Items still to fetch after the latest session.
+    pub fn remaining(&self) -> usize {
+        self.backlog.values().map(Vec::len).sum()
+    }
+
+    /// Whether a session has run and left nothing behind.
+    pub fn is_complete(&self) -> bool {
+        !self.sessions.is_empty() && self.remaining() == 0
+    }
+}
+
+/// What a service's backlog entries are.
+pub fn unit(service: Service) -> &'static str {
+    match service {
+        Service::Qobuz => "tracks",
+        Service::Bandcamp => "purchases",
+    }
+}
@@ -1,108 +1,255 @@
-use std::collections::HashMap;
-use std::io::{Cursor, Read as _};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read as _;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, bail};
+use indicatif::ProgressBar;
 use regex::Regex;
+use reqwest::cookie::{CookieStore, Jar};
 use serde::Deserialize;
+use tracing::{debug, warn};
 
+use crate::config::NetworkConfig;
+use crate::deep_scan;
+use crate::download::stream_to_file;
+use crate::health::AuthError;
+use crate::manifest::{HashingWriter, sha256_hex};
 use crate::models::{
-    Album, AlbumId, Artist, BandcampCollectionItem, BandcampCollectionResponse,
-    BandcampDownloadInfo, DiscNumber, PurchaseList, Track, TrackId, TrackNumber,
+    Album, AlbumId, AlbumImage, Artist, BandcampCollectionItem, BandcampCollectionResponse,
+    BandcampDownloadInfo, DiscNumber, GiftFilter, NameFilter, PurchaseList, Service, Since, Track,
+    TrackId, TrackNumber, UnknownTrackNumbers,
 };
+use crate::network;
+use crate::progress;
+use crate::retry::{self, RateLimiter, RequestPolicy};
 
 const BASE_URL: &str = "https://bandcamp.com";
 const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/121.0.0.0 Safari/537.36";
 const ITEMS_PER_PAGE: u32 = 100;
-const MAX_RETRIES: u32 = 3;
-const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
-const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(10);
-
-// --- Rate limiter ---
-
-struct RateLimiter {
-    last_request: Mutex<Instant>,
-    min_interval: Duration,
-}
-
-impl RateLimiter {
-    fn new(requests_per_second: f64) -> Self {
-        Self {
-            last_request: Mutex::new(Instant::now() - Duration::from_secs(1)),
-            min_interval: Duration::from_secs_f64(1.0 / requests_per_second),
-        }
-    }
-
-    async fn wait(&self) {
-        let wait_until = {
-            let mut last = self.last_request.lock().unwrap();
-            let now = Instant::now();
-            let earliest = *last + self.min_interval;
-            *last = earliest.max(now);
-            earliest
-        };
-        let now = Instant::now();
-        if wait_until > now {
-            tokio::time::sleep(wait_until - now).await;
-        }
-    }
-}
+/// Bytes read from the start of a download for content sniffing.
+const HEAD_LEN: usize = 512;
+/// Name an extracted album's ZIP is left under in the temp dir when it is
+/// to be kept.
+pub const KEPT_ZIP: &str = "bc_download.zip";
+/// How long to keep polling `statdownload` for a download that is still
+/// being prepared.
+const STAT_POLL_TIMEOUT: Duration = Duration::from_secs(300);
+const STAT_POLL_INITIAL: Duration = Duration::from_secs(2);
+const STAT_POLL_MAX: Duration = Duration::from_secs(30);
 
 // --- Bandcamp client ---
 
 pub struct BandcampClient {
     http: reqwest::Client,
-    #[allow(dead_code)]
+    /// The identity cookie the client started with.
     identity_cookie: String,
+    /// Holds the cookies Bandcamp sets, including a rotated identity.
+    jar: Arc<Jar>,
+    policy: RequestPolicy,
     rate_limiter: RateLimiter,
 }
 
+/// What the `statdownload` endpoint says about a download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatStatus {
+    /// Ready: at the given CDN URL, or at the original one if `None`.
+    Ready(Option<String>),
+    /// Still being prepared (large or freshly encoded items); ask again.
+    Pending,
+}
+
 /// Result of fetching all purchases: items + their redownload URLs.
 pub struct BandcampPurchases {
     pub items: Vec<BandcampCollectionItem>,
     pub redownload_urls: HashMap<String, String>,
 }
 
+impl BandcampPurchases {
+    /// The download page for `item`. A subscription item whose own key is
+    /// missing falls back to its subscription's, but only when nothing
+    /// else came through that subscription: the page would be for just
+    /// one of them.
+    pub fn redownload_url(&self, item: &BandcampCollectionItem) -> Option<&str> {
+        if let Some(url) = self.redownload_urls.get(&item.redownload_key()) {
+            return Some(url);
+        }
+        let shared = |other: &&BandcampCollectionItem| {
+            other.sale_item_type == item.sale_item_type && other.sale_item_id == item.sale_item_id
+        };
+        if !item.is_subscription() || self.items.iter().filter(shared).count() > 1 {
+            return None;
+        }
+        let key = format!("{}{}", item.sale_item_type, item.sale_item_id);
+        self.redownload_urls.get(&key).map(String::as_str)
+    }
+
+    /// Drop items the filter excludes.
+    pub fn retain_gifts(&mut self, filter: GiftFilter) {
+        self.items.retain(|item| filter.keeps(item.is_gift()));
+    }
+
+    /// Drop items the filter excludes.
+    pub fn retain_names(&mut self, filter: &NameFilter) {
+        self.items
+            .retain(|item| filter.keeps(&item.band_name, &item.item_title));
+    }
+
+    /// Drop items added to the collection before `since`.
+    pub fn retain_since(&mut self, since: &Since) {
+        self.items.retain(|item| since.keeps(item.purchased_at()));
+    }
+
+    /// Keep only the item whose album or track page is `url`, for `get`.
+    pub fn select_page(&mut self, url: &str) -> Result<()> {
+        let key = page_key(url);
+        self.items
+            .retain(|item| item.item_url.as_deref().is_some_and(|u| page_key(u) == key));
+        if self.items.is_empty() {
+            bail!("{url} is not in the Bandcamp collection");
+        }
+        Ok(())
+    }
+
+    /// Keep only the item `info`, fetched from the download page at `url`,
+    /// is for, for `get`. Something bought without being logged in isn't in
+    /// the collection; it is synced from the download page alone.
+    pub fn select_download(&mut self, info: &BandcampDownloadInfo, url: &str) {
+        self.items.retain(|item| item.item_id == info.item_id);
+        if self.items.is_empty() {
+            let item = download_page_item(info);
+            self.redownload_urls
+                .insert(item.redownload_key(), url.to_string());
+            self.items.push(item);
+        }
+    }
+}
+
+/// An album or track page URL without what doesn't tell pages apart: the
+/// scheme, a `www.`, a trailing slash, the query, and the host's case.
+pub fn page_key(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let (host, path) = url.split_once('/').unwrap_or((url, ""));
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    format!("{host}/{}", path.trim_end_matches('/'))
+}
+
+/// A collection item standing in for a purchase known only from its
+/// download page.
+pub fn download_page_item(info: &BandcampDownloadInfo) -> BandcampCollectionItem {
+    let is_track = info.download_type == "t";
+    BandcampCollectionItem {
+        band_name: info.artist.clone(),
+        item_title: info.title.clone(),
+        item_id: info.item_id,
+        item_type: if is_track { "track" } else { "album" }.to_string(),
+        sale_item_type: info.download_type.clone(),
+        sale_item_id: info.item_id,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
+    }
+}
+
 /// A single track extracted from a ZIP or downloaded directly.
 pub struct ExtractedTrack {
+    /// From the file's tags, else its name; 0 if neither had one.
     pub track_number: u8,
+    /// From the file's tags, else its name.
     pub title: String,
+    /// From the file's tags; 1 if they had none.
+    pub disc_number: u8,
+    /// The track's own artist, from the file's tags.
+    pub artist: Option<String>,
+    /// 1-based place among the download's tracks, in ZIP order.
+    pub position: usize,
     pub temp_path: PathBuf,
+    /// Hex SHA-256 of the extracted file, computed as it was written.
+    pub sha256: String,
 }
 
 // Helper for collection_summary response
 #[derive(Deserialize)]
 struct CollectionSummaryResponse {
     fan_id: u64,
+    #[serde(default)]
+    collection_summary: Option<CollectionSummaryBody>,
+}
+
+#[derive(Deserialize)]
+struct CollectionSummaryBody {
+    /// One entry per purchased album/track, keyed like "a1234567".
+    #[serde(default)]
+    tralbum_lookup: HashMap<String, serde_json::Value>,
+}
+
+/// The fan's identity plus how many items Bandcamp says they own.
+pub struct CollectionSummary {
+    pub fan_id: u64,
+    /// `None` if the response didn't include the collection lookup.
+    pub item_count: Option<usize>,
 }
 
 impl BandcampClient {
-    pub fn new(identity_cookie: String) -> Result<Self> {
+    /// A client pacing and retrying requests as `policy` says, connecting
+    /// as `network` says.
+    pub fn new(
+        identity_cookie: String,
+        policy: RequestPolicy,
+        network: &NetworkConfig,
+    ) -> Result<Self> {
+        let identity_cookie = normalize_identity_cookie(&identity_cookie)?;
+
         // Build cookie jar with identity cookie on bandcamp.com
-        let jar = reqwest::cookie::Jar::default();
+        let jar = Arc::new(Jar::default());
         let url = BASE_URL.parse::<reqwest::Url>().unwrap();
         jar.add_cookie_str(
             &format!("identity={}; Domain=bandcamp.com", identity_cookie),
             &url,
         );
 
-        let http = reqwest::Client::builder()
+        let http = network::client_builder(network)?
             .user_agent(USER_AGENT)
-            .cookie_provider(std::sync::Arc::new(jar))
+            .cookie_provider(Arc::clone(&jar))
             .build()
             .context("Failed to build HTTP client")?;
 
         Ok(Self {
             http,
             identity_cookie,
-            rate_limiter: RateLimiter::new(3.0),
+            jar,
+            rate_limiter: RateLimiter::new(policy.requests_per_second),
+            policy,
         })
     }
 
+    /// The identity cookie Bandcamp has since replaced the one the client
+    /// started with by, if it has. Bandcamp rotates it during a session;
+    /// keeping the new one makes it last longer before it has to be copied
+    /// from the browser again.
+    pub fn refreshed_identity(&self) -> Option<String> {
+        let url = BASE_URL.parse::<reqwest::Url>().unwrap();
+        let cookies = self.jar.cookies(&url)?;
+        let identity = normalize_identity_cookie(cookies.to_str().ok()?).ok()?;
+        (identity != self.identity_cookie).then_some(identity)
+    }
+
     /// Verify authentication and return the fan_id.
     pub async fn verify_auth(&self) -> Result<u64> {
+        Ok(self.collection_summary().await?.fan_id)
+    }
+
+    /// Fetch the collection summary (fan_id and reported item count).
+    /// Also serves as the authentication check.
+    pub async fn collection_summary(&self) -> Result<CollectionSummary> {
         self.rate_limiter.wait().await;
         let resp = self
             .http
@@ -113,33 +260,37 @@ impl BandcampClient {
 
         let status = resp.status();
         if status == 401 || status == 403 {
-            bail!(
-                "Bandcamp authentication failed: identity cookie is invalid or expired. \
-                   Update BANDCAMP_IDENTITY or [bandcamp] identity_cookie in config."
-            );
+            return Err(AuthError(Service::Bandcamp).into());
         }
         if !status.is_success() {
             bail!("Bandcamp collection_summary returned HTTP {}", status);
         }
 
-        let summary: CollectionSummaryResponse = resp
-            .json()
+        let body = resp
+            .text()
             .await
-            .context("Failed to parse collection_summary response")?;
-        Ok(summary.fan_id)
+            .context("Failed to read collection_summary response")?;
+        parse_collection_summary(&body)
     }
 
-    /// Fetch all purchases (collection items + hidden items) with pagination.
-    pub async fn get_purchases(&self, fan_id: u64) -> Result<BandcampPurchases> {
+    /// Fetch all purchases (collection items + hidden items) with pagination,
+    /// reporting pages and item counts on `pb`.
+    pub async fn get_purchases(&self, fan_id: u64, pb: &ProgressBar) -> Result<BandcampPurchases> {
         let mut all_items = Vec::new();
         let mut all_urls: HashMap<String, String> = HashMap::new();
 
         // Fetch visible collection items
-        self.fetch_paginated_items(fan_id, "collection_items", &mut all_items, &mut all_urls)
-            .await?;
+        self.fetch_paginated_items(
+            fan_id,
+            "collection_items",
+            &mut all_items,
+            &mut all_urls,
+            pb,
+        )
+        .await?;
 
         // Fetch hidden items
-        self.fetch_paginated_items(fan_id, "hidden_items", &mut all_items, &mut all_urls)
+        self.fetch_paginated_items(fan_id, "hidden_items", &mut all_items, &mut all_urls, pb)
             .await?;
 
         Ok(BandcampPurchases {
@@ -148,17 +299,37 @@ impl BandcampClient {
         })
     }
 
+    /// Fetch the fan's wishlist: items saved for later but not bought.
+    pub async fn get_wishlist(
+        &self,
+        fan_id: u64,
+        pb: &ProgressBar,
+    ) -> Result<Vec<BandcampCollectionItem>> {
+        let mut items = Vec::new();
+        self.fetch_paginated_items(
+            fan_id,
+            "wishlist_items",
+            &mut items,
+            &mut HashMap::new(),
+            pb,
+        )
+        .await?;
+        Ok(items)
+    }
+
     async fn fetch_paginated_items(
         &self,
         fan_id: u64,
         endpoint: &str,
         items: &mut Vec<BandcampCollectionItem>,
         urls: &mut HashMap<String, String>,
+        pb: &ProgressBar,
     ) -> Result<()> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs();
         let mut older_than_token = format!("{now}:0:a::");
+        let mut page = 0;
 
         loop {
             self.rate_limiter.wait().await;
@@ -187,6 +358,12 @@ impl BandcampClient {
 
             urls.extend(resp.redownload_urls);
             items.extend(resp.items);
+            page += 1;
+            pb.set_message(format!(
+                "{} page {page}: {} items",
+                endpoint.replace('_', " "),
+                items.len()
+            ));
 
             if !resp.more_available {
                 break;
@@ -208,6 +385,30 @@ impl BandcampClient {
         parse_download_page(&html)
     }
 
+    /// Numbered track titles from an album page; see `parse_tracklist`.
+    pub async fn get_tracklist(&self, page_url: &str) -> Result<Vec<(u8, String)>> {
+        self.rate_limiter.wait().await;
+
+        let html = self
+            .send_text_with_retry(self.http.get(page_url))
+            .await
+            .context("Failed to fetch album page")?;
+
+        Ok(parse_tracklist(&html))
+    }
+
+    /// Tags on an album or track page, genre first.
+    pub async fn get_tags(&self, page_url: &str) -> Result<Vec<String>> {
+        self.rate_limiter.wait().await;
+
+        let html = self
+            .send_text_with_retry(self.http.get(page_url))
+            .await
+            .context("Failed to fetch album page")?;
+
+        Ok(parse_tags(&html))
+    }
+
     /// Resolve the actual CDN download URL via Bandcamp's
     /// stat endpoint.
     ///
@@ -216,6 +417,10 @@ impl BandcampClient {
     /// `/download/` to get either:
     ///   - `result: 'ok'` → original URL is ready
     ///   - a JSON blob with `download_url` → the real CDN URL
+    ///
+    /// Until the file has been prepared, the stat endpoint
+    /// says so and the original URL fails; it is polled, with
+    /// growing delays, for up to `STAT_POLL_TIMEOUT`.
     async fn resolve_download_url(
         &self,
         download_url: &str,
@@ -228,45 +433,51 @@ impl BandcampClient {
             return Ok(download_url.to_string());
         }
 
-        self.rate_limiter.wait().await;
-
-        let body = self
-            .send_text_with_retry(self.http.get(&stat_url))
-            .await
-            .with_context(|| {
-                format!("stat request failed: {stat_url}")
-            })?;
-
-        // Response is JavaScript: `var _statDL_result = {...};`
-        // If it says result: 'ok', the original URL works.
-        if body.contains("result: 'ok'")
-            || body.contains("\"result\":\"ok\"")
-            || body.contains("\"result\": \"ok\"")
-        {
-            return Ok(download_url.to_string());
-        }
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            self.rate_limiter.wait().await;
 
-        // Otherwise extract "download_url":"<actual url>"
-        // from the JavaScript/JSON body.
-        let re = Regex::new(r#""download_url"\s*:\s*"([^"]+)""#)
-            .unwrap();
-        if let Some(caps) = re.captures(&body) {
-            return Ok(caps[1].to_string());
+            let body = self
+                .send_text_with_retry(self.http.get(&stat_url))
+                .await
+                .with_context(|| {
+                    format!("stat request failed: {stat_url}")
+                })?;
+
+            match parse_stat_response(&body)? {
+                StatStatus::Ready(url) => {
+                    return Ok(url.unwrap_or_else(|| download_url.to_string()));
+                }
+                StatStatus::Pending => {
+                    let delay = stat_poll_delay(attempt);
+                    if started.elapsed() + delay > STAT_POLL_TIMEOUT {
+                        bail!(
+                            "download still being prepared after {}s",
+                            STAT_POLL_TIMEOUT.as_secs()
+                        );
+                    }
+                    debug!("download not ready yet, asking again in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
         }
-
-        bail!(
-            "Could not extract download URL from stat \
-             response:\n{}",
-            &body[..body.len().min(500)]
-        );
     }
 
     /// Download an album ZIP (or single track file) and
     /// extract .m4a files.
+    ///
+    /// The body is streamed to a file in `temp_dir` so a
+    /// large album never sits in memory as one buffer. With `keep_zip`,
+    /// an album's ZIP is left there as [`KEPT_ZIP`] once extracted.
+    /// Progress is reported to `transfer`.
     pub async fn download_and_extract(
         &self,
         download_url: &str,
         temp_dir: &Path,
+        keep_zip: bool,
+        transfer: &mut progress::Transfer,
     ) -> Result<Vec<ExtractedTrack>> {
         // Resolve the real CDN URL via the stat endpoint.
         let resolved = self
@@ -296,17 +507,30 @@ impl BandcampClient {
             .unwrap_or("")
             .to_string();
 
-        let bytes = resp
-            .bytes()
+        if let Some(len) = resp.content_length() {
+            transfer.set_size(len);
+        }
+
+        let part_path = temp_dir.join("bc_download.part");
+        let sha256 = stream_to_file(resp, &part_path, transfer)
             .await
             .context("Failed to read download body")?;
 
+        let head = read_head(&part_path)?;
+
         if content_type.contains("zip")
-            || is_zip_magic(&bytes)
+            || is_zip_magic(&head)
         {
-            extract_zip(&bytes, temp_dir)
+            let tracks = extract_zip(&part_path, temp_dir);
+            if keep_zip && tracks.is_ok() {
+                std::fs::rename(&part_path, temp_dir.join(KEPT_ZIP))
+                    .context("Failed to keep the downloaded ZIP")?;
+            } else {
+                let _ = std::fs::remove_file(&part_path);
+            }
+            tracks
         } else {
-            extract_single_track(&bytes, temp_dir, &resolved)
+            single_track_from_file(&part_path, &head, temp_dir, &resolved, sha256)
         }
     }
 
@@ -315,89 +539,160 @@ impl BandcampClient {
         &self,
         request: reqwest::RequestBuilder,
     ) -> Result<T> {
-        let mut backoff = INITIAL_BACKOFF;
-
-        for attempt in 0..=MAX_RETRIES {
-            self.rate_limiter.wait().await;
-
-            let req = request
-                .try_clone()
-                .context("Request cannot be cloned for retry")?;
-
-            let resp = req.send().await?;
-            let status = resp.status();
-
-            if status.is_success() {
-                return resp.json().await.context("Failed to parse response JSON");
-            }
-
-            if status.as_u16() == 429 && attempt < MAX_RETRIES {
-                eprintln!(
-                    "HTTP 429 rate limited, backing off {:?}...",
-                    RATE_LIMIT_BACKOFF
-                );
-                tokio::time::sleep(RATE_LIMIT_BACKOFF).await;
-                continue;
-            }
-
-            let retryable = matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504);
-            if !retryable || attempt == MAX_RETRIES {
-                let body = resp.text().await.unwrap_or_default();
-                bail!("HTTP {} — {}", status, body);
-            }
-
-            eprintln!("HTTP {}, retrying in {:?}...", status, backoff);
-            tokio::time::sleep(backoff).await;
-            backoff *= 2;
-        }
-
-        unreachable!()
+        retry::send(&request, &self.policy, &self.rate_limiter)
+            .await?
+            .json()
+            .await
+            .context("Failed to parse response JSON")
     }
 
     /// Send a request expecting text response, with retry.
     async fn send_text_with_retry(&self, request: reqwest::RequestBuilder) -> Result<String> {
-        let mut backoff = INITIAL_BACKOFF;
-
-        for attempt in 0..=MAX_RETRIES {
-            self.rate_limiter.wait().await;
+        retry::send(&request, &self.policy, &self.rate_limiter)
+            .await?
+            .text()
+            .await
+            .context("Failed to read response text")
+    }
+}
 
-            let req = request
-                .try_clone()
-                .context("Request cannot be cloned for retry")?;
+// --- Identity cookie ---
 
-            let resp = req.send().await?;
-            let status = resp.status();
+/// Clean up a pasted identity cookie and check it has the expected shape.
+///
+/// Accepts the bare value, the value in quotes, `identity=<value>`, or a
+/// whole `Cookie:` header, either URL-encoded (as browsers show it) or
+/// decoded. Returns the value percent-encoded the way Bandcamp sets it.
+pub fn normalize_identity_cookie(raw: &str) -> Result<String> {
+    let mut value = raw.trim();
+    if let Some(prefix) = value.get(..7)
+        && prefix.eq_ignore_ascii_case("cookie:")
+    {
+        value = value[7..].trim();
+    }
+    if value.contains(';') || value.starts_with("identity=") {
+        value = value
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| name.trim() == "identity")
+            .map(|(_, v)| v.trim())
+            .context(
+                "No identity cookie found in the pasted cookies; \
+                 copy just the value of the cookie named `identity`",
+            )?;
+    }
+    let value = value.trim_matches(|c| c == '"' || c == '\'');
+
+    // Decode until stable, so double-encoded pastes come out the same too.
+    let mut decoded = value.to_string();
+    for _ in 0..3 {
+        let next = percent_decode(&decoded);
+        if next == decoded {
+            break;
+        }
+        decoded = next;
+    }
 
-            if status.is_success() {
-                return resp.text().await.context("Failed to read response text");
-            }
+    // Version, signature, and a JSON payload, separated by tabs.
+    let fields: Vec<&str> = decoded.split('\t').collect();
+    let well_formed = fields.len() == 3
+        && !fields[0].is_empty()
+        && fields[0].bytes().all(|b| b.is_ascii_digit())
+        && !fields[1].is_empty()
+        && fields[2].starts_with('{')
+        && fields[2].ends_with('}');
+    if !well_formed {
+        bail!(
+            "That doesn't look like a Bandcamp identity cookie. Copy the value of \
+             the `identity` cookie for bandcamp.com: a URL-encoded string starting \
+             with a number, like `7%09AbCd...%3D%09%7B%22id%22%3A...%7D`."
+        );
+    }
+    Ok(percent_encode(&decoded))
+}
 
-            if status.as_u16() == 429 && attempt < MAX_RETRIES {
-                eprintln!(
-                    "HTTP 429 rate limited, backing off {:?}...",
-                    RATE_LIMIT_BACKOFF
-                );
-                tokio::time::sleep(RATE_LIMIT_BACKOFF).await;
-                continue;
+/// Decode `%XX` escapes; malformed escapes are kept as-is.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
             }
-
-            let retryable = matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504);
-            if !retryable || attempt == MAX_RETRIES {
-                let body = resp.text().await.unwrap_or_default();
-                bail!("HTTP {} — {}", status, body);
+            (b, _) => {
+                out.push(b);
+                i += 1;
             }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
 
-            eprintln!("HTTP {}, retrying in {:?}...", status, backoff);
-            tokio::time::sleep(backoff).await;
-            backoff *= 2;
+/// Encode like JavaScript's `encodeURIComponent`, which is how Bandcamp
+/// writes the cookie.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() * 3);
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.!~*'()".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
         }
+    }
+    out
+}
+
+// --- Collection summary ---
+
+pub fn parse_collection_summary(json: &str) -> Result<CollectionSummary> {
+    let resp: CollectionSummaryResponse =
+        serde_json::from_str(json).context("Failed to parse collection_summary response")?;
+    Ok(CollectionSummary {
+        fan_id: resp.fan_id,
+        item_count: resp.collection_summary.map(|c| c.tralbum_lookup.len()),
+    })
+}
 
-        unreachable!()
+/// Compare the crawled items against the count Bandcamp reports.
+/// Returns a warning when they diverge, which usually means pagination
+/// stopped early or the API changed shape.
+pub fn check_collection_count(
+    summary: &CollectionSummary,
+    items: &[BandcampCollectionItem],
+) -> Option<String> {
+    let expected = summary.item_count?;
+    let crawled = items
+        .iter()
+        .map(|i| (i.item_type.as_str(), i.item_id))
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    if crawled == expected {
+        return None;
     }
+    let detail = if crawled < expected {
+        "the collection may be truncated (pagination stopped early?)"
+    } else {
+        "the summary may be stale or the API may have changed"
+    };
+    Some(format!(
+        "Bandcamp reports {expected} purchased items but {crawled} were fetched; {detail}"
+    ))
 }
 
 // --- HTML parsing ---
 
+/// Cover image URL for an art id: a 1200×1200 JPEG.
+pub fn art_url(art_id: u64) -> String {
+    format!("https://f4.bcbits.com/img/a{art_id:010}_10.jpg")
+}
+
 /// Parse the download page HTML to extract BandcampDownloadInfo.
 /// Looks for `<div id="pagedata" data-blob="...">` and decodes the HTML entities.
 pub fn parse_download_page(html: &str) -> Result<BandcampDownloadInfo> {
@@ -424,6 +719,139 @@ pub fn parse_download_page(html: &str) -> Result<BandcampDownloadInfo> {
         .context("No digital_items found in download page")
 }
 
+/// Read a `statdownload` response. It is JavaScript wrapping JSON
+/// (`var _statDL_result = {...};`) whose `result` is `ok` once the
+/// original URL is ready, and `err` on failure; otherwise a `download_url`
+/// is the CDN URL to fetch instead. Any other `result`, or none but a `retry_url`, means the
+/// file is still being prepared.
+pub fn parse_stat_response(body: &str) -> Result<StatStatus> {
+    let result_re = Regex::new(r#""?result"?\s*:\s*['"]([^'"]*)['"]"#).expect("valid regex");
+    let result = result_re.captures(body).map(|caps| caps[1].to_lowercase());
+    if let Some("ok" | "ready") = result.as_deref() {
+        return Ok(StatStatus::Ready(None));
+    }
+    let url_re = Regex::new(r#""download_url"\s*:\s*"([^"]+)""#).expect("valid regex");
+    if let Some(caps) = url_re.captures(body) {
+        return Ok(StatStatus::Ready(Some(caps[1].to_string())));
+    }
+
+    match result.as_deref() {
+        Some("err" | "error") => {
+            let error_re =
+                Regex::new(r#""?errortype"?\s*:\s*['"]([^'"]+)['"]"#).expect("valid regex");
+            match error_re.captures(body) {
+                Some(caps) => bail!("Bandcamp could not prepare the download: {}", &caps[1]),
+                None => bail!("Bandcamp could not prepare the download"),
+            }
+        }
+        Some(_) => Ok(StatStatus::Pending),
+        None if body.contains("retry_url") => Ok(StatStatus::Pending),
+        None => bail!(
+            "Could not extract download URL from stat response:\n{}",
+            &body[..body.floor_char_boundary(500)]
+        ),
+    }
+}
+
+/// How long to wait before polling `statdownload` again after poll
+/// `attempt` (from 0) found the download not ready: doubling from
+/// `STAT_POLL_INITIAL` up to `STAT_POLL_MAX`.
+pub fn stat_poll_delay(attempt: u32) -> Duration {
+    STAT_POLL_INITIAL
+        .saturating_mul(1 << attempt.min(8))
+        .min(STAT_POLL_MAX)
+}
+
+/// The tags listed on an album or track page (`<a class="tag" ...>`), in
+/// page order. Bandcamp lists the genre the artist picked first.
+pub fn parse_tags(html: &str) -> Vec<String> {
+    let re = Regex::new(r#"<a\s+class="tag"[^>]*>([^<]+)</a>"#).expect("valid regex");
+    let mut tags: Vec<String> = Vec::new();
+    for caps in re.captures_iter(html) {
+        let tag = decode_html_entities(caps[1].trim());
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// `(track number, title)` for each numbered track in an album page's
+/// `data-tralbum` blob, in page order.
+pub fn parse_tracklist(html: &str) -> Vec<(u8, String)> {
+    let re = Regex::new(r#"data-tralbum="([^"]*)""#).expect("valid regex");
+    let Some(caps) = re.captures(html) else {
+        return Vec::new();
+    };
+    let Ok(tralbum) = serde_json::from_str::<serde_json::Value>(&decode_html_entities(&caps[1]))
+    else {
+        return Vec::new();
+    };
+    let Some(tracks) = tralbum["trackinfo"].as_array() else {
+        return Vec::new();
+    };
+    tracks
+        .iter()
+        .filter_map(|t| {
+            let number = u8::try_from(t["track_num"].as_u64()?).ok()?;
+            Some((number, t["title"].as_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// Give tracks whose file name had no number one, according to `policy`,
+/// and re-sort them. `tracklist` is the album page's `(number, title)` list,
+/// used by `Tracklist`; tracks it doesn't name fall back to ZIP order. Under
+/// `Omit` nothing changes.
+pub fn number_unknown_tracks(
+    tracks: &mut [ExtractedTrack],
+    policy: UnknownTrackNumbers,
+    tracklist: &[(u8, String)],
+) {
+    if policy == UnknownTrackNumbers::Omit || tracks.iter().all(|t| t.track_number != 0) {
+        return;
+    }
+    let mut taken: BTreeSet<u8> = tracks
+        .iter()
+        .map(|t| t.track_number)
+        .filter(|&n| n != 0)
+        .collect();
+
+    if policy == UnknownTrackNumbers::Tracklist {
+        for track in tracks.iter_mut().filter(|t| t.track_number == 0) {
+            let key = title_key(&track.title);
+            if let Some(&(number, _)) = tracklist
+                .iter()
+                .find(|(n, title)| !taken.contains(n) && title_key(title) == key)
+            {
+                track.track_number = number;
+                taken.insert(number);
+            }
+        }
+    }
+
+    for track in tracks.iter_mut().filter(|t| t.track_number == 0) {
+        let number = u8::try_from(track.position)
+            .ok()
+            .filter(|n| !taken.contains(n))
+            .or_else(|| (1..=u8::MAX).find(|n| !taken.contains(n)))
+            .unwrap_or(u8::MAX);
+        track.track_number = number;
+        taken.insert(number);
+    }
+    tracks.sort_by_key(|t| (t.disc_number, t.track_number));
+}
+
+/// A title reduced to lowercase letters and digits, so a file name Bandcamp
+/// had to sanitize still matches the page.
+fn title_key(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
 /// Decode common HTML entities in a data-blob attribute value.
 fn decode_html_entities(s: &str) -> String {
     s.replace("&amp;", "&")
@@ -459,7 +887,8 @@ pub fn is_zip_magic(bytes: &[u8]) -> bool {
 
 /// Detect HTML content by looking for `<!DOCTYPE` or `<html`
 /// after stripping leading whitespace.
-fn is_html(bytes: &[u8]) -> bool {
+/// Whether a response body is an HTML page rather than a file.
+pub fn is_html(bytes: &[u8]) -> bool {
     let trimmed: &[u8] = bytes
         .iter()
         .position(|&b| {
@@ -475,9 +904,14 @@ fn is_html(bytes: &[u8]) -> bool {
         || prefix.starts_with(b"<html")
 }
 
-/// Extract .m4a files from a ZIP archive. Returns extracted tracks with metadata.
-fn extract_zip(zip_bytes: &[u8], temp_dir: &Path) -> Result<Vec<ExtractedTrack>> {
-    let reader = Cursor::new(zip_bytes);
+/// Extract .m4a files from a ZIP archive on disk. Returns extracted tracks with metadata.
+///
+/// Entries are copied out one at a time straight from the file, so the archive
+/// is never held in memory.
+pub fn extract_zip(zip_path: &Path, temp_dir: &Path) -> Result<Vec<ExtractedTrack>> {
+    let file = std::fs::File::open(zip_path)
+        .with_context(|| format!("Failed to open {}", zip_path.display()))?;
+    let reader = std::io::BufReader::new(file);
     let mut archive = zip::ZipArchive::new(reader).context("Failed to open ZIP archive")?;
 
     let mut tracks = Vec::new();
@@ -498,35 +932,84 @@ fn extract_zip(zip_bytes: &[u8], temp_dir: &Path) -> Result<Vec<ExtractedTrack>>
             .unwrap_or(&name);
 
         let (track_number, title) = parse_zip_track_filename(filename);
+        let position = tracks.len() + 1;
 
         let temp_path = temp_dir.join(format!("bc_extract_{i}.m4a"));
-        let mut buf = Vec::new();
-        entry
-            .read_to_end(&mut buf)
-            .with_context(|| format!("Failed to read ZIP entry: {name}"))?;
-        std::fs::write(&temp_path, &buf)
+        let out = std::fs::File::create(&temp_path)
             .with_context(|| format!("Failed to write temp file: {}", temp_path.display()))?;
+        let mut out = HashingWriter::new(out);
+        std::io::copy(&mut entry, &mut out)
+            .with_context(|| format!("Failed to read ZIP entry: {name}"))?;
+        let (_, sha256) = out.finish();
 
-        tracks.push(ExtractedTrack {
+        let mut track = ExtractedTrack {
             track_number,
             title,
+            disc_number: 1,
+            artist: None,
+            position,
             temp_path,
-        });
+            sha256,
+        };
+        apply_tags(&mut track);
+        tracks.push(track);
     }
 
-    // Sort by track number for consistent ordering
-    tracks.sort_by_key(|t| t.track_number);
+    // Sort by disc and track number for consistent ordering
+    tracks.sort_by_key(|t| (t.disc_number, t.track_number));
 
     Ok(tracks)
 }
 
+/// Name `track` by its file's embedded tags where they have a number,
+/// title, disc or artist, rather than by what its ZIP entry was called,
+/// which Bandcamp shortens and strips of characters. A file whose tags
+/// can't be read keeps what its name said.
+fn apply_tags(track: &mut ExtractedTrack) {
+    let tags = match deep_scan::read_tags(&track.temp_path) {
+        Ok(tags) => tags,
+        Err(e) => {
+            debug!("no tags for {}: {e:#}", track.title);
+            return;
+        }
+    };
+    if let Some(number) = tags.track_number.and_then(|n| u8::try_from(n).ok()) {
+        track.track_number = number;
+    }
+    if let Some(disc) = tags.disc_number.and_then(|n| u8::try_from(n).ok()) {
+        track.disc_number = disc.max(1);
+    }
+    if let Some(title) = tags.title {
+        track.title = title;
+    }
+    track.artist = tags.artist;
+}
+
 /// Extract a single track from a bare audio file response.
 pub fn extract_single_track(
     bytes: &[u8],
     temp_dir: &Path,
     download_url: &str,
 ) -> Result<Vec<ExtractedTrack>> {
-    if is_html(bytes) {
+    let part_path = temp_dir.join("bc_download.part");
+    std::fs::write(&part_path, bytes)
+        .with_context(|| format!("Failed to write temp file: {}", part_path.display()))?;
+    let head = &bytes[..bytes.len().min(HEAD_LEN)];
+    single_track_from_file(&part_path, head, temp_dir, download_url, sha256_hex(bytes))
+}
+
+/// Turn an already-downloaded file into a single extracted
+/// track. `head` is the first few bytes of the file, used
+/// to reject HTML error pages; `sha256` is the file's checksum.
+fn single_track_from_file(
+    part_path: &Path,
+    head: &[u8],
+    temp_dir: &Path,
+    download_url: &str,
+    sha256: String,
+) -> Result<Vec<ExtractedTrack>> {
+    if is_html(head) {
+        let _ = std::fs::remove_file(part_path);
         bail!(
             "Download returned HTML instead of audio \
              (likely an expired or unauthenticated URL)"
@@ -534,7 +1017,7 @@ pub fn extract_single_track(
     }
 
     let temp_path = temp_dir.join("bc_extract_single.m4a");
-    std::fs::write(&temp_path, bytes)
+    std::fs::rename(part_path, &temp_path)
         .with_context(|| format!("Failed to write temp file: {}", temp_path.display()))?;
 
     // Try to extract title from URL or content-disposition
@@ -543,10 +1026,26 @@ pub fn extract_single_track(
     Ok(vec![ExtractedTrack {
         track_number: 1,
         title,
+        disc_number: 1,
+        artist: None,
+        position: 1,
         temp_path,
+        sha256,
     }])
 }
 
+/// Read up to `HEAD_LEN` bytes from the start of a file for
+/// content sniffing.
+fn read_head(path: &Path) -> Result<Vec<u8>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    file.take(HEAD_LEN as u64)
+        .read_to_end(&mut head)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(head)
+}
+
 fn extract_title_from_url(url: &str) -> String {
     // Best effort: grab the last path segment before query params
     url.split('?')
@@ -603,12 +1102,64 @@ pub fn parse_zip_track_filename(filename: &str) -> (u8, String) {
     (track_number, title)
 }
 
+/// The tracks a download's extracted files become, in the same order. A
+/// single file is the item itself, titled after it; files of a multi-track
+/// download keep the numbers and titles from their names.
+pub fn extracted_tracks(
+    item: &BandcampCollectionItem,
+    album: &Album,
+    extracted: &[ExtractedTrack],
+) -> Vec<Track> {
+    let track = |id, title: &str, number, disc| Track {
+        id: TrackId(id),
+        title: title.to_string(),
+        version: None,
+        track_number: TrackNumber(number),
+        media_number: DiscNumber(disc),
+        duration: 0,
+        performer: album.artist.clone(),
+        isrc: None,
+        purchase: album.purchase.clone(),
+    };
+    if extracted.len() == 1 {
+        return vec![track(item.item_id, &item.item_title, 1, 1)];
+    }
+    extracted
+        .iter()
+        .map(|ext| {
+            // Keyed by disc and number, so the ids don't depend on ZIP
+            // order; tracks left unnumbered (`omit`) by ZIP position,
+            // past the discs' numbers.
+            let key = match (ext.disc_number, ext.track_number) {
+                (_, 0) => 900 + ext.position as u64,
+                (1, n) => u64::from(n),
+                (disc, n) => u64::from(disc) * 100 + u64::from(n),
+            };
+            let id = item.item_id.wrapping_mul(1000).wrapping_add(key);
+            let mut track = track(id, &ext.title, ext.track_number, ext.disc_number);
+            // Only an artist the album doesn't credit names the track as
+            // a compilation's.
+            if let Some(ref artist) = ext.artist {
+                let performer = Artist {
+                    id: 0,
+                    name: artist.clone(),
+                };
+                if !album.credits(&performer) {
+                    track.performer = performer;
+                }
+            }
+            track
+        })
+        .collect()
+}
+
 // --- Conversion to PurchaseList ---
 
 /// Convert Bandcamp collection items to the shared PurchaseList format.
 ///
 /// Groups items by sale_item_type: albums get full Album structs (tracks filled
 /// later during download), individual tracks get standalone Album wrappers.
+/// Subscription items are grouped by what they are.
 pub fn to_purchase_list(purchases: &BandcampPurchases) -> PurchaseList {
     let mut albums = Vec::new();
     let mut tracks = Vec::new();
@@ -619,8 +1170,8 @@ pub fn to_purchase_list(purchases: &BandcampPurchases) -> PurchaseList {
             name: item.band_name.clone(),
         };
 
-        match item.sale_item_type.as_str() {
-            "a" => {
+        match item.is_track() {
+            Some(false) => {
                 // Album purchase — tracks are populated during download (from ZIP contents)
                 albums.push(Album {
                     id: AlbumId(format!("bc-{}", item.item_id)),
@@ -629,26 +1180,35 @@ pub fn to_purchase_list(purchases: &BandcampPurchases) -> PurchaseList {
                     artist,
                     media_count: 1,
                     tracks_count: 0, // Unknown until we download
+                    upc: None,
+                    release_date: None,
+                    artists: Vec::new(),
+                    image: item.item_art_id.map(|id| AlbumImage {
+                        large: Some(art_url(id)),
+                    }),
                     tracks: None,    // Populated during download
+                    purchase: item.purchase_info(),
                 });
             }
-            "t" => {
+            Some(true) => {
                 // Individual track purchase
                 let track = Track {
                     id: TrackId(item.item_id),
                     title: item.item_title.clone(),
+                    version: None,
                     track_number: TrackNumber(1),
                     media_number: DiscNumber(1),
                     duration: 0,
                     performer: artist,
                     isrc: None,
+                    purchase: item.purchase_info(),
                 };
                 tracks.push(track);
             }
-            other => {
-                eprintln!(
-                    "Warning: unknown Bandcamp sale_item_type '{}' for '{}'",
-                    other, item.item_title
+            None => {
+                warn!(
+                    "unknown Bandcamp sale_item_type '{}' for '{}'",
+                    item.sale_item_type, item.item_title
                 );
             }
         }
@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::{info, warn};
+
+use crate::manifest::{Manifest, album_dir};
+
+/// How one album's `beet import` went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// Album directory, relative to the target directory.
+    pub dir: PathBuf,
+    pub error: Option<String>,
+}
+
+/// Album directories (relative to the target directory) of `new_tracks`
+/// that `before`, the manifest as it was when the sync started, had
+/// nothing recorded in: the albums this sync created.
+pub fn new_album_dirs(
+    before: &Manifest,
+    new_tracks: impl IntoIterator<Item = PathBuf>,
+) -> BTreeSet<PathBuf> {
+    let known: BTreeSet<PathBuf> = before
+        .entries()
+        .filter_map(|e| album_dir(&e.path))
+        .collect();
+    new_tracks
+        .into_iter()
+        .filter_map(|path| album_dir(&path))
+        .filter(|dir| !known.contains(dir))
+        .collect()
+}
+
+/// Arguments to `beet` importing `dir` as it is: `-A` keeps qoget's tags
+/// rather than autotagging, `-q` keeps beets from stopping to ask.
+pub fn import_args(dir: &Path) -> Vec<OsString> {
+    vec![
+        "import".into(),
+        "-A".into(),
+        "-q".into(),
+        dir.as_os_str().to_owned(),
+    ]
+}
+
+/// Run `beet import` on one album directory. Fails if beets can't be run
+/// or reports an error, with the last thing it printed.
+pub fn import_album(dir: &Path) -> Result<()> {
+    let output = Command::new("beet")
+        .args(import_args(dir))
+        .stdin(Stdio::null())
+        .output()
+        .context("running beet")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().rfind(|l| !l.trim().is_empty()) {
+            Some(line) => bail!("beet import failed ({}): {}", output.status, line.trim()),
+            None => bail!("beet import failed ({})", output.status),
+        }
+    }
+    Ok(())
+}
+
+/// Import each of `dirs` (relative to `target_dir`) into beets, off the
+/// async runtime, one album at a time so a failure only costs its album.
+pub async fn import_albums(target_dir: &Path, dirs: BTreeSet<PathBuf>) -> Vec<Import> {
+    let target_dir = target_dir.to_path_buf();
+    let imports = tokio::task::spawn_blocking(move || {
+        dirs.into_iter()
+            .map(|dir| {
+                let error = import_album(&target_dir.join(&dir))
+                    .err()
+                    .map(|e| format!("{e:#}"));
+                Import { dir, error }
+            })
+            .collect()
+    })
+    .await;
+    let imports: Vec<Import> = match imports {
+        Ok(imports) => imports,
+        Err(e) => {
+            warn!("beets import stopped: {e}");
+            return Vec::new();
+        }
+    };
+    for import in &imports {
+        match &import.error {
+            None => info!("Imported {} into beets", import.dir.display()),
+            Some(e) => warn!("beets import of {} failed: {e}", import.dir.display()),
+        }
+    }
+    imports
+}
+
+/// "Beets: 3 albums imported, 1 failed", for a service's summary.
+pub fn summary(imports: &[Import]) -> String {
+    let failed = imports.iter().filter(|i| i.error.is_some()).count();
+    format!(
+        "Beets: {} albums imported, {failed} failed",
+        imports.len() - failed
+    )
+}
@@ -1,14 +1,16 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::process::{Command, Stdio};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 
 use crate::task::{Stage, Task, TaskType};
 
 const PROMPT_DIR: &str = "workflow/prompts";
+const TOOLS_FILE: &str = "workflow/tools.toml";
 const LOG_DIR: &str = "var/agent-logs";
 
 /// URL-like patterns that indicate real API endpoints.
@@ -30,6 +32,7 @@ pub struct AgentResult {
     pub exit_code: i32,
     pub log_file: String,
     pub model: &'static str,
+    pub duration: Duration,
 }
 
 impl Stage {
@@ -66,6 +69,38 @@ impl Stage {
     }
 }
 
+/// Per-stage tool allowlists from workflow/tools.toml.
+type StageTools = BTreeMap<Stage, Vec<String>>;
+
+fn load_stage_tools() -> Result<StageTools> {
+    let contents = fs::read_to_string(TOOLS_FILE)
+        .with_context(|| format!("reading {TOOLS_FILE}"))?;
+    toml::from_str(&contents)
+        .with_context(|| format!("parsing {TOOLS_FILE}"))
+}
+
+/// The tools the agent may use for `stage`: the
+/// task's own list if it has one, else the stage's.
+fn allowed_tools(
+    configured: &StageTools,
+    task: &Task,
+    stage: Stage,
+) -> Result<String> {
+    let tools = task
+        .allowed_tools
+        .get(&stage)
+        .or_else(|| configured.get(&stage));
+    match tools {
+        Some(tools) if !tools.is_empty() => {
+            Ok(tools.join(","))
+        }
+        _ => bail!(
+            "no allowed tools for stage {stage} \
+             (see {TOOLS_FILE})"
+        ),
+    }
+}
+
 fn compose_prompt(
     task: &Task,
     stage: Stage,
@@ -99,6 +134,8 @@ fn compose_prompt(
 
 pub fn run(task: &Task, stage: Stage) -> Result<AgentResult> {
     let prompt = compose_prompt(task, stage)?;
+    let allowed_tools =
+        allowed_tools(&load_stage_tools()?, task, stage)?;
     let model = stage.model();
     let log_file =
         format!("{LOG_DIR}/{}-{stage}.log", task.id);
@@ -108,12 +145,6 @@ pub fn run(task: &Task, stage: Stage) -> Result<AgentResult> {
     fs::create_dir_all(LOG_DIR)?;
     fs::write(&prompt_file, &prompt)?;
 
-    let allowed_tools = [
-        "Read", "Grep", "Glob", "Write", "Edit",
-        "Bash(cargo:*)", "Bash(jj:*)", "Bash(ls:*)",
-    ]
-    .join(",");
-
     // Allow network for Reproduce/Test stages when the
     // task opts in. All other stages stay air-gapped.
     let network = task.allow_network
@@ -163,6 +194,7 @@ pub fn run(task: &Task, stage: Stage) -> Result<AgentResult> {
         exit_code: status.code().unwrap_or(1),
         log_file,
         model,
+        duration: start.elapsed(),
     })
 }
 
@@ -211,3 +243,78 @@ pub fn safety_check() -> Result<Vec<String>> {
 
     Ok(violations)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(overrides: StageTools) -> Task {
+        Task {
+            id: "001".into(),
+            priority: 1,
+            task_type: TaskType::Feature,
+            status: crate::task::Status::Pending,
+            title: "t".into(),
+            description: String::new(),
+            blockers: vec![],
+            stages: BTreeMap::new(),
+            context_files: vec![],
+            error: None,
+            allow_network: false,
+            allowed_tools: overrides,
+        }
+    }
+
+    #[test]
+    fn workflow_tools_file_parses() {
+        let contents = fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join(TOOLS_FILE),
+        )
+        .unwrap();
+        let tools: StageTools =
+            toml::from_str(&contents).unwrap();
+        let t = task(BTreeMap::new());
+        for stage in TaskType::Bug
+            .stages()
+            .iter()
+            .chain(TaskType::Feature.stages())
+        {
+            allowed_tools(&tools, &t, *stage).unwrap();
+        }
+        let verify =
+            allowed_tools(&tools, &t, Stage::Verify)
+                .unwrap();
+        assert!(!verify.contains("Write"));
+        assert!(!verify.contains("Edit"));
+    }
+
+    #[test]
+    fn task_overrides_stage_tools() {
+        let tools: StageTools = toml::from_str(
+            r#"impl = ["Read", "Edit"]"#,
+        )
+        .unwrap();
+
+        let plain = task(BTreeMap::new());
+        assert_eq!(
+            allowed_tools(&tools, &plain, Stage::Impl)
+                .unwrap(),
+            "Read,Edit"
+        );
+        assert!(
+            allowed_tools(&tools, &plain, Stage::Verify)
+                .is_err()
+        );
+
+        let custom = task(BTreeMap::from([(
+            Stage::Impl,
+            vec!["Read".into(), "Bash(python:*)".into()],
+        )]));
+        assert_eq!(
+            allowed_tools(&tools, &custom, Stage::Impl)
+                .unwrap(),
+            "Read,Bash(python:*)"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+use crate::task::{Status, Task};
+
+/// Phrases in a description that name a blocker, e.g.
+/// "depends on 003" or "Blocked by task #12".
+const REFERENCE: &str = r"(?i)\b(?:depends\s+on|blocked\s+by|requires)\s+(?:task\s+)?#?([A-Za-z0-9_-]+)";
+
+/// Ids of existing tasks that `task`'s description says
+/// it depends on.
+fn referenced_blockers(
+    task: &Task,
+    ids: &BTreeSet<&str>,
+) -> Vec<String> {
+    let re = Regex::new(REFERENCE).expect("valid regex");
+    let mut found = Vec::new();
+    for caps in re.captures_iter(&task.description) {
+        let id = &caps[1];
+        if id != task.id
+            && ids.contains(id)
+            && !found.iter().any(|f| f == id)
+        {
+            found.push(id.to_string());
+        }
+    }
+    found
+}
+
+/// A chain of blockers that leads back to its start,
+/// e.g. `["001", "002", "001"]`.
+pub fn find_cycle(tasks: &[Task]) -> Option<Vec<String>> {
+    let edges: BTreeMap<&str, &[String]> = tasks
+        .iter()
+        .map(|t| (t.id.as_str(), t.blockers.as_slice()))
+        .collect();
+
+    // Depth-first search; `path` is the current chain,
+    // `done` the tasks known not to reach a cycle.
+    fn visit<'a>(
+        id: &'a str,
+        edges: &BTreeMap<&'a str, &'a [String]>,
+        path: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(pos) = path.iter().position(|p| *p == id)
+        {
+            let mut cycle: Vec<String> = path[pos..]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+            cycle.push(id.to_string());
+            return Some(cycle);
+        }
+        if done.contains(id) {
+            return None;
+        }
+        path.push(id);
+        for next in edges.get(id).copied().unwrap_or(&[]) {
+            if let Some(c) = visit(next, edges, path, done)
+            {
+                return Some(c);
+            }
+        }
+        path.pop();
+        done.insert(id);
+        None
+    }
+
+    let mut done = BTreeSet::new();
+    for id in edges.keys() {
+        let mut path = Vec::new();
+        if let Some(c) = visit(id, &edges, &mut path, &mut done)
+        {
+            return Some(c);
+        }
+    }
+    None
+}
+
+/// Refuse a task set whose blockers can never all be
+/// satisfied, rather than deadlocking mid-run.
+pub fn validate(tasks: &[Task]) -> Result<()> {
+    if let Some(cycle) = find_cycle(tasks) {
+        bail!("blocker cycle: {}", cycle.join(" -> "));
+    }
+    Ok(())
+}
+
+/// Add blockers that unfinished tasks' descriptions
+/// refer to but that aren't recorded yet, skipping any
+/// that would create a cycle. Returns `(task, blocker)`
+/// for each one added.
+pub fn discover(
+    tasks: &mut [Task],
+) -> Vec<(String, String)> {
+    let ids: BTreeSet<String> =
+        tasks.iter().map(|t| t.id.clone()).collect();
+    let ids: BTreeSet<&str> =
+        ids.iter().map(String::as_str).collect();
+    let mut added = Vec::new();
+    for i in 0..tasks.len() {
+        if tasks[i].status == Status::Done {
+            continue;
+        }
+        for blocker in referenced_blockers(&tasks[i], &ids)
+        {
+            if tasks[i].blockers.contains(&blocker) {
+                continue;
+            }
+            tasks[i].blockers.push(blocker.clone());
+            if find_cycle(tasks).is_some() {
+                tasks[i].blockers.pop();
+                continue;
+            }
+            added.push((tasks[i].id.clone(), blocker));
+        }
+    }
+    added
+}
+
+/// Pairs of pending tasks that touch the same context
+/// files without either blocking the other, with the
+/// files they share. Worth a look, but not necessarily
+/// an ordering.
+pub fn overlaps(
+    tasks: &[Task],
+) -> Vec<(&str, &str, Vec<&str>)> {
+    let open: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.status != Status::Done)
+        .collect();
+    let mut found = Vec::new();
+    for (i, a) in open.iter().enumerate() {
+        for b in &open[i + 1..] {
+            if a.blockers.contains(&b.id)
+                || b.blockers.contains(&a.id)
+            {
+                continue;
+            }
+            let shared: Vec<&str> = a
+                .context_files
+                .iter()
+                .filter(|f| b.context_files.contains(f))
+                .map(String::as_str)
+                .collect();
+            if !shared.is_empty() {
+                found.push((
+                    a.id.as_str(),
+                    b.id.as_str(),
+                    shared,
+                ));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskType;
+
+    fn task(
+        id: &str,
+        description: &str,
+        blockers: &[&str],
+    ) -> Task {
+        Task {
+            id: id.into(),
+            priority: 1,
+            task_type: TaskType::Bug,
+            status: Status::Pending,
+            title: id.into(),
+            description: description.into(),
+            blockers: blockers
+                .iter()
+                .map(|b| b.to_string())
+                .collect(),
+            stages: BTreeMap::new(),
+            context_files: vec![],
+            error: None,
+            allow_network: false,
+            allowed_tools: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let tasks = vec![
+            task("001", "", &["003"]),
+            task("002", "", &["001"]),
+            task("003", "", &["002"]),
+            task("004", "", &["001"]),
+        ];
+        let err = validate(&tasks).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "blocker cycle: 001 -> 003 -> 002 -> 001"
+        );
+
+        let tasks = vec![
+            task("001", "", &[]),
+            task("002", "", &["001"]),
+            task("003", "", &["001", "002"]),
+        ];
+        assert!(validate(&tasks).is_ok());
+        assert!(
+            validate(&[task("001", "", &["001"])]).is_err()
+        );
+    }
+
+    #[test]
+    fn description_references_become_blockers() {
+        let mut tasks = vec![
+            task("001", "", &[]),
+            task(
+                "002",
+                "Depends on 001. Blocked by task #003, \
+                 requires 999.",
+                &[],
+            ),
+            task("003", "depends on 002", &[]),
+        ];
+        let added = discover(&mut tasks);
+        // 003 -> 002 would close a cycle, so it's skipped.
+        assert_eq!(
+            added,
+            vec![
+                ("002".to_string(), "001".to_string()),
+                ("002".to_string(), "003".to_string()),
+            ]
+        );
+        assert_eq!(tasks[1].blockers, ["001", "003"]);
+        assert!(tasks[2].blockers.is_empty());
+        assert!(discover(&mut tasks).is_empty());
+    }
+
+    #[test]
+    fn overlapping_context_files_are_reported() {
+        let mut a = task("001", "", &[]);
+        a.context_files =
+            vec!["src/a.rs".into(), "src/b.rs".into()];
+        let mut b = task("002", "", &[]);
+        b.context_files = vec!["src/b.rs".into()];
+        let mut c = task("003", "", &["001"]);
+        c.context_files = vec!["src/a.rs".into()];
+
+        let tasks = vec![a, b, c];
+        assert_eq!(
+            overlaps(&tasks),
+            vec![("001", "002", vec!["src/b.rs"])]
+        );
+    }
+}
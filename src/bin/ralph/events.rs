@@ -0,0 +1,157 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::task::Stage;
+
+const EVENT_LOG: &str = "var/ralph-events.jsonl";
+
+/// One entry in the structured run log. Each is
+/// written as a JSON object on its own line, tagged
+/// with `"event"` and a unix timestamp `"ts"`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    TaskPicked {
+        task: &'a str,
+        title: &'a str,
+        stage: Stage,
+    },
+    StageStarted {
+        task: &'a str,
+        stage: Stage,
+        change_id: &'a str,
+    },
+    AgentFinished {
+        task: &'a str,
+        stage: Stage,
+        model: &'a str,
+        exit_code: i32,
+        duration_secs: u64,
+        log_file: &'a str,
+    },
+    SafetyCheck {
+        task: &'a str,
+        stage: Stage,
+        violations: &'a [String],
+    },
+    TestsRun {
+        task: &'a str,
+        stage: Stage,
+        passed: bool,
+    },
+    StageFailed {
+        task: &'a str,
+        stage: Stage,
+        reason: &'a str,
+        retries: u32,
+        gave_up: bool,
+    },
+    StageDone {
+        task: &'a str,
+        stage: Stage,
+        change_id: &'a str,
+    },
+    Squash {
+        task: &'a str,
+        ok: bool,
+    },
+    TaskDone {
+        task: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: u64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Serialize an event as one JSONL line.
+fn to_line(event: &Event, ts: u64) -> Result<String> {
+    let mut line =
+        serde_json::to_string(&Record { ts, event })?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn append(path: &Path, event: &Event) -> Result<()> {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let line = to_line(event, ts)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| {
+            format!("opening {}", path.display())
+        })?;
+    file.write_all(line.as_bytes()).with_context(|| {
+        format!("writing {}", path.display())
+    })
+}
+
+/// Append an event to var/ralph-events.jsonl. The log
+/// is for dashboards, so a write failure is only
+/// reported, never fatal to the run.
+pub fn log(event: Event) {
+    if let Err(e) = append(Path::new(EVENT_LOG), &event) {
+        eprintln!("    warn: event log: {e:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_json_lines() {
+        let line = to_line(
+            &Event::TestsRun {
+                task: "t-1",
+                stage: Stage::Impl,
+                passed: true,
+            },
+            1700000000,
+        )
+        .unwrap();
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+
+        let v: serde_json::Value =
+            serde_json::from_str(&line).unwrap();
+        assert_eq!(v["event"], "tests-run");
+        assert_eq!(v["ts"], 1700000000);
+        assert_eq!(v["task"], "t-1");
+        assert_eq!(v["stage"], "impl");
+        assert_eq!(v["passed"], true);
+    }
+
+    #[test]
+    fn safety_violations_are_listed() {
+        let violations =
+            vec!["tests/x.rs: bad".to_string()];
+        let line = to_line(
+            &Event::SafetyCheck {
+                task: "t-2",
+                stage: Stage::Test,
+                violations: &violations,
+            },
+            0,
+        )
+        .unwrap();
+        let v: serde_json::Value =
+            serde_json::from_str(&line).unwrap();
+        assert_eq!(v["event"], "safety-check");
+        assert_eq!(v["violations"][0], "tests/x.rs: bad");
+    }
+}
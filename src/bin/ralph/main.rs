@@ -1,5 +1,8 @@
 mod agent;
+mod blockers;
+mod events;
 mod jj;
+mod pause;
 mod task;
 
 use std::process::Command;
@@ -8,6 +11,7 @@ use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
 
+use events::Event;
 use task::{Stage, Status, Task};
 
 const MAX_RETRIES: u32 = 2;
@@ -16,8 +20,39 @@ fn main() -> Result<()> {
     eprintln!("Ralph Wiggum reporting for duty!");
     eprintln!();
 
+    for (a, b, files) in
+        blockers::overlaps(&Task::load_all()?)
+    {
+        eprintln!(
+            "Note: tasks {a} and {b} both touch {} \
+             — should one block the other?",
+            files.join(", ")
+        );
+    }
+
     loop {
-        let tasks = Task::load_all()?;
+        // Operator checkpoint: the previous stage is
+        // finished and its state saved.
+        if !pause::checkpoint() {
+            return Ok(());
+        }
+
+        let mut tasks = Task::load_all()?;
+
+        // Pick up "depends on NNN" in new or edited
+        // tasks (including subtasks agents create).
+        for (id, blocker) in blockers::discover(&mut tasks)
+        {
+            eprintln!(
+                "Task {id}: blocked by {blocker} \
+                 (from its description)"
+            );
+            if let Some(t) =
+                tasks.iter().find(|t| t.id == id)
+            {
+                t.save()?;
+            }
+        }
 
         if tasks.is_empty() {
             eprintln!("No tasks in var/tasks/. Exiting.");
@@ -79,6 +114,11 @@ fn main() -> Result<()> {
             task.id, task.title
         );
         eprintln!("    Stage: {stage}");
+        events::log(Event::TaskPicked {
+            task: &task.id,
+            title: &task.title,
+            stage,
+        });
         if task.allow_network {
             let live = matches!(
                 stage,
@@ -99,6 +139,11 @@ fn main() -> Result<()> {
         let change_id = match jj::new_change(&mut task, stage) {
             Ok(cid) => {
                 eprintln!("    JJ change: {cid}");
+                events::log(Event::StageStarted {
+                    task: &task.id,
+                    stage,
+                    change_id: &cid,
+                });
                 cid
             }
             Err(e) => {
@@ -132,6 +177,14 @@ fn main() -> Result<()> {
             result.exit_code, result.model,
         );
         eprintln!("    Log: {}", result.log_file);
+        events::log(Event::AgentFinished {
+            task: &task.id,
+            stage,
+            model: result.model,
+            exit_code: result.exit_code,
+            duration_secs: result.duration.as_secs(),
+            log_file: &result.log_file,
+        });
 
         if result.exit_code != 0 {
             eprintln!("    FAILED: non-zero exit");
@@ -145,6 +198,11 @@ fn main() -> Result<()> {
 
         // Safety check
         let violations = agent::safety_check()?;
+        events::log(Event::SafetyCheck {
+            task: &task.id,
+            stage,
+            violations: &violations,
+        });
         if !violations.is_empty() {
             eprintln!("    FAILED: safety check");
             for v in &violations {
@@ -176,6 +234,11 @@ fn main() -> Result<()> {
                 .arg("test")
                 .output()
                 .context("running cargo test")?;
+            events::log(Event::TestsRun {
+                task: &task.id,
+                stage,
+                passed: cargo.status.success(),
+            });
 
             if !cargo.status.success() {
                 eprintln!("    FAILED: cargo test");
@@ -196,6 +259,11 @@ fn main() -> Result<()> {
 
         // Record success
         let cid = jj::current_change_id()?;
+        events::log(Event::StageDone {
+            task: &task.id,
+            stage,
+            change_id: &cid,
+        });
         task.set_stage_status(stage, Status::Done);
         task.set_stage_change_id(stage, cid);
         task.save()?;
@@ -203,9 +271,15 @@ fn main() -> Result<()> {
         // Check if all stages done
         if task.all_stages_done() {
             eprintln!("    All stages done — squashing...");
-            jj::squash_chain(&task)?;
+            let squashed = jj::squash_chain(&task);
+            events::log(Event::Squash {
+                task: &task.id,
+                ok: squashed.is_ok(),
+            });
+            squashed?;
             task.status = Status::Done;
             task.save()?;
+            events::log(Event::TaskDone { task: &task.id });
             eprintln!("=== Task {}: DONE ===", task.id);
         }
 
@@ -222,6 +296,13 @@ fn handle_failure(
 
     task.increment_stage_retries(stage);
     let retries = task.stage_retries(stage);
+    events::log(Event::StageFailed {
+        task: &task.id,
+        stage,
+        reason,
+        retries,
+        gave_up: retries > MAX_RETRIES,
+    });
 
     if retries > MAX_RETRIES {
         eprintln!(
@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const PAUSE_FILE: &str = "var/ralph-pause";
+
+/// What the operator asked for by creating
+/// var/ralph-pause.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pause {
+    /// Wait until the file is removed, then carry on.
+    Wait,
+    /// Stop ralph; the file's contents were "exit".
+    Exit,
+}
+
+impl Pause {
+    fn from_contents(contents: &str) -> Self {
+        if contents.trim().eq_ignore_ascii_case("exit") {
+            Pause::Exit
+        } else {
+            Pause::Wait
+        }
+    }
+}
+
+fn requested(path: &Path) -> Option<Pause> {
+    // The file may vanish between the check and the read;
+    // that just means no pause.
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|c| Pause::from_contents(&c))
+}
+
+/// Called between stages, never during one. If the
+/// operator has created var/ralph-pause, block until it
+/// is removed. Returns false if ralph should exit
+/// instead (the file says "exit"); the file is then
+/// removed so the next run starts normally.
+pub fn checkpoint() -> bool {
+    let path = Path::new(PAUSE_FILE);
+    let mut announced = false;
+    loop {
+        match requested(path) {
+            None => {
+                if announced {
+                    eprintln!("Resuming.");
+                    eprintln!();
+                }
+                return true;
+            }
+            Some(Pause::Exit) => {
+                let _ = std::fs::remove_file(path);
+                eprintln!(
+                    "{PAUSE_FILE} says exit — stopping \
+                     with all state saved."
+                );
+                return false;
+            }
+            Some(Pause::Wait) => {
+                if !announced {
+                    eprintln!(
+                        "Paused: remove {PAUSE_FILE} to \
+                         continue (or write \"exit\" to \
+                         it to stop)."
+                    );
+                    announced = true;
+                }
+                thread::sleep(Duration::from_secs(5));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contents_select_wait_or_exit() {
+        assert_eq!(Pause::from_contents(""), Pause::Wait);
+        assert_eq!(
+            Pause::from_contents("back soon\n"),
+            Pause::Wait
+        );
+        assert_eq!(
+            Pause::from_contents("exit\n"),
+            Pause::Exit
+        );
+        assert_eq!(
+            Pause::from_contents(" EXIT "),
+            Pause::Exit
+        );
+        assert_eq!(
+            requested(Path::new("var/no-such-pause")),
+            None
+        );
+    }
+}
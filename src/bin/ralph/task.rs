@@ -107,6 +107,13 @@ pub struct Task {
     /// the dead proxy, allowing upstream API access.
     #[serde(default)]
     pub allow_network: bool,
+    /// Per-stage replacements for the tool allowlists
+    /// in workflow/tools.toml.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub allowed_tools: BTreeMap<Stage, Vec<String>>,
 }
 
 impl Task {
@@ -137,6 +144,7 @@ impl Task {
             }
         }
         tasks.sort_by_key(|t| t.priority);
+        crate::blockers::validate(&tasks)?;
         Ok(tasks)
     }
 
@@ -334,6 +342,7 @@ mod tests {
             context_files: vec![],
             error: None,
             allow_network: false,
+            allowed_tools: BTreeMap::new(),
         };
         let blocked = Task {
             id: "002".into(),
@@ -347,6 +356,7 @@ mod tests {
             context_files: vec![],
             error: None,
             allow_network: false,
+            allowed_tools: BTreeMap::new(),
         };
         let all = vec![blocker.clone(), blocked.clone()];
 
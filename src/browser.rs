@@ -0,0 +1,359 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use aes::cipher::{BlockDecryptMut, KeyIvInit, block_padding::Pkcs7};
+use anyhow::{Context, Result, bail};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+/// Hosts the Bandcamp identity cookie is stored under.
+const BANDCAMP_HOSTS: [&str; 2] = [".bandcamp.com", "bandcamp.com"];
+
+/// Browsers whose cookie stores `qoget login bandcamp --from-browser` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Firefox,
+    Chrome,
+    Chromium,
+}
+
+impl FromStr for Browser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "firefox" => Ok(Browser::Firefox),
+            "chrome" => Ok(Browser::Chrome),
+            "chromium" => Ok(Browser::Chromium),
+            _ => Err(format!(
+                "unknown browser '{s}' (expected firefox, chrome, or chromium)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Browser::Firefox => write!(f, "Firefox"),
+            Browser::Chrome => write!(f, "Chrome"),
+            Browser::Chromium => write!(f, "Chromium"),
+        }
+    }
+}
+
+/// A cookie value and when the browser last used it (seconds since the Unix
+/// epoch), to pick the live one when several profiles have it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundCookie {
+    pub value: String,
+    pub last_used: i64,
+}
+
+/// Read the Bandcamp identity cookie from `browser`'s profiles, preferring
+/// the one used most recently. The browser may stay open: each cookie
+/// database is copied before it is read.
+pub fn bandcamp_identity(browser: Browser) -> Result<String> {
+    let stores = cookie_stores(browser);
+    if stores.is_empty() {
+        bail!("No {browser} profile with a cookie database found");
+    }
+
+    let mut newest: Option<FoundCookie> = None;
+    for store in &stores {
+        let found = match browser {
+            Browser::Firefox => read_firefox_cookie(store),
+            Browser::Chrome | Browser::Chromium => read_chromium_cookie(store, browser),
+        }
+        .with_context(|| format!("reading {}", store.display()))?;
+        if let Some(found) = found
+            && newest
+                .as_ref()
+                .is_none_or(|n| found.last_used > n.last_used)
+        {
+            newest = Some(found);
+        }
+    }
+    match newest {
+        Some(found) => Ok(found.value),
+        None => bail!("Not logged in to bandcamp.com in {browser}; log in there and try again"),
+    }
+}
+
+/// Cookie databases of every profile of `browser`.
+fn cookie_stores(browser: Browser) -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+    let (roots, files): (Vec<PathBuf>, &[&str]) = match browser {
+        Browser::Firefox => (firefox_roots(&home), &["cookies.sqlite"]),
+        Browser::Chrome | Browser::Chromium => (
+            chromium_roots(&home, browser),
+            &["Cookies", "Network/Cookies"],
+        ),
+    };
+
+    let mut stores = Vec::new();
+    for root in roots {
+        let Ok(profiles) = std::fs::read_dir(&root) else {
+            continue;
+        };
+        for profile in profiles.flatten() {
+            for file in files {
+                let path = profile.path().join(file);
+                if path.is_file() {
+                    stores.push(path);
+                }
+            }
+        }
+    }
+    stores.sort();
+    stores.dedup();
+    stores
+}
+
+fn firefox_roots(home: &Path) -> Vec<PathBuf> {
+    if cfg!(target_os = "macos") {
+        vec![home.join("Library/Application Support/Firefox/Profiles")]
+    } else if cfg!(windows) {
+        let appdata = PathBuf::from(std::env::var_os("APPDATA").unwrap_or_default());
+        vec![appdata.join("Mozilla/Firefox/Profiles")]
+    } else {
+        vec![
+            home.join(".mozilla/firefox"),
+            home.join("snap/firefox/common/.mozilla/firefox"),
+            home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"),
+        ]
+    }
+}
+
+fn chromium_roots(home: &Path, browser: Browser) -> Vec<PathBuf> {
+    let chrome = browser == Browser::Chrome;
+    if cfg!(target_os = "macos") {
+        let name = if chrome { "Google/Chrome" } else { "Chromium" };
+        vec![home.join("Library/Application Support").join(name)]
+    } else if cfg!(windows) {
+        let local = PathBuf::from(std::env::var_os("LOCALAPPDATA").unwrap_or_default());
+        let name = if chrome { "Google/Chrome" } else { "Chromium" };
+        vec![local.join(name).join("User Data")]
+    } else {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"));
+        let name = if chrome { "google-chrome" } else { "chromium" };
+        vec![
+            config.join(name),
+            home.join("snap/chromium/common/chromium"),
+        ]
+    }
+}
+
+/// Open a copy of a cookie database, since browsers keep theirs locked. The
+/// write-ahead log is copied too, so recent logins are included.
+fn open_copy(db: &Path) -> Result<(Connection, PathBuf)> {
+    let dir = private_temp_dir()?;
+    let copy = dir.join("cookies.sqlite");
+    std::fs::copy(db, &copy).with_context(|| format!("copying {}", db.display()))?;
+    for suffix in ["-wal", "-shm"] {
+        let mut side = db.as_os_str().to_owned();
+        side.push(suffix);
+        let mut side_copy = copy.as_os_str().to_owned();
+        side_copy.push(suffix);
+        let _ = std::fs::remove_file(&side_copy);
+        if Path::new(&side).exists() {
+            std::fs::copy(&side, &side_copy)?;
+        }
+    }
+    let conn = Connection::open_with_flags(&copy, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .with_context(|| format!("opening {}", copy.display()))?;
+    Ok((conn, dir))
+}
+
+/// Create a new directory under the temp dir that only the user can enter,
+/// for copies of their cookies. A directory already there, which another
+/// user could have made, is never reused.
+fn private_temp_dir() -> Result<PathBuf> {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    for _ in 0..16 {
+        let n = COPIES.fetch_add(1, Ordering::Relaxed);
+        let name = format!("qoget-cookies-{}-{nanos:08x}-{n}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        match create_private_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("creating {}", dir.display())),
+        }
+    }
+    bail!("could not create a private directory for cookie copies")
+}
+
+/// Create `dir`, failing if it exists, with access for its owner only (on
+/// Unix).
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::DirBuilder::new().create(dir)
+}
+
+/// The identity cookie in a Firefox `cookies.sqlite`, if present.
+pub fn read_firefox_cookie(db: &Path) -> Result<Option<FoundCookie>> {
+    let (conn, dir) = open_copy(db)?;
+    let found = conn
+        .query_row(
+            "SELECT value, lastAccessed FROM moz_cookies \
+             WHERE name = 'identity' AND host IN (?1, ?2) \
+             ORDER BY lastAccessed DESC LIMIT 1",
+            BANDCAMP_HOSTS,
+            |row| {
+                Ok(FoundCookie {
+                    value: row.get(0)?,
+                    // Microseconds
+                    last_used: row.get::<_, i64>(1)? / 1_000_000,
+                })
+            },
+        )
+        .optional();
+    drop(conn);
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(found?)
+}
+
+/// The identity cookie in a Chrome/Chromium `Cookies` database, decrypted.
+pub fn read_chromium_cookie(db: &Path, browser: Browser) -> Result<Option<FoundCookie>> {
+    let (conn, dir) = open_copy(db)?;
+    let result = (|| -> Result<_> {
+        // From schema version 24 the plaintext starts with a hash of the host.
+        let version: i64 = conn
+            .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let row = conn
+            .query_row(
+                "SELECT value, encrypted_value, last_access_utc FROM cookies \
+                 WHERE name = 'identity' AND host_key IN (?1, ?2) \
+                 ORDER BY last_access_utc DESC LIMIT 1",
+                BANDCAMP_HOSTS,
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Vec<u8>>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        Ok((version, row))
+    })();
+    drop(conn);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let (version, row) = result?;
+    let Some((plain, encrypted, last_access)) = row else {
+        return Ok(None);
+    };
+    let value = if encrypted.is_empty() {
+        plain
+    } else {
+        let password = safe_storage_password(browser, &encrypted)?;
+        decrypt_chromium_value(&encrypted, &password, version >= 24)?
+    };
+    Ok(Some(FoundCookie {
+        value,
+        // Microseconds since 1601-01-01
+        last_used: last_access / 1_000_000 - 11_644_473_600,
+    }))
+}
+
+/// Key-derivation rounds Chromium uses for cookie encryption on this OS.
+const PBKDF2_ROUNDS: u32 = if cfg!(target_os = "macos") { 1003 } else { 1 };
+
+/// Decrypt a `v10`/`v11` Chromium cookie (AES-128-CBC, key from PBKDF2 over
+/// the browser's safe-storage password). `host_hash_prefix` strips the
+/// SHA-256 of the host that newer databases put in front of the value.
+pub fn decrypt_chromium_value(
+    encrypted: &[u8],
+    password: &[u8],
+    host_hash_prefix: bool,
+) -> Result<String> {
+    let Some(ciphertext) = encrypted
+        .strip_prefix(b"v10")
+        .or_else(|| encrypted.strip_prefix(b"v11"))
+    else {
+        bail!("unsupported cookie encryption (only v10/v11 are understood)");
+    };
+    let mut key = [0u8; 16];
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, b"saltysalt", PBKDF2_ROUNDS, &mut key);
+    let plaintext = cbc::Decryptor::<aes::Aes128>::new(&key.into(), &[b' '; 16].into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| anyhow::anyhow!("cookie could not be decrypted (wrong key?)"))?;
+    let value = if host_hash_prefix && plaintext.len() >= 32 {
+        &plaintext[32..]
+    } else {
+        &plaintext[..]
+    };
+    String::from_utf8(value.to_vec()).context("decrypted cookie is not text")
+}
+
+/// The password Chromium derives its cookie key from.
+fn safe_storage_password(browser: Browser, encrypted: &[u8]) -> Result<Vec<u8>> {
+    // Linux `v10` cookies use a fixed password; `v11` ones one kept in the
+    // desktop keyring.
+    if cfg!(target_os = "linux") && encrypted.starts_with(b"v10") {
+        return Ok(b"peanuts".to_vec());
+    }
+    os_safe_storage_password(browser)
+}
+
+#[cfg(all(target_os = "linux", feature = "keyring"))]
+fn os_safe_storage_password(browser: Browser) -> Result<Vec<u8>> {
+    use secret_service::EncryptionType;
+    use secret_service::blocking::SecretService;
+
+    let application = match browser {
+        Browser::Chrome => "chrome",
+        _ => "chromium",
+    };
+    let ss =
+        SecretService::connect(EncryptionType::Dh).context("connecting to the desktop keyring")?;
+    let found = ss
+        .search_items(std::collections::HashMap::from([(
+            "application",
+            application,
+        )]))
+        .context("searching the desktop keyring")?;
+    let Some(item) = found.unlocked.into_iter().chain(found.locked).next() else {
+        bail!("{browser}'s cookie key is not in the desktop keyring");
+    };
+    item.unlock()?;
+    Ok(item.get_secret()?)
+}
+
+#[cfg(all(target_os = "macos", feature = "keyring"))]
+fn os_safe_storage_password(browser: Browser) -> Result<Vec<u8>> {
+    let (service, account) = match browser {
+        Browser::Chrome => ("Chrome Safe Storage", "Chrome"),
+        _ => ("Chromium Safe Storage", "Chromium"),
+    };
+    let password = keyring::Entry::new(service, account)?
+        .get_password()
+        .with_context(|| format!("reading \"{service}\" from the Keychain"))?;
+    Ok(password.into_bytes())
+}
+
+#[cfg(not(all(any(target_os = "linux", target_os = "macos"), feature = "keyring")))]
+fn os_safe_storage_password(browser: Browser) -> Result<Vec<u8>> {
+    bail!(
+        "Reading {browser}'s encrypted cookies isn't supported on this system; \
+         use --from-browser firefox or paste the cookie instead"
+    )
+}
@@ -1,7 +1,12 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use anyhow::{Context, Result, bail};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
 use crate::models::AppCredentials;
 
@@ -10,6 +15,116 @@ const PLAY_BASE: &str = "https://play.qobuz.com";
 const VALIDATION_TRACK_ID: u64 = 19512574;
 const VALIDATION_FORMAT_ID: u8 = 27;
 
+/// How long cached credentials are trusted before they are validated again.
+pub const CREDENTIALS_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Extracted app credentials as cached on disk, with when they last passed
+/// validation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedCredentials {
+    pub app_id: String,
+    pub app_secret: String,
+    /// Unix timestamp of the last successful validation.
+    pub validated_at: u64,
+}
+
+impl CachedCredentials {
+    pub fn path(cache_dir: &Path) -> PathBuf {
+        cache_dir.join("app-credentials.json")
+    }
+
+    pub fn load(cache_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(cache_dir);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading {}", path.display()));
+            }
+        };
+        let cached = serde_json::from_str(&contents)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Some(cached))
+    }
+
+    /// Atomic write: temp file + rename.
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        let path = Self::path(cache_dir);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::create_dir_all(cache_dir)
+            .with_context(|| format!("creating {}", cache_dir.display()))?;
+        let json = serde_json::to_string(self)?;
+        std::fs::write(&tmp, &json).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+
+    /// Whether the last validation is recent enough to skip another.
+    pub fn is_fresh(&self, now: u64, ttl: Duration) -> bool {
+        now.saturating_sub(self.validated_at) < ttl.as_secs()
+    }
+}
+
+/// App credentials from the on-disk cache, falling back to extraction.
+///
+/// Fresh cache entries are used as-is. Stale ones are re-validated with a
+/// single request and kept if they still pass; otherwise (or when nothing is
+/// cached) the bundle is scraped again and the result cached. Cache read and
+/// write problems only cost the extra requests, never the sync.
+pub async fn cached_credentials(
+    http_client: &reqwest::Client,
+    cache_dir: &Path,
+) -> Result<AppCredentials> {
+    let now = unix_now()?;
+    let cached = CachedCredentials::load(cache_dir).unwrap_or_else(|e| {
+        warn!("ignoring cached Qobuz app credentials: {e:#}");
+        None
+    });
+
+    if let Some(mut cached) = cached {
+        let valid = cached.is_fresh(now, CREDENTIALS_TTL)
+            || matches!(
+                validate_secret(http_client, &cached.app_id, &cached.app_secret).await,
+                Ok(true)
+            );
+        if valid {
+            if !cached.is_fresh(now, CREDENTIALS_TTL) {
+                cached.validated_at = now;
+                save_cache(&cached, cache_dir);
+            }
+            return Ok(AppCredentials {
+                app_id: cached.app_id,
+                app_secret: cached.app_secret,
+            });
+        }
+        info!("Cached Qobuz app credentials no longer validate");
+    }
+
+    info!("Extracting app credentials from Qobuz...");
+    let creds = extract_credentials(http_client).await?;
+    save_cache(
+        &CachedCredentials {
+            app_id: creds.app_id.clone(),
+            app_secret: creds.app_secret.clone(),
+            validated_at: now,
+        },
+        cache_dir,
+    );
+    Ok(creds)
+}
+
+fn save_cache(cached: &CachedCredentials, cache_dir: &Path) {
+    if let Err(e) = cached.save(cache_dir) {
+        warn!("could not cache Qobuz app credentials: {e:#}");
+    }
+}
+
+fn unix_now() -> Result<u64> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs())
+}
+
 /// Extract app_id and app_secret from the Qobuz web player's bundle.js.
 pub async fn extract_credentials(http_client: &reqwest::Client) -> Result<AppCredentials> {
     // Step 1: Fetch login page and find bundle.js URL
@@ -125,10 +240,7 @@ async fn validate_secret(
     app_id: &str,
     secret: &str,
 ) -> Result<bool> {
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)?
-        .as_secs()
-        .to_string();
+    let timestamp = unix_now()?.to_string();
 
     let sig = crate::client::generate_request_sig(
         VALIDATION_TRACK_ID,
@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+
+use crate::manifest::Manifest;
+
+/// Name of the checksum file kept in each album directory, in the format
+/// `sha256sum` writes and checks (`sha256sum -c checksums.sha256`).
+pub const CHECKSUM_FILE: &str = "checksums.sha256";
+
+/// Render `sha256sum` lines: the hex digest, two spaces, the file name.
+pub fn render(sums: &BTreeMap<String, String>) -> String {
+    sums.iter()
+        .map(|(name, sha256)| format!("{sha256}  {name}\n"))
+        .collect()
+}
+
+/// Parse `sha256sum` lines into file name -> hex digest. Binary-mode lines
+/// (`<digest> *<name>`) are read too; blank lines, comments and lines that
+/// aren't checksums are skipped.
+pub fn parse(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (sha256, name) = line.split_once(' ')?;
+            let name = name.strip_prefix([' ', '*'])?;
+            let valid = sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit());
+            (valid && !name.is_empty()).then(|| (name.to_string(), sha256.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// The checksums the manifest records for files directly in `dir`
+/// (relative to the target directory), by file name.
+fn recorded_in(manifest: &Manifest, dir: &Path) -> BTreeMap<String, String> {
+    manifest
+        .entries()
+        .filter(|e| e.path.parent() == Some(dir))
+        .filter_map(|e| {
+            let name = e.path.file_name()?.to_string_lossy().into_owned();
+            Some((name, e.sha256.clone()?))
+        })
+        .collect()
+}
+
+/// Directories (relative to the target directory) holding a file whose
+/// manifest entry was added, removed, moved, or re-hashed between `before`
+/// and `after`: the ones whose checksum files are out of date.
+pub fn changed_dirs(before: &Manifest, after: &Manifest) -> BTreeSet<PathBuf> {
+    let old: HashMap<&str, _> = before
+        .keyed_entries()
+        .map(|(k, e)| (k, (&e.path, &e.sha256)))
+        .collect();
+    let new: HashMap<&str, _> = after
+        .keyed_entries()
+        .map(|(k, e)| (k, (&e.path, &e.sha256)))
+        .collect();
+    let mut dirs = BTreeSet::new();
+    for (key, entry) in &old {
+        if new.get(key) != Some(entry) {
+            dirs.extend(entry.0.parent().map(Path::to_path_buf));
+        }
+    }
+    for (key, entry) in &new {
+        if old.get(key) != Some(entry) {
+            dirs.extend(entry.0.parent().map(Path::to_path_buf));
+        }
+    }
+    dirs
+}
+
+/// Rewrite the checksum file of each of `dirs` (relative to `target_dir`)
+/// from the checksums the manifest records for its files, and remove it
+/// from directories with none left. Only files qoget downloaded are
+/// listed. Returns how many files were written.
+pub fn refresh(
+    target_dir: &Path,
+    manifest: &Manifest,
+    dirs: impl IntoIterator<Item = PathBuf>,
+) -> Result<usize> {
+    let mut written = 0;
+    for dir in dirs {
+        if dir.as_os_str().is_empty() {
+            continue;
+        }
+        let path = target_dir.join(&dir).join(CHECKSUM_FILE);
+        let sums = recorded_in(manifest, &dir);
+        if sums.is_empty() {
+            match std::fs::remove_file(&path) {
+                Ok(()) => debug!("removed {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("removing {}", path.display())),
+            }
+            continue;
+        }
+        if !target_dir.join(&dir).is_dir() {
+            continue;
+        }
+        let contents = render(&sums);
+        if std::fs::read_to_string(&path).is_ok_and(|c| c == contents) {
+            continue;
+        }
+        let tmp = path.with_extension("sha256.tmp");
+        std::fs::write(&tmp, &contents).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// `refresh` the directories whose files changed since `before`, reporting
+/// rather than failing: the files are a convenience, the manifest is the
+/// record.
+pub fn refresh_changed(target_dir: &Path, before: &Manifest, after: &Manifest) {
+    match refresh(target_dir, after, changed_dirs(before, after)) {
+        Ok(0) => {}
+        Ok(n) => debug!("updated {n} checksum files"),
+        Err(e) => warn!("could not update checksum files: {e:#}"),
+    }
+}
+
+/// Every checksum file in the directories of `files` (relative to
+/// `target_dir`), as file path (relative to `target_dir`) -> hex digest.
+/// Unreadable checksum files are skipped.
+pub fn load_listed(target_dir: &Path, files: &[PathBuf]) -> HashMap<PathBuf, String> {
+    let dirs: BTreeSet<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+    let mut listed = HashMap::new();
+    for dir in dirs {
+        let path = target_dir.join(dir).join(CHECKSUM_FILE);
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for (name, sha256) in parse(&contents) {
+            listed.insert(dir.join(name), sha256);
+        }
+    }
+    listed
+}
@@ -1,40 +1,57 @@
-use std::time::Duration;
-
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
 use reqwest::RequestBuilder;
 use serde::de::DeserializeOwned;
+use tracing::trace;
 
+use crate::health::AuthError;
+use crate::http_cache::HttpCache;
 use crate::models::{
-    Album, AlbumId, FileUrlResponse, LoginResponse, PurchaseList, PurchaseResponse, TrackId,
-    UserAuth,
+    Album, AlbumId, CatalogSearchResponse, FavoritesResponse, FileUrlResponse, LoginResponse,
+    Playlist, PlaylistTrack, PlaylistsResponse, PurchaseList, PurchaseResponse, Service, Track,
+    TrackId, UserAuth,
 };
+use crate::retry::{self, RateLimiter, RequestPolicy};
 
 const BASE_URL: &str = "https://www.qobuz.com/api.json/0.2";
-const MAX_RETRIES: u32 = 3;
-const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
 
 pub struct QobuzClient {
     http: reqwest::Client,
     app_id: String,
     app_secret: String,
     auth_token: String,
+    policy: RequestPolicy,
+    rate_limiter: RateLimiter,
+    cache: Option<HttpCache>,
 }
 
 impl QobuzClient {
+    /// A client pacing and retrying requests as `policy` says.
     pub fn new(
         http: reqwest::Client,
         app_id: String,
         app_secret: String,
         auth_token: String,
+        policy: RequestPolicy,
     ) -> Self {
         Self {
             http,
             app_id,
             app_secret,
             auth_token,
+            rate_limiter: RateLimiter::new(policy.requests_per_second),
+            policy,
+            cache: None,
         }
     }
 
+    /// Cache purchase, favorite, playlist and album responses in `cache`
+    /// and revalidate them instead of fetching them whole every run.
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
     pub fn http(&self) -> &reqwest::Client {
         &self.http
     }
@@ -46,23 +63,54 @@ impl QobuzClient {
             .header("X-User-Auth-Token", &self.auth_token)
     }
 
-    /// Fetch all purchases, paginating through albums and tracks.
-    pub async fn get_purchases(&self) -> Result<PurchaseList> {
+    /// Send a request with retry on transient failures, as the client's
+    /// `RequestPolicy` says.
+    async fn send_with_retry<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        retry::send(&request, &self.policy, &self.rate_limiter)
+            .await?
+            .json()
+            .await
+            .context("Failed to parse response JSON")
+    }
+
+    /// Like `send_with_retry`, through the response cache if there is one.
+    async fn send_cached<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let Some(cache) = &self.cache else {
+            return self.send_with_retry(request).await;
+        };
+        let body = cache
+            .send(&self.auth_token, request, &self.policy, &self.rate_limiter)
+            .await?;
+        serde_json::from_str(&body).context("Failed to parse response JSON")
+    }
+
+    /// Fetch all purchases, paginating through albums and tracks and
+    /// reporting pages and item counts on `pb`.
+    pub async fn get_purchases(&self, pb: &ProgressBar) -> Result<PurchaseList> {
         let mut all_albums = Vec::new();
         let mut all_tracks = Vec::new();
         let limit: u64 = 500;
 
         let mut offset: u64 = 0;
+        let mut page = 0;
         loop {
-            let resp: PurchaseResponse = send_with_retry(
-                self.authed_get("/purchase/getUserPurchases")
-                    .query(&[("limit", limit.to_string()), ("offset", offset.to_string())]),
-            )
-            .await
-            .context("Failed to fetch purchases")?;
+            let resp: PurchaseResponse = self
+                .send_cached(
+                    self.authed_get("/purchase/getUserPurchases")
+                        .query(&[("limit", limit.to_string()), ("offset", offset.to_string())]),
+                )
+                .await
+                .context("Failed to fetch purchases")?;
 
             all_albums.extend(resp.albums.items);
             all_tracks.extend(resp.tracks.items);
+            page += 1;
+            pb.set_message(format!(
+                "page {page}: {} of {} albums, {} tracks",
+                all_albums.len(),
+                resp.albums.total,
+                all_tracks.len()
+            ));
 
             if offset + limit >= resp.albums.total {
                 break;
@@ -76,9 +124,123 @@ impl QobuzClient {
         })
     }
 
+    /// Fetch the albums and tracks the user has marked as favorites,
+    /// reporting pages and item counts on `pb`. Favorites needn't be
+    /// purchases; see `download::entitled_tracks`.
+    pub async fn get_favorites(&self, pb: &ProgressBar) -> Result<PurchaseList> {
+        let mut favorites = PurchaseList {
+            albums: Vec::new(),
+            tracks: Vec::new(),
+        };
+        let limit: u64 = 500;
+
+        for kind in ["albums", "tracks"] {
+            let mut offset: u64 = 0;
+            loop {
+                let resp: FavoritesResponse = self
+                    .send_cached(self.authed_get("/favorite/getUserFavorites").query(&[
+                        ("type", kind.to_string()),
+                        ("limit", limit.to_string()),
+                        ("offset", offset.to_string()),
+                    ]))
+                    .await
+                    .context("Failed to fetch favorites")?;
+
+                let total = match (resp.albums, resp.tracks) {
+                    (Some(albums), _) if kind == "albums" => {
+                        favorites.albums.extend(albums.items);
+                        albums.total
+                    }
+                    (_, Some(tracks)) if kind == "tracks" => {
+                        favorites.tracks.extend(tracks.items);
+                        tracks.total
+                    }
+                    _ => 0,
+                };
+                pb.set_message(format!(
+                    "favorites: {} albums, {} tracks",
+                    favorites.albums.len(),
+                    favorites.tracks.len()
+                ));
+
+                if offset + limit >= total {
+                    break;
+                }
+                offset += limit;
+            }
+        }
+
+        Ok(favorites)
+    }
+
+    /// Fetch the user's playlists, without their tracks.
+    pub async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        let mut playlists = Vec::new();
+        let limit: u64 = 500;
+        let mut offset: u64 = 0;
+        loop {
+            let resp: PlaylistsResponse = self
+                .send_cached(
+                    self.authed_get("/playlist/getUserPlaylists")
+                        .query(&[("limit", limit.to_string()), ("offset", offset.to_string())]),
+                )
+                .await
+                .context("Failed to fetch playlists")?;
+
+            playlists.extend(resp.playlists.items);
+            if offset + limit >= resp.playlists.total {
+                break;
+            }
+            offset += limit;
+        }
+        Ok(playlists)
+    }
+
+    /// Fetch every track of a playlist, in playlist order.
+    pub async fn get_playlist_tracks(&self, playlist_id: u64) -> Result<Vec<PlaylistTrack>> {
+        let mut tracks = Vec::new();
+        let limit: u64 = 500;
+        let mut offset: u64 = 0;
+        loop {
+            let resp: Playlist = self
+                .send_cached(self.authed_get("/playlist/get").query(&[
+                    ("playlist_id", playlist_id.to_string()),
+                    ("extra", "tracks".to_string()),
+                    ("limit", limit.to_string()),
+                    ("offset", offset.to_string()),
+                ]))
+                .await
+                .context("Failed to fetch playlist")?;
+
+            let Some(page) = resp.tracks else { break };
+            tracks.extend(page.items);
+            if offset + limit >= page.total {
+                break;
+            }
+            offset += limit;
+        }
+        Ok(tracks)
+    }
+
+    /// Search the whole Qobuz catalog, not just purchases, for up to
+    /// `limit` albums and `limit` tracks matching `query`.
+    pub async fn search_catalog(&self, query: &str, limit: u32) -> Result<PurchaseList> {
+        let resp: CatalogSearchResponse = self
+            .send_with_retry(
+                self.authed_get("/catalog/search")
+                    .query(&[("query", query.to_string()), ("limit", limit.to_string())]),
+            )
+            .await
+            .context("Failed to search the catalog")?;
+        Ok(PurchaseList {
+            albums: resp.albums.map(|a| a.items).unwrap_or_default(),
+            tracks: resp.tracks.map(|t| t.items).unwrap_or_default(),
+        })
+    }
+
     /// Fetch full album metadata including track listing.
     pub async fn get_album(&self, album_id: &AlbumId) -> Result<Album> {
-        send_with_retry(
+        self.send_cached(
             self.authed_get("/album/get")
                 .query(&[("album_id", album_id.0.as_str())]),
         )
@@ -86,13 +248,26 @@ impl QobuzClient {
         .context("Failed to fetch album")
     }
 
+    /// Fetch one track's metadata.
+    pub async fn get_track(&self, track_id: TrackId) -> Result<Track> {
+        self.send_cached(
+            self.authed_get("/track/get")
+                .query(&[("track_id", track_id.0.to_string())]),
+        )
+        .await
+        .context("Failed to fetch track")
+    }
+
     /// Get a signed download URL for a track.
     ///
     /// Uses `intent=stream` in both the query and signature. Qobuz now validates
     /// the intent parameter against the signature (previously it was ignored
     /// server-side). Using `intent=stream` with `format_id=5` still returns
     /// MP3 320 URLs for purchased content.
-    pub async fn get_file_url(&self, track_id: TrackId, format_id: u8) -> Result<String> {
+    ///
+    /// The response also says what the stream is (MIME type, and bit depth
+    /// and sample rate for lossless formats).
+    pub async fn get_file_url(&self, track_id: TrackId, format_id: u8) -> Result<FileUrlResponse> {
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs()
@@ -100,7 +275,7 @@ impl QobuzClient {
 
         let sig = generate_request_sig(track_id.0, format_id, &timestamp, &self.app_secret);
 
-        let resp: FileUrlResponse = send_with_retry(self.authed_get("/track/getFileUrl").query(&[
+        self.send_with_retry(self.authed_get("/track/getFileUrl").query(&[
             ("track_id", track_id.0.to_string()),
             ("format_id", format_id.to_string()),
             ("intent", "stream".to_string()),
@@ -108,9 +283,7 @@ impl QobuzClient {
             ("request_sig", sig),
         ]))
         .await
-        .context("Failed to get file URL")?;
-
-        Ok(resp.url)
+        .context("Failed to get file URL")
     }
 }
 
@@ -136,7 +309,7 @@ pub async fn login(
         .context("Login request failed")?;
 
     if resp.status() == 401 {
-        bail!("Authentication failed: invalid credentials");
+        return Err(AuthError(Service::Qobuz).into());
     }
 
     let login: LoginResponse = resp
@@ -158,41 +331,9 @@ pub fn generate_request_sig(
     timestamp: &str,
     app_secret: &str,
 ) -> String {
+    trace!(track_id, format_id, timestamp, "signing getFileUrl request");
     let data = format!(
         "trackgetFileUrlformat_id{format_id}intentstreamtrack_id{track_id}{timestamp}{app_secret}"
     );
     format!("{:x}", md5::compute(data.as_bytes()))
 }
-
-/// Send a request with retry on transient failures (429, 500, 502, 503, 504).
-/// Exponential backoff: 1s, 2s, 4s. Max 3 retries.
-/// Does NOT retry on 401 (auth) or 400 (bad request).
-async fn send_with_retry<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
-    let mut backoff = INITIAL_BACKOFF;
-
-    for attempt in 0..=MAX_RETRIES {
-        let req = request
-            .try_clone()
-            .context("Request cannot be cloned for retry")?;
-
-        let resp = req.send().await?;
-        let status = resp.status();
-
-        if status.is_success() {
-            return resp.json().await.context("Failed to parse response JSON");
-        }
-
-        let retryable = matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504);
-
-        if !retryable || attempt == MAX_RETRIES {
-            let body = resp.text().await.unwrap_or_default();
-            bail!("HTTP {} — {}", status, body);
-        }
-
-        eprintln!("HTTP {}, retrying in {:?}...", status, backoff);
-        tokio::time::sleep(backoff).await;
-        backoff *= 2;
-    }
-
-    unreachable!()
-}
@@ -0,0 +1,62 @@
+use std::cmp::Ordering;
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// How listing commands order artists, albums, and tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Case- and accent-insensitive order ("Édith Piaf" sorts under E).
+    #[default]
+    Unicode,
+    /// Plain byte order of the UTF-8 strings.
+    Bytes,
+}
+
+impl SortOrder {
+    pub fn from_byte_order_flag(byte_order: bool) -> Self {
+        if byte_order {
+            SortOrder::Bytes
+        } else {
+            SortOrder::Unicode
+        }
+    }
+}
+
+/// Primary collation key: canonical decomposition with combining marks
+/// removed, then lowercased.
+pub fn sort_key(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Compare two strings. Under `Unicode`, strings with equal keys fall back to
+/// byte order so the result is total and deterministic.
+pub fn compare(a: &str, b: &str, order: SortOrder) -> Ordering {
+    match order {
+        SortOrder::Bytes => a.cmp(b),
+        SortOrder::Unicode => sort_key(a).cmp(&sort_key(b)).then_with(|| a.cmp(b)),
+    }
+}
+
+/// Compare field sequences (e.g. `[artist, album, track]`) lexicographically.
+/// Under `Unicode`, all fields are compared by key before any byte-order
+/// tie-break, so accent or case differences in the artist never outrank the
+/// album.
+pub fn compare_fields(a: &[&str], b: &[&str], order: SortOrder) -> Ordering {
+    let by = |cmp: &dyn Fn(&str, &str) -> Ordering| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| cmp(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    };
+    match order {
+        SortOrder::Bytes => by(&|x, y| x.cmp(y)),
+        SortOrder::Unicode => {
+            by(&|x, y| sort_key(x).cmp(&sort_key(y))).then_with(|| by(&|x, y| x.cmp(y)))
+        }
+    }
+}
@@ -1,24 +1,51 @@
 use anyhow::{Context, Result, bail};
 use serde::Deserialize;
+use std::fmt;
 use std::io::{self, IsTerminal, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::bandcamp::normalize_identity_cookie;
+use crate::models::{FailOn, QobuzQuality, Service, SizeMismatchPolicy, UnknownTrackNumbers};
+use crate::network::{TlsVersion, load_ca_bundle, parse_proxy};
+use crate::path::{Naming, UnicodeForm};
+use crate::retry::RequestPolicy;
+use crate::secrets::{self, Secret, keyring_entry};
+use crate::session::{QobuzSession, SessionFile, write_private};
+use crate::transcode::TranscodeTarget;
 
 // --- Public config types ---
 
 pub struct Config {
     pub qobuz: QobuzState,
     pub bandcamp: Option<BandcampConfig>,
+    pub paths: PathsConfig,
+    pub sync: SyncConfig,
+    pub log: LogConfig,
+    pub notifications: NotificationsConfig,
+    pub hooks: HooksConfig,
+    pub integrations: IntegrationsConfig,
+    pub requests: RequestsConfig,
+    pub network: NetworkConfig,
+    /// `keep_archives` under [bandcamp]: keep each downloaded ZIP.
+    pub keep_bandcamp_archives: bool,
+    /// `quality` under [qobuz]: the format to ask Qobuz for first.
+    pub qobuz_quality: Option<QobuzQuality>,
+    pub transcode: TranscodeConfig,
 }
 
 pub enum QobuzState {
     /// Username and password both resolved — ready to sync.
     Ready(QobuzConfig),
+    /// No password, but `qoget login` saved a session — ready to sync.
+    Saved(QobuzSession),
     /// Username found but password missing — interactive prompt can complete it.
     Incomplete,
     /// No username found — Qobuz not configured.
     NotConfigured,
 }
 
+#[derive(Clone)]
 pub struct QobuzConfig {
     pub username: String,
     pub password: String,
@@ -26,8 +53,187 @@ pub struct QobuzConfig {
     pub app_secret: Option<String>,
 }
 
+/// How to authenticate with Qobuz: log in with a password, or reuse a
+/// saved session's token.
+#[derive(Clone)]
+pub enum QobuzLogin {
+    Password(QobuzConfig),
+    Saved(QobuzSession),
+}
+
+#[derive(Clone)]
 pub struct BandcampConfig {
     pub identity_cookie: String,
+    /// The cookie is from the session `qoget login` saved, which syncs
+    /// update when Bandcamp rotates it.
+    pub saved: bool,
+}
+
+/// How generated file and directory names are written.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PathsConfig {
+    /// Normalize names to NFC or NFD, so libraries shared between macOS and
+    /// Linux don't end up with duplicate-looking directories.
+    pub unicode_normalization: Option<UnicodeForm>,
+    /// How to name Bandcamp tracks whose file in the download has no
+    /// number [default: zip-order].
+    pub unknown_track_numbers: Option<UnknownTrackNumbers>,
+    /// Add each track's version ("2023 Remaster", "Live") to its file
+    /// name [default: false].
+    pub track_versions: Option<bool>,
+    /// Add each album's version ("Deluxe Edition") to its directory name
+    /// [default: false].
+    pub album_versions: Option<bool>,
+    /// Put each album's release year before its title in directory names,
+    /// as in `Artist/2023 - Album` [default: false].
+    pub album_years: Option<bool>,
+}
+
+impl PathsConfig {
+    /// How names are generated: the optional name parts turned on and the
+    /// Unicode normalization form.
+    pub fn naming(&self) -> Naming {
+        Naming {
+            track_versions: self.track_versions.unwrap_or(false),
+            album_versions: self.album_versions.unwrap_or(false),
+            album_years: self.album_years.unwrap_or(false),
+            unicode_form: self.unicode_normalization,
+            ..Naming::default()
+        }
+    }
+}
+
+/// Defaults for `qoget sync` that command-line flags override.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SyncConfig {
+    /// What to do with an existing file whose size doesn't fit the track.
+    pub on_size_mismatch: Option<SizeMismatchPolicy>,
+    /// Used when a command is run without a target directory. A leading
+    /// `~/` is expanded to the home directory.
+    pub target_dir: Option<PathBuf>,
+    /// Which failed downloads make the run exit non-zero.
+    pub fail_on: Option<FailOn>,
+    /// Write ReplayGain tags into newly downloaded albums (needs ffmpeg).
+    pub replaygain: Option<bool>,
+    /// After each sync, add the new tracks to `Recently Added.m3u8` in the
+    /// target directory, which keeps this many of the newest.
+    pub recently_added: Option<usize>,
+}
+
+/// Where a copy of the log goes, besides the console.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogConfig {
+    /// File the full log is appended to, as `--log-file` would. A leading
+    /// `~/` is expanded to the home directory.
+    pub file: Option<PathBuf>,
+}
+
+/// Where to report finished syncs.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationsConfig {
+    /// URL a JSON summary of each sync run is POSTed to.
+    pub webhook_url: Option<String>,
+}
+
+/// Commands run around each sync, through the shell.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HooksConfig {
+    /// Run before a sync touches the target directory, such as to mount
+    /// it; if it fails, the sync doesn't run.
+    pub pre_sync: Option<String>,
+    /// Run after a sync, whatever its outcome, with the run's JSON summary
+    /// on standard input; such as to unmount or snapshot the target.
+    pub post_sync: Option<String>,
+}
+
+/// Other software told about what a sync downloaded: media servers asked to
+/// rescan, and beets.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IntegrationsConfig {
+    pub jellyfin: Option<JellyfinConfig>,
+    pub plex: Option<PlexConfig>,
+    #[serde(default)]
+    pub beets: BeetsConfig,
+    #[serde(default)]
+    pub musicbrainz: MusicBrainzConfig,
+}
+
+/// Importing new albums into a beets library.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BeetsConfig {
+    /// Run `beet import -A` on each album directory a sync creates.
+    #[serde(default)]
+    pub auto_import: bool,
+}
+
+/// Looking up new albums on MusicBrainz and tagging them with its IDs.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MusicBrainzConfig {
+    /// Tag each album a sync downloads with the MusicBrainz IDs and release
+    /// date of the release it matches.
+    #[serde(default)]
+    pub enrich: bool,
+    /// Email address or URL sent in the User-Agent, as MusicBrainz asks of
+    /// the clients it serves.
+    pub contact: Option<String>,
+}
+
+/// A Jellyfin (or Emby) server, reached with an API key made under
+/// Dashboard > API Keys.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JellyfinConfig {
+    /// Base URL, such as `http://nas:8096`.
+    pub url: String,
+    pub api_key: String,
+}
+
+/// A Plex Media Server, reached with an `X-Plex-Token`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlexConfig {
+    /// Base URL, such as `http://nas:32400`.
+    pub url: String,
+    pub token: String,
+}
+
+/// What downloads are converted to once they arrive.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TranscodeConfig {
+    /// Codec and bitrate to transcode every download to with ffmpeg, such
+    /// as `opus-128`; downloads are kept as they are when unset.
+    pub target: Option<TranscodeTarget>,
+}
+
+/// How qoget reaches the internet.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NetworkConfig {
+    /// Proxy for every request (`http://`, `https://`, `socks5://` or
+    /// `socks5h://`), overriding `HTTPS_PROXY`/`ALL_PROXY`.
+    pub proxy: Option<String>,
+    /// PEM file of extra CA certificates to trust, such as a
+    /// TLS-intercepting proxy's. A leading `~/` is expanded to the home
+    /// directory.
+    pub ca_bundle: Option<PathBuf>,
+    /// Skip TLS certificate checks entirely. Only for debugging.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// Refuse servers offering an older TLS version ("1.0", "1.1" or "1.2").
+    pub min_tls_version: Option<TlsVersion>,
+}
+
+/// How each service's API client paces requests and retries failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestsConfig {
+    pub qobuz: RequestPolicy,
+    pub bandcamp: RequestPolicy,
+}
+
+impl Default for RequestsConfig {
+    fn default() -> Self {
+        Self {
+            qobuz: RequestPolicy::default_for(Service::Qobuz),
+            bandcamp: RequestPolicy::default_for(Service::Bandcamp),
+        }
+    }
 }
 
 // --- TOML deserialization types ---
@@ -37,6 +243,22 @@ struct FileConfig {
     // New format: [qobuz] and [bandcamp] sections
     qobuz: Option<QobuzFileSection>,
     bandcamp: Option<BandcampFileSection>,
+    #[serde(default)]
+    paths: PathsConfig,
+    #[serde(default)]
+    sync: SyncConfig,
+    #[serde(default)]
+    log: LogConfig,
+    #[serde(default)]
+    notifications: NotificationsConfig,
+    #[serde(default)]
+    hooks: HooksConfig,
+    #[serde(default)]
+    integrations: IntegrationsConfig,
+    #[serde(default)]
+    network: NetworkConfig,
+    #[serde(default)]
+    transcode: TranscodeConfig,
     // Old format: bare keys (backward compat for Qobuz)
     username: Option<String>,
     password: Option<String>,
@@ -50,11 +272,28 @@ struct QobuzFileSection {
     password: Option<String>,
     app_id: Option<String>,
     app_secret: Option<String>,
+    quality: Option<QobuzQuality>,
+    #[serde(flatten)]
+    requests: RequestSettings,
 }
 
 #[derive(Deserialize)]
 struct BandcampFileSection {
     identity_cookie: Option<String>,
+    keep_archives: Option<bool>,
+    #[serde(flatten)]
+    requests: RequestSettings,
+}
+
+/// Overrides of a service's `RequestPolicy`, in either section.
+#[derive(Deserialize, Default)]
+struct RequestSettings {
+    requests_per_second: Option<f64>,
+    max_retries: Option<u32>,
+    backoff_secs: Option<f64>,
+    rate_limit_backoff_secs: Option<f64>,
+    retry_statuses: Option<Vec<u16>>,
+    jitter: Option<f64>,
 }
 
 // --- File helpers ---
@@ -96,6 +335,42 @@ fn bandcamp_identity_from_file(fc: &FileConfig) -> Option<String> {
         .filter(|s| !s.is_empty())
 }
 
+/// Read and parse the config file, replacing `keyring:` secrets with what
+/// the keyring holds. Secrets an environment variable overrides are not
+/// looked up.
+fn read_file_config() -> Result<FileConfig> {
+    let file_contents = std::fs::read_to_string(config_path()).unwrap_or_default();
+    let mut fc: FileConfig =
+        toml::from_str(&file_contents).context("Failed to parse config file")?;
+
+    let env_set = |var| std::env::var(var).is_ok_and(|s| !s.is_empty());
+    if !env_set("QOBUZ_PASSWORD") {
+        let password = match fc.qobuz.as_mut().and_then(|q| q.password.as_mut()) {
+            Some(password) => Some(password),
+            None => fc.password.as_mut(),
+        };
+        if let Some(password) = password {
+            resolve_keyring_value(password, Secret::QobuzPassword)?;
+        }
+    }
+    if !env_set("BANDCAMP_IDENTITY")
+        && let Some(cookie) = fc
+            .bandcamp
+            .as_mut()
+            .and_then(|b| b.identity_cookie.as_mut())
+    {
+        resolve_keyring_value(cookie, Secret::BandcampIdentity)?;
+    }
+    Ok(fc)
+}
+
+fn resolve_keyring_value(value: &mut String, secret: Secret) -> Result<()> {
+    if let Some(name) = keyring_entry(value, secret).map(str::to_owned) {
+        *value = secrets::get(&name)?;
+    }
+    Ok(())
+}
+
 // --- Resolution (file only, no env vars) ---
 
 fn resolve_qobuz_from_file(fc: &FileConfig) -> QobuzState {
@@ -116,9 +391,104 @@ fn resolve_qobuz_from_file(fc: &FileConfig) -> QobuzState {
 fn resolve_bandcamp_from_file(fc: &FileConfig) -> Option<BandcampConfig> {
     Some(BandcampConfig {
         identity_cookie: bandcamp_identity_from_file(fc)?,
+        saved: false,
     })
 }
 
+fn keep_bandcamp_archives(fc: &FileConfig) -> bool {
+    fc.bandcamp
+        .as_ref()
+        .and_then(|b| b.keep_archives)
+        .unwrap_or(false)
+}
+
+fn qobuz_quality(fc: &FileConfig) -> Option<QobuzQuality> {
+    fc.qobuz.as_ref().and_then(|q| q.quality)
+}
+
+fn resolve_requests(fc: &FileConfig) -> Result<RequestsConfig> {
+    let qobuz = fc.qobuz.as_ref().map(|q| &q.requests);
+    let bandcamp = fc.bandcamp.as_ref().map(|b| &b.requests);
+    Ok(RequestsConfig {
+        qobuz: resolve_request_policy(qobuz, Service::Qobuz)
+            .context("invalid request settings in [qobuz]")?,
+        bandcamp: resolve_request_policy(bandcamp, Service::Bandcamp)
+            .context("invalid request settings in [bandcamp]")?,
+    })
+}
+
+fn resolve_request_policy(
+    settings: Option<&RequestSettings>,
+    service: Service,
+) -> Result<RequestPolicy> {
+    let mut policy = RequestPolicy::default_for(service);
+    let Some(settings) = settings else {
+        return Ok(policy);
+    };
+    let secs = |name: &str, value: f64| -> Result<Duration> {
+        Duration::try_from_secs_f64(value)
+            .map_err(|_| anyhow::anyhow!("{name} must be a number of seconds, got {value}"))
+    };
+    if let Some(rps) = settings.requests_per_second {
+        if !(rps.is_finite() && rps > 0.0) {
+            bail!("requests_per_second must be above 0, got {rps}");
+        }
+        policy.requests_per_second = Some(rps);
+    }
+    if let Some(retries) = settings.max_retries {
+        policy.max_retries = retries;
+    }
+    if let Some(backoff) = settings.backoff_secs {
+        policy.backoff = secs("backoff_secs", backoff)?;
+    }
+    if let Some(backoff) = settings.rate_limit_backoff_secs {
+        policy.rate_limit_backoff = Some(secs("rate_limit_backoff_secs", backoff)?);
+    }
+    if let Some(statuses) = &settings.retry_statuses {
+        if let Some(status) = statuses.iter().find(|s| !(400..=599).contains(*s)) {
+            bail!("retry_statuses must be HTTP error statuses (400-599), got {status}");
+        }
+        policy.retry_statuses = statuses.clone();
+    }
+    if let Some(jitter) = settings.jitter {
+        if !(0.0..=1.0).contains(&jitter) {
+            bail!("jitter must be from 0 to 1, got {jitter}");
+        }
+        policy.jitter = jitter;
+    }
+    Ok(policy)
+}
+
+fn resolve_sync(sync: SyncConfig) -> SyncConfig {
+    SyncConfig {
+        target_dir: sync.target_dir.map(|dir| expand_home(&dir)),
+        ..sync
+    }
+}
+
+fn resolve_log(log: LogConfig) -> LogConfig {
+    LogConfig {
+        file: log.file.map(|file| expand_home(&file)),
+    }
+}
+
+fn resolve_network(network: NetworkConfig) -> NetworkConfig {
+    NetworkConfig {
+        ca_bundle: network.ca_bundle.map(|path| expand_home(&path)),
+        ..network
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(rest)
+        }
+        Err(_) => path.to_path_buf(),
+    }
+}
+
 // --- Resolution (with env vars) ---
 
 fn resolve_qobuz(fc: &FileConfig) -> QobuzState {
@@ -149,19 +519,51 @@ fn resolve_bandcamp(fc: &FileConfig) -> Option<BandcampConfig> {
         .ok()
         .filter(|s| !s.is_empty())
         .or_else(|| bandcamp_identity_from_file(fc))?;
-    Some(BandcampConfig { identity_cookie })
+    Some(BandcampConfig {
+        identity_cookie,
+        saved: false,
+    })
 }
 
 // --- Public API ---
 
-fn config_path() -> PathBuf {
+/// Directory holding the config file and saved sessions
+/// (`$XDG_CONFIG_HOME/qoget`).
+pub fn config_dir() -> PathBuf {
     let config_dir = std::env::var_os("XDG_CONFIG_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|| {
             let home = std::env::var_os("HOME").unwrap_or_default();
             PathBuf::from(home).join(".config")
         });
-    config_dir.join("qoget").join("config.toml")
+    config_dir.join("qoget")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Directory for cached, regenerable data (`$XDG_CACHE_HOME/qoget`).
+pub fn cache_dir() -> PathBuf {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".cache")
+        });
+    cache_dir.join("qoget")
+}
+
+/// Directory for state kept between runs, such as the last run's failures
+/// (`$XDG_STATE_HOME/qoget`).
+pub fn state_dir() -> PathBuf {
+    let state_dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".local").join("state")
+        });
+    state_dir.join("qoget")
 }
 
 /// Parse config from TOML content only (no env vars, no prompts).
@@ -171,6 +573,17 @@ pub fn parse_toml_config(content: &str) -> Result<Config> {
     Ok(Config {
         qobuz: resolve_qobuz_from_file(&fc),
         bandcamp: resolve_bandcamp_from_file(&fc),
+        requests: resolve_requests(&fc)?,
+        keep_bandcamp_archives: keep_bandcamp_archives(&fc),
+        qobuz_quality: qobuz_quality(&fc),
+        paths: fc.paths,
+        sync: resolve_sync(fc.sync),
+        log: resolve_log(fc.log),
+        notifications: fc.notifications,
+        hooks: fc.hooks,
+        integrations: fc.integrations,
+        network: resolve_network(fc.network),
+        transcode: fc.transcode,
     })
 }
 
@@ -182,6 +595,15 @@ impl QobuzState {
         }
     }
 
+    /// Credentials usable without prompting: a password or a saved session.
+    pub fn login(self) -> Option<QobuzLogin> {
+        match self {
+            QobuzState::Ready(cfg) => Some(QobuzLogin::Password(cfg)),
+            QobuzState::Saved(session) => Some(QobuzLogin::Saved(session)),
+            _ => None,
+        }
+    }
+
     pub fn is_configured(&self) -> bool {
         !matches!(self, QobuzState::NotConfigured)
     }
@@ -193,24 +615,71 @@ impl QobuzState {
 /// 1. Environment variables (QOBUZ_USERNAME, QOBUZ_PASSWORD, BANDCAMP_IDENTITY)
 /// 2. Config file [service] section
 /// 3. Config file bare keys (Qobuz only, backward compat)
+/// 4. Sessions saved by `qoget login` (only when no password/cookie is set)
+///
+/// A password or cookie written as `keyring:` (or `keyring:NAME`) is read
+/// from the OS keyring; a missing entry is an error.
 ///
 /// Returns whatever is fully resolved. Interactive prompts are NOT done here;
 /// callers that need Qobuz can call `prompt_qobuz_credentials()` separately.
 pub fn load_config() -> Result<Config> {
-    let file_contents = std::fs::read_to_string(config_path()).unwrap_or_default();
-    let fc: FileConfig = toml::from_str(&file_contents).context("Failed to parse config file")?;
+    let fc = read_file_config()?;
+    let sessions = SessionFile::load(&config_dir())?;
+
+    let qobuz = match (resolve_qobuz(&fc), sessions.qobuz) {
+        (QobuzState::Incomplete | QobuzState::NotConfigured, Some(session)) => {
+            QobuzState::Saved(session)
+        }
+        (state, _) => state,
+    };
+    let bandcamp = resolve_bandcamp(&fc).or_else(|| {
+        sessions.bandcamp.map(|s| BandcampConfig {
+            identity_cookie: s.identity_cookie,
+            saved: true,
+        })
+    });
 
     Ok(Config {
-        qobuz: resolve_qobuz(&fc),
-        bandcamp: resolve_bandcamp(&fc),
+        qobuz,
+        bandcamp,
+        requests: resolve_requests(&fc)?,
+        keep_bandcamp_archives: keep_bandcamp_archives(&fc),
+        qobuz_quality: qobuz_quality(&fc),
+        paths: fc.paths,
+        sync: resolve_sync(fc.sync),
+        log: resolve_log(fc.log),
+        notifications: fc.notifications,
+        hooks: fc.hooks,
+        integrations: fc.integrations,
+        network: resolve_network(fc.network),
+        transcode: fc.transcode,
     })
 }
 
+/// The `[log] file` setting, read before any command runs so logging can
+/// start first. Unlike `load_config`, nothing is looked up in the keyring;
+/// an unreadable config file yields `None` and is reported by the command.
+pub fn configured_log_file() -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(config_path()).ok()?;
+    let fc: FileConfig = toml::from_str(&contents).ok()?;
+    resolve_log(fc.log).file
+}
+
+/// The `[network]` settings alone, for the warning at startup and for
+/// `login`, which needs no other config. Like `configured_log_file`, an
+/// unreadable config file yields the defaults.
+pub fn configured_network() -> NetworkConfig {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|contents| toml::from_str::<FileConfig>(&contents).ok())
+        .map(|fc| resolve_network(fc.network))
+        .unwrap_or_default()
+}
+
 /// Interactively prompt for missing Qobuz credentials, reusing any partial
 /// values already resolved from env/file.
 pub fn prompt_qobuz_credentials() -> Result<QobuzConfig> {
-    let file_contents = std::fs::read_to_string(config_path()).unwrap_or_default();
-    let fc: FileConfig = toml::from_str(&file_contents).context("Failed to parse config file")?;
+    let fc = read_file_config()?;
 
     let username = std::env::var("QOBUZ_USERNAME")
         .ok()
@@ -238,8 +707,685 @@ pub fn prompt_qobuz_credentials() -> Result<QobuzConfig> {
     })
 }
 
+/// The Bandcamp identity cookie from env/file, or prompted for (hidden).
+pub fn prompt_bandcamp_identity() -> Result<String> {
+    let fc = read_file_config()?;
+    if let Some(cfg) = resolve_bandcamp(&fc) {
+        return normalize_identity_cookie(&cfg.identity_cookie);
+    }
+
+    if !io::stdin().is_terminal() {
+        bail!(
+            "No identity cookie provided. Set BANDCAMP_IDENTITY or add identity_cookie to \
+             [bandcamp] in ~/.config/qoget/config.toml"
+        );
+    }
+    eprintln!(
+        "Log in to bandcamp.com, open browser dev tools (F12), go to \
+         Application > Cookies > bandcamp.com, and copy the 'identity' cookie value."
+    );
+    eprint!("Bandcamp identity cookie: ");
+    io::stderr().flush()?;
+    let cookie = rpassword::read_password().context("Failed to read cookie")?;
+    if cookie.trim().is_empty() {
+        bail!("Identity cookie cannot be empty");
+    }
+    normalize_identity_cookie(&cookie)
+}
+
+// --- config init ---
+
+/// Answers collected by `qoget config init`; `None` leaves a setting out.
+#[derive(Debug, Default)]
+pub struct InitAnswers {
+    pub qobuz_username: Option<String>,
+    pub qobuz_password: Option<String>,
+    pub bandcamp_identity: Option<String>,
+    pub target_dir: Option<String>,
+}
+
+/// Render answers as a config file. Sections with nothing set are omitted.
+pub fn render_config(answers: &InitAnswers) -> String {
+    let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+    let mut out =
+        String::from("# qoget configuration. See `qoget sync --help` for all settings.\n");
+
+    if answers.qobuz_username.is_some() || answers.qobuz_password.is_some() {
+        out.push_str("\n[qobuz]\n");
+        if let Some(ref username) = answers.qobuz_username {
+            out.push_str(&format!("username = {}\n", quote(username)));
+        }
+        if let Some(ref password) = answers.qobuz_password {
+            out.push_str(&format!("password = {}\n", quote(password)));
+        }
+    }
+    if let Some(ref cookie) = answers.bandcamp_identity {
+        out.push_str(&format!(
+            "\n[bandcamp]\nidentity_cookie = {}\n",
+            quote(cookie)
+        ));
+    }
+    if let Some(ref dir) = answers.target_dir {
+        out.push_str(&format!("\n[sync]\ntarget_dir = {}\n", quote(dir)));
+    }
+    out
+}
+
+/// Ask for each setting in turn; an empty answer skips it.
+pub fn prompt_init_answers() -> Result<InitAnswers> {
+    if !io::stdin().is_terminal() {
+        bail!("`qoget config init` is interactive; run it from a terminal");
+    }
+    let mut answers = InitAnswers::default();
+
+    eprintln!("Qobuz (leave empty to skip)");
+    answers.qobuz_username = prompt_optional("  Email: ")?;
+    if answers.qobuz_username.is_some() {
+        eprintln!("  Leave the password empty to be asked at each sync, or use `qoget login`.");
+        answers.qobuz_password = prompt_optional_hidden("  Password: ")?;
+    }
+
+    eprintln!("\nBandcamp (leave empty to skip)");
+    eprintln!(
+        "  Log in to bandcamp.com, open browser dev tools (F12), go to \
+         Application > Cookies > bandcamp.com, and copy the 'identity' cookie value."
+    );
+    answers.bandcamp_identity = prompt_optional_hidden("  Identity cookie: ")?
+        .map(|cookie| normalize_identity_cookie(&cookie))
+        .transpose()?;
+
+    eprintln!();
+    answers.target_dir = prompt_optional("Default target directory (leave empty to skip): ")?;
+    Ok(answers)
+}
+
+/// Write a new config file readable only by its owner. Refuses to replace an
+/// existing one unless `force` is set.
+pub fn write_config_file(contents: &str, force: bool) -> Result<PathBuf> {
+    let path = config_path();
+    if path.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        );
+    }
+    let dir = config_dir();
+    std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let tmp = path.with_extension("toml.tmp");
+    write_private(&tmp, contents.as_bytes())
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path)
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+    Ok(path)
+}
+
+// --- config show / validate ---
+
+/// Where an effective setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Env(&'static str),
+    File,
+    /// A `keyring:` value in the config file.
+    Keyring,
+    Session,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Env(var) => write!(f, "env {var}"),
+            Source::File => f.write_str("config file"),
+            Source::Keyring => f.write_str("keyring"),
+            Source::Session => f.write_str("saved session"),
+        }
+    }
+}
+
+/// One effective setting. Secrets are already redacted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub key: &'static str,
+    pub value: String,
+    pub source: Source,
+}
+
+/// What `qoget config show` and `qoget config validate` report.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub settings: Vec<Setting>,
+    /// One status line per service, e.g. `("qobuz", "ready (password login)")`.
+    pub services: Vec<(&'static str, String)>,
+    /// Anything that would stop an unattended sync or is silently ignored.
+    pub problems: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+pub const REDACTED: &str = "<redacted>";
+
+/// Settings the config file understands, as dotted keys.
+const KNOWN_KEYS: &[&str] = &[
+    "username",
+    "password",
+    "app_id",
+    "app_secret",
+    "qobuz.username",
+    "qobuz.password",
+    "qobuz.app_id",
+    "qobuz.app_secret",
+    "qobuz.quality",
+    "qobuz.requests_per_second",
+    "qobuz.max_retries",
+    "qobuz.backoff_secs",
+    "qobuz.rate_limit_backoff_secs",
+    "qobuz.retry_statuses",
+    "qobuz.jitter",
+    "bandcamp.identity_cookie",
+    "bandcamp.keep_archives",
+    "bandcamp.requests_per_second",
+    "bandcamp.max_retries",
+    "bandcamp.backoff_secs",
+    "bandcamp.rate_limit_backoff_secs",
+    "bandcamp.retry_statuses",
+    "bandcamp.jitter",
+    "paths.unicode_normalization",
+    "paths.unknown_track_numbers",
+    "paths.track_versions",
+    "paths.album_versions",
+    "paths.album_years",
+    "sync.on_size_mismatch",
+    "sync.target_dir",
+    "sync.fail_on",
+    "sync.replaygain",
+    "sync.recently_added",
+    "log.file",
+    "notifications.webhook_url",
+    "hooks.pre_sync",
+    "hooks.post_sync",
+    "integrations.jellyfin.url",
+    "integrations.jellyfin.api_key",
+    "integrations.plex.url",
+    "integrations.plex.token",
+    "integrations.beets.auto_import",
+    "integrations.musicbrainz.enrich",
+    "integrations.musicbrainz.contact",
+    "transcode.target",
+    "network.proxy",
+    "network.ca_bundle",
+    "network.accept_invalid_certs",
+    "network.min_tls_version",
+];
+
+/// Work out the effective settings from the config file's contents,
+/// environment variables (looked up through `env`) and saved sessions, with
+/// the same precedence as `load_config`. Nothing is prompted for or fetched.
+pub fn inspect_config(
+    file_contents: &str,
+    env: impl Fn(&str) -> Option<String>,
+    sessions: &SessionFile,
+) -> Result<ConfigReport> {
+    let fc: FileConfig = toml::from_str(file_contents).context("Failed to parse config file")?;
+    let table: toml::Table =
+        toml::from_str(file_contents).context("Failed to parse config file")?;
+    let mut report = ConfigReport::default();
+
+    for key in unknown_keys(&table) {
+        report
+            .problems
+            .push(format!("unknown setting `{key}` is ignored"));
+    }
+
+    let from_env = |var: &'static str| {
+        env(var)
+            .filter(|s| !s.is_empty())
+            .map(|v| (v, Source::Env(var)))
+    };
+    let from_file = |v: Option<String>| v.map(|v| (v, Source::File));
+    let mut set = |key, value: String, source| {
+        report.settings.push(Setting { key, value, source });
+    };
+
+    // Qobuz
+    let username = from_env("QOBUZ_USERNAME").or_else(|| from_file(qobuz_username_from_file(&fc)));
+    let password = from_env("QOBUZ_PASSWORD").or_else(|| from_file(qobuz_password_from_file(&fc)));
+    let password = password.map(|p| from_keyring(p, Secret::QobuzPassword, &mut report.problems));
+    let app_id = from_file(qobuz_app_id_from_file(&fc));
+    let app_secret = from_file(qobuz_app_secret_from_file(&fc));
+    if let Some((ref v, source)) = username {
+        set("qobuz.username", v.clone(), source);
+    }
+    if let Some((_, source)) = password {
+        set("qobuz.password", REDACTED.to_string(), source);
+    }
+    if let Some((ref v, source)) = app_id {
+        set("qobuz.app_id", v.clone(), source);
+    }
+    if let Some((_, source)) = app_secret {
+        set("qobuz.app_secret", REDACTED.to_string(), source);
+    }
+    if let Some(quality) = qobuz_quality(&fc) {
+        set("qobuz.quality", quality.to_string(), Source::File);
+    }
+    let qobuz_session = sessions
+        .qobuz
+        .as_ref()
+        .filter(|_| password.is_none() || username.is_none());
+    if let Some(session) = qobuz_session {
+        set(
+            "qobuz.user_id",
+            session.user_id.to_string(),
+            Source::Session,
+        );
+        set(
+            "qobuz.user_auth_token",
+            REDACTED.to_string(),
+            Source::Session,
+        );
+    }
+
+    // Bandcamp
+    let cookie =
+        from_env("BANDCAMP_IDENTITY").or_else(|| from_file(bandcamp_identity_from_file(&fc)));
+    let cookie = cookie.map(|c| from_keyring(c, Secret::BandcampIdentity, &mut report.problems));
+    if let Some((_, source)) = cookie {
+        set("bandcamp.identity_cookie", REDACTED.to_string(), source);
+    }
+    let bandcamp_session = sessions.bandcamp.as_ref().filter(|_| cookie.is_none());
+    if let Some(session) = bandcamp_session {
+        set(
+            "bandcamp.fan_id",
+            session.fan_id.to_string(),
+            Source::Session,
+        );
+        set(
+            "bandcamp.identity_cookie",
+            REDACTED.to_string(),
+            Source::Session,
+        );
+    }
+
+    if let Some(keep) = fc.bandcamp.as_ref().and_then(|b| b.keep_archives) {
+        set("bandcamp.keep_archives", keep.to_string(), Source::File);
+    }
+
+    // Request pacing and retries
+    let sections = [
+        (
+            fc.qobuz.as_ref().map(|q| &q.requests),
+            Service::Qobuz,
+            [
+                "qobuz.requests_per_second",
+                "qobuz.max_retries",
+                "qobuz.backoff_secs",
+                "qobuz.rate_limit_backoff_secs",
+                "qobuz.jitter",
+                "qobuz.retry_statuses",
+            ],
+        ),
+        (
+            fc.bandcamp.as_ref().map(|b| &b.requests),
+            Service::Bandcamp,
+            [
+                "bandcamp.requests_per_second",
+                "bandcamp.max_retries",
+                "bandcamp.backoff_secs",
+                "bandcamp.rate_limit_backoff_secs",
+                "bandcamp.jitter",
+                "bandcamp.retry_statuses",
+            ],
+        ),
+    ];
+    for (settings, service, keys) in sections {
+        let Some(settings) = settings else { continue };
+        let values = [
+            settings.requests_per_second,
+            settings.max_retries.map(f64::from),
+            settings.backoff_secs,
+            settings.rate_limit_backoff_secs,
+            settings.jitter,
+        ];
+        for (key, value) in keys.into_iter().zip(values) {
+            if let Some(value) = value {
+                set(key, value.to_string(), Source::File);
+            }
+        }
+        if let Some(statuses) = &settings.retry_statuses {
+            let statuses: Vec<String> = statuses.iter().map(u16::to_string).collect();
+            set(keys[5], statuses.join(", "), Source::File);
+        }
+        if let Err(e) = resolve_request_policy(Some(settings), service) {
+            report
+                .problems
+                .push(format!("[{}] {e:#}", service.to_string().to_lowercase()));
+        }
+    }
+
+    // Paths and sync defaults
+    if let Some(form) = fc.paths.unicode_normalization {
+        set(
+            "paths.unicode_normalization",
+            format!("{form:?}").to_lowercase(),
+            Source::File,
+        );
+    }
+    if let Some(policy) = fc.paths.unknown_track_numbers {
+        set(
+            "paths.unknown_track_numbers",
+            policy.to_string(),
+            Source::File,
+        );
+    }
+    if let Some(on) = fc.paths.track_versions {
+        set("paths.track_versions", on.to_string(), Source::File);
+    }
+    if let Some(on) = fc.paths.album_versions {
+        set("paths.album_versions", on.to_string(), Source::File);
+    }
+    if let Some(on) = fc.paths.album_years {
+        set("paths.album_years", on.to_string(), Source::File);
+    }
+    if let Some(policy) = fc.sync.on_size_mismatch {
+        set("sync.on_size_mismatch", policy.to_string(), Source::File);
+    }
+    if let Some(policy) = fc.sync.fail_on {
+        set("sync.fail_on", policy.to_string(), Source::File);
+    }
+    if let Some(replaygain) = fc.sync.replaygain {
+        set("sync.replaygain", replaygain.to_string(), Source::File);
+    }
+    if let Some(cap) = fc.sync.recently_added {
+        set("sync.recently_added", cap.to_string(), Source::File);
+    }
+    let target_dir = resolve_sync(fc.sync).target_dir;
+    if let Some(ref dir) = target_dir {
+        set("sync.target_dir", dir.display().to_string(), Source::File);
+    }
+    if let Some(file) = resolve_log(fc.log).file {
+        set("log.file", file.display().to_string(), Source::File);
+    }
+    if let Some(ref url) = fc.notifications.webhook_url {
+        set("notifications.webhook_url", redact_url(url), Source::File);
+    }
+    if let Some(ref command) = fc.hooks.pre_sync {
+        set("hooks.pre_sync", command.clone(), Source::File);
+    }
+    if let Some(ref command) = fc.hooks.post_sync {
+        set("hooks.post_sync", command.clone(), Source::File);
+    }
+    if let Some(ref jellyfin) = fc.integrations.jellyfin {
+        set(
+            "integrations.jellyfin.url",
+            jellyfin.url.clone(),
+            Source::File,
+        );
+        set(
+            "integrations.jellyfin.api_key",
+            REDACTED.to_string(),
+            Source::File,
+        );
+    }
+    if let Some(ref plex) = fc.integrations.plex {
+        set("integrations.plex.url", plex.url.clone(), Source::File);
+        set(
+            "integrations.plex.token",
+            REDACTED.to_string(),
+            Source::File,
+        );
+    }
+    if fc.integrations.musicbrainz.enrich {
+        set(
+            "integrations.musicbrainz.enrich",
+            "true".to_string(),
+            Source::File,
+        );
+    }
+    if let Some(ref contact) = fc.integrations.musicbrainz.contact {
+        set(
+            "integrations.musicbrainz.contact",
+            contact.clone(),
+            Source::File,
+        );
+    }
+    if fc.integrations.beets.auto_import {
+        set(
+            "integrations.beets.auto_import",
+            "true".to_string(),
+            Source::File,
+        );
+    }
+    if let Some(target) = fc.transcode.target {
+        set("transcode.target", target.to_string(), Source::File);
+    }
+    let network = resolve_network(fc.network);
+    if let Some(ref proxy) = network.proxy {
+        set("network.proxy", redact_url(proxy), Source::File);
+    }
+    if let Some(ref path) = network.ca_bundle {
+        set(
+            "network.ca_bundle",
+            path.display().to_string(),
+            Source::File,
+        );
+    }
+    if network.accept_invalid_certs {
+        set(
+            "network.accept_invalid_certs",
+            "true".to_string(),
+            Source::File,
+        );
+    }
+    if let Some(version) = network.min_tls_version {
+        set(
+            "network.min_tls_version",
+            version.as_str().to_string(),
+            Source::File,
+        );
+    }
+
+    let problems = &mut report.problems;
+    if username.is_none() && password.is_some() {
+        problems.push("Qobuz password is set without a username, so it is ignored".to_string());
+    }
+    if app_id.is_some() != app_secret.is_some() {
+        problems.push(
+            "qobuz.app_id and qobuz.app_secret are only used together; set both or neither"
+                .to_string(),
+        );
+    }
+    // A value still reading `keyring:` is one the keyring lookup failed for.
+    let unresolved = |value: &Option<(String, Source)>, secret| {
+        value
+            .as_ref()
+            .is_some_and(|(v, _)| keyring_entry(v, secret).is_some())
+    };
+    let qobuz = match (&username, &password, qobuz_session) {
+        (Some(_), Some(_), _) if unresolved(&password, Secret::QobuzPassword) => {
+            "password not in keyring".to_string()
+        }
+        (Some(_), Some(_), _) => "ready (password login)".to_string(),
+        (_, _, Some(session)) => format!("ready (saved session for user {})", session.user_id),
+        (Some(_), None, None) => {
+            problems.push(
+                "Qobuz username is set but there is no password or saved session; sync will \
+                 prompt for one. Set QOBUZ_PASSWORD, add password to [qobuz], or run \
+                 `qoget login qobuz`"
+                    .to_string(),
+            );
+            "incomplete: no password".to_string()
+        }
+        (None, _, None) => "not configured".to_string(),
+    };
+
+    let bandcamp = match (&cookie, bandcamp_session) {
+        _ if unresolved(&cookie, Secret::BandcampIdentity) => {
+            "identity cookie not in keyring".to_string()
+        }
+        (Some((raw, source)), _) => match normalize_identity_cookie(raw) {
+            Ok(_) => "ready (identity cookie)".to_string(),
+            Err(e) => {
+                problems.push(format!("Bandcamp identity cookie ({source}): {e:#}"));
+                "invalid identity cookie".to_string()
+            }
+        },
+        (None, Some(session)) => format!("ready (saved session for fan {})", session.fan_id),
+        (None, None) => "not configured".to_string(),
+    };
+
+    if username.is_none()
+        && qobuz_session.is_none()
+        && cookie.is_none()
+        && bandcamp_session.is_none()
+    {
+        problems
+            .push("no service is configured; run `qoget config init` or `qoget login`".to_string());
+    }
+    if let Some(ref url) = fc.notifications.webhook_url
+        && !reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+    {
+        problems.push(format!(
+            "notifications.webhook_url `{}` is not an http(s) URL",
+            redact_url(url)
+        ));
+    }
+    let servers = [
+        (
+            "integrations.jellyfin.url",
+            fc.integrations.jellyfin.as_ref().map(|j| &j.url),
+        ),
+        (
+            "integrations.plex.url",
+            fc.integrations.plex.as_ref().map(|p| &p.url),
+        ),
+    ];
+    for (key, url) in servers {
+        if let Some(url) = url
+            && !reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+        {
+            problems.push(format!("{key} `{url}` is not an http(s) URL"));
+        }
+    }
+    if let Some(ref proxy) = network.proxy
+        && let Err(e) = parse_proxy(proxy)
+    {
+        problems.push(format!("network.proxy: {e:#}"));
+    }
+    if let Some(ref path) = network.ca_bundle
+        && let Err(e) = load_ca_bundle(path)
+    {
+        problems.push(format!("network.ca_bundle: {e:#}"));
+    }
+    if let Some(ref dir) = target_dir
+        && dir.exists()
+        && !dir.is_dir()
+    {
+        problems.push(format!(
+            "sync.target_dir {} is not a directory",
+            dir.display()
+        ));
+    }
+
+    report.services = vec![("qobuz", qobuz), ("bandcamp", bandcamp)];
+    Ok(report)
+}
+
+/// A webhook URL with its path and query hidden: services such as Discord
+/// and Slack put the secret token there.
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(u) if u.has_host() => format!(
+            "{}://{}/{REDACTED}",
+            u.scheme(),
+            u.host_str().unwrap_or_default()
+        ),
+        _ => REDACTED.to_string(),
+    }
+}
+
+/// Swap a `keyring:` file value for the secret it names, noting a problem
+/// if the keyring doesn't have it.
+fn from_keyring(
+    (value, source): (String, Source),
+    secret: Secret,
+    problems: &mut Vec<String>,
+) -> (String, Source) {
+    let Some(name) = keyring_entry(&value, secret).filter(|_| source == Source::File) else {
+        return (value, source);
+    };
+    match secrets::get(name) {
+        Ok(secret) => (secret, Source::Keyring),
+        Err(e) => {
+            problems.push(format!("{secret}: {e:#}"));
+            (value, Source::Keyring)
+        }
+    }
+}
+
+fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    let mut unknown = Vec::new();
+    collect_unknown_keys(table, "", &mut unknown);
+    unknown
+}
+
+/// Sections nest, as `[integrations.jellyfin]` does, so keys are checked
+/// by their full dotted path.
+fn collect_unknown_keys(table: &toml::Table, prefix: &str, unknown: &mut Vec<String>) {
+    for (key, value) in table {
+        let dotted = format!("{prefix}{key}");
+        match value.as_table() {
+            Some(section) => collect_unknown_keys(section, &format!("{dotted}."), unknown),
+            None if !KNOWN_KEYS.contains(&dotted.as_str()) => unknown.push(dotted),
+            None => {}
+        }
+    }
+}
+
 // --- Interactive prompts ---
 
+/// Read a secret for `qoget config set-secret`: hidden at a terminal,
+/// otherwise one line from stdin so it can be piped in.
+pub fn read_secret(secret: Secret) -> Result<String> {
+    let value = if io::stdin().is_terminal() {
+        let label = match secret {
+            Secret::QobuzPassword => "Qobuz password: ",
+            Secret::BandcampIdentity => "Bandcamp identity cookie: ",
+        };
+        prompt_optional_hidden(label)?
+    } else {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let trimmed = input.trim();
+        (!trimmed.is_empty()).then(|| trimmed.to_string())
+    };
+    let Some(value) = value else {
+        bail!("{secret} cannot be empty");
+    };
+    match secret {
+        Secret::QobuzPassword => Ok(value),
+        Secret::BandcampIdentity => normalize_identity_cookie(&value),
+    }
+}
+
+fn prompt_optional(label: &str) -> Result<Option<String>> {
+    eprint!("{label}");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let trimmed = input.trim();
+    Ok((!trimmed.is_empty()).then(|| trimmed.to_string()))
+}
+
+fn prompt_optional_hidden(label: &str) -> Result<Option<String>> {
+    eprint!("{label}");
+    io::stderr().flush()?;
+    let input = rpassword::read_password().context("Failed to read input")?;
+    Ok((!input.is_empty()).then_some(input))
+}
+
 fn prompt_username() -> Result<String> {
     if !io::stdin().is_terminal() {
         bail!(
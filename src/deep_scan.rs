@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tracing::debug;
+
+use crate::models::{Album, Track};
+use crate::status::scan_library;
+use crate::tags;
+
+/// What a file's embedded tags say it is, as far as matching it to a
+/// purchase goes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub isrc: Option<String>,
+}
+
+impl TrackTags {
+    /// Set the field a tag named `key` (case-insensitive, in any of the
+    /// usual spellings) holds. Other tags are ignored.
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.trim_end_matches('\0').trim();
+        if value.is_empty() {
+            return;
+        }
+        let field = match key.to_ascii_uppercase().as_str() {
+            "TITLE" | "TIT2" | "©NAM" => &mut self.title,
+            "ARTIST" | "TPE1" | "©ART" => &mut self.artist,
+            "ALBUMARTIST" | "ALBUM ARTIST" | "ALBUM_ARTIST" | "TPE2" | "AART" => {
+                &mut self.album_artist
+            }
+            "ALBUM" | "TALB" | "©ALB" => &mut self.album,
+            "ISRC" | "TSRC" => &mut self.isrc,
+            "TRACKNUMBER" | "TRCK" | "TRKN" => return set_number(&mut self.track_number, value),
+            "DISCNUMBER" | "TPOS" | "DISK" => return set_number(&mut self.disc_number, value),
+            _ => return,
+        };
+        if field.is_none() {
+            *field = Some(value.to_string());
+        }
+    }
+}
+
+/// Set `number` from a track or disc number tag, "3" or "3/12", unless it
+/// is set already.
+fn set_number(number: &mut Option<u32>, value: &str) {
+    let value = value.split('/').next().unwrap_or_default().trim();
+    if number.is_none() {
+        *number = value.parse().ok();
+    }
+}
+
+/// Read the tags of an audio file, chosen by its extension: ID3v2 for MP3,
+/// Vorbis comments for FLAC and Opus, iTunes items for M4A. Only the start
+/// of the file (or, for M4A, its `moov` atom) is read.
+pub fn read_tags(path: &Path) -> Result<TrackTags> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let mut tags = TrackTags::default();
+    match ext.as_str() {
+        "m4a" => {
+            for (key, value) in tags::mp4_text_items(path)? {
+                tags.set(&key, &value);
+            }
+        }
+        "mp3" | "flac" | "opus" => {
+            let file = File::open(path).with_context(|| format!("reading {}", path.display()))?;
+            let mut file = BufReader::new(file);
+            let comments = match ext.as_str() {
+                "mp3" => id3_frames(&mut file),
+                "flac" => flac_comments(&mut file),
+                _ => opus_comments(&mut file),
+            }
+            .with_context(|| format!("reading tags of {}", path.display()))?;
+            for (key, value) in comments {
+                tags.set(&key, &value);
+            }
+        }
+        _ => bail!("{} is not a format qoget reads tags from", path.display()),
+    }
+    Ok(tags)
+}
+
+/// The text frames of an ID3v2.3 or v2.4 tag at the start of an MP3, as
+/// (frame id, text). A file without one has none.
+fn id3_frames(file: &mut impl Read) -> Result<Vec<(String, String)>> {
+    let mut header = [0; 10];
+    file.read_exact(&mut header)?;
+    if &header[..3] != b"ID3" || !(3..=4).contains(&header[3]) {
+        return Ok(Vec::new());
+    }
+    let version = header[3];
+    let mut tag = vec![0; synchsafe(&header[6..10]) as usize];
+    file.read_exact(&mut tag)?;
+
+    let mut pos = 0;
+    if header[5] & 0x40 != 0 && tag.len() >= 4 {
+        // Extended header; its size counts itself in v2.4 only.
+        let size = if version == 4 {
+            synchsafe(&tag[..4]) as usize
+        } else {
+            u32::from_be_bytes(tag[..4].try_into().expect("4 bytes")) as usize + 4
+        };
+        pos = size;
+    }
+    let mut frames = Vec::new();
+    while pos + 10 <= tag.len() && tag[pos] != 0 {
+        let id = String::from_utf8_lossy(&tag[pos..pos + 4]).into_owned();
+        let size_bytes = &tag[pos + 4..pos + 8];
+        let size = if version == 4 {
+            synchsafe(size_bytes)
+        } else {
+            u32::from_be_bytes(size_bytes.try_into().expect("4 bytes"))
+        } as usize;
+        let body = tag.get(pos + 10..pos + 10 + size).unwrap_or_default();
+        if id.starts_with('T')
+            && let Some((&encoding, text)) = body.split_first()
+        {
+            frames.push((id, id3_text(encoding, text)));
+        }
+        pos += 10 + size;
+    }
+    Ok(frames)
+}
+
+/// A 28-bit integer stored 7 bits to a byte.
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |n, &b| (n << 7) | u32::from(b & 0x7f))
+}
+
+/// Decode an ID3 text frame's body. Of several null-separated values
+/// (v2.4), the first is kept.
+fn id3_text(encoding: u8, text: &[u8]) -> String {
+    let decoded = match encoding {
+        // UTF-16 with a byte order mark, or big-endian without one
+        1 | 2 => {
+            let (big_endian, text) = match text {
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                _ => (encoding == 2, text),
+            };
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|c| {
+                    let pair = [c[0], c[1]];
+                    if big_endian {
+                        u16::from_be_bytes(pair)
+                    } else {
+                        u16::from_le_bytes(pair)
+                    }
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        3 => String::from_utf8_lossy(text).into_owned(),
+        // ISO-8859-1
+        _ => text.iter().map(|&b| char::from(b)).collect(),
+    };
+    decoded.split('\0').next().unwrap_or_default().to_string()
+}
+
+/// The Vorbis comments in a FLAC file's metadata blocks.
+fn flac_comments<R: Read + Seek>(file: &mut R) -> Result<Vec<(String, String)>> {
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        bail!("not a FLAC file");
+    }
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        if header[0] & 0x7f == 4 {
+            let mut block = vec![0; len as usize];
+            file.read_exact(&mut block)?;
+            return vorbis_comments(&block);
+        }
+        if header[0] & 0x80 != 0 {
+            return Ok(Vec::new());
+        }
+        file.seek(SeekFrom::Current(i64::from(len)))?;
+    }
+}
+
+/// The Vorbis comments in an Ogg Opus file's second packet (`OpusTags`).
+fn opus_comments(file: &mut impl Read) -> Result<Vec<(String, String)>> {
+    let mut packets: Vec<Vec<u8>> = vec![Vec::new()];
+    while packets.len() < 3 {
+        let mut header = [0; 27];
+        file.read_exact(&mut header)?;
+        if &header[..4] != b"OggS" {
+            bail!("not an Ogg file");
+        }
+        let mut lacing = vec![0; header[26] as usize];
+        file.read_exact(&mut lacing)?;
+        for len in lacing {
+            let mut segment = vec![0; len as usize];
+            file.read_exact(&mut segment)?;
+            packets.last_mut().expect("never empty").extend(segment);
+            // A segment shorter than 255 bytes ends the packet.
+            if len < 255 {
+                packets.push(Vec::new());
+            }
+        }
+    }
+    match packets[1].strip_prefix(b"OpusTags") {
+        Some(comments) => vorbis_comments(comments),
+        None => bail!("no OpusTags packet"),
+    }
+}
+
+/// A Vorbis comment block: vendor string, then `KEY=value` comments, all
+/// length-prefixed little-endian.
+fn vorbis_comments(block: &[u8]) -> Result<Vec<(String, String)>> {
+    let mut pos = 0;
+    let vendor = read_u32_le(block, &mut pos)? as usize;
+    pos += vendor;
+    let count = read_u32_le(block, &mut pos)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32_le(block, &mut pos)? as usize;
+        let comment = block
+            .get(pos..pos + len)
+            .context("truncated Vorbis comments")?;
+        pos += len;
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            comments.push((key.to_string(), value.to_string()));
+        }
+    }
+    Ok(comments)
+}
+
+fn read_u32_le(block: &[u8], pos: &mut usize) -> Result<u32> {
+    let bytes = block
+        .get(*pos..*pos + 4)
+        .context("truncated Vorbis comments")?;
+    *pos += 4;
+    Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+}
+
+/// Lowercase letters and digits only, so punctuation and spacing a tagger
+/// changed ("’" for "'", "feat." for "ft") matter as little as possible.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn normalize_isrc(isrc: &str) -> String {
+    isrc.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Audio files in the target directory, indexed by what their tags say, so
+/// purchases can be found however a tagger renamed or moved them.
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    by_isrc: HashMap<String, PathBuf>,
+    /// (artist, album, title), normalized; the album artist and the track
+    /// artist both index a file.
+    by_name: HashMap<(String, String, String), PathBuf>,
+    /// (artist, album), normalized.
+    albums: HashSet<(String, String)>,
+    files: usize,
+}
+
+impl TagIndex {
+    /// Read the tags of every audio file under `root`. Files whose tags
+    /// can't be read are left out.
+    pub fn build(root: &Path) -> Result<Self> {
+        let mut index = Self::default();
+        for rel in scan_library(root)? {
+            let path = root.join(rel);
+            match read_tags(&path) {
+                Ok(tags) => index.insert(path, &tags),
+                Err(e) => debug!("no tags read: {e:#}"),
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, tags: &TrackTags) {
+        self.files += 1;
+        if let Some(isrc) = &tags.isrc {
+            self.by_isrc
+                .entry(normalize_isrc(isrc))
+                .or_insert_with(|| path.clone());
+        }
+        let (Some(album), Some(title)) = (&tags.album, &tags.title) else {
+            return;
+        };
+        for artist in [&tags.album_artist, &tags.artist].into_iter().flatten() {
+            let (artist, album) = (normalize(artist), normalize(album));
+            self.albums.insert((artist.clone(), album.clone()));
+            self.by_name
+                .entry((artist, album, normalize(title)))
+                .or_insert_with(|| path.clone());
+        }
+    }
+
+    /// How many files were indexed.
+    pub fn len(&self) -> usize {
+        self.files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files == 0
+    }
+
+    /// The file holding `track` of `album`: the one with its ISRC, or else
+    /// the one tagged with its artist, album and title.
+    pub fn find_track(&self, album: &Album, track: &Track) -> Option<&Path> {
+        if let Some(isrc) = &track.isrc
+            && let Some(path) = self.by_isrc.get(&normalize_isrc(isrc))
+        {
+            return Some(path);
+        }
+        let (album_title, title) = (normalize(&album.title), normalize(&track.title));
+        [&album.artist.name, &track.performer.name]
+            .into_iter()
+            .find_map(|artist| {
+                let key = (normalize(artist), album_title.clone(), title.clone());
+                self.by_name.get(&key)
+            })
+            .map(PathBuf::as_path)
+    }
+
+    /// Whether any file is tagged with `artist` and `album`.
+    pub fn has_album(&self, artist: &str, album: &str) -> bool {
+        self.albums.contains(&(normalize(artist), normalize(album)))
+    }
+}
@@ -1,57 +1,206 @@
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures::stream::{self, StreamExt};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::MultiProgress;
+use sha2::{Digest, Sha256};
 use tokio::io::AsyncWriteExt;
+use tracing::{Instrument, debug, debug_span, warn};
 
 use crate::bandcamp::{self, BandcampClient, BandcampPurchases};
 use crate::client::QobuzClient;
+use crate::manifest::{Manifest, ManifestEntry};
 use crate::models::{
-    Album, AlbumId, Artist, BandcampCollectionItem, BandcampDownloadError, BandcampSyncResult,
-    DiscNumber, DownloadError, DownloadTask, SyncPlan, SyncResult, Track, TrackId, TrackNumber,
+    Album, AlbumId, Artist, AudioFormat, AudioQuality, BandcampCollectionItem,
+    BandcampDownloadError, BandcampDownloadInfo, BandcampSyncResult, CompletedDownload,
+    DownloadError, DownloadTask, FileUrlResponse, QobuzQuality, Service, SkipReason, SyncPlan,
+    SyncResult, Track, TrackId, UnknownTrackNumbers,
 };
-use crate::path::{sanitize_component, track_path};
+use crate::path::{Naming, album_dir, is_dated_album_dir, sanitize_component, track_paths};
+use crate::progress;
+use crate::space;
+use crate::storage::{self, Storage};
+use crate::tags;
+use crate::transcode::{self, TranscodeTarget, planned_format};
 
 const CONCURRENT_DOWNLOADS: usize = 4;
+/// Where Bandcamp downloads are unpacked, under the target directory.
+pub const BANDCAMP_TEMP_DIR: &str = ".qoget-temp";
+/// Where an item's tracks are laid out under the temp dir before they are
+/// moved into the library together.
+const STAGING_DIR: &str = "staged";
+/// Directory under the target that kept Bandcamp ZIPs go to.
+pub const ARCHIVES_DIR: &str = "Archives";
+/// Pause before each download in a `backfill` session.
+pub const BACKFILL_PACE: Duration = Duration::from_secs(3);
 const FORMAT_ID_MP3_320: u8 = 5;
 const FORMAT_ID_CD_QUALITY: u8 = 6;
 
-/// Result of a single track download indicating which format was used.
-pub enum DownloadOutcome {
-    Mp3,
-    FlacFallback,
+/// How hard a run leans on the stores.
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadLimits {
+    /// Qobuz tracks downloaded at once.
+    pub concurrency: usize,
+    /// Pause before each download.
+    pub pace: Duration,
+    /// Bytes a session may download before leaving the rest for the next
+    /// one.
+    pub budget: Option<u64>,
+}
+
+/// `sync`: as fast as the stores comfortably allow, with no budget.
+impl Default for DownloadLimits {
+    fn default() -> Self {
+        Self {
+            concurrency: CONCURRENT_DOWNLOADS,
+            pace: Duration::ZERO,
+            budget: None,
+        }
+    }
+}
+
+impl DownloadLimits {
+    /// `backfill`: one download at a time, `pace` apart, stopping at
+    /// `budget`.
+    pub fn backfill(pace: Duration, budget: u64) -> Self {
+        Self {
+            concurrency: 1,
+            pace,
+            budget: Some(budget),
+        }
+    }
+}
+
+/// How a Qobuz sync's downloads run, beyond what they are.
+#[derive(Debug, Clone, Default)]
+pub struct QobuzOptions {
+    pub limits: DownloadLimits,
+    pub quality: QobuzQuality,
+    /// Transcode each download to this, instead of keeping the format
+    /// Qobuz delivered.
+    pub transcode: Option<TranscodeTarget>,
+    /// Draw no progress bars.
+    pub quiet: bool,
+    pub events: progress::Events,
+    /// Where the library is: the target directory, or a remote target.
+    pub storage: storage::Shared,
+}
+
+/// How a Bandcamp sync runs, beyond what it downloads.
+#[derive(Debug, Clone, Default)]
+pub struct BandcampOptions {
+    pub dry_run: bool,
+    /// Warn instead of failing when an item looks too big for the disk.
+    pub ignore_space: bool,
+    pub limits: DownloadLimits,
+    pub unknown_track_numbers: UnknownTrackNumbers,
+    pub naming: Naming,
+    /// Transcode each extracted track to this, instead of keeping AAC.
+    pub transcode: Option<TranscodeTarget>,
+    /// Draw no progress bars.
+    pub quiet: bool,
+    /// Keep each downloaded ZIP under [`ARCHIVES_DIR`].
+    pub keep_archives: bool,
+    /// Item ids to download, when only some were chosen; other unsynced
+    /// items are skipped.
+    pub selected: Option<BTreeSet<u64>>,
+    pub events: progress::Events,
+    /// Where the library is: the target directory, or a remote target.
+    pub storage: storage::Shared,
 }
 
 /// Execute all downloads in the sync plan with bounded parallelism and progress bars.
-pub async fn execute_downloads(client: &QobuzClient, plan: SyncPlan) -> Result<SyncResult> {
-    let skipped = plan.skipped;
-    let total = plan.downloads.len() as u64;
-
-    let multi = Arc::new(MultiProgress::new());
-    let overall = multi.add(ProgressBar::new(total));
-    overall.set_style(
-        ProgressStyle::default_bar()
-            .template("[{pos}/{len}] {msg}")
-            .expect("valid template"),
-    );
-
-    let results: Vec<Result<(DownloadTask, DownloadOutcome), DownloadError>> =
-        stream::iter(plan.downloads.into_iter().map(|task| {
+/// Finished files are put in `opts.storage`.
+pub async fn execute_downloads(
+    client: &QobuzClient,
+    plan: SyncPlan,
+    opts: &QobuzOptions,
+) -> Result<SyncResult> {
+    let QobuzOptions {
+        ref limits,
+        quality,
+        ref events,
+        ..
+    } = *opts;
+    let estimated = plan.estimated_bytes();
+    let (downloads, skipped) = plan.into_parts();
+    let total = downloads.len() as u64;
+
+    let multi = Arc::new(progress::multi(opts.quiet));
+    let overall = progress::counter(&multi, total);
+    let bytes = progress::total_bytes(&multi, estimated);
+    let albums = Mutex::new(progress::AlbumLog::new(downloads.iter().map(|task| {
+        (
+            task.album.id.clone(),
+            format!("{} - {}", task.album.artist.name, task.album.title),
+        )
+    })));
+
+    let results: Vec<Result<(DownloadTask, Downloaded), DownloadError>> =
+        stream::iter(downloads.into_iter().map(|task| {
             let multi = Arc::clone(&multi);
             let overall = overall.clone();
+            let share = progress::Share::new(&bytes, task.estimated_bytes());
+            let albums = &albums;
+            let span = debug_span!(
+                "track",
+                id = %task.track.id,
+                album = %task.album.title,
+                title = %task.track.title
+            );
             async move {
-                overall.set_message(format!("{} - {}", task.album.artist.name, task.track.title));
+                if !limits.pace.is_zero() {
+                    tokio::time::sleep(limits.pace).await;
+                }
+                overall.set_message(format!("{} - {}", task.album.artist.name, task.album.title));
 
-                let result = download_one(client, &task, &multi).await;
+                let item = progress::DownloadItem {
+                    service: Service::Qobuz,
+                    id: task.track.id.to_string(),
+                    name: format!("{} - {}", task.track.performer.name, task.track.title),
+                };
+                events.track_started(&item);
+                let pb = progress::bytes(&multi, None, task.track.title.clone());
+                let transfer = progress::Transfer::new(pb, events, item.clone()).with_share(share);
+                let result = download_one(client, &task, opts, transfer).await;
                 overall.inc(1);
+                match &result {
+                    Ok(_) => events.track_done(&item),
+                    Err(e) => events.item_failed(&item, &format!("{e:#}")),
+                }
+
+                let finished = {
+                    let mut albums = albums.lock().unwrap();
+                    match &result {
+                        Ok(Downloaded {
+                            fallback: Some(offered),
+                            ..
+                        }) => albums.note(
+                            &task.album.id,
+                            format!("{quality} unavailable, got {offered}: {}", task.track.title),
+                        ),
+                        Ok(_) => {}
+                        Err(e) => albums.note(
+                            &task.album.id,
+                            format!("Failed: {}: {e:#}", task.track.title),
+                        ),
+                    }
+                    albums.finish(&task.album.id, result.is_ok())
+                };
+                if let Some(lines) = finished {
+                    progress::println(&multi, &lines);
+                }
 
                 match result {
                     Ok(outcome) => Ok((task, outcome)),
                     Err(e) => {
-                        // Clean up temp files on failure (both .mp3.tmp and .flac.tmp)
-                        for ext in [task.file_extension, ".flac"] {
+                        // Clean up temp files on failure: the download's
+                        // (.mp3.tmp or .flac.tmp) and a transcode's
+                        for ext in [task.file_extension, ".mp3", ".flac"] {
                             let ext_no_dot = &ext[1..];
                             let temp_path =
                                 task.target_path.with_extension(format!("{ext_no_dot}.tmp"));
@@ -64,23 +213,38 @@ pub async fn execute_downloads(client: &QobuzClient, plan: SyncPlan) -> Result<S
                     }
                 }
             }
+            .instrument(span)
         }))
-        .buffer_unordered(CONCURRENT_DOWNLOADS)
+        .buffer_unordered(limits.concurrency.max(1))
         .collect()
         .await;
 
     overall.finish_and_clear();
+    bytes.finish_and_clear();
 
     let mut succeeded = Vec::new();
     let mut failed = Vec::new();
     let mut fallback_count = 0;
     for result in results {
         match result {
-            Ok((task, outcome)) => {
-                if matches!(outcome, DownloadOutcome::FlacFallback) {
+            Ok((
+                task,
+                Downloaded {
+                    fallback,
+                    format,
+                    quality,
+                    sha256,
+                },
+            )) => {
+                if fallback.is_some() {
                     fallback_count += 1;
                 }
-                succeeded.push(task);
+                succeeded.push(CompletedDownload {
+                    task,
+                    format,
+                    quality,
+                    sha256,
+                });
             }
             Err(err) => failed.push(err),
         }
@@ -94,42 +258,102 @@ pub async fn execute_downloads(client: &QobuzClient, plan: SyncPlan) -> Result<S
     })
 }
 
-/// Download a single track: get URL (with format fallback), stream to temp file, rename to target.
+/// Of `tracks`, those the account may download in full rather than only as
+/// a preview: purchases, but not favorites that were only ever streamed. A
+/// track whose check fails is left out, to be checked again next sync.
+pub async fn entitled_tracks(client: &QobuzClient, tracks: Vec<TrackId>) -> HashSet<TrackId> {
+    stream::iter(tracks.into_iter().map(|id| async move {
+        // As for downloads, CD Quality stands in for a missing MP3.
+        let file = match client.get_file_url(id, FORMAT_ID_MP3_320).await {
+            Ok(file) => Ok(file),
+            Err(_) => client.get_file_url(id, FORMAT_ID_CD_QUALITY).await,
+        };
+        match file {
+            Ok(file) => (!file.sample).then_some(id),
+            Err(e) => {
+                debug!("entitlement check for track {id} failed: {e:#}");
+                None
+            }
+        }
+    }))
+    .buffer_unordered(CONCURRENT_DOWNLOADS)
+    .filter_map(|id| async move { id })
+    .collect()
+    .await
+}
+
+/// What `download_one` fetched.
+struct Downloaded {
+    /// What Qobuz sent, if it was another format than `quality` asked for.
+    fallback: Option<String>,
+    /// What the file on disk is: the download's format, or the transcode
+    /// target's.
+    format: AudioFormat,
+    /// Reported by Qobuz for lossless streams, unless transcoded.
+    quality: Option<AudioQuality>,
+    sha256: String,
+}
+
+/// "MP3", "FLAC 24-bit/96 kHz", or "FLAC" if Qobuz didn't say.
+fn stream_label(format: AudioFormat, quality: Option<AudioQuality>) -> String {
+    match (format, quality) {
+        (AudioFormat::Mp3, _) => "MP3".to_string(),
+        (_, Some(q)) => format!("FLAC {q}"),
+        (_, None) => "FLAC".to_string(),
+    }
+}
+
+/// Ask Qobuz for a track's file URL in each of `quality`'s formats in turn,
+/// until one is offered.
+async fn file_url(
+    client: &QobuzClient,
+    task: &DownloadTask,
+    quality: QobuzQuality,
+) -> Result<FileUrlResponse> {
+    let mut last_err = None;
+    for &format_id in quality.format_ids() {
+        match client.get_file_url(task.track.id, format_id).await {
+            Ok(file) => return Ok(file),
+            Err(e) => {
+                debug!("format {format_id} URL request failed: {e:#}");
+                last_err = Some(e);
+            }
+        }
+    }
+    let err = last_err.context("no formats to request")?;
+    Err(err.context(format!("unavailable in every format up to {quality}")))
+}
+
+/// Download a single track: get URL (with format fallback), stream to temp file, put at target.
 ///
-/// Tries MP3 320 first. If the format request fails, retries with CD Quality (FLAC).
-/// Returns which format was actually downloaded.
+/// Asks for `quality`'s format first, then for the formats below it (MP3
+/// falls back to CD Quality). Returns which format was actually downloaded.
+/// With a transcode target, any is transcoded to the planned path instead
+/// of renamed.
 async fn download_one(
     client: &QobuzClient,
     task: &DownloadTask,
-    multi: &MultiProgress,
-) -> Result<DownloadOutcome> {
-    // Try MP3 320, fall back to CD Quality on error
-    let (url, outcome) = match client
-        .get_file_url(task.track.id, FORMAT_ID_MP3_320)
-        .await
-    {
-        Ok(url) => (url, DownloadOutcome::Mp3),
-        Err(_mp3_err) => {
-            eprintln!(
-                "  MP3 unavailable, downloading CD Quality: {} - {}",
-                task.album.artist.name, task.track.title
-            );
-            let url = client
-                .get_file_url(task.track.id, FORMAT_ID_CD_QUALITY)
-                .await
-                .map_err(|cd_err| {
-                    anyhow::anyhow!(
-                        "unavailable in both MP3 320 and CD Quality: {cd_err:#}"
-                    )
-                })?;
-            (url, DownloadOutcome::FlacFallback)
-        }
-    };
+    opts: &QobuzOptions,
+    mut transfer: progress::Transfer,
+) -> Result<Downloaded> {
+    let QobuzOptions {
+        quality,
+        transcode,
+        ref storage,
+        ..
+    } = *opts;
+    let file = file_url(client, task, quality).await?;
+    let format = file.format();
+    let stream_quality = file.quality();
+    let fallback = (format != quality.format()).then(|| stream_label(format, stream_quality));
+    if let Some(stream_quality) = stream_quality {
+        debug!("stream is {stream_quality} ({})", file.mime_type);
+    }
 
     // Determine actual target path (may differ from planned if fallback occurred)
-    let actual_target = match outcome {
-        DownloadOutcome::Mp3 => task.target_path.clone(),
-        DownloadOutcome::FlacFallback => task.target_path.with_extension("flac"),
+    let actual_target = match transcode {
+        Some(_) => task.target_path.clone(),
+        None => task.target_path.with_extension(&format.extension()[1..]),
     };
 
     // Ensure parent directory exists
@@ -138,43 +362,65 @@ async fn download_one(
     }
 
     // Download to temp file in same directory, then rename
-    let actual_ext = match outcome {
-        DownloadOutcome::Mp3 => task.file_extension,
-        DownloadOutcome::FlacFallback => ".flac",
-    };
-    let ext_no_dot = &actual_ext[1..];
+    let ext_no_dot = &format.extension()[1..];
     let temp_path = actual_target.with_extension(format!("{ext_no_dot}.tmp"));
 
-    let resp = client.http().get(&url).send().await?;
+    let resp = client.http().get(&file.url).send().await?;
 
     if !resp.status().is_success() {
         anyhow::bail!("Download returned HTTP {}", resp.status());
     }
 
-    // Set up per-file progress bar if content-length is known
-    let content_len = resp.content_length();
-    let pb = multi.add(ProgressBar::new(content_len.unwrap_or(0)));
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("  {bytes}/{total_bytes} {bar:30} {msg}")
-            .expect("valid template"),
-    );
-    pb.set_message(task.track.title.clone());
-
-    let bytes = resp.bytes().await?;
-    pb.set_position(bytes.len() as u64);
-
-    let mut file = tokio::fs::File::create(&temp_path).await?;
-    file.write_all(&bytes).await?;
-    file.flush().await?;
-    drop(file);
+    if let Some(len) = resp.content_length() {
+        transfer.set_size(len);
+    }
+
+    let sha256 = stream_to_file(resp, &temp_path, &mut transfer).await?;
 
-    pb.finish_and_clear();
+    transfer.finish();
 
-    // Atomic rename
-    tokio::fs::rename(&temp_path, &actual_target).await?;
+    let Some(target) = transcode else {
+        // Atomic rename, or an upload to a remote target
+        storage.put(&temp_path, &actual_target).await?;
+        debug!("saved {}", actual_target.display());
+        return Ok(Downloaded {
+            fallback,
+            format,
+            quality: stream_quality,
+            sha256,
+        });
+    };
+    let sha256 = transcode::transcode(&temp_path, &actual_target, target).await?;
+    storage.put(&actual_target, &actual_target).await?;
+    debug!("saved {} as {target}", actual_target.display());
+    Ok(Downloaded {
+        fallback,
+        format: target.format(),
+        quality: None,
+        sha256,
+    })
+}
 
-    Ok(outcome)
+/// Stream a response body to `path` chunk by chunk, reporting each chunk to
+/// `transfer` as it arrives. Memory
+/// use stays flat regardless of file size. Returns the hex SHA-256 of the
+/// body, hashed on the way through.
+pub(crate) async fn stream_to_file(
+    resp: reqwest::Response,
+    path: &Path,
+    transfer: &mut progress::Transfer,
+) -> Result<String> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut stream = resp.bytes_stream();
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        transfer.inc(chunk.len() as u64);
+    }
+    file.flush().await?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
 // --- Bandcamp download dispatch ---
@@ -183,85 +429,200 @@ async fn download_one(
 ///
 /// Operates at the album/item level (not individual tracks) since Bandcamp delivers albums
 /// as ZIP archives. For incremental sync, albums with existing .m4a files are skipped.
+/// Placed tracks are recorded in `manifest`; the caller saves it.
 pub async fn execute_bandcamp_downloads(
     client: &BandcampClient,
     purchases: &BandcampPurchases,
     target_dir: &Path,
-    dry_run: bool,
+    opts: &BandcampOptions,
+    manifest: &mut Manifest,
 ) -> Result<BandcampSyncResult> {
-    let multi = Arc::new(MultiProgress::new());
-    let overall = multi.add(ProgressBar::new(purchases.items.len() as u64));
-    overall.set_style(
-        ProgressStyle::default_bar()
-            .template("[{pos}/{len}] {msg}")
-            .expect("valid template"),
-    );
+    let BandcampOptions {
+        dry_run,
+        ignore_space,
+        ref limits,
+        ..
+    } = *opts;
+    prefetch_album_dirs(purchases, target_dir, &*opts.storage, opts.naming).await?;
+    let multi = Arc::new(progress::multi(opts.quiet));
+    let overall = progress::counter(&multi, purchases.items.len() as u64);
 
     let mut result = BandcampSyncResult {
         downloaded: 0,
         skipped: 0,
         would_download: 0,
+        retagged: 0,
+        succeeded: Vec::new(),
+        skipped_items: Vec::new(),
+        sizes: HashMap::new(),
         failed: Vec::new(),
     };
 
-    let temp_dir = target_dir.join(".qoget-temp");
+    let temp_dir = target_dir.join(BANDCAMP_TEMP_DIR);
+    // Bytes downloaded so far, by the sizes Bandcamp gives.
+    let mut spent = 0u64;
 
-    for item in &purchases.items {
+    for (index, item) in purchases.items.iter().enumerate() {
         let desc = format!("{} - {}", item.band_name, item.item_title);
         overall.set_message(desc.clone());
 
-        // Look up redownload URL by "{sale_item_type}{sale_item_id}" key
-        let key = format!("{}{}", item.sale_item_type, item.sale_item_id);
-        let redownload_url = match purchases.redownload_urls.get(&key) {
+        let redownload_url = match purchases.redownload_url(item) {
             Some(url) => url,
             None => {
+                let error = format!("No redownload URL found (key: {})", item.redownload_key());
+                opts.events.item_failed(&events_item(item), &error);
                 result.failed.push(BandcampDownloadError {
+                    item_id: item.item_id,
                     description: desc,
-                    error: format!("No redownload URL found (key: {key})"),
+                    error,
                 });
                 overall.inc(1);
                 continue;
             }
         };
 
-        // Build album struct for path computation
-        let album = Album {
-            id: AlbumId(format!("bc-{}", item.item_id)),
-            title: item.item_title.clone(),
-            version: None,
-            artist: Artist {
-                id: item.sale_item_id,
-                name: item.band_name.clone(),
-            },
-            media_count: 1,
-            tracks_count: 0,
-            tracks: None,
-        };
+        let album = item_album(item);
+
+        let span = debug_span!("album", id = item.item_id, title = %item.item_title);
 
         // Check if already synced
-        if is_already_synced(target_dir, item, &album).await {
+        if let Some(dir) = synced_album_dir(
+            target_dir,
+            &album,
+            &*opts.storage,
+            opts.naming,
+            opts.transcode,
+        )
+        .await?
+        {
             result.skipped += 1;
+            result
+                .skipped_items
+                .push((desc.clone(), SkipReason::AlreadyExists));
+            if !dry_run {
+                result.retagged += catch_up_steps(client, item, &dir, target_dir, manifest, &multi)
+                    .instrument(span)
+                    .await;
+            }
+            overall.inc(1);
+            continue;
+        }
+
+        if opts
+            .selected
+            .as_ref()
+            .is_some_and(|s| !s.contains(&item.item_id))
+        {
+            result.skipped_items.push((desc, SkipReason::NotSelected));
             overall.inc(1);
             continue;
         }
 
         if dry_run {
-            println!("{}", desc);
+            // Only the download page says how big the album is.
+            match client.get_download_info(redownload_url).await {
+                Ok(info) => {
+                    if let Some(size) = download_size(&info) {
+                        result.sizes.insert(item.item_id, size);
+                    }
+                }
+                Err(e) => debug!("no download size for {desc}: {e:#}"),
+            }
             result.would_download += 1;
+            result.skipped_items.push((desc, SkipReason::DryRun));
             overall.inc(1);
             continue;
         }
 
+        if limits.budget.is_some_and(|budget| spent >= budget) {
+            result.skipped_items.push((desc, SkipReason::Deferred));
+            overall.inc(1);
+            continue;
+        }
+        if !limits.pace.is_zero() {
+            tokio::time::sleep(limits.pace).await;
+        }
+
+        // Fetch download page; it says how big the download is
+        let info = match client
+            .get_download_info(redownload_url)
+            .instrument(span.clone())
+            .await
+        {
+            Ok(info) => info,
+            Err(e) => {
+                let error = format!("{e:#}");
+                opts.events.item_failed(&events_item(item), &error);
+                result.failed.push(BandcampDownloadError {
+                    item_id: item.item_id,
+                    description: desc,
+                    error,
+                });
+                overall.inc(1);
+                continue;
+            }
+        };
+        let size = download_size(&info);
+        if let Some(size) = size {
+            overall.set_message(format!("{desc} ({})", space::human(size)));
+        }
+        let size = size.unwrap_or(0);
+        // An album ZIP is unpacked next to itself, so it needs room twice.
+        if let Err(e) = space::check(&desc, size * 2, target_dir, ignore_space) {
+            // Every later item would hit the same full disk.
+            let remaining = purchases.items.len() - index - 1;
+            warn!("not attempting the remaining {remaining} Bandcamp items");
+            let error = format!("{e:#}");
+            opts.events.item_failed(&events_item(item), &error);
+            result.failed.push(BandcampDownloadError {
+                item_id: item.item_id,
+                description: desc,
+                error,
+            });
+            break;
+        }
+
         // Download
         tokio::fs::create_dir_all(&temp_dir).await?;
-        match download_bandcamp_item(client, redownload_url, item, &album, target_dir, &temp_dir)
+        opts.events.track_started(&events_item(item));
+        match download_bandcamp_item(client, &info, item, &album, target_dir, opts, &multi)
+            .instrument(span)
             .await
         {
-            Ok(count) => result.downloaded += count,
+            Ok((placed, tagged)) => {
+                opts.events.track_done(&events_item(item));
+                spent += size;
+                result.downloaded += placed.len();
+                result.succeeded.push((
+                    desc,
+                    placed.iter().map(|(_, path, _)| path.clone()).collect(),
+                ));
+                for (track, path, sha256) in placed {
+                    let tagged = tagged.as_ref().filter(|t| t.done.contains_key(&path));
+                    // Tagging rewrote the file, and hashed the new contents.
+                    let sha256 = tagged.and_then(|t| t.done[&path].clone()).unwrap_or(sha256);
+                    let mut entry = ManifestEntry::hashed(
+                        &album,
+                        &track,
+                        planned_format(AudioFormat::Aac, opts.transcode),
+                        target_dir,
+                        &path,
+                        Some(sha256),
+                    );
+                    if let Some(tagged) = tagged {
+                        entry.genre = tagged.genre.clone();
+                        entry.mark_step(tags::GENRE_STEP);
+                    }
+                    manifest.record(Service::Bandcamp, track.id, entry);
+                }
+            }
             Err(e) => {
+                let error = format!("{e:#}");
+                opts.events.item_failed(&events_item(item), &error);
                 result.failed.push(BandcampDownloadError {
+                    item_id: item.item_id,
                     description: desc,
-                    error: format!("{e:#}"),
+                    error,
                 });
             }
         }
@@ -277,95 +638,419 @@ pub async fn execute_bandcamp_downloads(
     Ok(result)
 }
 
-/// Check if a Bandcamp item is already synced locally.
+/// A Bandcamp item, as progress events name it.
+fn events_item(item: &BandcampCollectionItem) -> progress::DownloadItem {
+    progress::DownloadItem {
+        service: Service::Bandcamp,
+        id: item.item_id.to_string(),
+        name: format!("{} - {}", item.band_name, item.item_title),
+    }
+}
+
+/// How big the AAC download of an item is, by what its download page says.
+fn download_size(info: &BandcampDownloadInfo) -> Option<u64> {
+    info.downloads.get("aac-hi").and_then(|f| f.size_bytes())
+}
+
+/// The album struct a Bandcamp item's paths are computed from.
+pub fn item_album(item: &BandcampCollectionItem) -> Album {
+    Album {
+        id: AlbumId(format!("bc-{}", item.item_id)),
+        title: item.item_title.clone(),
+        version: None,
+        artist: Artist {
+            id: item.sale_item_id,
+            name: item.band_name.clone(),
+        },
+        media_count: 1,
+        tracks_count: 0,
+        upc: None,
+        release_date: None,
+        artists: Vec::new(),
+        image: None,
+        tracks: None,
+        purchase: item.purchase_info(),
+    }
+}
+
+/// The items `execute_bandcamp_downloads` would download: those with
+/// nothing synced yet.
+pub async fn bandcamp_pending<'a>(
+    purchases: &'a BandcampPurchases,
+    target_dir: &Path,
+    storage: &dyn Storage,
+    naming: Naming,
+    transcode: Option<TranscodeTarget>,
+) -> Result<Vec<&'a BandcampCollectionItem>> {
+    prefetch_album_dirs(purchases, target_dir, storage, naming).await?;
+    let mut pending = Vec::new();
+    for item in &purchases.items {
+        if synced_album_dir(target_dir, &item_album(item), storage, naming, transcode)
+            .await?
+            .is_none()
+        {
+            pending.push(item);
+        }
+    }
+    Ok(pending)
+}
+
+/// Where a Bandcamp item is already synced locally, if it is.
 ///
-/// Checks the album directory for any .m4a files. Works for
-/// both multi-track albums and single tracks since both end
-/// up under `Artist/Title/`.
-async fn is_already_synced(
+/// Checks the album directory for any .m4a files (or files of the
+/// transcode target's format). Works for both multi-track albums and
+/// single tracks since both end up under `Artist/Title/`. With years in
+/// directory names, the album may be under any year, as only the download
+/// page says which.
+async fn synced_album_dir(
     target_dir: &Path,
-    _item: &BandcampCollectionItem,
     album: &Album,
-) -> bool {
-    let album_dir = target_dir
-        .join(sanitize_component(&album.artist.name))
-        .join(sanitize_component(&album.title));
-    has_m4a_files(&album_dir).await
+    storage: &dyn Storage,
+    naming: Naming,
+    transcode: Option<TranscodeTarget>,
+) -> Result<Option<PathBuf>> {
+    let ext = &planned_format(AudioFormat::Aac, transcode).extension()[1..];
+    let dir = album_dir(target_dir, album, naming);
+    if has_audio_files(storage, &dir, ext).await? {
+        return Ok(Some(dir));
+    }
+    if !naming.album_years || album.year().is_some() {
+        return Ok(None);
+    }
+    let (Some(artist_dir), Some(undated)) = (dir.parent(), dir.file_name()) else {
+        return Ok(None);
+    };
+    let undated = undated.to_string_lossy();
+    for name in storage.list(artist_dir).await? {
+        let dated = artist_dir.join(&name);
+        if is_dated_album_dir(&name.to_string_lossy(), &undated)
+            && has_audio_files(storage, &dated, ext).await?
+        {
+            return Ok(Some(dated));
+        }
+    }
+    Ok(None)
+}
+
+/// Look up the directories `synced_album_dir` checks for each of
+/// `purchases` at once, for a remote target.
+async fn prefetch_album_dirs(
+    purchases: &BandcampPurchases,
+    target_dir: &Path,
+    storage: &dyn Storage,
+    naming: Naming,
+) -> Result<()> {
+    if storage.is_local() {
+        return Ok(());
+    }
+    let mut dirs = Vec::new();
+    for item in &purchases.items {
+        let dir = album_dir(target_dir, &item_album(item), naming);
+        dirs.extend(dir.parent().map(Path::to_path_buf));
+        dirs.push(dir);
+    }
+    storage.prefetch(&dirs).await
+}
+
+/// Give an already-synced item's recorded tracks the post-download steps
+/// they lack: tracks synced before the step existed, or whose earlier run
+/// failed or was interrupted. Returns how many tracks were updated.
+async fn catch_up_steps(
+    client: &BandcampClient,
+    item: &BandcampCollectionItem,
+    synced_dir: &Path,
+    target_dir: &Path,
+    manifest: &mut Manifest,
+    multi: &MultiProgress,
+) -> usize {
+    let dir = synced_dir.strip_prefix(target_dir).unwrap_or(synced_dir);
+    let pending: Vec<PathBuf> = manifest
+        .album_entries_mut(Service::Bandcamp, dir)
+        .filter(|e| !e.has_step(tags::GENRE_STEP))
+        .map(|e| target_dir.join(&e.path))
+        .filter(|path| path.is_file())
+        .collect();
+    if pending.is_empty() {
+        return 0;
+    }
+    debug!("{} tracks lack the genre step", pending.len());
+    let Some(tagged) = tag_genre(client, item, &pending, multi).await else {
+        return 0;
+    };
+
+    let mut updated = 0;
+    for entry in manifest.album_entries_mut(Service::Bandcamp, dir) {
+        let Some(sha256) = tagged.done.get(&target_dir.join(&entry.path)) else {
+            continue;
+        };
+        if tagged.genre.is_some() {
+            entry.genre = tagged.genre.clone();
+            entry.refresh(target_dir, sha256.clone());
+        }
+        entry.mark_step(tags::GENRE_STEP);
+        updated += 1;
+    }
+    updated
 }
 
 /// Download and extract a single Bandcamp item (album ZIP or single track).
+/// Returns each placed track with its final path and checksum, and how
+/// tagging went.
 async fn download_bandcamp_item(
     client: &BandcampClient,
-    redownload_url: &str,
+    info: &BandcampDownloadInfo,
     item: &BandcampCollectionItem,
     album: &Album,
     target_dir: &Path,
-    temp_dir: &Path,
-) -> Result<usize> {
-    // Fetch download page and get aac-hi URL
-    let info = client.get_download_info(redownload_url).await?;
-    let url = bandcamp::aac_hi_url(&info)?;
+    opts: &BandcampOptions,
+    multi: &MultiProgress,
+) -> Result<(Vec<(Track, PathBuf, String)>, Option<GenreTagging>)> {
+    let url = bandcamp::aac_hi_url(info)?;
+    let temp_dir = &target_dir.join(BANDCAMP_TEMP_DIR);
+    let numbering = opts.unknown_track_numbers;
 
     // Download and extract
-    let extracted = client.download_and_extract(url, temp_dir).await?;
-    let mut count = 0;
-
-    if extracted.len() > 1 {
-        // Multi-track: use extracted track metadata for paths
-        for ext_track in extracted {
-            let track = Track {
-                id: TrackId(
-                    item.item_id
-                        .wrapping_mul(1000)
-                        .wrapping_add(ext_track.track_number as u64),
-                ),
-                title: ext_track.title,
-                track_number: TrackNumber(ext_track.track_number),
-                media_number: DiscNumber(1),
-                duration: 0,
-                performer: album.artist.clone(),
-                isrc: None,
-            };
-            let target = track_path(target_dir, album, &track, ".m4a");
-            if let Some(parent) = target.parent() {
-                tokio::fs::create_dir_all(parent).await?;
+    let pb = progress::bytes(multi, None, item.item_title.clone());
+    let mut transfer = progress::Transfer::new(pb, &opts.events, events_item(item));
+    let extracted = client
+        .download_and_extract(url, temp_dir, opts.keep_archives, &mut transfer)
+        .await;
+    transfer.finish();
+    let mut extracted = extracted?;
+    debug!("extracted {} tracks", extracted.len());
+    let mut placed = Vec::new();
+
+    if extracted.len() > 1 && extracted.iter().any(|t| t.track_number == 0) {
+        let tracklist = match (numbering, item.item_url.as_deref()) {
+            (UnknownTrackNumbers::Tracklist, Some(page_url)) => {
+                client.get_tracklist(page_url).await.unwrap_or_else(|e| {
+                    warn!("no tracklist for {}: {e:#}", item.item_title);
+                    Vec::new()
+                })
             }
-            tokio::fs::rename(&ext_track.temp_path, &target).await?;
-            count += 1;
+            _ => Vec::new(),
+        };
+        bandcamp::number_unknown_tracks(&mut extracted, numbering, &tracklist);
+    }
+    let tracks = bandcamp::extracted_tracks(item, album, &extracted);
+    // Discs come from the files' tags; laid out as "Disc N" like Qobuz's.
+    let discs = tracks.iter().map(|t| t.media_number.0).max().unwrap_or(1);
+    let album = &Album {
+        media_count: discs.max(album.media_count),
+        release_date: info
+            .iso_release_date()
+            .or_else(|| album.release_date.clone()),
+        ..album.clone()
+    };
+
+    // Lay the album out under the temp dir first, so a failure part way
+    // through never leaves a partial album that looks synced.
+    let staged_dir = album_dir(&temp_dir.join(STAGING_DIR), album, opts.naming);
+    let transcode = opts.transcode;
+    let ext = planned_format(AudioFormat::Aac, transcode).extension();
+    let target_album_dir = album_dir(target_dir, album, opts.naming);
+    let targets = track_paths(target_dir, album, &tracks, ext, opts.naming);
+    let mut files = Vec::new();
+    for ((track, ext_track), target) in tracks.into_iter().zip(extracted).zip(targets) {
+        // Named after the target, which may have been shortened to fit.
+        let staged = staged_dir.join(
+            target
+                .strip_prefix(&target_album_dir)
+                .expect("track paths are under the album directory"),
+        );
+        if let Some(parent) = staged.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-    } else {
-        // Single track: use item metadata for consistent path
-        let track = Track {
-            id: TrackId(item.item_id),
-            title: item.item_title.clone(),
-            track_number: TrackNumber(1),
-            media_number: DiscNumber(1),
-            duration: 0,
-            performer: album.artist.clone(),
-            isrc: None,
+        let sha256 = match transcode {
+            Some(to) => transcode::transcode(&ext_track.temp_path, &staged, to).await?,
+            None => {
+                tokio::fs::rename(&ext_track.temp_path, &staged).await?;
+                ext_track.sha256
+            }
         };
-        let target = track_path(target_dir, album, &track, ".m4a");
-        if let Some(parent) = target.parent() {
+        files.push((staged, target.clone()));
+        placed.push((track, target, sha256));
+    }
+
+    // Tagged while staged, so the files are finished wherever they go.
+    let staged: Vec<PathBuf> = files.iter().map(|(staged, _)| staged.clone()).collect();
+    let tagged = tag_genre(client, item, &staged, multi)
+        .await
+        .map(|tagged| tagged.moved(&files));
+    opts.storage
+        .place_album(&staged_dir, &target_album_dir, &files)
+        .await?;
+
+    let zip = temp_dir.join(bandcamp::KEPT_ZIP);
+    if opts.keep_archives && zip.is_file() {
+        let archive = archive_path(target_dir, album, opts.naming);
+        if let Err(e) = opts.storage.put(&zip, &archive).await {
+            warn!("could not keep {}: {e:#}", archive.display());
+        }
+    }
+    Ok((placed, tagged))
+}
+
+/// Move a staged album into the library in one step. `files` pairs each
+/// staged file under `staged_dir` with its place under `album_dir`.
+///
+/// A new album is a single directory rename. If the album directory exists
+/// already (say it holds only cover art), the files are moved one by one,
+/// and those already moved are put back if one fails.
+pub async fn place_staged(
+    staged_dir: &Path,
+    album_dir: &Path,
+    files: &[(PathBuf, PathBuf)],
+) -> Result<()> {
+    if tokio::fs::metadata(album_dir).await.is_err() {
+        if let Some(parent) = album_dir.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
-        if let Some(ext_track) = extracted.into_iter().next() {
-            tokio::fs::rename(&ext_track.temp_path, &target).await?;
-            count += 1;
+        return tokio::fs::rename(staged_dir, album_dir)
+            .await
+            .with_context(|| {
+                format!(
+                    "renaming {} -> {}",
+                    staged_dir.display(),
+                    album_dir.display()
+                )
+            });
+    }
+
+    let mut moved: Vec<&(PathBuf, PathBuf)> = Vec::new();
+    for file in files {
+        let (staged, target) = file;
+        let result = async {
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(staged, target).await
         }
+        .await;
+        if let Err(e) = result {
+            for (staged, target) in moved.into_iter().rev() {
+                let _ = tokio::fs::rename(target, staged).await;
+            }
+            return Err(e).with_context(|| {
+                format!("renaming {} -> {}", staged.display(), target.display())
+            });
+        }
+        moved.push(file);
     }
+    Ok(())
+}
 
-    Ok(count)
+/// Where an album's original ZIP is kept: `Archives/Artist - Album.zip`.
+pub fn archive_path(target_dir: &Path, album: &Album, naming: Naming) -> PathBuf {
+    let name = format!("{} - {}", album.artist.name, album.title);
+    target_dir
+        .join(ARCHIVES_DIR)
+        .join(format!("{}.zip", sanitize_component(&name, naming)))
 }
 
-/// Check if a directory contains any .m4a files (non-recursive).
-async fn has_m4a_files(dir: &Path) -> bool {
-    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
-        return false;
+/// Outcome of the genre step for an item's files.
+struct GenreTagging {
+    /// First tag on the item's page; `None` if it lists none.
+    genre: Option<String>,
+    /// Files the step finished on (all of them when there was no genre),
+    /// with the new checksum of each file it rewrote.
+    done: BTreeMap<PathBuf, Option<String>>,
+}
+
+impl GenreTagging {
+    /// The same outcome for the files' places in the library: `files`
+    /// pairs each tagged file with where it went.
+    fn moved(self, files: &[(PathBuf, PathBuf)]) -> Self {
+        let done = files
+            .iter()
+            .filter_map(|(from, to)| Some((to.clone(), self.done.get(from)?.clone())))
+            .collect();
+        Self {
+            genre: self.genre,
+            done,
+        }
+    }
+}
+
+/// Write the genre from the item's Bandcamp page into `paths`. Tagging is
+/// best-effort: problems are reported, not fatal. `None` if the page
+/// couldn't be read, so the step is retried on a later sync.
+async fn tag_genre(
+    client: &BandcampClient,
+    item: &BandcampCollectionItem,
+    paths: &[PathBuf],
+    multi: &MultiProgress,
+) -> Option<GenreTagging> {
+    let page_url = item.item_url.as_deref()?;
+    let genre = match client.get_tags(page_url).await {
+        Ok(tags) => tags.into_iter().next(),
+        Err(e) => {
+            let _ = multi.println(format!(
+                "  Warning: no genre for {}: {e:#}",
+                item.item_title
+            ));
+            return None;
+        }
+    };
+    // Only MP4 files are tagged; files transcoded to another format are
+    // left without the genre.
+    let (paths, untaggable): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .iter()
+        .cloned()
+        .partition(|p| p.extension().is_some_and(|e| e == "m4a"));
+    let Some(tag) = genre.clone() else {
+        return Some(GenreTagging {
+            genre,
+            done: paths
+                .iter()
+                .chain(&untaggable)
+                .map(|p| (p.clone(), None))
+                .collect(),
+        });
     };
-    while let Ok(Some(entry)) = entries.next_entry().await {
-        if entry.path().extension().and_then(|e| e.to_str()) == Some("m4a") {
-            return true;
+
+    let results = tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .map(|path| {
+                let result = tags::set_mp4_genre(&path, &tag);
+                (path, result)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    let mut done: BTreeMap<PathBuf, Option<String>> =
+        untaggable.into_iter().map(|p| (p, None)).collect();
+    for (path, result) in results {
+        match result {
+            Ok(sha256) => {
+                done.insert(path, Some(sha256));
+            }
+            Err(e) => {
+                let _ = multi.println(format!("  Warning: could not tag genre: {e:#}"));
+            }
+        }
+    }
+    Some(GenreTagging { genre, done })
+}
+
+/// Check if a directory contains any files with extension `ext`, directly
+/// or in a multi-disc album's `Disc N` subdirectories.
+async fn has_audio_files(storage: &dyn Storage, dir: &Path, ext: &str) -> Result<bool> {
+    let names = storage.list(dir).await?;
+    let is_audio =
+        |name: &OsString| Path::new(name).extension().and_then(|e| e.to_str()) == Some(ext);
+    if names.iter().any(is_audio) {
+        return Ok(true);
+    }
+    for disc in names
+        .iter()
+        .filter(|n| n.to_string_lossy().starts_with("Disc "))
+    {
+        if storage.list(&dir.join(disc)).await?.iter().any(is_audio) {
+            return Ok(true);
         }
     }
-    false
+    Ok(false)
 }
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::bandcamp::BandcampPurchases;
+use crate::download::item_album;
+use crate::models::{AudioFormat, SizeMismatch, SizeMismatchPolicy, SkipReason, SyncPlan};
+use crate::path::{Naming, album_dir, is_dated_album_dir, keep_both_path};
+use crate::space::human;
+use crate::transcode::{TranscodeTarget, planned_format};
+
+/// How a planned track compares with what's on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Not there yet; would be downloaded.
+    Download,
+    /// Already synced.
+    Synced,
+    /// Something is there, but not what sync expects: a file of an
+    /// implausible size, or of another format.
+    Mismatch,
+}
+
+impl Change {
+    pub fn marker(self) -> char {
+        match self {
+            Change::Download => '+',
+            Change::Synced => '=',
+            Change::Mismatch => '!',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub change: Change,
+    /// The file, relative to its album's directory.
+    pub name: String,
+    /// What doesn't match, for mismatches.
+    pub note: Option<String>,
+    /// About how many bytes it takes to download, for lines that would be.
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumDiff {
+    /// "Artist - Album"
+    pub title: String,
+    pub lines: Vec<DiffLine>,
+}
+
+impl AlbumDiff {
+    /// About how many bytes the album's downloads take.
+    pub fn bytes(&self) -> u64 {
+        self.lines.iter().filter_map(|l| l.size).sum()
+    }
+}
+
+/// What a dry run found: each album's planned tracks, marked by how they
+/// compare with the target directory's contents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub albums: Vec<AlbumDiff>,
+}
+
+impl Diff {
+    /// How many lines are marked `change`.
+    pub fn count(&self, change: Change) -> usize {
+        self.albums
+            .iter()
+            .flat_map(|a| &a.lines)
+            .filter(|l| l.change == change)
+            .count()
+    }
+
+    /// About how many bytes the whole run would download.
+    pub fn bytes(&self) -> u64 {
+        self.albums.iter().map(AlbumDiff::bytes).sum()
+    }
+
+    /// The diff as printed: an album heading with the size of its
+    /// downloads, then one `+`, `=` or `!` line per track.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for album in &self.albums {
+            out.push_str(&album.title);
+            if album.lines.iter().any(|l| l.size.is_some()) {
+                out.push_str(&format!("  (~{} to download)", human(album.bytes())));
+            }
+            out.push('\n');
+            for line in &album.lines {
+                out.push_str(&format!("  {} {}", line.change.marker(), line.name));
+                if let Some(size) = line.size {
+                    out.push_str(&format!("  ~{}", human(size)));
+                }
+                if let Some(note) = &line.note {
+                    out.push_str(&format!("  ({note})"));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// The directory next to `dir` that is its album under some year, as
+/// Bandcamp albums are once their download page has given it.
+fn dated_album_dir(dir: &Path) -> Option<PathBuf> {
+    let undated = dir.file_name()?.to_string_lossy();
+    std::fs::read_dir(dir.parent()?)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| is_dated_album_dir(&e.file_name().to_string_lossy(), &undated))
+        .map(|e| e.path())
+}
+
+/// Directory listings, read once per directory.
+#[derive(Default)]
+struct Listings(HashMap<PathBuf, Vec<OsString>>);
+
+impl Listings {
+    fn get(&mut self, dir: &Path) -> &[OsString] {
+        self.0.entry(dir.to_path_buf()).or_insert_with(|| {
+            let mut names: Vec<OsString> = std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+                .map(|e| e.file_name())
+                .collect();
+            names.sort();
+            names
+        })
+    }
+
+    /// An audio file next to `path` with its name but another extension:
+    /// the track synced in another format, or tagged by hand.
+    fn other_format(&mut self, path: &Path) -> Option<String> {
+        let (dir, stem) = (path.parent()?, path.file_stem()?);
+        let ext = path.extension();
+        self.get(dir)
+            .iter()
+            .map(Path::new)
+            .find(|name| {
+                name.file_stem() == Some(stem) && name.extension() != ext && is_audio(name)
+            })
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+fn is_audio(name: &Path) -> bool {
+    name.extension()
+        .and_then(|e| e.to_str())
+        .and_then(AudioFormat::from_extension)
+        .is_some()
+}
+
+fn size_note(mismatch: &SizeMismatch) -> String {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    let action = match mismatch.resolution {
+        SizeMismatchPolicy::Keep => "kept",
+        SizeMismatchPolicy::Overwrite => "would be replaced",
+        SizeMismatchPolicy::KeepBoth => "new copy would be saved alongside",
+    };
+    format!(
+        "{:.1} MB on disk, expected {:.1}-{:.1} MB; {action}",
+        mb(mismatch.actual),
+        mb(*mismatch.expected.start()),
+        mb(*mismatch.expected.end()),
+    )
+}
+
+/// Compare a dry run's plan with the target directory. Tracks sync would
+/// download are `+`, unless the file they'd replace has an implausible
+/// size or the track is there in another format (`!`); tracks already
+/// synced are `=`. Tracks left out for other reasons aren't listed.
+pub fn plan_diff(plan: &SyncPlan) -> Diff {
+    let mut listings = Listings::default();
+    let mut diff = Diff::default();
+    for album in &plan.albums {
+        let Some(first) = album.skipped.first() else {
+            continue;
+        };
+        // Multi-disc albums' tracks are a "Disc N" directory further down.
+        let base = match album.album.media_count {
+            0 | 1 => first.target_path.parent(),
+            _ => first.target_path.parent().and_then(Path::parent),
+        }
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+
+        let mut lines = Vec::new();
+        for track in &album.skipped {
+            let mismatch = plan.size_mismatches.iter().find(|m| {
+                m.path == track.target_path || keep_both_path(&m.path) == track.target_path
+            });
+            let (change, note) = match track.reason {
+                SkipReason::AlreadyExists => (Change::Synced, None),
+                SkipReason::SizeMismatch | SkipReason::DryRun if mismatch.is_some() => {
+                    (Change::Mismatch, mismatch.map(size_note))
+                }
+                SkipReason::DryRun => match listings.other_format(&track.target_path) {
+                    Some(name) => (Change::Mismatch, Some(format!("{name} is there instead"))),
+                    None => (Change::Download, None),
+                },
+                _ => continue,
+            };
+            let name = track
+                .target_path
+                .strip_prefix(&base)
+                .unwrap_or(&track.target_path);
+            let downloads = matches!(track.reason, SkipReason::DryRun);
+            lines.push(DiffLine {
+                change,
+                name: name.display().to_string(),
+                note,
+                size: downloads.then(|| track.estimated_bytes()),
+            });
+        }
+        if !lines.is_empty() {
+            diff.albums.push(AlbumDiff {
+                title: format!("{} - {}", album.album.artist.name, album.album.title),
+                lines,
+            });
+        }
+    }
+    diff
+}
+
+/// Compare Bandcamp purchases with the target directory. Bandcamp delivers
+/// whole albums, so an album not synced yet is a single `+` line, sized by
+/// `sizes` (by item id, as Bandcamp gives them); a synced one lists its
+/// files as `=`. An album directory holding audio files, but none in the
+/// format sync looks for, lists them as `!`, and is downloaded again.
+pub fn bandcamp_diff(
+    purchases: &BandcampPurchases,
+    target_dir: &Path,
+    sizes: &HashMap<u64, u64>,
+    naming: Naming,
+    transcode: Option<TranscodeTarget>,
+) -> Diff {
+    let mut listings = Listings::default();
+    let ext = &planned_format(AudioFormat::Aac, transcode).extension()[1..];
+    let mut diff = Diff::default();
+    for item in &purchases.items {
+        let mut dir = album_dir(target_dir, &item_album(item), naming);
+        if naming.album_years
+            && !dir.is_dir()
+            && let Some(dated) = dated_album_dir(&dir)
+        {
+            dir = dated;
+        }
+        let audio: Vec<&Path> = listings
+            .get(&dir)
+            .iter()
+            .map(Path::new)
+            .filter(|name| is_audio(name))
+            .collect();
+        let synced = audio
+            .iter()
+            .any(|name| name.extension().and_then(|e| e.to_str()) == Some(ext));
+        let change = if synced {
+            Change::Synced
+        } else {
+            Change::Mismatch
+        };
+        let mut lines: Vec<DiffLine> = audio
+            .iter()
+            .map(|name| DiffLine {
+                change,
+                name: name.display().to_string(),
+                note: (!synced).then(|| format!("not .{ext}")),
+                size: None,
+            })
+            .collect();
+        if !synced {
+            lines.push(DiffLine {
+                change: Change::Download,
+                name: "whole album".to_string(),
+                note: None,
+                size: sizes.get(&item.item_id).copied(),
+            });
+        }
+        diff.albums.push(AlbumDiff {
+            title: format!("{} - {}", item.band_name, item.item_title),
+            lines,
+        });
+    }
+    diff
+}
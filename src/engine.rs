@@ -0,0 +1,1569 @@
+//! Running a sync: every configured service's purchases fetched, planned
+//! against the target directory, and downloaded, with the steps around it
+//! (lock, hooks, failures file, media servers, webhook). The `qoget`
+//! binary's `sync`, `get`, `retry`, and `backfill` commands are built on
+//! [`SyncEngine`], and other programs can embed it the same way.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use tracing::{Instrument, debug, error, info, info_span, warn};
+
+use crate::{
+    bandcamp, beets, bundle, checksums, client, config, deep_scan, download, dry_run, hooks,
+    http_cache, lock, logging, manifest, media_server, models, musicbrainz, network, notify,
+    orphans, path, playlist, progress, prune, replaygain, report, select, session, snapshot, space,
+    storage, sync, trash,
+};
+
+/// A sync of one target directory, ready to run.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use qoget::engine::{SyncEngine, SyncOptions};
+///
+/// let cfg = qoget::config::load_config()?;
+/// let run = SyncEngine::new(cfg, "/music", SyncOptions::default())
+///     .run()
+///     .await?;
+/// for service in &run.report.services {
+///     println!("{}: {} downloaded", service.service, service.succeeded.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyncEngine {
+    cfg: config::Config,
+    target_dir: PathBuf,
+    opts: SyncOptions,
+    service: Option<models::Service>,
+    wait_lock: bool,
+    before_release: Option<BeforeRelease>,
+}
+
+/// Called with a run's report while the target is still locked.
+type BeforeRelease = Box<dyn FnOnce(&report::SyncReport) -> Result<()> + Send>;
+
+/// How a [`SyncEngine`] run went.
+pub struct SyncRun {
+    pub report: report::SyncReport,
+    /// Why the run failed, if a service did; the report says which.
+    pub result: Result<()>,
+}
+
+impl SyncEngine {
+    /// Sync `target_dir` with the services `cfg` configures. Settings
+    /// `opts` leaves unset are filled in from `cfg`.
+    pub fn new(cfg: config::Config, target_dir: impl Into<PathBuf>, opts: SyncOptions) -> Self {
+        Self {
+            opts: opts.with_config(&cfg),
+            cfg,
+            target_dir: target_dir.into(),
+            service: None,
+            wait_lock: false,
+            before_release: None,
+        }
+    }
+
+    /// Only sync `service`.
+    pub fn service(mut self, service: models::Service) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    /// Wait for another sync of the target to finish instead of failing.
+    pub fn wait_lock(mut self, wait: bool) -> Self {
+        self.wait_lock = wait;
+        self
+    }
+
+    /// Call `f` with the finished report while the target is still locked
+    /// and before the `post_sync` hook, e.g. to save state kept in the
+    /// target. An error from it is the run's error, once the hook has run.
+    pub fn before_release(
+        mut self,
+        f: impl FnOnce(&report::SyncReport) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.before_release = Some(Box::new(f));
+        self
+    }
+
+    /// Report each download's progress to `sink` as well as to the bars,
+    /// which [`SyncOptions::quiet`] turns off.
+    pub fn events(mut self, sink: Arc<dyn progress::SyncEvents>) -> Self {
+        self.opts.events = progress::Events::new(sink);
+        self
+    }
+
+    /// The options the run uses, with the config's filled in.
+    pub fn options(&self) -> &SyncOptions {
+        &self.opts
+    }
+
+    /// Run the sync: the `pre_sync` hook is run and the target locked, then
+    /// each service synced; afterwards failures are saved, media servers
+    /// and the webhook told, the lock released and the `post_sync` hook run.
+    ///
+    /// Everything the run is set up with comes from its [`SyncOptions`] and
+    /// only applies to this run; nothing is left set for the process.
+    ///
+    /// Errors are for runs that never got as far as the services; how they
+    /// went is in the returned [`SyncRun`].
+    pub async fn run(self) -> Result<SyncRun> {
+        let Self {
+            cfg,
+            target_dir,
+            opts,
+            service,
+            wait_lock,
+            before_release,
+        } = self;
+        // What the target was given as; a remote target is synced through
+        // a local working directory.
+        let target = target_dir;
+        let (target_dir, opts) = match storage::Remote::parse(&target, &opts.network)? {
+            Some(remote) => remote_target(remote, opts)?,
+            None => (target.clone(), opts),
+        };
+        if !opts.dry_run {
+            check_ffmpeg(&opts)?;
+        }
+        let target_dir = &target_dir;
+        let hook_cfg = cfg.hooks.clone();
+        if let Some(ref command) = hook_cfg.pre_sync {
+            hooks::pre_sync(command, &target, opts.dry_run).await?;
+        }
+        // A dry run writes nothing, so it needn't keep a real sync waiting.
+        let lock = if opts.dry_run {
+            None
+        } else {
+            Some(lock::SyncLock::acquire(target_dir, wait_lock)?)
+        };
+        if lock.is_some() {
+            clean_orphans(target_dir);
+        }
+
+        let webhook_url = cfg.notifications.webhook_url.clone();
+        let integrations = cfg.integrations.clone();
+        let mut report = report::SyncReport::new(&target, opts.dry_run);
+        let result = sync_services(cfg, target_dir, &opts, service, &mut report).await;
+        report.finish(&result);
+        save_failures(&report);
+        media_server::refresh(&integrations, &opts.network, &report).await;
+        if let Some(url) = webhook_url {
+            notify_webhook(&url, &report, &opts.network).await;
+        }
+        let recorded = before_release.map_or(Ok(()), |f| f(&report));
+        // Released first, so the hook can unmount the target.
+        drop(lock);
+        run_post_sync(&hook_cfg, &report).await;
+        recorded?;
+        Ok(SyncRun { report, result })
+    }
+}
+
+/// Sync into `remote` through its local working directory, without the
+/// steps that need the library's files on this machine.
+fn remote_target(remote: storage::Remote, mut opts: SyncOptions) -> Result<(PathBuf, SyncOptions)> {
+    if opts.prune.is_some() {
+        bail!(
+            "--prune needs the library on this machine; {} is remote",
+            remote.url()
+        );
+    }
+    if opts.deep_scan {
+        bail!(
+            "--deep-scan needs the library on this machine; {} is remote",
+            remote.url()
+        );
+    }
+    let mut skipped = Vec::new();
+    if std::mem::take(&mut opts.replaygain) {
+        skipped.push("ReplayGain");
+    }
+    if opts.musicbrainz.take().is_some() {
+        skipped.push("MusicBrainz enrichment");
+    }
+    if std::mem::take(&mut opts.beets_import) {
+        skipped.push("beets import");
+    }
+    if opts.recently_added.take().is_some() {
+        skipped.push("the Recently Added playlist");
+    }
+    if !skipped.is_empty() {
+        warn!(
+            "{} skipped for the remote target {}",
+            skipped.join(", "),
+            remote.url()
+        );
+    }
+    let root = remote.root().to_path_buf();
+    std::fs::create_dir_all(&root).with_context(|| format!("creating {}", root.display()))?;
+    info!(
+        "Syncing to {} (working directory {})",
+        remote.url(),
+        root.display()
+    );
+    opts.storage = storage::Shared::new(Arc::new(remote));
+    Ok((root, opts))
+}
+
+/// Remove temp files an interrupted run left in the target, before
+/// anything is planned from what is there.
+fn clean_orphans(target_dir: &Path) {
+    match orphans::clean(target_dir) {
+        Ok(removed) if removed.is_empty() => {}
+        Ok(removed) => {
+            info!(
+                "Removed {} leftover temp file(s) from an interrupted run",
+                removed.len()
+            );
+            for path in removed {
+                debug!("removed {}", path.display());
+            }
+        }
+        Err(e) => warn!("could not clean up leftover temp files: {e:#}"),
+    }
+}
+
+/// What `sync --prune` does with synced tracks no longer purchased.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PruneMode {
+    /// Only list them: `--prune-dry-run`, or `--prune` with `--dry-run`.
+    List,
+    /// Move them to the trash once confirmed.
+    Trash,
+}
+
+/// Per-run settings shared by every service's sync. The fields are named
+/// for the `sync` flags and config keys that set them in the `qoget`
+/// binary; the default is a plain `sync` with nothing from the config.
+#[derive(Clone, Default)]
+pub struct SyncOptions {
+    pub dry_run: bool,
+    pub gifts: models::GiftFilter,
+    /// From `--on-size-mismatch`; falls back to the config file.
+    pub on_size_mismatch: Option<models::SizeMismatchPolicy>,
+    /// From `--quality`, or `quality` under [qobuz].
+    pub quality: Option<models::QobuzQuality>,
+    /// From `--ignore-space`: warn instead of failing when the target looks
+    /// too full for the downloads.
+    pub ignore_space: bool,
+    pub limits: download::DownloadLimits,
+    /// From `unknown_track_numbers` under [paths] in the config file.
+    pub unknown_track_numbers: Option<models::UnknownTrackNumbers>,
+    /// From `--windows-safe-names`, and `track_versions`, `album_versions`,
+    /// `album_years` and `unicode_normalization` under [paths] in the
+    /// config file.
+    pub naming: path::Naming,
+    /// From `--keep-zips`, or `keep_archives` under [bandcamp].
+    pub keep_archives: bool,
+    /// From `--replaygain`, or `replaygain` under [sync].
+    pub replaygain: bool,
+    /// From `recently_added` under [sync]: how many tracks `Recently
+    /// Added.m3u8` keeps, if it is kept at all.
+    pub recently_added: Option<usize>,
+    /// From `--deep-scan`: also look for purchases by the tags of the files
+    /// in the target, not just at their planned paths.
+    pub deep_scan: bool,
+    /// From `--prune` and `--prune-dry-run`.
+    pub prune: Option<PruneMode>,
+    /// From `--interactive`: ask which albums to download before starting.
+    pub interactive: bool,
+    /// From `--artist` and `--album`.
+    pub names: models::NameFilter,
+    /// From `--since`.
+    pub since: Option<models::Since>,
+    /// From `--include-favorites`: also sync Qobuz favorites the account
+    /// has bought.
+    pub include_favorites: bool,
+    /// From `get`: sync this one album or track instead of the purchases.
+    pub item: Option<models::GetItem>,
+    /// From `retry`: download again only what the last run failed on.
+    pub retry: Option<report::RetryList>,
+    /// From `auto_import` under [integrations.beets].
+    pub beets_import: bool,
+    /// From [integrations.musicbrainz], when `enrich` is on.
+    pub musicbrainz: Option<config::MusicBrainzConfig>,
+    /// From `target` under [transcode].
+    pub transcode: Option<crate::transcode::TranscodeTarget>,
+    /// From the request settings under [qobuz] and [bandcamp]: how each
+    /// service's client paces and retries requests.
+    pub requests: config::RequestsConfig,
+    /// From [network]: how every HTTP client connects.
+    pub network: config::NetworkConfig,
+    /// From `--quiet`: draw no progress bars.
+    pub quiet: bool,
+    /// Where download progress is reported besides the bars, for embedders.
+    pub events: progress::Events,
+    /// Where the library is kept. Set by [`SyncEngine::run`] for a remote
+    /// target, whose local working directory is then the target directory.
+    pub storage: storage::Shared,
+}
+
+impl SyncOptions {
+    /// Fill in settings the command line left unset from the config file.
+    pub fn with_config(self, cfg: &config::Config) -> Self {
+        Self {
+            on_size_mismatch: self.on_size_mismatch.or(cfg.sync.on_size_mismatch),
+            quality: self.quality.or(cfg.qobuz_quality),
+            unknown_track_numbers: cfg.paths.unknown_track_numbers,
+            naming: path::Naming {
+                windows_safe: self.naming.windows_safe,
+                ..cfg.paths.naming()
+            },
+            keep_archives: self.keep_archives || cfg.keep_bandcamp_archives,
+            replaygain: self.replaygain || cfg.sync.replaygain.unwrap_or(false),
+            recently_added: cfg.sync.recently_added,
+            beets_import: cfg.integrations.beets.auto_import,
+            musicbrainz: Some(cfg.integrations.musicbrainz.clone()).filter(|m| m.enrich),
+            transcode: cfg.transcode.target,
+            requests: cfg.requests.clone(),
+            network: cfg.network.clone(),
+            ..self
+        }
+    }
+
+    /// Whether only some purchases are to be synced, by name or date.
+    fn narrowed(&self) -> bool {
+        !self.names.is_empty() || self.since.is_some()
+    }
+
+    /// "--artist 'X' and --since 30d", for log lines.
+    fn describe_narrowing(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(artist) = &self.names.artist {
+            parts.push(format!("--artist '{artist}'"));
+        }
+        if let Some(album) = &self.names.album {
+            parts.push(format!("--album '{album}'"));
+        }
+        if let Some(since) = &self.since {
+            parts.push(format!("--since {since}"));
+        }
+        parts.join(" and ")
+    }
+}
+
+/// Add a service's newly synced tracks to `Recently Added.m3u8`, if the
+/// config keeps one. A failure only costs the playlist, not the sync.
+fn add_recently_added(target_dir: &Path, opts: &SyncOptions, entries: &[&manifest::ManifestEntry]) {
+    let Some(cap) = opts.recently_added.filter(|&cap| cap > 0) else {
+        return;
+    };
+    if entries.is_empty() {
+        return;
+    }
+    match playlist::add_recently_added(target_dir, entries, cap) {
+        Ok(path) => debug!("added {} tracks to {}", entries.len(), path.display()),
+        Err(e) => warn!("could not update {}: {e:#}", playlist::RECENTLY_ADDED),
+    }
+}
+
+/// List what `sync --prune` finds of `service` that its purchases no longer
+/// include, and in `Trash` mode move it to the trash once the user agrees.
+fn prune_service(
+    target_dir: &Path,
+    manifest: &mut manifest::Manifest,
+    service: models::Service,
+    purchased: &prune::Purchased,
+    mode: PruneMode,
+) -> Result<()> {
+    let stale = prune::stale_tracks(manifest, service, purchased)?;
+    if stale.is_empty() {
+        info!("{service}: nothing to prune");
+        return Ok(());
+    }
+    for track in &stale {
+        println!(
+            "[prune] {service:<8} {} / {} / {}",
+            track.artist, track.album, track.title
+        );
+    }
+    if mode == PruneMode::List {
+        info!(
+            "Dry run: {} {service} tracks would be moved to the trash",
+            stale.len()
+        );
+        return Ok(());
+    }
+    let question = format!("Move these {} {service} tracks to the trash?", stale.len());
+    if !select::confirm(&question)? {
+        info!("Nothing pruned");
+        return Ok(());
+    }
+    let batch = prune::prune(target_dir, manifest, &stale)?;
+    manifest.save(target_dir)?;
+    info!("Moved {} tracks to {}", stale.len(), batch.display());
+    Ok(())
+}
+
+/// Read the tags of every audio file in the target, for `--deep-scan`.
+async fn build_tag_index(target_dir: &Path) -> Result<deep_scan::TagIndex> {
+    info!("Reading tags of the files in {}...", target_dir.display());
+    let root = target_dir.to_path_buf();
+    let index = tokio::task::spawn_blocking(move || deep_scan::TagIndex::build(&root)).await??;
+    debug!("read tags of {} files", index.len());
+    Ok(index)
+}
+
+/// Fail before anything is downloaded if a step that runs ffmpeg is on and
+/// ffmpeg can't be run.
+pub fn check_ffmpeg(opts: &SyncOptions) -> Result<()> {
+    if opts.transcode.is_some() {
+        crate::transcode::check_ffmpeg("transcoding")?;
+    }
+    if opts.replaygain {
+        crate::transcode::check_ffmpeg("ReplayGain tagging")?;
+    }
+    if opts.musicbrainz.is_some() {
+        crate::transcode::check_ffmpeg("MusicBrainz tagging")?;
+    }
+    Ok(())
+}
+
+/// Keep a real run's failed downloads in the state directory, where they
+/// outlast the terminal's scrollback. Not being able to is only a warning.
+pub fn save_failures(report: &report::SyncReport) {
+    if report.dry_run {
+        return;
+    }
+    let path = config::state_dir().join(report::FAILURES_FILE);
+    match report.write_failures(&path) {
+        Ok(0) => {}
+        Ok(n) => info!(
+            target: logging::SUMMARY,
+            "{n} failed downloads listed in {}",
+            path.display()
+        ),
+        Err(e) => warn!("{e:#}"),
+    }
+}
+
+/// Run the `[hooks] post_sync` command, if there is one. A failure is
+/// reported, but doesn't change how the sync went.
+pub async fn run_post_sync(hook_cfg: &config::HooksConfig, report: &report::SyncReport) {
+    if let Some(ref command) = hook_cfg.post_sync
+        && let Err(e) = hooks::post_sync(command, report).await
+    {
+        warn!("{e:#}");
+    }
+}
+
+/// POST a run summary to the configured webhook. Failure only warns: the
+/// sync itself is done.
+pub async fn notify_webhook(
+    url: &str,
+    report: &report::SyncReport,
+    network: &config::NetworkConfig,
+) {
+    match notify::post_webhook(url, report, network).await {
+        Ok(()) => debug!("webhook notified"),
+        Err(e) => warn!("webhook notification failed: {e:#}"),
+    }
+}
+
+/// Sync each configured service (or only `service_filter`), recording the
+/// outcome in `report`.
+async fn sync_services(
+    cfg: config::Config,
+    target_dir: &Path,
+    opts: &SyncOptions,
+    service_filter: Option<models::Service>,
+    report: &mut report::SyncReport,
+) -> Result<()> {
+    let should_run = |svc: models::Service| -> bool { service_filter.is_none_or(|f| f == svc) };
+
+    let has_bandcamp = cfg.bandcamp.is_some();
+    let qobuz_configured = cfg.qobuz.is_configured();
+
+    if !qobuz_configured && !has_bandcamp {
+        if service_filter.is_some() && service_filter != Some(models::Service::Qobuz) {
+            bail!(
+                "Bandcamp is not configured.\n\n\
+                 Add to ~/.config/qoget/config.toml:\n\n  \
+                 [bandcamp]\n  \
+                 identity_cookie = \"YOUR_COOKIE\"\n\n\
+                 To get the cookie: log in to bandcamp.com, open browser dev tools (F12),\n\
+                 go to Application > Cookies > bandcamp.com, and copy the 'identity' cookie value.\n\n\
+                 Or set the BANDCAMP_IDENTITY environment variable."
+            );
+        }
+        // Nothing configured from file/env — try interactive Qobuz login
+        let qobuz_cfg = config::prompt_qobuz_credentials()?;
+        info!("Syncing Qobuz...");
+        let login = config::QobuzLogin::Password(qobuz_cfg);
+        return run_qobuz_sync(login, target_dir, opts, report).await;
+    }
+
+    let mut any_failure = false;
+
+    if should_run(models::Service::Qobuz) {
+        match cfg.qobuz {
+            config::QobuzState::Ready(qobuz_cfg) => {
+                info!("Syncing Qobuz...");
+                let login = config::QobuzLogin::Password(qobuz_cfg);
+                if let Err(e) = run_qobuz_sync(login, target_dir, opts, report).await {
+                    error!("Qobuz sync failed: {e:#}");
+                    any_failure = true;
+                }
+            }
+            config::QobuzState::Saved(session) => {
+                info!("Syncing Qobuz...");
+                let login = config::QobuzLogin::Saved(session);
+                if let Err(e) = run_qobuz_sync(login, target_dir, opts, report).await {
+                    error!("Qobuz sync failed: {e:#}");
+                    any_failure = true;
+                }
+            }
+            config::QobuzState::Incomplete => {
+                // Username found but password missing — prompt for it
+                match config::prompt_qobuz_credentials() {
+                    Ok(qobuz_cfg) => {
+                        info!("Syncing Qobuz...");
+                        let login = config::QobuzLogin::Password(qobuz_cfg);
+                        if let Err(e) = run_qobuz_sync(login, target_dir, opts, report).await {
+                            error!("Qobuz sync failed: {e:#}");
+                            any_failure = true;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Qobuz sync skipped: {e:#}");
+                        report.add(report::ServiceReport::new(models::Service::Qobuz), &Err(e));
+                        any_failure = true;
+                    }
+                }
+            }
+            config::QobuzState::NotConfigured if service_filter.is_some() => {
+                // User explicitly requested Qobuz but nothing configured
+                match config::prompt_qobuz_credentials() {
+                    Ok(qobuz_cfg) => {
+                        info!("Syncing Qobuz...");
+                        let login = config::QobuzLogin::Password(qobuz_cfg);
+                        if let Err(e) = run_qobuz_sync(login, target_dir, opts, report).await {
+                            error!("Qobuz sync failed: {e:#}");
+                            any_failure = true;
+                        }
+                    }
+                    Err(e) => bail!("Qobuz is not configured: {e:#}"),
+                }
+            }
+            config::QobuzState::NotConfigured => {}
+        }
+    }
+
+    if should_run(models::Service::Bandcamp) {
+        match cfg.bandcamp {
+            Some(bandcamp_cfg) => {
+                info!("Syncing Bandcamp...");
+                if let Err(e) = run_bandcamp_sync(bandcamp_cfg, target_dir, opts, report).await {
+                    error!("Bandcamp sync failed: {e:#}");
+                    any_failure = true;
+                }
+            }
+            None if service_filter.is_some() => {
+                bail!(
+                    "Bandcamp is not configured.\n\n\
+                     Add to ~/.config/qoget/config.toml:\n\n  \
+                     [bandcamp]\n  \
+                     identity_cookie = \"YOUR_COOKIE\"\n\n\
+                     To get the cookie: log in to bandcamp.com, open browser dev tools (F12),\n\
+                     go to Application > Cookies > bandcamp.com, and copy the 'identity' cookie value.\n\n\
+                     Or set the BANDCAMP_IDENTITY environment variable."
+                );
+            }
+            None => {}
+        }
+    }
+
+    // Hint about unconfigured services (only when no --service filter)
+    if service_filter.is_none() {
+        if !qobuz_configured && has_bandcamp {
+            info!(
+                "\nHint: Qobuz sync is also available. \
+                 Set QOBUZ_USERNAME/QOBUZ_PASSWORD or add [qobuz] to config."
+            );
+        }
+        if !has_bandcamp && qobuz_configured {
+            info!(
+                "\nHint: Bandcamp sync is also available. \
+                 Set BANDCAMP_IDENTITY or add [bandcamp] to config."
+            );
+        }
+    }
+
+    if any_failure {
+        bail!("One or more services failed: {}", report.failed_services());
+    }
+
+    Ok(())
+}
+
+/// Cache the fetched purchase list for offline commands. Failure only warns:
+/// the cache is a convenience and must not break a sync.
+fn save_snapshot(snap: &snapshot::Snapshot) {
+    if let Err(e) = snap.save(&config::cache_dir()) {
+        warn!("could not cache {} purchase list: {e:#}", snap.service);
+    }
+}
+
+/// Keep the identity cookie Bandcamp rotated during a sync in the session
+/// `qoget login` saved, if that is where the cookie came from. A cookie
+/// from the config file or environment is left for the user to update.
+/// Failure only warns, like `save_snapshot`.
+fn save_refreshed_identity(client: &bandcamp::BandcampClient, saved_session: bool) {
+    let Some(identity) = client.refreshed_identity() else {
+        return;
+    };
+    if !saved_session {
+        debug!("Bandcamp rotated the identity cookie; only saved sessions are updated");
+        return;
+    }
+    let config_dir = config::config_dir();
+    let result = session::SessionFile::load(&config_dir).and_then(|mut sessions| {
+        let Some(ref mut saved) = sessions.bandcamp else {
+            return Ok(());
+        };
+        saved.identity_cookie = identity;
+        sessions.save(&config_dir)
+    });
+    match result {
+        Ok(()) => debug!("saved Bandcamp's rotated identity cookie"),
+        Err(e) => warn!("could not save the rotated Bandcamp cookie: {e:#}"),
+    }
+}
+
+/// Log in to Qobuz with a password, returning the session `qoget login`
+/// would save.
+pub async fn qobuz_password_login(
+    http: &reqwest::Client,
+    qobuz_cfg: config::QobuzConfig,
+) -> Result<session::QobuzSession> {
+    let config::QobuzConfig {
+        username,
+        password,
+        app_id,
+        app_secret,
+    } = qobuz_cfg;
+
+    let creds = match (app_id, app_secret) {
+        (Some(id), Some(secret)) => models::AppCredentials {
+            app_id: id,
+            app_secret: secret,
+        },
+        _ => bundle::cached_credentials(http, &config::cache_dir()).await?,
+    };
+
+    info!("Logging in to Qobuz...");
+    let auth = client::login(http, &creds.app_id, &username, &password).await?;
+    info!("Logged in as user {}", auth.user_id);
+
+    Ok(session::QobuzSession {
+        app_id: creds.app_id,
+        app_secret: creds.app_secret,
+        user_auth_token: auth.token,
+        user_id: auth.user_id,
+    })
+}
+
+/// Log in to Qobuz. Also says whether a saved session was used, since its
+/// token may have expired since.
+pub async fn qobuz_client(
+    login: config::QobuzLogin,
+    opts: &SyncOptions,
+) -> Result<(client::QobuzClient, bool)> {
+    let http = network::client(&opts.network)?;
+
+    let (session, saved) = match login {
+        config::QobuzLogin::Password(qobuz_cfg) => {
+            (qobuz_password_login(&http, qobuz_cfg).await?, false)
+        }
+        config::QobuzLogin::Saved(session) => {
+            info!("Using saved Qobuz session for user {}", session.user_id);
+            (session, true)
+        }
+    };
+
+    let qobuz = client::QobuzClient::new(
+        http,
+        session.app_id,
+        session.app_secret,
+        session.user_auth_token,
+        opts.requests.qobuz.clone(),
+    )
+    .with_cache(http_cache::HttpCache::new(config::cache_dir().join("http")));
+    Ok((qobuz, saved))
+}
+
+/// Log in to Qobuz (or reuse a saved session) and fetch the purchase list
+/// (album track listings are not filled in).
+pub async fn fetch_qobuz_purchases(
+    login: config::QobuzLogin,
+    opts: &SyncOptions,
+) -> Result<(client::QobuzClient, models::PurchaseList)> {
+    let (qobuz, saved) = qobuz_client(login, opts).await?;
+
+    let multi = progress::multi(opts.quiet);
+    let pb = progress::spinner(&multi, "Fetching Qobuz purchases");
+    let purchases = qobuz.get_purchases(&pb).await;
+    pb.finish_and_clear();
+    let purchases = if saved {
+        purchases.context(
+            "Fetching purchases with the saved Qobuz session failed \
+             (run `qoget login qobuz` if it has expired)",
+        )?
+    } else {
+        purchases?
+    };
+    info!(
+        "Found {} albums and {} standalone tracks",
+        purchases.albums.len(),
+        purchases.tracks.len()
+    );
+
+    Ok((qobuz, purchases))
+}
+
+/// Log in to Qobuz (or reuse a saved session) and fetch just `items`, for
+/// `get` and `retry`, as a purchase list of those albums (with their
+/// tracks) and tracks.
+async fn fetch_qobuz_items(
+    login: config::QobuzLogin,
+    items: &[models::QobuzItem],
+    opts: &SyncOptions,
+) -> Result<(client::QobuzClient, models::PurchaseList)> {
+    let (qobuz, saved) = qobuz_client(login, opts).await?;
+    let fetched = async {
+        let mut list = models::PurchaseList {
+            albums: Vec::new(),
+            tracks: Vec::new(),
+        };
+        for item in items {
+            match item {
+                models::QobuzItem::Album(id) => {
+                    let album = qobuz.get_album(id).await?;
+                    info!("Found {} - {}", album.artist.name, album.title);
+                    list.albums.push(album);
+                }
+                models::QobuzItem::Track(id) => {
+                    let track = qobuz.get_track(*id).await?;
+                    info!("Found {} - {}", track.performer.name, track.title);
+                    list.tracks.push(track);
+                }
+            }
+        }
+        anyhow::Ok(list)
+    }
+    .await;
+    let fetched = if saved {
+        fetched.context(
+            "Fetching the item with the saved Qobuz session failed \
+             (run `qoget login qobuz` if it has expired)",
+        )?
+    } else {
+        fetched?
+    };
+    Ok((qobuz, fetched))
+}
+
+/// Favorites, for `sync --include-favorites`.
+async fn fetch_qobuz_favorites(
+    qobuz: &client::QobuzClient,
+    opts: &SyncOptions,
+) -> Result<models::PurchaseList> {
+    let multi = progress::multi(opts.quiet);
+    let pb = progress::spinner(&multi, "Fetching Qobuz favorites");
+    let favorites = qobuz.get_favorites(&pb).await;
+    pb.finish_and_clear();
+    let favorites = favorites?;
+    info!(
+        "Found {} favorite albums and {} favorite tracks",
+        favorites.albums.len(),
+        favorites.tracks.len()
+    );
+    Ok(favorites)
+}
+
+/// Fetch track listings for albums the purchase list returned without them.
+pub async fn fill_album_tracks(
+    qobuz: &client::QobuzClient,
+    purchases: &mut models::PurchaseList,
+    opts: &SyncOptions,
+) -> Result<()> {
+    let missing = purchases
+        .albums
+        .iter()
+        .filter(|a| a.tracks.is_none())
+        .count();
+    if missing == 0 {
+        return Ok(());
+    }
+
+    let multi = progress::multi(opts.quiet);
+    if multi.is_hidden() {
+        info!("Fetching track listings for {missing} albums...");
+    }
+    let pb = progress::counter(&multi, missing as u64);
+    let result = async {
+        for album in &mut purchases.albums {
+            if album.tracks.is_none() {
+                pb.set_message(format!("{} - {}", album.artist.name, album.title));
+                let full = qobuz.get_album(&album.id).await?;
+                album.tracks = full.tracks;
+                pb.inc(1);
+            }
+        }
+        Ok(())
+    }
+    .await;
+    pb.finish_and_clear();
+    result
+}
+
+pub async fn run_qobuz_sync(
+    login: config::QobuzLogin,
+    target_dir: &Path,
+    opts: &SyncOptions,
+    report: &mut report::SyncReport,
+) -> Result<()> {
+    let mut service_report = report::ServiceReport::new(models::Service::Qobuz);
+    let result = qobuz_sync(login, target_dir, opts, &mut service_report)
+        .instrument(info_span!("service", name = "qobuz"))
+        .await;
+    report.add(service_report, &result);
+    result
+}
+
+async fn qobuz_sync(
+    login: config::QobuzLogin,
+    target_dir: &Path,
+    opts: &SyncOptions,
+    report: &mut report::ServiceReport,
+) -> Result<()> {
+    let SyncOptions {
+        dry_run,
+        gifts,
+        on_size_mismatch,
+        quality,
+        ignore_space,
+        limits,
+        interactive,
+        include_favorites,
+        ..
+    } = *opts;
+    let quality = quality.unwrap_or_default();
+    let (qobuz, mut purchases) = match (&opts.item, &opts.retry) {
+        (Some(models::GetItem::Qobuz(item)), _) => {
+            fetch_qobuz_items(login, std::slice::from_ref(item), opts).await?
+        }
+        (_, Some(retry)) => fetch_qobuz_items(login, &retry.qobuz_items, opts).await?,
+        _ => fetch_qobuz_purchases(login, opts).await?,
+    };
+    let mut favorites = if include_favorites {
+        Some(fetch_qobuz_favorites(&qobuz, opts).await?)
+    } else {
+        None
+    };
+
+    // Narrowing before the track lists are fetched is what makes syncing one
+    // album quick, but leaves too little to cache as the library snapshot.
+    let narrowed = opts.narrowed();
+    if narrowed {
+        for list in std::iter::once(&mut purchases).chain(&mut favorites) {
+            list.retain_names(&opts.names);
+            if let Some(since) = &opts.since {
+                list.retain_since(since);
+            }
+        }
+        info!(
+            "{} albums and {} tracks match {}",
+            purchases.albums.len(),
+            purchases.tracks.len(),
+            opts.describe_narrowing()
+        );
+    }
+
+    fill_album_tracks(&qobuz, &mut purchases, opts).await?;
+    if let Some(retry) = &opts.retry {
+        purchases.retain_tracks(&retry.qobuz_tracks);
+    }
+
+    if !narrowed && opts.item.is_none() && opts.retry.is_none() {
+        save_snapshot(&snapshot::Snapshot::from_qobuz(&purchases));
+    }
+    // Tracks not bought are only known to be downloadable once checked.
+    let purchased: HashSet<models::TrackId> = purchases.track_ids().collect();
+    if let Some(mut favorites) = favorites {
+        favorites
+            .albums
+            .retain(|a| !purchases.albums.iter().any(|p| p.id == a.id));
+        fill_album_tracks(&qobuz, &mut favorites, opts).await?;
+        let (albums, tracks) = purchases.add_missing(favorites);
+        info!("{albums} favorite albums and {tracks} favorite tracks aren't purchases");
+    }
+    purchases.retain_gifts(gifts);
+
+    let mut manifest = manifest::Manifest::load(target_dir)?;
+    if let Some(mode) = opts.prune {
+        let purchased = prune::Purchased::qobuz(&purchases);
+        prune_service(
+            target_dir,
+            &mut manifest,
+            models::Service::Qobuz,
+            &purchased,
+            mode,
+        )?;
+    }
+    let before = manifest.clone();
+    let ext = crate::transcode::planned_format(quality.format(), opts.transcode).extension();
+    let tasks = sync::collect_tasks(&purchases, target_dir, ext, opts.naming);
+    let mut existing = sync::scan_existing_in(
+        &tasks,
+        models::Service::Qobuz,
+        &manifest,
+        &*opts.storage,
+        opts.naming,
+    )
+    .await?;
+    if opts.deep_scan {
+        let index = build_tag_index(target_dir).await?;
+        let matched = existing.add_tagged(&tasks, &index);
+        if !matched.is_empty() {
+            info!("{} tracks found elsewhere by their tags", matched.len());
+            // Recorded, so later syncs find them without a deep scan.
+            for (task, path) in matched {
+                let ext = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or_default();
+                let Some(format) = models::AudioFormat::from_extension(ext) else {
+                    continue;
+                };
+                let entry = manifest::ManifestEntry::hashed(
+                    &task.album,
+                    &task.track,
+                    format,
+                    target_dir,
+                    &path,
+                    None,
+                );
+                manifest.record(models::Service::Qobuz, task.track.id, entry);
+            }
+            if !dry_run {
+                manifest.save(target_dir)?;
+            }
+        }
+    }
+    let mut plan = sync::build_sync_plan(
+        tasks,
+        &existing,
+        dry_run,
+        on_size_mismatch.unwrap_or_default(),
+        opts.naming,
+    );
+    // Favorites, and what `get` fetched, may not have been bought.
+    if include_favorites || opts.item.is_some() {
+        let unchecked: Vec<models::TrackId> = plan
+            .downloads()
+            .map(|d| &d.track)
+            .chain(
+                plan.skipped()
+                    .filter(|s| matches!(s.reason, models::SkipReason::DryRun))
+                    .map(|s| &s.track),
+            )
+            .map(|t| t.id)
+            .filter(|id| !purchased.contains(id))
+            .collect();
+        if !unchecked.is_empty() {
+            let entitled = download::entitled_tracks(&qobuz, unchecked.clone()).await;
+            let not_entitled: HashSet<_> = unchecked
+                .into_iter()
+                .filter(|id| !entitled.contains(id))
+                .collect();
+            let skipped = plan.skip_tracks(&not_entitled, models::SkipReason::NotEntitled);
+            if skipped > 0 {
+                info!("{skipped} tracks skipped: not purchased, so only previews are offered");
+            }
+        }
+    }
+    if let Some(budget) = limits.budget {
+        let deferred = plan.defer_beyond(budget);
+        if deferred > 0 {
+            info!(
+                "{deferred} tracks left for a later session, past the {} budget",
+                space::human(budget)
+            );
+        }
+    }
+    if interactive && plan.download_count() > 0 {
+        let entries: Vec<String> = plan
+            .albums_to_download()
+            .map(|a| {
+                format!(
+                    "{} - {} ({} tracks)",
+                    a.album.artist.name,
+                    a.album.title,
+                    a.downloads.len()
+                )
+            })
+            .collect();
+        let selected = select::choose("Qobuz albums to download", &entries)?;
+        plan.select_albums(&selected);
+    }
+
+    info!(
+        "{} tracks to download (~{}), {} already synced",
+        plan.download_count(),
+        space::human(plan.estimated_bytes()),
+        plan.skipped_count() - mismatches_kept(&plan)
+    );
+    report_size_mismatches(&plan.size_mismatches);
+
+    report.add_skipped(plan.skipped(), target_dir);
+
+    if dry_run {
+        let diff = dry_run::plan_diff(&plan);
+        print!("{}", diff.render());
+        info!(
+            target: logging::SUMMARY,
+            "\nDry run: {} tracks would be downloaded (~{}), {} already synced{}",
+            plan.skipped()
+                .filter(|s| matches!(s.reason, models::SkipReason::DryRun))
+                .count(),
+            space::human(diff.bytes()),
+            plan.skipped()
+                .filter(|s| matches!(s.reason, models::SkipReason::AlreadyExists))
+                .count(),
+            mismatch_summary(&diff),
+        );
+        return Ok(());
+    }
+
+    if plan.download_count() == 0 {
+        info!(target: logging::SUMMARY, "Qobuz library is up to date.");
+        return Ok(());
+    }
+
+    // A remote target's working directory only holds a few at a time.
+    if opts.storage.is_local() {
+        space::check(
+            &format!("{} Qobuz tracks", plan.download_count()),
+            plan.estimated_bytes(),
+            target_dir,
+            ignore_space,
+        )?;
+    }
+
+    // Only files whose replacement downloads this session are moved aside.
+    let replaced: Vec<_> = plan
+        .size_mismatches
+        .iter()
+        .filter(|m| m.resolution == models::SizeMismatchPolicy::Overwrite)
+        .filter(|m| plan.downloads().any(|d| d.target_path == m.path))
+        .filter(|_| opts.storage.is_local())
+        .collect();
+    if !replaced.is_empty() {
+        let bin = trash::Trash::new(target_dir);
+        for mismatch in &replaced {
+            bin.move_file(&mismatch.path)?;
+        }
+        info!(
+            "Moved {} files being replaced to {}",
+            replaced.len(),
+            bin.batch_dir().display()
+        );
+    }
+
+    let qobuz_opts = download::QobuzOptions {
+        limits,
+        quality,
+        transcode: opts.transcode,
+        quiet: opts.quiet,
+        events: opts.events.clone(),
+        storage: opts.storage.clone(),
+    };
+    let result = download::execute_downloads(&qobuz, plan, &qobuz_opts).await?;
+    report.add_qobuz(&result, target_dir);
+
+    for done in &result.succeeded {
+        let mut entry = manifest::ManifestEntry::hashed(
+            &done.task.album,
+            &done.task.track,
+            done.format,
+            target_dir,
+            &done.path(),
+            Some(done.sha256.clone()),
+        );
+        entry.quality = done.quality;
+        manifest.record(models::Service::Qobuz, done.task.track.id, entry);
+    }
+    if opts.replaygain && !result.succeeded.is_empty() {
+        let new_tracks = result.succeeded.iter().filter_map(|done| {
+            done.path()
+                .strip_prefix(target_dir)
+                .ok()
+                .map(Path::to_path_buf)
+        });
+        replaygain::tag_new_albums(
+            &mut manifest,
+            models::Service::Qobuz,
+            target_dir,
+            new_tracks,
+        )
+        .await;
+    }
+    if let Some(ref mb) = opts.musicbrainz
+        && !result.succeeded.is_empty()
+    {
+        musicbrainz::enrich_albums(
+            &mut manifest,
+            models::Service::Qobuz,
+            target_dir,
+            musicbrainz::AlbumQuery::for_qobuz(target_dir, &result.succeeded),
+            mb.contact.as_deref(),
+            &opts.network,
+        )
+        .await;
+    }
+    if !result.succeeded.is_empty() {
+        manifest.save(target_dir)?;
+        if opts.storage.is_local() {
+            checksums::refresh_changed(target_dir, &before, &manifest);
+        }
+    }
+    let new_tracks = result.succeeded.iter().filter_map(|done| {
+        done.path()
+            .strip_prefix(target_dir)
+            .ok()
+            .map(Path::to_path_buf)
+    });
+    let imports = import_into_beets(target_dir, opts, &before, new_tracks).await;
+    report.add_beets(&imports);
+    let added: Vec<_> = result
+        .succeeded
+        .iter()
+        .filter_map(|done| manifest.get(models::Service::Qobuz, done.task.track.id))
+        .collect();
+    add_recently_added(target_dir, opts, &added);
+
+    // A plain MP3 run keeps the short summary.
+    let formats = result.format_counts();
+    if formats.iter().any(|(label, _)| label != "MP3") {
+        let formats: Vec<String> = formats
+            .iter()
+            .map(|(label, count)| format!("{count} {label}"))
+            .collect();
+        info!(
+            target: logging::SUMMARY,
+            "\nQobuz: {} succeeded ({}), {} failed, {} skipped",
+            result.succeeded.len(),
+            formats.join(", "),
+            result.failed.len(),
+            result.skipped.len()
+        );
+    } else {
+        info!(
+            target: logging::SUMMARY,
+            "\nQobuz: {} succeeded, {} failed, {} skipped",
+            result.succeeded.len(),
+            result.failed.len(),
+            result.skipped.len()
+        );
+    }
+    if !imports.is_empty() {
+        info!(target: logging::SUMMARY, "{}", beets::summary(&imports));
+    }
+
+    if !result.failed.is_empty() {
+        for err in &result.failed {
+            warn!(
+                "failed to download {} - {}: {}",
+                err.task.album.title, err.task.track.title, err.error
+            );
+        }
+        return Err(report::DownloadsFailed(models::Service::Qobuz).into());
+    }
+
+    Ok(())
+}
+
+/// Hand the album directories a sync created to `beet import`, if
+/// `[integrations.beets] auto_import` is set. Failures are reported per
+/// album and don't fail the sync.
+async fn import_into_beets(
+    target_dir: &Path,
+    opts: &SyncOptions,
+    before: &manifest::Manifest,
+    new_tracks: impl IntoIterator<Item = PathBuf>,
+) -> Vec<beets::Import> {
+    if !opts.beets_import || opts.dry_run {
+        return Vec::new();
+    }
+    let dirs = beets::new_album_dirs(before, new_tracks);
+    if dirs.is_empty() {
+        return Vec::new();
+    }
+    beets::import_albums(target_dir, dirs).await
+}
+
+fn mismatches_kept(plan: &models::SyncPlan) -> usize {
+    plan.skipped()
+        .filter(|s| matches!(s.reason, models::SkipReason::SizeMismatch))
+        .count()
+}
+
+/// ", 2 local files don't match" for a dry run's summary, if any don't.
+fn mismatch_summary(diff: &dry_run::Diff) -> String {
+    match diff.count(dry_run::Change::Mismatch) {
+        0 => String::new(),
+        n => format!(", {n} local files don't match (!)"),
+    }
+}
+
+/// List existing files whose size didn't fit the track, and what the plan
+/// does with each.
+fn report_size_mismatches(mismatches: &[models::SizeMismatch]) {
+    if mismatches.is_empty() {
+        return;
+    }
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    let mut msg = format!(
+        "{} existing files have an unexpected size:",
+        mismatches.len()
+    );
+    for m in mismatches {
+        let action = match m.resolution {
+            models::SizeMismatchPolicy::Keep => "kept",
+            models::SizeMismatchPolicy::Overwrite => "will be replaced",
+            models::SizeMismatchPolicy::KeepBoth => "new copy saved alongside",
+        };
+        msg += &format!(
+            "\n  {}: {:.1} MB, expected {:.1}-{:.1} MB ({action})",
+            m.path.display(),
+            mb(m.actual),
+            mb(*m.expected.start()),
+            mb(*m.expected.end()),
+        );
+    }
+    if mismatches
+        .iter()
+        .any(|m| m.resolution == models::SizeMismatchPolicy::Keep)
+    {
+        msg += "\nUse --on-size-mismatch overwrite or keep-both to download them.";
+    }
+    warn!("{msg}");
+}
+
+/// Authenticate with Bandcamp and crawl the full collection, warning if the
+/// crawl disagrees with the collection's reported size.
+pub async fn fetch_bandcamp_purchases(
+    bandcamp_cfg: config::BandcampConfig,
+    opts: &SyncOptions,
+) -> Result<(bandcamp::BandcampClient, bandcamp::BandcampPurchases)> {
+    let bc_client = bandcamp::BandcampClient::new(
+        bandcamp_cfg.identity_cookie,
+        opts.requests.bandcamp.clone(),
+        &opts.network,
+    )?;
+
+    info!("Verifying Bandcamp authentication...");
+    let fan_id = bc_client.verify_auth().await?;
+    info!("Bandcamp fan_id: {fan_id}");
+
+    let multi = progress::multi(opts.quiet);
+    let pb = progress::spinner(&multi, "Fetching Bandcamp purchases");
+    let purchases = bc_client.get_purchases(fan_id, &pb).await;
+    pb.finish_and_clear();
+    let purchases = purchases?;
+    info!(
+        "Found {} Bandcamp items ({} with download URLs)",
+        purchases.items.len(),
+        purchases.redownload_urls.len()
+    );
+
+    // Re-read the summary after crawling so items bought mid-crawl aren't
+    // reported as missing.
+    match bc_client.collection_summary().await {
+        Ok(summary) => {
+            if let Some(warning) = bandcamp::check_collection_count(&summary, &purchases.items) {
+                warn!("{warning}");
+            }
+        }
+        Err(e) => warn!("could not refresh Bandcamp collection counts: {e:#}"),
+    }
+
+    Ok((bc_client, purchases))
+}
+
+/// Narrow `purchases` to the one item `get` was given a link to.
+async fn select_bandcamp_link(
+    client: &bandcamp::BandcampClient,
+    purchases: &mut bandcamp::BandcampPurchases,
+    link: &models::BandcampLink,
+) -> Result<()> {
+    match link {
+        models::BandcampLink::Page(url) => purchases.select_page(url)?,
+        models::BandcampLink::Download(url) => {
+            let info = client
+                .get_download_info(url)
+                .await
+                .context("Reading the Bandcamp download link failed (it may have expired)")?;
+            purchases.select_download(&info, url);
+        }
+    }
+    for item in &purchases.items {
+        info!("Found {} - {}", item.band_name, item.item_title);
+    }
+    Ok(())
+}
+
+pub async fn run_bandcamp_sync(
+    bandcamp_cfg: config::BandcampConfig,
+    target_dir: &Path,
+    opts: &SyncOptions,
+    report: &mut report::SyncReport,
+) -> Result<()> {
+    let mut service_report = report::ServiceReport::new(models::Service::Bandcamp);
+    let result = bandcamp_sync(bandcamp_cfg, target_dir, opts, &mut service_report)
+        .instrument(info_span!("service", name = "bandcamp"))
+        .await;
+    report.add(service_report, &result);
+    result
+}
+
+async fn bandcamp_sync(
+    bandcamp_cfg: config::BandcampConfig,
+    target_dir: &Path,
+    opts: &SyncOptions,
+    report: &mut report::ServiceReport,
+) -> Result<()> {
+    let SyncOptions {
+        dry_run,
+        gifts,
+        ignore_space,
+        limits,
+        unknown_track_numbers,
+        keep_archives,
+        interactive,
+        ..
+    } = *opts;
+    let saved_session = bandcamp_cfg.saved;
+    let (bc_client, mut purchases) = fetch_bandcamp_purchases(bandcamp_cfg, opts).await?;
+
+    match (&opts.item, &opts.retry) {
+        (Some(models::GetItem::Bandcamp(link)), _) => {
+            select_bandcamp_link(&bc_client, &mut purchases, link).await?;
+        }
+        (_, Some(retry)) => {
+            purchases
+                .items
+                .retain(|item| retry.bandcamp_items.contains(&item.item_id));
+            let gone = retry
+                .bandcamp_items
+                .len()
+                .saturating_sub(purchases.items.len());
+            if gone > 0 {
+                warn!("{gone} failed Bandcamp items are no longer in the collection");
+            }
+        }
+        _ => save_snapshot(&snapshot::Snapshot::from_bandcamp(&purchases)),
+    }
+    purchases.retain_gifts(gifts);
+    let purchased = opts
+        .prune
+        .map(|_| prune::Purchased::bandcamp(&purchases, opts.naming));
+    if opts.narrowed() {
+        purchases.retain_names(&opts.names);
+        if let Some(since) = &opts.since {
+            purchases.retain_since(since);
+        }
+        info!(
+            "{} purchases match {}",
+            purchases.items.len(),
+            opts.describe_narrowing()
+        );
+    }
+
+    if opts.deep_scan {
+        let index = build_tag_index(target_dir).await?;
+        let elsewhere: HashSet<u64> = download::bandcamp_pending(
+            &purchases,
+            target_dir,
+            &*opts.storage,
+            opts.naming,
+            opts.transcode,
+        )
+        .await?
+        .into_iter()
+        .filter(|item| index.has_album(&item.band_name, &item.item_title))
+        .map(|item| item.item_id)
+        .collect();
+        if !elsewhere.is_empty() {
+            info!(
+                "{} Bandcamp items found elsewhere by their tags; not downloading them",
+                elsewhere.len()
+            );
+            purchases
+                .items
+                .retain(|item| !elsewhere.contains(&item.item_id));
+        }
+    }
+
+    let mut selected = None;
+    if interactive {
+        let pending = download::bandcamp_pending(
+            &purchases,
+            target_dir,
+            &*opts.storage,
+            opts.naming,
+            opts.transcode,
+        )
+        .await?;
+        if !pending.is_empty() {
+            let entries: Vec<String> = pending
+                .iter()
+                .map(|item| format!("{} - {}", item.band_name, item.item_title))
+                .collect();
+            let chosen = select::choose("Bandcamp purchases to download", &entries)?;
+            selected = Some(chosen.iter().map(|&i| pending[i].item_id).collect());
+        }
+    }
+
+    let mut manifest = manifest::Manifest::load(target_dir)?;
+    if let (Some(mode), Some(purchased)) = (opts.prune, purchased) {
+        prune_service(
+            target_dir,
+            &mut manifest,
+            models::Service::Bandcamp,
+            &purchased,
+            mode,
+        )?;
+    }
+    let before = manifest.clone();
+    let result = download::execute_bandcamp_downloads(
+        &bc_client,
+        &purchases,
+        target_dir,
+        &download::BandcampOptions {
+            dry_run,
+            ignore_space,
+            limits,
+            unknown_track_numbers: unknown_track_numbers.unwrap_or_default(),
+            naming: opts.naming,
+            transcode: opts.transcode,
+            quiet: opts.quiet,
+            keep_archives,
+            selected,
+            events: opts.events.clone(),
+            storage: opts.storage.clone(),
+        },
+        &mut manifest,
+    )
+    .await?;
+    report.add_bandcamp(&result, target_dir);
+    if opts.replaygain && result.downloaded > 0 {
+        let new_tracks = result
+            .succeeded
+            .iter()
+            .flat_map(|(_, paths)| paths)
+            .filter_map(|path| path.strip_prefix(target_dir).ok().map(Path::to_path_buf));
+        replaygain::tag_new_albums(
+            &mut manifest,
+            models::Service::Bandcamp,
+            target_dir,
+            new_tracks,
+        )
+        .await;
+    }
+    if let Some(ref mb) = opts.musicbrainz
+        && result.downloaded > 0
+    {
+        let new_tracks = result
+            .succeeded
+            .iter()
+            .flat_map(|(_, paths)| paths)
+            .filter_map(|path| path.strip_prefix(target_dir).ok().map(Path::to_path_buf));
+        let albums = musicbrainz::AlbumQuery::from_manifest(&manifest, new_tracks);
+        musicbrainz::enrich_albums(
+            &mut manifest,
+            models::Service::Bandcamp,
+            target_dir,
+            albums,
+            mb.contact.as_deref(),
+            &opts.network,
+        )
+        .await;
+    }
+    if result.downloaded > 0 || result.retagged > 0 {
+        manifest.save(target_dir)?;
+        if opts.storage.is_local() {
+            checksums::refresh_changed(target_dir, &before, &manifest);
+        }
+    }
+    let new_tracks = result
+        .succeeded
+        .iter()
+        .flat_map(|(_, paths)| paths)
+        .filter_map(|path| path.strip_prefix(target_dir).ok().map(Path::to_path_buf));
+    let imports = import_into_beets(target_dir, opts, &before, new_tracks).await;
+    report.add_beets(&imports);
+    let added: Vec<_> = result
+        .succeeded
+        .iter()
+        .flat_map(|(_, paths)| paths)
+        .filter_map(|path| {
+            manifest
+                .entries_for(models::Service::Bandcamp)
+                .find(|e| target_dir.join(&e.path) == *path)
+        })
+        .collect();
+    add_recently_added(target_dir, opts, &added);
+
+    if dry_run {
+        let diff = dry_run::bandcamp_diff(
+            &purchases,
+            target_dir,
+            &result.sizes,
+            opts.naming,
+            opts.transcode,
+        );
+        print!("{}", diff.render());
+        info!(
+            target: logging::SUMMARY,
+            "\nDry run: {} would be downloaded (~{}), {} already synced{}",
+            result.would_download,
+            space::human(diff.bytes()),
+            result.skipped,
+            mismatch_summary(&diff),
+        );
+    } else {
+        info!(
+            target: logging::SUMMARY,
+            "\nBandcamp: {} tracks downloaded, {} already synced",
+            result.downloaded, result.skipped
+        );
+        if result.retagged > 0 {
+            info!(
+                target: logging::SUMMARY,
+                "Tagged {} previously synced tracks",
+                result.retagged
+            );
+        }
+        if !imports.is_empty() {
+            info!(target: logging::SUMMARY, "{}", beets::summary(&imports));
+        }
+    }
+
+    save_refreshed_identity(&bc_client, saved_session);
+
+    if !result.failed.is_empty() {
+        for err in &result.failed {
+            warn!("failed to download {}: {}", err.description, err.error);
+        }
+        return Err(report::DownloadsFailed(models::Service::Bandcamp).into());
+    }
+
+    Ok(())
+}
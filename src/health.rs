@@ -0,0 +1,131 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::models::Service;
+
+/// When a failing service is taken out of rotation, and for how long.
+#[derive(Debug, Clone)]
+pub struct HealthPolicy {
+    /// Consecutive failures before a service is marked unhealthy.
+    /// Authentication failures mark it unhealthy immediately.
+    pub failure_threshold: u32,
+    /// Cool-down after the first time a service is marked unhealthy.
+    /// Doubles on every failed retry, up to `max_cooldown`.
+    pub base_cooldown: Duration,
+    pub max_cooldown: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            base_cooldown: Duration::from_secs(15 * 60),
+            max_cooldown: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Change in a service's health worth telling the user about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Nothing new to report (including escalations of an existing cool-down).
+    None,
+    /// The service just became unhealthy and will be skipped for `cooldown`.
+    BecameUnhealthy { cooldown: Duration },
+    /// A previously unhealthy service synced successfully.
+    Recovered,
+}
+
+/// Per-service failure tracking for daemon mode.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceHealth {
+    consecutive_failures: u32,
+    /// Times the service has been put into cool-down since it was last healthy.
+    strikes: u32,
+    retry_at: Option<Instant>,
+}
+
+impl ServiceHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.strikes == 0
+    }
+
+    /// Whether the service should be synced this cycle.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn record_success(&mut self) -> Transition {
+        let was_unhealthy = !self.is_healthy();
+        *self = Self::default();
+        if was_unhealthy {
+            Transition::Recovered
+        } else {
+            Transition::None
+        }
+    }
+
+    pub fn record_failure(
+        &mut self,
+        auth_failure: bool,
+        now: Instant,
+        policy: &HealthPolicy,
+    ) -> Transition {
+        self.consecutive_failures += 1;
+        if !auth_failure && self.consecutive_failures < policy.failure_threshold {
+            return Transition::None;
+        }
+
+        self.strikes += 1;
+        let cooldown = policy
+            .base_cooldown
+            .saturating_mul(2u32.saturating_pow(self.strikes - 1))
+            .min(policy.max_cooldown);
+        self.retry_at = Some(now + cooldown);
+
+        if self.strikes == 1 {
+            Transition::BecameUnhealthy { cooldown }
+        } else {
+            Transition::None
+        }
+    }
+}
+
+/// A login or identity cookie the service rejected, as the clients report
+/// it.
+#[derive(Debug)]
+pub struct AuthError(pub Service);
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Service::Qobuz => write!(f, "Qobuz authentication failed: invalid credentials"),
+            Service::Bandcamp => write!(
+                f,
+                "Bandcamp authentication failed: identity cookie is invalid or expired. \
+                 Update BANDCAMP_IDENTITY or [bandcamp] identity_cookie in config."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// Whether an error is a rejected login or identity cookie, which retrying
+/// on the next cycle will not fix.
+pub fn is_auth_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<AuthError>())
+}
+
+/// Whether an error is a failure to reach the service at all (no
+/// connection, or a timeout) rather than something it answered.
+pub fn is_network_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout())
+    })
+}
@@ -0,0 +1,97 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::report::SyncReport;
+
+/// Which hook a command runs as. Passed to it as `QOGET_HOOK`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before a sync touches the target directory (`[hooks] pre_sync`).
+    PreSync,
+    /// After a sync, however it went (`[hooks] post_sync`).
+    PostSync,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreSync => "pre_sync",
+            Hook::PostSync => "post_sync",
+        }
+    }
+}
+
+/// `command` as the platform's shell runs it.
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Run a hook's shell `command`, with `QOGET_HOOK`, `QOGET_TARGET_DIR` and
+/// `QOGET_DRY_RUN` (`1` or `0`) set and `stdin` written to its standard
+/// input. Its output goes to qoget's. Fails if it can't be started or
+/// exits unsuccessfully.
+pub fn run(
+    hook: Hook,
+    command: &str,
+    target_dir: &Path,
+    dry_run: bool,
+    stdin: &[u8],
+) -> Result<()> {
+    info!("Running {} hook", hook.name());
+    let mut child = shell(command)
+        .env("QOGET_HOOK", hook.name())
+        .env("QOGET_TARGET_DIR", target_dir)
+        .env("QOGET_DRY_RUN", if dry_run { "1" } else { "0" })
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("{} hook could not be run", hook.name()))?;
+    if let Some(mut pipe) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early; that's
+        // its business.
+        let _ = pipe.write_all(stdin);
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("waiting for the {} hook", hook.name()))?;
+    if !status.success() {
+        bail!("{} hook `{command}` failed ({status})", hook.name());
+    }
+    Ok(())
+}
+
+/// Run the pre-sync hook, off the async runtime.
+pub async fn pre_sync(command: &str, target_dir: &Path, dry_run: bool) -> Result<()> {
+    let (command, target_dir) = (command.to_string(), target_dir.to_path_buf());
+    tokio::task::spawn_blocking(move || run(Hook::PreSync, &command, &target_dir, dry_run, &[]))
+        .await?
+}
+
+/// Run the post-sync hook, off the async runtime, with the run's JSON
+/// summary (as `--report` writes it) on its standard input.
+pub async fn post_sync(command: &str, report: &SyncReport) -> Result<()> {
+    let json = serde_json::to_string_pretty(report).context("serializing report")? + "\n";
+    let command = command.to_string();
+    let (target_dir, dry_run) = (report.target_dir.clone(), report.dry_run);
+    tokio::task::spawn_blocking(move || {
+        run(
+            Hook::PostSync,
+            &command,
+            &target_dir,
+            dry_run,
+            json.as_bytes(),
+        )
+    })
+    .await?
+}
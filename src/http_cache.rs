@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use reqwest::RequestBuilder;
+use reqwest::StatusCode;
+use reqwest::header::{
+    ETAG, HeaderMap, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::manifest::sha256_hex;
+use crate::retry::{self, RateLimiter, RequestPolicy};
+
+/// A response body kept on disk, with the validators the server sent for
+/// it so the next request can ask whether it changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+impl CachedResponse {
+    /// The entry to keep for a response, or `None` if the server sent
+    /// neither an `ETag` nor a `Last-Modified` to revalidate it with.
+    pub fn from_headers(url: &str, headers: &HeaderMap, body: String) -> Option<Self> {
+        let header = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Self {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            body,
+        })
+    }
+
+    /// `request` made conditional on this entry being out of date.
+    pub fn conditional(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// Metadata responses cached on disk, one file per URL, and reused when
+/// the server answers a conditional request with 304 Not Modified.
+///
+/// Responses belong to an account, so each entry is also keyed by a
+/// `scope` (the auth token) that is never written out.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, url: &str, scope: &str) -> PathBuf {
+        let key = sha256_hex(format!("{scope}\n{url}").as_bytes());
+        self.dir.join(format!("{key}.json"))
+    }
+
+    pub fn load(&self, url: &str, scope: &str) -> Result<Option<CachedResponse>> {
+        let path = self.path(url, scope);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        let cached: CachedResponse = serde_json::from_str(&contents)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        // A hash collision is as good as a miss.
+        Ok(Some(cached).filter(|c| c.url == url))
+    }
+
+    /// Atomic write: temp file + rename.
+    pub fn save(&self, scope: &str, cached: &CachedResponse) -> Result<()> {
+        let path = self.path(&cached.url, scope);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        let json = serde_json::to_string(cached)?;
+        std::fs::write(&tmp, &json).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+
+    /// Send `request` like `retry::send` and return the response body,
+    /// conditionally if it was cached before. A 304 returns the cached body;
+    /// a fresh body with validators replaces it. Cache read and write
+    /// problems only cost the full response, never the request.
+    pub async fn send(
+        &self,
+        scope: &str,
+        request: RequestBuilder,
+        policy: &RequestPolicy,
+        limiter: &RateLimiter,
+    ) -> Result<String> {
+        let url = request
+            .try_clone()
+            .context("Request cannot be cloned for caching")?
+            .build()?
+            .url()
+            .to_string();
+        let cached = self.load(&url, scope).unwrap_or_else(|e| {
+            warn!("ignoring cached response: {e:#}");
+            None
+        });
+        let request = match &cached {
+            Some(cached) => cached.conditional(request),
+            None => request,
+        };
+
+        let resp = retry::send(&request, policy, limiter).await?;
+        if let Some(cached) = cached
+            && resp.status() == StatusCode::NOT_MODIFIED
+        {
+            debug!(
+                path = resp.url().path(),
+                "not modified, using cached response"
+            );
+            return Ok(cached.body);
+        }
+
+        let headers = resp.headers().clone();
+        let body = resp.text().await?;
+        if let Some(fresh) = CachedResponse::from_headers(&url, &headers, body.clone())
+            && let Err(e) = self.save(scope, &fresh)
+        {
+            warn!("could not cache response: {e:#}");
+        }
+        Ok(body)
+    }
+}
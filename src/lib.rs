@@ -1,8 +1,48 @@
+pub mod art;
+pub mod backfill;
 pub mod bandcamp;
+pub mod beets;
+pub mod browser;
 pub mod bundle;
+pub mod checksums;
 pub mod client;
+pub mod collate;
 pub mod config;
+pub mod deep_scan;
 pub mod download;
+pub mod dry_run;
+pub mod engine;
+pub mod health;
+pub mod hooks;
+pub mod http_cache;
+pub mod list;
+pub mod lock;
+pub mod logging;
+pub mod manifest;
+pub mod media_server;
 pub mod models;
+pub mod musicbrainz;
+pub mod network;
+pub mod notify;
+pub mod orphans;
 pub mod path;
+pub mod playlist;
+pub mod progress;
+pub mod prune;
+pub mod replaygain;
+pub mod report;
+pub mod retry;
+pub mod s3;
+pub mod search;
+pub mod secrets;
+pub mod select;
+pub mod session;
+pub mod snapshot;
+pub mod space;
+pub mod status;
+pub mod storage;
 pub mod sync;
+pub mod tags;
+pub mod trash;
+pub mod transcode;
+pub mod verify;
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::bandcamp::BandcampPurchases;
+use crate::collate::{self, SortOrder};
+use crate::models::{BandcampCollectionItem, PurchaseList, Service};
+
+/// Whether a purchase was a whole album or a single track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PurchaseKind {
+    Album,
+    Track,
+}
+
+impl fmt::Display for PurchaseKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PurchaseKind::Album => write!(f, "album"),
+            PurchaseKind::Track => write!(f, "track"),
+        }
+    }
+}
+
+/// One row of `qoget list` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub service: Service,
+    pub kind: PurchaseKind,
+    /// The store's own ID for the album or track.
+    pub id: String,
+    pub artist: String,
+    pub title: String,
+    /// `None` when the store doesn't report it before download (Bandcamp).
+    pub track_count: Option<u16>,
+    /// Received as a gift (or voucher) rather than bought.
+    pub gift: bool,
+}
+
+impl ListEntry {
+    /// Tab-separated: service, type, id, artist, title, track count ("-" if
+    /// unknown), and acquisition ("purchase" or "gift").
+    pub fn to_line(&self) -> String {
+        let tracks = self
+            .track_count
+            .map_or_else(|| "-".to_string(), |n| n.to_string());
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.service.to_string().to_lowercase(),
+            self.kind,
+            self.id,
+            self.artist,
+            self.title,
+            tracks,
+            if self.gift { "gift" } else { "purchase" }
+        )
+    }
+}
+
+pub fn from_qobuz(purchases: &PurchaseList) -> Vec<ListEntry> {
+    let albums = purchases.albums.iter().map(|album| ListEntry {
+        service: Service::Qobuz,
+        kind: PurchaseKind::Album,
+        id: album.id.0.clone(),
+        artist: album.artist.name.clone(),
+        title: album.title.clone(),
+        track_count: Some(album.tracks_count),
+        gift: album.purchase.is_gift(),
+    });
+    let tracks = purchases.tracks.iter().map(|track| ListEntry {
+        service: Service::Qobuz,
+        kind: PurchaseKind::Track,
+        id: track.id.0.to_string(),
+        artist: track.performer.name.clone(),
+        title: track.title.clone(),
+        track_count: Some(1),
+        gift: track.purchase.is_gift(),
+    });
+    albums.chain(tracks).collect()
+}
+
+pub fn from_bandcamp(purchases: &BandcampPurchases) -> Vec<ListEntry> {
+    purchases
+        .items
+        .iter()
+        .map(|item| {
+            let kind = if item.is_track() == Some(true) {
+                PurchaseKind::Track
+            } else {
+                PurchaseKind::Album
+            };
+            ListEntry {
+                service: Service::Bandcamp,
+                kind,
+                id: item.item_id.to_string(),
+                artist: item.band_name.clone(),
+                title: item.item_title.clone(),
+                track_count: (kind == PurchaseKind::Track).then_some(1),
+                gift: item.is_gift(),
+            }
+        })
+        .collect()
+}
+
+/// Sort by artist, then title, then service.
+pub fn sort(entries: &mut [ListEntry], order: SortOrder) {
+    entries.sort_by(|a, b| {
+        collate::compare_fields(&[&a.artist, &a.title], &[&b.artist, &b.title], order)
+            .then_with(|| a.service.to_string().cmp(&b.service.to_string()))
+    });
+}
+
+/// One item of a Bandcamp wishlist, for `qoget list --bandcamp-wishlist`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WishlistEntry {
+    pub kind: PurchaseKind,
+    /// Bandcamp's ID for the album or track.
+    pub id: String,
+    pub artist: String,
+    pub title: String,
+    /// The item's page, where it can be bought.
+    pub url: Option<String>,
+}
+
+impl WishlistEntry {
+    /// Tab-separated: type, id, artist, title, and page URL ("-" if unknown).
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.kind,
+            self.id,
+            self.artist,
+            self.title,
+            self.url.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+pub fn from_bandcamp_wishlist(items: &[BandcampCollectionItem]) -> Vec<WishlistEntry> {
+    items
+        .iter()
+        .map(|item| WishlistEntry {
+            // Wishlist items carry no sale type; `item_type` is "album" or
+            // "track" (or "package" for merch bundles, listed as albums).
+            kind: if item.item_type == "track" || item.sale_item_type == "t" {
+                PurchaseKind::Track
+            } else {
+                PurchaseKind::Album
+            },
+            id: item.item_id.to_string(),
+            artist: item.band_name.clone(),
+            title: item.item_title.clone(),
+            url: item.item_url.clone(),
+        })
+        .collect()
+}
+
+/// Sort by artist, then title.
+pub fn sort_wishlist(entries: &mut [WishlistEntry], order: SortOrder) {
+    entries.sort_by(|a, b| {
+        collate::compare_fields(&[&a.artist, &a.title], &[&b.artist, &b.title], order)
+    });
+}
+
+/// The wishlist as CSV, with a header row.
+pub fn wishlist_csv(entries: &[WishlistEntry]) -> String {
+    let mut out = "type,id,artist,title,url\n".to_string();
+    for entry in entries {
+        let kind = entry.kind.to_string();
+        let fields = [
+            kind.as_str(),
+            entry.id.as_str(),
+            entry.artist.as_str(),
+            entry.title.as_str(),
+            entry.url.as_deref().unwrap_or(""),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quote a CSV field if it needs it, doubling any quotes inside.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Write the wishlist as CSV if `path` ends in `.csv`, JSON otherwise.
+pub fn export_wishlist(entries: &[WishlistEntry], path: &Path) -> Result<()> {
+    let contents = if path.extension().is_some_and(|e| e == "csv") {
+        wishlist_csv(entries)
+    } else {
+        serde_json::to_string_pretty(entries).context("serializing wishlist")? + "\n"
+    };
+    std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+}
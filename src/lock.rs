@@ -0,0 +1,80 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+const LOCK_FILE: &str = ".qoget.lock";
+
+/// Exclusive claim on a target directory for the length of a sync, so two
+/// runs (say, overlapping cron jobs) can't race on the same files.
+///
+/// The lock is an OS advisory lock on `<target>/.qoget.lock`, so it is
+/// released whenever the process ends, including on Ctrl-C or a crash; the
+/// file itself is left in place and only records the holder's pid.
+pub struct SyncLock {
+    _file: File,
+}
+
+impl SyncLock {
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join(LOCK_FILE)
+    }
+
+    /// Take the lock, or fail at once if another process holds it. With
+    /// `wait`, block until it is free instead.
+    pub fn acquire(target_dir: &Path, wait: bool) -> Result<Self> {
+        std::fs::create_dir_all(target_dir)
+            .with_context(|| format!("creating {}", target_dir.display()))?;
+        let path = Self::path(target_dir);
+        // Not truncated on open: until the lock is ours, the pid in it is
+        // the holder's.
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("opening {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = holder(&mut file);
+                if !wait {
+                    bail!(
+                        "another qoget{holder} is already syncing {}; \
+                         pass --wait-lock to wait for it",
+                        target_dir.display()
+                    );
+                }
+                info!(
+                    "Waiting for another qoget{holder} to finish syncing {}...",
+                    target_dir.display()
+                );
+                file.lock()
+                    .with_context(|| format!("locking {}", path.display()))?;
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("locking {}", path.display()));
+            }
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// " (pid N)" from the lock file, if it names one.
+fn holder(file: &mut File) -> String {
+    let mut contents = String::new();
+    let _ = file.read_to_string(&mut contents);
+    match contents.trim().parse::<u32>() {
+        Ok(pid) => format!(" (pid {pid})"),
+        Err(_) => String::new(),
+    }
+}
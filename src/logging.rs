@@ -0,0 +1,149 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Environment variable that overrides the `-v`/`-q` level, in
+/// `target=level,...` form (e.g. `qoget=trace`).
+pub const LOG_ENV: &str = "RUST_LOG";
+
+/// Target of the events that sum up a service's sync. `--quiet` still
+/// shows these, so a cron job's mail says what the run did.
+pub const SUMMARY: &str = "qoget::summary";
+
+/// How much qoget reports on stderr, from `--quiet` and `-v` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Warnings, errors, and each service's summary; no progress bars.
+    Quiet,
+    /// Progress and summaries.
+    Normal,
+    /// Also requests, retries, and per-track steps (`-v`).
+    Debug,
+    /// Everything, including signature inputs (`-vv`).
+    Trace,
+}
+
+impl Verbosity {
+    pub fn from_flags(verbose: u8, quiet: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Debug,
+            (false, _) => Verbosity::Trace,
+        }
+    }
+
+    fn level(self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::WARN,
+            Verbosity::Normal => LevelFilter::INFO,
+            Verbosity::Debug => LevelFilter::DEBUG,
+            Verbosity::Trace => LevelFilter::TRACE,
+        }
+    }
+
+    /// Filter for qoget's own events; other crates only report warnings.
+    pub fn filter(self) -> Targets {
+        Targets::new()
+            .with_default(LevelFilter::WARN)
+            .with_target("qoget", self.level())
+            .with_target(SUMMARY, self.level().max(LevelFilter::INFO))
+    }
+}
+
+/// Install the stderr logger. `RUST_LOG`, when set and valid, replaces the
+/// filter derived from `verbosity`.
+///
+/// With `log_file`, every run also appends a timestamped log to that file at
+/// debug level (trace with `-vv`) whatever the console shows, so unattended
+/// syncs leave a record of each request, retry, and failure.
+pub fn init(verbosity: Verbosity, log_file: Option<&Path>) -> Result<()> {
+    let console_filter = std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|spec| Targets::from_str(&spec).ok())
+        .unwrap_or_else(|| verbosity.filter());
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .event_format(Plain)
+        .with_filter(console_filter);
+
+    let file = match log_file {
+        Some(path) => {
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("creating {}", dir.display()))?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("opening log file {}", path.display()))?;
+            let level = verbosity.max(Verbosity::Debug);
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(Mutex::new(file))
+                    .with_filter(level.filter()),
+            )
+        }
+        None => None,
+    };
+
+    let _ = tracing_subscriber::registry()
+        .with(console)
+        .with(file)
+        .try_init();
+    Ok(())
+}
+
+/// Formats events the way qoget's messages have always looked: progress as
+/// plain lines, and `Warning:`/`Error:` prefixes. Debug and trace events are
+/// prefixed with their level and the spans (service, album, track) they
+/// happened in.
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            Level::INFO => {}
+            level => {
+                write!(writer, "{}", level.as_str().to_lowercase())?;
+                if let Some(scope) = ctx.event_scope() {
+                    for span in scope.from_root() {
+                        write!(writer, " {}", span.name())?;
+                        let ext = span.extensions();
+                        if let Some(fields) = ext.get::<FormattedFields<N>>()
+                            && !fields.is_empty()
+                        {
+                            write!(writer, "{{{fields}}}")?;
+                        }
+                    }
+                }
+                write!(writer, ": ")?;
+            }
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
@@ -1,9 +1,15 @@
 use std::path::PathBuf;
 use std::process;
 
-use anyhow::{Result, bail};
-use clap::{Parser, Subcommand};
-use qoget::{bandcamp, bundle, client, config, download, models, sync};
+use anyhow::{Context, Result, bail};
+use clap::{CommandFactory, Parser, Subcommand};
+use qoget::engine::{self, PruneMode, SyncEngine, SyncOptions, SyncRun};
+use qoget::{
+    art, backfill, bandcamp, browser, checksums, collate, config, download, health, hooks, list,
+    lock, logging, manifest, media_server, models, network, path, playlist, progress, report,
+    retry, search, secrets, session, snapshot, space, status, storage, trash, verify,
+};
+use tracing::{debug, error, info, warn};
 
 #[derive(Parser)]
 #[command(
@@ -11,56 +17,1269 @@ use qoget::{bandcamp, bundle, client, config, download, models, sync};
     about = "Sync purchased music from Qobuz and Bandcamp to a local directory"
 )]
 struct Cli {
+    /// Show more detail: -v for requests, retries and per-track steps,
+    /// -vv for everything
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only show warnings, errors, and each service's summary, without
+    /// progress bars (for cron)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also append a detailed, timestamped log to PATH, whatever the
+    /// console shows [default: `file` under [log] in the config file]
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
 
-#[derive(Subcommand)]
-enum Command {
-    /// Sync purchased music to a local directory
-    ///
-    /// Downloads from all configured services by default.
-    /// Qobuz downloads MP3 320 (.mp3), Bandcamp downloads AAC (.m4a).
-    ///
-    /// Configure services in ~/.config/qoget/config.toml:
-    ///
-    ///   [qobuz]
-    ///   username = "you@example.com"
-    ///   password = "secret"
-    ///
-    ///   [bandcamp]
-    ///   identity_cookie = "your-cookie"
-    ///
-    /// Or via environment variables: QOBUZ_USERNAME, QOBUZ_PASSWORD, BANDCAMP_IDENTITY
-    Sync {
-        /// Target directory for downloaded music
-        target_dir: PathBuf,
+/// Filter purchases by how they were acquired.
+#[derive(clap::Args)]
+struct GiftArgs {
+    /// Skip items received as gifts (or redeemed from vouchers)
+    #[arg(long, conflicts_with = "only_gifts")]
+    exclude_gifts: bool,
+
+    /// Only include items received as gifts (or redeemed from vouchers)
+    #[arg(long)]
+    only_gifts: bool,
+}
+
+impl GiftArgs {
+    fn filter(&self) -> models::GiftFilter {
+        models::GiftFilter::from_flags(self.exclude_gifts, self.only_gifts)
+    }
+}
+
+/// Options that narrow the purchases a sync sees, which would make
+/// `--prune` take everything outside them for gone.
+const PRUNE_CONFLICTS: [&str; 5] = ["artist", "album", "since", "exclude_gifts", "only_gifts"];
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Create ~/.config/qoget/config.toml interactively
+    ///
+    /// Asks for Qobuz credentials, the Bandcamp identity cookie, and a
+    /// default target directory; any of them can be skipped. The file is
+    /// created readable only by you.
+    Init {
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the config and saved sessions without syncing
+    ///
+    /// Reports which services are ready, incomplete or not configured, and
+    /// exits non-zero if anything needs fixing: a Qobuz username without a
+    /// password or saved session, a malformed identity cookie, an unknown
+    /// setting, and so on.
+    Validate,
+    /// Print the effective settings and where each comes from
+    ///
+    /// Environment variables override the config file, which overrides
+    /// sessions saved by `qoget login`. Passwords, tokens and cookies are
+    /// redacted.
+    Show,
+    /// Store a password or cookie in the OS keyring
+    ///
+    /// Uses the Secret Service on Linux, the Keychain on macOS, and the
+    /// Credential Manager on Windows. Reads the value hidden from the
+    /// terminal, or from stdin when piped. Then refer to it in the config
+    /// file with `password = "keyring:"` under [qobuz] or
+    /// `identity_cookie = "keyring:"` under [bandcamp].
+    SetSecret {
+        /// qobuz-password or bandcamp-identity
+        secret: secrets::Secret,
+
+        /// Keyring entry to use instead of the secret's default name; refer
+        /// to it as `keyring:NAME`
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+    },
+    /// Remove a secret stored with `config set-secret`
+    DeleteSecret {
+        /// qobuz-password or bandcamp-identity
+        secret: secrets::Secret,
+
+        /// Keyring entry to remove instead of the secret's default name
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// List trashed batches and their sizes
+    List {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+    },
+
+    /// Permanently delete everything in the trash
+    Empty {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArtCommand {
+    /// Fetch missing album covers for an existing library
+    ///
+    /// Saves each synced album's cover from its store as cover.jpg in the
+    /// album directory. Albums that already have a cover image, and all
+    /// audio files, are left alone.
+    Backfill {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+
+        /// Only fetch covers for this service (qobuz or bandcamp)
+        #[arg(long)]
+        service: Option<String>,
+
+        /// List the albums that would get a cover without fetching anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sync purchased music to a local directory
+    ///
+    /// Downloads from all configured services by default.
+    /// Qobuz downloads MP3 320 (.mp3) unless --quality asks for FLAC,
+    /// Bandcamp downloads AAC (.m4a).
+    ///
+    /// Configure services in ~/.config/qoget/config.toml:
+    ///
+    ///   [qobuz]
+    ///   username = "you@example.com"
+    ///   password = "secret"
+    ///
+    ///   [bandcamp]
+    ///   identity_cookie = "your-cookie"
+    ///
+    /// Or via environment variables: QOBUZ_USERNAME, QOBUZ_PASSWORD, BANDCAMP_IDENTITY
+    Sync {
+        /// Target directory for downloaded music, an sftp://, webdav://
+        /// or webdavs:// URL to sync to a server, or s3://bucket/prefix
+        /// for an S3-compatible bucket [default: `target_dir` under [sync]
+        /// in the config file]
+        target_dir: Option<PathBuf>,
+
+        /// Preview what would be downloaded without downloading
+        #[arg(long)]
+        dry_run: bool,
+
+        /// List the albums each service would download and ask which of them
+        /// to download; the rest are left for a later sync
+        #[arg(long, conflicts_with = "dry_run")]
+        interactive: bool,
+
+        /// Sync only the specified service (qobuz or bandcamp)
+        #[arg(long, value_name = "NAME")]
+        service: Option<String>,
+
+        /// Sync only purchases by artists matching PATTERN: part of the name,
+        /// or a glob such as "miles*" for the whole name; case is ignored
+        #[arg(long, value_name = "PATTERN")]
+        artist: Option<models::NamePattern>,
+
+        /// Sync only albums whose title matches PATTERN, as for --artist
+        #[arg(long, value_name = "PATTERN")]
+        album: Option<models::NamePattern>,
+
+        /// Sync only purchases made since DATE (e.g. 2024-01-01) or within
+        /// an age such as 30d or 8w; purchases of unknown date are kept
+        #[arg(long, value_name = "DATE")]
+        since: Option<models::Since>,
+
+        /// Also sync Qobuz favorites, where the account may download them in
+        /// full (e.g. bought outside the Qobuz store); others are skipped
+        #[arg(long)]
+        include_favorites: bool,
+
+        /// Use Windows-safe file names even when not running on Windows
+        /// (e.g. when the target is an NTFS or exFAT drive)
+        #[arg(long)]
+        windows_safe_names: bool,
+
+        /// What to do when a file to be downloaded already exists with a size
+        /// that doesn't fit the track (e.g. an old low-bitrate rip): keep,
+        /// overwrite, or keep-both [default: keep]
+        #[arg(long, value_name = "POLICY")]
+        on_size_mismatch: Option<models::SizeMismatchPolicy>,
+
+        /// Qobuz format to ask for first: mp3, cd, hires-96 or hires-192,
+        /// falling back to the ones below it if a release lacks it
+        /// [default: mp3]
+        #[arg(long, value_name = "QUALITY")]
+        quality: Option<models::QobuzQuality>,
+
+        /// Write a JSON report of the run (downloads, failures, skips and
+        /// timings) to PATH; TOML if PATH ends in .toml
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+
+        /// Which failed downloads make the exit status non-zero: any, all
+        /// (only a service whose every download failed), or none. A service
+        /// that can't sync at all always does [default: any]
+        #[arg(long, value_name = "POLICY")]
+        fail_on: Option<models::FailOn>,
+
+        /// If another qoget is syncing the same directory, wait for it to
+        /// finish instead of exiting with an error
+        #[arg(long)]
+        wait_lock: bool,
+
+        /// Download even if the target filesystem looks too full; by default
+        /// sync stops before downloading what won't fit
+        #[arg(long)]
+        ignore_space: bool,
+
+        /// Also keep each downloaded Bandcamp ZIP, as
+        /// TARGET_DIR/Archives/Artist - Album.zip [config: keep_archives
+        /// under [bandcamp]]
+        #[arg(long)]
+        keep_zips: bool,
+
+        /// Move synced tracks that are no longer among a service's purchases
+        /// (refunds, regional takedowns) to the trash, after listing them
+        /// and asking to confirm
+        #[arg(long, conflicts_with_all = PRUNE_CONFLICTS)]
+        prune: bool,
+
+        /// List what --prune would move to the trash, and stop there
+        #[arg(long, conflicts_with_all = PRUNE_CONFLICTS)]
+        prune_dry_run: bool,
+
+        /// Also find tracks already in the target by reading the tags of
+        /// every audio file, for libraries a tagger has renamed or moved
+        #[arg(long)]
+        deep_scan: bool,
+
+        /// Write ReplayGain tags (R128 for Opus) into newly downloaded
+        /// albums, measured with ffmpeg [config: replaygain under [sync]]
+        #[arg(long)]
+        replaygain: bool,
+
+        #[command(flatten)]
+        gifts: GiftArgs,
+    },
+
+    /// Download one album or track
+    ///
+    /// Syncs just that release like `sync` would, without syncing the rest
+    /// of the library: handy right after buying a record.
+    ///
+    /// For Qobuz, takes a store or web player link, or an album ID, and
+    /// fetches only that release. Tracks the account hasn't bought are
+    /// skipped, as Qobuz only offers previews of them.
+    ///
+    /// For Bandcamp, takes an album or track page link, found in the
+    /// collection, or the download link from the purchase's email receipt,
+    /// which works even for something bought without being logged in.
+    Get {
+        /// Album or track link, Bandcamp download link, or Qobuz album ID
+        #[arg(value_name = "URL_OR_ID")]
+        item: models::GetItem,
+
+        /// Target directory for downloaded music [default: `target_dir` under
+        /// [sync] in the config file]
+        target_dir: Option<PathBuf>,
+
+        /// Preview what would be downloaded without downloading
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Qobuz format to ask for first: mp3, cd, hires-96 or hires-192,
+        /// falling back to the ones below it if the release lacks it
+        /// [default: mp3]
+        #[arg(long, value_name = "QUALITY")]
+        quality: Option<models::QobuzQuality>,
+    },
+
+    /// Download again what the last run failed to
+    ///
+    /// Reads the failed downloads the last `sync`, `get`, `backfill`, or
+    /// `daemon` cycle listed in ~/.local/state/qoget/last-failures.json and
+    /// syncs only those. Qobuz albums are fetched by ID instead of listing
+    /// every purchase; Bandcamp's download links only come with the
+    /// collection, so it is still fetched, but only the failed items are
+    /// downloaded.
+    Retry {
+        /// Target directory [default: the one the failures were in]
+        target_dir: Option<PathBuf>,
+
+        /// Preview what would be downloaded without downloading
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Manage files qoget set aside instead of deleting
+    ///
+    /// Tracks no longer among a service's purchases are moved here by
+    /// `sync --prune`. Files removed by `verify --repair` or replaced by
+    /// `sync --on-size-mismatch overwrite` are kept here too, all under
+    /// TARGET_DIR/.qoget/trash/<timestamp>/ until the trash is emptied.
+    Trash {
+        #[command(subcommand)]
+        action: TrashCommand,
+    },
+
+    /// Manage album cover art
+    Art {
+        #[command(subcommand)]
+        action: ArtCommand,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// For example: qoget completions bash > /etc/bash_completion.d/qoget
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page
+    ///
+    /// With --out-dir, writes qoget.1 plus one page per subcommand
+    /// (qoget-sync.1, ...) to that directory instead.
+    Manpage {
+        /// Directory to write all pages to
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+
+    /// Create or inspect the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+
+    /// Log in and save the session for later syncs
+    ///
+    /// Checks that the credentials work, then stores the Qobuz token or
+    /// Bandcamp cookie in ~/.config/qoget/session.json (readable only by
+    /// you), so the config file doesn't need a password. Logs in to both
+    /// services when none is named.
+    Login {
+        /// Service to log in to (qobuz or bandcamp)
+        #[arg(value_name = "SERVICE")]
+        service: Option<String>,
+
+        /// Take the Bandcamp identity cookie from a browser you're logged in
+        /// with (firefox, chrome, or chromium) instead of asking for it
+        #[arg(long, value_name = "BROWSER")]
+        from_browser: Option<browser::Browser>,
+    },
+
+    /// Download a whole library for the first time, over several sessions
+    ///
+    /// Like `sync`, but gentler on the stores: one download at a time with a
+    /// pause before each, stopping once a session has downloaded about
+    /// --budget worth. Run it again, by hand or from cron, to carry on where
+    /// the last session stopped; progress is kept in
+    /// TARGET_DIR/.qoget/backfill.json. Once nothing is left, use `sync`.
+    Backfill {
+        /// Target directory for downloaded music [default: `target_dir` under
+        /// [sync] in the config file]
+        target_dir: Option<PathBuf>,
+
+        /// How much one session may download, e.g. 10GB or 500MB
+        #[arg(long, value_name = "SIZE", value_parser = space::parse_size, default_value = "25GB")]
+        budget: u64,
+
+        /// Seconds to wait before each download
+        #[arg(long, value_name = "SECS", default_value_t = download::BACKFILL_PACE.as_secs())]
+        pace: u64,
+
+        /// Backfill only the specified service (qobuz or bandcamp)
+        #[arg(long, value_name = "NAME")]
+        service: Option<String>,
+
+        /// Show progress so far without downloading anything
+        #[arg(long)]
+        status: bool,
+
+        #[command(flatten)]
+        gifts: GiftArgs,
+    },
+
+    /// Keep syncing on an interval
+    ///
+    /// Runs a sync of every configured service each cycle. A service that
+    /// fails authentication, or fails several cycles in a row, is skipped for
+    /// an escalating cool-down while the others keep syncing. Credentials must
+    /// come from the config file, environment, or `qoget login`; there are no
+    /// prompts.
+    Daemon {
+        /// Target directory for downloaded music [default: `target_dir` under
+        /// [sync] in the config file]
+        target_dir: Option<PathBuf>,
+
+        /// Seconds between sync cycles
+        #[arg(long, value_name = "SECS", default_value_t = 3600)]
+        interval: u64,
+
+        /// Sync only the specified service (qobuz or bandcamp)
+        #[arg(long, value_name = "NAME")]
+        service: Option<String>,
+
+        /// What to do when a file to be downloaded already exists with a size
+        /// that doesn't fit the track (e.g. an old low-bitrate rip): keep,
+        /// overwrite, or keep-both [default: keep]
+        #[arg(long, value_name = "POLICY")]
+        on_size_mismatch: Option<models::SizeMismatchPolicy>,
+
+        #[command(flatten)]
+        gifts: GiftArgs,
+    },
+
+    /// List purchases without downloading anything
+    ///
+    /// Prints one tab-separated line per purchase: service, type (album or
+    /// track), store ID, artist, title, track count ("-" if the store
+    /// doesn't report it up front), and "purchase" or "gift".
+    ///
+    /// With --bandcamp-wishlist, lists the Bandcamp wishlist instead: type,
+    /// ID, artist, title, and the page to buy it from.
+    List {
+        /// List only the specified service (qobuz or bandcamp)
+        #[arg(long, value_name = "NAME")]
+        service: Option<String>,
+
+        #[command(flatten)]
+        gifts: GiftArgs,
+
+        /// Sort in byte order instead of accent-insensitive order
+        #[arg(long)]
+        byte_order: bool,
+
+        /// List what is wishlisted on Bandcamp rather than purchases
+        #[arg(long, conflicts_with_all = ["service", "exclude_gifts", "only_gifts"])]
+        bandcamp_wishlist: bool,
+
+        /// Also write the wishlist to PATH as JSON; CSV if PATH ends in .csv
+        #[arg(long, value_name = "PATH", requires = "bandcamp_wishlist")]
+        export: Option<PathBuf>,
+    },
+
+    /// Compare a synced directory against current purchases
+    ///
+    /// Lists purchased albums that are partially synced or missing locally,
+    /// and audio files that don't belong to any purchase. Nothing is
+    /// downloaded or changed.
+    Status {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+
+        /// Check only the specified service (qobuz or bandcamp)
+        #[arg(long, value_name = "NAME")]
+        service: Option<String>,
+
+        /// Sort in byte order instead of accent-insensitive order
+        #[arg(long)]
+        byte_order: bool,
+    },
+
+    /// Check synced files for damage and manual moves
+    ///
+    /// Flags files that are empty, suspiciously small, or not audio at all
+    /// (e.g. HTML error pages saved as .mp3/.m4a). Files that were moved or
+    /// renamed within the target directory are recognized by extension and
+    /// size, so they can be re-linked instead of downloaded again.
+    Verify {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+
+        /// Also compare file contents against checksums recorded at download
+        /// (reads every file in full)
+        #[arg(long)]
+        checksums: bool,
+
+        /// Record the new locations of moved files in the manifest
+        #[arg(long)]
+        update_manifest: bool,
+
+        /// Move damaged files to the trash so the next sync downloads them again
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Print the local directory of a synced album
+    ///
+    /// QUERY is matched case-insensitively against synced artists and albums,
+    /// either as free text or as "artist/album".
+    Open {
+        /// Directory previously used as a sync target
+        target_dir: PathBuf,
+
+        /// Artist and/or album to look up
+        query: String,
+
+        /// Open the directory with the system file manager instead of printing it
+        #[arg(long)]
+        launch: bool,
+
+        /// List ambiguous matches in byte order instead of accent-insensitive order
+        #[arg(long)]
+        byte_order: bool,
+    },
+
+    /// Search purchases and the local library, or the Qobuz catalog
+    ///
+    /// Matches artist, album, and track names against the purchase lists
+    /// cached by the last sync and the target directory's manifest. Each hit
+    /// shows its service and, if synced, its local path.
+    ///
+    /// With --catalog (qoget search --catalog "miles davis"), searches the
+    /// Qobuz catalog and the Bandcamp collection instead. Each match is
+    /// printed as a tab-separated line: service, type (album or track),
+    /// what to pass to `qoget get`, artist, and title.
+    Search {
+        /// Directory previously used as a sync target, followed by the
+        /// query; given only the query, the directory is `target_dir`
+        /// under [sync] in the config file
+        #[arg(value_name = "TARGET_DIR", required_unless_present = "catalog")]
+        target_dir: Option<PathBuf>,
+
+        /// Text to search for (case-insensitive)
+        query: Option<String>,
+
+        /// Search the Qobuz catalog and the Bandcamp collection for QUERY
+        /// instead of purchases and the library
+        #[arg(long, value_name = "QUERY", conflicts_with_all = ["target_dir", "query"])]
+        catalog: Option<String>,
+
+        /// Sort results in byte order instead of accent-insensitive order
+        #[arg(long)]
+        byte_order: bool,
+    },
+
+    /// Export Qobuz playlists as M3U files of synced tracks
+    ///
+    /// Writes one .m3u8 file per playlist to the target's Playlists/
+    /// directory, listing the playlist's tracks that have been synced, by
+    /// paths relative to it. Tracks not in the library are left out, and
+    /// playlists with none in it are not written.
+    Playlists {
+        /// Directory previously used as a sync target [default: from config]
+        target_dir: Option<PathBuf>,
+
+        /// List the playlists and what they would hold without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let verbosity = logging::Verbosity::from_flags(cli.verbose, cli.quiet);
+    // Progress bars' redraws would fill a cron job's log with control
+    // characters.
+    let quiet = verbosity == logging::Verbosity::Quiet;
+    let log_file = cli.log_file.or_else(config::configured_log_file);
+    if let Err(e) = logging::init(verbosity, log_file.as_deref()) {
+        eprintln!("Error: {e:#}");
+        process::exit(1);
+    }
+    network::warn_insecure(&config::configured_network());
+    debug!(
+        "qoget {} started: {:?}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::args().skip(1).collect::<Vec<_>>()
+    );
+
+    match cli.command {
+        Command::Sync {
+            target_dir,
+            dry_run,
+            interactive,
+            service,
+            artist,
+            album,
+            since,
+            include_favorites,
+            windows_safe_names,
+            on_size_mismatch,
+            quality,
+            report,
+            fail_on,
+            wait_lock,
+            ignore_space,
+            keep_zips,
+            prune,
+            prune_dry_run,
+            deep_scan,
+            replaygain,
+            gifts,
+        } => {
+            let opts = SyncOptions {
+                dry_run,
+                gifts: gifts.filter(),
+                on_size_mismatch,
+                quality,
+                ignore_space,
+                keep_archives: keep_zips,
+                replaygain,
+                deep_scan,
+                prune: match (prune || prune_dry_run, prune_dry_run || dry_run) {
+                    (false, _) => None,
+                    (true, true) => Some(PruneMode::List),
+                    (true, false) => Some(PruneMode::Trash),
+                },
+                interactive,
+                names: models::NameFilter { artist, album },
+                since,
+                include_favorites,
+                naming: path::Naming {
+                    windows_safe: windows_safe_names,
+                    ..Default::default()
+                },
+                quiet,
+                ..Default::default()
+            };
+            match run_sync(target_dir, opts, service, report, fail_on, wait_lock).await {
+                Ok(0) => {}
+                Ok(code) => process::exit(code),
+                Err(e) => {
+                    error!("{e:#}");
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Get {
+            item,
+            target_dir,
+            dry_run,
+            quality,
+        } => {
+            let service = Some(item.service().to_string());
+            let opts = SyncOptions {
+                dry_run,
+                quality,
+                item: Some(item),
+                quiet,
+                ..Default::default()
+            };
+            match run_sync(target_dir, opts, service, None, None, false).await {
+                Ok(0) => {}
+                Ok(code) => process::exit(code),
+                Err(e) => {
+                    error!("{e:#}");
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Retry {
+            target_dir,
+            dry_run,
+        } => match run_retry(target_dir, dry_run, quiet).await {
+            Ok(0) => {}
+            Ok(code) => process::exit(code),
+            Err(e) => {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        },
+        Command::Trash { action } => {
+            if let Err(e) = run_trash(action) {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Art { action } => {
+            if let Err(e) = run_art(action, quiet).await {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "qoget", &mut std::io::stdout());
+        }
+        Command::Manpage { out_dir } => {
+            if let Err(e) = run_manpage(out_dir) {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Config { action } => {
+            if let Err(e) = run_config(action) {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Login {
+            service,
+            from_browser,
+        } => {
+            if let Err(e) = run_login(service, from_browser).await {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Backfill {
+            target_dir,
+            budget,
+            pace,
+            service,
+            status,
+            gifts,
+        } => {
+            let opts = SyncOptions {
+                gifts: gifts.filter(),
+                limits: download::DownloadLimits::backfill(
+                    std::time::Duration::from_secs(pace),
+                    budget,
+                ),
+                quiet,
+                ..Default::default()
+            };
+            match run_backfill(target_dir, opts, service, status).await {
+                Ok(0) => {}
+                Ok(code) => process::exit(code),
+                Err(e) => {
+                    error!("{e:#}");
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Daemon {
+            target_dir,
+            interval,
+            service,
+            on_size_mismatch,
+            gifts,
+        } => {
+            let interval = std::time::Duration::from_secs(interval);
+            let opts = SyncOptions {
+                gifts: gifts.filter(),
+                on_size_mismatch,
+                quiet,
+                ..Default::default()
+            };
+            if let Err(e) = run_daemon(target_dir, interval, service, opts).await {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::List {
+            service,
+            gifts,
+            byte_order,
+            bandcamp_wishlist,
+            export,
+        } => {
+            let order = collate::SortOrder::from_byte_order_flag(byte_order);
+            let result = if bandcamp_wishlist {
+                run_wishlist(export, order, quiet).await
+            } else {
+                run_list(service, gifts.filter(), order, quiet).await
+            };
+            if let Err(e) = result {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Status {
+            target_dir,
+            service,
+            byte_order,
+        } => {
+            let order = collate::SortOrder::from_byte_order_flag(byte_order);
+            if let Err(e) = run_status(target_dir, service, order, quiet).await {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Verify {
+            target_dir,
+            checksums,
+            update_manifest,
+            repair,
+        } => {
+            let fixes = VerifyFixes {
+                update_manifest,
+                repair,
+            };
+            if let Err(e) = run_verify(target_dir, checksums, fixes) {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Open {
+            target_dir,
+            query,
+            launch,
+            byte_order,
+        } => {
+            let order = collate::SortOrder::from_byte_order_flag(byte_order);
+            if let Err(e) = run_open(&target_dir, &query, launch, order) {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Search {
+            target_dir,
+            query,
+            catalog,
+            byte_order,
+        } => {
+            let order = collate::SortOrder::from_byte_order_flag(byte_order);
+            let result = match (catalog, target_dir, query) {
+                (Some(catalog), ..) => run_catalog_search(&catalog, quiet).await,
+                (None, Some(target_dir), Some(query)) => run_search(&target_dir, &query, order),
+                // A lone argument is the query, for the configured target.
+                (None, query, None) => {
+                    let query = query.unwrap_or_default();
+                    resolve_target_dir(None)
+                        .and_then(|dir| run_search(&dir, &query.to_string_lossy(), order))
+                }
+                (None, None, Some(_)) => unreachable!("clap fills TARGET_DIR first"),
+            };
+            if let Err(e) = result {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+        Command::Playlists {
+            target_dir,
+            dry_run,
+        } => {
+            if let Err(e) = run_playlists(target_dir, dry_run, quiet).await {
+                error!("{e:#}");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Credentials for commands that only read purchase lists (`list`,
+/// `status`, `search`), and the options their clients and paths are set up
+/// with. Prompts for Qobuz only when it is partly configured, explicitly
+/// requested, or the sole option.
+fn resolve_read_only_services(
+    service: Option<&str>,
+    quiet: bool,
+) -> Result<(
+    Option<config::QobuzLogin>,
+    Option<config::BandcampConfig>,
+    SyncOptions,
+)> {
+    let cfg = config::load_config()?;
+    let opts = SyncOptions {
+        quiet,
+        ..Default::default()
+    }
+    .with_config(&cfg);
+
+    let service_filter = match service {
+        Some(s) => Some(parse_service(s)?),
+        None => None,
+    };
+    let should_run = |svc: models::Service| -> bool { service_filter.is_none_or(|f| f == svc) };
+
+    let qobuz_cfg = match cfg.qobuz {
+        _ if !should_run(models::Service::Qobuz) => None,
+        config::QobuzState::Ready(qobuz_cfg) => Some(config::QobuzLogin::Password(qobuz_cfg)),
+        config::QobuzState::Saved(session) => Some(config::QobuzLogin::Saved(session)),
+        config::QobuzState::Incomplete => Some(config::QobuzLogin::Password(
+            config::prompt_qobuz_credentials()?,
+        )),
+        config::QobuzState::NotConfigured if service_filter.is_some() || cfg.bandcamp.is_none() => {
+            Some(config::QobuzLogin::Password(
+                config::prompt_qobuz_credentials()?,
+            ))
+        }
+        config::QobuzState::NotConfigured => None,
+    };
+    let bandcamp_cfg = cfg
+        .bandcamp
+        .filter(|_| should_run(models::Service::Bandcamp));
+    if service_filter == Some(models::Service::Bandcamp) && bandcamp_cfg.is_none() {
+        bail!("Bandcamp is not configured. Set BANDCAMP_IDENTITY or add [bandcamp] to config.");
+    }
+    Ok((qobuz_cfg, bandcamp_cfg, opts))
+}
+
+async fn run_status(
+    target_dir: Option<PathBuf>,
+    service: Option<String>,
+    order: collate::SortOrder,
+    quiet: bool,
+) -> Result<()> {
+    let target_dir = &resolve_target_dir(target_dir)?;
+    let (qobuz_cfg, bandcamp_cfg, opts) = resolve_read_only_services(service.as_deref(), quiet)?;
+
+    let mut purchases = Vec::new();
+    if let Some(qobuz_cfg) = qobuz_cfg {
+        let (qobuz, mut list) = engine::fetch_qobuz_purchases(qobuz_cfg, &opts).await?;
+        engine::fill_album_tracks(&qobuz, &mut list, &opts).await?;
+        purchases.push((models::Service::Qobuz, list));
+    }
+    if let Some(bandcamp_cfg) = bandcamp_cfg {
+        let (_, bc) = engine::fetch_bandcamp_purchases(bandcamp_cfg, &opts).await?;
+        purchases.push((models::Service::Bandcamp, bandcamp::to_purchase_list(&bc)));
+    }
+
+    info!("Scanning {}...", target_dir.display());
+    let local_files = status::scan_library(target_dir)?;
+    let manifest = manifest::Manifest::load(target_dir)?;
+    let lists: Vec<_> = purchases.iter().map(|(svc, list)| (*svc, list)).collect();
+    let mut report =
+        status::match_library(&lists, &local_files, &manifest, opts.naming, opts.transcode);
+
+    report.albums.sort_by(|a, b| {
+        collate::compare_fields(&[&a.artist, &a.title], &[&b.artist, &b.title], order)
+    });
+    for album in &report.albums {
+        let label = match album.state {
+            status::SyncState::Complete => continue,
+            status::SyncState::Partial => "partial",
+            status::SyncState::Missing => "missing",
+        };
+        let counts = match album.expected {
+            Some(expected) => format!(" ({}/{} tracks)", album.present, expected),
+            None => String::new(),
+        };
+        println!(
+            "[{label}] {:<8} {} / {}{counts}",
+            album.service, album.artist, album.title
+        );
+    }
+    for path in &report.unmatched {
+        println!("[unmatched] {}", path.display());
+    }
+
+    info!(
+        target: logging::SUMMARY,
+        "\n{} albums complete, {} partial, {} missing; {} local files not in any purchase",
+        report.count(status::SyncState::Complete),
+        report.count(status::SyncState::Partial),
+        report.count(status::SyncState::Missing),
+        report.unmatched.len()
+    );
+    Ok(())
+}
+
+async fn run_list(
+    service: Option<String>,
+    gifts: models::GiftFilter,
+    order: collate::SortOrder,
+    quiet: bool,
+) -> Result<()> {
+    let (qobuz_cfg, bandcamp_cfg, opts) = resolve_read_only_services(service.as_deref(), quiet)?;
+
+    let mut entries = Vec::new();
+    if let Some(qobuz_cfg) = qobuz_cfg {
+        let (_, mut purchases) = engine::fetch_qobuz_purchases(qobuz_cfg, &opts).await?;
+        purchases.retain_gifts(gifts);
+        entries.extend(list::from_qobuz(&purchases));
+    }
+    if let Some(bandcamp_cfg) = bandcamp_cfg {
+        let (_, mut purchases) = engine::fetch_bandcamp_purchases(bandcamp_cfg, &opts).await?;
+        purchases.retain_gifts(gifts);
+        entries.extend(list::from_bandcamp(&purchases));
+    }
+
+    list::sort(&mut entries, order);
+    for entry in &entries {
+        println!("{}", entry.to_line());
+    }
+    info!(target: logging::SUMMARY, "\n{} purchases", entries.len());
+    Ok(())
+}
+
+async fn run_wishlist(
+    export: Option<PathBuf>,
+    order: collate::SortOrder,
+    quiet: bool,
+) -> Result<()> {
+    let (_, Some(bandcamp_cfg), opts) = resolve_read_only_services(Some("bandcamp"), quiet)? else {
+        bail!("Bandcamp is not configured");
+    };
+    let bc_client = bandcamp::BandcampClient::new(
+        bandcamp_cfg.identity_cookie,
+        opts.requests.bandcamp,
+        &opts.network,
+    )?;
+    let fan_id = bc_client.verify_auth().await?;
+
+    let multi = progress::multi(opts.quiet);
+    let pb = progress::spinner(&multi, "Fetching Bandcamp wishlist");
+    let items = bc_client.get_wishlist(fan_id, &pb).await;
+    pb.finish_and_clear();
+
+    let mut entries = list::from_bandcamp_wishlist(&items?);
+    list::sort_wishlist(&mut entries, order);
+    for entry in &entries {
+        println!("{}", entry.to_line());
+    }
+    if let Some(path) = export {
+        list::export_wishlist(&entries, &path)?;
+        info!("Wrote {}", path.display());
+    }
+    info!(target: logging::SUMMARY, "\n{} wishlisted items", entries.len());
+    Ok(())
+}
+
+/// How many albums, and how many tracks, a catalog search lists.
+const CATALOG_SEARCH_LIMIT: u32 = 20;
 
-        /// Preview what would be downloaded without downloading
-        #[arg(long)]
-        dry_run: bool,
+/// `search QUERY`: the Qobuz catalog and the Bandcamp collection.
+async fn run_catalog_search(query: &str, quiet: bool) -> Result<()> {
+    let (qobuz_cfg, bandcamp_cfg, opts) = resolve_read_only_services(None, quiet)?;
 
-        /// Sync only the specified service (qobuz or bandcamp)
-        #[arg(long, value_name = "NAME")]
-        service: Option<String>,
-    },
+    let mut hits = Vec::new();
+    if let Some(qobuz_cfg) = qobuz_cfg {
+        let (qobuz, _) = engine::qobuz_client(qobuz_cfg, &opts).await?;
+        let results = qobuz.search_catalog(query, CATALOG_SEARCH_LIMIT).await?;
+        hits.extend(search::qobuz_catalog_hits(&results));
+    }
+    if let Some(bandcamp_cfg) = bandcamp_cfg {
+        let (_, purchases) = engine::fetch_bandcamp_purchases(bandcamp_cfg, &opts).await?;
+        hits.extend(search::bandcamp_collection_hits(&purchases, query));
+    }
+
+    for hit in &hits {
+        println!("{}", hit.to_line());
+    }
+    info!(target: logging::SUMMARY, "\n{} matches", hits.len());
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+/// What `verify` may change on disk.
+struct VerifyFixes {
+    /// Record moved files' new paths in the manifest.
+    update_manifest: bool,
+    /// Move damaged files to the trash and forget them, so the next sync
+    /// re-downloads them.
+    repair: bool,
+}
 
-    match cli.command {
-        Command::Sync {
-            target_dir,
-            dry_run,
-            service,
-        } => {
-            if let Err(e) = run_sync(&target_dir, dry_run, service).await {
-                eprintln!("Error: {e:#}");
-                process::exit(1);
+fn run_verify(target_dir: Option<PathBuf>, checksums: bool, fixes: VerifyFixes) -> Result<()> {
+    let target_dir = &resolve_target_dir(target_dir)?;
+    let mut manifest = manifest::Manifest::load(target_dir)?;
+    if manifest.is_empty() {
+        bail!(
+            "No sync manifest in {}. Run `qoget sync` into this directory first.",
+            target_dir.display()
+        );
+    }
+
+    let local = verify::scan_files(target_dir)?;
+    let mut report = verify::check_manifest(&manifest, &local);
+    verify::check_integrity(target_dir, &manifest, checksums, &mut report)?;
+    if checksums {
+        verify::check_checksum_files(target_dir, &manifest, &local, &mut report)?;
+    }
+    let before = manifest.clone();
+
+    for moved in &report.moved {
+        println!(
+            "[moved]   {} -> {}",
+            moved.from.display(),
+            moved.to.display()
+        );
+    }
+    for missing in &report.missing {
+        println!("[missing] {}", missing.path.display());
+    }
+    for damaged in &report.damaged {
+        println!(
+            "[damaged] {}: {}",
+            damaged.path.display(),
+            damaged.damage.describe()
+        );
+    }
+
+    let mut changed = false;
+    if !report.moved.is_empty() {
+        if fixes.update_manifest {
+            for moved in &report.moved {
+                manifest.relocate(&moved.key, moved.to.clone());
+            }
+            changed = true;
+            info!(target: logging::SUMMARY, "Updated {} manifest entries", report.moved.len());
+        } else {
+            info!(
+                target: logging::SUMMARY,
+                "\n{} files were moved. Run with --update-manifest to record their new \
+                 locations so sync doesn't download them again.",
+                report.moved.len()
+            );
+        }
+    }
+    if !report.damaged.is_empty() {
+        if fixes.repair {
+            let bin = trash::Trash::new(target_dir);
+            for damaged in &report.damaged {
+                bin.move_file(&damaged.path)?;
+                manifest.remove(&damaged.key);
+            }
+            changed = true;
+            info!(
+                target: logging::SUMMARY,
+                "Moved {} damaged files to {}; run `qoget sync` to download them again",
+                report.damaged.len(),
+                bin.batch_dir().display()
+            );
+        } else {
+            info!(
+                target: logging::SUMMARY,
+                "\n{} files are damaged. Run with --repair to move them to the trash so \
+                 the next sync downloads them again.",
+                report.damaged.len()
+            );
+        }
+    }
+    if changed {
+        manifest.save(target_dir)?;
+        checksums::refresh_changed(target_dir, &before, &manifest);
+    }
+
+    if !report.missing.is_empty() {
+        bail!(
+            "{} recorded files are missing; `qoget sync` will download them again",
+            report.missing.len()
+        );
+    }
+    let unresolved = (!fixes.update_manifest && !report.moved.is_empty())
+        || (!fixes.repair && !report.damaged.is_empty());
+    if unresolved {
+        bail!("Library has problems; see above");
+    }
+    if report.is_clean() {
+        info!(target: logging::SUMMARY, "All recorded files are in place and intact");
+    }
+    Ok(())
+}
+
+fn run_search(target_dir: &std::path::Path, query: &str, order: collate::SortOrder) -> Result<()> {
+    let manifest = manifest::Manifest::load(target_dir)?;
+    let cache_dir = config::cache_dir();
+    let mut snapshots = Vec::new();
+    for service in [models::Service::Qobuz, models::Service::Bandcamp] {
+        if let Some(snap) = snapshot::Snapshot::load(&cache_dir, service)? {
+            snapshots.push(snap);
+        }
+    }
+    if snapshots.is_empty() && manifest.is_empty() {
+        bail!("Nothing to search. Run `qoget sync` first.");
+    }
+
+    let mut hits = search::search(query, &snapshots, &manifest);
+    hits.sort_by(|a, b| {
+        collate::compare_fields(
+            &[&a.artist, &a.album, a.track.as_deref().unwrap_or("")],
+            &[&b.artist, &b.album, b.track.as_deref().unwrap_or("")],
+            order,
+        )
+    });
+    for hit in &hits {
+        let name = match &hit.track {
+            Some(track) => format!("{} / {} / {}", hit.artist, hit.album, track),
+            None => format!("{} / {}", hit.artist, hit.album),
+        };
+        match &hit.local_path {
+            Some(path) => println!(
+                "[synced] {:<8} {name}\n         {}",
+                hit.service,
+                target_dir.join(path).display()
+            ),
+            None => println!("[remote] {:<8} {name}", hit.service),
+        }
+    }
+
+    let synced = hits.iter().filter(|h| h.is_synced()).count();
+    info!(
+        target: logging::SUMMARY,
+        "\n{} matches ({} synced, {} remote only)",
+        hits.len(),
+        synced,
+        hits.len() - synced
+    );
+    Ok(())
+}
+
+fn run_open(
+    target_dir: &std::path::Path,
+    query: &str,
+    launch: bool,
+    order: collate::SortOrder,
+) -> Result<()> {
+    let manifest = manifest::Manifest::load(target_dir)?;
+    if manifest.is_empty() {
+        bail!(
+            "No sync manifest in {}. Run `qoget sync` into this directory first.",
+            target_dir.display()
+        );
+    }
+
+    let mut matches = manifest.find_albums(query);
+    matches.sort_by(|a, b| {
+        collate::compare_fields(&[&a.artist, &a.album], &[&b.artist, &b.album], order)
+    });
+    let album = match matches.as_slice() {
+        [] => bail!("No synced album matches '{query}'"),
+        [album] => album,
+        many => {
+            eprintln!("'{query}' matches {} albums:", many.len());
+            for a in many {
+                eprintln!("  {} / {}", a.artist, a.album);
             }
+            bail!("Query is ambiguous; use \"artist/album\" to narrow it down");
         }
+    };
+
+    let dir = target_dir.join(&album.dir);
+    if launch {
+        open_with_system_handler(&dir)
+    } else {
+        println!("{}", dir.display());
+        Ok(())
+    }
+}
+
+fn open_with_system_handler(dir: &std::path::Path) -> Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    let status = process::Command::new(program)
+        .arg(dir)
+        .status()
+        .with_context(|| format!("Failed to run {program}"))?;
+    // explorer.exe exits non-zero even on success
+    if !status.success() && !cfg!(target_os = "windows") {
+        bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+/// The target directory given on the command line, else `[sync] target_dir`
+/// from the config file.
+fn target_dir_from(arg: Option<PathBuf>, cfg: &config::Config) -> Result<PathBuf> {
+    arg.or_else(|| cfg.sync.target_dir.clone()).context(
+        "No target directory given. Pass one, or set target_dir under [sync] in \
+         ~/.config/qoget/config.toml",
+    )
+}
+
+/// Like `target_dir_from`, loading the config only when it's needed.
+fn resolve_target_dir(arg: Option<PathBuf>) -> Result<PathBuf> {
+    match arg {
+        Some(dir) => Ok(dir),
+        None => target_dir_from(None, &config::load_config()?),
     }
 }
 
@@ -72,287 +1291,609 @@ fn parse_service(s: &str) -> Result<models::Service> {
     }
 }
 
+/// Returns the exit status once the services have been synced; errors are
+/// for runs that never got that far.
 async fn run_sync(
-    target_dir: &std::path::Path,
-    dry_run: bool,
+    target_dir: Option<PathBuf>,
+    opts: SyncOptions,
     service: Option<String>,
-) -> Result<()> {
+    report_path: Option<PathBuf>,
+    fail_on: Option<models::FailOn>,
+    wait_lock: bool,
+) -> Result<i32> {
     let cfg = config::load_config()?;
+    let fail_on = fail_on.or(cfg.sync.fail_on).unwrap_or_default();
+    let target_dir = target_dir_from(target_dir, &cfg)?;
+    let mut engine = SyncEngine::new(cfg, target_dir, opts).wait_lock(wait_lock);
+    if let Some(s) = service.as_deref() {
+        engine = engine.service(parse_service(s)?);
+    }
+    let SyncRun { report, result } = engine.run().await?;
+    if let Some(path) = report_path {
+        report.write(&path)?;
+        info!("Report written to {}", path.display());
+    }
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) if report.tolerates(fail_on) => {
+            warn!("{e:#}; exiting successfully under --fail-on {fail_on}");
+            Ok(0)
+        }
+        Err(e) => {
+            error!("{e:#}");
+            Ok(report.exit_code())
+        }
+    }
+}
 
-    let service_filter = match service.as_deref() {
-        Some(s) => Some(parse_service(s)?),
-        None => None,
+/// Sync only what the last run's failures file lists, into the target
+/// directory it names. Returns the exit status, like `run_sync`.
+async fn run_retry(target_dir: Option<PathBuf>, dry_run: bool, quiet: bool) -> Result<i32> {
+    let path = config::state_dir().join(report::FAILURES_FILE);
+    let retry = report::RetryList::load(&path)?.unwrap_or_default();
+    let services = retry.services();
+    if services.is_empty() {
+        info!("No failed downloads to retry.");
+        return Ok(0);
+    }
+    if let Some(ref dir) = target_dir
+        && !same_dir(dir, &retry.target_dir)
+    {
+        bail!(
+            "The failed downloads were for {}, not {}",
+            retry.target_dir.display(),
+            dir.display()
+        );
+    }
+    let target_dir = target_dir.unwrap_or_else(|| retry.target_dir.clone());
+    let service = match services[..] {
+        [service] => Some(service.to_string()),
+        _ => None,
+    };
+    let opts = SyncOptions {
+        dry_run,
+        retry: Some(retry),
+        quiet,
+        ..Default::default()
     };
+    run_sync(Some(target_dir), opts, service, None, None, false).await
+}
 
-    let should_run = |svc: models::Service| -> bool { service_filter.is_none_or(|f| f == svc) };
+/// Whether `a` and `b` name the same directory, however they are spelled.
+fn same_dir(a: &std::path::Path, b: &std::path::Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
 
-    let has_bandcamp = cfg.bandcamp.is_some();
-    let qobuz_configured = cfg.qobuz.is_configured();
-
-    if !qobuz_configured && !has_bandcamp {
-        if service_filter.is_some() && service_filter != Some(models::Service::Qobuz) {
-            bail!(
-                "Bandcamp is not configured.\n\n\
-                 Add to ~/.config/qoget/config.toml:\n\n  \
-                 [bandcamp]\n  \
-                 identity_cookie = \"YOUR_COOKIE\"\n\n\
-                 To get the cookie: log in to bandcamp.com, open browser dev tools (F12),\n\
-                 go to Application > Cookies > bandcamp.com, and copy the 'identity' cookie value.\n\n\
-                 Or set the BANDCAMP_IDENTITY environment variable."
-            );
+/// Run one backfill session, or with `status` only report progress.
+/// Returns the exit status, like `run_sync`.
+async fn run_backfill(
+    target_dir: Option<PathBuf>,
+    opts: SyncOptions,
+    service: Option<String>,
+    status: bool,
+) -> Result<i32> {
+    let cfg = config::load_config()?;
+    let target_dir = target_dir_from(target_dir, &cfg)?;
+    // A remote target's progress is kept with its manifest.
+    let state_dir = storage::local_dir(&target_dir, &cfg.network)?;
+    if status {
+        print_backfill_status(&backfill::Backfill::load(&state_dir)?);
+        return Ok(0);
+    }
+    let mut state = backfill::Backfill::load(&state_dir)?;
+    if state.is_complete() {
+        info!("Backfill already complete; checking for anything new.");
+    }
+
+    let mut engine = SyncEngine::new(cfg, target_dir, opts);
+    if let Some(s) = service.as_deref() {
+        engine = engine.service(parse_service(s)?);
+    }
+    let engine = engine.before_release(move |report| {
+        state.record_session(report);
+        state.save(&state_dir)?;
+        print_backfill_status(&state);
+        Ok(())
+    });
+    let SyncRun { report, result } = engine.run().await?;
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            error!("{e:#}");
+            Ok(report.exit_code())
+        }
+    }
+}
+
+fn print_backfill_status(state: &backfill::Backfill) {
+    let Some(last) = state.sessions.last() else {
+        info!("No backfill sessions yet.");
+        return;
+    };
+    info!(
+        "Backfill: {} files downloaded over {} sessions; last session downloaded {}, \
+         {} failed, {} deferred",
+        state.downloaded(),
+        state.sessions.len(),
+        last.downloaded,
+        last.failed,
+        last.deferred
+    );
+    if state.is_complete() {
+        info!("Nothing left; from now on `qoget sync` keeps the library up to date.");
+        return;
+    }
+    for (service, backlog) in &state.backlog {
+        if backlog.is_empty() {
+            continue;
         }
-        // Nothing configured from file/env — try interactive Qobuz login
-        let qobuz_cfg = config::prompt_qobuz_credentials()?;
-        eprintln!("Syncing Qobuz...");
-        return run_qobuz_sync(qobuz_cfg, target_dir, dry_run).await;
+        info!(
+            "{service}: {} {} left, starting with {}",
+            backlog.len(),
+            backfill::unit(*service),
+            backlog[0]
+        );
     }
+    info!("Run `qoget backfill` again to continue.");
+}
 
-    let mut any_failure = false;
+fn run_manpage(out_dir: Option<PathBuf>) -> Result<()> {
+    match out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+            clap_mangen::generate_to(Cli::command(), &dir)
+                .with_context(|| format!("writing man pages to {}", dir.display()))?;
+            info!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(Cli::command())
+            .render(&mut std::io::stdout())
+            .context("writing man page")?,
+    }
+    Ok(())
+}
 
-    if should_run(models::Service::Qobuz) {
-        match cfg.qobuz {
-            config::QobuzState::Ready(qobuz_cfg) => {
-                eprintln!("Syncing Qobuz...");
-                if let Err(e) = run_qobuz_sync(qobuz_cfg, target_dir, dry_run).await {
-                    eprintln!("Qobuz sync failed: {e:#}");
-                    any_failure = true;
-                }
+fn run_config(action: ConfigCommand) -> Result<()> {
+    match action {
+        ConfigCommand::Init { force } => {
+            let path = config::config_path();
+            // Check before asking anything, not after the user typed it all.
+            if path.exists() && !force {
+                bail!(
+                    "{} already exists; pass --force to overwrite it",
+                    path.display()
+                );
             }
-            config::QobuzState::Incomplete => {
-                // Username found but password missing — prompt for it
-                match config::prompt_qobuz_credentials() {
-                    Ok(qobuz_cfg) => {
-                        eprintln!("Syncing Qobuz...");
-                        if let Err(e) = run_qobuz_sync(qobuz_cfg, target_dir, dry_run).await {
-                            eprintln!("Qobuz sync failed: {e:#}");
-                            any_failure = true;
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Qobuz sync skipped: {e:#}");
-                        any_failure = true;
-                    }
-                }
+            let answers = config::prompt_init_answers()?;
+            let path = config::write_config_file(&config::render_config(&answers), force)?;
+            eprintln!("\nWrote {}", path.display());
+            if answers.qobuz_username.is_some() && answers.qobuz_password.is_none() {
+                eprintln!("Run `qoget login qobuz` to save a session instead of a password.");
             }
-            config::QobuzState::NotConfigured if service_filter.is_some() => {
-                // User explicitly requested Qobuz but nothing configured
-                match config::prompt_qobuz_credentials() {
-                    Ok(qobuz_cfg) => {
-                        eprintln!("Syncing Qobuz...");
-                        if let Err(e) = run_qobuz_sync(qobuz_cfg, target_dir, dry_run).await {
-                            eprintln!("Qobuz sync failed: {e:#}");
-                            any_failure = true;
-                        }
-                    }
-                    Err(e) => bail!("Qobuz is not configured: {e:#}"),
-                }
+        }
+        ConfigCommand::Validate => {
+            let report = inspect_current_config()?;
+            for (service, status) in &report.services {
+                println!("{service}: {status}");
+            }
+            for problem in &report.problems {
+                eprintln!("Problem: {problem}");
+            }
+            match report.problems.len() {
+                0 => eprintln!("Config OK"),
+                1 => bail!("1 problem found"),
+                n => bail!("{n} problems found"),
             }
-            config::QobuzState::NotConfigured => {}
         }
-    }
-
-    if should_run(models::Service::Bandcamp) {
-        match cfg.bandcamp {
-            Some(bandcamp_cfg) => {
-                eprintln!("Syncing Bandcamp...");
-                if let Err(e) = run_bandcamp_sync(bandcamp_cfg, target_dir, dry_run).await {
-                    eprintln!("Bandcamp sync failed: {e:#}");
-                    any_failure = true;
+        ConfigCommand::SetSecret { secret, entry } => {
+            let name = entry.as_deref().unwrap_or(secret.default_entry());
+            let value = config::read_secret(secret)?;
+            secrets::set(name, &value)?;
+            eprintln!("Stored {secret} in the keyring as qoget/{name}");
+            let reference = match entry {
+                Some(ref name) => format!("keyring:{name}"),
+                None => "keyring:".to_string(),
+            };
+            match secret {
+                secrets::Secret::QobuzPassword => {
+                    eprintln!("Use it with `password = \"{reference}\"` under [qobuz]");
+                }
+                secrets::Secret::BandcampIdentity => {
+                    eprintln!("Use it with `identity_cookie = \"{reference}\"` under [bandcamp]");
                 }
             }
-            None if service_filter.is_some() => {
-                bail!(
-                    "Bandcamp is not configured.\n\n\
-                     Add to ~/.config/qoget/config.toml:\n\n  \
-                     [bandcamp]\n  \
-                     identity_cookie = \"YOUR_COOKIE\"\n\n\
-                     To get the cookie: log in to bandcamp.com, open browser dev tools (F12),\n\
-                     go to Application > Cookies > bandcamp.com, and copy the 'identity' cookie value.\n\n\
-                     Or set the BANDCAMP_IDENTITY environment variable."
-                );
-            }
-            None => {}
         }
-    }
-
-    // Hint about unconfigured services (only when no --service filter)
-    if service_filter.is_none() {
-        if !qobuz_configured && has_bandcamp {
-            eprintln!(
-                "\nHint: Qobuz sync is also available. \
-                 Set QOBUZ_USERNAME/QOBUZ_PASSWORD or add [qobuz] to config."
-            );
+        ConfigCommand::DeleteSecret { secret, entry } => {
+            let name = entry.as_deref().unwrap_or(secret.default_entry());
+            if secrets::delete(name)? {
+                eprintln!("Removed qoget/{name} from the keyring");
+            } else {
+                eprintln!("No keyring entry qoget/{name}");
+            }
         }
-        if !has_bandcamp && qobuz_configured {
-            eprintln!(
-                "\nHint: Bandcamp sync is also available. \
-                 Set BANDCAMP_IDENTITY or add [bandcamp] to config."
-            );
+        ConfigCommand::Show => {
+            let report = inspect_current_config()?;
+            let key_width = report.settings.iter().map(|s| s.key.len()).max();
+            let value_width = report.settings.iter().map(|s| s.value.len()).max();
+            for setting in &report.settings {
+                println!(
+                    "{:kw$}  {:vw$}  ({})",
+                    setting.key,
+                    setting.value,
+                    setting.source,
+                    kw = key_width.unwrap_or(0),
+                    vw = value_width.unwrap_or(0),
+                );
+            }
+            if report.settings.is_empty() {
+                eprintln!("Nothing is configured");
+            }
+            println!();
+            for (service, status) in &report.services {
+                println!("{service}: {status}");
+            }
+            for problem in &report.problems {
+                eprintln!("Problem: {problem}");
+            }
         }
     }
-
-    if any_failure {
-        bail!("One or more services failed");
-    }
-
     Ok(())
 }
 
-async fn run_qobuz_sync(
-    qobuz_cfg: config::QobuzConfig,
-    target_dir: &std::path::Path,
-    dry_run: bool,
-) -> Result<()> {
-    let http = reqwest::Client::new();
-
-    let config::QobuzConfig {
-        username,
-        password,
-        app_id,
-        app_secret,
-    } = qobuz_cfg;
-
-    let creds = match (app_id, app_secret) {
-        (Some(id), Some(secret)) => models::AppCredentials {
-            app_id: id,
-            app_secret: secret,
-        },
-        _ => {
-            eprintln!("Extracting app credentials from Qobuz...");
-            bundle::extract_credentials(&http).await?
-        }
+/// Inspect the config file, environment and saved sessions as a sync would
+/// see them.
+fn inspect_current_config() -> Result<config::ConfigReport> {
+    let path = config::config_path();
+    if path.exists() {
+        eprintln!("Config file: {}", path.display());
+    } else {
+        eprintln!("Config file: {} (not found)", path.display());
+    }
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
     };
+    let sessions = session::SessionFile::load(&config::config_dir())?;
+    config::inspect_config(&contents, |var| std::env::var(var).ok(), &sessions)
+}
 
-    eprintln!("Logging in to Qobuz...");
-    let auth = client::login(&http, &creds.app_id, &username, &password).await?;
-    eprintln!("Logged in as user {}", auth.user_id);
+async fn run_art(action: ArtCommand, quiet: bool) -> Result<()> {
+    let ArtCommand::Backfill {
+        target_dir,
+        service,
+        dry_run,
+    } = action;
+    let target_dir = &resolve_target_dir(target_dir)?;
+    let (qobuz_cfg, bandcamp_cfg, opts) = resolve_read_only_services(service.as_deref(), quiet)?;
 
-    let qobuz = client::QobuzClient::new(http, creds.app_id, creds.app_secret, auth.token);
+    let mut purchases = Vec::new();
+    if let Some(qobuz_cfg) = qobuz_cfg {
+        let (qobuz, mut list) = engine::fetch_qobuz_purchases(qobuz_cfg, &opts).await?;
+        engine::fill_album_tracks(&qobuz, &mut list, &opts).await?;
+        purchases.push((models::Service::Qobuz, list));
+    }
+    if let Some(bandcamp_cfg) = bandcamp_cfg {
+        let (_, bc) = engine::fetch_bandcamp_purchases(bandcamp_cfg, &opts).await?;
+        purchases.push((models::Service::Bandcamp, bandcamp::to_purchase_list(&bc)));
+    }
 
-    eprintln!("Fetching Qobuz purchases...");
-    let mut purchases = qobuz.get_purchases().await?;
-    eprintln!(
-        "Found {} albums and {} standalone tracks",
-        purchases.albums.len(),
-        purchases.tracks.len()
-    );
+    let manifest = manifest::Manifest::load(target_dir)?;
+    let lists: Vec<_> = purchases.iter().map(|(svc, list)| (*svc, list)).collect();
+    let plan = art::plan_backfill(target_dir, &lists, &manifest, opts.naming);
 
-    for album in &mut purchases.albums {
-        if album.tracks.is_none() {
-            let full = qobuz.get_album(&album.id).await?;
-            album.tracks = full.tracks;
+    let http = network::client(&opts.network)?;
+    let mut fetched = 0;
+    let mut failed = 0;
+    for task in &plan.tasks {
+        if dry_run {
+            println!("{}", task.dir.display());
+            continue;
+        }
+        match art::fetch_cover(&http, &task.url, &target_dir.join(&task.dir)).await {
+            Ok(_) => {
+                info!("  {} / {}", task.artist, task.title);
+                fetched += 1;
+            }
+            Err(e) => {
+                warn!("no cover for {} / {}: {e:#}", task.artist, task.title);
+                failed += 1;
+            }
         }
     }
 
-    let tasks = sync::collect_tasks(&purchases, target_dir, ".mp3");
-    let existing = sync::scan_existing(&tasks).await;
-    let plan = sync::build_sync_plan(tasks, &existing, dry_run);
-
-    eprintln!(
-        "{} tracks to download, {} already synced",
-        plan.downloads.len(),
-        plan.skipped.len()
+    let missing = if dry_run {
+        format!("{} missing a cover", plan.tasks.len())
+    } else {
+        format!("{fetched} covers fetched, {failed} failed")
+    };
+    info!(
+        "\n{missing}; {} already had one, {} have no store artwork, {} not synced",
+        plan.present, plan.unavailable, plan.not_synced
     );
-
-    if dry_run {
-        for task in &plan.skipped {
-            if matches!(task.reason, models::SkipReason::DryRun) {
-                println!("{}", task.target_path.display());
-            }
-        }
-        eprintln!(
-            "\nDry run: {} tracks would be downloaded, {} already synced",
-            plan.skipped
-                .iter()
-                .filter(|s| matches!(s.reason, models::SkipReason::DryRun))
-                .count(),
-            plan.skipped
-                .iter()
-                .filter(|s| matches!(s.reason, models::SkipReason::AlreadyExists))
-                .count(),
-        );
-        return Ok(());
+    if failed > 0 {
+        bail!("{failed} covers could not be fetched");
     }
+    Ok(())
+}
 
-    if plan.downloads.is_empty() {
-        eprintln!("Qobuz library is up to date.");
-        return Ok(());
-    }
+async fn run_playlists(target_dir: Option<PathBuf>, dry_run: bool, quiet: bool) -> Result<()> {
+    let target_dir = &resolve_target_dir(target_dir)?;
+    let (Some(login), _, opts) = resolve_read_only_services(Some("qobuz"), quiet)? else {
+        bail!("Qobuz is not configured");
+    };
+    let manifest = manifest::Manifest::load(target_dir)?;
+    let (qobuz, saved) = engine::qobuz_client(login, &opts).await?;
+    let playlists = qobuz.get_playlists().await;
+    let playlists = if saved {
+        playlists.context(
+            "Fetching playlists with the saved Qobuz session failed \
+             (run `qoget login qobuz` if it has expired)",
+        )?
+    } else {
+        playlists?
+    };
 
-    let result = download::execute_downloads(&qobuz, plan).await?;
+    let names = playlist::file_names(&playlists, opts.naming);
+    let mut written = 0;
+    for (list, name) in playlists.iter().zip(&names) {
+        let tracks = qobuz
+            .get_playlist_tracks(list.id)
+            .await
+            .with_context(|| format!("fetching playlist '{}'", list.name))?;
+        let rendered = playlist::render(&tracks, &manifest);
+        if rendered.found == 0 {
+            info!(
+                "  {}: none of {} tracks synced, skipped",
+                list.name,
+                tracks.len()
+            );
+            continue;
+        }
+        if dry_run {
+            println!(
+                "{name}\t{} synced\t{} missing",
+                rendered.found, rendered.missing
+            );
+        } else {
+            let path = playlist::write(target_dir, name, &rendered.contents)?;
+            info!(
+                "  {}: {} tracks ({} not synced)",
+                path.display(),
+                rendered.found,
+                rendered.missing
+            );
+        }
+        written += 1;
+    }
 
-    if result.fallback_count > 0 {
-        eprintln!(
-            "\nQobuz: {} succeeded ({} as FLAC), {} failed, {} skipped",
-            result.succeeded.len(),
-            result.fallback_count,
-            result.failed.len(),
-            result.skipped.len()
-        );
+    let verb = if dry_run {
+        "would be written"
     } else {
-        eprintln!(
-            "\nQobuz: {} succeeded, {} failed, {} skipped",
-            result.succeeded.len(),
-            result.failed.len(),
-            result.skipped.len()
-        );
-    }
+        "written"
+    };
+    info!("\n{written} of {} playlists {verb}", playlists.len());
+    Ok(())
+}
 
-    if !result.failed.is_empty() {
-        eprintln!("\nFailed Qobuz downloads:");
-        for err in &result.failed {
+fn run_trash(action: TrashCommand) -> Result<()> {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    match action {
+        TrashCommand::List { target_dir } => {
+            let batches = trash::list_batches(&resolve_target_dir(target_dir)?)?;
+            for batch in &batches {
+                println!(
+                    "{}\t{} files\t{:.1} MB",
+                    batch.name,
+                    batch.files,
+                    mb(batch.bytes)
+                );
+            }
+            if batches.is_empty() {
+                eprintln!("Trash is empty");
+            }
+        }
+        TrashCommand::Empty { target_dir } => {
+            let batches = trash::empty(&resolve_target_dir(target_dir)?)?;
+            let files: usize = batches.iter().map(|b| b.files).sum();
+            let bytes: u64 = batches.iter().map(|b| b.bytes).sum();
             eprintln!(
-                "  {} - {}: {}",
-                err.task.album.title, err.task.track.title, err.error
+                "Deleted {files} files ({:.1} MB) from {} batches",
+                mb(bytes),
+                batches.len()
             );
         }
-        bail!("Some Qobuz downloads failed");
     }
-
     Ok(())
 }
 
-async fn run_bandcamp_sync(
-    bandcamp_cfg: config::BandcampConfig,
-    target_dir: &std::path::Path,
-    dry_run: bool,
-) -> Result<()> {
-    let bc_client = bandcamp::BandcampClient::new(bandcamp_cfg.identity_cookie)?;
+async fn run_login(service: Option<String>, from_browser: Option<browser::Browser>) -> Result<()> {
+    let services = match service.as_deref() {
+        Some(s) => vec![parse_service(s)?],
+        None if from_browser.is_some() => vec![models::Service::Bandcamp],
+        None => vec![models::Service::Qobuz, models::Service::Bandcamp],
+    };
+    if from_browser.is_some() && services != [models::Service::Bandcamp] {
+        bail!("--from-browser only applies to `qoget login bandcamp`");
+    }
+    let config_dir = config::config_dir();
+    let mut sessions = session::SessionFile::load(&config_dir)?;
+    // Logging in needs no config file, so only [network] is read.
+    let network = config::configured_network();
 
-    eprintln!("Verifying Bandcamp authentication...");
-    let fan_id = bc_client.verify_auth().await?;
-    eprintln!("Bandcamp fan_id: {fan_id}");
-
-    eprintln!("Fetching Bandcamp purchases...");
-    let purchases = bc_client.get_purchases(fan_id).await?;
-    eprintln!(
-        "Found {} Bandcamp items ({} with download URLs)",
-        purchases.items.len(),
-        purchases.redownload_urls.len()
+    for service in services {
+        match service {
+            models::Service::Qobuz => {
+                let qobuz_cfg = config::prompt_qobuz_credentials()?;
+                let http = network::client(&network)?;
+                sessions.qobuz = Some(engine::qobuz_password_login(&http, qobuz_cfg).await?);
+            }
+            models::Service::Bandcamp => {
+                let identity_cookie = match from_browser {
+                    Some(browser) => {
+                        info!("Reading the Bandcamp cookie from {browser}...");
+                        bandcamp::normalize_identity_cookie(&browser::bandcamp_identity(browser)?)?
+                    }
+                    None => config::prompt_bandcamp_identity()?,
+                };
+                let bc_client = bandcamp::BandcampClient::new(
+                    identity_cookie.clone(),
+                    retry::RequestPolicy::default_for(models::Service::Bandcamp),
+                    &network,
+                )?;
+                info!("Verifying Bandcamp authentication...");
+                let fan_id = bc_client.verify_auth().await?;
+                info!("Logged in as Bandcamp fan {fan_id}");
+                sessions.bandcamp = Some(session::BandcampSession {
+                    identity_cookie,
+                    fan_id,
+                });
+            }
+        }
+        // Save after each service so one failed login doesn't lose the other.
+        sessions.save(&config_dir)?;
+    }
+
+    info!(
+        "Session saved to {}",
+        session::SessionFile::path(&config_dir).display()
     );
+    Ok(())
+}
 
-    let result =
-        download::execute_bandcamp_downloads(&bc_client, &purchases, target_dir, dry_run).await?;
+async fn run_daemon(
+    target_dir: Option<PathBuf>,
+    interval: std::time::Duration,
+    service: Option<String>,
+    opts: SyncOptions,
+) -> Result<()> {
+    let cfg = config::load_config()?;
+    let opts = opts.with_config(&cfg);
+    engine::check_ffmpeg(&opts)?;
+    let target_dir = &target_dir_from(target_dir, &cfg)?;
 
-    if dry_run {
-        eprintln!(
-            "\nDry run: {} would be downloaded, {} already synced",
-            result.would_download, result.skipped
-        );
-    } else {
-        eprintln!(
-            "\nBandcamp: {} tracks downloaded, {} already synced",
-            result.downloaded, result.skipped
+    let service_filter = match service.as_deref() {
+        Some(s) => Some(parse_service(s)?),
+        None => None,
+    };
+    let should_run = |svc: models::Service| -> bool { service_filter.is_none_or(|f| f == svc) };
+
+    let qobuz = cfg
+        .qobuz
+        .login()
+        .filter(|_| should_run(models::Service::Qobuz));
+    let bandcamp = cfg
+        .bandcamp
+        .filter(|_| should_run(models::Service::Bandcamp));
+    if qobuz.is_none() && bandcamp.is_none() {
+        bail!(
+            "No services configured for daemon mode. \
+             Set credentials in ~/.config/qoget/config.toml or the environment."
         );
     }
 
-    if !result.failed.is_empty() {
-        eprintln!("\nFailed Bandcamp downloads:");
-        for err in &result.failed {
-            eprintln!("  {}: {}", err.description, err.error);
+    let policy = health::HealthPolicy::default();
+    let mut qobuz_health = health::ServiceHealth::default();
+    let mut bandcamp_health = health::ServiceHealth::default();
+
+    loop {
+        if let Some(ref command) = cfg.hooks.pre_sync
+            && let Err(e) = hooks::pre_sync(command, target_dir, opts.dry_run).await
+        {
+            warn!("{e:#}; skipping this cycle");
+            tokio::time::sleep(interval).await;
+            continue;
         }
-        bail!("Some Bandcamp downloads failed");
+        // Taken per cycle, so a manual sync in between only delays the
+        // daemon.
+        let lock = lock::SyncLock::acquire(target_dir, true)?;
+        let mut cycle = report::SyncReport::new(target_dir, opts.dry_run);
+        let mut outcome = Ok(());
+        if let Some(ref qobuz_login) = qobuz {
+            outcome = outcome.and(
+                daemon_step(
+                    models::Service::Qobuz,
+                    &mut qobuz_health,
+                    &policy,
+                    engine::run_qobuz_sync(qobuz_login.clone(), target_dir, &opts, &mut cycle),
+                )
+                .await,
+            );
+        }
+        if let Some(ref bandcamp_cfg) = bandcamp {
+            outcome = outcome.and(
+                daemon_step(
+                    models::Service::Bandcamp,
+                    &mut bandcamp_health,
+                    &policy,
+                    engine::run_bandcamp_sync(bandcamp_cfg.clone(), target_dir, &opts, &mut cycle),
+                )
+                .await,
+            );
+        }
+        cycle.finish(&outcome);
+        engine::save_failures(&cycle);
+        media_server::refresh(&cfg.integrations, &opts.network, &cycle).await;
+        // Only cycles that changed something or went wrong are worth a
+        // message; an hourly "nothing new" would drown the rest.
+        let eventful = cycle
+            .services
+            .iter()
+            .any(|s| !s.succeeded.is_empty() || !s.failed.is_empty() || s.error.is_some());
+        if let Some(ref url) = cfg.notifications.webhook_url
+            && eventful
+        {
+            engine::notify_webhook(url, &cycle, &opts.network).await;
+        }
+        drop(lock);
+        engine::run_post_sync(&cfg.hooks, &cycle).await;
+        tokio::time::sleep(interval).await;
     }
+}
 
-    Ok(())
+/// Run one service's sync for a daemon cycle, unless it is cooling down.
+/// Health changes are reported once, not every cycle. Returns the sync's
+/// result, which is already logged.
+async fn daemon_step(
+    service: models::Service,
+    health: &mut health::ServiceHealth,
+    policy: &health::HealthPolicy,
+    sync: impl std::future::Future<Output = Result<()>>,
+) -> Result<()> {
+    if !health.is_due(std::time::Instant::now()) {
+        return Ok(());
+    }
+
+    info!("Syncing {service}...");
+    let result = sync.await;
+    let transition = match &result {
+        Ok(()) => health.record_success(),
+        Err(e) => {
+            error!("{service} sync failed: {e:#}");
+            match report::FailureKind::classify(e) {
+                // The service works; the failed items are retried next cycle
+                // like any other missing download.
+                report::FailureKind::Downloads => health.record_success(),
+                kind => health.record_failure(
+                    kind == report::FailureKind::Auth,
+                    std::time::Instant::now(),
+                    policy,
+                ),
+            }
+        }
+    };
+
+    match transition {
+        health::Transition::BecameUnhealthy { cooldown } => warn!(
+            "{service} marked unhealthy after {} failure(s); \
+             skipping it for {} min, then retrying with increasing delays",
+            health.consecutive_failures(),
+            cooldown.as_secs() / 60
+        ),
+        health::Transition::Recovered => info!("{service} is healthy again"),
+        health::Transition::None => {}
+    }
+    result
 }
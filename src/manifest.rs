@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::{Album, AudioFormat, AudioQuality, Service, Track, TrackId};
+
+const MANIFEST_FILE: &str = ".qoget-manifest.json";
+
+/// Per-target record of what was actually downloaded for each track.
+///
+/// Lives at `<target>/.qoget-manifest.json`. Used by `scan_existing` to find
+/// tracks whose on-disk format differs from the planned one (e.g. FLAC
+/// fallback for an MP3 task), and by library commands such as `open` to
+/// resolve artists and albums to local directories.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Keyed by `"<service>:<track id>"`.
+    #[serde(default)]
+    tracks: BTreeMap<String, ManifestEntry>,
+    /// Target directory this manifest was loaded from, if any.
+    #[serde(skip)]
+    root: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub format: AudioFormat,
+    #[serde(default)]
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+    #[serde(default)]
+    pub title: String,
+    /// Path of the track file, relative to the target directory.
+    #[serde(default)]
+    pub path: PathBuf,
+    /// File size in bytes when recorded, used to recognize the file if it
+    /// is moved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Hex SHA-256 of the file when recorded, checked by `verify --checksums`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Bit depth and sample rate of a lossless download, as the store
+    /// reported them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<AudioQuality>,
+    /// Genre written to the file's tags, when the store provides one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Post-download steps completed on the file, by name, with the version
+    /// of each step that ran.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub steps: BTreeMap<String, u32>,
+}
+
+/// A post-download step applied to track files, such as writing tags.
+///
+/// Completed steps are recorded per track, so a later sync redoes a step
+/// only for tracks that never finished it, or finished an older `version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessingStep {
+    pub name: &'static str,
+    pub version: u32,
+}
+
+impl ManifestEntry {
+    /// Build an entry for a track placed at `path` under `target_dir`.
+    /// Size and checksum are read from the file if it exists.
+    pub fn new(
+        album: &Album,
+        track: &Track,
+        format: AudioFormat,
+        target_dir: &Path,
+        path: &Path,
+    ) -> Self {
+        let sha256 = file_sha256(path).ok();
+        Self::hashed(album, track, format, target_dir, path, sha256)
+    }
+
+    /// Like `new`, for a file whose checksum was computed as it was written,
+    /// so it isn't read back.
+    pub fn hashed(
+        album: &Album,
+        track: &Track,
+        format: AudioFormat,
+        target_dir: &Path,
+        path: &Path,
+        sha256: Option<String>,
+    ) -> Self {
+        Self {
+            format,
+            artist: album.artist.name.clone(),
+            album: album.title.clone(),
+            title: track.title.clone(),
+            path: path.strip_prefix(target_dir).unwrap_or(path).to_path_buf(),
+            size: std::fs::metadata(path).ok().map(|m| m.len()),
+            sha256,
+            quality: None,
+            genre: None,
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// True if `step` ran on this file at its current version (or later).
+    pub fn has_step(&self, step: ProcessingStep) -> bool {
+        self.steps
+            .get(step.name)
+            .is_some_and(|&v| v >= step.version)
+    }
+
+    pub fn mark_step(&mut self, step: ProcessingStep) {
+        self.steps.insert(step.name.to_string(), step.version);
+    }
+
+    /// Update size and checksum after the file was modified in place. The
+    /// checksum is read back from the file unless `sha256` gives it.
+    pub fn refresh(&mut self, target_dir: &Path, sha256: Option<String>) {
+        let path = target_dir.join(&self.path);
+        self.size = std::fs::metadata(&path).ok().map(|m| m.len());
+        self.sha256 = sha256.or_else(|| file_sha256(&path).ok());
+    }
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = HashingWriter::new(std::io::sink());
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finish().1)
+}
+
+/// Hex-encoded SHA-256 of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// A writer that hashes everything written through it, so a file's
+/// checksum comes for free while it is first written.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: std::io::Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The inner writer and the hex SHA-256 of what was written.
+    pub fn finish(self) -> (W, String) {
+        (self.inner, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A local album directory known to the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LocalAlbum {
+    pub artist: String,
+    pub album: String,
+    /// Album directory, relative to the target directory.
+    pub dir: PathBuf,
+}
+
+/// A track's key in the manifest, `"<service>:<track id>"`.
+pub fn entry_key(service: Service, track_id: TrackId) -> String {
+    format!("{}:{}", service.to_string().to_lowercase(), track_id)
+}
+
+impl Manifest {
+    pub fn path(target_dir: &Path) -> PathBuf {
+        target_dir.join(MANIFEST_FILE)
+    }
+
+    /// Load the manifest for a target directory. A missing file yields an
+    /// empty manifest.
+    pub fn load(target_dir: &Path) -> Result<Self> {
+        let path = Self::path(target_dir);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    root: Some(target_dir.to_path_buf()),
+                    ..Self::default()
+                });
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading {}", path.display()));
+            }
+        };
+        let mut manifest: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        manifest.root = Some(target_dir.to_path_buf());
+        Ok(manifest)
+    }
+
+    /// Atomic write: temp file + rename.
+    pub fn save(&self, target_dir: &Path) -> Result<()> {
+        let path = Self::path(target_dir);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::create_dir_all(target_dir)
+            .with_context(|| format!("creating {}", target_dir.display()))?;
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&tmp, &json).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+
+    pub fn record(&mut self, service: Service, track_id: TrackId, entry: ManifestEntry) {
+        self.tracks.insert(entry_key(service, track_id), entry);
+    }
+
+    pub fn get(&self, service: Service, track_id: TrackId) -> Option<&ManifestEntry> {
+        self.tracks.get(&entry_key(service, track_id))
+    }
+
+    pub fn format_for(&self, service: Service, track_id: TrackId) -> Option<AudioFormat> {
+        self.get(service, track_id).map(|e| e.format)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.tracks.values()
+    }
+
+    /// Entries with their `"<service>:<track id>"` keys.
+    pub fn keyed_entries(&self) -> impl Iterator<Item = (&str, &ManifestEntry)> {
+        self.tracks.iter().map(|(k, e)| (k.as_str(), e))
+    }
+
+    /// Point an entry at a new path (relative to the target directory).
+    /// Returns false if no entry has this key.
+    pub fn relocate(&mut self, key: &str, path: PathBuf) -> bool {
+        match self.tracks.get_mut(key) {
+            Some(entry) => {
+                entry.path = path;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop an entry, e.g. after its file was found damaged and deleted.
+    pub fn remove(&mut self, key: &str) -> Option<ManifestEntry> {
+        self.tracks.remove(key)
+    }
+
+    /// Absolute path recorded for a track, when the manifest was loaded from
+    /// a target directory.
+    pub fn recorded_path(&self, service: Service, track_id: TrackId) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        Some(root.join(&self.get(service, track_id)?.path))
+    }
+
+    /// Album directory (relative to the target) of a recorded track.
+    pub fn recorded_album_dir(&self, service: Service, track_id: TrackId) -> Option<PathBuf> {
+        album_dir(&self.get(service, track_id)?.path)
+    }
+
+    /// Entries recorded for one service.
+    pub fn entries_for(&self, service: Service) -> impl Iterator<Item = &ManifestEntry> {
+        let prefix = format!("{}:", service.to_string().to_lowercase());
+        self.tracks
+            .iter()
+            .filter(move |(k, _)| k.starts_with(&prefix))
+            .map(|(_, e)| e)
+    }
+
+    /// Entries of one service whose files are in the album directory `dir`
+    /// (relative to the target directory).
+    pub fn album_entries_mut<'a>(
+        &'a mut self,
+        service: Service,
+        dir: &'a Path,
+    ) -> impl Iterator<Item = &'a mut ManifestEntry> {
+        let prefix = format!("{}:", service.to_string().to_lowercase());
+        self.tracks
+            .iter_mut()
+            .filter(move |(k, e)| {
+                k.starts_with(&prefix) && album_dir(&e.path).as_deref() == Some(dir)
+            })
+            .map(|(_, e)| e)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Distinct album directories, sorted by artist then album.
+    pub fn albums(&self) -> Vec<LocalAlbum> {
+        let mut albums: Vec<LocalAlbum> = self
+            .tracks
+            .values()
+            .filter_map(|e| {
+                Some(LocalAlbum {
+                    artist: e.artist.clone(),
+                    album: e.album.clone(),
+                    dir: album_dir(&e.path)?,
+                })
+            })
+            .collect();
+        albums.sort();
+        albums.dedup();
+        albums
+    }
+
+    /// Albums matching a case-insensitive query. The query is either free
+    /// text matched against the artist or album name, or `artist/album` where
+    /// each side is matched against the respective field.
+    pub fn find_albums(&self, query: &str) -> Vec<LocalAlbum> {
+        let query = query.to_lowercase();
+        self.albums()
+            .into_iter()
+            .filter(|a| {
+                let artist = a.artist.to_lowercase();
+                let album = a.album.to_lowercase();
+                match query.split_once('/') {
+                    Some((qa, qb)) => artist.contains(qa.trim()) && album.contains(qb.trim()),
+                    None => artist.contains(&query) || album.contains(&query),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Album directory for a track path (`Artist/Album[/Disc N]/file`), i.e. the
+/// first two components.
+pub(crate) fn album_dir(track_path: &Path) -> Option<PathBuf> {
+    let mut components = track_path.components();
+    let artist = components.next()?;
+    let album = components.next()?;
+    // Need at least a file below the album directory
+    components.next()?;
+    Some(Path::new(artist.as_os_str()).join(album.as_os_str()))
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tracing::{info, warn};
+
+use crate::config::{IntegrationsConfig, JellyfinConfig, NetworkConfig, PlexConfig};
+use crate::network;
+use crate::report::SyncReport;
+
+/// How long to wait for a media server to accept a scan request. The scan
+/// itself runs on the server afterwards.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn http(network: &NetworkConfig) -> Result<reqwest::Client> {
+    network::client_builder(network)?
+        .timeout(REFRESH_TIMEOUT)
+        .build()
+        .context("building HTTP client")
+}
+
+/// `path` under a server's base URL, whether or not that ends in `/`.
+pub fn endpoint(base: &str, path: &str) -> String {
+    format!("{}/{path}", base.trim_end_matches('/'))
+}
+
+/// Ask a Jellyfin server to scan all its libraries.
+pub async fn refresh_jellyfin(cfg: &JellyfinConfig, network: &NetworkConfig) -> Result<()> {
+    let resp = http(network)?
+        .post(endpoint(&cfg.url, "Library/Refresh"))
+        .header("X-Emby-Token", &cfg.api_key)
+        .send()
+        .await
+        .context("sending Jellyfin library refresh")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("Jellyfin returned HTTP {status}");
+    }
+    Ok(())
+}
+
+/// Ask a Plex server to scan all its library sections.
+pub async fn refresh_plex(cfg: &PlexConfig, network: &NetworkConfig) -> Result<()> {
+    let resp = http(network)?
+        .get(endpoint(&cfg.url, "library/sections/all/refresh"))
+        .header("X-Plex-Token", &cfg.token)
+        .send()
+        .await
+        .context("sending Plex library refresh")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("Plex returned HTTP {status}");
+    }
+    Ok(())
+}
+
+/// True if `report` is of a real run that downloaded at least one item,
+/// even if others failed: the files it did get are worth scanning.
+pub fn should_refresh(report: &SyncReport) -> bool {
+    !report.dry_run && report.services.iter().any(|s| !s.succeeded.is_empty())
+}
+
+/// Tell each configured media server to rescan, if `report` downloaded
+/// anything. Failures are only warned about: the sync itself is done.
+pub async fn refresh(cfg: &IntegrationsConfig, network: &NetworkConfig, report: &SyncReport) {
+    if !should_refresh(report) {
+        return;
+    }
+    if let Some(ref jellyfin) = cfg.jellyfin {
+        match refresh_jellyfin(jellyfin, network).await {
+            Ok(()) => info!("Asked Jellyfin to scan its libraries"),
+            Err(e) => warn!("Jellyfin library refresh failed: {e:#}"),
+        }
+    }
+    if let Some(ref plex) = cfg.plex {
+        match refresh_plex(plex, network).await {
+            Ok(()) => info!("Asked Plex to scan its libraries"),
+            Err(e) => warn!("Plex library refresh failed: {e:#}"),
+        }
+    }
+}
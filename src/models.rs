@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use serde::{Deserialize, Deserializer};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
 
 fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
@@ -14,12 +17,23 @@ where
 
 // --- Service enum ---
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Service {
     Qobuz,
     Bandcamp,
 }
 
+impl Service {
+    /// Format a sync downloads from this service when nothing else is recorded.
+    pub fn default_format(self) -> AudioFormat {
+        match self {
+            Service::Qobuz => AudioFormat::Mp3,
+            Service::Bandcamp => AudioFormat::Aac,
+        }
+    }
+}
+
 impl fmt::Display for Service {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -29,9 +43,166 @@ impl fmt::Display for Service {
     }
 }
 
+// --- Audio format ---
+
+/// On-disk audio format of a downloaded track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Mp3,
+    Flac,
+    Aac,
+    /// Only ever produced by transcoding.
+    Opus,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 4] = [
+        AudioFormat::Mp3,
+        AudioFormat::Flac,
+        AudioFormat::Aac,
+        AudioFormat::Opus,
+    ];
+
+    /// File extension including the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => ".mp3",
+            AudioFormat::Flac => ".flac",
+            AudioFormat::Aac => ".m4a",
+            AudioFormat::Opus => ".opus",
+        }
+    }
+
+    /// Inverse of `extension`, without the dot; case-insensitive.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        AudioFormat::ALL
+            .into_iter()
+            .find(|f| f.extension()[1..].eq_ignore_ascii_case(ext))
+    }
+
+    /// Sizes a store download of `duration` seconds plausibly has in this
+    /// format. MP3 and AAC are near their fixed bitrate (320 and 256 kbps);
+    /// FLAC spans quiet mono to 24-bit/192 kHz; Opus any bitrate it may be
+    /// transcoded to. The top end allows 1 MiB for tags and embedded artwork.
+    pub fn plausible_size(self, duration: u32) -> RangeInclusive<u64> {
+        let (min_rate, max_rate) = match self {
+            AudioFormat::Mp3 => (24_000, 80_000),
+            AudioFormat::Flac => (25_000, 1_200_000),
+            AudioFormat::Aac => (19_000, 64_000),
+            AudioFormat::Opus => (2_000, 64_000),
+        };
+        let secs = u64::from(duration);
+        secs * min_rate..=secs * max_rate + 1024 * 1024
+    }
+
+    /// Rough size of a store download of `duration` seconds, for estimating
+    /// how much room a sync needs: the fixed bitrate for MP3 and AAC, CD
+    /// quality for FLAC, 128 kbps for Opus.
+    pub fn typical_size(self, duration: u32) -> u64 {
+        let rate = match self {
+            AudioFormat::Mp3 => 40_000,
+            AudioFormat::Flac => 110_000,
+            AudioFormat::Aac => 32_000,
+            AudioFormat::Opus => 16_000,
+        };
+        u64::from(duration) * rate
+    }
+}
+
+/// The best format sync asks Qobuz for (`--quality`, or `quality` under
+/// [qobuz]). Qobuz sends the best it has up to that, so an album only sold
+/// in CD quality still comes as 16-bit FLAC under `hires-192`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum QobuzQuality {
+    /// MP3 320 kbps (format 5).
+    #[default]
+    #[serde(rename = "mp3")]
+    Mp3,
+    /// 16-bit/44.1 kHz FLAC (format 6).
+    #[serde(rename = "cd")]
+    Cd,
+    /// 24-bit FLAC up to 96 kHz (format 7).
+    #[serde(rename = "hires-96")]
+    Hires96,
+    /// 24-bit FLAC up to 192 kHz (format 27).
+    #[serde(rename = "hires-192")]
+    Hires192,
+}
+
+impl QobuzQuality {
+    /// Qobuz `format_id`s to request, best first: the one asked for, then
+    /// what stands in when a track isn't offered in it.
+    pub fn format_ids(self) -> &'static [u8] {
+        match self {
+            QobuzQuality::Mp3 => &[5, 6],
+            QobuzQuality::Cd => &[6, 5],
+            QobuzQuality::Hires96 => &[7, 6, 5],
+            QobuzQuality::Hires192 => &[27, 7, 6, 5],
+        }
+    }
+
+    /// The format files are planned in.
+    pub fn format(self) -> AudioFormat {
+        match self {
+            QobuzQuality::Mp3 => AudioFormat::Mp3,
+            _ => AudioFormat::Flac,
+        }
+    }
+}
+
+impl FromStr for QobuzQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mp3" => Ok(QobuzQuality::Mp3),
+            "cd" => Ok(QobuzQuality::Cd),
+            "hires-96" => Ok(QobuzQuality::Hires96),
+            "hires-192" => Ok(QobuzQuality::Hires192),
+            _ => Err(format!(
+                "unknown quality '{s}' (expected mp3, cd, hires-96, or hires-192)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QobuzQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QobuzQuality::Mp3 => "mp3",
+            QobuzQuality::Cd => "cd",
+            QobuzQuality::Hires96 => "hires-96",
+            QobuzQuality::Hires192 => "hires-192",
+        })
+    }
+}
+
+/// Bit depth and sample rate of a lossless stream, as Qobuz reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioQuality {
+    pub bit_depth: u8,
+    /// In kHz, e.g. 44.1 or 96.
+    pub sampling_rate: f64,
+}
+
+impl AudioQuality {
+    /// More than CD quality (16-bit/44.1 kHz or 48 kHz).
+    pub fn is_hires(self) -> bool {
+        self.bit_depth > 16 || self.sampling_rate > 48.0
+    }
+}
+
+/// The collector's shorthand: `24-96`, `16-44.1`.
+impl fmt::Display for AudioQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.bit_depth, self.sampling_rate)
+    }
+}
+
 // --- Newtype wrappers ---
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct TrackId(pub u64);
 
@@ -51,6 +222,117 @@ impl fmt::Display for AlbumId {
     }
 }
 
+/// One Qobuz album or track, as given to `qoget get`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QobuzItem {
+    Album(AlbumId),
+    Track(TrackId),
+}
+
+/// Parses a store or web player link (`https://www.qobuz.com/us-en/album/
+/// kind-of-blue-miles-davis/0886443927087`, `https://open.qobuz.com/track/
+/// 5966783`), or a bare album ID.
+impl FromStr for QobuzItem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let bad = || format!("'{s}' is not a Qobuz album or track link or album ID");
+        let is_id = |id: &str| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+        let Some((_, rest)) = s.split_once("qobuz.com/") else {
+            return if is_id(s) {
+                Ok(QobuzItem::Album(AlbumId(s.to_string())))
+            } else {
+                Err(bad())
+            };
+        };
+        let path = rest.split(['?', '#']).next().unwrap_or_default();
+        let segments: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        let id = segments.last().copied().unwrap_or_default();
+        if segments.contains(&"track") {
+            id.parse()
+                .map(|id| QobuzItem::Track(TrackId(id)))
+                .map_err(|_| bad())
+        } else if segments.contains(&"album") && is_id(id) {
+            Ok(QobuzItem::Album(AlbumId(id.to_string())))
+        } else {
+            Err(bad())
+        }
+    }
+}
+
+impl fmt::Display for QobuzItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QobuzItem::Album(id) => write!(f, "album {id}"),
+            QobuzItem::Track(id) => write!(f, "track {id}"),
+        }
+    }
+}
+
+/// One Bandcamp purchase, as given to `qoget get`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BandcampLink {
+    /// An album or track page, on bandcamp.com or an artist's own domain.
+    Page(String),
+    /// A download page, as linked from the purchase's email receipt.
+    Download(String),
+}
+
+impl FromStr for BandcampLink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let bad = || format!("'{s}' is not a Bandcamp album, track or download link");
+        let rest = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+            .ok_or_else(bad)?;
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = host.to_lowercase();
+        if matches!(host.as_str(), "bandcamp.com" | "www.bandcamp.com")
+            && path.starts_with("download")
+        {
+            Ok(BandcampLink::Download(s.to_string()))
+        } else if path.starts_with("album/") || path.starts_with("track/") {
+            Ok(BandcampLink::Page(s.to_string()))
+        } else {
+            Err(bad())
+        }
+    }
+}
+
+/// What `qoget get` fetches: a Qobuz album or track, or a Bandcamp purchase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetItem {
+    Qobuz(QobuzItem),
+    Bandcamp(BandcampLink),
+}
+
+impl GetItem {
+    pub fn service(&self) -> Service {
+        match self {
+            GetItem::Qobuz(_) => Service::Qobuz,
+            GetItem::Bandcamp(_) => Service::Bandcamp,
+        }
+    }
+}
+
+/// Qobuz links and bare IDs are Qobuz's; any other link is taken for
+/// Bandcamp's, whose artists may use their own domains.
+impl FromStr for GetItem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains("qobuz.com/") || !s.contains("://") {
+            s.parse().map(GetItem::Qobuz)
+        } else {
+            s.parse().map(GetItem::Bandcamp)
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(transparent)]
 pub struct TrackNumber(pub u8);
@@ -73,33 +355,505 @@ impl fmt::Display for DiscNumber {
 
 // --- API response types (serde) ---
 
+/// The id Qobuz's catalog gives "Various Artists", the artist its
+/// compilations are credited to (as `artist.id` in `/album/get` responses
+/// for them). The name is localized ("Verschiedene Interpreten", "Artistes
+/// divers"), so the id is what is checked first.
+pub const VARIOUS_ARTISTS_ID: u64 = 145383;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Artist {
     pub id: u64,
     pub name: String,
 }
 
+impl Artist {
+    /// Whether `other` is the same artist: the same id, or the same name
+    /// up to case. Bandcamp artists have no id (0).
+    pub fn is(&self, other: &Artist) -> bool {
+        (self.id != 0 && self.id == other.id)
+            || self.name.trim().eq_ignore_ascii_case(other.name.trim())
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Album {
     pub id: AlbumId,
     pub title: String,
     pub version: Option<String>,
+    /// The album artist.
     pub artist: Artist,
+    /// Everyone Qobuz credits on the album, `artist` included: the other
+    /// halves of collaborations and featured artists.
+    #[serde(default)]
+    pub artists: Vec<Artist>,
     pub media_count: u8,
     pub tracks_count: u16,
+    /// Barcode (UPC/EAN), when Qobuz gives one.
+    #[serde(default)]
+    pub upc: Option<String>,
+    /// Original release date, `YYYY-MM-DD`: from Qobuz, or for Bandcamp
+    /// from the download page.
+    #[serde(default, rename = "release_date_original")]
+    pub release_date: Option<String>,
+    #[serde(default)]
+    pub image: Option<AlbumImage>,
     #[serde(default)]
     pub tracks: Option<PaginatedList<Track>>,
+    #[serde(flatten)]
+    pub purchase: PurchaseInfo,
+}
+
+impl Album {
+    /// Whether the album is credited to Various Artists rather than
+    /// anyone in particular.
+    pub fn is_various_artists(&self) -> bool {
+        self.artist.id == VARIOUS_ARTISTS_ID
+            || ["Various Artists", "Various", "VA"]
+                .iter()
+                .any(|name| self.artist.name.trim().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether `performer` is one of the album's own artists: the album
+    /// artist, one credited alongside them, or the album artist with a
+    /// guest ("Band feat. Guest", "Band & Friend").
+    pub fn credits(&self, performer: &Artist) -> bool {
+        if self.artist.is(performer) || self.artists.iter().any(|a| a.is(performer)) {
+            return true;
+        }
+        let (album_artist, performer) = (
+            self.artist.name.trim().to_lowercase(),
+            performer.name.trim().to_lowercase(),
+        );
+        !album_artist.is_empty()
+            && performer.strip_prefix(&album_artist).is_some_and(|rest| {
+                [" feat", " ft.", " with ", " & ", " and ", ", ", " x "]
+                    .iter()
+                    .any(|joiner| rest.starts_with(joiner))
+            })
+    }
+
+    /// Whether `track`'s file names its artist: every track of a Various
+    /// Artists album not by Various Artists itself, and tracks by someone
+    /// the album doesn't credit.
+    pub fn is_compilation_track(&self, track: &Track) -> bool {
+        if self.is_various_artists() {
+            !self.artist.is(&track.performer)
+        } else {
+            !self.credits(&track.performer)
+        }
+    }
+
+    /// The year `release_date` starts with, if it has one.
+    pub fn year(&self) -> Option<&str> {
+        self.release_date
+            .as_deref()
+            .and_then(|date| date.get(..4))
+            .filter(|year| year.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+/// Cover image URLs. Qobuz also sends `small` and `thumbnail` sizes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlbumImage {
+    /// 600×600 JPEG.
+    #[serde(default)]
+    pub large: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Track {
     pub id: TrackId,
     pub title: String,
+    /// What sets this recording apart from others of the same title, e.g.
+    /// "2023 Remaster" or "Live".
+    pub version: Option<String>,
     pub track_number: TrackNumber,
     pub media_number: DiscNumber,
     pub duration: u32,
     pub performer: Artist,
     pub isrc: Option<String>,
+    #[serde(flatten)]
+    pub purchase: PurchaseInfo,
+}
+
+/// Order metadata attached to entries from `/purchase/getUserPurchases`.
+/// Absent on catalog responses such as `/album/get`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PurchaseInfo {
+    /// Unix timestamp of the order.
+    #[serde(default)]
+    pub purchased_at: Option<u64>,
+    /// How the item was acquired, e.g. "purchase", "gift", "voucher".
+    #[serde(default)]
+    pub purchase_channel: Option<String>,
+}
+
+impl PurchaseInfo {
+    /// True when the item was received as a gift or redeemed from a voucher.
+    pub fn is_gift(&self) -> bool {
+        matches!(
+            self.purchase_channel.as_deref(),
+            Some("gift") | Some("voucher")
+        )
+    }
+}
+
+/// Earliest purchase date to keep, from `--since`: a date (`2024-01-01`,
+/// from midnight UTC) or an age back from now (`30d`, `12w`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Since {
+    /// Unix time of the cutoff.
+    pub at: u64,
+    text: String,
+}
+
+impl Since {
+    /// Parse `s`, counting ages back from `now` (Unix time).
+    pub fn parse_at(s: &str, now: u64) -> Result<Self, String> {
+        let text = s.trim();
+        let at = parse_age(text)
+            .map(|secs| now.saturating_sub(secs))
+            .or_else(|| parse_date(text))
+            .ok_or_else(|| {
+                format!("invalid date '{s}' (expected e.g. 2024-01-01, or an age like 30d or 8w)")
+            })?;
+        Ok(Since {
+            at,
+            text: text.to_string(),
+        })
+    }
+
+    /// Whether a purchase made at `purchased_at` is recent enough. Purchases
+    /// of unknown date are kept, rather than skipped unseen.
+    pub fn keeps(&self, purchased_at: Option<u64>) -> bool {
+        purchased_at.is_none_or(|t| t >= self.at)
+    }
+}
+
+impl FromStr for Since {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Since::parse_at(s, now)
+    }
+}
+
+impl fmt::Display for Since {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Seconds in an age such as `30d` or `8w`.
+fn parse_age(s: &str) -> Option<u64> {
+    let unit = match s.chars().last()? {
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        _ => return None,
+    };
+    let n: u64 = s[..s.len() - 1].parse().ok()?;
+    n.checked_mul(unit)
+}
+
+/// Unix time of midnight UTC on a `YYYY-MM-DD` date.
+fn parse_date(s: &str) -> Option<u64> {
+    let mut parts = s.splitn(3, '-');
+    let mut next = |len: usize| {
+        let part = parts.next().filter(|p| p.len() == len)?;
+        part.parse::<i64>().ok()
+    };
+    let (year, month, day) = (next(4)?, next(2)?, next(2)?);
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let next_month = match month {
+        12 => days_from_civil(year + 1, 1, 1),
+        _ => days_from_civil(year, month + 1, 1),
+    };
+    if day < 1 || days >= next_month {
+        return None;
+    }
+    u64::try_from(days * 86_400).ok()
+}
+
+/// Days since 1970-01-01 of a civil date (proleptic Gregorian).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The civil date `(year, month, day)` of a day since 1970-01-01
+/// (proleptic Gregorian); the inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// A Unix timestamp as UTC in ISO 8601 basic format, `YYYYMMDDTHHMMSSZ`.
+pub(crate) fn utc_basic(unix_secs: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    let secs = unix_secs % 86_400;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Which purchases to keep, by how they were acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GiftFilter {
+    #[default]
+    All,
+    /// Only items bought by the user.
+    ExcludeGifts,
+    /// Only items received as gifts (or from vouchers).
+    OnlyGifts,
+}
+
+impl GiftFilter {
+    /// From the `--exclude-gifts` / `--only-gifts` flags (mutually exclusive).
+    pub fn from_flags(exclude_gifts: bool, only_gifts: bool) -> Self {
+        match (exclude_gifts, only_gifts) {
+            (true, _) => GiftFilter::ExcludeGifts,
+            (_, true) => GiftFilter::OnlyGifts,
+            _ => GiftFilter::All,
+        }
+    }
+
+    pub fn keeps(self, is_gift: bool) -> bool {
+        match self {
+            GiftFilter::All => true,
+            GiftFilter::ExcludeGifts => !is_gift,
+            GiftFilter::OnlyGifts => is_gift,
+        }
+    }
+}
+
+/// A name to match from `--artist` or `--album`, ignoring case: a glob
+/// (`*`, `?`, `[abc]`) that must match the whole name, or else any part of
+/// the name.
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("pattern is empty".to_string());
+        }
+        let is_glob = s.contains(['*', '?', '[']);
+        let body = if is_glob {
+            format!("^{}$", glob_to_regex(s))
+        } else {
+            regex::escape(s)
+        };
+        let regex = Regex::new(&format!("(?i){body}"))
+            .map_err(|e| format!("invalid pattern '{s}': {e}"))?;
+        Ok(NamePattern {
+            pattern: s.to_string(),
+            regex,
+        })
+    }
+}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Regex source for a glob. A `[` with no closing `]` is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::new();
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                match rest.find(']') {
+                    Some(end) if end > 0 => {
+                        let class = rest[..end].replace('\\', "\\\\");
+                        match class.strip_prefix('!') {
+                            Some(negated) => out.push_str(&format!("[^{negated}]")),
+                            None => out.push_str(&format!("[{class}]")),
+                        }
+                        // Past the class and its `]`.
+                        chars.nth(rest[..end].chars().count());
+                    }
+                    _ => out.push_str("\\["),
+                }
+            }
+            c => out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    out
+}
+
+/// Which purchases to keep, by artist and album name. Both must match
+/// when both are given.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    pub artist: Option<NamePattern>,
+    pub album: Option<NamePattern>,
+}
+
+impl NameFilter {
+    /// True when it keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.artist.is_none() && self.album.is_none()
+    }
+
+    pub fn keeps(&self, artist: &str, album: &str) -> bool {
+        self.artist.as_ref().is_none_or(|p| p.matches(artist))
+            && self.album.as_ref().is_none_or(|p| p.matches(album))
+    }
+}
+
+/// What sync does when a planned file already exists but its size is far
+/// from what the track's length predicts, e.g. an old 128 kbps rip where a
+/// 320 kbps download is planned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizeMismatchPolicy {
+    /// Leave the existing file alone (but report it).
+    #[default]
+    Keep,
+    /// Download in its place, moving the existing file to the trash.
+    Overwrite,
+    /// Download next to the existing file under a suffixed name.
+    KeepBoth,
+}
+
+impl FromStr for SizeMismatchPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(SizeMismatchPolicy::Keep),
+            "overwrite" => Ok(SizeMismatchPolicy::Overwrite),
+            "keep-both" => Ok(SizeMismatchPolicy::KeepBoth),
+            _ => Err(format!(
+                "unknown policy '{s}' (expected keep, overwrite, or keep-both)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SizeMismatchPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeMismatchPolicy::Keep => write!(f, "keep"),
+            SizeMismatchPolicy::Overwrite => write!(f, "overwrite"),
+            SizeMismatchPolicy::KeepBoth => write!(f, "keep-both"),
+        }
+    }
+}
+
+/// Which failures make `sync` exit non-zero. A service that can't sync at
+/// all (bad credentials, unreachable) always does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailOn {
+    /// Any failed download.
+    #[default]
+    Any,
+    /// Only a service whose every download failed.
+    All,
+    /// No failed download; only services that couldn't sync.
+    None,
+}
+
+impl FromStr for FailOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "any" => Ok(FailOn::Any),
+            "all" => Ok(FailOn::All),
+            "none" => Ok(FailOn::None),
+            _ => Err(format!("unknown policy '{s}' (expected any, all, or none)")),
+        }
+    }
+}
+
+impl fmt::Display for FailOn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailOn::Any => write!(f, "any"),
+            FailOn::All => write!(f, "all"),
+            FailOn::None => write!(f, "none"),
+        }
+    }
+}
+
+/// How Bandcamp tracks whose file name carries no track number are named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnknownTrackNumbers {
+    /// Number them by their place in the download's ZIP.
+    #[default]
+    ZipOrder,
+    /// Look them up by title in the tracklist on the album's page, falling
+    /// back to ZIP order.
+    Tracklist,
+    /// Leave the number out of the file name.
+    Omit,
+}
+
+impl FromStr for UnknownTrackNumbers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "zip-order" => Ok(UnknownTrackNumbers::ZipOrder),
+            "tracklist" => Ok(UnknownTrackNumbers::Tracklist),
+            "omit" => Ok(UnknownTrackNumbers::Omit),
+            _ => Err(format!(
+                "unknown policy '{s}' (expected zip-order, tracklist, or omit)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UnknownTrackNumbers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnknownTrackNumbers::ZipOrder => write!(f, "zip-order"),
+            UnknownTrackNumbers::Tracklist => write!(f, "tracklist"),
+            UnknownTrackNumbers::Omit => write!(f, "omit"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -116,6 +870,53 @@ pub struct PurchaseResponse {
     pub tracks: PaginatedList<Track>,
 }
 
+/// One page of `/favorite/getUserFavorites`, which lists one `type` of
+/// favorite per request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FavoritesResponse {
+    #[serde(default)]
+    pub albums: Option<PaginatedList<Album>>,
+    #[serde(default)]
+    pub tracks: Option<PaginatedList<Track>>,
+}
+
+/// `/catalog/search`: the albums and tracks matching a query, best first.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CatalogSearchResponse {
+    #[serde(default)]
+    pub albums: Option<PaginatedList<Album>>,
+    #[serde(default)]
+    pub tracks: Option<PaginatedList<Track>>,
+}
+
+/// A user playlist. `/playlist/getUserPlaylists` lists them without
+/// tracks; `/playlist/get` returns one with a page of them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Playlist {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub tracks_count: u32,
+    #[serde(default)]
+    pub tracks: Option<PaginatedList<PlaylistTrack>>,
+}
+
+/// A playlist entry: any catalog track, purchased or not.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistTrack {
+    pub id: TrackId,
+    pub title: String,
+    #[serde(default)]
+    pub duration: u32,
+    #[serde(default)]
+    pub performer: Option<Artist>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistsResponse {
+    pub playlists: PaginatedList<Playlist>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct LoginResponse {
     pub user_auth_token: String,
@@ -133,6 +934,34 @@ pub struct FileUrlResponse {
     pub url: String,
     pub format_id: u8,
     pub mime_type: String,
+    /// In kHz. Sent for every format.
+    #[serde(default)]
+    pub sampling_rate: Option<f64>,
+    /// Sent for lossless formats only.
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+    /// Set when the account may only stream a preview of the track, i.e.
+    /// hasn't bought it.
+    #[serde(default)]
+    pub sample: bool,
+}
+
+impl FileUrlResponse {
+    /// What the stream is: MP3 for format 5, FLAC for the lossless ones.
+    pub fn format(&self) -> AudioFormat {
+        match self.format_id {
+            5 => AudioFormat::Mp3,
+            _ => AudioFormat::Flac,
+        }
+    }
+
+    /// The stream's bit depth and sample rate, if it is lossless.
+    pub fn quality(&self) -> Option<AudioQuality> {
+        Some(AudioQuality {
+            bit_depth: self.bit_depth?,
+            sampling_rate: self.sampling_rate?,
+        })
+    }
 }
 
 // --- Domain types ---
@@ -158,6 +987,75 @@ pub struct PurchaseList {
     pub tracks: Vec<Track>,
 }
 
+impl PurchaseList {
+    /// Drop albums and standalone tracks the filter excludes.
+    pub fn retain_gifts(&mut self, filter: GiftFilter) {
+        self.albums.retain(|a| filter.keeps(a.purchase.is_gift()));
+        self.tracks.retain(|t| filter.keeps(t.purchase.is_gift()));
+    }
+
+    /// Drop albums and standalone tracks the filter excludes. A standalone
+    /// track's album is its own title, as in its path.
+    pub fn retain_names(&mut self, filter: &NameFilter) {
+        self.albums
+            .retain(|a| filter.keeps(&a.artist.name, &a.title));
+        self.tracks
+            .retain(|t| filter.keeps(&t.performer.name, &t.title));
+    }
+
+    /// Add the albums and standalone tracks of `other` that aren't already
+    /// here, by id. Returns how many albums and tracks were added.
+    pub fn add_missing(&mut self, other: PurchaseList) -> (usize, usize) {
+        let album_ids: HashSet<AlbumId> = self.albums.iter().map(|a| a.id.clone()).collect();
+        let mut track_ids: HashSet<TrackId> = self.track_ids().collect();
+        let albums_before = self.albums.len();
+        let tracks_before = self.tracks.len();
+        for album in other.albums {
+            if !album_ids.contains(&album.id) {
+                self.albums.push(album);
+            }
+        }
+        for track in other.tracks {
+            if track_ids.insert(track.id) {
+                self.tracks.push(track);
+            }
+        }
+        (
+            self.albums.len() - albums_before,
+            self.tracks.len() - tracks_before,
+        )
+    }
+
+    /// Every track, on albums (as far as their track lists are known) and
+    /// standalone.
+    pub fn track_ids(&self) -> impl Iterator<Item = TrackId> + '_ {
+        self.albums
+            .iter()
+            .filter_map(|a| a.tracks.as_ref())
+            .flat_map(|t| t.items.iter())
+            .chain(&self.tracks)
+            .map(|t| t.id)
+    }
+
+    /// Drop albums and standalone tracks bought before `since`.
+    pub fn retain_since(&mut self, since: &Since) {
+        self.albums.retain(|a| since.keeps(a.purchase.purchased_at));
+        self.tracks.retain(|t| since.keeps(t.purchase.purchased_at));
+    }
+
+    /// Keep only the tracks in `ids`, dropping albums left without any.
+    pub fn retain_tracks(&mut self, ids: &HashSet<TrackId>) {
+        for album in &mut self.albums {
+            if let Some(tracks) = &mut album.tracks {
+                tracks.items.retain(|t| ids.contains(&t.id));
+            }
+        }
+        self.albums
+            .retain(|a| a.tracks.as_ref().is_some_and(|t| !t.items.is_empty()));
+        self.tracks.retain(|t| ids.contains(&t.id));
+    }
+}
+
 pub struct DownloadTask {
     pub track: Track,
     pub album: Album,
@@ -165,9 +1063,29 @@ pub struct DownloadTask {
     pub file_extension: &'static str,
 }
 
+impl DownloadTask {
+    /// Rough size of the download, assuming it comes in the format its
+    /// extension names.
+    pub fn estimated_bytes(&self) -> u64 {
+        let ext = self.file_extension.trim_start_matches('.');
+        AudioFormat::from_extension(ext)
+            .unwrap_or(AudioFormat::Mp3)
+            .typical_size(self.track.duration)
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum SkipReason {
     AlreadyExists,
     DryRun,
+    /// Exists with an implausible size, and the policy is to keep it.
+    SizeMismatch,
+    /// Left for a later `backfill` session, past this one's size budget.
+    Deferred,
+    /// Left out when choosing what to download with `sync --interactive`.
+    NotSelected,
+    /// A favorite the account hasn't bought (`sync --include-favorites`).
+    NotEntitled,
 }
 
 pub struct SkippedTrack {
@@ -176,10 +1094,167 @@ pub struct SkippedTrack {
     pub reason: SkipReason,
 }
 
-pub struct SyncPlan {
+impl SkippedTrack {
+    /// Rough size of the track's download, as `DownloadTask::estimated_bytes`
+    /// would have it; for sizing what a dry run would download.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.target_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(AudioFormat::from_extension)
+            .unwrap_or(AudioFormat::Mp3)
+            .typical_size(self.track.duration)
+    }
+}
+
+/// Planned work for a single album: tracks to fetch and tracks to skip.
+pub struct AlbumPlan {
+    pub album: Album,
     pub downloads: Vec<DownloadTask>,
     pub skipped: Vec<SkippedTrack>,
+}
+
+impl AlbumPlan {
+    /// Turn every planned download into a skip. Returns how many there were.
+    pub fn skip_downloads(&mut self, reason: SkipReason) -> usize {
+        let count = self.downloads.len();
+        self.skipped
+            .extend(self.downloads.drain(..).map(|d| SkippedTrack {
+                track: d.track,
+                target_path: d.target_path,
+                reason,
+            }));
+        count
+    }
+}
+
+/// A planned target that already exists with an implausible size.
+pub struct SizeMismatch {
+    /// The existing file.
+    pub path: PathBuf,
+    pub actual: u64,
+    pub expected: RangeInclusive<u64>,
+    /// What the plan does about it.
+    pub resolution: SizeMismatchPolicy,
+}
+
+/// Sync plan grouped by album. Albums keep the order in which they were
+/// first seen; tracks within an album are ordered by disc and track number.
+pub struct SyncPlan {
+    pub albums: Vec<AlbumPlan>,
     pub total_tracks: usize,
+    /// Existing files whose size didn't fit the track, in plan order. Each is
+    /// also in `albums`, as a skip or a download depending on the policy.
+    pub size_mismatches: Vec<SizeMismatch>,
+}
+
+impl SyncPlan {
+    /// All planned downloads, flattened across albums.
+    pub fn downloads(&self) -> impl Iterator<Item = &DownloadTask> {
+        self.albums.iter().flat_map(|a| a.downloads.iter())
+    }
+
+    /// All skipped tracks, flattened across albums.
+    pub fn skipped(&self) -> impl Iterator<Item = &SkippedTrack> {
+        self.albums.iter().flat_map(|a| a.skipped.iter())
+    }
+
+    pub fn download_count(&self) -> usize {
+        self.albums.iter().map(|a| a.downloads.len()).sum()
+    }
+
+    /// Roughly how many bytes the planned downloads take.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.downloads().map(DownloadTask::estimated_bytes).sum()
+    }
+
+    /// Keep as many albums' downloads, in plan order, as fit in `budget`
+    /// bytes by estimate, and skip the rest as `Deferred`. The first album
+    /// with downloads is always kept, however big, so a session makes
+    /// progress. Returns how many tracks were deferred.
+    pub fn defer_beyond(&mut self, budget: u64) -> usize {
+        let mut planned = 0u64;
+        let mut full = false;
+        let mut deferred = 0;
+        for album in &mut self.albums {
+            if album.downloads.is_empty() {
+                continue;
+            }
+            let size: u64 = album
+                .downloads
+                .iter()
+                .map(DownloadTask::estimated_bytes)
+                .sum();
+            if !full && (planned == 0 || planned + size <= budget) {
+                planned += size;
+                continue;
+            }
+            // Later albums wait too, even small ones, so sessions work
+            // through the library in order.
+            full = true;
+            deferred += album.skip_downloads(SkipReason::Deferred);
+        }
+        deferred
+    }
+
+    /// Albums with downloads planned, in plan order.
+    pub fn albums_to_download(&self) -> impl Iterator<Item = &AlbumPlan> {
+        self.albums.iter().filter(|a| !a.downloads.is_empty())
+    }
+
+    /// Keep the downloads of the albums `albums_to_download` lists at the
+    /// positions in `selected`, and skip the rest as `NotSelected`. Returns
+    /// how many tracks were skipped.
+    pub fn select_albums(&mut self, selected: &BTreeSet<usize>) -> usize {
+        self.albums
+            .iter_mut()
+            .filter(|a| !a.downloads.is_empty())
+            .enumerate()
+            .filter(|(i, _)| !selected.contains(i))
+            .map(|(_, album)| album.skip_downloads(SkipReason::NotSelected))
+            .sum()
+    }
+
+    /// Skip the planned downloads of the tracks in `ids`, and in a dry run
+    /// what would have been downloaded. Returns how many there were.
+    pub fn skip_tracks(&mut self, ids: &HashSet<TrackId>, reason: SkipReason) -> usize {
+        let mut count = 0;
+        for album in &mut self.albums {
+            for skipped in &mut album.skipped {
+                if matches!(skipped.reason, SkipReason::DryRun) && ids.contains(&skipped.track.id) {
+                    skipped.reason = reason;
+                    count += 1;
+                }
+            }
+            let (skip, keep) = album
+                .downloads
+                .drain(..)
+                .partition(|d| ids.contains(&d.track.id));
+            album.downloads = keep;
+            count += skip.len();
+            album.skipped.extend(skip.into_iter().map(|d| SkippedTrack {
+                track: d.track,
+                target_path: d.target_path,
+                reason,
+            }));
+        }
+        count
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.albums.iter().map(|a| a.skipped.len()).sum()
+    }
+
+    /// Flatten into owned downloads and skips, in album order.
+    pub fn into_parts(self) -> (Vec<DownloadTask>, Vec<SkippedTrack>) {
+        let mut downloads = Vec::new();
+        let mut skipped = Vec::new();
+        for album in self.albums {
+            downloads.extend(album.downloads);
+            skipped.extend(album.skipped);
+        }
+        (downloads, skipped)
+    }
 }
 
 pub struct DownloadError {
@@ -187,13 +1262,61 @@ pub struct DownloadError {
     pub error: String,
 }
 
+/// A finished download and the format that actually landed on disk.
+pub struct CompletedDownload {
+    pub task: DownloadTask,
+    pub format: AudioFormat,
+    /// Bit depth and sample rate of a lossless download.
+    pub quality: Option<AudioQuality>,
+    /// Hex SHA-256 of the file, computed as it was written.
+    pub sha256: String,
+}
+
+impl CompletedDownload {
+    /// Final on-disk path, accounting for format fallback.
+    pub fn path(&self) -> PathBuf {
+        self.task
+            .target_path
+            .with_extension(&self.format.extension()[1..])
+    }
+
+    /// What the file is, as summaries count them: `MP3`, `FLAC 24-96`.
+    pub fn label(&self) -> String {
+        let format = match self.format {
+            AudioFormat::Mp3 => "MP3",
+            AudioFormat::Flac => "FLAC",
+            AudioFormat::Aac => "AAC",
+            AudioFormat::Opus => "Opus",
+        };
+        match self.quality {
+            Some(quality) => format!("{format} {quality}"),
+            None => format.to_string(),
+        }
+    }
+}
+
 pub struct SyncResult {
-    pub succeeded: Vec<DownloadTask>,
+    pub succeeded: Vec<CompletedDownload>,
     pub failed: Vec<DownloadError>,
     pub skipped: Vec<SkippedTrack>,
+    /// Downloads in another format than the quality asked for, such as
+    /// FLAC because MP3 wasn't offered.
     pub fallback_count: usize,
 }
 
+impl SyncResult {
+    /// How many downloads came in each format and quality, most first.
+    pub fn format_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for done in &self.succeeded {
+            *counts.entry(done.label()).or_default() += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        counts
+    }
+}
+
 // --- Bandcamp API response types ---
 
 #[derive(Debug, Clone, Deserialize)]
@@ -201,7 +1324,8 @@ pub struct BandcampCollectionResponse {
     pub more_available: bool,
     #[serde(deserialize_with = "null_as_default")]
     pub last_token: String,
-    #[serde(deserialize_with = "null_as_default")]
+    /// Absent from `wishlist_items`, which has nothing to download.
+    #[serde(default, deserialize_with = "null_as_default")]
     pub redownload_urls: HashMap<String, String>,
     pub items: Vec<BandcampCollectionItem>,
 }
@@ -215,11 +1339,83 @@ pub struct BandcampCollectionItem {
     pub item_id: u64,
     #[serde(deserialize_with = "null_as_default")]
     pub item_type: String,
-    #[serde(deserialize_with = "null_as_default")]
+    /// Empty (and the id 0) for wishlist items, which were never sold.
+    #[serde(default, deserialize_with = "null_as_default")]
     pub sale_item_type: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub sale_item_id: u64,
     #[serde(deserialize_with = "null_as_default")]
     pub token: String,
+    /// Set when the item was gifted to the fan rather than bought by them.
+    #[serde(default)]
+    pub gift_id: Option<u64>,
+    #[serde(default)]
+    pub gift_sender_name: Option<String>,
+    /// Cover image id; see `bandcamp::art_url`.
+    #[serde(default)]
+    pub item_art_id: Option<u64>,
+    /// Public album or track page, e.g. `https://artist.bandcamp.com/album/x`.
+    #[serde(default)]
+    pub item_url: Option<String>,
+}
+
+impl BandcampCollectionItem {
+    pub fn is_gift(&self) -> bool {
+        self.gift_id.is_some()
+    }
+
+    /// Delivered through a paid artist subscription (fan club) rather than
+    /// bought on its own. These come with the sale type `s` and the
+    /// subscription's id, whatever the release is.
+    pub fn is_subscription(&self) -> bool {
+        self.sale_item_type == "s"
+    }
+
+    /// Key of the item's download page in `redownload_urls`: its sale type
+    /// and id ("a123"). Subscription items all share their subscription's
+    /// sale id, so each is keyed by its own type and id instead.
+    pub fn redownload_key(&self) -> String {
+        if self.is_subscription() {
+            let kind = if self.item_type == "track" { "t" } else { "a" };
+            format!("{kind}{}", self.item_id)
+        } else {
+            format!("{}{}", self.sale_item_type, self.sale_item_id)
+        }
+    }
+
+    /// Whether the item is a single track rather than an album, or `None`
+    /// for a sale type qoget doesn't know.
+    pub fn is_track(&self) -> Option<bool> {
+        match self.sale_item_type.as_str() {
+            "a" => Some(false),
+            "t" => Some(true),
+            "s" => Some(self.item_type == "track"),
+            _ => None,
+        }
+    }
+
+    /// When the item was added to the collection: the Unix time that
+    /// starts its pagination token (`1700000000:3318844:a::`).
+    pub fn purchased_at(&self) -> Option<u64> {
+        self.token.split(':').next()?.parse().ok()
+    }
+
+    /// Purchase metadata in the shared form used by `Album`/`Track`.
+    pub fn purchase_info(&self) -> PurchaseInfo {
+        PurchaseInfo {
+            purchased_at: self.purchased_at(),
+            purchase_channel: Some(
+                if self.is_gift() {
+                    "gift"
+                } else if self.is_subscription() {
+                    "subscription"
+                } else {
+                    "purchase"
+                }
+                .to_string(),
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -229,6 +1425,24 @@ pub struct BandcampDownloadInfo {
     pub artist: String,
     pub download_type: String,
     pub downloads: HashMap<String, BandcampDownloadFormat>,
+    /// E.g. "17 Mar 2017 00:00:00 GMT".
+    #[serde(default)]
+    pub release_date: Option<String>,
+}
+
+impl BandcampDownloadInfo {
+    /// `release_date` as `YYYY-MM-DD`, if it parses.
+    pub fn iso_release_date(&self) -> Option<String> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let mut parts = self.release_date.as_deref()?.split_whitespace();
+        let day: u8 = parts.next()?.parse().ok()?;
+        let month = parts.next()?;
+        let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? + 1;
+        let year: u16 = parts.next()?.parse().ok()?;
+        Some(format!("{year:04}-{month:02}-{day:02}"))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -237,16 +1451,40 @@ pub struct BandcampDownloadFormat {
     pub size_mb: String,
 }
 
+impl BandcampDownloadFormat {
+    /// `size_mb` ("90.5MB") in bytes, if it parses.
+    pub fn size_bytes(&self) -> Option<u64> {
+        let mb = self.size_mb.trim();
+        let mb = mb
+            .strip_suffix("MB")
+            .or_else(|| mb.strip_suffix("mb"))
+            .unwrap_or(mb);
+        let mb: f64 = mb.trim().parse().ok()?;
+        (mb.is_finite() && mb >= 0.0).then_some((mb * 1024.0 * 1024.0) as u64)
+    }
+}
+
 // --- Bandcamp sync result ---
 
 pub struct BandcampSyncResult {
     pub downloaded: usize,
     pub skipped: usize,
     pub would_download: usize,
+    /// Already-synced tracks that were given post-download steps they lacked.
+    pub retagged: usize,
+    /// Items downloaded, with the track files placed for each.
+    pub succeeded: Vec<(String, Vec<PathBuf>)>,
+    /// Items not downloaded, and why (already synced, or a dry run).
+    pub skipped_items: Vec<(String, SkipReason)>,
+    /// In a dry run, the download size Bandcamp gives for each item that
+    /// would be downloaded, by item id, where it gave one.
+    pub sizes: HashMap<u64, u64>,
     pub failed: Vec<BandcampDownloadError>,
 }
 
 pub struct BandcampDownloadError {
+    /// The item's id, as in `sizes`.
+    pub item_id: u64,
     pub description: String,
     pub error: String,
 }
@@ -0,0 +1,467 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use tracing::{debug, info, warn};
+
+use crate::config::NetworkConfig;
+use crate::manifest::{Manifest, ProcessingStep, album_dir};
+use crate::models::{AudioFormat, CompletedDownload, Service};
+use crate::network;
+use crate::retry::{self, RateLimiter, RequestPolicy};
+use crate::tags;
+
+/// Tagging an album's tracks with the MusicBrainz IDs and release date of
+/// the release they were matched to.
+pub const MUSICBRAINZ_STEP: ProcessingStep = ProcessingStep {
+    name: "musicbrainz",
+    version: 1,
+};
+
+const API_URL: &str = "https://musicbrainz.org/ws/2";
+
+/// Search results below this score (out of 100) aren't trusted.
+const MIN_SEARCH_SCORE: u32 = 90;
+
+/// ISRCs tried per album before falling back to a search by name.
+const MAX_ISRC_LOOKUPS: usize = 3;
+
+/// The IDs Picard writes, as Vorbis comments (FLAC, Opus) and as the ID3
+/// `TXXX` descriptions and iTunes freeform names it uses (MP3, M4A).
+const ID_TAGS: [(&str, &str); 5] = [
+    ("MUSICBRAINZ_ALBUMID", "MusicBrainz Album Id"),
+    ("MUSICBRAINZ_RELEASEGROUPID", "MusicBrainz Release Group Id"),
+    ("MUSICBRAINZ_ALBUMARTISTID", "MusicBrainz Album Artist Id"),
+    ("MUSICBRAINZ_TRACKID", "MusicBrainz Track Id"),
+    ("MUSICBRAINZ_RELEASETRACKID", "MusicBrainz Release Track Id"),
+];
+
+/// What's known of a downloaded album to find it by on MusicBrainz, most
+/// specific first.
+#[derive(Debug, Clone, Default)]
+pub struct AlbumQuery {
+    /// Album directory, relative to the target directory.
+    pub dir: PathBuf,
+    pub artist: String,
+    pub title: String,
+    /// UPC/EAN, when the store gives one.
+    pub barcode: Option<String>,
+    pub isrcs: Vec<String>,
+}
+
+impl AlbumQuery {
+    /// One query per album the Qobuz `downloads` went into, with the
+    /// album's barcode and its downloaded tracks' ISRCs.
+    pub fn for_qobuz(target_dir: &Path, downloads: &[CompletedDownload]) -> Vec<Self> {
+        let mut albums: BTreeMap<PathBuf, Self> = BTreeMap::new();
+        for done in downloads {
+            let path = done.path();
+            let Some(dir) = path.strip_prefix(target_dir).ok().and_then(album_dir) else {
+                continue;
+            };
+            let album = &done.task.album;
+            let query = albums.entry(dir.clone()).or_insert_with(|| Self {
+                dir,
+                artist: album.artist.name.clone(),
+                title: album.title.clone(),
+                barcode: album.upc.clone().filter(|upc| !upc.is_empty()),
+                isrcs: Vec::new(),
+            });
+            query.isrcs.extend(done.task.track.isrc.clone());
+        }
+        albums.into_values().collect()
+    }
+
+    /// One query by artist and title per album directory of `new_tracks`
+    /// (relative to the target directory), as the manifest records them.
+    pub fn from_manifest(
+        manifest: &Manifest,
+        new_tracks: impl IntoIterator<Item = PathBuf>,
+    ) -> Vec<Self> {
+        let dirs: BTreeSet<PathBuf> = new_tracks
+            .into_iter()
+            .filter_map(|path| album_dir(&path))
+            .collect();
+        dirs.into_iter()
+            .filter_map(|dir| {
+                let entry = manifest
+                    .entries()
+                    .find(|e| album_dir(&e.path).as_deref() == Some(dir.as_path()))?;
+                Some(Self {
+                    artist: entry.artist.clone(),
+                    title: entry.album.clone(),
+                    dir,
+                    ..Self::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub id: String,
+    pub title: String,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Search relevance, for search results.
+    #[serde(default)]
+    pub score: Option<u32>,
+    #[serde(rename = "release-group", default)]
+    pub release_group: Option<ReleaseGroup>,
+    #[serde(rename = "artist-credit", default)]
+    pub artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    pub media: Vec<Medium>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseGroup {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArtistCredit {
+    pub artist: CreditedArtist,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreditedArtist {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Medium {
+    pub position: u32,
+    #[serde(default)]
+    pub tracks: Vec<ReleaseTrack>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseTrack {
+    /// The release track's own ID.
+    pub id: String,
+    pub position: u32,
+    pub recording: Recording,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recording {
+    pub id: String,
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+/// A release search's results.
+#[derive(Debug, Deserialize)]
+pub struct ReleaseList {
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+/// The recordings with an ISRC, with the releases they're on.
+#[derive(Debug, Deserialize)]
+pub struct IsrcLookup {
+    #[serde(default)]
+    pub recordings: Vec<Recording>,
+}
+
+/// A title reduced to lowercase letters and digits, so punctuation and
+/// spacing differences between stores and MusicBrainz don't matter.
+fn normalize(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether a MusicBrainz release title names the album: the same title,
+/// or the store's title with an edition added (`Kind of Blue (Legacy
+/// Edition)` for `Kind of Blue`).
+pub fn title_matches(release: &str, album: &str) -> bool {
+    let (release, album) = (normalize(release), normalize(album));
+    !release.is_empty() && album.starts_with(&release)
+}
+
+/// The release among an ISRC's recordings that is the album titled
+/// `title`. An ISRC's recording turns up on compilations too, so a
+/// release of another title is no match.
+pub fn isrc_release<'a>(lookup: &'a IsrcLookup, title: &str) -> Option<&'a Release> {
+    lookup
+        .recordings
+        .iter()
+        .flat_map(|r| &r.releases)
+        .find(|r| title_matches(&r.title, title))
+}
+
+/// The best trusted result of a search for the album titled `title`.
+pub fn search_release<'a>(list: &'a ReleaseList, title: &str) -> Option<&'a Release> {
+    list.releases
+        .iter()
+        .find(|r| r.score.unwrap_or(0) >= MIN_SEARCH_SCORE && title_matches(&r.title, title))
+}
+
+/// A Lucene query for a release by artist and title.
+pub fn search_query(artist: &str, title: &str) -> String {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "release:\"{}\" AND artist:\"{}\"",
+        quote(title),
+        quote(artist)
+    )
+}
+
+/// Disc and track number of a track file at `path`, from the names qoget
+/// gives them: `[Disc N/]NN - Title.ext`.
+pub fn track_position(path: &Path) -> Option<(u32, u32)> {
+    let name = path.file_name()?.to_str()?;
+    let digits: String = name.chars().take_while(char::is_ascii_digit).collect();
+    let track = digits.parse().ok()?;
+    let disc = path
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|d| d.to_str())
+        .and_then(|d| d.strip_prefix("Disc "))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1);
+    Some((disc, track))
+}
+
+/// The MusicBrainz ID tags for a track of `format` at `position` on
+/// `release`. Track IDs are left out when the track can't be placed.
+pub fn id_tags(
+    format: AudioFormat,
+    release: &Release,
+    position: Option<(u32, u32)>,
+) -> Vec<(String, String)> {
+    let track = position.and_then(|(disc, number)| {
+        release
+            .media
+            .iter()
+            .find(|m| m.position == disc)?
+            .tracks
+            .iter()
+            .find(|t| t.position == number)
+    });
+    let artists: Vec<&str> = release
+        .artist_credit
+        .iter()
+        .map(|c| c.artist.id.as_str())
+        .collect();
+    let values = [
+        Some(release.id.clone()),
+        release.release_group.as_ref().map(|g| g.id.clone()),
+        (!artists.is_empty()).then(|| artists.join("; ")),
+        track.map(|t| t.recording.id.clone()),
+        track.map(|t| t.id.clone()),
+    ];
+    ID_TAGS
+        .iter()
+        .zip(values)
+        .filter_map(|((vorbis, picard), value)| {
+            let name = match format {
+                AudioFormat::Flac | AudioFormat::Opus => vorbis,
+                AudioFormat::Mp3 | AudioFormat::Aac => picard,
+            };
+            Some((name.to_string(), value?))
+        })
+        .collect()
+}
+
+/// A MusicBrainz web service client, kept to the one request per second
+/// MusicBrainz allows.
+pub struct MusicBrainz {
+    http: reqwest::Client,
+    policy: RequestPolicy,
+    limiter: RateLimiter,
+}
+
+impl MusicBrainz {
+    /// `contact`, an email address or URL, goes in the User-Agent, as
+    /// MusicBrainz asks of clients.
+    pub fn new(contact: Option<&str>, network: &NetworkConfig) -> Result<Self> {
+        let mut user_agent = format!("qoget/{}", env!("CARGO_PKG_VERSION"));
+        if let Some(contact) = contact {
+            user_agent.push_str(&format!(" ( {contact} )"));
+        }
+        let policy = RequestPolicy {
+            requests_per_second: Some(1.0),
+            // MusicBrainz answers 503 when it's being asked too often.
+            rate_limit_backoff: None,
+            ..RequestPolicy::default_for(Service::Bandcamp)
+        };
+        Ok(Self {
+            http: network::client_builder(network)?
+                .user_agent(user_agent)
+                .timeout(Duration::from_secs(30))
+                .build()
+                .context("building HTTP client")?,
+            limiter: RateLimiter::new(policy.requests_per_second),
+            policy,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        let request = self
+            .http
+            .get(format!("{API_URL}/{path}"))
+            .query(query)
+            .query(&[("fmt", "json")]);
+        let resp = retry::send(&request, &self.policy, &self.limiter).await?;
+        resp.json()
+            .await
+            .with_context(|| format!("parsing MusicBrainz {path} response"))
+    }
+
+    /// The ID of the release `album` is, looked up by barcode, then by its
+    /// tracks' ISRCs, then by artist and title; `None` if nothing matched
+    /// well enough.
+    pub async fn find_release(&self, album: &AlbumQuery) -> Result<Option<String>> {
+        if let Some(barcode) = &album.barcode {
+            let query = format!("barcode:{barcode}");
+            let list: ReleaseList = self.get("release", &[("query", &query)]).await?;
+            if let Some(release) = list.releases.first() {
+                return Ok(Some(release.id.clone()));
+            }
+        }
+        for isrc in album.isrcs.iter().take(MAX_ISRC_LOOKUPS) {
+            // Unknown ISRCs are a 404; the next lookup may still match.
+            match self
+                .get::<IsrcLookup>(&format!("isrc/{isrc}"), &[("inc", "releases")])
+                .await
+            {
+                Ok(lookup) => {
+                    if let Some(release) = isrc_release(&lookup, &album.title) {
+                        return Ok(Some(release.id.clone()));
+                    }
+                }
+                Err(e) => debug!("MusicBrainz ISRC {isrc}: {e:#}"),
+            }
+        }
+        let query = search_query(&album.artist, &album.title);
+        let list: ReleaseList = self
+            .get("release", &[("query", &query), ("limit", "5")])
+            .await?;
+        Ok(search_release(&list, &album.title).map(|r| r.id.clone()))
+    }
+
+    /// A release with its tracks, release group and artists.
+    pub async fn release(&self, id: &str) -> Result<Release> {
+        self.get(
+            &format!("release/{id}"),
+            &[("inc", "recordings release-groups artist-credits")],
+        )
+        .await
+    }
+}
+
+/// Write `release`'s IDs and date into an album's tracks. Returns each
+/// track's new SHA-256, in order.
+pub async fn tag_album(
+    tracks: &[(PathBuf, AudioFormat)],
+    release: &Release,
+) -> Result<Vec<String>> {
+    let mut shas = Vec::with_capacity(tracks.len());
+    for (path, format) in tracks {
+        let mut tags = id_tags(*format, release, track_position(path));
+        let date = release.date.clone().filter(|d| !d.is_empty());
+        if let Some(date) = &date
+            && *format != AudioFormat::Aac
+        {
+            tags.push(("date".to_string(), date.clone()));
+        }
+        let mut sha = tags::write_tags(path, *format, tags).await?;
+        if let Some(date) = date
+            && *format == AudioFormat::Aac
+        {
+            let path = path.clone();
+            sha = tokio::task::spawn_blocking(move || tags::set_mp4_date(&path, &date)).await??;
+        }
+        shas.push(sha);
+    }
+    Ok(shas)
+}
+
+/// Look up each of `albums` on MusicBrainz and tag its tracks with what
+/// was found, marking the step on their manifest entries. Best-effort: an
+/// album that isn't found or fails is reported and left as it was.
+/// Returns how many albums were tagged.
+pub async fn enrich_albums(
+    manifest: &mut Manifest,
+    service: Service,
+    target_dir: &Path,
+    albums: Vec<AlbumQuery>,
+    contact: Option<&str>,
+    network: &NetworkConfig,
+) -> usize {
+    let client = match MusicBrainz::new(contact, network) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("MusicBrainz lookups skipped: {e:#}");
+            return 0;
+        }
+    };
+    let mut tagged = 0;
+    for album in albums {
+        let release = match client.find_release(&album).await {
+            Ok(Some(id)) => client.release(&id).await,
+            Ok(None) => {
+                info!("MusicBrainz: no match for {}", album.dir.display());
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        let release = match release {
+            Ok(release) => release,
+            Err(e) => {
+                warn!(
+                    "MusicBrainz lookup failed for {}: {e:#}",
+                    album.dir.display()
+                );
+                continue;
+            }
+        };
+        let mut tracks: Vec<(PathBuf, AudioFormat)> = manifest
+            .album_entries_mut(service, &album.dir)
+            .map(|e| (target_dir.join(&e.path), e.format))
+            .filter(|(path, _)| path.is_file())
+            .collect();
+        tracks.sort_by(|a, b| a.0.cmp(&b.0));
+        if tracks.is_empty() {
+            continue;
+        }
+        let shas = match tag_album(&tracks, &release).await {
+            Ok(shas) => shas,
+            Err(e) => {
+                warn!(
+                    "MusicBrainz tags skipped for {}: {e:#}",
+                    album.dir.display()
+                );
+                continue;
+            }
+        };
+        let shas: BTreeMap<&Path, &String> =
+            tracks.iter().map(|(p, _)| p.as_path()).zip(&shas).collect();
+        for entry in manifest.album_entries_mut(service, &album.dir) {
+            if let Some(sha) = shas.get(target_dir.join(&entry.path).as_path()) {
+                entry.refresh(target_dir, Some((*sha).clone()));
+                entry.mark_step(MUSICBRAINZ_STEP);
+            }
+        }
+        debug!(
+            "{} matched MusicBrainz release {}",
+            album.dir.display(),
+            release.id
+        );
+        tagged += 1;
+    }
+    if tagged > 0 {
+        info!("MusicBrainz: tagged {tagged} albums");
+    }
+    tagged
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use reqwest::tls::{Certificate, Version};
+use reqwest::{ClientBuilder, NoProxy, Proxy};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::config::NetworkConfig;
+
+/// Proxy URL schemes reqwest can connect through.
+const SCHEMES: &[&str] = &["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+/// Oldest TLS version to accept from servers (`[network] min_tls_version`).
+/// The platform TLS library qoget uses can't be limited to TLS 1.3 alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls10,
+    #[serde(rename = "1.1")]
+    Tls11,
+    #[serde(rename = "1.2")]
+    Tls12,
+}
+
+impl TlsVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TlsVersion::Tls10 => "1.0",
+            TlsVersion::Tls11 => "1.1",
+            TlsVersion::Tls12 => "1.2",
+        }
+    }
+
+    fn to_reqwest(self) -> Version {
+        match self {
+            TlsVersion::Tls10 => Version::TLS_1_0,
+            TlsVersion::Tls11 => Version::TLS_1_1,
+            TlsVersion::Tls12 => Version::TLS_1_2,
+        }
+    }
+}
+
+/// Warn once, at startup, about settings that weaken every client built
+/// with them.
+pub fn warn_insecure(settings: &NetworkConfig) {
+    if settings.accept_invalid_certs {
+        warn!("network.accept_invalid_certs is set: TLS certificates are not checked");
+    }
+}
+
+/// Check a proxy URL and build the reqwest proxy for it. Hosts in
+/// `NO_PROXY` still bypass it.
+pub fn parse_proxy(url: &str) -> Result<Proxy> {
+    let parsed = reqwest::Url::parse(url).context("invalid proxy URL")?;
+    if !SCHEMES.contains(&parsed.scheme()) {
+        bail!(
+            "unsupported proxy scheme `{}`; use one of {}",
+            parsed.scheme(),
+            SCHEMES.join(", ")
+        );
+    }
+    let proxy = Proxy::all(url).context("invalid proxy URL")?;
+    Ok(proxy.no_proxy(NoProxy::from_env()))
+}
+
+/// Read the PEM certificates in a CA bundle, such as the root of a
+/// TLS-intercepting proxy.
+pub fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("parsing certificates in {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificates in {}", path.display());
+    }
+    Ok(certs)
+}
+
+/// Set `builder` up as `settings` say: proxy, extra trusted certificates
+/// and TLS checks. Without a proxy setting, reqwest uses `HTTPS_PROXY`,
+/// `HTTP_PROXY` and `ALL_PROXY` (and their lowercase forms) as usual.
+pub fn apply(mut builder: ClientBuilder, settings: &NetworkConfig) -> Result<ClientBuilder> {
+    if let Some(url) = settings.proxy.as_deref().filter(|p| !p.is_empty()) {
+        builder = builder.proxy(parse_proxy(url)?);
+    }
+    if let Some(path) = &settings.ca_bundle {
+        for cert in load_ca_bundle(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if settings.accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(version) = settings.min_tls_version {
+        builder = builder.min_tls_version(version.to_reqwest());
+    }
+    Ok(builder)
+}
+
+/// A client builder set up with the `[network]` settings.
+pub fn client_builder(settings: &NetworkConfig) -> Result<ClientBuilder> {
+    apply(reqwest::Client::builder(), settings)
+}
+
+/// A default client, with the `[network]` settings.
+pub fn client(settings: &NetworkConfig) -> Result<reqwest::Client> {
+    client_builder(settings)?
+        .build()
+        .context("Failed to build HTTP client")
+}
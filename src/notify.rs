@@ -0,0 +1,127 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::config::NetworkConfig;
+use crate::models::Service;
+use crate::network;
+use crate::report::{FailureKind, ReportItem, SyncReport};
+
+/// How long to wait for a webhook before giving up on it.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a webhook receives: counts rather than the full track lists of a
+/// `--report`, plus a one-line summary under the field names Slack (`text`)
+/// and Discord (`content`) display.
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub text: String,
+    pub content: String,
+    pub started_at: u64,
+    pub duration_secs: f64,
+    pub target_dir: &'a Path,
+    pub dry_run: bool,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+    pub services: Vec<ServiceSummary<'a>>,
+}
+
+/// One service's part of a webhook payload.
+#[derive(Debug, Serialize)]
+pub struct ServiceSummary<'a> {
+    pub service: Service,
+    pub duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub fallback_count: usize,
+    /// The items that failed, with their errors.
+    pub failures: &'a [ReportItem],
+}
+
+impl<'a> WebhookPayload<'a> {
+    /// Summarize a finished run.
+    pub fn new(report: &'a SyncReport) -> Self {
+        let services: Vec<ServiceSummary> = report
+            .services
+            .iter()
+            .map(|s| ServiceSummary {
+                service: s.service,
+                duration_secs: s.duration_secs,
+                error: s.error.as_deref(),
+                failure: s.failure,
+                succeeded: s.succeeded.len(),
+                failed: s.failed.len(),
+                skipped: s.skipped.len(),
+                fallback_count: s.fallback_count,
+                failures: &s.failed,
+            })
+            .collect();
+        let text = summary_line(report, &services);
+        Self {
+            content: text.clone(),
+            text,
+            started_at: report.started_at,
+            duration_secs: report.duration_secs,
+            target_dir: &report.target_dir,
+            dry_run: report.dry_run,
+            ok: report.ok,
+            error: report.error.as_deref(),
+            services,
+        }
+    }
+}
+
+fn summary_line(report: &SyncReport, services: &[ServiceSummary]) -> String {
+    let outcome = match (report.ok, report.dry_run) {
+        (false, _) => "failed",
+        (true, true) => "finished (dry run)",
+        (true, false) => "finished",
+    };
+    let mut line = format!(
+        "qoget sync of {} {outcome} in {}s",
+        report.target_dir.display(),
+        report.duration_secs.round()
+    );
+    for s in services {
+        line.push_str(&format!(
+            "; {}: {} downloaded, {} failed, {} skipped",
+            s.service, s.succeeded, s.failed, s.skipped
+        ));
+        if let Some(error) = s.error {
+            line.push_str(&format!(" ({error})"));
+        }
+    }
+    if let Some(error) = &report.error
+        && services.iter().all(|s| s.error.is_none())
+    {
+        line.push_str(&format!(": {error}"));
+    }
+    line
+}
+
+/// POST a summary of `report` as JSON to `url`.
+pub async fn post_webhook(url: &str, report: &SyncReport, network: &NetworkConfig) -> Result<()> {
+    let http = network::client_builder(network)?
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .context("building HTTP client")?;
+    let resp = http
+        .post(url)
+        .json(&WebhookPayload::new(report))
+        .send()
+        .await
+        .context("sending webhook")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("webhook returned HTTP {status}");
+    }
+    Ok(())
+}
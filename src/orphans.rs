@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::art::COVER_FILE;
+use crate::download::BANDCAMP_TEMP_DIR;
+use crate::models::AudioFormat;
+
+/// What a `.tmp` file written in place is named for, before the suffix:
+/// downloads and transcodes (by audio extension), retagging, checksum
+/// files, playlists, and JSON state such as the manifest.
+const TEMP_KINDS: &[&str] = &["tagging", "sha256", "m3u8", "json"];
+
+/// Whether `name` is a file qoget writes in place and renames once done,
+/// so one still there was left by a run that didn't finish.
+fn is_temp_file(name: &str) -> bool {
+    if name == format!(".{COVER_FILE}.part") {
+        return true;
+    }
+    let Some(kind) = name
+        .strip_suffix(".tmp")
+        .and_then(|stem| stem.rsplit_once('.'))
+        .map(|(_, kind)| kind)
+    else {
+        return false;
+    };
+    TEMP_KINDS.contains(&kind)
+        || AudioFormat::ALL
+            .iter()
+            .any(|f| f.extension()[1..].eq_ignore_ascii_case(kind))
+}
+
+/// Remove what crashed or killed runs left in `target_dir`: half-written
+/// files (`*.mp3.tmp`, `*.flac.tmp`, ...) and the Bandcamp temp directory
+/// with its unpacked `.m4a` files, so none are taken for library content.
+/// Call it with the target locked, as a running sync's are not orphans.
+///
+/// Returns what was removed, relative to the target. Hidden directories
+/// other than the temp directory are left alone.
+pub fn clean(target_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    let temp_dir = target_dir.join(BANDCAMP_TEMP_DIR);
+    if temp_dir.is_dir() {
+        std::fs::remove_dir_all(&temp_dir)
+            .with_context(|| format!("removing {}", temp_dir.display()))?;
+        removed.push(PathBuf::from(BANDCAMP_TEMP_DIR));
+    }
+
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let dir = target_dir.join(&rel);
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("reading {}", dir.display()))?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let child = rel.join(&*name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if !name.starts_with('.') {
+                    stack.push(child);
+                }
+            } else if file_type.is_file() && is_temp_file(&name) {
+                let path = entry.path();
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("removing {}", path.display()))?;
+                removed.push(child);
+            }
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
@@ -1,14 +1,137 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
 use crate::models::{Album, Track};
 
-/// Replace or remove characters that are invalid or problematic in filesystem paths.
-pub fn sanitize_component(s: &str) -> String {
+/// Which filesystem's naming rules `sanitize_component` must satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeMode {
+    /// Rules shared by all platforms.
+    Portable,
+    /// Additionally avoid NTFS/Win32 restrictions: reserved device names
+    /// (CON, NUL, COM1, ...) and trailing dots or spaces.
+    Windows,
+}
+
+/// How generated names are built: their optional parts, from [paths] in
+/// the config file, and the filesystem rules they follow. The default
+/// leaves the parts out and follows this platform's rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Naming {
+    /// Add each track's version to its file name: "02 - Breathe (2023
+    /// Remaster).flac", so a remaster and the original don't collapse into
+    /// one file.
+    pub track_versions: bool,
+    /// Add each album's version to its directory name: "Artist/Album
+    /// (Deluxe Edition)", so two editions of a record are kept apart.
+    pub album_versions: bool,
+    /// Put each album's release year before its title in its directory
+    /// name: "Artist/2023 - Album".
+    pub album_years: bool,
+    /// Use Windows-safe names even when not running on Windows, e.g. when
+    /// syncing to an NTFS or exFAT drive from Linux.
+    pub windows_safe: bool,
+    /// The Unicode normalization form every name component is put in, if
+    /// any.
+    pub unicode_form: Option<UnicodeForm>,
+}
+
+impl Naming {
+    /// The rules names must satisfy: Windows' when running on Windows or
+    /// with [`windows_safe`](Self::windows_safe), the portable ones
+    /// otherwise.
+    pub fn sanitize_mode(self) -> SanitizeMode {
+        if cfg!(windows) || self.windows_safe {
+            SanitizeMode::Windows
+        } else {
+            SanitizeMode::Portable
+        }
+    }
+}
+
+/// Which edition of a record `album` is, when directory names tell
+/// editions apart (see [`Naming::album_versions`]): its version, unless the
+/// title already says it. Tracks shared by two editions belong to each.
+pub fn edition(album: &Album, naming: Naming) -> Option<&str> {
+    if !naming.album_versions {
+        return None;
+    }
+    album
+        .version
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && !contains_ignoring_case(&album.title, v))
+}
+
+fn contains_ignoring_case(text: &str, part: &str) -> bool {
+    text.to_lowercase().contains(&part.to_lowercase())
+}
+
+/// Unicode normalization form applied to every generated path component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeForm {
+    /// Composed ("é" as one code point), as most Linux tools produce.
+    Nfc,
+    /// Decomposed ("e" + combining accent), as macOS HFS+ stores names.
+    Nfd,
+}
+
+impl UnicodeForm {
+    pub fn normalize(self, s: &str) -> String {
+        match self {
+            UnicodeForm::Nfc => s.nfc().collect(),
+            UnicodeForm::Nfd => s.nfd().collect(),
+        }
+    }
+}
+
+/// Device names Windows reserves in every directory, with or without an extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest path the platform's file APIs accept, in bytes.
+pub const MAX_PATH_BYTES: usize = if cfg!(windows) {
+    260
+} else if cfg!(target_os = "macos") {
+    1024
+} else {
+    4096
+};
+
+/// Longest file name most filesystems allow, in bytes.
+const MAX_NAME_BYTES: usize = 255;
+
+/// Room kept in a track path for what is added while writing it: a temp
+/// suffix such as `.tagging.tmp`, or Bandcamp's staging directory.
+const PATH_HEADROOM: usize = 32;
+
+/// Marks a title shortened to fit the path length limits.
+const ELLIPSIS: &str = "…";
+
+/// Replace or remove characters that are invalid or problematic in filesystem paths,
+/// using `naming`'s rules (see [`Naming::sanitize_mode`]) and Unicode
+/// normalization form.
+pub fn sanitize_component(s: &str, naming: Naming) -> String {
+    match naming.unicode_form {
+        Some(form) => sanitize_component_with(&form.normalize(s), naming.sanitize_mode()),
+        None => sanitize_component_with(s, naming.sanitize_mode()),
+    }
+}
+
+/// Like [`sanitize_component`], with an explicit mode.
+pub fn sanitize_component_with(s: &str, mode: SanitizeMode) -> String {
     let mut out = String::with_capacity(s.len());
     for ch in s.chars() {
         match ch {
             '/' | '\\' | ':' => out.push('-'),
             '*' | '?' | '"' | '<' | '>' | '|' => {}
+            c if c.is_control() => {}
             _ => out.push(ch),
         }
     }
@@ -43,16 +166,64 @@ pub fn sanitize_component(s: &str) -> String {
         result.truncate(end);
     }
 
+    if mode == SanitizeMode::Windows {
+        result = windows_safe(result);
+    }
+
     result
 }
 
+/// Strip trailing dots and spaces (Win32 silently drops them) and suffix
+/// reserved device names with `_`, e.g. "Con" -> "Con_", "nul.txt" -> "nul_.txt".
+fn windows_safe(mut s: String) -> String {
+    s.truncate(s.trim_end_matches(['.', ' ']).len());
+
+    let stem_len = s.find('.').unwrap_or(s.len());
+    let stem = s[..stem_len].trim_end();
+    if WINDOWS_RESERVED
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(stem))
+    {
+        s.insert(stem.len(), '_');
+        if s.len() > 255 {
+            s.pop();
+            s.truncate(s.trim_end_matches(['.', ' ']).len());
+        }
+    }
+
+    s
+}
+
+/// Directory holding an album's tracks: base / album_artist / album_title,
+/// with the album's [`edition`] after the title if it has one, and its
+/// year before it if years are on (see [`Naming::album_years`]) and it has one.
+pub fn album_dir(base: &Path, album: &Album, naming: Naming) -> PathBuf {
+    let title = match edition(album, naming) {
+        Some(version) => format!("{} ({version})", album.title),
+        None => album.title.clone(),
+    };
+    let name = match album.year().filter(|_| naming.album_years) {
+        Some(year) => format!("{year} - {title}"),
+        None => title,
+    };
+    base.join(sanitize_component(&album.artist.name, naming))
+        .join(sanitize_component(&name, naming))
+}
+
+/// Whether `name` is the album directory name `undated` with some year
+/// before it ("2023 - Album" for "Album"). A Bandcamp album's year isn't
+/// known until its download page is fetched, so until then its directory
+/// is looked for this way.
+pub fn is_dated_album_dir(name: &str, undated: &str) -> bool {
+    name.split_once(" - ").is_some_and(|(year, rest)| {
+        year.len() == 4 && year.bytes().all(|b| b.is_ascii_digit()) && rest == undated
+    })
+}
+
 /// Build the target path for a track file:
 ///   base / album_artist / album_title [/ Disc N] / NN - [Track Artist - ] Title{ext}
-pub fn track_path(base: &Path, album: &Album, track: &Track, ext: &str) -> PathBuf {
-    let artist_dir = sanitize_component(&album.artist.name);
-    let album_dir = sanitize_component(&album.title);
-
-    let mut path = base.join(&artist_dir).join(&album_dir);
+pub fn track_path(base: &Path, album: &Album, track: &Track, ext: &str, naming: Naming) -> PathBuf {
+    let mut path = album_dir(base, album, naming);
 
     // Multi-disc: add "Disc N" subdirectory
     if album.media_count > 1 {
@@ -60,16 +231,106 @@ pub fn track_path(base: &Path, album: &Album, track: &Track, ext: &str) -> PathB
     }
 
     // Build filename
-    let track_title = sanitize_component(&track.title);
-    let is_compilation = track.performer.name != album.artist.name;
+    let track_title = sanitize_component(&versioned_title(track, naming), naming);
+    let is_compilation = album.is_compilation_track(track);
 
-    let num = track.track_number.0;
-    let filename = if is_compilation {
-        let track_artist = sanitize_component(&track.performer.name);
-        format!("{num:02} - {track_artist} - {track_title}{ext}")
+    // Track number 0 means unknown, and gets no prefix rather than "00".
+    let num = match track.track_number.0 {
+        0 => String::new(),
+        n => format!("{n:02} - "),
+    };
+    let name = if is_compilation {
+        let track_artist = sanitize_component(&track.performer.name, naming);
+        format!("{track_artist} - {track_title}")
     } else {
-        format!("{num:02} - {track_title}{ext}")
+        track_title
     };
 
-    path.join(filename)
+    // A deep target and a long classical title can add up to more than the
+    // OS takes, which would only fail once the file is created.
+    let dir_len = path.as_os_str().len() + 1;
+    let budget = MAX_NAME_BYTES.min(MAX_PATH_BYTES.saturating_sub(dir_len + PATH_HEADROOM));
+    path.join(fit_filename(&num, &name, ext, budget))
+}
+
+/// The track's title, followed by its version in parentheses if file
+/// names include versions (see [`Naming::track_versions`]) and the title
+/// doesn't mention it already.
+fn versioned_title(track: &Track, naming: Naming) -> String {
+    let version = track
+        .version
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && naming.track_versions);
+    match version {
+        Some(v) if !contains_ignoring_case(&track.title, v) => format!("{} ({v})", track.title),
+        _ => track.title.clone(),
+    }
+}
+
+/// Target paths for an album's `tracks`, as [`track_path`] builds them,
+/// except that a track whose path an earlier one already took gets an
+/// ordinal: "03 - Interlude (2).mp3". Two tracks sharing a number and
+/// title, or hidden tracks with neither, would otherwise overwrite each
+/// other. Paths are compared ignoring case, as some filesystems do.
+pub fn track_paths<'a>(
+    base: &Path,
+    album: &Album,
+    tracks: impl IntoIterator<Item = &'a Track>,
+    ext: &str,
+    naming: Naming,
+) -> Vec<PathBuf> {
+    let mut taken = HashSet::new();
+    tracks
+        .into_iter()
+        .map(|track| {
+            let path = track_path(base, album, track, ext, naming);
+            let mut candidate = path.clone();
+            let mut n = 1;
+            while !taken.insert(candidate.to_string_lossy().to_lowercase()) {
+                n += 1;
+                candidate = with_ordinal(&path, ext, n);
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// `path` with " (n)" added before its extension `ext`.
+fn with_ordinal(path: &Path, ext: &str, n: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = name.strip_suffix(ext).unwrap_or(&name);
+    let name = format!("{stem} ({n})");
+    path.with_file_name(format!("{}{ext}", name.trim_start()))
+}
+
+/// `{num}{name}{ext}`, with the end of `name` cut off and replaced by "…"
+/// if needed to keep it within `budget` bytes. The track number and the
+/// extension are always kept.
+fn fit_filename(num: &str, name: &str, ext: &str, budget: usize) -> String {
+    let full = format!("{num}{name}{ext}");
+    if full.len() <= budget {
+        return full;
+    }
+    let mut end = budget
+        .saturating_sub(num.len() + ELLIPSIS.len() + ext.len())
+        .min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Also keeps a trailing dot or space, which Windows drops, from ending
+    // up before the ellipsis.
+    let kept = name[..end].trim_end_matches(['.', ' ', '-']);
+    format!("{num}{kept}{ELLIPSIS}{ext}")
+}
+
+/// Where a `keep-both` download goes when a different file already sits at
+/// `path`: `01 - Title (qoget).mp3` next to `01 - Title.mp3`.
+pub fn keep_both_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem} (qoget).{}", ext.to_string_lossy()),
+        None => format!("{stem} (qoget)"),
+    };
+    path.with_file_name(name)
 }
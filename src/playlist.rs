@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::models::{Playlist, PlaylistTrack, Service};
+use crate::path::{Naming, sanitize_component};
+
+/// Directory under the target that exported playlists are written to.
+pub const PLAYLISTS_DIR: &str = "Playlists";
+
+/// Playlist of the most recently synced tracks, at the top of the target.
+pub const RECENTLY_ADDED: &str = "Recently Added.m3u8";
+
+/// A playlist as an M3U file, with how many of its tracks made it in.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RenderedPlaylist {
+    pub contents: String,
+    /// Tracks found in the library and listed in the file.
+    pub found: usize,
+    /// Tracks left out because they were never synced.
+    pub missing: usize,
+}
+
+/// Render `tracks` as an extended M3U playlist of the synced files the
+/// manifest records for them, in playlist order. Paths are relative to
+/// [`PLAYLISTS_DIR`] and always use `/`, so the library can be moved or
+/// shared between machines without breaking them.
+pub fn render(tracks: &[PlaylistTrack], manifest: &Manifest) -> RenderedPlaylist {
+    let mut out = RenderedPlaylist {
+        contents: "#EXTM3U\n".to_string(),
+        ..Default::default()
+    };
+    for track in tracks {
+        let Some(entry) = manifest.get(Service::Qobuz, track.id) else {
+            out.missing += 1;
+            continue;
+        };
+        let artist = match &track.performer {
+            Some(performer) => performer.name.as_str(),
+            None => entry.artist.as_str(),
+        };
+        out.contents.push_str(&format!(
+            "#EXTINF:{},{artist} - {}\n../{}\n",
+            track.duration,
+            track.title,
+            m3u_path(entry)
+        ));
+        out.found += 1;
+    }
+    out
+}
+
+/// An entry's path relative to the target, with `/` as the separator.
+fn m3u_path(entry: &ManifestEntry) -> String {
+    let path: Vec<_> = entry
+        .path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    path.join("/")
+}
+
+/// Add newly synced tracks to the end of [`RECENTLY_ADDED`]'s `contents`
+/// (empty if there is no such file yet), keeping only the newest `cap`
+/// tracks. A track already listed moves to the end rather than appearing
+/// twice. The manifest doesn't keep durations, so they are given as -1.
+pub fn append_recent(contents: &str, entries: &[&ManifestEntry], cap: usize) -> String {
+    let added: Vec<(String, String)> = entries
+        .iter()
+        .map(|entry| {
+            let info = format!("#EXTINF:-1,{} - {}", entry.artist, entry.title);
+            (info, m3u_path(entry))
+        })
+        .collect();
+
+    let mut tracks: Vec<(String, String)> = Vec::new();
+    let mut info = None;
+    for line in contents.lines() {
+        if line.starts_with("#EXTINF:") {
+            info = Some(line.to_string());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let info = info.take().unwrap_or_else(|| "#EXTINF:-1,".to_string());
+            if !added.iter().any(|(_, path)| path == line) {
+                tracks.push((info, line.to_string()));
+            }
+        }
+    }
+    tracks.extend(added);
+    let skip = tracks.len().saturating_sub(cap);
+
+    let mut out = "#EXTM3U\n".to_string();
+    for (info, path) in &tracks[skip..] {
+        out.push_str(&format!("{info}\n{path}\n"));
+    }
+    out
+}
+
+/// Add `entries` to `<target>/Recently Added.m3u8` as `append_recent` does,
+/// creating it if need be.
+pub fn add_recently_added(
+    target_dir: &Path,
+    entries: &[&ManifestEntry],
+    cap: usize,
+) -> Result<PathBuf> {
+    let path = target_dir.join(RECENTLY_ADDED);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    write_file(&path, &append_recent(&contents, entries, cap))?;
+    Ok(path)
+}
+
+/// File names for `playlists`, in the same order. Playlists whose names
+/// clash once sanitized (Qobuz allows duplicates) get their id appended.
+pub fn file_names(playlists: &[Playlist], naming: Naming) -> Vec<String> {
+    let stems: Vec<String> = playlists
+        .iter()
+        .map(|p| sanitize_component(&p.name, naming))
+        .collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for stem in &stems {
+        *counts.entry(stem.to_lowercase()).or_default() += 1;
+    }
+    playlists
+        .iter()
+        .zip(stems)
+        .map(|(playlist, stem)| {
+            if counts[&stem.to_lowercase()] > 1 {
+                format!("{stem} ({}).m3u8", playlist.id)
+            } else {
+                format!("{stem}.m3u8")
+            }
+        })
+        .collect()
+}
+
+/// Write a rendered playlist to `<target>/Playlists/<file_name>`, replacing
+/// any earlier export of it.
+pub fn write(target_dir: &Path, file_name: &str, contents: &str) -> Result<PathBuf> {
+    let dir = target_dir.join(PLAYLISTS_DIR);
+    std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = dir.join(file_name);
+    write_file(&path, contents)?;
+    Ok(path)
+}
+
+/// Atomic write: temp file + rename.
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    let tmp = path.with_extension("m3u8.tmp");
+    std::fs::write(&tmp, contents).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+}
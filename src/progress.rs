@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use tracing::info;
+
+use crate::models::Service;
+
+/// Where a phase's bars go: stderr, or nowhere when `hidden`, as for
+/// `--quiet`: their redraws fill a cron job's log with control characters.
+pub fn multi(hidden: bool) -> MultiProgress {
+    if hidden {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    }
+}
+
+/// A spinner for a phase whose size isn't known up front, such as crawling
+/// a paginated purchase list. It keeps ticking while requests are throttled
+/// or retried, so a slow crawl doesn't look hung. When stderr isn't a
+/// terminal the bars are hidden, so `msg` is printed once instead.
+pub fn spinner(multi: &MultiProgress, msg: &str) -> ProgressBar {
+    if multi.is_hidden() {
+        info!("{msg}...");
+    }
+    let pb = multi.add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner} {prefix} {msg} [{elapsed}]")
+            .expect("valid template"),
+    );
+    pb.set_prefix(msg.to_string());
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb
+}
+
+/// A `[pos/len] message` counter for a phase with a known number of items.
+pub fn counter(multi: &MultiProgress, len: u64) -> ProgressBar {
+    let pb = multi.add(ProgressBar::new(len));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{pos}/{len}] {msg}")
+            .expect("valid template"),
+    );
+    pb
+}
+
+/// A byte-count bar for a single file transfer.
+pub fn bytes(multi: &MultiProgress, len: Option<u64>, msg: String) -> ProgressBar {
+    let pb = multi.add(ProgressBar::new(len.unwrap_or(0)));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("  {bytes}/{total_bytes} {bar:30} {msg}")
+            .expect("valid template"),
+    );
+    pb.set_message(msg);
+    pb
+}
+
+/// A whole-run byte bar, with throughput and time left, over downloads
+/// estimated at `len` bytes in all. Each download feeds it through a
+/// `Share`, which corrects the estimate as real sizes come in.
+pub fn total_bytes(multi: &MultiProgress, len: u64) -> ProgressBar {
+    let pb = multi.add(ProgressBar::new(len));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{bytes}/{total_bytes} {bar:30} {bytes_per_sec}, {eta} left")
+            .expect("valid template"),
+    );
+    pb
+}
+
+/// One download's part of a `total_bytes` bar. It starts as the estimate
+/// the bar's length was made of, becomes the download's real size once that
+/// is known, and whatever wasn't downloaded comes off the total when the
+/// share is dropped, so a failed or smaller download leaves no gap.
+pub struct Share {
+    total: ProgressBar,
+    expected: u64,
+    done: u64,
+}
+
+impl Share {
+    pub fn new(total: &ProgressBar, estimate: u64) -> Self {
+        Self {
+            total: total.clone(),
+            expected: estimate,
+            done: 0,
+        }
+    }
+
+    /// The download turned out to be `size` bytes.
+    pub fn set_size(&mut self, size: u64) {
+        if size > self.expected {
+            self.total.inc_length(size - self.expected);
+        } else {
+            self.total.dec_length(self.expected - size);
+        }
+        self.expected = size;
+    }
+
+    /// `n` more bytes arrived.
+    pub fn inc(&mut self, n: u64) {
+        self.done += n;
+        if self.done > self.expected {
+            self.total.inc_length(self.done - self.expected);
+            self.expected = self.done;
+        }
+        self.total.inc(n);
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.total.dec_length(self.expected - self.done);
+    }
+}
+
+/// Print `text` above `multi`'s bars, or log it when they are hidden
+/// because stderr isn't a terminal. Nothing shows with `--quiet`.
+pub fn println(multi: &MultiProgress, text: &str) {
+    if multi.is_hidden() {
+        info!("{text}");
+    } else {
+        let _ = multi.println(text);
+    }
+}
+
+/// A run's per-track lines, grouped by album. Concurrent downloads finish
+/// in any order; each album's lines come out together, under its heading
+/// and above a completion line, once its last track is done.
+pub struct AlbumLog<K> {
+    albums: HashMap<K, AlbumLines>,
+}
+
+struct AlbumLines {
+    heading: String,
+    tracks: usize,
+    left: usize,
+    failed: usize,
+    notes: Vec<String>,
+}
+
+impl<K: Hash + Eq> AlbumLog<K> {
+    /// `tracks` holds one `(album, heading)` pair per track to download.
+    pub fn new(tracks: impl IntoIterator<Item = (K, String)>) -> Self {
+        let mut albums: HashMap<K, AlbumLines> = HashMap::new();
+        for (key, heading) in tracks {
+            let album = albums.entry(key).or_insert_with(|| AlbumLines {
+                heading,
+                tracks: 0,
+                left: 0,
+                failed: 0,
+                notes: Vec::new(),
+            });
+            album.tracks += 1;
+            album.left += 1;
+        }
+        Self { albums }
+    }
+
+    /// Add a line about one of `album`'s tracks, to show under its heading.
+    pub fn note(&mut self, album: &K, line: String) {
+        if let Some(album) = self.albums.get_mut(album) {
+            album.notes.push(line);
+        }
+    }
+
+    /// One of `album`'s tracks is done. Returns the album's lines once that
+    /// was its last.
+    pub fn finish(&mut self, album: &K, ok: bool) -> Option<String> {
+        let lines = self.albums.get_mut(album)?;
+        lines.left -= 1;
+        if !ok {
+            lines.failed += 1;
+        }
+        if lines.left > 0 {
+            return None;
+        }
+        let mut out = lines.heading.clone();
+        for note in &lines.notes {
+            out.push_str(&format!("\n  {note}"));
+        }
+        if lines.failed == 0 {
+            out.push_str(&format!("\n  Done: {} tracks", lines.tracks));
+        } else {
+            out.push_str(&format!(
+                "\n  Done: {} of {} tracks, {} failed",
+                lines.tracks - lines.failed,
+                lines.tracks,
+                lines.failed
+            ));
+        }
+        Some(out)
+    }
+}
+
+/// A download, as [`SyncEvents`] name it: a Qobuz track, or a Bandcamp
+/// purchase (downloaded as one ZIP).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadItem {
+    pub service: Service,
+    /// Qobuz track ID or Bandcamp item ID.
+    pub id: String,
+    /// "Artist - Title".
+    pub name: String,
+}
+
+/// Progress of a sync's downloads, for programs embedding qoget that draw
+/// their own progress instead of the bars on stderr (see [`hide`]). Called
+/// from the sync's task; each method does nothing unless overridden.
+pub trait SyncEvents: Send + Sync {
+    /// `item` started downloading.
+    fn on_track_started(&self, _item: &DownloadItem) {}
+    /// `bytes` more of `item` arrived.
+    fn on_bytes(&self, _item: &DownloadItem, _bytes: u64) {}
+    /// `item` was downloaded and is in place.
+    fn on_track_done(&self, _item: &DownloadItem) {}
+    /// `item` couldn't be downloaded, and won't be this run.
+    fn on_item_failed(&self, _item: &DownloadItem, _error: &str) {}
+}
+
+/// Where a run's [`SyncEvents`] go, if anywhere.
+#[derive(Clone, Default)]
+pub struct Events(Option<Arc<dyn SyncEvents>>);
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.0.is_some() { "set" } else { "none" };
+        f.debug_tuple("Events").field(&state).finish()
+    }
+}
+
+impl Events {
+    pub fn new(sink: Arc<dyn SyncEvents>) -> Self {
+        Self(Some(sink))
+    }
+
+    pub fn track_started(&self, item: &DownloadItem) {
+        if let Some(sink) = &self.0 {
+            sink.on_track_started(item);
+        }
+    }
+
+    pub fn bytes(&self, item: &DownloadItem, bytes: u64) {
+        if let Some(sink) = &self.0 {
+            sink.on_bytes(item, bytes);
+        }
+    }
+
+    pub fn track_done(&self, item: &DownloadItem) {
+        if let Some(sink) = &self.0 {
+            sink.on_track_done(item);
+        }
+    }
+
+    pub fn item_failed(&self, item: &DownloadItem, error: &str) {
+        if let Some(sink) = &self.0 {
+            sink.on_item_failed(item, error);
+        }
+    }
+}
+
+/// One file transfer's progress, reported to its own bar, its `Share` of
+/// the run's byte bar if there is one, and the run's [`Events`].
+pub struct Transfer {
+    pb: ProgressBar,
+    share: Option<Share>,
+    events: Events,
+    item: DownloadItem,
+}
+
+impl Transfer {
+    pub fn new(pb: ProgressBar, events: &Events, item: DownloadItem) -> Self {
+        Self {
+            pb,
+            share: None,
+            events: events.clone(),
+            item,
+        }
+    }
+
+    /// Also count the transfer towards a run's `total_bytes` bar.
+    pub fn with_share(self, share: Share) -> Self {
+        Self {
+            share: Some(share),
+            ..self
+        }
+    }
+
+    /// The transfer turned out to be `size` bytes.
+    pub fn set_size(&mut self, size: u64) {
+        self.pb.set_length(size);
+        if let Some(share) = &mut self.share {
+            share.set_size(size);
+        }
+    }
+
+    /// `n` more bytes arrived.
+    pub fn inc(&mut self, n: u64) {
+        self.pb.inc(n);
+        if let Some(share) = &mut self.share {
+            share.inc(n);
+        }
+        self.events.bytes(&self.item, n);
+    }
+
+    /// Take the transfer's bar off the screen.
+    pub fn finish(&self) {
+        self.pb.finish_and_clear();
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::bandcamp::BandcampPurchases;
+use crate::checksums;
+use crate::download::item_album;
+use crate::manifest::{Manifest, entry_key};
+use crate::models::{PurchaseList, Service};
+use crate::path::{Naming, album_dir, is_dated_album_dir};
+use crate::trash::Trash;
+
+/// What a service's purchases still include, for `sync --prune`.
+pub enum Purchased {
+    /// Manifest keys of the purchased Qobuz tracks.
+    Tracks(HashSet<String>),
+    /// Album directories (relative to the target) of the purchased
+    /// Bandcamp items. Their tracks get ids only when downloaded, so
+    /// Bandcamp files are matched by directory.
+    AlbumDirs(HashSet<PathBuf>),
+}
+
+impl Purchased {
+    pub fn qobuz(purchases: &PurchaseList) -> Self {
+        Self::Tracks(
+            purchases
+                .track_ids()
+                .map(|id| entry_key(Service::Qobuz, id))
+                .collect(),
+        )
+    }
+
+    pub fn bandcamp(purchases: &BandcampPurchases, naming: Naming) -> Self {
+        Self::AlbumDirs(
+            purchases
+                .items
+                .iter()
+                .map(|item| album_dir(Path::new(""), &item_album(item), naming))
+                .collect(),
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Tracks(keys) => keys.is_empty(),
+            Self::AlbumDirs(dirs) => dirs.is_empty(),
+        }
+    }
+}
+
+/// A synced track its service no longer sells to the account (a refund,
+/// a regional takedown).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleTrack {
+    /// Manifest key, `"<service>:<track id>"`.
+    pub key: String,
+    /// Relative to the target directory.
+    pub path: PathBuf,
+    pub artist: String,
+    pub album: String,
+    pub title: String,
+}
+
+/// Tracks recorded for `service` that `purchased` no longer includes. Only
+/// files qoget synced (and so recorded) are candidates; nothing else in the
+/// target is ever pruned. An empty purchase list is refused rather than
+/// taken to mean everything went.
+pub fn stale_tracks(
+    manifest: &Manifest,
+    service: Service,
+    purchased: &Purchased,
+) -> Result<Vec<StaleTrack>> {
+    let prefix = format!("{}:", service.to_string().to_lowercase());
+    let recorded: Vec<_> = manifest
+        .keyed_entries()
+        .filter(|(key, _)| key.starts_with(&prefix))
+        .collect();
+    if purchased.is_empty() && !recorded.is_empty() {
+        bail!("{service} listed no purchases at all; not pruning");
+    }
+    Ok(recorded
+        .into_iter()
+        .filter(|(key, entry)| match purchased {
+            Purchased::Tracks(keys) => !keys.contains(*key),
+            Purchased::AlbumDirs(dirs) => !dirs.iter().any(|dir| in_album_dir(&entry.path, dir)),
+        })
+        .map(|(key, entry)| StaleTrack {
+            key: key.to_string(),
+            path: entry.path.clone(),
+            artist: entry.artist.clone(),
+            album: entry.album.clone(),
+            title: entry.title.clone(),
+        })
+        .collect())
+}
+
+/// Whether `path` is in the album directory `dir`, or in the same album's
+/// directory under a year ("Artist/2023 - Album"), which a Bandcamp album
+/// only gets from its download page.
+fn in_album_dir(path: &Path, dir: &Path) -> bool {
+    if path.starts_with(dir) {
+        return true;
+    }
+    let (Some(artist_dir), Some(undated)) = (dir.parent(), dir.file_name()) else {
+        return false;
+    };
+    path.strip_prefix(artist_dir)
+        .ok()
+        .and_then(|rest| rest.components().next())
+        .is_some_and(|name| {
+            is_dated_album_dir(
+                &name.as_os_str().to_string_lossy(),
+                &undated.to_string_lossy(),
+            )
+        })
+}
+
+/// Move `stale` tracks into a new trash batch and drop them from the
+/// manifest. Directories left empty are removed. Files already gone are
+/// just forgotten. Returns the batch directory.
+pub fn prune(target_dir: &Path, manifest: &mut Manifest, stale: &[StaleTrack]) -> Result<PathBuf> {
+    let trash = Trash::new(target_dir);
+    let mut dirs = BTreeSet::new();
+    for track in stale {
+        if target_dir.join(&track.path).exists() {
+            trash.move_file(&track.path)?;
+        }
+        manifest.remove(&track.key);
+        dirs.extend(track.path.parent().map(Path::to_path_buf));
+    }
+    // Albums pruned whole lose their checksum files, and then their
+    // directories.
+    checksums::refresh(target_dir, manifest, dirs.iter().cloned())?;
+    for dir in &dirs {
+        // Disc, album and artist directories, if nothing else is in them
+        for dir in target_dir.join(dir).ancestors().take(3) {
+            if dir == target_dir || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(trash.batch_dir())
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+use tracing::{debug, info, warn};
+
+use crate::manifest::{Manifest, ProcessingStep, album_dir};
+use crate::models::{AudioFormat, Service};
+use crate::tags;
+use crate::transcode::run_ffmpeg;
+
+/// Writing ReplayGain (or, for Opus, R128) gain tags into an album's tracks.
+pub const REPLAYGAIN_STEP: ProcessingStep = ProcessingStep {
+    name: "replaygain",
+    version: 1,
+};
+
+/// ReplayGain 2.0's reference loudness, in LUFS.
+const REPLAYGAIN_REFERENCE: f64 = -18.0;
+/// The loudness Opus' R128 gain tags are relative to (EBU R128), in LUFS.
+const R128_REFERENCE: f64 = -23.0;
+
+/// What ffmpeg's EBU R128 filter measured for a track or album.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS.
+    pub integrated: f64,
+    /// Sample peak in dBFS.
+    pub peak: f64,
+}
+
+impl Loudness {
+    /// Gain in dB that brings this to ReplayGain's reference loudness.
+    pub fn replaygain(&self) -> f64 {
+        REPLAYGAIN_REFERENCE - self.integrated
+    }
+
+    /// Gain towards the R128 reference, as the Q7.8 fixed-point integer
+    /// Opus tags use.
+    pub fn r128(&self) -> i16 {
+        ((R128_REFERENCE - self.integrated) * 256.0)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+
+    /// Sample peak as a linear amplitude, 1.0 being full scale.
+    pub fn linear_peak(&self) -> f64 {
+        10f64.powf(self.peak / 20.0)
+    }
+}
+
+/// ffmpeg arguments that measure `inputs` played back to back.
+pub fn measure_args(inputs: &[PathBuf]) -> Vec<OsString> {
+    let mut args: Vec<OsString> = ["-hide_banner", "-nostats", "-nostdin"]
+        .map(OsString::from)
+        .to_vec();
+    let mut streams = String::new();
+    for (i, input) in inputs.iter().enumerate() {
+        args.extend(["-i".into(), input.as_os_str().to_owned()]);
+        streams.push_str(&format!("[{i}:a]"));
+    }
+    args.extend([
+        "-filter_complex".into(),
+        format!(
+            "{streams}concat=n={}:v=0:a=1,ebur128=peak=sample:framelog=verbose",
+            inputs.len()
+        )
+        .into(),
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ]);
+    args
+}
+
+/// Read the integrated loudness and sample peak from the summary ffmpeg's
+/// `ebur128` filter logs at the end of a run.
+pub fn parse_summary(log: &str) -> Result<Loudness> {
+    let Some((_, summary)) = log.rsplit_once("Summary:") else {
+        bail!("ffmpeg printed no loudness summary");
+    };
+    let value = |label: &str, unit: &str| {
+        summary.lines().find_map(|line| {
+            let rest = line.trim().strip_prefix(label)?;
+            rest.trim().strip_suffix(unit)?.trim().parse::<f64>().ok()
+        })
+    };
+    let Some(integrated) = value("I:", "LUFS") else {
+        bail!("no integrated loudness in ffmpeg's summary");
+    };
+    let Some(peak) = value("Peak:", "dBFS") else {
+        bail!("no sample peak in ffmpeg's summary");
+    };
+    Ok(Loudness { integrated, peak })
+}
+
+/// The tags to write into a track of `format`. Opus files get R128 gains
+/// (players apply them on top of the header gain); everything else gets
+/// ReplayGain gains and peaks.
+pub fn gain_tags(format: AudioFormat, track: Loudness, album: Loudness) -> Vec<(String, String)> {
+    let tags = match format {
+        AudioFormat::Opus => vec![
+            ("R128_TRACK_GAIN", track.r128().to_string()),
+            ("R128_ALBUM_GAIN", album.r128().to_string()),
+        ],
+        _ => vec![
+            (
+                "REPLAYGAIN_TRACK_GAIN",
+                format!("{:+.2} dB", track.replaygain()),
+            ),
+            (
+                "REPLAYGAIN_TRACK_PEAK",
+                format!("{:.6}", track.linear_peak()),
+            ),
+            (
+                "REPLAYGAIN_ALBUM_GAIN",
+                format!("{:+.2} dB", album.replaygain()),
+            ),
+            (
+                "REPLAYGAIN_ALBUM_PEAK",
+                format!("{:.6}", album.linear_peak()),
+            ),
+        ],
+    };
+    tags.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+async fn measure(inputs: &[PathBuf]) -> Result<Loudness> {
+    let out = run_ffmpeg(measure_args(inputs)).await?;
+    let log = String::from_utf8_lossy(&out.stderr);
+    if !out.status.success() {
+        bail!("ffmpeg could not measure loudness: {}", log.trim());
+    }
+    parse_summary(&log)
+}
+
+/// Measure an album's tracks, alone and together, and write their gain
+/// tags. Returns each track's new SHA-256, in order.
+pub async fn tag_album(tracks: &[(PathBuf, AudioFormat)]) -> Result<Vec<String>> {
+    let paths: Vec<PathBuf> = tracks.iter().map(|(path, _)| path.clone()).collect();
+    let album = measure(&paths).await?;
+    let mut shas = Vec::with_capacity(tracks.len());
+    for (path, format) in tracks {
+        let track = measure(std::slice::from_ref(path)).await?;
+        debug!(
+            lufs = track.integrated,
+            peak = track.peak,
+            "measured {}",
+            path.display()
+        );
+        let mut tags = gain_tags(*format, track, album);
+        // MP4 ReplayGain taggers name the freeform items in lower case.
+        if *format == AudioFormat::Aac {
+            tags.iter_mut().for_each(|(k, _)| *k = k.to_lowercase());
+        }
+        shas.push(tags::write_tags(path, *format, tags).await?);
+    }
+    Ok(shas)
+}
+
+/// Tag the albums that `new_tracks` (paths relative to the target
+/// directory) were downloaded into, and mark the step on their manifest
+/// entries. Albums are measured whole, so tracks synced earlier get their
+/// album gain updated too. Best-effort: an album that fails is reported
+/// and left untagged. Returns how many albums were tagged.
+pub async fn tag_new_albums(
+    manifest: &mut Manifest,
+    service: Service,
+    target_dir: &Path,
+    new_tracks: impl IntoIterator<Item = PathBuf>,
+) -> usize {
+    let dirs: BTreeSet<PathBuf> = new_tracks
+        .into_iter()
+        .filter_map(|path| album_dir(&path))
+        .collect();
+    let mut tagged = 0;
+    for dir in dirs {
+        let mut tracks: Vec<(PathBuf, AudioFormat)> = manifest
+            .album_entries_mut(service, &dir)
+            .map(|e| (target_dir.join(&e.path), e.format))
+            .filter(|(path, _)| path.is_file())
+            .collect();
+        // Album order, so the album is measured as it plays.
+        tracks.sort_by(|a, b| a.0.cmp(&b.0));
+        if tracks.is_empty() {
+            continue;
+        }
+        let shas = match tag_album(&tracks).await {
+            Ok(shas) => shas,
+            Err(e) => {
+                warn!("ReplayGain skipped for {}: {e:#}", dir.display());
+                continue;
+            }
+        };
+        for entry in manifest.album_entries_mut(service, &dir) {
+            let path = target_dir.join(&entry.path);
+            if let Some(i) = tracks.iter().position(|(p, _)| *p == path) {
+                entry.refresh(target_dir, Some(shas[i].clone()));
+                entry.mark_step(REPLAYGAIN_STEP);
+            }
+        }
+        tagged += 1;
+    }
+    if tagged > 0 {
+        info!("ReplayGain: tagged {tagged} albums");
+    }
+    tagged
+}
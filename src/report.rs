@@ -0,0 +1,517 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::beets::Import;
+use crate::health;
+use crate::models::{
+    AlbumId, AudioFormat, AudioQuality, BandcampSyncResult, FailOn, QobuzItem, Service, SkipReason,
+    SkippedTrack, SyncResult, TrackId,
+};
+use crate::sync::standalone_track;
+
+/// File under the state directory listing the last run's failed
+/// downloads.
+pub const FAILURES_FILE: &str = "last-failures.json";
+
+/// Machine-readable summary of a `sync` run, written by `--report`.
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    /// Unix time the run started.
+    pub started_at: u64,
+    pub duration_secs: f64,
+    pub target_dir: PathBuf,
+    pub dry_run: bool,
+    /// True if every service synced without failures.
+    pub ok: bool,
+    /// Why the run as a whole failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub services: Vec<ServiceReport>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+/// What one service's sync did.
+#[derive(Debug, Serialize)]
+pub struct ServiceReport {
+    pub service: Service,
+    pub duration_secs: f64,
+    /// Why the service's sync stopped early or failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What kind of failure `error` is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    /// Tracks downloaded in another format than the quality asked for,
+    /// such as FLAC because MP3 wasn't offered (Qobuz).
+    pub fallback_count: usize,
+    pub succeeded: Vec<ReportItem>,
+    pub failed: Vec<ReportItem>,
+    pub skipped: Vec<ReportItem>,
+    /// Albums handed to `beet import` under `[integrations.beets]`, with
+    /// the error for those it failed on.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub beets: Vec<ReportItem>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+/// What made a service's sync fail, ordered from least to most serious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    /// Some downloads failed; the rest of the sync went through.
+    Downloads,
+    /// Anything else, e.g. an unexpected API response or a disk error.
+    Other,
+    /// The service couldn't be reached.
+    Network,
+    /// The login or identity cookie was rejected.
+    Auth,
+}
+
+impl FailureKind {
+    pub fn classify(err: &anyhow::Error) -> Self {
+        if health::is_auth_error(err) {
+            FailureKind::Auth
+        } else if health::is_network_error(err) {
+            FailureKind::Network
+        } else if err.chain().any(|e| e.is::<DownloadsFailed>()) {
+            FailureKind::Downloads
+        } else {
+            FailureKind::Other
+        }
+    }
+
+    /// Exit status of a run whose worst failure is of this kind.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Other => 1,
+            FailureKind::Downloads => 3,
+            FailureKind::Network => 4,
+            FailureKind::Auth => 5,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Downloads => write!(f, "downloads"),
+            FailureKind::Other => write!(f, "other"),
+            FailureKind::Network => write!(f, "network"),
+            FailureKind::Auth => write!(f, "auth"),
+        }
+    }
+}
+
+/// A service sync that got through but couldn't download some items; they
+/// are listed in its report.
+#[derive(Debug)]
+pub struct DownloadsFailed(pub Service);
+
+impl fmt::Display for DownloadsFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Some {} downloads failed", self.0)
+    }
+}
+
+impl std::error::Error for DownloadsFailed {}
+
+/// A track (Qobuz) or purchase (Bandcamp) in a report.
+#[derive(Debug, Serialize)]
+pub struct ReportItem {
+    /// "Artist - Track" for Qobuz tracks, "Artist - Title" for Bandcamp
+    /// purchases.
+    pub name: String,
+    /// Qobuz track ID, or Bandcamp item ID of a failed download.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Qobuz album ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_id: Option<String>,
+    /// File path, relative to the target directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<AudioFormat>,
+    /// Bit depth and sample rate of a lossless download.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<AudioQuality>,
+    /// Why a skipped item was skipped: `already-synced`, `dry-run`,
+    /// `size-mismatch`, `deferred` (past a `backfill` session's budget), or
+    /// `not-selected` (left out under `sync --interactive`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReportItem {
+    fn named(name: String) -> Self {
+        Self {
+            name,
+            id: None,
+            album_id: None,
+            path: None,
+            format: None,
+            quality: None,
+            reason: None,
+            error: None,
+        }
+    }
+}
+
+fn reason_label(reason: &SkipReason) -> &'static str {
+    match reason {
+        SkipReason::AlreadyExists => "already-synced",
+        SkipReason::DryRun => "dry-run",
+        SkipReason::SizeMismatch => "size-mismatch",
+        SkipReason::Deferred => "deferred",
+        SkipReason::NotSelected => "not-selected",
+        SkipReason::NotEntitled => "not-entitled",
+    }
+}
+
+fn relative(path: &Path, target_dir: &Path) -> PathBuf {
+    path.strip_prefix(target_dir).unwrap_or(path).to_path_buf()
+}
+
+impl SyncReport {
+    pub fn new(target_dir: &Path, dry_run: bool) -> Self {
+        let started_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            started_at,
+            duration_secs: 0.0,
+            target_dir: target_dir.to_path_buf(),
+            dry_run,
+            ok: false,
+            error: None,
+            services: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Record a finished service sync.
+    pub fn add(&mut self, mut service: ServiceReport, result: &Result<()>) {
+        service.duration_secs = service.started.elapsed().as_secs_f64();
+        service.error = result.as_ref().err().map(|e| format!("{e:#}"));
+        service.failure = result.as_ref().err().map(FailureKind::classify);
+        self.services.push(service);
+    }
+
+    /// The most serious failure among the services, if any failed.
+    pub fn worst_failure(&self) -> Option<FailureKind> {
+        self.services.iter().filter_map(|s| s.failure).max()
+    }
+
+    /// The services that failed, e.g. "Qobuz (auth), Bandcamp (downloads)".
+    pub fn failed_services(&self) -> String {
+        self.services
+            .iter()
+            .filter_map(|s| Some(format!("{} ({})", s.service, s.failure?)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Exit status for a run that ended this way: 0 if it succeeded, the
+    /// code of the most serious service failure if any service failed, and
+    /// 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.ok {
+            return 0;
+        }
+        self.worst_failure().map_or(1, FailureKind::exit_code)
+    }
+
+    /// Close the report with the run's outcome.
+    pub fn finish(&mut self, result: &Result<()>) {
+        self.duration_secs = self.started.elapsed().as_secs_f64();
+        self.ok = result.is_ok();
+        self.error = result.as_ref().err().map(|e| format!("{e:#}"));
+    }
+
+    /// Whether every failure in the run is one `policy` lets pass, so the
+    /// run can still exit successfully. A service that failed before
+    /// downloading anything never passes, nor does a run in which no
+    /// service got started.
+    pub fn tolerates(&self, policy: FailOn) -> bool {
+        if self.services.is_empty() {
+            return false;
+        }
+        self.services.iter().all(|s| {
+            let attempted = !s.succeeded.is_empty() || !s.failed.is_empty();
+            match (&s.error, policy) {
+                (None, _) => true,
+                (Some(_), FailOn::Any) => false,
+                (Some(_), FailOn::All) => attempted && !s.succeeded.is_empty(),
+                (Some(_), FailOn::None) => attempted,
+            }
+        })
+    }
+
+    /// Write the report as TOML if `path` ends in `.toml`, JSON otherwise.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = if path.extension().is_some_and(|e| e == "toml") {
+            toml::to_string_pretty(self).context("serializing report")?
+        } else {
+            serde_json::to_string_pretty(self).context("serializing report")? + "\n"
+        };
+        std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+/// The failed downloads of a run, as [`FAILURES_FILE`] keeps them.
+#[derive(Debug, Serialize)]
+pub struct FailureList<'a> {
+    /// Unix time the run started.
+    pub started_at: u64,
+    pub target_dir: &'a Path,
+    pub failures: Vec<Failure<'a>>,
+}
+
+/// One failed download and the service it was from.
+#[derive(Debug, Serialize)]
+pub struct Failure<'a> {
+    pub service: Service,
+    #[serde(flatten)]
+    pub item: &'a ReportItem,
+}
+
+impl SyncReport {
+    /// Every service's failed downloads, in service order.
+    pub fn failures(&self) -> FailureList<'_> {
+        FailureList {
+            started_at: self.started_at,
+            target_dir: &self.target_dir,
+            failures: self
+                .services
+                .iter()
+                .flat_map(|s| {
+                    s.failed.iter().map(|item| Failure {
+                        service: s.service,
+                        item,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Write the run's failed downloads to `path` as JSON, creating its
+    /// directory, and return how many there were. A run without failures
+    /// removes an earlier run's file instead, so it never outlives a retry
+    /// that worked.
+    pub fn write_failures(&self, path: &Path) -> Result<usize> {
+        let list = self.failures();
+        if list.failures.is_empty() {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("removing {}", path.display()));
+                }
+                _ => return Ok(0),
+            }
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(&list).context("serializing failures")? + "\n";
+        std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))?;
+        Ok(list.failures.len())
+    }
+}
+
+/// What `retry` downloads again: the failures a [`FAILURES_FILE`] lists.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetryList {
+    /// The target directory of the run that failed.
+    pub target_dir: PathBuf,
+    /// Qobuz albums, and standalone tracks, to fetch again.
+    pub qobuz_items: Vec<QobuzItem>,
+    /// Of what `qobuz_items` fetches, the tracks to download.
+    pub qobuz_tracks: HashSet<TrackId>,
+    /// Bandcamp item IDs.
+    pub bandcamp_items: BTreeSet<u64>,
+}
+
+#[derive(Deserialize)]
+struct SavedFailures {
+    target_dir: PathBuf,
+    failures: Vec<SavedFailure>,
+}
+
+#[derive(Deserialize)]
+struct SavedFailure {
+    service: Service,
+    id: Option<String>,
+    album_id: Option<String>,
+}
+
+impl RetryList {
+    /// Read the list from a failures file's contents. Failures without the
+    /// IDs to find them again are left out.
+    pub fn parse(json: &str) -> Result<Self> {
+        let saved: SavedFailures = serde_json::from_str(json).context("parsing failures")?;
+        let mut list = Self {
+            target_dir: saved.target_dir,
+            ..Self::default()
+        };
+        for failure in saved.failures {
+            let Some(id) = failure.id.and_then(|id| id.parse::<u64>().ok()) else {
+                continue;
+            };
+            match failure.service {
+                Service::Bandcamp => {
+                    list.bandcamp_items.insert(id);
+                }
+                Service::Qobuz => {
+                    let Some(album) = failure.album_id.map(AlbumId) else {
+                        continue;
+                    };
+                    let item = match standalone_track(&album) {
+                        Some(track) => QobuzItem::Track(track),
+                        None => QobuzItem::Album(album),
+                    };
+                    if !list.qobuz_items.contains(&item) {
+                        list.qobuz_items.push(item);
+                    }
+                    list.qobuz_tracks.insert(TrackId(id));
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    /// Read the failures file at `path`; `None` if there isn't one.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::parse(&json)
+                .with_context(|| format!("reading {}", path.display()))
+                .map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// The services with something to retry.
+    pub fn services(&self) -> Vec<Service> {
+        let mut services = Vec::new();
+        if !self.qobuz_tracks.is_empty() {
+            services.push(Service::Qobuz);
+        }
+        if !self.bandcamp_items.is_empty() {
+            services.push(Service::Bandcamp);
+        }
+        services
+    }
+}
+
+impl ServiceReport {
+    pub fn new(service: Service) -> Self {
+        Self {
+            service,
+            duration_secs: 0.0,
+            error: None,
+            failure: None,
+            fallback_count: 0,
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+            skipped: Vec::new(),
+            beets: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Record tracks a sync plan skipped.
+    pub fn add_skipped<'a>(
+        &mut self,
+        skipped: impl IntoIterator<Item = &'a SkippedTrack>,
+        target_dir: &Path,
+    ) {
+        for s in skipped {
+            self.skipped.push(ReportItem {
+                id: Some(s.track.id.to_string()),
+                path: Some(relative(&s.target_path, target_dir)),
+                reason: Some(reason_label(&s.reason)),
+                ..ReportItem::named(format!("{} - {}", s.track.performer.name, s.track.title))
+            });
+        }
+    }
+
+    /// Record the outcome of Qobuz downloads. Skipped tracks are recorded
+    /// from the plan with `add_skipped`.
+    pub fn add_qobuz(&mut self, result: &SyncResult, target_dir: &Path) {
+        self.fallback_count += result.fallback_count;
+        for done in &result.succeeded {
+            let task = &done.task;
+            self.succeeded.push(ReportItem {
+                id: Some(task.track.id.to_string()),
+                album_id: Some(task.album.id.to_string()),
+                path: Some(relative(&done.path(), target_dir)),
+                format: Some(done.format),
+                quality: done.quality,
+                ..ReportItem::named(format!(
+                    "{} - {}",
+                    task.track.performer.name, task.track.title
+                ))
+            });
+        }
+        for err in &result.failed {
+            let task = &err.task;
+            self.failed.push(ReportItem {
+                id: Some(task.track.id.to_string()),
+                album_id: Some(task.album.id.to_string()),
+                path: Some(relative(&task.target_path, target_dir)),
+                error: Some(err.error.clone()),
+                ..ReportItem::named(format!(
+                    "{} - {}",
+                    task.track.performer.name, task.track.title
+                ))
+            });
+        }
+    }
+
+    /// Record how each album's beets import went.
+    pub fn add_beets(&mut self, imports: &[Import]) {
+        for import in imports {
+            self.beets.push(ReportItem {
+                path: Some(import.dir.clone()),
+                error: import.error.clone(),
+                ..ReportItem::named(import.dir.display().to_string())
+            });
+        }
+    }
+
+    /// Record the outcome of Bandcamp downloads.
+    pub fn add_bandcamp(&mut self, result: &BandcampSyncResult, target_dir: &Path) {
+        for (name, paths) in &result.succeeded {
+            for path in paths {
+                self.succeeded.push(ReportItem {
+                    path: Some(relative(path, target_dir)),
+                    format: Some(AudioFormat::Aac),
+                    ..ReportItem::named(name.clone())
+                });
+            }
+        }
+        for err in &result.failed {
+            self.failed.push(ReportItem {
+                id: Some(err.item_id.to_string()),
+                error: Some(err.error.clone()),
+                ..ReportItem::named(err.description.clone())
+            });
+        }
+        for (name, reason) in &result.skipped_items {
+            self.skipped.push(ReportItem {
+                reason: Some(reason_label(reason)),
+                ..ReportItem::named(name.clone())
+            });
+        }
+    }
+}
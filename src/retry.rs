@@ -0,0 +1,149 @@
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use tracing::{debug, warn};
+
+use crate::models::Service;
+
+/// Statuses retried unless the config says otherwise: rate limiting and
+/// the server errors that usually pass.
+pub const DEFAULT_RETRY_STATUSES: &[u16] = &[429, 500, 502, 503, 504];
+
+/// How a service's API client paces its requests and retries transient
+/// failures. Set from the `[qobuz]` and `[bandcamp]` config sections.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestPolicy {
+    /// Most requests started per second; `None` for no limit.
+    pub requests_per_second: Option<f64>,
+    /// Retries after the first attempt before giving up.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+    /// Fixed wait after an HTTP 429, instead of `backoff`.
+    pub rate_limit_backoff: Option<Duration>,
+    /// HTTP statuses worth retrying; any other failure is returned at once.
+    pub retry_statuses: Vec<u16>,
+    /// Up to this fraction of each wait is added at random, so clients
+    /// that failed together don't all retry at the same moment.
+    pub jitter: f64,
+}
+
+impl RequestPolicy {
+    /// What qoget uses when the config sets nothing: Qobuz unthrottled,
+    /// Bandcamp (which has no public API) at 3 requests per second with a
+    /// long pause when it says to slow down.
+    pub fn default_for(service: Service) -> Self {
+        match service {
+            Service::Qobuz => Self {
+                requests_per_second: None,
+                max_retries: 3,
+                backoff: Duration::from_secs(1),
+                rate_limit_backoff: None,
+                retry_statuses: DEFAULT_RETRY_STATUSES.to_vec(),
+                jitter: 0.1,
+            },
+            Service::Bandcamp => Self {
+                requests_per_second: Some(3.0),
+                max_retries: 3,
+                backoff: Duration::from_secs(1),
+                rate_limit_backoff: Some(Duration::from_secs(10)),
+                retry_statuses: DEFAULT_RETRY_STATUSES.to_vec(),
+                jitter: 0.1,
+            },
+        }
+    }
+
+    /// Whether a response with `status` is worth retrying.
+    pub fn retries(&self, status: u16) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    /// How long to wait before retry number `retry` (from 0) after `status`,
+    /// before jitter.
+    pub fn delay(&self, retry: u32, status: u16) -> Duration {
+        match self.rate_limit_backoff {
+            Some(wait) if status == 429 => wait,
+            _ => self.backoff.saturating_mul(1 << retry.min(16)),
+        }
+    }
+
+    /// `delay` plus a random share of it, up to `jitter`.
+    pub fn jittered(&self, delay: Duration) -> Duration {
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let random = RandomState::new().hash_one(Instant::now()) as f64 / u64::MAX as f64;
+        delay.mul_f64(1.0 + self.jitter * random)
+    }
+}
+
+/// Spaces requests out to a policy's `requests_per_second`.
+pub struct RateLimiter {
+    last_request: Mutex<Instant>,
+    min_interval: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<f64>) -> Self {
+        Self {
+            last_request: Mutex::new(Instant::now() - Duration::from_secs(1)),
+            min_interval: requests_per_second
+                .map_or(Duration::ZERO, |rps| Duration::from_secs_f64(1.0 / rps)),
+        }
+    }
+
+    pub async fn wait(&self) {
+        let wait_until = {
+            let mut last = self.last_request.lock().unwrap();
+            let now = Instant::now();
+            let earliest = *last + self.min_interval;
+            *last = earliest.max(now);
+            earliest
+        };
+        let now = Instant::now();
+        if wait_until > now {
+            tokio::time::sleep(wait_until - now).await;
+        }
+    }
+}
+
+/// Send `request`, retrying transient failures as `policy` says, and
+/// return the first successful (or 304 Not Modified) response. Statuses outside the policy's
+/// `retry_statuses`, such as 401 (auth) or 400 (bad request), fail at once.
+pub async fn send(
+    request: &RequestBuilder,
+    policy: &RequestPolicy,
+    limiter: &RateLimiter,
+) -> Result<Response> {
+    let mut retry = 0;
+    loop {
+        limiter.wait().await;
+
+        let (http, req) = request
+            .try_clone()
+            .context("Request cannot be cloned for retry")?
+            .build_split();
+        let req = req?;
+        debug!(method = %req.method(), path = req.url().path(), attempt = retry, "request");
+
+        let resp = http.execute(req).await?;
+        let status = resp.status();
+
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(resp);
+        }
+
+        if !policy.retries(status.as_u16()) || retry >= policy.max_retries {
+            let body = resp.text().await.unwrap_or_default();
+            bail!("HTTP {} — {}", status, body);
+        }
+
+        let delay = policy.jittered(policy.delay(retry, status.as_u16()));
+        warn!("HTTP {status}, retrying in {delay:?}...");
+        tokio::time::sleep(delay).await;
+        retry += 1;
+    }
+}
@@ -0,0 +1,531 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use tracing::{debug, warn};
+
+use crate::config::NetworkConfig;
+use crate::network;
+use crate::storage::{Entry, Metadata, xml_unescape};
+
+/// Files this big or bigger are uploaded in parts of this size, so a
+/// hi-res FLAC never sits in memory whole and a failed part is all that
+/// is sent again.
+pub const PART_SIZE: u64 = 16 * 1024 * 1024;
+/// `x-amz-content-sha256` for a body that isn't hashed up front.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Keys and region to sign requests with, from the usual AWS environment
+/// variables (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+/// `AWS_SESSION_TOKEN`, `AWS_REGION`) or the shared credentials file
+/// (`~/.aws/credentials`, profile `AWS_PROFILE` or `default`).
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+    pub region: String,
+}
+
+impl Credentials {
+    pub fn from_env() -> Result<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+        if let (Some(access_key), Some(secret_key)) =
+            (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+        {
+            return Ok(Self {
+                access_key,
+                secret_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+                region,
+            });
+        }
+        let profile = var("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let path = var("AWS_SHARED_CREDENTIALS_FILE").map_or_else(
+            || {
+                let home = std::env::var_os("HOME").unwrap_or_default();
+                Path::new(&home).join(".aws").join("credentials")
+            },
+            Into::into,
+        );
+        let file = std::fs::read_to_string(&path).ok();
+        let keys = file.as_deref().and_then(|f| profile_keys(f, &profile));
+        let Some((access_key, secret_key, session_token)) = keys else {
+            bail!(
+                "No S3 credentials. Set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, \
+                 or add a [{profile}] profile to {}",
+                path.display()
+            );
+        };
+        Ok(Self {
+            access_key,
+            secret_key,
+            session_token,
+            region,
+        })
+    }
+}
+
+/// The access key, secret key and session token of `profile` in an AWS
+/// shared credentials file.
+fn profile_keys(file: &str, profile: &str) -> Option<(String, String, Option<String>)> {
+    let mut in_profile = false;
+    let (mut access, mut secret, mut token) = (None, None, None);
+    for line in file.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = name.trim() == profile;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_profile) else {
+            continue;
+        };
+        let value = Some(value.trim().to_string());
+        match key.trim() {
+            "aws_access_key_id" => access = value,
+            "aws_secret_access_key" => secret = value,
+            "aws_session_token" => token = value,
+            _ => {}
+        }
+    }
+    Some((access?, secret?, token))
+}
+
+/// A bucket, or a prefix in one, on S3 or an S3-compatible store such as
+/// MinIO. `AWS_ENDPOINT_URL_S3` or `AWS_ENDPOINT_URL` points it at a store
+/// other than AWS, which is then addressed by path
+/// (`http://minio:9000/bucket/key`).
+pub struct Bucket {
+    http: reqwest::Client,
+    /// Where requests go: the bucket's own host on AWS, or the endpoint.
+    base: Url,
+    /// Path of the bucket on `base`: empty, or `/bucket` for path-style
+    /// addressing.
+    bucket_path: String,
+    /// Key prefix of the target, without slashes at either end.
+    prefix: String,
+    creds: Credentials,
+}
+
+impl Bucket {
+    pub fn new(
+        name: &str,
+        prefix: &str,
+        creds: Credentials,
+        network: &NetworkConfig,
+    ) -> Result<Self> {
+        let endpoint = std::env::var("AWS_ENDPOINT_URL_S3")
+            .or_else(|_| std::env::var("AWS_ENDPOINT_URL"))
+            .ok()
+            .filter(|e| !e.is_empty());
+        let (base, bucket_path) = match endpoint {
+            Some(endpoint) => (
+                Url::parse(&endpoint).with_context(|| format!("invalid S3 endpoint {endpoint}"))?,
+                format!("/{}", uri_encode(name, false)),
+            ),
+            // Dotted names don't match the wildcard certificate.
+            None if name.contains('.') => (
+                Url::parse(&format!("https://s3.{}.amazonaws.com", creds.region))?,
+                format!("/{}", uri_encode(name, false)),
+            ),
+            None => (
+                Url::parse(&format!("https://{name}.s3.{}.amazonaws.com", creds.region))?,
+                String::new(),
+            ),
+        };
+        Ok(Self {
+            http: network::client(network)?,
+            base,
+            bucket_path,
+            prefix: prefix.trim_matches('/').to_string(),
+            creds,
+        })
+    }
+
+    /// The object key for `rel`, a path under the target.
+    fn key(&self, rel: &str) -> String {
+        match (self.prefix.as_str(), rel) {
+            (prefix, "") => prefix.to_string(),
+            ("", rel) => rel.to_string(),
+            (prefix, rel) => format!("{prefix}/{rel}"),
+        }
+    }
+
+    /// Send a signed request for `key` (empty for the bucket itself).
+    /// `query` is in canonical form: encoded, and sorted by name.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, String)],
+        body: Body,
+    ) -> Result<reqwest::Response> {
+        let path = match key {
+            "" => format!("{}/", self.bucket_path),
+            key => format!("{}/{}", self.bucket_path, uri_encode(key, false)),
+        };
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut url = self.base.clone();
+        url.set_path(&path);
+        url.set_query(Some(&query).filter(|q| !q.is_empty()).map(String::as_str));
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let payload_hash = match &body {
+            Body::Empty => hex_sha256(b""),
+            Body::Bytes(bytes) => hex_sha256(bytes),
+            Body::Stream(..) => UNSIGNED_PAYLOAD.to_string(),
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let amz_date = amz_date(now);
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(ref token) = self.creds.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = authorization(
+            &self.creds,
+            method.as_str(),
+            url.path(),
+            url.query().unwrap_or_default(),
+            &headers,
+            &payload_hash,
+            &amz_date,
+        );
+
+        let mut req = self
+            .http
+            .request(method.clone(), url.clone())
+            .header("Authorization", authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            req = req.header(*name, value);
+        }
+        req = match body {
+            Body::Empty => req,
+            Body::Bytes(bytes) => req.body(bytes),
+            Body::Stream(body, len) => req.header(reqwest::header::CONTENT_LENGTH, len).body(body),
+        };
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            let code = capture(&tag_regex("Code"), &text).unwrap_or_default();
+            bail!("{method} {url} returned HTTP {status} {code}");
+        }
+        Ok(resp)
+    }
+
+    /// Objects and common prefixes directly under `rel`.
+    pub async fn list(&self, rel: &str) -> Result<Vec<Entry>> {
+        let prefix = match self.key(rel) {
+            key if key.is_empty() => key,
+            key => format!("{key}/"),
+        };
+        let mut entries = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = Vec::new();
+            if let Some(ref token) = token {
+                query.push(("continuation-token", uri_encode(token, true)));
+            }
+            query.extend([
+                ("delimiter", "%2F".to_string()),
+                ("list-type", "2".to_string()),
+                ("prefix", uri_encode(&prefix, true)),
+            ]);
+            let body = self
+                .send(Method::GET, "", &query, Body::Empty)
+                .await?
+                .text()
+                .await?;
+            let page = parse_listing(&body, &prefix);
+            entries.extend(page.entries);
+            match page.next {
+                Some(next) => token = Some(next),
+                None => return Ok(entries),
+            }
+        }
+    }
+
+    /// Upload the file at `from` as `rel`: in one request, or in parts of
+    /// [`PART_SIZE`] when it is that big. Either way the object appears
+    /// whole or not at all.
+    pub async fn upload(&self, from: &Path, rel: &str) -> Result<()> {
+        let key = self.key(rel);
+        let file = tokio::fs::File::open(from)
+            .await
+            .with_context(|| format!("opening {}", from.display()))?;
+        let len = file.metadata().await?.len();
+        if len < PART_SIZE {
+            let chunks = futures::stream::try_unfold(file, |mut file| async move {
+                let mut buf = vec![0; 64 * 1024];
+                let n = file.read(&mut buf).await?;
+                if n == 0 {
+                    return Ok::<_, std::io::Error>(None);
+                }
+                buf.truncate(n);
+                Ok(Some((buf, file)))
+            });
+            let body = Body::Stream(reqwest::Body::wrap_stream(chunks), len);
+            self.send(Method::PUT, &key, &[], body).await?;
+            return Ok(());
+        }
+
+        let body = self
+            .send(
+                Method::POST,
+                &key,
+                &[("uploads", String::new())],
+                Body::Empty,
+            )
+            .await?
+            .text()
+            .await?;
+        let upload_id = capture(&tag_regex("UploadId"), &body)
+            .context("no UploadId in the CreateMultipartUpload response")?;
+        let result = self.upload_parts(file, &key, &upload_id).await;
+        if result.is_err() {
+            let query = [("uploadId", uri_encode(&upload_id, true))];
+            if let Err(e) = self.send(Method::DELETE, &key, &query, Body::Empty).await {
+                warn!("could not abort the upload of {key}: {e:#}");
+            }
+        }
+        result
+    }
+
+    async fn upload_parts(
+        &self,
+        mut file: tokio::fs::File,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<()> {
+        let mut etags = Vec::new();
+        loop {
+            let mut part = Vec::with_capacity(PART_SIZE as usize);
+            (&mut file).take(PART_SIZE).read_to_end(&mut part).await?;
+            if part.is_empty() {
+                break;
+            }
+            let number = etags.len() + 1;
+            let query = [
+                ("partNumber", number.to_string()),
+                ("uploadId", uri_encode(upload_id, true)),
+            ];
+            let resp = self
+                .send(Method::PUT, key, &query, Body::Bytes(part))
+                .await
+                .with_context(|| format!("uploading part {number}"))?;
+            let etag = resp
+                .headers()
+                .get("ETag")
+                .and_then(|v| v.to_str().ok())
+                .context("no ETag for an uploaded part")?
+                .to_string();
+            debug!("uploaded part {number} of {key}");
+            etags.push(etag);
+        }
+
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in etags.iter().enumerate() {
+            complete.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+                i + 1
+            ));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+        let query = [("uploadId", uri_encode(upload_id, true))];
+        let body = self
+            .send(
+                Method::POST,
+                key,
+                &query,
+                Body::Bytes(complete.into_bytes()),
+            )
+            .await?
+            .text()
+            .await?;
+        // Completing can fail after the response has started, as a 200.
+        if body.contains("<Error>") {
+            let code = capture(&tag_regex("Code"), &body).unwrap_or_default();
+            bail!("completing the upload of {key} failed: {code}");
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, rel: &str) -> Result<()> {
+        self.send(Method::DELETE, &self.key(rel), &[], Body::Empty)
+            .await
+            .map(drop)
+    }
+}
+
+enum Body {
+    Empty,
+    /// Signed with its hash.
+    Bytes(Vec<u8>),
+    /// Sent unsigned, with its length.
+    Stream(reqwest::Body, u64),
+}
+
+/// One page of a ListObjectsV2 response.
+#[derive(Debug)]
+pub struct Listing {
+    /// Objects and common prefixes, named relative to the listed prefix.
+    pub entries: Vec<Entry>,
+    /// Continuation token for the next page, if there is one.
+    pub next: Option<String>,
+}
+
+/// Parse a ListObjectsV2 page of the objects under `prefix` (which ends in
+/// `/`, or is empty for the bucket root).
+pub fn parse_listing(xml: &str, prefix: &str) -> Listing {
+    let contents = Regex::new(r"(?s)<Contents>(.*?)</Contents>").expect("valid regex");
+    let common = Regex::new(r"(?s)<CommonPrefixes>(.*?)</CommonPrefixes>").expect("valid regex");
+    let (key, size, sub) = (tag_regex("Key"), tag_regex("Size"), tag_regex("Prefix"));
+    let name = |full: &str| {
+        let full = xml_unescape(full);
+        let name = full.strip_prefix(prefix)?.trim_end_matches('/');
+        (!name.is_empty()).then(|| name.to_string())
+    };
+
+    let mut entries = Vec::new();
+    for caps in contents.captures_iter(xml) {
+        let Some(object) = capture(&key, &caps[1]).and_then(|k| name(&k)) else {
+            continue;
+        };
+        entries.push(Entry {
+            name: object,
+            meta: Metadata {
+                is_dir: false,
+                len: capture(&size, &caps[1])
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+            },
+        });
+    }
+    for caps in common.captures_iter(xml) {
+        if let Some(dir) = capture(&sub, &caps[1]).and_then(|p| name(&p)) {
+            entries.push(Entry {
+                name: dir,
+                meta: Metadata {
+                    is_dir: true,
+                    len: 0,
+                },
+            });
+        }
+    }
+    let truncated = capture(&tag_regex("IsTruncated"), xml).is_some_and(|t| t == "true");
+    Listing {
+        entries,
+        next: capture(&tag_regex("NextContinuationToken"), xml)
+            .filter(|_| truncated)
+            .map(|t| xml_unescape(&t)),
+    }
+}
+
+fn tag_regex(name: &str) -> Regex {
+    Regex::new(&format!("<{name}>([^<]*)</{name}>")).expect("valid regex")
+}
+
+fn capture(re: &Regex, text: &str) -> Option<String> {
+    re.captures(text).map(|c| c[1].to_string())
+}
+
+/// Percent-encode `s` as SigV4 wants: everything but unreserved
+/// characters, and `/` too unless it separates a path.
+pub fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The `x-amz-date` SigV4 dates a request with, `YYYYMMDDTHHMMSSZ` in
+/// UTC, for a Unix timestamp.
+pub fn amz_date(unix_secs: u64) -> String {
+    crate::models::utc_basic(unix_secs)
+}
+
+/// The `Authorization` header for an S3 request, by AWS Signature Version
+/// 4. `path` and `query` are already encoded, the query sorted by name;
+/// `headers` are the lower-case names and values to sign, sorted, and
+/// must include `host`, `x-amz-content-sha256` and `x-amz-date`
+/// (`amz_date`).
+pub fn authorization(
+    creds: &Credentials,
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+    amz_date: &str,
+) -> String {
+    let mut headers = headers.to_vec();
+    headers.sort_by_key(|(name, _)| *name);
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request =
+        format!("{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}");
+
+    let date = &amz_date[..8];
+    let scope = format!("{date}/{}/s3/aws4_request", creds.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex_sha256(canonical_request.as_bytes())
+    );
+    let key = hmac_sha256(format!("AWS4{}", creds.secret_key).as_bytes(), date);
+    let key = hmac_sha256(&key, &creds.region);
+    let key = hmac_sha256(&key, "s3");
+    let key = hmac_sha256(&key, "aws4_request");
+    let signature: String = hmac_sha256(&key, &string_to_sign)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, \
+         Signature={signature}",
+        creds.access_key
+    )
+}
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::bandcamp::BandcampPurchases;
+use crate::list::PurchaseKind;
+use crate::manifest::Manifest;
+use crate::models::{PurchaseList, Service};
+use crate::snapshot::Snapshot;
+
+/// A single search result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub service: Service,
+    pub artist: String,
+    pub album: String,
+    /// `None` for album-level hits (Bandcamp snapshots list albums, not tracks).
+    pub track: Option<String>,
+    /// Local path relative to the target directory, when synced.
+    pub local_path: Option<PathBuf>,
+}
+
+impl SearchHit {
+    pub fn is_synced(&self) -> bool {
+        self.local_path.is_some()
+    }
+}
+
+/// Case-insensitive substring search over remote snapshots and the local
+/// manifest. Pure function — no I/O.
+///
+/// Snapshot items are matched on artist, album, and track title, and
+/// annotated with their local path when the manifest has them. Manifest
+/// entries that no snapshot covers (e.g. no snapshot cached yet) are
+/// reported as local-only hits.
+pub fn search(query: &str, snapshots: &[Snapshot], manifest: &Manifest) -> Vec<SearchHit> {
+    let query = query.to_lowercase();
+    let matches = |fields: &[&str]| fields.iter().any(|f| f.to_lowercase().contains(&query));
+
+    let mut hits = Vec::new();
+    let mut seen_paths: HashSet<PathBuf> = HashSet::new();
+
+    for snapshot in snapshots {
+        for item in &snapshot.items {
+            let track = item.track.as_deref().unwrap_or("");
+            if !matches(&[&item.artist, &item.album, track]) {
+                continue;
+            }
+
+            let local_path = match item.track_id {
+                Some(id) => manifest.get(snapshot.service, id).map(|e| e.path.clone()),
+                None => manifest
+                    .entries_for(snapshot.service)
+                    .find(|e| e.artist == item.artist && e.album == item.album)
+                    .and_then(|e| e.path.parent().map(|p| p.to_path_buf())),
+            };
+            if let Some(ref p) = local_path {
+                seen_paths.insert(p.clone());
+            }
+
+            hits.push(SearchHit {
+                service: snapshot.service,
+                artist: item.artist.clone(),
+                album: item.album.clone(),
+                track: item.track.clone(),
+                local_path,
+            });
+        }
+    }
+
+    for service in [Service::Qobuz, Service::Bandcamp] {
+        for entry in manifest.entries_for(service) {
+            if !matches(&[&entry.artist, &entry.album, &entry.title]) {
+                continue;
+            }
+            let covered = seen_paths.contains(&entry.path)
+                || entry.path.parent().is_some_and(|p| seen_paths.contains(p));
+            if covered {
+                continue;
+            }
+            hits.push(SearchHit {
+                service,
+                artist: entry.artist.clone(),
+                album: entry.album.clone(),
+                track: Some(entry.title.clone()),
+                local_path: Some(entry.path.clone()),
+            });
+        }
+    }
+
+    hits
+}
+
+/// A release found by `qoget search QUERY`, in the Qobuz catalog or the
+/// Bandcamp collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogHit {
+    pub service: Service,
+    pub kind: PurchaseKind,
+    /// What to pass to `qoget get`: a Qobuz album ID or track link, or a
+    /// Bandcamp page link. `None` for Bandcamp items without a page.
+    pub get: Option<String>,
+    pub artist: String,
+    pub title: String,
+}
+
+impl CatalogHit {
+    /// Tab-separated: service, type, what to pass to `get` ("-" if
+    /// nothing), artist, title.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.service.to_string().to_lowercase(),
+            self.kind,
+            self.get.as_deref().unwrap_or("-"),
+            self.artist,
+            self.title
+        )
+    }
+}
+
+/// Hits from a Qobuz catalog search, albums then tracks, in the order
+/// Qobuz ranked them.
+pub fn qobuz_catalog_hits(results: &PurchaseList) -> Vec<CatalogHit> {
+    let albums = results.albums.iter().map(|album| CatalogHit {
+        service: Service::Qobuz,
+        kind: PurchaseKind::Album,
+        get: Some(album.id.0.clone()),
+        artist: album.artist.name.clone(),
+        title: match &album.version {
+            Some(version) if !version.is_empty() => format!("{} ({version})", album.title),
+            _ => album.title.clone(),
+        },
+    });
+    let tracks = results.tracks.iter().map(|track| CatalogHit {
+        service: Service::Qobuz,
+        kind: PurchaseKind::Track,
+        // A bare ID is taken for an album's.
+        get: Some(format!("https://open.qobuz.com/track/{}", track.id)),
+        artist: track.performer.name.clone(),
+        title: track.title.clone(),
+    });
+    albums.chain(tracks).collect()
+}
+
+/// Items in the Bandcamp collection whose artist or title contains `query`,
+/// ignoring case. Pure function, as Bandcamp has no collection search.
+pub fn bandcamp_collection_hits(purchases: &BandcampPurchases, query: &str) -> Vec<CatalogHit> {
+    let query = query.to_lowercase();
+    purchases
+        .items
+        .iter()
+        .filter(|item| {
+            item.band_name.to_lowercase().contains(&query)
+                || item.item_title.to_lowercase().contains(&query)
+        })
+        .map(|item| CatalogHit {
+            service: Service::Bandcamp,
+            kind: if item.is_track() == Some(true) {
+                PurchaseKind::Track
+            } else {
+                PurchaseKind::Album
+            },
+            get: item.item_url.clone(),
+            artist: item.band_name.clone(),
+            title: item.item_title.clone(),
+        })
+        .collect()
+}
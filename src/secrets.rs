@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+/// Config values starting with this are looked up in the OS credential store
+/// (Secret Service, macOS Keychain, Windows Credential Manager).
+pub const KEYRING_PREFIX: &str = "keyring:";
+
+/// Service name qoget's keyring entries are stored under.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "qoget";
+
+/// Secrets that can live in the keyring instead of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    QobuzPassword,
+    BandcampIdentity,
+}
+
+impl Secret {
+    /// Keyring entry used by a bare `keyring:` value.
+    pub fn default_entry(self) -> &'static str {
+        match self {
+            Secret::QobuzPassword => "qobuz-password",
+            Secret::BandcampIdentity => "bandcamp-identity",
+        }
+    }
+}
+
+impl FromStr for Secret {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "qobuz-password" => Ok(Secret::QobuzPassword),
+            "bandcamp-identity" => Ok(Secret::BandcampIdentity),
+            _ => Err(format!(
+                "unknown secret '{s}' (expected qobuz-password or bandcamp-identity)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.default_entry())
+    }
+}
+
+/// The keyring entry a config value refers to: `keyring:` means the
+/// secret's default entry, `keyring:NAME` the entry `NAME`. `None` for an
+/// ordinary value.
+pub fn keyring_entry(value: &str, secret: Secret) -> Option<&str> {
+    let name = value.strip_prefix(KEYRING_PREFIX)?.trim();
+    Some(if name.is_empty() {
+        secret.default_entry()
+    } else {
+        name
+    })
+}
+
+#[cfg(feature = "keyring")]
+fn entry(name: &str) -> Result<keyring::Entry> {
+    use anyhow::Context;
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .with_context(|| format!("opening keyring entry {KEYRING_SERVICE}/{name}"))
+}
+
+/// Read a secret stored by `qoget config set-secret`.
+#[cfg(feature = "keyring")]
+pub fn get(name: &str) -> Result<String> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(value),
+        Err(keyring::Error::NoEntry) => anyhow::bail!(
+            "no keyring entry {KEYRING_SERVICE}/{name}; store one with `qoget config set-secret`"
+        ),
+        Err(e) => Err(anyhow::Error::new(e)
+            .context(format!("reading keyring entry {KEYRING_SERVICE}/{name}"))),
+    }
+}
+
+#[cfg(feature = "keyring")]
+pub fn set(name: &str, value: &str) -> Result<()> {
+    use anyhow::Context;
+    entry(name)?
+        .set_password(value)
+        .with_context(|| format!("writing keyring entry {KEYRING_SERVICE}/{name}"))
+}
+
+/// Remove a stored secret. Returns false if there was none.
+#[cfg(feature = "keyring")]
+pub fn delete(name: &str) -> Result<bool> {
+    match entry(name)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(anyhow::Error::new(e)
+            .context(format!("deleting keyring entry {KEYRING_SERVICE}/{name}"))),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unsupported() -> anyhow::Error {
+    anyhow::anyhow!("this build of qoget has no keyring support (enable the `keyring` feature)")
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn get(_name: &str) -> Result<String> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn set(_name: &str, _value: &str) -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn delete(_name: &str) -> Result<bool> {
+    Err(unsupported())
+}
@@ -0,0 +1,74 @@
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{Result, bail};
+
+/// Positions (0-based) picked by a selection such as `1,3-5`, where the
+/// user counts from 1. `all` (or `*`) picks everything; an empty answer
+/// picks nothing.
+pub fn parse_selection(input: &str, count: usize) -> Result<BTreeSet<usize>> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("all") || input == "*" {
+        return Ok((0..count).collect());
+    }
+    let mut selected = BTreeSet::new();
+    for part in input.split([',', ' ']).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (parse_number(a, count)?, parse_number(b, count)?),
+            None => {
+                let n = parse_number(part, count)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            bail!("'{part}' is backwards; use {end}-{start}");
+        }
+        selected.extend(start - 1..end);
+    }
+    Ok(selected)
+}
+
+fn parse_number(s: &str, count: usize) -> Result<usize> {
+    match s.trim().parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Ok(n),
+        _ => bail!("'{}' is not a number from 1 to {count}", s.trim()),
+    }
+}
+
+/// Ask a yes/no `question` on stderr; anything but yes is no. Needs a
+/// terminal.
+pub fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("can't ask \"{question}\" without a terminal");
+    }
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// List `entries` on stderr, numbered from 1, and ask which to download.
+/// Asks again until the answer parses. Needs a terminal.
+pub fn choose(heading: &str, entries: &[String]) -> Result<BTreeSet<usize>> {
+    if !io::stdin().is_terminal() {
+        bail!("--interactive needs a terminal to ask what to download");
+    }
+    eprintln!("\n{heading}:");
+    let width = entries.len().to_string().len();
+    for (i, entry) in entries.iter().enumerate() {
+        eprintln!("  {:>width$}. {entry}", i + 1);
+    }
+    loop {
+        eprint!("Download which? (e.g. 1,3-5, \"all\", or Enter for none): ");
+        io::stderr().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(BTreeSet::new());
+        }
+        match parse_selection(&input, entries.len()) {
+            Ok(selected) => return Ok(selected),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
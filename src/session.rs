@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Credentials saved by `qoget login`, so syncs can authenticate without a
+/// password in the config file. Lives next to the config file and is only
+/// readable by its owner.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qobuz: Option<QobuzSession>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandcamp: Option<BandcampSession>,
+}
+
+/// A Qobuz user token and the app credentials it was issued for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QobuzSession {
+    pub app_id: String,
+    pub app_secret: String,
+    pub user_auth_token: String,
+    pub user_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandcampSession {
+    pub identity_cookie: String,
+    pub fan_id: u64,
+}
+
+impl SessionFile {
+    pub fn path(config_dir: &Path) -> PathBuf {
+        config_dir.join("session.json")
+    }
+
+    /// Load saved sessions; a missing file means none have been saved.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = Self::path(config_dir);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading {}", path.display()));
+            }
+        };
+        serde_json::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Atomic write: temp file + rename. The file is created owner-only
+    /// before any secret is written to it.
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        let path = Self::path(config_dir);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::create_dir_all(config_dir)
+            .with_context(|| format!("creating {}", config_dir.display()))?;
+        let json = serde_json::to_string_pretty(self)?;
+        write_private(&tmp, json.as_bytes())
+            .with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+}
+
+/// Create `path` readable and writable only by its owner (on Unix) and
+/// write `contents` to it, replacing any existing file.
+#[cfg(unix)]
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let _ = std::fs::remove_file(path);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::bandcamp::BandcampPurchases;
+use crate::models::{PurchaseList, Service, TrackId};
+
+/// Cached copy of a service's purchase list, written after each sync so
+/// offline commands (e.g. `search`) can see what is available remotely.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub service: Service,
+    /// Unix timestamp of when the purchase list was fetched.
+    pub fetched_at: u64,
+    pub items: Vec<SnapshotItem>,
+}
+
+/// One purchased track, or one album when the service does not list tracks
+/// up front (Bandcamp).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotItem {
+    pub artist: String,
+    pub album: String,
+    #[serde(default)]
+    pub track: Option<String>,
+    #[serde(default)]
+    pub track_id: Option<TrackId>,
+}
+
+impl Snapshot {
+    pub fn from_qobuz(purchases: &PurchaseList) -> Self {
+        let mut items = Vec::new();
+        for album in &purchases.albums {
+            let Some(ref tracks) = album.tracks else {
+                continue;
+            };
+            for track in &tracks.items {
+                items.push(SnapshotItem {
+                    artist: album.artist.name.clone(),
+                    album: album.title.clone(),
+                    track: Some(track.title.clone()),
+                    track_id: Some(track.id),
+                });
+            }
+        }
+        for track in &purchases.tracks {
+            items.push(SnapshotItem {
+                artist: track.performer.name.clone(),
+                album: track.title.clone(),
+                track: Some(track.title.clone()),
+                track_id: Some(track.id),
+            });
+        }
+        Self::new(Service::Qobuz, items)
+    }
+
+    pub fn from_bandcamp(purchases: &BandcampPurchases) -> Self {
+        let items = purchases
+            .items
+            .iter()
+            .map(|item| SnapshotItem {
+                artist: item.band_name.clone(),
+                album: item.item_title.clone(),
+                track: None,
+                track_id: None,
+            })
+            .collect();
+        Self::new(Service::Bandcamp, items)
+    }
+
+    fn new(service: Service, items: Vec<SnapshotItem>) -> Self {
+        let fetched_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            service,
+            fetched_at,
+            items,
+        }
+    }
+
+    pub fn path(cache_dir: &Path, service: Service) -> PathBuf {
+        cache_dir
+            .join("snapshots")
+            .join(format!("{}.json", service.to_string().to_lowercase()))
+    }
+
+    /// Load the cached snapshot for a service, if one exists.
+    pub fn load(cache_dir: &Path, service: Service) -> Result<Option<Self>> {
+        let path = Self::path(cache_dir, service);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading {}", path.display()));
+            }
+        };
+        let snapshot = serde_json::from_str(&contents)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Some(snapshot))
+    }
+
+    /// Atomic write: temp file + rename.
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        let path = Self::path(cache_dir, self.service);
+        let tmp = path.with_extension("json.tmp");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        let json = serde_json::to_string(self)?;
+        std::fs::write(&tmp, &json).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+}
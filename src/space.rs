@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use tracing::{debug, warn};
+
+/// Room to leave free beyond the estimate, since sizes are approximate and
+/// the manifest, tags, and cover art need some too.
+pub const HEADROOM: u64 = 100 * 1024 * 1024;
+
+/// Bytes an ordinary user can still write on the filesystem holding `path`,
+/// or `None` where that can't be found out. `path` need not exist yet; its
+/// nearest existing ancestor is asked instead.
+pub fn available(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    free_bytes(existing)
+}
+
+#[cfg(unix)]
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stat` is only read after
+    // statvfs reports that it filled it in.
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)] // the field types vary by platform
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_bytes(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Fail if `needed` bytes (plus `HEADROOM`) won't fit in `target_dir`, so a
+/// full disk is one clear error up front rather than a write error per
+/// file. With `ignore`, only warn. `what` names the downloads, e.g. "42
+/// Qobuz tracks".
+pub fn check(what: &str, needed: u64, target_dir: &Path, ignore: bool) -> Result<()> {
+    let Some(free) = available(target_dir) else {
+        debug!(
+            "free space on {} unknown; not checked",
+            target_dir.display()
+        );
+        return Ok(());
+    };
+    debug!(needed, free, "space check for {what}");
+    if needed.saturating_add(HEADROOM) <= free {
+        return Ok(());
+    }
+    let msg = format!(
+        "{what}: about {} needed, but only {} is free in {}",
+        human(needed),
+        human(free),
+        target_dir.display()
+    );
+    if ignore {
+        warn!("{msg}; continuing under --ignore-space");
+        return Ok(());
+    }
+    bail!("{msg}. Free up space, or pass --ignore-space to try anyway")
+}
+
+/// Parse a size like `25GB`, `500MB`, `1.5 TB`, or a plain number of
+/// bytes. Units are binary (1 GB = 1024 MB), matching `human`.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => bail!("unknown size unit in '{s}'; use MB, GB, or TB"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("'{s}' is not a size, e.g. 25GB"))?;
+    Ok((number * scale as f64) as u64)
+}
+
+/// `812 MB`, `3.4 GB`.
+pub fn human(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    let mb = bytes as f64 / MB;
+    if mb < 1024.0 {
+        format!("{mb:.0} MB")
+    } else {
+        format!("{:.1} GB", mb / 1024.0)
+    }
+}
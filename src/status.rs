@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::manifest::Manifest;
+use crate::models::{AlbumId, AudioFormat, PurchaseList, Service};
+use crate::path::{Naming, album_dir, edition};
+use crate::sync::collect_tasks;
+use crate::transcode::{TranscodeTarget, planned_format};
+
+/// How much of a purchased album is on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    Complete,
+    /// Some tracks present, some missing.
+    Partial,
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumStatus {
+    pub service: Service,
+    pub artist: String,
+    pub title: String,
+    /// Planned track count; `None` when the store doesn't list tracks up
+    /// front (Bandcamp albums), in which case any file in the album
+    /// directory counts as complete.
+    pub expected: Option<usize>,
+    pub present: usize,
+    pub state: SyncState,
+}
+
+/// Result of comparing a target directory against purchase lists.
+#[derive(Debug, Default)]
+pub struct LibraryStatus {
+    pub albums: Vec<AlbumStatus>,
+    /// Audio files (relative to the target) that no purchase accounts for.
+    pub unmatched: Vec<PathBuf>,
+}
+
+impl LibraryStatus {
+    pub fn count(&self, state: SyncState) -> usize {
+        self.albums.iter().filter(|a| a.state == state).count()
+    }
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    AudioFormat::ALL
+        .iter()
+        .any(|f| f.extension()[1..].eq_ignore_ascii_case(ext))
+}
+
+/// Recursively list audio files under `root`, as paths relative to it.
+/// Hidden files and directories (e.g. the manifest) are skipped.
+pub fn scan_library(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let dir = root.join(&rel);
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("reading {}", dir.display()))?;
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let child = rel.join(&name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                stack.push(child);
+            } else if file_type.is_file() && is_audio_file(&child) {
+                files.push(child);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Match local files against each service's purchases. Pure function — no I/O.
+///
+/// A track counts as present at its planned path, or at the path the
+/// manifest recorded for it (format fallback). Albums without a track
+/// listing are matched by directory.
+pub fn match_library(
+    purchases: &[(Service, &PurchaseList)],
+    local_files: &[PathBuf],
+    manifest: &Manifest,
+    naming: Naming,
+    transcode: Option<TranscodeTarget>,
+) -> LibraryStatus {
+    let local: HashSet<&Path> = local_files.iter().map(PathBuf::as_path).collect();
+    let mut claimed: HashSet<&Path> = HashSet::new();
+    let mut status = LibraryStatus::default();
+
+    for &(service, list) in purchases {
+        let ext = planned_format(service.default_format(), transcode).extension();
+        let tasks = collect_tasks(list, Path::new(""), ext, naming);
+
+        // Tasks arrive grouped by album; dedup standalone tracks that are
+        // also part of a purchased album. Editions kept apart by name each
+        // have their own copy.
+        let mut seen_tracks = HashSet::new();
+        let mut in_albums = HashSet::new();
+        let mut current: Option<(AlbumId, usize)> = None;
+        for task in &tasks {
+            let standalone = task.album.tracks_count <= 1;
+            if standalone && in_albums.contains(&task.track.id)
+                || !seen_tracks.insert((task.track.id, edition(&task.album, naming)))
+            {
+                continue;
+            }
+            if !standalone {
+                in_albums.insert(task.track.id);
+            }
+            let recorded = manifest
+                .get(service, task.track.id)
+                .map(|e| e.path.as_path());
+            let found = [Some(task.target_path.as_path()), recorded]
+                .into_iter()
+                .flatten()
+                .find(|p| local.contains(p));
+            if let Some(path) = found.and_then(|p| local.get(p).copied()) {
+                claimed.insert(path);
+            }
+
+            let idx = match current {
+                Some((ref id, idx)) if *id == task.album.id => idx,
+                _ => {
+                    status.albums.push(AlbumStatus {
+                        service,
+                        artist: task.album.artist.name.clone(),
+                        title: task.album.title.clone(),
+                        expected: Some(0),
+                        present: 0,
+                        state: SyncState::Missing,
+                    });
+                    let idx = status.albums.len() - 1;
+                    current = Some((task.album.id.clone(), idx));
+                    idx
+                }
+            };
+            let album = &mut status.albums[idx];
+            album.expected = album.expected.map(|n| n + 1);
+            album.present += usize::from(found.is_some());
+        }
+
+        for album in list.albums.iter().filter(|a| a.tracks.is_none()) {
+            let dir = album_dir(Path::new(""), album, naming);
+            let files: Vec<&Path> = local_files
+                .iter()
+                .map(PathBuf::as_path)
+                .filter(|p| p.starts_with(&dir))
+                .collect();
+            status.albums.push(AlbumStatus {
+                service,
+                artist: album.artist.name.clone(),
+                title: album.title.clone(),
+                expected: None,
+                present: files.len(),
+                state: SyncState::Missing,
+            });
+            claimed.extend(files);
+        }
+    }
+
+    for album in &mut status.albums {
+        album.state = match (album.present, album.expected) {
+            (0, _) => SyncState::Missing,
+            (present, Some(expected)) if present < expected => SyncState::Partial,
+            _ => SyncState::Complete,
+        };
+    }
+
+    status.unmatched = local_files
+        .iter()
+        .filter(|p| !claimed.contains(p.as_path()))
+        .cloned()
+        .collect();
+    status
+}
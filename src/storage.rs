@@ -0,0 +1,949 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, bail};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use regex::Regex;
+use reqwest::{Method, StatusCode, Url};
+use tokio::io::AsyncReadExt;
+use tracing::debug;
+
+use crate::config::{self, NetworkConfig};
+use crate::network;
+use crate::path::{Naming, sanitize_component};
+use crate::s3;
+
+/// Environment variable a WebDAV target's password can come from, rather
+/// than the URL.
+pub const WEBDAV_PASSWORD_VAR: &str = "QOGET_WEBDAV_PASSWORD";
+
+/// Most directories a remote target lists in one go when planning.
+const LIST_BATCH: usize = 500;
+
+/// Size and kind of a stored file or directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub len: u64,
+}
+
+/// An entry in a stored directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub meta: Metadata,
+}
+
+/// Where synced files end up. Paths are those under the target directory;
+/// a remote target has a local working directory stand in for its root,
+/// which also keeps the manifest and the downloads in progress.
+///
+/// Lookups only fail when the storage can't be asked at all, such as a
+/// remote target that can't be reached; a sync can't be planned then.
+pub trait Storage: Send + Sync {
+    /// Size and kind of `path`, or `None` if nothing is there.
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Option<Metadata>>>;
+
+    /// Names in `dir`; none if it doesn't exist.
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<OsString>>>;
+
+    /// Look up `dirs` ahead of the `metadata` and `list` calls planning
+    /// makes in them, in as few requests as the storage can. Nothing is
+    /// fetched ahead by default.
+    fn prefetch<'a>(&'a self, dirs: &'a [PathBuf]) -> BoxFuture<'a, Result<()>> {
+        let _ = dirs;
+        async { Ok(()) }.boxed()
+    }
+
+    /// Move the finished local file `from` to `to`, creating the
+    /// directories it goes in.
+    fn put<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    /// Remove the file at `path`.
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    /// Move a staged album into the library. `files` pairs each staged file
+    /// under `staged_dir` with its place under `album_dir`. By default the
+    /// files are put one by one, and those already put are removed if one
+    /// fails.
+    fn place_album<'a>(
+        &'a self,
+        staged_dir: &'a Path,
+        album_dir: &'a Path,
+        files: &'a [(PathBuf, PathBuf)],
+    ) -> BoxFuture<'a, Result<()>> {
+        let _ = (staged_dir, album_dir);
+        put_each(self, files).boxed()
+    }
+
+    /// Whether the files are on this machine, so that steps which read
+    /// them back (tagging, ReplayGain, checksums) can run.
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// Put `files` one by one, removing those already put if one fails.
+async fn put_each<S: Storage + ?Sized>(storage: &S, files: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut placed = Vec::new();
+    for (staged, target) in files {
+        if let Err(e) = storage.put(staged, target).await {
+            for target in placed.into_iter().rev() {
+                let _ = storage.remove(target).await;
+            }
+            return Err(e);
+        }
+        placed.push(target);
+    }
+    Ok(())
+}
+
+/// The target directory itself. Anything that can't be read counts as not
+/// there, so its lookups never fail.
+pub struct Local;
+
+impl Storage for Local {
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Option<Metadata>>> {
+        async move {
+            Ok(tokio::fs::metadata(path).await.ok().map(|meta| Metadata {
+                is_dir: meta.is_dir(),
+                len: meta.len(),
+            }))
+        }
+        .boxed()
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<OsString>>> {
+        async move {
+            let read_from = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            let mut names = Vec::new();
+            if let Ok(mut rd) = tokio::fs::read_dir(read_from).await {
+                while let Ok(Some(entry)) = rd.next_entry().await {
+                    names.push(entry.file_name());
+                }
+            }
+            Ok(names)
+        }
+        .boxed()
+    }
+
+    fn put<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        async move {
+            if from == to {
+                return Ok(());
+            }
+            if let Some(parent) = to.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(from, to)
+                .await
+                .with_context(|| format!("renaming {} -> {}", from.display(), to.display()))
+        }
+        .boxed()
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        async move {
+            tokio::fs::remove_file(path)
+                .await
+                .with_context(|| format!("removing {}", path.display()))
+        }
+        .boxed()
+    }
+
+    fn place_album<'a>(
+        &'a self,
+        staged_dir: &'a Path,
+        album_dir: &'a Path,
+        files: &'a [(PathBuf, PathBuf)],
+    ) -> BoxFuture<'a, Result<()>> {
+        crate::download::place_staged(staged_dir, album_dir, files).boxed()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// The storage a sync goes through: [`Local`] unless it is set to a
+/// remote target's.
+#[derive(Clone)]
+pub struct Shared(Arc<dyn Storage>);
+
+impl Shared {
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self(storage)
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self(Arc::new(Local))
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.0.is_local() { "local" } else { "remote" };
+        f.debug_tuple("Shared").field(&kind).finish()
+    }
+}
+
+impl std::ops::Deref for Shared {
+    type Target = dyn Storage;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// Whether `target` names a remote target (`sftp://`, `webdav://`,
+/// `webdavs://` or `s3://`) rather than a directory.
+pub fn is_remote(target: &Path) -> bool {
+    target.to_str().is_some_and(|s| {
+        ["sftp://", "webdav://", "webdavs://", "s3://"]
+            .iter()
+            .any(|p| s.starts_with(p))
+    })
+}
+
+/// A target on a NAS or server reached over SFTP or WebDAV, or in an S3
+/// bucket, given as a URL: `sftp://user@nas/music`,
+/// `webdavs://user@nas/dav/music` (plain `webdav://` for HTTP), or
+/// `s3://bucket/music`.
+///
+/// SFTP goes through the system `sftp` command, so logging in works as it
+/// does for `ssh` (keys, agent, `~/.ssh/config`), without prompting. A
+/// WebDAV password can be part of the URL or come from
+/// `QOGET_WEBDAV_PASSWORD`. S3 is described at [`s3::Bucket`].
+///
+/// Directory listings are cached for the run, and planning fetches those it
+/// needs up front: for SFTP, many in one session. A listing that fails
+/// fails the lookup, rather than looking like an empty directory.
+pub struct Remote {
+    /// The URL, without any password.
+    url: String,
+    root: PathBuf,
+    backend: Backend,
+    /// Listings fetched so far, by local directory; `None` when it doesn't
+    /// exist remotely.
+    listings: Mutex<HashMap<PathBuf, Option<Vec<Entry>>>>,
+}
+
+enum Backend {
+    Sftp(Sftp),
+    WebDav(WebDav),
+    S3(s3::Bucket),
+}
+
+impl Remote {
+    /// The remote target `target` names, if it is one, connecting to it as
+    /// `network` says.
+    pub fn parse(target: &Path, network: &NetworkConfig) -> Result<Option<Self>> {
+        if !is_remote(target) {
+            return Ok(None);
+        }
+        let raw = target.to_string_lossy();
+        let mut url = Url::parse(&raw).with_context(|| format!("invalid target URL {raw}"))?;
+        let host = url
+            .host_str()
+            .with_context(|| format!("no host in target URL {raw}"))?
+            .to_string();
+        let user = Some(url.username())
+            .filter(|u| !u.is_empty())
+            .map(percent_decode);
+        let password = url.password().map(percent_decode);
+        let _ = url.set_password(None);
+        let backend = match url.scheme() {
+            "sftp" => Backend::Sftp(Sftp {
+                destination: match user {
+                    Some(user) => format!("{user}@{host}"),
+                    None => host,
+                },
+                port: url.port(),
+                path: percent_decode(url.path()),
+            }),
+            "s3" => Backend::S3(s3::Bucket::new(
+                &host,
+                &percent_decode(url.path()),
+                s3::Credentials::from_env()?,
+                network,
+            )?),
+            scheme => {
+                let http = if scheme == "webdavs" { "https" } else { "http" };
+                let mut base = Url::parse(&format!("{http}{}", &url.as_str()[scheme.len()..]))?;
+                let _ = base.set_username("");
+                if !base.path().ends_with('/') {
+                    base.set_path(&format!("{}/", base.path()));
+                }
+                Backend::WebDav(WebDav {
+                    http: network::client(network)?,
+                    base,
+                    user,
+                    password: password.or_else(|| std::env::var(WEBDAV_PASSWORD_VAR).ok()),
+                    created: Mutex::new(HashSet::new()),
+                })
+            }
+        };
+        let url = url.to_string();
+        Ok(Some(Self {
+            root: work_dir(&url),
+            url,
+            backend,
+            listings: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// The local working directory that stands in for the target: the
+    /// manifest and the downloads in progress are kept there.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// `path`, under the working directory, as a path from the remote
+    /// root with `/` separators.
+    fn remote_path(&self, path: &Path) -> Result<String> {
+        let rel = path
+            .strip_prefix(&self.root)
+            .with_context(|| format!("{} is not under {}", path.display(), self.root.display()))?;
+        let parts: Vec<String> = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        Ok(parts.join("/"))
+    }
+
+    /// What is in `dir`, or `None` if it doesn't exist remotely.
+    async fn entries(&self, dir: &Path) -> Result<Option<Vec<Entry>>> {
+        if let Some(cached) = self.listings.lock().unwrap().get(dir) {
+            return Ok(cached.clone());
+        }
+        let rel = self.remote_path(dir)?;
+        let listed = self
+            .backend
+            .list(&rel)
+            .await
+            .with_context(|| format!("listing {rel} on {}", self.url))?;
+        self.listings
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), listed.clone());
+        Ok(listed)
+    }
+
+    /// Forget the listings `path` would change.
+    fn changed(&self, path: &Path) {
+        self.listings
+            .lock()
+            .unwrap()
+            .retain(|dir, _| !path.starts_with(dir));
+    }
+
+    /// Once `from` is uploaded to `to`: forget what it changed and remove
+    /// it from the working directory, which only holds files on their way
+    /// out.
+    async fn uploaded(&self, from: &Path, to: &Path) -> Result<()> {
+        self.changed(to);
+        tokio::fs::remove_file(from).await?;
+        let mut dir = from.parent();
+        while let Some(d) = dir.filter(|d| d.starts_with(&self.root) && *d != self.root) {
+            if std::fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+        Ok(())
+    }
+}
+
+impl Storage for Remote {
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Option<Metadata>>> {
+        async move {
+            if path == self.root {
+                return Ok(Some(Metadata {
+                    is_dir: true,
+                    len: 0,
+                }));
+            }
+            let (Some(name), Some(parent)) = (path.file_name(), path.parent()) else {
+                return Ok(None);
+            };
+            let name = name.to_string_lossy();
+            let entries = self.entries(parent).await?.unwrap_or_default();
+            Ok(entries.into_iter().find(|e| e.name == name).map(|e| e.meta))
+        }
+        .boxed()
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<OsString>>> {
+        async move {
+            Ok(self
+                .entries(dir)
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|e| OsString::from(e.name))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn prefetch<'a>(&'a self, dirs: &'a [PathBuf]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let wanted: Vec<PathBuf> = {
+                let cached = self.listings.lock().unwrap();
+                let mut seen = HashSet::new();
+                dirs.iter()
+                    .filter(|dir| dir.starts_with(&self.root))
+                    .filter(|dir| !cached.contains_key(*dir) && seen.insert(*dir))
+                    .cloned()
+                    .collect()
+            };
+            for chunk in wanted.chunks(LIST_BATCH) {
+                let rels = chunk
+                    .iter()
+                    .map(|dir| self.remote_path(dir))
+                    .collect::<Result<Vec<_>>>()?;
+                let listed = self
+                    .backend
+                    .list_many(&rels)
+                    .await
+                    .with_context(|| format!("listing directories on {}", self.url))?;
+                let mut cached = self.listings.lock().unwrap();
+                for (dir, listed) in chunk.iter().zip(listed) {
+                    cached.insert(dir.clone(), listed);
+                }
+            }
+            debug!("listed {} directories on {}", wanted.len(), self.url);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn put<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let rel = self.remote_path(to)?;
+            self.backend
+                .upload(from, &rel)
+                .await
+                .with_context(|| format!("uploading {rel} to {}", self.url))?;
+            debug!("uploaded {rel}");
+            self.uploaded(from, to).await
+        }
+        .boxed()
+    }
+
+    /// Over SFTP, the whole album goes up in one session.
+    fn place_album<'a>(
+        &'a self,
+        staged_dir: &'a Path,
+        album_dir: &'a Path,
+        files: &'a [(PathBuf, PathBuf)],
+    ) -> BoxFuture<'a, Result<()>> {
+        let _ = staged_dir;
+        async move {
+            let Backend::Sftp(sftp) = &self.backend else {
+                return put_each(self, files).await;
+            };
+            let mut uploads = Vec::with_capacity(files.len());
+            for (staged, target) in files {
+                uploads.push((staged.as_path(), self.remote_path(target)?));
+            }
+            if let Err(e) = sftp.upload_all(&uploads).await {
+                let rels: Vec<String> = uploads.into_iter().map(|(_, rel)| rel).collect();
+                let _ = sftp.delete_all(&rels).await;
+                self.changed(album_dir);
+                let dir = self.remote_path(album_dir)?;
+                return Err(e).with_context(|| format!("uploading {dir} to {}", self.url));
+            }
+            debug!("uploaded {} files", files.len());
+            for (staged, target) in files {
+                self.uploaded(staged, target).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let rel = self.remote_path(path)?;
+            self.changed(path);
+            self.backend
+                .delete(&rel)
+                .await
+                .with_context(|| format!("removing {rel} from {}", self.url))
+        }
+        .boxed()
+    }
+}
+
+/// The directory a target's state is kept in on this machine: the target
+/// itself, or a remote target's working directory.
+pub fn local_dir(target: &Path, network: &NetworkConfig) -> Result<PathBuf> {
+    Ok(match Remote::parse(target, network)? {
+        Some(remote) => remote.root,
+        None => target.to_path_buf(),
+    })
+}
+
+/// Where a remote target's manifest and downloads in progress are kept:
+/// under the state directory, named after the URL by this platform's rules.
+pub fn work_dir(url: &str) -> PathBuf {
+    let name = url.split_once("://").map_or(url, |(_, rest)| rest);
+    config::state_dir().join("remote").join(sanitize_component(
+        name.trim_end_matches('/'),
+        Naming::default(),
+    ))
+}
+
+impl Backend {
+    async fn list(&self, rel: &str) -> Result<Option<Vec<Entry>>> {
+        match self {
+            Backend::Sftp(sftp) => sftp.list(rel).await,
+            Backend::WebDav(dav) => dav.list(rel).await,
+            // A prefix exists only while objects are under it.
+            Backend::S3(bucket) => Ok(Some(bucket.list(rel).await?).filter(|e| !e.is_empty())),
+        }
+    }
+
+    /// `list` for each of `rels`: in one session over SFTP, one request at
+    /// a time otherwise.
+    async fn list_many(&self, rels: &[String]) -> Result<Vec<Option<Vec<Entry>>>> {
+        if let Backend::Sftp(sftp) = self {
+            return sftp.list_many(rels).await;
+        }
+        let mut listed = Vec::with_capacity(rels.len());
+        for rel in rels {
+            listed.push(self.list(rel).await?);
+        }
+        Ok(listed)
+    }
+
+    async fn upload(&self, from: &Path, rel: &str) -> Result<()> {
+        match self {
+            Backend::Sftp(sftp) => sftp.upload(from, rel).await,
+            Backend::WebDav(dav) => dav.upload(from, rel).await,
+            Backend::S3(bucket) => bucket.upload(from, rel).await,
+        }
+    }
+
+    async fn delete(&self, rel: &str) -> Result<()> {
+        match self {
+            Backend::Sftp(sftp) => sftp.delete(rel).await,
+            Backend::WebDav(dav) => dav.delete(rel).await,
+            Backend::S3(bucket) => bucket.delete(rel).await,
+        }
+    }
+}
+
+// --- SFTP ---
+
+struct Sftp {
+    /// `user@host`, or the host (or `~/.ssh/config` alias) alone.
+    destination: String,
+    port: Option<u16>,
+    /// Remote directory of the target.
+    path: String,
+}
+
+impl Sftp {
+    fn remote(&self, rel: &str) -> String {
+        match (self.path.trim_end_matches('/'), rel) {
+            ("", "") => ".".to_string(),
+            (root, "") => root.to_string(),
+            ("", rel) => rel.to_string(),
+            (root, rel) => format!("{root}/{rel}"),
+        }
+    }
+
+    /// Run `commands` in one `sftp` batch session, off the async runtime.
+    /// A command starting with `-` may fail without failing the batch.
+    async fn batch(&self, commands: &str) -> Result<String> {
+        let mut cmd = Command::new("sftp");
+        cmd.args(["-q", "-b", "-"]);
+        if let Some(port) = self.port {
+            cmd.arg("-P").arg(port.to_string());
+        }
+        cmd.arg(&self.destination)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let commands = commands.to_string();
+        let out = tokio::task::spawn_blocking(move || {
+            let mut child = cmd.spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(commands.as_bytes())?;
+            }
+            child.wait_with_output()
+        })
+        .await?
+        .context("sftp could not be run (is OpenSSH installed?)")?;
+        if !out.status.success() {
+            bail!(
+                "sftp {} failed: {}",
+                self.destination,
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    async fn list(&self, rel: &str) -> Result<Option<Vec<Entry>>> {
+        let mut listed = self.list_many(&[rel.to_string()]).await?;
+        Ok(listed.pop().flatten())
+    }
+
+    /// List each of `rels` in one session. A missing directory lists as
+    /// empty, which is all callers need; the session failing fails them
+    /// all.
+    async fn list_many(&self, rels: &[String]) -> Result<Vec<Option<Vec<Entry>>>> {
+        let commands: String = rels
+            .iter()
+            .map(|rel| format!("-ls -la {}\n", sftp_quote(&self.remote(rel))))
+            .collect();
+        let out = self.batch(&commands).await?;
+        let listings = split_sftp_output(&out);
+        if listings.len() != rels.len() {
+            bail!(
+                "sftp {} answered {} of {} listings",
+                self.destination,
+                listings.len(),
+                rels.len()
+            );
+        }
+        Ok(listings
+            .into_iter()
+            .map(|listing| Some(parse_sftp_listing(listing)))
+            .collect())
+    }
+
+    /// Put each `(from, rel)` of `files` in one session, each under a
+    /// temporary name first so a file at its `rel` is always whole.
+    async fn upload_all(&self, files: &[(&Path, String)]) -> Result<()> {
+        let mut commands = String::new();
+        let mut made = HashSet::new();
+        for (from, rel) in files {
+            let parts: Vec<&str> = rel.split('/').collect();
+            for i in 1..parts.len() {
+                let dir = self.remote(&parts[..i].join("/"));
+                if made.insert(dir.clone()) {
+                    commands.push_str(&format!("-mkdir {}\n", sftp_quote(&dir)));
+                }
+            }
+            let dest = self.remote(rel);
+            let tmp = format!("{dest}.tmp");
+            commands.push_str(&format!(
+                "put {} {}\nrename {} {}\n",
+                sftp_quote(&from.to_string_lossy()),
+                sftp_quote(&tmp),
+                sftp_quote(&tmp),
+                sftp_quote(&dest)
+            ));
+        }
+        self.batch(&commands).await.map(drop)
+    }
+
+    async fn upload(&self, from: &Path, rel: &str) -> Result<()> {
+        self.upload_all(&[(from, rel.to_string())]).await
+    }
+
+    async fn delete(&self, rel: &str) -> Result<()> {
+        let path = sftp_quote(&self.remote(rel));
+        self.batch(&format!("rm {path}\n")).await.map(drop)
+    }
+
+    /// Remove whichever of `rels` exist, in one session.
+    async fn delete_all(&self, rels: &[String]) -> Result<()> {
+        let commands: String = rels
+            .iter()
+            .map(|rel| format!("-rm {}\n", sftp_quote(&self.remote(rel))))
+            .collect();
+        self.batch(&commands).await.map(drop)
+    }
+}
+
+/// Quote an argument for an `sftp` batch file.
+fn sftp_quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The output of each command in an `sftp` batch, split at the command
+/// lines it echoes.
+pub fn split_sftp_output(out: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = Vec::new();
+    let mut offset = 0;
+    for line in out.split_inclusive('\n') {
+        if line.starts_with("sftp>") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| &out[start..starts.get(i + 1).copied().unwrap_or(out.len())])
+        .collect()
+}
+
+/// Entries of an `sftp` `ls -la` listing: lines like
+/// `-rw-r--r--  1 jo users 8123456 Jan 1 12:00 01 - Track.m4a`. The
+/// echoed commands, `.` and `..` are left out.
+pub fn parse_sftp_listing(out: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for line in out.lines() {
+        if line.starts_with("sftp>") {
+            continue;
+        }
+        let mut rest = line;
+        let mut fields = Vec::new();
+        for _ in 0..8 {
+            let trimmed = rest.trim_start();
+            let Some(end) = trimmed.find(char::is_whitespace) else {
+                break;
+            };
+            fields.push(&trimmed[..end]);
+            rest = &trimmed[end..];
+        }
+        let Some(name) = rest.strip_prefix(' ') else {
+            continue;
+        };
+        let (Some(mode), Some(len)) = (fields.first(), fields.get(4)) else {
+            continue;
+        };
+        let Ok(len) = len.parse() else {
+            continue;
+        };
+        if fields.len() < 8 || name == "." || name == ".." {
+            continue;
+        }
+        entries.push(Entry {
+            name: name.to_string(),
+            meta: Metadata {
+                is_dir: mode.starts_with('d'),
+                len,
+            },
+        });
+    }
+    entries
+}
+
+// --- WebDAV ---
+
+struct WebDav {
+    http: reqwest::Client,
+    /// The target's URL over HTTP(S), ending in `/`.
+    base: Url,
+    user: Option<String>,
+    password: Option<String>,
+    /// Collections made or found this run.
+    created: Mutex<HashSet<String>>,
+}
+
+impl WebDav {
+    fn url(&self, rel: &str, dir: bool) -> Result<Url> {
+        let mut url = self.base.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|()| anyhow::anyhow!("{} can't have a path", self.base))?;
+            segments.pop_if_empty();
+            if !rel.is_empty() {
+                segments.extend(rel.split('/'));
+            }
+            if dir {
+                segments.push("");
+            }
+        }
+        Ok(url)
+    }
+
+    fn request(&self, method: &str, url: Url) -> reqwest::RequestBuilder {
+        let method = Method::from_bytes(method.as_bytes()).expect("valid method");
+        let req = self.http.request(method, url);
+        match &self.user {
+            Some(user) => req.basic_auth(user, self.password.as_deref()),
+            None => req,
+        }
+    }
+
+    async fn list(&self, rel: &str) -> Result<Option<Vec<Entry>>> {
+        let url = self.url(rel, true)?;
+        let resp = self
+            .request("PROPFIND", url.clone())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+        match resp.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            StatusCode::MULTI_STATUS => {}
+            status => bail!("PROPFIND {url} returned HTTP {status}"),
+        }
+        let body = resp.text().await?;
+        Ok(Some(parse_propfind(&body, &percent_decode(url.path()))))
+    }
+
+    async fn mkcol(&self, rel: &str) -> Result<()> {
+        if self.created.lock().unwrap().contains(rel) {
+            return Ok(());
+        }
+        let url = self.url(rel, true)?;
+        let status = self.request("MKCOL", url.clone()).send().await?.status();
+        // 405: it exists already.
+        if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+            bail!("MKCOL {url} returned HTTP {status}");
+        }
+        self.created.lock().unwrap().insert(rel.to_string());
+        Ok(())
+    }
+
+    /// PUT the file under a temporary name and MOVE it into place, so a
+    /// file at `rel` is always whole.
+    async fn upload(&self, from: &Path, rel: &str) -> Result<()> {
+        let parts: Vec<&str> = rel.split('/').collect();
+        for i in 1..parts.len() {
+            self.mkcol(&parts[..i].join("/")).await?;
+        }
+        let tmp = self.url(&format!("{rel}.tmp"), false)?;
+        let dest = self.url(rel, false)?;
+
+        let file = tokio::fs::File::open(from)
+            .await
+            .with_context(|| format!("opening {}", from.display()))?;
+        let len = file.metadata().await?.len();
+        let chunks = futures::stream::try_unfold(file, |mut file| async move {
+            let mut buf = vec![0; 64 * 1024];
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                return Ok::<_, std::io::Error>(None);
+            }
+            buf.truncate(n);
+            Ok(Some((buf, file)))
+        });
+        let status = self
+            .request("PUT", tmp.clone())
+            .header(reqwest::header::CONTENT_LENGTH, len)
+            .body(reqwest::Body::wrap_stream(chunks))
+            .send()
+            .await?
+            .status();
+        if !status.is_success() {
+            bail!("PUT {tmp} returned HTTP {status}");
+        }
+        let status = self
+            .request("MOVE", tmp.clone())
+            .header("Destination", dest.as_str())
+            .header("Overwrite", "T")
+            .send()
+            .await?
+            .status();
+        if !status.is_success() {
+            bail!("MOVE {tmp} returned HTTP {status}");
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, rel: &str) -> Result<()> {
+        let url = self.url(rel, false)?;
+        let status = self.request("DELETE", url.clone()).send().await?.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            bail!("DELETE {url} returned HTTP {status}");
+        }
+        Ok(())
+    }
+}
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<propfind xmlns="DAV:"><prop><resourcetype/><getcontentlength/></prop></propfind>"#;
+
+/// Entries of a `PROPFIND` (depth 1) response for the collection at
+/// `dir_path`, which is left out. Namespace prefixes vary by server, so
+/// elements are matched by local name.
+fn parse_propfind(xml: &str, dir_path: &str) -> Vec<Entry> {
+    let tag = |name: &str| format!(r"<(?:[A-Za-z][\w.-]*:)?{name}\b[^>]*>");
+    let response = Regex::new(&format!(
+        r"(?s){}(.*?)</(?:[A-Za-z][\w.-]*:)?response>",
+        tag("response")
+    ))
+    .expect("valid regex");
+    let href = Regex::new(&format!(r"(?s){}\s*([^<]*?)\s*<", tag("href"))).expect("valid regex");
+    let collection = Regex::new(&tag("collection")).expect("valid regex");
+    let length = Regex::new(&format!(r"{}\s*(\d+)", tag("getcontentlength"))).expect("valid regex");
+
+    let dir_path = dir_path.trim_end_matches('/');
+    let mut entries = Vec::new();
+    for caps in response.captures_iter(xml) {
+        let body = &caps[1];
+        let Some(href) = href.captures(body) else {
+            continue;
+        };
+        // Servers give the path, or the whole URL.
+        let href = percent_decode(&xml_unescape(&href[1]));
+        let path = match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+            None => href.as_str(),
+        };
+        let path = path.trim_end_matches('/');
+        if path == dir_path {
+            continue;
+        }
+        let Some((_, name)) = path.rsplit_once('/') else {
+            continue;
+        };
+        entries.push(Entry {
+            name: name.to_string(),
+            meta: Metadata {
+                is_dir: collection.is_match(body),
+                len: length
+                    .captures(body)
+                    .and_then(|c| c[1].parse().ok())
+                    .unwrap_or(0),
+            },
+        });
+    }
+    entries
+}
+
+pub(crate) fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(b) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(b);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
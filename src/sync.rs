@@ -1,109 +1,394 @@
 use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
 
+use anyhow::Result;
+
+use crate::deep_scan::TagIndex;
+use crate::manifest::Manifest;
 use crate::models::{
-    Album, AlbumId, DownloadTask, PurchaseList, SkipReason, SkippedTrack, SyncPlan, Track, TrackId,
+    Album, AlbumId, AlbumPlan, AudioFormat, DownloadTask, PurchaseList, Service, SizeMismatch,
+    SizeMismatchPolicy, SkipReason, SkippedTrack, SyncPlan, Track, TrackId,
 };
-use crate::path::track_path;
+use crate::path::{Naming, UnicodeForm, edition, keep_both_path, track_path, track_paths};
+use crate::storage::{self, Storage};
 
-/// Set of local files that exist and are non-empty.
-pub struct ExistingFiles(HashSet<PathBuf>);
+/// Local files that exist and are non-empty, by planned path.
+pub struct ExistingFiles {
+    found: HashSet<PathBuf>,
+    /// Planned paths whose file has an implausible size for the track:
+    /// the size on disk and the plausible range.
+    mismatched: HashMap<PathBuf, (u64, RangeInclusive<u64>)>,
+}
 
-/// Alternative extensions to check when determining if a track already exists.
-/// Handles format fallback: a task planned as `.mp3` may already exist as `.flac`.
-const ALT_EXTENSIONS: &[&str] = &[".flac", ".mp3"];
+impl ExistingFiles {
+    /// Count tasks whose file wasn't found where planned, but is somewhere
+    /// in `index` by its tags (say, after a tagger renamed it), as existing.
+    /// Returns each such task with the file found for it.
+    pub fn add_tagged<'a>(
+        &mut self,
+        tasks: &'a [DownloadTask],
+        index: &TagIndex,
+    ) -> Vec<(&'a DownloadTask, PathBuf)> {
+        let mut matched = Vec::new();
+        for task in tasks {
+            if self.found.contains(&task.target_path)
+                || self.mismatched.contains_key(&task.target_path)
+            {
+                continue;
+            }
+            if let Some(path) = index.find_track(&task.album, &task.track) {
+                self.found.insert(task.target_path.clone());
+                matched.push((task, path.to_path_buf()));
+            }
+        }
+        matched
+    }
+}
 
 /// Scan the target paths in the plan and stat each one.
-/// Also checks alternative extensions (e.g., `.flac` for a `.mp3` task) so that
-/// tracks downloaded via format fallback are recognized as already synced.
+/// When the manifest records a different on-disk format for a track (e.g.
+/// FLAC fallback for an MP3 task), the recorded format's path is checked
+/// instead, so fallback downloads are recognized as already synced.
+/// A track whose manifest-recorded path still exists (e.g. after `verify`
+/// relocated a manually moved file) also counts as existing.
+/// When a Unicode normalization form is configured, a file whose on-disk name
+/// is in the other form (e.g. synced from macOS) also counts as existing.
+/// A file at the planned path that qoget didn't record, and whose size is
+/// implausible for the track's length and format, is set aside as a size
+/// mismatch instead (see `AudioFormat::plausible_size`).
 /// This is the only I/O in the sync module — keeps build_sync_plan pure.
-pub async fn scan_existing(tasks: &[DownloadTask]) -> ExistingFiles {
+pub async fn scan_existing(
+    tasks: &[DownloadTask],
+    service: Service,
+    manifest: &Manifest,
+    naming: Naming,
+) -> Result<ExistingFiles> {
+    scan_existing_in(tasks, service, manifest, &storage::Local, naming).await
+}
+
+/// `scan_existing`, looking for the files in `storage`. Fails if `storage`
+/// can't be looked in, rather than planning to download everything again.
+pub async fn scan_existing_in(
+    tasks: &[DownloadTask],
+    service: Service,
+    manifest: &Manifest,
+    storage: &dyn Storage,
+    naming: Naming,
+) -> Result<ExistingFiles> {
+    if !storage.is_local() {
+        storage.prefetch(&planned_dirs(tasks)).await?;
+    }
     let mut existing = HashSet::new();
+    let mut mismatched = HashMap::new();
+    let mut lookup = naming
+        .unicode_form
+        .map(|form| NormalizedLookup::new(form, storage));
+    let shared = shared_by_editions(tasks, naming);
     for task in tasks {
-        if file_exists_nonempty(&task.target_path).await {
+        let actual_path = match manifest.format_for(service, task.track.id) {
+            Some(format) if format.extension() != task.file_extension => {
+                task.target_path.with_extension(&format.extension()[1..])
+            }
+            _ => task.target_path.clone(),
+        };
+        // A track two editions share has one manifest entry, for whichever
+        // copy was downloaded last; it says nothing about the other one.
+        let recorded = manifest
+            .recorded_path(service, task.track.id)
+            .filter(|p| *p != actual_path)
+            .filter(|p| !shared.contains(&task.track.id) || p.parent() == actual_path.parent());
+        let on_disk = nonempty_file_size(storage, &actual_path).await?;
+        if let Some(size) = on_disk
+            && task.track.duration > 0
+            && manifest.get(service, task.track.id).is_none()
+            && let Some(format) = AudioFormat::from_extension(&task.file_extension[1..])
+        {
+            let expected = format.plausible_size(task.track.duration);
+            if !expected.contains(&size) {
+                mismatched.insert(task.target_path.clone(), (size, expected));
+                continue;
+            }
+        }
+        let found = on_disk.is_some()
+            || match recorded {
+                Some(ref p) => file_exists_nonempty(storage, p).await?,
+                None => false,
+            }
+            || match lookup {
+                Some(ref mut lookup) => match lookup.find(&actual_path).await? {
+                    Some(on_disk) => file_exists_nonempty(storage, &on_disk).await?,
+                    None => false,
+                },
+                None => false,
+            };
+        if found {
+            // Record the original planned path so build_sync_plan marks it as skipped
             existing.insert(task.target_path.clone());
-            continue;
         }
-        // Check alternative extensions (e.g., .flac when task targets .mp3)
-        for alt_ext in ALT_EXTENSIONS {
-            if *alt_ext == task.file_extension {
+    }
+    Ok(ExistingFiles {
+        found: existing,
+        mismatched,
+    })
+}
+
+/// Every directory the tasks' files would be in, and the directories above
+/// them: those `scan_existing_in` looks in.
+fn planned_dirs(tasks: &[DownloadTask]) -> Vec<PathBuf> {
+    let mut dirs = HashSet::new();
+    for task in tasks {
+        for dir in task.target_path.ancestors().skip(1) {
+            if !dirs.insert(dir) {
+                break;
+            }
+        }
+    }
+    dirs.into_iter().map(Path::to_path_buf).collect()
+}
+
+/// Tracks that are part of more than one edition of a record (see
+/// `path::edition`), so downloaded into each.
+fn shared_by_editions(tasks: &[DownloadTask], naming: Naming) -> HashSet<TrackId> {
+    let mut shared = HashSet::new();
+    if !tasks.iter().any(|t| edition(&t.album, naming).is_some()) {
+        return shared;
+    }
+    let mut first: HashMap<TrackId, Option<&str>> = HashMap::new();
+    for task in tasks.iter().filter(|t| t.album.tracks_count > 1) {
+        let edition = edition(&task.album, naming);
+        if *first.entry(task.track.id).or_insert(edition) != edition {
+            shared.insert(task.track.id);
+        }
+    }
+    shared
+}
+
+/// Resolves paths against the filesystem by comparing names in a single
+/// normalization form. Directory listings are cached, so a scan costs one
+/// `read_dir` per directory rather than per missing track.
+struct NormalizedLookup<'a> {
+    form: UnicodeForm,
+    storage: &'a dyn Storage,
+    /// Planned directory -> its actual on-disk path, if any.
+    dirs: HashMap<PathBuf, Option<PathBuf>>,
+    /// On-disk directory -> normalized entry name -> actual entry name.
+    listings: HashMap<PathBuf, HashMap<String, OsString>>,
+}
+
+impl<'a> NormalizedLookup<'a> {
+    fn new(form: UnicodeForm, storage: &'a dyn Storage) -> Self {
+        Self {
+            form,
+            storage,
+            dirs: HashMap::new(),
+            listings: HashMap::new(),
+        }
+    }
+
+    async fn find(&mut self, path: &Path) -> Result<Option<PathBuf>> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(None);
+        };
+        let Some(dir) = self.resolve_dir(parent).await? else {
+            return Ok(None);
+        };
+        let name = self.form.normalize(&name.to_string_lossy());
+        let actual = self.listing(&dir).await?.get(&name).cloned();
+        Ok(actual.map(|actual| dir.join(actual)))
+    }
+
+    async fn resolve_dir(&mut self, dir: &Path) -> Result<Option<PathBuf>> {
+        if let Some(cached) = self.dirs.get(dir) {
+            return Ok(cached.clone());
+        }
+
+        let mut planned = PathBuf::new();
+        let mut actual = PathBuf::new();
+        for component in dir.components() {
+            planned.push(component);
+            if let Some(cached) = self.dirs.get(&planned) {
+                match cached {
+                    Some(cached) => actual = cached.clone(),
+                    None => return Ok(None),
+                }
                 continue;
             }
-            let alt_path = task.target_path.with_extension(&alt_ext[1..]);
-            if file_exists_nonempty(&alt_path).await {
-                // Record the original planned path so build_sync_plan marks it as skipped
-                existing.insert(task.target_path.clone());
-                break;
+            let resolved = match component {
+                Component::Normal(name) => {
+                    let verbatim = actual.join(name);
+                    if self
+                        .storage
+                        .metadata(&verbatim)
+                        .await?
+                        .is_some_and(|m| m.is_dir)
+                    {
+                        Some(verbatim)
+                    } else {
+                        let key = self.form.normalize(&name.to_string_lossy());
+                        self.listing(&actual)
+                            .await?
+                            .get(&key)
+                            .map(|n| actual.join(n))
+                    }
+                }
+                other => Some(actual.join(other)),
+            };
+            self.dirs.insert(planned.clone(), resolved.clone());
+            match resolved {
+                Some(resolved) => actual = resolved,
+                None => return Ok(None),
             }
         }
+        Ok(Some(actual))
     }
-    ExistingFiles(existing)
+
+    async fn listing(&mut self, dir: &Path) -> Result<&HashMap<String, OsString>> {
+        if !self.listings.contains_key(dir) {
+            let mut names = HashMap::new();
+            for name in self.storage.list(dir).await? {
+                names.insert(self.form.normalize(&name.to_string_lossy()), name);
+            }
+            self.listings.insert(dir.to_path_buf(), names);
+        }
+        Ok(&self.listings[dir])
+    }
+}
+
+async fn file_exists_nonempty(storage: &dyn Storage, path: &Path) -> Result<bool> {
+    Ok(nonempty_file_size(storage, path).await?.is_some())
 }
 
-async fn file_exists_nonempty(path: &Path) -> bool {
-    tokio::fs::metadata(path)
-        .await
-        .is_ok_and(|m| m.is_file() && m.len() > 0)
+async fn nonempty_file_size(storage: &dyn Storage, path: &Path) -> Result<Option<u64>> {
+    Ok(storage
+        .metadata(path)
+        .await?
+        .filter(|m| !m.is_dir && m.len > 0)
+        .map(|m| m.len))
 }
 
 /// Build a sync plan from pre-built download tasks. Pure function — no I/O.
 ///
 /// Deduplicates by TrackId: if the same track appears in multiple purchases
 /// (e.g., as a standalone single and within an album), keeps the album version
-/// (prefers the DownloadTask whose album has more than one track).
+/// (prefers the DownloadTask whose album has more than one track). When
+/// directory names include album versions, each edition of a record (see
+/// `path::edition`) keeps its own copy of the tracks they share.
 ///
 /// After dedup, classifies each task as download or skip based on:
+/// - existing files with an implausible size → per `on_size_mismatch`:
+///   SkipReason::SizeMismatch (keep), download in place (overwrite), or
+///   download to a suffixed path (keep-both); all are listed in
+///   `SyncPlan::size_mismatches`
 /// - existing files (non-empty) → SkipReason::AlreadyExists
 /// - dry_run mode → SkipReason::DryRun
+///
+/// Results are grouped per album (see `SyncPlan`).
 pub fn build_sync_plan(
     tasks: Vec<DownloadTask>,
     existing: &ExistingFiles,
     dry_run: bool,
+    on_size_mismatch: SizeMismatchPolicy,
+    naming: Naming,
 ) -> SyncPlan {
-    // Deduplicate by TrackId: prefer album version (album with tracks_count > 1)
-    let mut best: HashMap<TrackId, DownloadTask> = HashMap::new();
-    for task in tasks {
-        let id = task.track.id;
-        match best.get(&id) {
-            Some(existing_task)
-                if existing_task.album.tracks_count > 1 && task.album.tracks_count <= 1 =>
-            {
-                // Keep the existing album version over a standalone
-            }
-            _ => {
-                best.insert(id, task);
-            }
+    // Deduplicate by TrackId: prefer album version (album with tracks_count > 1).
+    // Only indices are kept here so tasks are never held twice.
+    let in_albums: HashSet<TrackId> = tasks
+        .iter()
+        .filter(|t| t.album.tracks_count > 1)
+        .map(|t| t.track.id)
+        .collect();
+    let key = |task: &DownloadTask| {
+        (
+            task.track.id,
+            edition(&task.album, naming).map(str::to_string),
+        )
+    };
+    let mut best: HashMap<(TrackId, Option<String>), usize> = HashMap::with_capacity(tasks.len());
+    for (i, task) in tasks.iter().enumerate() {
+        // Leave a track bought on its own too to the album, whichever
+        // edition that is.
+        if task.album.tracks_count <= 1 && in_albums.contains(&task.track.id) {
+            continue;
         }
+        best.insert(key(task), i);
     }
+    let total_tracks = best.len();
 
-    let deduped: Vec<DownloadTask> = best.into_values().collect();
-    let total_tracks = deduped.len();
+    // Group winners per album, in order of first appearance
+    let mut albums: Vec<AlbumPlan> = Vec::new();
+    let mut index: HashMap<AlbumId, usize> = HashMap::new();
+    let mut size_mismatches = Vec::new();
 
-    let mut downloads = Vec::new();
-    let mut skipped = Vec::new();
+    for (i, mut task) in tasks.into_iter().enumerate() {
+        if best.get(&key(&task)) != Some(&i) {
+            continue;
+        }
+        let slot = *index.entry(task.album.id.clone()).or_insert_with(|| {
+            albums.push(AlbumPlan {
+                album: task.album.clone(),
+                downloads: Vec::new(),
+                skipped: Vec::new(),
+            });
+            albums.len() - 1
+        });
+        let album_plan = &mut albums[slot];
 
-    for task in deduped {
-        if existing.0.contains(&task.target_path) {
-            skipped.push(SkippedTrack {
+        if let Some((actual, expected)) = existing.mismatched.get(&task.target_path) {
+            size_mismatches.push(SizeMismatch {
+                path: task.target_path.clone(),
+                actual: *actual,
+                expected: expected.clone(),
+                resolution: on_size_mismatch,
+            });
+            match on_size_mismatch {
+                SizeMismatchPolicy::Keep => {
+                    album_plan.skipped.push(SkippedTrack {
+                        track: task.track,
+                        target_path: task.target_path,
+                        reason: SkipReason::SizeMismatch,
+                    });
+                    continue;
+                }
+                SizeMismatchPolicy::Overwrite => {}
+                SizeMismatchPolicy::KeepBoth => {
+                    task.target_path = keep_both_path(&task.target_path);
+                }
+            }
+        } else if existing.found.contains(&task.target_path) {
+            album_plan.skipped.push(SkippedTrack {
                 track: task.track,
                 target_path: task.target_path,
                 reason: SkipReason::AlreadyExists,
             });
-        } else if dry_run {
-            skipped.push(SkippedTrack {
+            continue;
+        }
+
+        if dry_run {
+            album_plan.skipped.push(SkippedTrack {
                 track: task.track,
                 target_path: task.target_path,
                 reason: SkipReason::DryRun,
             });
         } else {
-            downloads.push(task);
+            album_plan.downloads.push(task);
         }
     }
 
+    for album_plan in &mut albums {
+        album_plan
+            .downloads
+            .sort_by_key(|t| (t.track.media_number.0, t.track.track_number.0));
+        album_plan
+            .skipped
+            .sort_by_key(|t| (t.track.media_number.0, t.track.track_number.0));
+    }
+
     SyncPlan {
-        downloads,
-        skipped,
+        albums,
         total_tracks,
+        size_mismatches,
     }
 }
 
@@ -113,47 +398,106 @@ pub fn collect_tasks(
     purchases: &PurchaseList,
     base_dir: &Path,
     ext: &'static str,
+    naming: Naming,
 ) -> Vec<DownloadTask> {
-    let mut all_tasks: Vec<DownloadTask> = Vec::new();
-
-    for album in &purchases.albums {
-        if let Some(ref paginated) = album.tracks {
-            for track in &paginated.items {
-                let target = track_path(base_dir, album, track, ext);
-                all_tasks.push(DownloadTask {
-                    track: track.clone(),
-                    album: album.clone(),
-                    target_path: target,
-                    file_extension: ext,
-                });
-            }
-        }
-    }
+    let album_tracks: usize = purchases
+        .albums
+        .iter()
+        .filter_map(|a| a.tracks.as_ref())
+        .map(|t| t.items.len())
+        .sum();
+    let mut all_tasks = Vec::with_capacity(album_tracks + purchases.tracks.len());
+    all_tasks.extend(iter_tasks(purchases, base_dir, ext, naming));
+    all_tasks
+}
+
+/// Lazily yield download tasks album by album, then standalone tracks.
+///
+/// Each task carries a track-less copy of its album (see `album_header`), so
+/// memory grows linearly with the number of tracks. `tests/memory_test.rs`
+/// holds planning a 50k-track library to a fixed peak-memory budget.
+pub fn iter_tasks<'a>(
+    purchases: &'a PurchaseList,
+    base_dir: &'a Path,
+    ext: &'static str,
+    naming: Naming,
+) -> impl Iterator<Item = DownloadTask> + 'a {
+    let album_tasks = purchases.albums.iter().flat_map(move |album| {
+        let header = album_header(album);
+        let tracks: Vec<&Track> = album
+            .tracks
+            .iter()
+            .flat_map(|paginated| paginated.items.iter())
+            .collect();
+        let paths = track_paths(base_dir, &header, tracks.iter().copied(), ext, naming);
+        tracks
+            .into_iter()
+            .zip(paths)
+            .map(move |(track, target_path)| DownloadTask {
+                track: track.clone(),
+                target_path,
+                album: header.clone(),
+                file_extension: ext,
+            })
+    });
 
     // Standalone track purchases
-    for track in &purchases.tracks {
+    let standalone_tasks = purchases.tracks.iter().map(move |track| {
         let album = standalone_album(track);
-        let target = track_path(base_dir, &album, track, ext);
-        all_tasks.push(DownloadTask {
+        DownloadTask {
             track: track.clone(),
+            target_path: track_path(base_dir, &album, track, ext, naming),
             album,
-            target_path: target,
             file_extension: ext,
-        });
-    }
+        }
+    });
 
-    all_tasks
+    album_tasks.chain(standalone_tasks)
+}
+
+/// Clone an album without its track listing. Cloning the full listing into
+/// every task would make memory quadratic in album size.
+fn album_header(album: &Album) -> Album {
+    Album {
+        id: album.id.clone(),
+        title: album.title.clone(),
+        version: album.version.clone(),
+        artist: album.artist.clone(),
+        artists: album.artists.clone(),
+        media_count: album.media_count,
+        tracks_count: album.tracks_count,
+        upc: album.upc.clone(),
+        release_date: album.release_date.clone(),
+        image: album.image.clone(),
+        tracks: None,
+        purchase: album.purchase.clone(),
+    }
 }
 
+/// Prefix of the album IDs `standalone_album` makes up.
+const STANDALONE_PREFIX: &str = "standalone-";
+
 /// Create a minimal album struct for standalone track purchases.
 fn standalone_album(track: &Track) -> Album {
     Album {
-        id: AlbumId(format!("standalone-{}", track.id)),
+        id: AlbumId(format!("{STANDALONE_PREFIX}{}", track.id)),
         title: track.title.clone(),
         version: None,
         artist: track.performer.clone(),
         media_count: 1,
         tracks_count: 1,
+        upc: None,
+        release_date: None,
+        artists: Vec::new(),
+        image: None,
         tracks: None,
+        purchase: track.purchase.clone(),
     }
 }
+
+/// The track a standalone purchase's made-up album stands for, if `album`
+/// is one.
+pub fn standalone_track(album: &AlbumId) -> Option<TrackId> {
+    let id = album.0.strip_prefix(STANDALONE_PREFIX)?;
+    id.parse().ok().map(TrackId)
+}
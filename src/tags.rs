@@ -0,0 +1,515 @@
+use std::ffi::OsString;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::manifest::{ProcessingStep, file_sha256, sha256_hex};
+use crate::models::AudioFormat;
+use crate::transcode::{muxer, run_ffmpeg};
+
+/// Writing the store's genre into a track's tags.
+pub const GENRE_STEP: ProcessingStep = ProcessingStep {
+    name: "genre",
+    version: 1,
+};
+
+/// Atoms whose payload is a list of child atoms, on the way to the `ilst`
+/// metadata list and to the chunk offset tables.
+const CONTAINERS: [&[u8; 4]; 8] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"meta", b"ilst",
+];
+
+/// iTunes genre, free text.
+const GENRE: [u8; 4] = *b"\xa9gen";
+/// iTunes release date, `YYYY` or `YYYY-MM-DD`.
+const DATE: [u8; 4] = *b"\xa9day";
+/// Legacy numeric (ID3v1 index) genre, dropped when a text genre is set.
+const GENRE_ID: [u8; 4] = *b"gnre";
+/// Freeform item, named by its `mean` and `name` children.
+const FREEFORM: [u8; 4] = *b"----";
+/// The `mean` of the freeform items iTunes and taggers write.
+const FREEFORM_MEAN: &[u8] = b"com.apple.iTunes";
+
+/// `hdlr` payload marking a `meta` atom as iTunes metadata.
+const MDIR_HANDLER: [u8; 25] = [
+    0, 0, 0, 0, 0, 0, 0, 0, b'm', b'd', b'i', b'r', b'a', b'p', b'p', b'l', 0, 0, 0, 0, 0, 0, 0, 0,
+    0,
+];
+
+struct Atom {
+    kind: [u8; 4],
+    /// Version and flags of a full box (iTunes `meta`), kept verbatim.
+    prefix: Vec<u8>,
+    body: Body,
+}
+
+enum Body {
+    Data(Vec<u8>),
+    Children(Vec<Atom>),
+}
+
+/// A top-level atom's position in the file.
+struct Span {
+    kind: [u8; 4],
+    start: usize,
+    payload: usize,
+    end: usize,
+}
+
+fn spans(buf: &[u8]) -> Result<Vec<Span>> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let rest = &buf[pos..];
+        if rest.len() < 8 {
+            bail!("truncated atom header at byte {pos}");
+        }
+        let kind: [u8; 4] = rest[4..8].try_into().expect("4 bytes");
+        let (header, size) = match u32::from_be_bytes(rest[..4].try_into().expect("4 bytes")) {
+            0 => (8, rest.len() as u64),
+            1 if rest.len() >= 16 => (
+                16,
+                u64::from_be_bytes(rest[8..16].try_into().expect("8 bytes")),
+            ),
+            1 => bail!("truncated atom header at byte {pos}"),
+            n => (8, u64::from(n)),
+        };
+        if size < header as u64 || size > rest.len() as u64 {
+            bail!(
+                "atom '{}' at byte {pos} has an invalid size",
+                kind.escape_ascii()
+            );
+        }
+        spans.push(Span {
+            kind,
+            start: pos,
+            payload: pos + header,
+            end: pos + size as usize,
+        });
+        pos += size as usize;
+    }
+    Ok(spans)
+}
+
+fn parse_atom(kind: [u8; 4], payload: &[u8]) -> Result<Atom> {
+    if !CONTAINERS.contains(&&kind) {
+        return Ok(Atom {
+            kind,
+            prefix: Vec::new(),
+            body: Body::Data(payload.to_vec()),
+        });
+    }
+    // iTunes-style `meta` is a full box; QuickTime-style is a plain container
+    // whose payload starts with a (non-zero) child size.
+    let prefix = if &kind == b"meta" && payload.starts_with(&[0; 4]) {
+        4
+    } else {
+        0
+    };
+    let children = spans(&payload[prefix..])?
+        .into_iter()
+        .map(|s| parse_atom(s.kind, &payload[prefix..][s.payload..s.end]))
+        .collect::<Result<_>>()?;
+    Ok(Atom {
+        kind,
+        prefix: payload[..prefix].to_vec(),
+        body: Body::Children(children),
+    })
+}
+
+fn write_atom(atom: &Atom, out: &mut Vec<u8>) -> Result<()> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&atom.kind);
+    out.extend_from_slice(&atom.prefix);
+    match &atom.body {
+        Body::Data(data) => out.extend_from_slice(data),
+        Body::Children(children) => {
+            for child in children {
+                write_atom(child, out)?;
+            }
+        }
+    }
+    let size = u32::try_from(out.len() - start).context("metadata atom too large")?;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+    Ok(())
+}
+
+fn children_mut(atom: &mut Atom) -> Result<&mut Vec<Atom>> {
+    match &mut atom.body {
+        Body::Children(children) => Ok(children),
+        Body::Data(_) => bail!("'{}' is not a container", atom.kind.escape_ascii()),
+    }
+}
+
+fn child_or_insert<'a>(
+    atom: &'a mut Atom,
+    kind: &[u8; 4],
+    make: fn() -> Atom,
+) -> Result<&'a mut Atom> {
+    let children = children_mut(atom)?;
+    let idx = match children.iter().position(|c| &c.kind == kind) {
+        Some(idx) => idx,
+        None => {
+            children.push(make());
+            children.len() - 1
+        }
+    };
+    Ok(&mut children[idx])
+}
+
+fn container(kind: &[u8; 4]) -> Atom {
+    Atom {
+        kind: *kind,
+        prefix: Vec::new(),
+        body: Body::Children(Vec::new()),
+    }
+}
+
+fn itunes_meta() -> Atom {
+    Atom {
+        kind: *b"meta",
+        prefix: vec![0; 4],
+        body: Body::Children(vec![Atom {
+            kind: *b"hdlr",
+            prefix: Vec::new(),
+            body: Body::Data(MDIR_HANDLER.to_vec()),
+        }]),
+    }
+}
+
+/// Add `delta` to every chunk offset at or past `from`: the sample data that
+/// moved because the `moov` before it changed size.
+fn shift_chunk_offsets(atom: &mut Atom, from: u64, delta: i64) -> Result<()> {
+    match &mut atom.body {
+        Body::Children(children) => {
+            for child in children {
+                shift_chunk_offsets(child, from, delta)?;
+            }
+        }
+        Body::Data(data) if &atom.kind == b"stco" || &atom.kind == b"co64" => {
+            let width = if &atom.kind == b"stco" { 4 } else { 8 };
+            for entry in data
+                .get_mut(8..)
+                .unwrap_or_default()
+                .chunks_exact_mut(width)
+            {
+                let mut bytes = [0u8; 8];
+                bytes[8 - width..].copy_from_slice(entry);
+                let offset = u64::from_be_bytes(bytes);
+                if offset < from {
+                    continue;
+                }
+                let shifted = offset
+                    .checked_add_signed(delta)
+                    .context("chunk offset out of range")?;
+                if width == 4 {
+                    let shifted = u32::try_from(shifted).context("chunk offset out of range")?;
+                    entry.copy_from_slice(&shifted.to_be_bytes());
+                } else {
+                    entry.copy_from_slice(&shifted.to_be_bytes());
+                }
+            }
+        }
+        Body::Data(_) => {}
+    }
+    Ok(())
+}
+
+/// Set the genre of an MP4/M4A file, replacing any genre it had. Only the
+/// metadata changes; the file is rewritten through a temporary file so an
+/// interruption can't leave it half-written. Returns the new file's hex
+/// SHA-256.
+pub fn set_mp4_genre(path: &Path, genre: &str) -> Result<String> {
+    edit_mp4_items(path, |items| {
+        items.retain(|item| item.kind != GENRE && item.kind != GENRE_ID);
+        items.push(Atom {
+            kind: GENRE,
+            prefix: Vec::new(),
+            body: Body::Data(text_data(genre)),
+        });
+    })
+}
+
+/// Set the release date (`©day`) of an MP4/M4A file, replacing any it had.
+/// Rewritten like `set_mp4_genre`; returns the new file's hex SHA-256.
+pub fn set_mp4_date(path: &Path, date: &str) -> Result<String> {
+    edit_mp4_items(path, |items| {
+        items.retain(|item| item.kind != DATE);
+        items.push(Atom {
+            kind: DATE,
+            prefix: Vec::new(),
+            body: Body::Data(text_data(date)),
+        });
+    })
+}
+
+/// Set iTunes freeform (`----`) text items of an MP4/M4A file, the way
+/// ReplayGain taggers write them, replacing items of the same names.
+/// Rewritten like `set_mp4_genre`; returns the new file's hex SHA-256.
+pub fn set_mp4_freeform(path: &Path, values: &[(&str, &str)]) -> Result<String> {
+    edit_mp4_items(path, |items| {
+        items.retain(|item| {
+            freeform_name(item).is_none_or(|name| !values.iter().any(|(n, _)| n.as_bytes() == name))
+        });
+        for (name, value) in values {
+            let mut data = Vec::new();
+            push_child(&mut data, b"mean", &[&[0; 4], FREEFORM_MEAN]);
+            push_child(&mut data, b"name", &[&[0; 4], name.as_bytes()]);
+            data.extend_from_slice(&text_data(value));
+            items.push(Atom {
+                kind: FREEFORM,
+                prefix: Vec::new(),
+                body: Body::Data(data),
+            });
+        }
+    })
+}
+
+/// A `data` atom holding UTF-8 text.
+fn text_data(text: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    // Type 1 (UTF-8), default locale
+    push_child(
+        &mut data,
+        b"data",
+        &[&[0, 0, 0, 1, 0, 0, 0, 0], text.as_bytes()],
+    );
+    data
+}
+
+fn push_child(out: &mut Vec<u8>, kind: &[u8; 4], parts: &[&[u8]]) {
+    let len: usize = 8 + parts.iter().map(|p| p.len()).sum::<usize>();
+    out.extend_from_slice(&(len as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+}
+
+/// The name of a freeform item, from its `name` child.
+fn freeform_name(item: &Atom) -> Option<&[u8]> {
+    let Body::Data(data) = &item.body else {
+        return None;
+    };
+    if item.kind != FREEFORM {
+        return None;
+    }
+    let spans = spans(data).ok()?;
+    let name = spans.iter().find(|s| &s.kind == b"name")?;
+    data.get(name.payload + 4..name.end)
+}
+
+/// Rewrite the iTunes item list of an MP4/M4A file with `edit`, through a
+/// temporary file. Returns the new file's hex SHA-256.
+fn edit_mp4_items(path: &Path, edit: impl FnOnce(&mut Vec<Atom>)) -> Result<String> {
+    let buf = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let spans = spans(&buf).with_context(|| format!("parsing {}", path.display()))?;
+    let Some(moov_span) = spans.iter().find(|s| &s.kind == b"moov") else {
+        bail!("{} has no moov atom", path.display());
+    };
+    let mut moov = parse_atom(*b"moov", &buf[moov_span.payload..moov_span.end])?;
+
+    let udta = child_or_insert(&mut moov, b"udta", || container(b"udta"))?;
+    let meta = child_or_insert(udta, b"meta", itunes_meta)?;
+    let ilst = child_or_insert(meta, b"ilst", || container(b"ilst"))?;
+    edit(children_mut(ilst)?);
+
+    let mut new_moov = Vec::new();
+    write_atom(&moov, &mut new_moov)?;
+    let delta = new_moov.len() as i64 - (moov_span.end - moov_span.start) as i64;
+    if delta != 0 {
+        shift_chunk_offsets(&mut moov, moov_span.end as u64, delta)?;
+        new_moov.clear();
+        write_atom(&moov, &mut new_moov)?;
+    }
+
+    let mut out = Vec::with_capacity(buf.len() + new_moov.len());
+    out.extend_from_slice(&buf[..moov_span.start]);
+    out.extend_from_slice(&new_moov);
+    out.extend_from_slice(&buf[moov_span.end..]);
+
+    let tmp = path.with_extension("m4a.tagging");
+    std::fs::write(&tmp, &out).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+    Ok(sha256_hex(&out))
+}
+
+/// The text genre of an MP4/M4A file, if it has one.
+pub fn mp4_genre(path: &Path) -> Result<Option<String>> {
+    let items = mp4_items(path)?;
+    match items.iter().find(|c| c.kind == GENRE).map(|c| &c.body) {
+        Some(Body::Data(data)) if data.len() >= 16 && &data[4..8] == b"data" => {
+            Ok(Some(String::from_utf8_lossy(&data[16..]).into_owned()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The value of the freeform item `name` of an MP4/M4A file, if it has one.
+pub fn mp4_freeform(path: &Path, name: &str) -> Result<Option<String>> {
+    let items = mp4_items(path)?;
+    let Some(item) = items
+        .iter()
+        .find(|item| freeform_name(item) == Some(name.as_bytes()))
+    else {
+        return Ok(None);
+    };
+    Ok(item_value(item).map(|value| String::from_utf8_lossy(value).into_owned()))
+}
+
+/// The text items of an MP4/M4A file, keyed by atom name (`©nam`, `aART`)
+/// or, for freeform items, by their name (`ISRC`). The track and disc
+/// number items `trkn` and `disk` are given as just the number.
+pub fn mp4_text_items(path: &Path) -> Result<Vec<(String, String)>> {
+    let items = mp4_items(path)?;
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let value = item_value(item)?;
+            if item.kind == FREEFORM {
+                let name = String::from_utf8_lossy(freeform_name(item)?).into_owned();
+                return Some((name, String::from_utf8_lossy(value).into_owned()));
+            }
+            // Latin-1, for the leading ©
+            let key: String = item.kind.iter().map(|&b| char::from(b)).collect();
+            if &item.kind == b"trkn" || &item.kind == b"disk" {
+                let number = u16::from_be_bytes(value.get(2..4)?.try_into().ok()?);
+                return Some((key, number.to_string()));
+            }
+            Some((key, String::from_utf8_lossy(value).into_owned()))
+        })
+        .collect())
+}
+
+/// The value in an item's `data` child, after its type and locale.
+fn item_value(item: &Atom) -> Option<&[u8]> {
+    let Body::Data(data) = &item.body else {
+        return None;
+    };
+    let spans = spans(data).ok()?;
+    spans
+        .iter()
+        .find(|s| &s.kind == b"data")
+        .and_then(|s| data.get(s.payload + 8..s.end))
+}
+
+/// The payload of an MP4 file's `moov` atom, read without loading the
+/// (much larger) media data around it.
+fn read_moov(path: &Path) -> Result<Option<Vec<u8>>> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("reading {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let mut pos = 0;
+    while pos + 8 <= len {
+        let mut header = [0; 16];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header[..8])?;
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into().expect("4 bytes"))
+        {
+            0 => (8, len - pos),
+            1 => {
+                file.read_exact(&mut header[8..])?;
+                (
+                    16,
+                    u64::from_be_bytes(header[8..].try_into().expect("8 bytes")),
+                )
+            }
+            n => (8, u64::from(n)),
+        };
+        if size < header_len || pos + size > len {
+            bail!("{} has an atom with an invalid size", path.display());
+        }
+        if &header[4..8] == b"moov" {
+            let mut payload = vec![0; (size - header_len) as usize];
+            file.seek(SeekFrom::Start(pos + header_len))?;
+            file.read_exact(&mut payload)?;
+            return Ok(Some(payload));
+        }
+        pos += size;
+    }
+    Ok(None)
+}
+
+/// The iTunes metadata items of an MP4/M4A file; none if it has no list.
+fn mp4_items(path: &Path) -> Result<Vec<Atom>> {
+    let Some(moov) = read_moov(path)? else {
+        return Ok(Vec::new());
+    };
+    let mut atom =
+        parse_atom(*b"moov", &moov).with_context(|| format!("parsing {}", path.display()))?;
+    for kind in [b"udta", b"meta", b"ilst"] {
+        let Body::Children(children) = atom.body else {
+            return Ok(Vec::new());
+        };
+        match children.into_iter().find(|c| &c.kind == kind) {
+            Some(child) => atom = child,
+            None => return Ok(Vec::new()),
+        }
+    }
+    match atom.body {
+        Body::Children(items) => Ok(items),
+        Body::Data(_) => Ok(Vec::new()),
+    }
+}
+
+/// Write `tags` into `path`, through a temporary file. MP4 files get iTunes
+/// freeform items of the names given; other formats are remuxed by ffmpeg
+/// with the streams copied and the tags set as `-metadata`. Returns the new
+/// file's hex SHA-256.
+pub async fn write_tags(
+    path: &Path,
+    format: AudioFormat,
+    tags: Vec<(String, String)>,
+) -> Result<String> {
+    if format == AudioFormat::Aac {
+        let path = path.to_path_buf();
+        return tokio::task::spawn_blocking(move || {
+            let items: Vec<(&str, &str)> =
+                tags.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+            set_mp4_freeform(&path, &items)
+        })
+        .await?;
+    }
+
+    let tmp = path.with_extension("tagging.tmp");
+    let mut args: Vec<OsString> = ["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]
+        .map(OsString::from)
+        .to_vec();
+    args.extend([
+        "-i".into(),
+        path.as_os_str().to_owned(),
+        "-map".into(),
+        "0".into(),
+        "-c".into(),
+        "copy".into(),
+        "-map_metadata".into(),
+        "0".into(),
+    ]);
+    for (key, value) in tags {
+        args.extend(["-metadata".into(), format!("{key}={value}").into()]);
+    }
+    args.extend([
+        "-f".into(),
+        muxer(format).into(),
+        tmp.as_os_str().to_owned(),
+    ]);
+    let out = run_ffmpeg(args).await?;
+    if !out.status.success() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        bail!(
+            "ffmpeg could not tag {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || file_sha256(&path))
+        .await?
+        .context("hashing the tagged file")
+}
@@ -0,0 +1,192 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::manifest::file_sha256;
+use crate::models::AudioFormat;
+
+/// Codecs downloads can be transcoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Opus,
+    Aac,
+    Mp3,
+}
+
+impl Codec {
+    fn name(self) -> &'static str {
+        match self {
+            Codec::Opus => "opus",
+            Codec::Aac => "aac",
+            Codec::Mp3 => "mp3",
+        }
+    }
+
+    /// ffmpeg's encoder for the codec.
+    fn encoder(self) -> &'static str {
+        match self {
+            Codec::Opus => "libopus",
+            Codec::Aac => "aac",
+            Codec::Mp3 => "libmp3lame",
+        }
+    }
+}
+
+/// ffmpeg's name for the container files of `format` are written in, so
+/// output can go to a temp name whose extension says nothing.
+pub fn muxer(format: AudioFormat) -> &'static str {
+    match format {
+        AudioFormat::Mp3 => "mp3",
+        AudioFormat::Flac => "flac",
+        AudioFormat::Aac => "ipod",
+        AudioFormat::Opus => "opus",
+    }
+}
+
+/// What downloads are transcoded to after they arrive (`[transcode]
+/// target`), written as codec and bitrate in kbps: `opus-128`, `aac-256`,
+/// `mp3-192`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TranscodeTarget {
+    pub codec: Codec,
+    /// Kilobits per second.
+    pub bitrate: u32,
+}
+
+impl TranscodeTarget {
+    /// The format transcoded files are recorded as in the manifest.
+    pub fn format(self) -> AudioFormat {
+        match self.codec {
+            Codec::Opus => AudioFormat::Opus,
+            Codec::Aac => AudioFormat::Aac,
+            Codec::Mp3 => AudioFormat::Mp3,
+        }
+    }
+
+    /// ffmpeg arguments that transcode `input` into `output`, copying its
+    /// tags.
+    pub fn ffmpeg_args(self, input: &Path, output: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = ["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]
+            .map(OsString::from)
+            .to_vec();
+        args.extend([
+            "-i".into(),
+            input.as_os_str().to_owned(),
+            "-map".into(),
+            "0:a".into(),
+            "-map_metadata".into(),
+            "0".into(),
+            "-c:a".into(),
+            self.codec.encoder().into(),
+            "-b:a".into(),
+            format!("{}k", self.bitrate).into(),
+            "-f".into(),
+            muxer(self.format()).into(),
+            output.as_os_str().to_owned(),
+        ]);
+        args
+    }
+}
+
+impl FromStr for TranscodeTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((codec, bitrate)) = s.split_once('-') else {
+            bail!("'{s}' is not a codec and bitrate such as opus-128");
+        };
+        let codec = match codec.to_ascii_lowercase().as_str() {
+            "opus" => Codec::Opus,
+            "aac" => Codec::Aac,
+            "mp3" => Codec::Mp3,
+            _ => bail!("unknown codec '{codec}' (expected opus, aac or mp3)"),
+        };
+        let bitrate = match bitrate.parse::<u32>() {
+            Ok(kbps) if (32..=320).contains(&kbps) => kbps,
+            _ => bail!("bitrate must be from 32 to 320 kbps, got '{bitrate}'"),
+        };
+        Ok(Self { codec, bitrate })
+    }
+}
+
+impl TryFrom<String> for TranscodeTarget {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for TranscodeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.codec.name(), self.bitrate)
+    }
+}
+
+/// The format a download delivered as `format` ends up in on disk: the
+/// format of `target`, if downloads are transcoded. Sync plans with its
+/// extension, so transcoded files count as synced.
+pub fn planned_format(format: AudioFormat, target: Option<TranscodeTarget>) -> AudioFormat {
+    target.map_or(format, TranscodeTarget::format)
+}
+
+/// Fail early, before anything is downloaded, if ffmpeg can't be run for
+/// `purpose` ("transcoding").
+pub fn check_ffmpeg(purpose: &str) -> Result<()> {
+    let status = Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("{purpose} needs ffmpeg, which could not be run"))?;
+    if !status.success() {
+        bail!("{purpose} needs ffmpeg, and `ffmpeg -version` failed");
+    }
+    Ok(())
+}
+
+/// Run ffmpeg with `args` off the async runtime, capturing its output.
+pub async fn run_ffmpeg(args: Vec<OsString>) -> Result<Output> {
+    tokio::task::spawn_blocking(move || {
+        Command::new("ffmpeg")
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+    })
+    .await?
+    .context("ffmpeg could not be run")
+}
+
+/// Transcode `input` into `output` as `target` says and remove `input`.
+/// The new file is written beside `output` and renamed into place, so a
+/// failure leaves nothing at `output`. Returns the new file's SHA-256.
+pub async fn transcode(input: &Path, output: &Path, target: TranscodeTarget) -> Result<String> {
+    let ext = &target.format().extension()[1..];
+    let tmp = output.with_extension(format!("{ext}.tmp"));
+    debug!(%target, "transcoding {}", input.display());
+    let out = run_ffmpeg(target.ffmpeg_args(input, &tmp)).await?;
+    if !out.status.success() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        bail!(
+            "ffmpeg could not transcode {} to {target}: {}",
+            input.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+
+    tokio::fs::rename(&tmp, output)
+        .await
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), output.display()))?;
+    let _ = tokio::fs::remove_file(input).await;
+    let output = output.to_path_buf();
+    tokio::task::spawn_blocking(move || file_sha256(&output))
+        .await?
+        .context("hashing the transcoded file")
+}
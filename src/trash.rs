@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Where files qoget would otherwise delete go instead:
+/// `<target>/.qoget/trash/<timestamp>/`, one batch per run, keeping each
+/// file's path relative to the target so it can be put back by hand.
+pub struct Trash {
+    root: PathBuf,
+    batch: String,
+}
+
+/// One run's worth of trashed files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashBatch {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Trash {
+    /// A batch named for the current UTC time. Nothing is created on disk
+    /// until a file is moved.
+    pub fn new(root: &Path) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self::with_batch(root, &batch_name(now))
+    }
+
+    pub fn with_batch(root: &Path, batch: &str) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            batch: batch.to_string(),
+        }
+    }
+
+    /// `<root>/.qoget/trash`
+    pub fn dir(root: &Path) -> PathBuf {
+        root.join(".qoget").join("trash")
+    }
+
+    pub fn batch_dir(&self) -> PathBuf {
+        Self::dir(&self.root).join(&self.batch)
+    }
+
+    /// Move `path` (absolute, or relative to the target) into this batch and
+    /// return where it ended up. A name already taken in the batch gets a
+    /// numeric suffix.
+    pub fn move_file(&self, path: &Path) -> Result<PathBuf> {
+        let full = self.root.join(path);
+        let rel = full.strip_prefix(&self.root).unwrap_or(path);
+        let mut dest = self.batch_dir().join(rel);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        let mut n = 1;
+        while dest.exists() {
+            let name = rel.file_name().unwrap_or_default().to_string_lossy();
+            dest.set_file_name(format!("{name}.{n}"));
+            n += 1;
+        }
+        std::fs::rename(&full, &dest)
+            .with_context(|| format!("moving {} -> {}", full.display(), dest.display()))?;
+        Ok(dest)
+    }
+}
+
+/// Trash batches under `root`, oldest first.
+pub fn list_batches(root: &Path) -> Result<Vec<TrashBatch>> {
+    let dir = Trash::dir(root);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+    };
+
+    let mut batches = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("reading {}", dir.display()))?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let mut files = 0;
+        let mut bytes = 0;
+        let mut stack = vec![entry.path()];
+        while let Some(dir) = stack.pop() {
+            for child in
+                std::fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))?
+            {
+                let child = child.with_context(|| format!("reading {}", dir.display()))?;
+                let meta = child.metadata()?;
+                if meta.is_dir() {
+                    stack.push(child.path());
+                } else {
+                    files += 1;
+                    bytes += meta.len();
+                }
+            }
+        }
+        batches.push(TrashBatch {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            files,
+            bytes,
+        });
+    }
+    batches.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(batches)
+}
+
+/// Permanently delete everything in the trash, returning the batches removed.
+pub fn empty(root: &Path) -> Result<Vec<TrashBatch>> {
+    let batches = list_batches(root)?;
+    let dir = Trash::dir(root);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
+    }
+    Ok(batches)
+}
+
+/// `YYYYMMDDTHHMMSSZ` for a Unix timestamp: sorts by time, and is safe in
+/// file names everywhere.
+pub fn batch_name(unix_secs: u64) -> String {
+    crate::models::utc_basic(unix_secs)
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::bandcamp::is_html;
+use crate::checksums;
+use crate::manifest::{Manifest, file_sha256};
+use crate::status::scan_library;
+
+/// Smaller track files are almost certainly cut short: this is under two
+/// seconds of 128 kbps audio.
+pub const MIN_TRACK_BYTES: u64 = 32 * 1024;
+
+/// Bytes read from the start of each file for format sniffing.
+const HEAD_LEN: usize = 512;
+
+/// An audio file found under the target directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalFile {
+    /// Relative to the target directory.
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// A manifest entry whose file was found at a different path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovedFile {
+    pub key: String,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// A manifest entry whose file is gone and couldn't be located elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingFile {
+    pub key: String,
+    pub path: PathBuf,
+}
+
+/// What is wrong with a file that is where the manifest says it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    Empty,
+    Truncated {
+        size: u64,
+    },
+    /// An HTML page (usually an error page) saved under an audio extension.
+    Html,
+    /// Doesn't start like a file of its extension's format.
+    WrongFormat,
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Damage {
+    pub fn describe(&self) -> String {
+        match self {
+            Damage::Empty => "empty file".to_string(),
+            Damage::Truncated { size } => format!("truncated ({size} bytes)"),
+            Damage::Html => "HTML page, not audio".to_string(),
+            Damage::WrongFormat => "contents don't match the file extension".to_string(),
+            Damage::ChecksumMismatch { expected, actual } => {
+                format!("checksum mismatch (recorded {expected}, found {actual})")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamagedFile {
+    /// Empty for a file the manifest doesn't record.
+    pub key: String,
+    pub path: PathBuf,
+    pub damage: Damage,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub moved: Vec<MovedFile>,
+    pub missing: Vec<MissingFile>,
+    pub damaged: Vec<DamagedFile>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.moved.is_empty() && self.missing.is_empty() && self.damaged.is_empty()
+    }
+}
+
+/// List audio files under `root` with their sizes.
+pub fn scan_files(root: &Path) -> Result<Vec<LocalFile>> {
+    scan_library(root)?
+        .into_iter()
+        .map(|path| {
+            let full = root.join(&path);
+            let size = std::fs::metadata(&full)
+                .with_context(|| format!("reading {}", full.display()))?
+                .len();
+            Ok(LocalFile { path, size })
+        })
+        .collect()
+}
+
+/// Check every manifest entry against the files on disk. Pure function — no I/O.
+///
+/// An entry whose file is missing is matched against files no other entry
+/// claims, with the same extension and recorded size. When several
+/// candidates remain, one with the same file name wins; if that is still
+/// ambiguous the entry is reported missing. Entries recorded without a size
+/// only match a file with the same name.
+pub fn check_manifest(manifest: &Manifest, local: &[LocalFile]) -> VerifyReport {
+    let present: HashSet<&Path> = local.iter().map(|f| f.path.as_path()).collect();
+    let referenced: HashSet<&Path> = manifest.entries().map(|e| e.path.as_path()).collect();
+    let mut taken: HashSet<&Path> = HashSet::new();
+    let mut report = VerifyReport::default();
+
+    for (key, entry) in manifest.keyed_entries() {
+        if present.contains(entry.path.as_path()) {
+            continue;
+        }
+
+        let ext = entry.path.extension();
+        let name = entry.path.file_name();
+        let candidates: Vec<&LocalFile> = local
+            .iter()
+            .filter(|f| !referenced.contains(f.path.as_path()) && !taken.contains(f.path.as_path()))
+            .filter(|f| {
+                f.path
+                    .extension()
+                    .zip(ext)
+                    .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b))
+            })
+            .filter(|f| match entry.size {
+                Some(size) => f.size == size,
+                None => f.path.file_name() == name,
+            })
+            .collect();
+
+        let found = match candidates.as_slice() {
+            [only] => Some(*only),
+            many => {
+                let same_name: Vec<_> =
+                    many.iter().filter(|f| f.path.file_name() == name).collect();
+                match same_name.as_slice() {
+                    [only] => Some(**only),
+                    _ => None,
+                }
+            }
+        };
+
+        match found {
+            Some(file) => {
+                taken.insert(&file.path);
+                report.moved.push(MovedFile {
+                    key: key.to_string(),
+                    from: entry.path.clone(),
+                    to: file.path.clone(),
+                });
+            }
+            None => report.missing.push(MissingFile {
+                key: key.to_string(),
+                path: entry.path.clone(),
+            }),
+        }
+    }
+
+    report
+}
+
+/// Whether `head` starts like a file of the given extension. Unknown
+/// extensions are not checked.
+fn has_audio_magic(head: &[u8], ext: &str) -> bool {
+    match ext.to_ascii_lowercase().as_str() {
+        "mp3" => {
+            head.starts_with(b"ID3")
+                || (head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0)
+        }
+        "flac" => head.starts_with(b"fLaC"),
+        "m4a" => head.get(4..8) == Some(b"ftyp"),
+        "opus" => head.starts_with(b"OggS"),
+        _ => true,
+    }
+}
+
+/// Judge a file from its size and first bytes. Pure function — no I/O.
+pub fn classify(size: u64, head: &[u8], ext: &str) -> Option<Damage> {
+    if size == 0 {
+        Some(Damage::Empty)
+    } else if is_html(head) {
+        Some(Damage::Html)
+    } else if size < MIN_TRACK_BYTES {
+        Some(Damage::Truncated { size })
+    } else if !has_audio_magic(head, ext) {
+        Some(Damage::WrongFormat)
+    } else {
+        None
+    }
+}
+
+/// Inspect every manifest entry whose file is in place, adding damaged files
+/// to the report. With `checksums`, entries that recorded a SHA-256 are also
+/// re-hashed (this reads every such file in full).
+pub fn check_integrity(
+    root: &Path,
+    manifest: &Manifest,
+    checksums: bool,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let skip: HashSet<&Path> = report
+        .missing
+        .iter()
+        .map(|m| m.path.as_path())
+        .chain(report.moved.iter().map(|m| m.from.as_path()))
+        .collect();
+
+    for (key, entry) in manifest.keyed_entries() {
+        if skip.contains(entry.path.as_path()) {
+            continue;
+        }
+        let full = root.join(&entry.path);
+        let size = std::fs::metadata(&full)
+            .with_context(|| format!("reading {}", full.display()))?
+            .len();
+        let head = read_head(&full)?;
+        let ext = entry
+            .path
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut damage = classify(size, &head, &ext);
+        if damage.is_none()
+            && checksums
+            && let Some(ref expected) = entry.sha256
+        {
+            let actual =
+                file_sha256(&full).with_context(|| format!("hashing {}", full.display()))?;
+            if actual != *expected {
+                damage = Some(Damage::ChecksumMismatch {
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        if let Some(damage) = damage {
+            report.damaged.push(DamagedFile {
+                key: key.to_string(),
+                path: entry.path.clone(),
+                damage,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check files against the checksum files in their album directories (see
+/// `checksums`), where the manifest records no checksum of its own: files
+/// recorded without one, and files the manifest doesn't know, say after it
+/// was lost. Files already reported damaged are skipped. Reads each file
+/// checked in full.
+pub fn check_checksum_files(
+    root: &Path,
+    manifest: &Manifest,
+    local: &[LocalFile],
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let recorded: HashMap<&Path, (&str, bool)> = manifest
+        .keyed_entries()
+        .map(|(key, e)| (e.path.as_path(), (key, e.sha256.is_some())))
+        .collect();
+    let paths: Vec<PathBuf> = local.iter().map(|f| f.path.clone()).collect();
+    let listed = checksums::load_listed(root, &paths);
+    let reported: HashSet<PathBuf> = report.damaged.iter().map(|d| d.path.clone()).collect();
+
+    for file in local {
+        let Some(expected) = listed.get(&file.path) else {
+            continue;
+        };
+        let key = match recorded.get(file.path.as_path()) {
+            // Checked against the manifest's checksum already
+            Some((_, true)) => continue,
+            Some((key, false)) => key.to_string(),
+            None => String::new(),
+        };
+        if reported.contains(&file.path) {
+            continue;
+        }
+        let full = root.join(&file.path);
+        let actual = file_sha256(&full).with_context(|| format!("hashing {}", full.display()))?;
+        if actual != *expected {
+            report.damaged.push(DamagedFile {
+                key,
+                path: file.path.clone(),
+                damage: Damage::ChecksumMismatch {
+                    expected: expected.clone(),
+                    actual,
+                },
+            });
+        }
+    }
+    Ok(())
+}
+
+fn read_head(path: &Path) -> Result<Vec<u8>> {
+    use std::io::Read as _;
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    std::fs::File::open(path)
+        .with_context(|| format!("opening {}", path.display()))?
+        .take(HEAD_LEN as u64)
+        .read_to_end(&mut head)
+        .with_context(|| format!("reading {}", path.display()))?;
+    Ok(head)
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use qoget::art::{has_cover, plan_backfill};
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{Album, AlbumImage, AudioFormat, PurchaseList, Service, Track, TrackId};
+use qoget::path::Naming;
+
+mod common;
+
+fn make_track(id: u64) -> Track {
+    common::track(id, &format!("Track {id}"), 1, "Artist")
+}
+
+fn make_album(title: &str, image: Option<&str>, tracks: Vec<Track>) -> Album {
+    let album = Album {
+        image: image.map(|url| AlbumImage {
+            large: Some(url.to_string()),
+        }),
+        ..common::album(&title.to_lowercase(), title, "Artist")
+    };
+    common::with_tracks(album, tracks)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-art-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn backfill_plans_only_synced_albums_without_cover() {
+    let base = temp_dir("plan");
+    for dir in ["Artist/Bare", "Artist/Covered", "Artist/No Art"] {
+        std::fs::create_dir_all(base.join(dir)).unwrap();
+    }
+    std::fs::write(base.join("Artist/Covered/folder.jpg"), b"\xFF\xD8\xFF").unwrap();
+
+    let list = PurchaseList {
+        albums: vec![
+            make_album("Bare", Some("https://img/bare.jpg"), vec![make_track(1)]),
+            make_album("Covered", Some("https://img/covered.jpg"), vec![]),
+            make_album("No Art", None, vec![]),
+            make_album("Elsewhere", Some("https://img/elsewhere.jpg"), vec![]),
+        ],
+        tracks: vec![],
+    };
+    let plan = plan_backfill(
+        &base,
+        &[(Service::Qobuz, &list)],
+        &Manifest::default(),
+        Naming::default(),
+    );
+
+    assert_eq!(plan.tasks.len(), 1);
+    assert_eq!(plan.tasks[0].dir, Path::new("Artist/Bare"));
+    assert_eq!(plan.tasks[0].url, "https://img/bare.jpg");
+    assert_eq!(plan.present, 1);
+    assert_eq!(plan.unavailable, 1);
+    assert_eq!(plan.not_synced, 1);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn backfill_follows_manifest_to_moved_albums() {
+    let base = temp_dir("moved");
+    let moved = base.join("Renamed/Album Dir/01 - Track 7.mp3");
+    std::fs::create_dir_all(moved.parent().unwrap()).unwrap();
+    std::fs::write(&moved, b"ID3").unwrap();
+
+    let album = make_album("Album", Some("https://img/a.jpg"), vec![make_track(7)]);
+    let mut manifest = Manifest::default();
+    let entry = ManifestEntry::new(&album, &make_track(7), AudioFormat::Mp3, &base, &moved);
+    manifest.record(Service::Qobuz, TrackId(7), entry);
+
+    let list = PurchaseList {
+        albums: vec![album],
+        tracks: vec![],
+    };
+    let plan = plan_backfill(
+        &base,
+        &[(Service::Qobuz, &list)],
+        &manifest,
+        Naming::default(),
+    );
+    assert_eq!(plan.tasks.len(), 1);
+    assert_eq!(plan.tasks[0].dir, Path::new("Renamed/Album Dir"));
+
+    std::fs::write(base.join("Renamed/Album Dir/cover.jpg"), b"\xFF\xD8\xFF").unwrap();
+    assert!(has_cover(&base.join("Renamed/Album Dir")));
+    let plan = plan_backfill(
+        &base,
+        &[(Service::Qobuz, &list)],
+        &manifest,
+        Naming::default(),
+    );
+    assert!(plan.tasks.is_empty());
+    assert_eq!(plan.present, 1);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
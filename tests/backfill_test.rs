@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use qoget::backfill::Backfill;
+use qoget::health::AuthError;
+use qoget::models::{BandcampDownloadError, BandcampSyncResult, Service, SkipReason};
+use qoget::report::{DownloadsFailed, ServiceReport, SyncReport};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-backfill-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn bandcamp_session(
+    target: &Path,
+    downloaded: &[&str],
+    failed: &[&str],
+    deferred: &[&str],
+) -> SyncReport {
+    let mut report = SyncReport::new(target, false);
+    let mut bandcamp = ServiceReport::new(Service::Bandcamp);
+    bandcamp.add_bandcamp(
+        &BandcampSyncResult {
+            downloaded: downloaded.len(),
+            skipped: 0,
+            would_download: 0,
+            retagged: 0,
+            succeeded: downloaded
+                .iter()
+                .map(|d| (d.to_string(), vec![target.join(d).join("01 - One.m4a")]))
+                .collect(),
+            skipped_items: deferred
+                .iter()
+                .map(|d| (d.to_string(), SkipReason::Deferred))
+                .collect(),
+            sizes: HashMap::new(),
+            failed: failed
+                .iter()
+                .map(|f| BandcampDownloadError {
+                    item_id: 1,
+                    description: f.to_string(),
+                    error: "HTTP 500".to_string(),
+                })
+                .collect(),
+        },
+        target,
+    );
+    let result = if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(DownloadsFailed(Service::Bandcamp).into())
+    };
+    report.add(bandcamp, &result);
+    report.finish(&result);
+    report
+}
+
+#[test]
+fn sessions_carry_the_backlog_forward() {
+    let target = temp_dir("sessions");
+    let mut state = Backfill::load(&target).unwrap();
+    assert!(state.sessions.is_empty());
+    assert!(!state.is_complete());
+
+    state.record_session(&bandcamp_session(
+        &target,
+        &["Low - Hey What"],
+        &["Grouper - Shade"],
+        &["Boards of Canada - Geogaddi", "Stereolab - Dots and Loops"],
+    ));
+    state.save(&target).unwrap();
+
+    let mut state = Backfill::load(&target).unwrap();
+    assert_eq!(state.downloaded(), 1);
+    assert_eq!(
+        state.backlog[&Service::Bandcamp],
+        [
+            "Grouper - Shade",
+            "Boards of Canada - Geogaddi",
+            "Stereolab - Dots and Loops"
+        ]
+    );
+    assert_eq!(state.sessions[0].deferred, 2);
+    assert!(!state.is_complete());
+
+    // A session that couldn't log in leaves the backlog alone.
+    let mut broken = SyncReport::new(&target, false);
+    let err = Err(AuthError(Service::Bandcamp).into());
+    broken.add(ServiceReport::new(Service::Bandcamp), &err);
+    broken.finish(&err);
+    state.record_session(&broken);
+    assert_eq!(state.remaining(), 3);
+
+    state.record_session(&bandcamp_session(
+        &target,
+        &[
+            "Grouper - Shade",
+            "Boards of Canada - Geogaddi",
+            "Stereolab - Dots and Loops",
+        ],
+        &[],
+        &[],
+    ));
+    assert_eq!(state.downloaded(), 4);
+    assert_eq!(state.sessions.len(), 3);
+    assert!(state.is_complete());
+
+    let _ = std::fs::remove_dir_all(&target);
+}
@@ -1,13 +1,18 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use qoget::bandcamp::{
-    BandcampPurchases, extract_single_track, is_zip_magic,
-    parse_zip_track_filename, to_purchase_list,
+    BandcampClient, BandcampPurchases, ExtractedTrack, StatStatus, check_collection_count,
+    extract_single_track, extract_zip, extracted_tracks, is_zip_magic, normalize_identity_cookie,
+    number_unknown_tracks, parse_collection_summary, parse_stat_response, parse_tags,
+    parse_tracklist, parse_zip_track_filename, stat_poll_delay, to_purchase_list,
 };
+use qoget::config::NetworkConfig;
 use qoget::models::{
     BandcampCollectionItem, BandcampCollectionResponse,
-    BandcampDownloadFormat, BandcampDownloadInfo,
+    BandcampDownloadFormat, BandcampDownloadInfo, GiftFilter, Service, Since, UnknownTrackNumbers,
 };
+use qoget::retry::RequestPolicy;
 
 // --- BandcampCollectionResponse deserialization ---
 
@@ -72,6 +77,7 @@ fn deserialize_download_info() {
         "title": "Album Title",
         "artist": "Artist Name",
         "download_type": "a",
+        "release_date": "17 Mar 2017 00:00:00 GMT",
         "downloads": {
             "aac-hi": { "url": "https://popplers5.bandcamp.com/download/album?enc=aac-hi&id=123", "size_mb": "90.5MB" },
             "mp3-320": { "url": "https://popplers5.bandcamp.com/download/album?enc=mp3-320&id=123", "size_mb": "120.1MB" },
@@ -89,6 +95,7 @@ fn deserialize_download_info() {
     assert!(info.downloads.contains_key("mp3-320"));
     assert!(info.downloads.contains_key("flac"));
     assert_eq!(info.downloads["aac-hi"].size_mb, "90.5MB");
+    assert_eq!(info.iso_release_date().as_deref(), Some("2017-03-17"));
 }
 
 // --- aac_hi_url extraction ---
@@ -117,6 +124,7 @@ fn aac_hi_url_found() {
         artist: "Artist".to_string(),
         download_type: "a".to_string(),
         downloads,
+        release_date: None,
     };
 
     let url = qoget::bandcamp::aac_hi_url(&info).unwrap();
@@ -140,6 +148,7 @@ fn aac_hi_url_missing() {
         artist: "Test Artist".to_string(),
         download_type: "a".to_string(),
         downloads,
+        release_date: None,
     };
 
     let err = qoget::bandcamp::aac_hi_url(&info).unwrap_err();
@@ -221,6 +230,10 @@ fn make_item(band: &str, title: &str, item_id: u64, sale_type: &str) -> Bandcamp
         sale_item_type: sale_type.to_string(),
         sale_item_id: item_id,
         token: "tok".to_string(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
     }
 }
 
@@ -247,6 +260,21 @@ fn to_purchase_list_albums() {
     assert_eq!(pl.albums[1].title, "Kodama");
 }
 
+#[test]
+fn to_purchase_list_album_art() {
+    let mut with_art = make_item("Deafheaven", "Sunbather", 100, "a");
+    with_art.item_art_id = Some(3056543911);
+    let purchases = BandcampPurchases {
+        items: vec![with_art, make_item("Alcest", "Kodama", 200, "a")],
+        redownload_urls: HashMap::new(),
+    };
+
+    let pl = to_purchase_list(&purchases);
+    let url = pl.albums[0].image.as_ref().and_then(|i| i.large.as_deref());
+    assert_eq!(url, Some("https://f4.bcbits.com/img/a3056543911_10.jpg"));
+    assert!(pl.albums[1].image.is_none());
+}
+
 #[test]
 fn to_purchase_list_tracks() {
     let purchases = BandcampPurchases {
@@ -279,6 +307,61 @@ fn to_purchase_list_mixed() {
     assert_eq!(pl.tracks.len(), 1);
 }
 
+#[test]
+fn to_purchase_list_subscription_items() {
+    let mut album = make_item("Band", "Members Album", 500, "s");
+    album.item_type = "album".to_string();
+    album.sale_item_id = 77;
+    let mut track = make_item("Band", "Members Single", 501, "s");
+    track.sale_item_id = 77;
+    let purchases = BandcampPurchases {
+        items: vec![album, track],
+        redownload_urls: HashMap::new(),
+    };
+
+    let pl = to_purchase_list(&purchases);
+    assert_eq!(pl.albums.len(), 1);
+    assert_eq!(pl.albums[0].id.0, "bc-500");
+    assert_eq!(
+        pl.albums[0].purchase.purchase_channel.as_deref(),
+        Some("subscription")
+    );
+    assert!(!pl.albums[0].purchase.is_gift());
+    assert_eq!(pl.tracks.len(), 1);
+    assert_eq!(pl.tracks[0].id.0, 501);
+}
+
+#[test]
+fn subscription_items_have_their_own_redownload_urls() {
+    let mut album = make_item("Band", "Members Album", 500, "s");
+    album.item_type = "album".to_string();
+    album.sale_item_id = 77;
+    let mut track = make_item("Band", "Members Single", 501, "s");
+    track.sale_item_id = 77;
+    let bought = make_item("Band", "Bought", 600, "a");
+    let url = |id: u64| format!("https://bandcamp.com/download?id={id}");
+    let mut purchases = BandcampPurchases {
+        items: vec![album.clone(), track.clone(), bought.clone()],
+        redownload_urls: HashMap::from([
+            ("a500".to_string(), url(500)),
+            ("t501".to_string(), url(501)),
+            ("a600".to_string(), url(600)),
+            ("s77".to_string(), url(77)),
+        ]),
+    };
+    assert_eq!(album.redownload_key(), "a500");
+    assert_eq!(purchases.redownload_url(&album), Some(url(500).as_str()));
+    assert_eq!(purchases.redownload_url(&track), Some(url(501).as_str()));
+    assert_eq!(purchases.redownload_url(&bought), Some(url(600).as_str()));
+
+    // The subscription's own page only stands in for a lone item; shared,
+    // it would download one item for all of them.
+    purchases.redownload_urls.remove("t501");
+    assert_eq!(purchases.redownload_url(&track), None);
+    purchases.items.remove(0);
+    assert_eq!(purchases.redownload_url(&track), Some(url(77).as_str()));
+}
+
 #[test]
 fn to_purchase_list_unknown_type_skipped() {
     let purchases = BandcampPurchases {
@@ -339,3 +422,570 @@ fn bug_001_html_bytes_are_not_zip() {
     );
 }
 
+
+// --- ZIP extraction ---
+
+#[test]
+fn extract_zip_from_file() {
+    use std::io::Write as _;
+
+    let temp_dir = std::env::temp_dir().join("qoget_test_extract_zip");
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let zip_path = temp_dir.join("album.zip");
+    {
+        let file = std::fs::File::create(&zip_path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        let opts = zip::write::SimpleFileOptions::default();
+        zip.start_file("Artist - Album - 02 Second.m4a", opts).unwrap();
+        zip.write_all(b"two").unwrap();
+        zip.start_file("Artist - Album - 01 First.m4a", opts).unwrap();
+        zip.write_all(b"one").unwrap();
+        zip.start_file("cover.jpg", opts).unwrap();
+        zip.write_all(b"jpg").unwrap();
+        zip.finish().unwrap();
+    }
+
+    let tracks = extract_zip(&zip_path, &temp_dir).unwrap();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0].track_number, 1);
+    assert_eq!(tracks[0].title, "First");
+    assert_eq!(tracks[0].position, 2);
+    assert_eq!(std::fs::read(&tracks[0].temp_path).unwrap(), b"one");
+    assert_eq!(tracks[1].title, "Second");
+    for track in &tracks {
+        assert_eq!(
+            track.sha256,
+            qoget::manifest::file_sha256(&track.temp_path).unwrap()
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// An M4A holding just the iTunes items `items`, as (atom, value).
+fn tagged_m4a(items: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut ilst = Vec::new();
+    for (kind, value) in items {
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(value);
+        ilst.extend(atom(kind, &atom(b"data", &data)));
+    }
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"ilst", &ilst));
+    let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+    file.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+    file
+}
+
+#[test]
+fn extracted_tracks_are_named_by_their_tags() {
+    use std::io::Write as _;
+
+    let temp_dir = std::env::temp_dir().join("qoget_test_extract_tagged");
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let zip_path = temp_dir.join("album.zip");
+    {
+        let file = std::fs::File::create(&zip_path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        let opts = zip::write::SimpleFileOptions::default();
+        // Bandcamp strips what file names can't hold.
+        zip.start_file("Band - Album - 01 Duet Fe.m4a", opts).unwrap();
+        zip.write_all(&tagged_m4a(&[
+            (b"\xa9nam", "Duet: Fée".as_bytes()),
+            (b"\xa9ART", b"Guest"),
+            (b"trkn", &[0, 0, 0, 1, 0, 2, 0, 0]),
+            (b"disk", &[0, 0, 0, 2, 0, 2]),
+        ]))
+        .unwrap();
+        zip.start_file("Band - Album - 01 Intro.m4a", opts).unwrap();
+        zip.write_all(&tagged_m4a(&[(b"\xa9ART", b"band")])).unwrap();
+        zip.start_file("Band - Album - 02 Untagged.m4a", opts).unwrap();
+        zip.write_all(b"two").unwrap();
+        zip.finish().unwrap();
+    }
+
+    let extracted = extract_zip(&zip_path, &temp_dir).unwrap();
+    let item = collection_item("album", 7);
+    let album = qoget::download::item_album(&item);
+    let tracks: Vec<_> = extracted_tracks(&item, &album, &extracted)
+        .into_iter()
+        .map(|t| (t.media_number.0, t.track_number.0, t.title, t.performer.name))
+        .collect();
+    assert_eq!(
+        tracks,
+        [
+            (1, 1, "Intro".to_string(), "Band".to_string()),
+            (1, 2, "Untagged".to_string(), "Band".to_string()),
+            (2, 1, "Duet: Fée".to_string(), "Guest".to_string()),
+        ]
+    );
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn extracted_track_ids_follow_disc_and_number_not_zip_order() {
+    use std::io::Write as _;
+
+    let temp_dir = std::env::temp_dir().join("qoget_test_extract_two_discs");
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    // (disc, track) of each file, in the order the ZIP holds them.
+    let ids = |order: &[(u8, u8)]| {
+        let zip_path = temp_dir.join("album.zip");
+        {
+            let file = std::fs::File::create(&zip_path).unwrap();
+            let mut zip = zip::ZipWriter::new(file);
+            let opts = zip::write::SimpleFileOptions::default();
+            for &(disc, number) in order {
+                zip.start_file(format!("Band - Album - {disc}-{number:02} Track.m4a"), opts)
+                    .unwrap();
+                zip.write_all(&tagged_m4a(&[
+                    (b"trkn", &[0, 0, 0, number, 0, 2, 0, 0]),
+                    (b"disk", &[0, 0, 0, disc, 0, 2]),
+                ]))
+                .unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let extracted = extract_zip(&zip_path, &temp_dir).unwrap();
+        let item = collection_item("album", 7);
+        let album = qoget::download::item_album(&item);
+        let mut ids: Vec<_> = extracted_tracks(&item, &album, &extracted)
+            .into_iter()
+            .map(|t| ((t.media_number.0, t.track_number.0), t.id.0))
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    let ordered = ids(&[(1, 1), (1, 2), (2, 1), (2, 2)]);
+    assert_eq!(
+        ordered,
+        [
+            ((1, 1), 7001),
+            ((1, 2), 7002),
+            ((2, 1), 7201),
+            ((2, 2), 7202),
+        ]
+    );
+    assert_eq!(ids(&[(2, 2), (1, 2), (2, 1), (1, 1)]), ordered);
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+// --- Collection summary / count validation ---
+
+fn collection_item(item_type: &str, item_id: u64) -> BandcampCollectionItem {
+    BandcampCollectionItem {
+        band_name: "Band".to_string(),
+        item_title: format!("Item {item_id}"),
+        item_id,
+        item_type: item_type.to_string(),
+        sale_item_type: item_type[..1].to_string(),
+        sale_item_id: item_id,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
+    }
+}
+
+#[test]
+fn parse_collection_summary_counts_items() {
+    let json = r#"{
+        "fan_id": 42,
+        "collection_summary": {
+            "fan_id": 42,
+            "username": "someone",
+            "tralbum_lookup": {
+                "a1": {"item_type": "a", "item_id": 1},
+                "t2": {"item_type": "t", "item_id": 2}
+            }
+        }
+    }"#;
+    let summary = parse_collection_summary(json).unwrap();
+    assert_eq!(summary.fan_id, 42);
+    assert_eq!(summary.item_count, Some(2));
+
+    let bare = parse_collection_summary(r#"{"fan_id": 7}"#).unwrap();
+    assert_eq!(bare.fan_id, 7);
+    assert_eq!(bare.item_count, None);
+}
+
+#[test]
+fn collection_count_mismatch_warns() {
+    let json = r#"{"fan_id": 1, "collection_summary": {"tralbum_lookup":
+        {"a1": {}, "a2": {}, "t3": {}}}}"#;
+    let summary = parse_collection_summary(json).unwrap();
+
+    let all = vec![
+        collection_item("album", 1),
+        collection_item("album", 2),
+        collection_item("track", 3),
+    ];
+    assert!(check_collection_count(&summary, &all).is_none());
+
+    let truncated = &all[..2];
+    let warning = check_collection_count(&summary, truncated).unwrap();
+    assert!(warning.contains("reports 3"), "{warning}");
+    assert!(warning.contains("2 were fetched"), "{warning}");
+
+    // Same item seen in both visible and hidden lists counts once
+    let mut dup = all.clone();
+    dup.push(collection_item("album", 1));
+    assert!(check_collection_count(&summary, &dup).is_none());
+}
+
+#[test]
+fn collection_count_unknown_is_not_checked() {
+    let summary = parse_collection_summary(r#"{"fan_id": 1}"#).unwrap();
+    assert!(check_collection_count(&summary, &[]).is_none());
+}
+
+// --- Gift classification ---
+
+#[test]
+fn deserialize_gifted_item() {
+    let json = r#"{
+        "band_name": "Band",
+        "item_title": "Present",
+        "item_id": 5,
+        "item_type": "album",
+        "sale_item_type": "a",
+        "sale_item_id": 5,
+        "token": "t",
+        "gift_id": 987,
+        "gift_sender_name": "A Friend"
+    }"#;
+    let item: BandcampCollectionItem = serde_json::from_str(json).unwrap();
+    assert!(item.is_gift());
+    assert_eq!(item.gift_sender_name.as_deref(), Some("A Friend"));
+
+    let purchases = BandcampPurchases {
+        items: vec![item, make_item("Band", "Bought", 6, "a")],
+        redownload_urls: HashMap::new(),
+    };
+    let list = to_purchase_list(&purchases);
+    assert!(list.albums[0].purchase.is_gift());
+    assert!(!list.albums[1].purchase.is_gift());
+}
+
+#[test]
+fn retain_gifts_filters_items() {
+    let mut gifted = make_item("Band", "Present", 5, "a");
+    gifted.gift_id = Some(1);
+    let all = BandcampPurchases {
+        items: vec![gifted, make_item("Band", "Bought", 6, "t")],
+        redownload_urls: HashMap::new(),
+    };
+
+    let mut only = BandcampPurchases {
+        items: all.items.clone(),
+        redownload_urls: HashMap::new(),
+    };
+    only.retain_gifts(GiftFilter::OnlyGifts);
+    assert_eq!(only.items.len(), 1);
+    assert_eq!(only.items[0].item_title, "Present");
+
+    let mut excluded = all;
+    excluded.retain_gifts(GiftFilter::ExcludeGifts);
+    assert_eq!(excluded.items.len(), 1);
+    assert_eq!(excluded.items[0].item_title, "Bought");
+}
+
+// --- Single items for `get` ---
+
+#[test]
+fn select_page_matches_item_url_loosely() {
+    let mut wanted = make_item("Low", "Hey What", 5, "a");
+    wanted.item_url = Some("https://low.bandcamp.com/album/hey-what".to_string());
+    let mut purchases = BandcampPurchases {
+        items: vec![make_item("Low", "Double Negative", 6, "a"), wanted],
+        redownload_urls: HashMap::new(),
+    };
+    purchases
+        .select_page("http://Low.Bandcamp.com/album/hey-what/?from=search")
+        .unwrap();
+    assert_eq!(purchases.items.len(), 1);
+    assert_eq!(purchases.items[0].item_title, "Hey What");
+
+    let err = purchases
+        .select_page("https://low.bandcamp.com/album/things-we-lost")
+        .unwrap_err();
+    assert!(err.to_string().contains("not in the Bandcamp collection"));
+}
+
+#[test]
+fn select_download_falls_back_to_the_download_page() {
+    let url = "https://bandcamp.com/download?from=receipt&payment_id=9&sig=abc";
+    let info = BandcampDownloadInfo {
+        item_id: 77,
+        title: "Single".to_string(),
+        artist: "Guest".to_string(),
+        download_type: "t".to_string(),
+        downloads: HashMap::new(),
+        release_date: None,
+    };
+    let mut purchases = BandcampPurchases {
+        items: vec![make_item("Band", "Album", 5, "a")],
+        redownload_urls: HashMap::new(),
+    };
+    purchases.select_download(&info, url);
+    assert_eq!(purchases.items.len(), 1);
+    let item = &purchases.items[0];
+    assert_eq!((item.band_name.as_str(), item.item_id), ("Guest", 77));
+    assert_eq!(item.is_track(), Some(true));
+    assert_eq!(purchases.redownload_urls["t77"], url);
+
+    // In the collection, the collection's own entry is kept.
+    let mut purchases = BandcampPurchases {
+        items: vec![make_item("Guest", "Single", 77, "t")],
+        redownload_urls: HashMap::new(),
+    };
+    purchases.select_download(&info, url);
+    assert_eq!(purchases.items[0].token, "tok");
+    assert!(purchases.redownload_urls.is_empty());
+}
+
+// --- Purchase dates ---
+
+#[test]
+fn purchase_date_comes_from_token() {
+    let mut old = make_item("Band", "Old", 5, "a");
+    old.token = "1600000000:5:a::".to_string();
+    let mut new = make_item("Band", "New", 6, "a");
+    new.token = "1700000000:6:a::".to_string();
+    let unknown = make_item("Band", "Unknown", 7, "a");
+
+    assert_eq!(old.purchased_at(), Some(1_600_000_000));
+    assert_eq!(new.purchase_info().purchased_at, Some(1_700_000_000));
+    assert_eq!(unknown.purchased_at(), None);
+
+    let mut purchases = BandcampPurchases {
+        items: vec![old, new, unknown],
+        redownload_urls: HashMap::new(),
+    };
+    purchases.retain_since(&Since::parse_at("2023-01-01", 0).unwrap());
+    let titles: Vec<&str> = purchases
+        .items
+        .iter()
+        .map(|i| i.item_title.as_str())
+        .collect();
+    assert_eq!(titles, ["New", "Unknown"]);
+}
+
+// --- Identity cookie ---
+
+const COOKIE: &str =
+    "7%09AbCdEf%2Bgh%2F%3D%09%7B%22id%22%3A123456%2C%22ex%22%3A0%7D";
+
+#[test]
+fn identity_cookie_canonical_value_unchanged() {
+    assert_eq!(normalize_identity_cookie(COOKIE).unwrap(), COOKIE);
+}
+
+#[test]
+fn new_client_has_no_refreshed_identity() {
+    // The jar hands back the cookie the client was made with, which isn't
+    // mistaken for one Bandcamp rotated.
+    let client = BandcampClient::new(
+        format!("identity={COOKIE}"),
+        RequestPolicy::default_for(Service::Bandcamp),
+        &NetworkConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(client.refreshed_identity(), None);
+}
+
+#[test]
+fn identity_cookie_pasted_forms_normalize() {
+    let decoded = "7\tAbCdEf+gh/=\t{\"id\":123456,\"ex\":0}";
+    let double = COOKIE.replace('%', "%25");
+    let pasted = [
+        format!("  {COOKIE}\n"),
+        format!("\"{COOKIE}\""),
+        format!("identity={COOKIE}"),
+        format!("Cookie: client_id=abc; identity={COOKIE}; js_logged_in=1"),
+        decoded.to_string(),
+        double,
+    ];
+    for raw in &pasted {
+        assert_eq!(
+            normalize_identity_cookie(raw).unwrap(),
+            COOKIE,
+            "from {raw:?}"
+        );
+    }
+}
+
+#[test]
+fn identity_cookie_rejects_other_values() {
+    for raw in ["", "abc123", "client_id=abc; js_logged_in=1", "7%09sig"] {
+        assert!(normalize_identity_cookie(raw).is_err(), "{raw:?}");
+    }
+    let err = normalize_identity_cookie("hello").unwrap_err();
+    assert!(format!("{err}").contains("identity"));
+}
+
+// --- Download preparation ---
+
+#[test]
+fn stat_response_ready_uses_original_url() {
+    let body = "var _statDL_result = { result: 'ok', retry_url: '' };";
+    assert_eq!(parse_stat_response(body).unwrap(), StatStatus::Ready(None));
+    let body = r#"{"result":"ok","download_url":"https://p4.bcbits.com/x"}"#;
+    assert_eq!(parse_stat_response(body).unwrap(), StatStatus::Ready(None));
+}
+
+#[test]
+fn stat_response_with_cdn_url() {
+    let body =
+        r#"var _statDL_result = {"download_url":"https://p4.bcbits.com/download/album/abc"};"#;
+    assert_eq!(
+        parse_stat_response(body).unwrap(),
+        StatStatus::Ready(Some("https://p4.bcbits.com/download/album/abc".to_string()))
+    );
+}
+
+#[test]
+fn stat_response_still_preparing() {
+    let body = r#"{"result":"preparing","retry_url":"https://bandcamp.com/statdownload/x"}"#;
+    assert_eq!(parse_stat_response(body).unwrap(), StatStatus::Pending);
+    let body = r#"{"retry_url":"https://bandcamp.com/statdownload/x"}"#;
+    assert_eq!(parse_stat_response(body).unwrap(), StatStatus::Pending);
+}
+
+#[test]
+fn stat_response_errors() {
+    let body = r#"{"result":"err","errortype":"ExpiredFreeDownloadError"}"#;
+    let err = parse_stat_response(body).unwrap_err().to_string();
+    assert!(err.contains("ExpiredFreeDownloadError"), "{err}");
+    assert!(parse_stat_response("<html>Service unavailable</html>").is_err());
+    // The page quoted in the error is cut short between characters.
+    let page = format!("<html> {}</html>", "é".repeat(400));
+    assert!(parse_stat_response(&page).is_err());
+}
+
+#[test]
+fn stat_polling_backs_off_to_a_cap() {
+    let delays: Vec<Duration> = (0..6).map(stat_poll_delay).collect();
+    assert_eq!(delays, [2, 4, 8, 16, 30, 30].map(Duration::from_secs));
+    assert_eq!(stat_poll_delay(u32::MAX), Duration::from_secs(30));
+}
+
+// --- Album page tags ---
+
+#[test]
+fn parse_tags_in_page_order() {
+    let html = r#"
+        <div class="tralbumData tralbum-tags">
+            <a class="tag" href="https://bandcamp.com/discover/post-rock?from=tralbum">post-rock</a>
+            <a class="tag" href="https://bandcamp.com/discover/ambient?from=tralbum">ambient</a>
+            <a class="tag" href="https://bandcamp.com/discover/drum-bass?from=tralbum">drum &amp; bass</a>
+            <a class="tag" href="https://bandcamp.com/discover/ambient?from=tralbum">ambient</a>
+        </div>"#;
+    assert_eq!(parse_tags(html), ["post-rock", "ambient", "drum & bass"]);
+    assert!(parse_tags("<html></html>").is_empty());
+}
+
+// --- Unknown track numbers ---
+
+#[test]
+fn parse_tracklist_from_tralbum_blob() {
+    let html = r#"<script data-band="{}" data-tralbum="{&quot;trackinfo&quot;:[
+        {&quot;track_num&quot;:1,&quot;title&quot;:&quot;Heavy Water/I&#39;d Rather Be Sleeping&quot;},
+        {&quot;track_num&quot;:2,&quot;title&quot;:&quot;Alien Observer&quot;},
+        {&quot;track_num&quot;:null,&quot;title&quot;:&quot;Bonus&quot;}
+    ]}"></script>"#;
+    assert_eq!(
+        parse_tracklist(html),
+        [
+            (1, "Heavy Water/I'd Rather Be Sleeping".to_string()),
+            (2, "Alien Observer".to_string()),
+        ]
+    );
+    assert!(parse_tracklist("<html></html>").is_empty());
+}
+
+fn extracted(number: u8, title: &str, position: usize) -> ExtractedTrack {
+    ExtractedTrack {
+        track_number: number,
+        title: title.to_string(),
+        disc_number: 1,
+        artist: None,
+        position,
+        temp_path: format!("/tmp/bc_extract_{position}.m4a").into(),
+        sha256: String::new(),
+    }
+}
+
+fn numbering(tracks: &[ExtractedTrack]) -> Vec<(u8, &str)> {
+    tracks
+        .iter()
+        .map(|t| (t.track_number, t.title.as_str()))
+        .collect()
+}
+
+#[test]
+fn unknown_track_numbers_follow_policy() {
+    let album = || {
+        vec![
+            extracted(0, "Alien Observer", 1),
+            extracted(1, "Dragonfly", 2),
+            extracted(0, "Heavy Water-I'd Rather Be Sleeping", 3),
+        ]
+    };
+    let tracklist = [
+        (1, "Dragonfly".to_string()),
+        (2, "Heavy Water/I'd Rather Be Sleeping".to_string()),
+        (3, "Alien Observer".to_string()),
+    ];
+
+    // ZIP order: position 1 is taken by a numbered track, so the first
+    // unnumbered one gets the next free number.
+    let mut tracks = album();
+    number_unknown_tracks(&mut tracks, UnknownTrackNumbers::ZipOrder, &tracklist);
+    assert_eq!(
+        numbering(&tracks),
+        [
+            (1, "Dragonfly"),
+            (2, "Alien Observer"),
+            (3, "Heavy Water-I'd Rather Be Sleeping"),
+        ]
+    );
+
+    let mut tracks = album();
+    number_unknown_tracks(&mut tracks, UnknownTrackNumbers::Tracklist, &tracklist);
+    assert_eq!(
+        numbering(&tracks),
+        [
+            (1, "Dragonfly"),
+            (2, "Heavy Water-I'd Rather Be Sleeping"),
+            (3, "Alien Observer"),
+        ]
+    );
+
+    // No tracklist to go on: same as ZIP order.
+    let mut tracks = album();
+    number_unknown_tracks(&mut tracks, UnknownTrackNumbers::Tracklist, &[]);
+    assert_eq!(tracks[1].title, "Alien Observer");
+
+    let mut tracks = album();
+    number_unknown_tracks(&mut tracks, UnknownTrackNumbers::Omit, &tracklist);
+    assert_eq!(tracks[0].track_number, 0);
+    assert_eq!(tracks[2].track_number, 0);
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use qoget::beets::{Import, import_args, new_album_dirs, summary};
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{Album, AudioFormat, Service, TrackId};
+use qoget::report::ServiceReport;
+
+mod common;
+
+fn entry(path: &str) -> ManifestEntry {
+    let album = Album {
+        tracks_count: 1,
+        ..common::album("a1", "Album", "Artist")
+    };
+    let track = common::track(1, "One", 1, "Artist");
+    ManifestEntry::new(
+        &album,
+        &track,
+        AudioFormat::Mp3,
+        Path::new(""),
+        Path::new(path),
+    )
+}
+
+#[test]
+fn only_albums_new_to_the_manifest_are_imported() {
+    let mut before = Manifest::default();
+    before.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Low/Hey What/01 - White Horses.mp3"),
+    );
+    let new_tracks = [
+        "Low/Hey What/02 - I Can Wait.mp3",
+        "Grouper/Shade/01 - Followed the Ocean.m4a",
+        "Grouper/Shade/02 - Unclean Mind.m4a",
+        "Bach/Mass in B minor/Disc 2/01 - Credo.mp3",
+    ]
+    .map(PathBuf::from);
+
+    let dirs = new_album_dirs(&before, new_tracks);
+    assert_eq!(
+        dirs,
+        BTreeSet::from([
+            PathBuf::from("Bach/Mass in B minor"),
+            PathBuf::from("Grouper/Shade"),
+        ])
+    );
+    assert_eq!(
+        import_args(Path::new("/music/Grouper/Shade")),
+        ["import", "-A", "-q", "/music/Grouper/Shade"]
+    );
+}
+
+#[test]
+fn imports_are_summarized_and_reported() {
+    let imports = [
+        Import {
+            dir: PathBuf::from("Grouper/Shade"),
+            error: None,
+        },
+        Import {
+            dir: PathBuf::from("Low/Hey What"),
+            error: Some("beet import failed (exit status: 1): database is locked".to_string()),
+        },
+    ];
+    assert_eq!(summary(&imports), "Beets: 1 albums imported, 1 failed");
+
+    let mut report = ServiceReport::new(Service::Bandcamp);
+    report.add_beets(&imports);
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["beets"][0]["path"], "Grouper/Shade");
+    assert!(json["beets"][0].get("error").is_none());
+    assert_eq!(json["beets"][1]["name"], "Low/Hey What");
+    assert!(
+        json["beets"][1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("database is locked")
+    );
+
+    let json = serde_json::to_value(ServiceReport::new(Service::Qobuz)).unwrap();
+    assert!(json.get("beets").is_none());
+}
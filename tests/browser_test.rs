@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use qoget::browser::{Browser, read_chromium_cookie, read_firefox_cookie};
+use rusqlite::Connection;
+
+fn temp_db(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-browser-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("cookies.sqlite")
+}
+
+#[test]
+fn firefox_identity_cookie_is_read() {
+    let db = temp_db("firefox");
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE moz_cookies (name TEXT, value TEXT, host TEXT, lastAccessed INTEGER);
+         INSERT INTO moz_cookies VALUES ('identity', '7%09old%09%7B%7D', '.bandcamp.com', 1000000);
+         INSERT INTO moz_cookies VALUES ('identity', '7%09new%09%7B%7D', '.bandcamp.com', 9000000);
+         INSERT INTO moz_cookies VALUES ('identity', 'other', '.example.com', 99000000);
+         INSERT INTO moz_cookies VALUES ('session', 'x', '.bandcamp.com', 99000000);",
+    )
+    .unwrap();
+    drop(conn);
+
+    let found = read_firefox_cookie(&db).unwrap().unwrap();
+    assert_eq!(found.value, "7%09new%09%7B%7D");
+    assert_eq!(found.last_used, 9);
+
+    std::fs::remove_dir_all(db.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn firefox_without_bandcamp_login_finds_nothing() {
+    let db = temp_db("firefox-empty");
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE moz_cookies (name TEXT, value TEXT, host TEXT, lastAccessed INTEGER);",
+    )
+    .unwrap();
+    drop(conn);
+
+    assert!(read_firefox_cookie(&db).unwrap().is_none());
+
+    std::fs::remove_dir_all(db.parent().unwrap()).unwrap();
+}
+
+/// Linux Chrome `v10` cookies are encrypted with a key derived from the
+/// fixed password "peanuts".
+#[cfg(target_os = "linux")]
+#[test]
+fn chrome_v10_cookie_is_decrypted() {
+    use aes::cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+    use sha2::{Digest, Sha256};
+
+    let mut key = [0u8; 16];
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(b"peanuts", b"saltysalt", 1, &mut key);
+    let mut plaintext = Sha256::digest(b".bandcamp.com").to_vec();
+    plaintext.extend_from_slice(b"7%09abc%09%7B%7D");
+    let mut encrypted = b"v10".to_vec();
+    encrypted.extend(
+        cbc::Encryptor::<aes::Aes128>::new(&key.into(), &[b' '; 16].into())
+            .encrypt_padded_vec_mut::<Pkcs7>(&plaintext),
+    );
+
+    let db = temp_db("chrome");
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE meta (key TEXT, value TEXT);
+         INSERT INTO meta VALUES ('version', '24');
+         CREATE TABLE cookies (name TEXT, value TEXT, encrypted_value BLOB, host_key TEXT,
+                               last_access_utc INTEGER);",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO cookies VALUES ('identity', '', ?1, '.bandcamp.com', 13380000000000000)",
+        [&encrypted],
+    )
+    .unwrap();
+    drop(conn);
+
+    let found = read_chromium_cookie(&db, Browser::Chrome).unwrap().unwrap();
+    assert_eq!(found.value, "7%09abc%09%7B%7D");
+
+    std::fs::remove_dir_all(db.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn browser_names_parse() {
+    assert_eq!("Firefox".parse::<Browser>(), Ok(Browser::Firefox));
+    assert_eq!("chrome".parse::<Browser>(), Ok(Browser::Chrome));
+    assert!("safari".parse::<Browser>().is_err());
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use qoget::bundle::{CREDENTIALS_TTL, CachedCredentials};
+
+fn cached(validated_at: u64) -> CachedCredentials {
+    CachedCredentials {
+        app_id: "123456789".to_string(),
+        app_secret: "abcdef".to_string(),
+        validated_at,
+    }
+}
+
+#[test]
+fn freshness_follows_ttl() {
+    let ttl = Duration::from_secs(100);
+    assert!(cached(1_000).is_fresh(1_050, ttl));
+    assert!(!cached(1_000).is_fresh(1_100, ttl));
+    // Clock went backwards: still fresh rather than underflowing
+    assert!(cached(1_000).is_fresh(900, ttl));
+    assert!(CREDENTIALS_TTL >= Duration::from_secs(24 * 60 * 60));
+}
+
+#[test]
+fn cache_round_trip() {
+    let dir = std::env::temp_dir().join(format!("qoget-bundle-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(CachedCredentials::load(&dir).unwrap(), None);
+    cached(42).save(&dir).unwrap();
+    assert_eq!(CachedCredentials::load(&dir).unwrap(), Some(cached(42)));
+
+    std::fs::write(CachedCredentials::path(&dir), "not json").unwrap();
+    assert!(CachedCredentials::load(&dir).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use qoget::checksums::{CHECKSUM_FILE, changed_dirs, parse, refresh, render};
+use qoget::manifest::{Manifest, ManifestEntry, file_sha256};
+use qoget::models::{Album, AudioFormat, Service, TrackId};
+
+mod common;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-sums-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn entry(path: &str, sha256: Option<&str>) -> ManifestEntry {
+    let album = Album {
+        tracks_count: 1,
+        ..common::album("a1", "Album", "Artist")
+    };
+    let track = common::track(1, "One", 1, "Artist");
+    let mut e = ManifestEntry::new(
+        &album,
+        &track,
+        AudioFormat::Mp3,
+        Path::new(""),
+        Path::new(path),
+    );
+    e.sha256 = sha256.map(str::to_string);
+    e
+}
+
+#[test]
+fn sha256sum_format_round_trips() {
+    let sums = BTreeMap::from([
+        ("01 - One.mp3".to_string(), "a".repeat(64)),
+        ("02 - Two  Spaces.mp3".to_string(), "b".repeat(64)),
+    ]);
+    let rendered = render(&sums);
+    assert_eq!(
+        rendered.lines().next(),
+        Some(format!("{}  01 - One.mp3", "a".repeat(64)).as_str())
+    );
+    assert_eq!(parse(&rendered), sums);
+
+    // Binary mode, uppercase digests, comments and junk
+    let other = format!(
+        "# made by hand\n{} *03 - Three.flac\nnot a checksum\n",
+        "C".repeat(64)
+    );
+    assert_eq!(
+        parse(&other),
+        BTreeMap::from([("03 - Three.flac".to_string(), "c".repeat(64))])
+    );
+}
+
+#[test]
+fn changed_dirs_cover_added_moved_rehashed_and_removed_entries() {
+    let mut before = Manifest::default();
+    before.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("A/Same/01.mp3", Some("1")),
+    );
+    before.record(Service::Qobuz, TrackId(2), entry("A/Old/02.mp3", Some("2")));
+    before.record(
+        Service::Qobuz,
+        TrackId(3),
+        entry("A/Tagged/03.mp3", Some("3")),
+    );
+    before.record(
+        Service::Qobuz,
+        TrackId(4),
+        entry("A/Gone/04.mp3", Some("4")),
+    );
+
+    let mut after = before.clone();
+    after.relocate("qobuz:2", PathBuf::from("A/New/02.mp3"));
+    after.record(
+        Service::Qobuz,
+        TrackId(3),
+        entry("A/Tagged/03.mp3", Some("33")),
+    );
+    after.remove("qobuz:4");
+    after.record(
+        Service::Qobuz,
+        TrackId(5),
+        entry("A/Added/05.mp3", Some("5")),
+    );
+
+    let dirs: BTreeSet<PathBuf> = ["A/Added", "A/Gone", "A/New", "A/Old", "A/Tagged"]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    assert_eq!(changed_dirs(&before, &after), dirs);
+}
+
+#[test]
+fn refresh_writes_recorded_checksums_and_removes_stale_files() {
+    let dir = temp_dir("refresh");
+    let album = dir.join("Artist/Album");
+    std::fs::create_dir_all(&album).unwrap();
+    for name in ["01 - One.mp3", "02 - Two.mp3", "cover.jpg"] {
+        std::fs::write(album.join(name), name).unwrap();
+    }
+    let sha = |name: &str| file_sha256(&album.join(name)).unwrap();
+    let mut manifest = Manifest::default();
+    for (id, name) in [(1, "01 - One.mp3"), (2, "02 - Two.mp3")] {
+        manifest.record(
+            Service::Qobuz,
+            TrackId(id),
+            entry(&format!("Artist/Album/{name}"), Some(&sha(name))),
+        );
+    }
+    // Recorded without a checksum, so not listed
+    manifest.record(
+        Service::Qobuz,
+        TrackId(3),
+        entry("Artist/Album/03 - Three.mp3", None),
+    );
+
+    let dirs = || [PathBuf::from("Artist/Album")];
+    assert_eq!(refresh(&dir, &manifest, dirs()).unwrap(), 1);
+    let written = std::fs::read_to_string(album.join(CHECKSUM_FILE)).unwrap();
+    assert_eq!(
+        written,
+        format!(
+            "{}  01 - One.mp3\n{}  02 - Two.mp3\n",
+            sha("01 - One.mp3"),
+            sha("02 - Two.mp3")
+        )
+    );
+    // Unchanged, so not rewritten
+    assert_eq!(refresh(&dir, &manifest, dirs()).unwrap(), 0);
+
+    manifest.remove("qobuz:1");
+    manifest.remove("qobuz:2");
+    refresh(&dir, &manifest, dirs()).unwrap();
+    assert!(!album.join(CHECKSUM_FILE).exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{AudioFormat, Service, TrackId};
+
+fn qoget(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_qoget"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn completions_cover_subcommands() {
+    let out = qoget(&["completions", "bash"]);
+    assert!(out.status.success());
+    let script = String::from_utf8(out.stdout).unwrap();
+    for sub in ["sync", "daemon", "verify", "login"] {
+        assert!(script.contains(sub), "missing {sub}");
+    }
+
+    assert!(!qoget(&["completions", "tcsh"]).status.success());
+}
+
+#[test]
+fn manpage_renders_roff() {
+    let out = qoget(&["manpage"]);
+    assert!(out.status.success());
+    let page = String::from_utf8(out.stdout).unwrap();
+    assert!(page.starts_with(".ie \\n(.g .ds Aq"), "{}", &page[..40]);
+    assert!(page.contains("qoget"));
+}
+
+#[test]
+fn manpage_out_dir_writes_a_page_per_subcommand() {
+    let dir = std::env::temp_dir().join(format!("qoget-man-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let out = qoget(&["manpage", "--out-dir", dir.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert!(dir.join("qoget.1").exists());
+    assert!(dir.join("qoget-sync.1").exists());
+    assert!(dir.join("qoget-trash-empty.1").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verbosity_flags_are_global() {
+    assert!(qoget(&["-q", "completions", "bash"]).status.success());
+    assert!(qoget(&["completions", "bash", "-vv"]).status.success());
+
+    let out = qoget(&["-v", "--quiet", "completions", "bash"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn log_file_records_debug_events_when_quiet() {
+    let dir = std::env::temp_dir().join(format!("qoget-log-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let log = dir.join("logs/qoget.log");
+
+    let args = [
+        "-q",
+        "--log-file",
+        log.to_str().unwrap(),
+        "completions",
+        "bash",
+    ];
+    let out = qoget(&args);
+    assert!(out.status.success());
+    assert!(
+        out.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("DEBUG"), "{contents}");
+    assert!(contents.contains("started"), "{contents}");
+
+    assert!(qoget(&args).status.success());
+    let appended = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(appended.matches("started").count(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prune_refuses_narrowed_syncs() {
+    for narrowing in [["--artist", "x"], ["--since", "30d"]] {
+        let mut args = vec!["sync", "/nonexistent", "--prune"];
+        args.extend(narrowing);
+        let out = qoget(&args);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("cannot be used with"), "{stderr}");
+    }
+}
+
+#[test]
+fn get_rejects_links_that_are_not_albums_or_tracks() {
+    let out = qoget(&[
+        "get",
+        "https://www.qobuz.com/us-en/interpreter/miles-davis/1234",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("not a Qobuz album or track"), "{stderr}");
+}
+
+#[test]
+fn retry_without_failures_has_nothing_to_do() {
+    let state = std::env::temp_dir().join(format!("qoget-state-{}", std::process::id()));
+    let out = Command::new(env!("CARGO_BIN_EXE_qoget"))
+        .arg("retry")
+        .env("XDG_STATE_HOME", &state)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("No failed downloads to retry"), "{stderr}");
+}
+
+#[test]
+fn search_with_only_a_query_uses_the_configured_target() {
+    let root = std::env::temp_dir().join(format!("qoget-search-{}", std::process::id()));
+    let music = root.join("music");
+    std::fs::create_dir_all(root.join("config/qoget")).unwrap();
+    std::fs::create_dir_all(&music).unwrap();
+    std::fs::write(
+        root.join("config/qoget/config.toml"),
+        format!("[sync]\ntarget_dir = {:?}\n", music.to_str().unwrap()),
+    )
+    .unwrap();
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        ManifestEntry {
+            format: AudioFormat::Mp3,
+            artist: "Miles Davis".to_string(),
+            album: "Kind of Blue".to_string(),
+            title: "So What".to_string(),
+            path: PathBuf::from("Miles Davis/Kind of Blue/01 - So What.mp3"),
+            size: None,
+            sha256: None,
+            quality: None,
+            genre: None,
+            steps: BTreeMap::new(),
+        },
+    );
+    manifest.save(&music).unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_qoget"))
+        .args(["search", "so what", "--quiet"])
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .env("XDG_CACHE_HOME", root.join("cache"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(stdout.contains("01 - So What.mp3"), "{stdout}");
+    // The count is a summary, shown even with --quiet.
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("1 matches (1 synced"), "{stderr}");
+
+    // The catalog is only searched when asked for, and never with a target.
+    let out = qoget(&["search", "--catalog", "so what", "/music"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
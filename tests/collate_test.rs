@@ -0,0 +1,52 @@
+use std::cmp::Ordering;
+
+use qoget::collate::{SortOrder, compare, compare_fields, sort_key};
+
+#[test]
+fn sort_key_strips_accents_and_case() {
+    assert_eq!(sort_key("Édith Piaf"), "edith piaf");
+    assert_eq!(sort_key("Björk"), "bjork");
+    // Precomposed and decomposed forms collate identically
+    assert_eq!(sort_key("Caf\u{e9}"), sort_key("Cafe\u{301}"));
+}
+
+#[test]
+fn unicode_order_files_accented_names_under_base_letter() {
+    let mut artists = vec!["Zappa", "Édith Piaf", "ABBA", "eels"];
+    artists.sort_by(|a, b| compare(a, b, SortOrder::Unicode));
+    assert_eq!(artists, vec!["ABBA", "Édith Piaf", "eels", "Zappa"]);
+}
+
+#[test]
+fn byte_order_is_plain_utf8_order() {
+    let mut artists = vec!["Zappa", "Édith Piaf", "ABBA", "eels"];
+    artists.sort_by(|a, b| compare(a, b, SortOrder::Bytes));
+    assert_eq!(artists, vec!["ABBA", "Zappa", "eels", "Édith Piaf"]);
+}
+
+#[test]
+fn equal_keys_fall_back_to_bytes() {
+    assert_eq!(
+        compare("abba", "ABBA", SortOrder::Unicode),
+        Ordering::Greater
+    );
+    assert_eq!(compare("ABBA", "ABBA", SortOrder::Unicode), Ordering::Equal);
+}
+
+#[test]
+fn accent_differences_in_artist_do_not_outrank_album() {
+    let a = ["Émilie", "Album B"];
+    let b = ["emilie", "Album A"];
+    assert_eq!(
+        compare_fields(&a, &b, SortOrder::Unicode),
+        Ordering::Greater
+    );
+    assert_eq!(compare_fields(&a, &b, SortOrder::Bytes), Ordering::Greater);
+
+    let c = ["Émilie", "Album A"];
+    assert_eq!(
+        compare_fields(&c, &b, SortOrder::Unicode),
+        Ordering::Greater
+    );
+    assert_eq!(compare_fields(&b, &b, SortOrder::Unicode), Ordering::Equal);
+}
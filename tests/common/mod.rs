@@ -0,0 +1,64 @@
+//! Purchase fixtures shared by the integration tests, so a new field on
+//! `Album` or `Track` is filled in here once. Each test crate uses only
+//! some of them.
+#![allow(dead_code)]
+
+use qoget::models::{
+    Album, AlbumId, Artist, DiscNumber, PaginatedList, PurchaseInfo, Track, TrackId, TrackNumber,
+};
+
+/// An artist with id 1.
+pub fn artist(name: &str) -> Artist {
+    Artist {
+        id: 1,
+        name: name.to_string(),
+    }
+}
+
+/// A 200-second track on disc 1 by `performer`.
+pub fn track(id: u64, title: &str, number: u8, performer: &str) -> Track {
+    Track {
+        id: TrackId(id),
+        title: title.to_string(),
+        version: None,
+        track_number: TrackNumber(number),
+        media_number: DiscNumber(1),
+        duration: 200,
+        performer: artist(performer),
+        isrc: None,
+        purchase: PurchaseInfo::default(),
+    }
+}
+
+/// A single-disc album by `artist` without its track listing, as Qobuz's
+/// purchase list gives albums.
+pub fn album(id: &str, title: &str, artist_name: &str) -> Album {
+    Album {
+        id: AlbumId(id.to_string()),
+        title: title.to_string(),
+        version: None,
+        artist: artist(artist_name),
+        artists: Vec::new(),
+        media_count: 1,
+        tracks_count: 0,
+        upc: None,
+        release_date: None,
+        image: None,
+        tracks: None,
+        purchase: PurchaseInfo::default(),
+    }
+}
+
+/// `album` with `tracks` as its whole track listing.
+pub fn with_tracks(album: Album, tracks: Vec<Track>) -> Album {
+    Album {
+        tracks_count: tracks.len() as u16,
+        tracks: Some(PaginatedList {
+            offset: 0,
+            limit: 50,
+            total: tracks.len() as u64,
+            items: tracks,
+        }),
+        ..album
+    }
+}
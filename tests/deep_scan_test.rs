@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+
+use qoget::deep_scan::{TagIndex, TrackTags, read_tags};
+use qoget::manifest::Manifest;
+use qoget::models::{Album, PurchaseList, Service, SizeMismatchPolicy, Track, TrackId};
+use qoget::path::Naming;
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+use qoget::tags::set_mp4_freeform;
+
+mod common;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-deep-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn synchsafe(n: usize) -> [u8; 4] {
+    [
+        (n >> 21) as u8 & 0x7f,
+        (n >> 14) as u8 & 0x7f,
+        (n >> 7) as u8 & 0x7f,
+        n as u8 & 0x7f,
+    ]
+}
+
+/// An ID3v2.4 tag with UTF-8 text frames, then some "audio".
+fn mp3(frames: &[(&str, &str)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (id, text) in frames {
+        body.extend_from_slice(id.as_bytes());
+        body.extend_from_slice(&synchsafe(text.len() + 1));
+        body.extend_from_slice(&[0, 0, 3]);
+        body.extend_from_slice(text.as_bytes());
+    }
+    let mut file = b"ID3\x04\x00\x00".to_vec();
+    file.extend_from_slice(&synchsafe(body.len()));
+    file.extend(body);
+    file.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    file
+}
+
+fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&4u32.to_le_bytes());
+    out.extend_from_slice(b"test");
+    out.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        out.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        out.extend_from_slice(comment.as_bytes());
+    }
+    out
+}
+
+fn flac(comments: &[&str]) -> Vec<u8> {
+    let mut file = b"fLaC".to_vec();
+    // STREAMINFO, then a picture block to skip over
+    file.extend_from_slice(&[0, 0, 0, 34]);
+    file.extend_from_slice(&[0; 34]);
+    file.extend_from_slice(&[6, 0, 0, 5]);
+    file.extend_from_slice(b"image");
+    let block = vorbis_comments(comments);
+    file.push(0x80 | 4);
+    file.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+    file.extend(block);
+    file
+}
+
+fn ogg_page(sequence: u32, packet: &[u8]) -> Vec<u8> {
+    let mut lacing = vec![255; packet.len() / 255];
+    lacing.push((packet.len() % 255) as u8);
+    let mut page = b"OggS\0\0".to_vec();
+    page.extend_from_slice(&[0; 8]);
+    page.extend_from_slice(&1u32.to_le_bytes());
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&[0; 4]);
+    page.push(lacing.len() as u8);
+    page.extend(lacing);
+    page.extend_from_slice(packet);
+    page
+}
+
+fn opus(comments: &[&str]) -> Vec<u8> {
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend(vorbis_comments(comments));
+    let mut file = ogg_page(0, b"OpusHead\x01\x02");
+    file.extend(ogg_page(1, &tags));
+    file
+}
+
+fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn mp4_item(kind: &[u8; 4], value: &[u8]) -> Vec<u8> {
+    let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    data.extend_from_slice(value);
+    atom(kind, &atom(b"data", &data))
+}
+
+fn m4a() -> Vec<u8> {
+    let mut ilst = mp4_item(b"\xa9nam", b"Opening");
+    ilst.extend(mp4_item(b"\xa9ART", b"Band"));
+    ilst.extend(mp4_item(b"\xa9alb", b"Record"));
+    ilst.extend(mp4_item(b"trkn", &[0, 0, 0, 7, 0, 10, 0, 0]));
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"ilst", &ilst));
+    let udta = atom(b"udta", &atom(b"meta", &meta));
+    let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+    // Media data before the moov, which is read without it
+    file.extend(atom(b"mdat", &[0; 4096]));
+    file.extend(atom(b"moov", &udta));
+    file
+}
+
+fn tags(title: &str, artist: &str, album: &str) -> TrackTags {
+    TrackTags {
+        title: Some(title.to_string()),
+        artist: Some(artist.to_string()),
+        album: Some(album.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn tags_are_read_from_each_format() {
+    let dir = temp_dir("formats");
+    let files = [
+        (
+            "a.mp3",
+            mp3(&[
+                ("TIT2", "Opening"),
+                ("TPE1", "Band"),
+                ("TALB", "Record"),
+                ("TRCK", "7/10"),
+                ("TSRC", "GB-AAA-24-00001"),
+            ]),
+        ),
+        (
+            "a.flac",
+            flac(&[
+                "TITLE=Opening",
+                "artist=Band",
+                "ALBUM=Record",
+                "TRACKNUMBER=7",
+                "ISRC=GB-AAA-24-00001",
+            ]),
+        ),
+        (
+            "a.opus",
+            opus(&[
+                "TITLE=Opening",
+                "ARTIST=Band",
+                "ALBUM=Record",
+                "TRACKNUMBER=7",
+                "ISRC=GB-AAA-24-00001",
+            ]),
+        ),
+        ("a.m4a", m4a()),
+    ];
+    for (name, contents) in &files {
+        std::fs::write(dir.join(name), contents).unwrap();
+    }
+    set_mp4_freeform(&dir.join("a.m4a"), &[("ISRC", "GB-AAA-24-00001")]).unwrap();
+
+    for (name, _) in &files {
+        let read = read_tags(&dir.join(name)).unwrap();
+        assert_eq!(
+            read,
+            TrackTags {
+                track_number: Some(7),
+                isrc: Some("GB-AAA-24-00001".to_string()),
+                ..tags("Opening", "Band", "Record")
+            },
+            "{name}"
+        );
+    }
+
+    // An MP3 without an ID3 tag just has no tags.
+    std::fs::write(dir.join("b.mp3"), [0xff; 16]).unwrap();
+    assert_eq!(read_tags(&dir.join("b.mp3")).unwrap(), TrackTags::default());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn long_opus_comments_span_pages() {
+    let dir = temp_dir("opus");
+    let title = format!("TITLE={}", "x".repeat(600));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend(vorbis_comments(&[&title, "ALBUM=Record"]));
+    let mut file = ogg_page(0, b"OpusHead\x01\x02");
+    // Split the packet across two pages at a 255-byte segment boundary.
+    let (first, rest) = tags.split_at(510);
+    let mut page = b"OggS\0\0".to_vec();
+    page.extend_from_slice(&[0; 20]);
+    page.extend_from_slice(&[2, 255, 255]);
+    page.extend_from_slice(first);
+    file.extend(page);
+    file.extend(ogg_page(2, rest));
+    let path = dir.join("a.opus");
+    std::fs::write(&path, file).unwrap();
+
+    let read = read_tags(&path).unwrap();
+    assert_eq!(read.title.map(|t| t.len()), Some(600));
+    assert_eq!(read.album.as_deref(), Some("Record"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn track(id: u64, title: &str, isrc: Option<&str>) -> Track {
+    Track {
+        isrc: isrc.map(str::to_string),
+        ..common::track(id, title, id as u8, "Band")
+    }
+}
+
+fn album(tracks: Vec<Track>) -> Album {
+    common::with_tracks(common::album("a1", "Record", "Band"), tracks)
+}
+
+#[test]
+fn index_matches_by_isrc_then_names() {
+    let mut index = TagIndex::default();
+    index.insert(
+        PathBuf::from("/lib/x.flac"),
+        &TrackTags {
+            isrc: Some("gbaaa2400001".to_string()),
+            ..tags("Something Else", "Band", "Record")
+        },
+    );
+    index.insert(
+        PathBuf::from("/lib/y.flac"),
+        &tags("Don’t Stop (Live)", "BAND", "Record"),
+    );
+    assert_eq!(index.len(), 2);
+
+    let record = album(vec![]);
+    let by_isrc = track(1, "Opening", Some("GB-AAA-24-00001"));
+    assert_eq!(
+        index.find_track(&record, &by_isrc),
+        Some(Path::new("/lib/x.flac"))
+    );
+    let by_name = track(2, "Don't Stop (live)", None);
+    assert_eq!(
+        index.find_track(&record, &by_name),
+        Some(Path::new("/lib/y.flac"))
+    );
+    assert_eq!(index.find_track(&record, &track(3, "Closing", None)), None);
+
+    assert!(index.has_album("band", "record"));
+    assert!(!index.has_album("Band", "Other Record"));
+}
+
+#[tokio::test]
+async fn tagged_files_count_as_existing() {
+    let dir = temp_dir("plan");
+    let purchases = PurchaseList {
+        albums: vec![album(vec![
+            track(1, "Opening", None),
+            track(2, "Closing", None),
+        ])],
+        tracks: vec![],
+    };
+    let renamed = dir.join("Renamed/01 Opening.flac");
+    let mut index = TagIndex::default();
+    index.insert(renamed.clone(), &tags("Opening", "Band", "Record"));
+
+    let tasks = collect_tasks(&purchases, &dir, ".mp3", Naming::default());
+    let mut existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let matched = existing.add_tagged(&tasks, &index);
+    assert_eq!(matched.len(), 1);
+    assert_eq!(matched[0].0.track.id, TrackId(1));
+    assert_eq!(matched[0].1, renamed);
+
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    let downloads: Vec<_> = plan.downloads().map(|d| d.track.id).collect();
+    assert_eq!(downloads, vec![TrackId(2)]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use qoget::download::{archive_path, place_staged};
+use qoget::path::Naming;
+
+mod common;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Stage `names` under `<root>/staged/Artist/Album`, returning the staged
+/// album directory and each file paired with its place in the library.
+fn stage(root: &Path, names: &[&str]) -> (PathBuf, PathBuf, Vec<(PathBuf, PathBuf)>) {
+    let staged_dir = root.join("staged/Artist/Album");
+    let album_dir = root.join("library/Artist/Album");
+    std::fs::create_dir_all(&staged_dir).unwrap();
+    let files = names
+        .iter()
+        .map(|name| {
+            let staged = staged_dir.join(name);
+            std::fs::write(&staged, name).unwrap();
+            (staged, album_dir.join(name))
+        })
+        .collect();
+    (staged_dir, album_dir, files)
+}
+
+fn listing(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn new_album_is_moved_in_whole() {
+    let root = temp_dir("place-new");
+    let (staged_dir, album_dir, files) = stage(&root, &["01 - A.m4a", "02 - B.m4a"]);
+
+    place_staged(&staged_dir, &album_dir, &files).await.unwrap();
+
+    assert_eq!(listing(&album_dir), ["01 - A.m4a", "02 - B.m4a"]);
+    assert!(!staged_dir.exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn failed_move_into_existing_album_is_undone() {
+    let root = temp_dir("place-existing");
+    let (staged_dir, album_dir, files) = stage(&root, &["01 - A.m4a", "02 - B.m4a"]);
+    std::fs::create_dir_all(&album_dir).unwrap();
+    std::fs::write(album_dir.join("cover.jpg"), "art").unwrap();
+    // The second track never made it to the staging area.
+    std::fs::remove_file(&files[1].0).unwrap();
+
+    let err = place_staged(&staged_dir, &album_dir, &files)
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("02 - B.m4a"), "{err:#}");
+    assert_eq!(listing(&album_dir), ["cover.jpg"]);
+    assert_eq!(listing(&staged_dir), ["01 - A.m4a"]);
+
+    std::fs::write(&files[1].0, "B").unwrap();
+    place_staged(&staged_dir, &album_dir, &files).await.unwrap();
+    assert_eq!(
+        listing(&album_dir),
+        ["01 - A.m4a", "02 - B.m4a", "cover.jpg"]
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn archives_are_named_after_artist_and_album() {
+    let album = common::album("bc-1", "Songs: Ohia", "AC/DC");
+    let path = archive_path(Path::new("/music"), &album, Naming::default());
+    assert_eq!(path.parent(), Some(Path::new("/music/Archives")));
+    let name = path.file_name().unwrap().to_string_lossy();
+    assert!(name.starts_with("AC"), "{name}");
+    assert!(name.ends_with(".zip"), "{name}");
+    assert!(!name.contains('/'), "{name}");
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use qoget::bandcamp::BandcampPurchases;
+use qoget::dry_run::{Change, bandcamp_diff, plan_diff};
+use qoget::manifest::Manifest;
+use qoget::models::{
+    Album, BandcampCollectionItem, PurchaseList, Service, SizeMismatchPolicy, Track,
+};
+use qoget::path::Naming;
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+
+mod common;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-dry-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn track(id: u64, title: &str) -> Track {
+    common::track(id, title, id as u8, "Band")
+}
+
+fn album(tracks: Vec<Track>) -> Album {
+    common::with_tracks(common::album("a1", "Record", "Band"), tracks)
+}
+
+#[tokio::test]
+async fn plan_diff_marks_each_track() {
+    let dir = temp_dir("plan");
+    let purchases = PurchaseList {
+        albums: vec![album(vec![
+            track(1, "Synced"),
+            track(2, "New"),
+            track(3, "Too Small"),
+            track(4, "Other Format"),
+        ])],
+        tracks: vec![],
+    };
+    let album_dir = dir.join("Band/Record");
+    std::fs::create_dir_all(&album_dir).unwrap();
+    // 200 seconds of MP3 is megabytes, not a few bytes.
+    std::fs::write(album_dir.join("01 - Synced.mp3"), vec![0; 8_000_000]).unwrap();
+    std::fs::write(album_dir.join("03 - Too Small.mp3"), b"tiny").unwrap();
+    std::fs::write(album_dir.join("04 - Other Format.flac"), b"flac").unwrap();
+
+    let tasks = collect_tasks(&purchases, &dir, ".mp3", Naming::default());
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        true,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    let diff = plan_diff(&plan);
+
+    assert_eq!(diff.count(Change::Download), 1);
+    assert_eq!(diff.count(Change::Synced), 1);
+    assert_eq!(diff.count(Change::Mismatch), 2);
+    let rendered = diff.render();
+    let lines: Vec<&str> = rendered.lines().collect();
+    // 200 seconds of MP3 at 320 kbps, for each of the two downloads.
+    assert_eq!(diff.bytes(), 2 * 200 * 40_000);
+    assert_eq!(lines[0], "Band - Record  (~15 MB to download)");
+    assert_eq!(lines[1], "  = 01 - Synced.mp3");
+    assert_eq!(lines[2], "  + 02 - New.mp3  ~8 MB");
+    assert!(lines[3].starts_with("  ! 03 - Too Small.mp3  (0.0 MB on disk"));
+    assert!(lines[3].ends_with("; kept)"), "{}", lines[3]);
+    assert_eq!(
+        lines[4],
+        "  ! 04 - Other Format.mp3  ~8 MB  (04 - Other Format.flac is there instead)"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn item(title: &str, item_id: u64) -> BandcampCollectionItem {
+    BandcampCollectionItem {
+        band_name: "Band".to_string(),
+        item_title: title.to_string(),
+        item_id,
+        item_type: "album".to_string(),
+        sale_item_type: "a".to_string(),
+        sale_item_id: item_id,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
+    }
+}
+
+#[test]
+fn bandcamp_diff_works_by_album() {
+    let dir = temp_dir("bandcamp");
+    for (album, file) in [("Kept", "01 - One.m4a"), ("Converted", "01 - One.flac")] {
+        std::fs::create_dir_all(dir.join("Band").join(album)).unwrap();
+        std::fs::write(dir.join("Band").join(album).join(file), b"audio").unwrap();
+    }
+    let purchases = BandcampPurchases {
+        items: vec![item("Kept", 1), item("New", 2), item("Converted", 3)],
+        redownload_urls: HashMap::new(),
+    };
+    // Bandcamp gave a size for one of the albums to download.
+    let sizes = HashMap::from([(2, 90 * 1024 * 1024)]);
+    let diff = bandcamp_diff(&purchases, &dir, &sizes, Naming::default(), None);
+    assert_eq!(diff.bytes(), 90 * 1024 * 1024);
+    assert_eq!(
+        diff.render(),
+        "Band - Kept\n  = 01 - One.m4a\n\
+         Band - New  (~90 MB to download)\n  + whole album  ~90 MB\n\
+         Band - Converted\n  ! 01 - One.flac  (not .m4a)\n  + whole album\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
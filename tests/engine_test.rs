@@ -0,0 +1,39 @@
+use qoget::config::parse_toml_config;
+use qoget::engine::{SyncEngine, SyncOptions};
+use qoget::models::{QobuzQuality, Service};
+
+#[test]
+fn options_are_filled_from_the_config() {
+    let cfg = parse_toml_config("[qobuz]\nquality = \"cd\"\n").unwrap();
+    let engine = SyncEngine::new(cfg, "/music", SyncOptions::default());
+    assert_eq!(engine.options().quality, Some(QobuzQuality::Cd));
+
+    let cfg = parse_toml_config("[qobuz]\nquality = \"cd\"\n").unwrap();
+    let opts = SyncOptions {
+        quality: Some(QobuzQuality::Hires192),
+        ..SyncOptions::default()
+    };
+    let engine = SyncEngine::new(cfg, "/music", opts);
+    assert_eq!(engine.options().quality, Some(QobuzQuality::Hires192));
+}
+
+#[tokio::test]
+async fn unconfigured_service_fails_the_run_not_the_engine() {
+    let cfg = parse_toml_config("").unwrap();
+    let opts = SyncOptions {
+        dry_run: true,
+        ..SyncOptions::default()
+    };
+    let run = SyncEngine::new(cfg, "/nonexistent/music", opts)
+        .service(Service::Bandcamp)
+        .run()
+        .await
+        .unwrap();
+    let err = run.result.unwrap_err();
+    assert!(
+        err.to_string().contains("Bandcamp is not configured"),
+        "{err:#}"
+    );
+    assert!(run.report.dry_run);
+    assert!(!run.report.ok);
+}
@@ -0,0 +1,232 @@
+//! Golden files for the directory tree and tags sync plans for fixture
+//! libraries, so a change to paths or tagging shows up as a diff to review
+//! instead of silently moving files around in users' libraries.
+//!
+//! Each `tests/golden/NAME.json` fixture is rendered and compared with
+//! `tests/golden/NAME.txt`. After a deliberate change, rewrite the expected
+//! files with `QOGET_BLESS=1 cargo test --test golden_test` and review the
+//! diff.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use qoget::bandcamp::{
+    ExtractedTrack, extracted_tracks, number_unknown_tracks, parse_tags, parse_tracklist,
+    parse_zip_track_filename,
+};
+use qoget::download::item_album;
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{
+    Album, AudioFormat, BandcampCollectionItem, PurchaseList, Service, SizeMismatchPolicy, Track,
+    UnknownTrackNumbers,
+};
+use qoget::path::{Naming, track_path};
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+
+/// Never created; paths are shown relative to it.
+const TARGET: &str = "/nonexistent/Music";
+
+#[derive(Deserialize)]
+struct QobuzFixture {
+    albums: Vec<Album>,
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+#[derive(Deserialize)]
+struct BandcampFixture {
+    item: BandcampCollectionItem,
+    #[serde(default)]
+    unknown_track_numbers: UnknownTrackNumbers,
+    /// Names of the files in the download, in ZIP order.
+    files: Vec<String>,
+    /// The item's page, for its tags and tracklist.
+    #[serde(default)]
+    page: String,
+}
+
+/// Windows' rules are a superset of the others, and make the output the
+/// same on every platform.
+fn naming() -> Naming {
+    Naming {
+        windows_safe: true,
+        ..Naming::default()
+    }
+}
+
+fn fixture_path(name: &str, ext: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.{ext}"))
+}
+
+fn load<T: for<'de> Deserialize<'de>>(name: &str) -> T {
+    let path = fixture_path(name, "json");
+    let json = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+/// Compare `actual` with the expected file, or rewrite it under
+/// `QOGET_BLESS=1`.
+fn assert_golden(name: &str, actual: &str) {
+    let path = fixture_path(name, "txt");
+    if std::env::var_os("QOGET_BLESS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    if expected == actual {
+        return;
+    }
+    let mut diff = String::new();
+    let (old, new): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+    for i in 0..old.len().max(new.len()) {
+        match (old.get(i), new.get(i)) {
+            (Some(o), Some(n)) if o == n => {}
+            (o, n) => {
+                if let Some(o) = o {
+                    diff.push_str(&format!("{:>4} - {o}\n", i + 1));
+                }
+                if let Some(n) = n {
+                    diff.push_str(&format!("{:>4} + {n}\n", i + 1));
+                }
+            }
+        }
+    }
+    panic!(
+        "{} is out of date (- expected, + actual):\n{diff}\n\
+         If the change is intended, rerun with QOGET_BLESS=1 and review the diff.",
+        path.display()
+    );
+}
+
+/// One file per paragraph: its path under the target, then the tags qoget
+/// records for it.
+fn render(mut files: Vec<(ManifestEntry, &Track)>) -> String {
+    files.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+    let mut out = String::new();
+    for (entry, track) in files {
+        let path: Vec<_> = entry
+            .path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        out.push_str(&path.join("/"));
+        out.push('\n');
+        let number = track.track_number.0.to_string();
+        let disc = track.media_number.0.to_string();
+        let tags = [
+            ("artist", Some(entry.artist.as_str())),
+            ("album", Some(entry.album.as_str())),
+            ("title", Some(entry.title.as_str())),
+            ("performer", Some(track.performer.name.as_str())),
+            ("track", Some(number.as_str())),
+            ("disc", Some(disc.as_str())),
+            ("genre", entry.genre.as_deref()),
+        ];
+        for (tag, value) in tags {
+            if let Some(value) = value {
+                out.push_str(&format!("    {tag}: {value}\n"));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+async fn qobuz_golden(name: &str) {
+    let fixture: QobuzFixture = load(name);
+    let purchases = PurchaseList {
+        albums: fixture.albums,
+        tracks: fixture.tracks,
+    };
+    let target = Path::new(TARGET);
+    let tasks = collect_tasks(&purchases, target, ".mp3", naming());
+    let existing = scan_existing(&tasks, Service::Qobuz, &Manifest::default(), naming())
+        .await
+        .unwrap();
+    let plan = build_sync_plan(tasks, &existing, false, SizeMismatchPolicy::Keep, naming());
+    let files = plan
+        .downloads()
+        .map(|task| {
+            let entry = ManifestEntry::hashed(
+                &task.album,
+                &task.track,
+                AudioFormat::Mp3,
+                target,
+                &task.target_path,
+                None,
+            );
+            (entry, &task.track)
+        })
+        .collect::<Vec<_>>();
+    assert!(!files.is_empty(), "{name} plans no downloads");
+    assert_golden(name, &render(files));
+}
+
+fn bandcamp_golden(name: &str) {
+    let fixture: BandcampFixture = load(name);
+    let mut extracted: Vec<ExtractedTrack> = fixture
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            let (track_number, title) = parse_zip_track_filename(file);
+            ExtractedTrack {
+                track_number,
+                title,
+                disc_number: 1,
+                artist: None,
+                position: i + 1,
+                temp_path: PathBuf::from(file),
+                sha256: String::new(),
+            }
+        })
+        .collect();
+    if extracted.len() > 1 {
+        let tracklist = parse_tracklist(&fixture.page);
+        number_unknown_tracks(&mut extracted, fixture.unknown_track_numbers, &tracklist);
+    }
+
+    let album = item_album(&fixture.item);
+    let tracks = extracted_tracks(&fixture.item, &album, &extracted);
+    let genre = parse_tags(&fixture.page).into_iter().next();
+    let target = Path::new(TARGET);
+    let files = tracks
+        .iter()
+        .map(|track| {
+            let path = track_path(target, &album, track, ".m4a", naming());
+            let mut entry =
+                ManifestEntry::hashed(&album, track, AudioFormat::Aac, target, &path, None);
+            entry.genre = genre.clone();
+            (entry, track)
+        })
+        .collect();
+    assert_golden(name, &render(files));
+}
+
+#[tokio::test]
+async fn qobuz_multi_disc() {
+    qobuz_golden("qobuz_multi_disc").await;
+}
+
+#[tokio::test]
+async fn qobuz_compilation() {
+    qobuz_golden("qobuz_compilation").await;
+}
+
+#[tokio::test]
+async fn qobuz_collaboration() {
+    qobuz_golden("qobuz_collaboration").await;
+}
+
+#[tokio::test]
+async fn qobuz_classical() {
+    qobuz_golden("qobuz_classical").await;
+}
+
+#[test]
+fn bandcamp_label_release() {
+    bandcamp_golden("bandcamp_label_release");
+}
@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use qoget::health::{AuthError, HealthPolicy, ServiceHealth, Transition, is_auth_error};
+use qoget::models::Service;
+
+fn policy() -> HealthPolicy {
+    HealthPolicy {
+        failure_threshold: 3,
+        base_cooldown: Duration::from_secs(60),
+        max_cooldown: Duration::from_secs(300),
+    }
+}
+
+#[test]
+fn transient_failures_below_threshold_keep_service_due() {
+    let p = policy();
+    let now = Instant::now();
+    let mut h = ServiceHealth::default();
+
+    assert_eq!(h.record_failure(false, now, &p), Transition::None);
+    assert_eq!(h.record_failure(false, now, &p), Transition::None);
+    assert!(h.is_healthy());
+    assert!(h.is_due(now));
+}
+
+#[test]
+fn repeated_failures_mark_unhealthy_once() {
+    let p = policy();
+    let now = Instant::now();
+    let mut h = ServiceHealth::default();
+
+    h.record_failure(false, now, &p);
+    h.record_failure(false, now, &p);
+    assert_eq!(
+        h.record_failure(false, now, &p),
+        Transition::BecameUnhealthy {
+            cooldown: Duration::from_secs(60)
+        }
+    );
+    assert!(!h.is_due(now + Duration::from_secs(59)));
+    assert!(h.is_due(now + Duration::from_secs(60)));
+
+    // Failed retry escalates silently.
+    let retry = now + Duration::from_secs(60);
+    assert_eq!(h.record_failure(false, retry, &p), Transition::None);
+    assert!(!h.is_due(retry + Duration::from_secs(119)));
+    assert!(h.is_due(retry + Duration::from_secs(120)));
+}
+
+#[test]
+fn auth_failure_is_immediately_unhealthy() {
+    let p = policy();
+    let now = Instant::now();
+    let mut h = ServiceHealth::default();
+
+    assert!(matches!(
+        h.record_failure(true, now, &p),
+        Transition::BecameUnhealthy { .. }
+    ));
+    assert!(!h.is_due(now));
+}
+
+#[test]
+fn cooldown_is_capped() {
+    let p = policy();
+    let mut now = Instant::now();
+    let mut h = ServiceHealth::default();
+
+    for _ in 0..10 {
+        h.record_failure(true, now, &p);
+        now += Duration::from_secs(1000);
+    }
+    let last = now - Duration::from_secs(1000);
+    assert!(h.is_due(last + Duration::from_secs(300)));
+}
+
+#[test]
+fn success_recovers_and_resets() {
+    let p = policy();
+    let now = Instant::now();
+    let mut h = ServiceHealth::default();
+
+    h.record_failure(true, now, &p);
+    assert_eq!(h.record_success(), Transition::Recovered);
+    assert!(h.is_healthy());
+    assert!(h.is_due(now));
+    assert_eq!(h.consecutive_failures(), 0);
+    assert_eq!(h.record_success(), Transition::None);
+}
+
+#[test]
+fn detects_auth_errors_in_context_chain() {
+    let err = anyhow::Error::new(AuthError(Service::Qobuz)).context("Qobuz login");
+    assert!(is_auth_error(&err));
+    assert!(!is_auth_error(&anyhow!("HTTP 500 — oops")));
+    // Only the error the clients return counts, not one that reads like it.
+    assert!(!is_auth_error(&anyhow!(
+        "Authentication failed: invalid credentials"
+    )));
+}
@@ -0,0 +1,59 @@
+#![cfg(unix)]
+
+use std::path::PathBuf;
+
+use qoget::hooks::{Hook, post_sync, pre_sync, run};
+use qoget::report::SyncReport;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-hooks-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn pre_sync_sees_the_target_and_can_stop_the_sync() {
+    let dir = temp_dir("pre");
+    let out = dir.join("env.txt");
+    let command = format!(
+        "echo \"$QOGET_HOOK $QOGET_TARGET_DIR $QOGET_DRY_RUN\" > '{}'",
+        out.display()
+    );
+    pre_sync(&command, &dir, true).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        format!("pre_sync {} 1\n", dir.display())
+    );
+
+    let err = pre_sync("exit 3", &dir, false).await.unwrap_err();
+    assert!(
+        err.to_string().contains("pre_sync hook `exit 3` failed"),
+        "{err}"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn post_sync_gets_the_report_on_stdin() {
+    let dir = temp_dir("post");
+    let out = dir.join("report.json");
+    let mut report = SyncReport::new(&dir, false);
+    report.finish(&Ok(()));
+    post_sync(&format!("cat > '{}'", out.display()), &report)
+        .await
+        .unwrap();
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    assert_eq!(json["ok"], true);
+    assert_eq!(json["dry_run"], false);
+    assert_eq!(json["target_dir"], dir.display().to_string());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn hooks_that_ignore_their_input_still_succeed() {
+    let big = vec![b'x'; 1 << 20];
+    run(Hook::PostSync, "true", &std::env::temp_dir(), false, &big).unwrap();
+}
@@ -0,0 +1,110 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use reqwest::header::{HeaderMap, HeaderValue};
+
+use qoget::http_cache::{CachedResponse, HttpCache};
+use qoget::models::Service;
+use qoget::retry::{RateLimiter, RequestPolicy};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn only_responses_with_validators_are_kept() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(
+        CachedResponse::from_headers("https://x/a", &headers, "{}".into()),
+        None
+    );
+
+    headers.insert("etag", HeaderValue::from_static("\"v1\""));
+    let cached = CachedResponse::from_headers("https://x/a", &headers, "{}".into()).unwrap();
+    assert_eq!(cached.etag.as_deref(), Some("\"v1\""));
+    assert_eq!(cached.last_modified, None);
+}
+
+#[test]
+fn entries_are_keyed_by_url_and_scope() {
+    let dir = temp_dir("http-cache-keys");
+    let cache = HttpCache::new(&dir);
+    let cached = CachedResponse {
+        url: "https://x/a?offset=0".into(),
+        etag: None,
+        last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+        body: "{\"a\":1}".into(),
+    };
+    cache.save("token-1", &cached).unwrap();
+
+    assert_eq!(
+        cache.load("https://x/a?offset=0", "token-1").unwrap(),
+        Some(cached)
+    );
+    assert_eq!(
+        cache.load("https://x/a?offset=500", "token-1").unwrap(),
+        None
+    );
+    assert_eq!(cache.load("https://x/a?offset=0", "token-2").unwrap(), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Answer one request per connection with each of `responses` in turn,
+/// returning the requests received.
+fn serve(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/purchases?offset=0",
+        listener.local_addr().unwrap()
+    );
+    let server = std::thread::spawn(move || {
+        responses
+            .into_iter()
+            .map(|response| {
+                let (mut conn, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = conn.read(&mut buf).unwrap();
+                    assert!(n > 0, "connection closed early");
+                    request.extend_from_slice(&buf[..n]);
+                }
+                conn.write_all(response.as_bytes()).unwrap();
+                String::from_utf8(request).unwrap().to_lowercase()
+            })
+            .collect()
+    });
+    (url, server)
+}
+
+#[tokio::test]
+async fn not_modified_reuses_the_cached_body() {
+    let (url, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 7\r\nConnection: close\r\n\r\n{\"a\":1}",
+        "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+    ]);
+    let dir = temp_dir("http-cache-send");
+    let cache = HttpCache::new(&dir);
+    let http = reqwest::Client::builder().no_proxy().build().unwrap();
+    let policy = RequestPolicy::default_for(Service::Qobuz);
+    let limiter = RateLimiter::new(None);
+
+    for _ in 0..2 {
+        let body = cache
+            .send("token", http.get(&url), &policy, &limiter)
+            .await
+            .unwrap();
+        assert_eq!(body, "{\"a\":1}");
+    }
+
+    let requests = server.join().unwrap();
+    assert!(!requests[0].contains("if-none-match"), "{}", requests[0]);
+    assert!(
+        requests[1].contains("if-none-match: \"v1\""),
+        "{}",
+        requests[1]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
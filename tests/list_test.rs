@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use qoget::bandcamp::BandcampPurchases;
+use qoget::collate::SortOrder;
+use qoget::list::{
+    ListEntry, PurchaseKind, from_bandcamp, from_bandcamp_wishlist, from_qobuz, sort, wishlist_csv,
+};
+use qoget::models::{
+    Album, BandcampCollectionItem, BandcampCollectionResponse, PurchaseList, Service, Track,
+};
+
+mod common;
+
+fn qobuz_purchases() -> PurchaseList {
+    PurchaseList {
+        albums: vec![Album {
+            tracks_count: 5,
+            ..common::album("0060253780968", "Kind of Blue", "Miles Davis")
+        }],
+        tracks: vec![Track {
+            duration: 260,
+            ..common::track(42, "Naima", 1, "John Coltrane")
+        }],
+    }
+}
+
+fn bandcamp_item(
+    sale_item_type: &str,
+    item_id: u64,
+    band: &str,
+    title: &str,
+) -> BandcampCollectionItem {
+    BandcampCollectionItem {
+        band_name: band.to_string(),
+        item_title: title.to_string(),
+        item_id,
+        item_type: if sale_item_type == "t" {
+            "track"
+        } else {
+            "album"
+        }
+        .to_string(),
+        sale_item_type: sale_item_type.to_string(),
+        sale_item_id: item_id,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
+    }
+}
+
+#[test]
+fn qobuz_albums_and_tracks() {
+    let entries = from_qobuz(&qobuz_purchases());
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].kind, PurchaseKind::Album);
+    assert_eq!(entries[0].id, "0060253780968");
+    assert_eq!(entries[0].track_count, Some(5));
+
+    assert_eq!(entries[1].kind, PurchaseKind::Track);
+    assert_eq!(entries[1].id, "42");
+    assert_eq!(entries[1].artist, "John Coltrane");
+}
+
+#[test]
+fn bandcamp_album_track_counts_are_unknown() {
+    let purchases = BandcampPurchases {
+        items: vec![
+            bandcamp_item("a", 100, "Deafheaven", "Sunbather"),
+            bandcamp_item("t", 200, "Deafheaven", "Dream House"),
+        ],
+        redownload_urls: HashMap::new(),
+    };
+    let entries = from_bandcamp(&purchases);
+    assert_eq!(entries[0].kind, PurchaseKind::Album);
+    assert_eq!(entries[0].track_count, None);
+    assert_eq!(entries[1].kind, PurchaseKind::Track);
+    assert_eq!(entries[1].track_count, Some(1));
+}
+
+#[test]
+fn line_is_tab_separated() {
+    let entry = ListEntry {
+        service: Service::Bandcamp,
+        kind: PurchaseKind::Album,
+        id: "100".to_string(),
+        artist: "Deafheaven".to_string(),
+        title: "Sunbather".to_string(),
+        track_count: None,
+        gift: false,
+    };
+    assert_eq!(
+        entry.to_line(),
+        "bandcamp\talbum\t100\tDeafheaven\tSunbather\t-\tpurchase"
+    );
+}
+
+#[test]
+fn sorted_by_artist_then_title() {
+    let mut entries = from_qobuz(&qobuz_purchases());
+    entries.extend(from_bandcamp(&BandcampPurchases {
+        items: vec![bandcamp_item("a", 1, "Édith Piaf", "La Vie en rose")],
+        redownload_urls: HashMap::new(),
+    }));
+
+    sort(&mut entries, SortOrder::Unicode);
+    let artists: Vec<&str> = entries.iter().map(|e| e.artist.as_str()).collect();
+    assert_eq!(artists, ["Édith Piaf", "John Coltrane", "Miles Davis"]);
+
+    sort(&mut entries, SortOrder::Bytes);
+    let artists: Vec<&str> = entries.iter().map(|e| e.artist.as_str()).collect();
+    assert_eq!(artists, ["John Coltrane", "Miles Davis", "Édith Piaf"]);
+}
+
+#[test]
+fn wishlist_response_parses_without_sale_fields() {
+    let json = r#"{
+        "more_available": false,
+        "last_token": "1700000000:300:t::",
+        "tracklists": {},
+        "items": [{
+            "band_name": "Deafheaven",
+            "item_title": "Dream House",
+            "item_id": 300,
+            "item_type": "track",
+            "token": "1700000000:300:t::",
+            "item_url": "https://deafheaven.bandcamp.com/track/dream-house"
+        }]
+    }"#;
+    let resp: BandcampCollectionResponse = serde_json::from_str(json).unwrap();
+    assert!(resp.redownload_urls.is_empty());
+
+    let entries = from_bandcamp_wishlist(&resp.items);
+    assert_eq!(entries[0].kind, PurchaseKind::Track);
+    assert_eq!(entries[0].id, "300");
+    assert_eq!(
+        entries[0].to_line(),
+        "track\t300\tDeafheaven\tDream House\thttps://deafheaven.bandcamp.com/track/dream-house"
+    );
+}
+
+#[test]
+fn wishlist_csv_quotes_fields_that_need_it() {
+    let mut item = bandcamp_item("a", 100, "Godspeed You! Black Emperor", "Yanqui U.X.O.");
+    item.item_title = "Lift Your Skinny Fists Like Antennas to Heaven, \"Live\"".to_string();
+    let entries = from_bandcamp_wishlist(&[item]);
+
+    assert_eq!(
+        wishlist_csv(&entries),
+        "type,id,artist,title,url\n\
+         album,100,Godspeed You! Black Emperor,\
+         \"Lift Your Skinny Fists Like Antennas to Heaven, \"\"Live\"\"\",\n"
+    );
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use qoget::lock::SyncLock;
+
+fn temp_target(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-lock-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn second_sync_fails_fast_until_first_releases() {
+    let target = temp_target("fail-fast");
+
+    let held = SyncLock::acquire(&target, false).unwrap();
+    let pid = std::fs::read_to_string(SyncLock::path(&target)).unwrap();
+    assert_eq!(pid.trim(), std::process::id().to_string());
+
+    let err = SyncLock::acquire(&target, false).err().unwrap().to_string();
+    assert!(err.contains("already syncing"), "{err}");
+    assert!(
+        err.contains(&format!("pid {}", std::process::id())),
+        "{err}"
+    );
+    assert!(err.contains("--wait-lock"), "{err}");
+
+    drop(held);
+    SyncLock::acquire(&target, false).unwrap();
+
+    std::fs::remove_dir_all(&target).unwrap();
+}
+
+#[test]
+fn wait_lock_blocks_until_released() {
+    let target = temp_target("wait");
+    let held = SyncLock::acquire(&target, false).unwrap();
+
+    let waiter = {
+        let target = target.clone();
+        std::thread::spawn(move || SyncLock::acquire(&target, true).map(|_| ()))
+    };
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!waiter.is_finished());
+
+    drop(held);
+    waiter.join().unwrap().unwrap();
+
+    std::fs::remove_dir_all(&target).unwrap();
+}
@@ -0,0 +1,34 @@
+use qoget::logging::{SUMMARY, Verbosity};
+use tracing::Level;
+
+#[test]
+fn flags_map_to_verbosity() {
+    assert_eq!(Verbosity::from_flags(0, false), Verbosity::Normal);
+    assert_eq!(Verbosity::from_flags(1, false), Verbosity::Debug);
+    assert_eq!(Verbosity::from_flags(2, false), Verbosity::Trace);
+    assert_eq!(Verbosity::from_flags(5, false), Verbosity::Trace);
+    assert_eq!(Verbosity::from_flags(0, true), Verbosity::Quiet);
+}
+
+#[test]
+fn filter_levels_apply_to_qoget_only() {
+    let normal = Verbosity::Normal.filter();
+    assert!(normal.would_enable("qoget::client", &Level::INFO));
+    assert!(!normal.would_enable("qoget::client", &Level::DEBUG));
+    assert!(!normal.would_enable("hyper", &Level::INFO));
+    assert!(normal.would_enable("hyper", &Level::WARN));
+
+    let quiet = Verbosity::Quiet.filter();
+    assert!(!quiet.would_enable("qoget", &Level::INFO));
+    assert!(quiet.would_enable("qoget", &Level::WARN));
+    assert!(quiet.would_enable(SUMMARY, &Level::INFO));
+    assert!(
+        !Verbosity::Normal
+            .filter()
+            .would_enable(SUMMARY, &Level::DEBUG)
+    );
+
+    let trace = Verbosity::Trace.filter();
+    assert!(trace.would_enable("qoget::client", &Level::TRACE));
+    assert!(!trace.would_enable("reqwest", &Level::DEBUG));
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use qoget::manifest::{
+    HashingWriter, Manifest, ManifestEntry, ProcessingStep, file_sha256, sha256_hex,
+};
+use qoget::models::{Album, AudioFormat, Service, Track, TrackId};
+use qoget::path::{Naming, track_path};
+
+mod common;
+
+fn make_album(artist: &str, title: &str) -> Album {
+    Album {
+        tracks_count: 1,
+        ..common::album(&format!("{artist}-{title}"), title, artist)
+    }
+}
+
+fn make_track(id: u64, artist: &str) -> Track {
+    common::track(id, &format!("Track {id}"), 1, artist)
+}
+
+fn manifest_with(albums: &[(&str, &str)]) -> Manifest {
+    let base = Path::new("/music");
+    let mut manifest = Manifest::default();
+    for (i, (artist, title)) in albums.iter().enumerate() {
+        let album = make_album(artist, title);
+        let track = make_track(i as u64, artist);
+        let path = track_path(base, &album, &track, ".mp3", Naming::default());
+        let entry = ManifestEntry::new(&album, &track, AudioFormat::Mp3, base, &path);
+        manifest.record(Service::Qobuz, track.id, entry);
+    }
+    manifest
+}
+
+#[test]
+fn entry_path_is_relative_to_target() {
+    let manifest = manifest_with(&[("Miles Davis", "Kind of Blue")]);
+    let entry = manifest.get(Service::Qobuz, TrackId(0)).unwrap();
+    assert_eq!(
+        entry.path,
+        Path::new("Miles Davis/Kind of Blue/01 - Track 0.mp3")
+    );
+    assert!(manifest.get(Service::Bandcamp, TrackId(0)).is_none());
+}
+
+#[test]
+fn find_albums_free_text() {
+    let manifest = manifest_with(&[
+        ("Miles Davis", "Kind of Blue"),
+        ("Miles Davis", "Bitches Brew"),
+        ("John Coltrane", "Blue Train"),
+    ]);
+
+    assert_eq!(manifest.find_albums("miles").len(), 2);
+    assert_eq!(manifest.find_albums("blue").len(), 2);
+    let hits = manifest.find_albums("brew");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].dir, Path::new("Miles Davis/Bitches Brew"));
+}
+
+#[test]
+fn find_albums_artist_slash_album() {
+    let manifest = manifest_with(&[
+        ("Miles Davis", "Kind of Blue"),
+        ("John Coltrane", "Blue Train"),
+    ]);
+
+    let hits = manifest.find_albums("coltrane/blue");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].album, "Blue Train");
+    assert!(manifest.find_albums("davis/train").is_empty());
+}
+
+const TAGS_V1: ProcessingStep = ProcessingStep {
+    name: "tags",
+    version: 1,
+};
+const TAGS_V2: ProcessingStep = ProcessingStep {
+    name: "tags",
+    version: 2,
+};
+
+#[test]
+fn processing_steps_are_versioned() {
+    let mut manifest = manifest_with(&[("Miles Davis", "Kind of Blue")]);
+    let dir = Path::new("Miles Davis/Kind of Blue");
+    let entry = manifest
+        .album_entries_mut(Service::Qobuz, dir)
+        .next()
+        .unwrap();
+    assert!(!entry.has_step(TAGS_V1));
+
+    entry.mark_step(TAGS_V1);
+    assert!(entry.has_step(TAGS_V1));
+    // A newer version of the step is still pending.
+    assert!(!entry.has_step(TAGS_V2));
+
+    let json = serde_json::to_string(&manifest).unwrap();
+    let reloaded: Manifest = serde_json::from_str(&json).unwrap();
+    let entry = reloaded.get(Service::Qobuz, TrackId(0)).unwrap();
+    assert!(entry.has_step(TAGS_V1));
+}
+
+#[test]
+fn entries_without_steps_load_and_save_unchanged() {
+    let json = r#"{"tracks":{"qobuz:1":{"format":"mp3","path":"A/B/01 - T.mp3"}}}"#;
+    let manifest: Manifest = serde_json::from_str(json).unwrap();
+    assert!(
+        manifest
+            .get(Service::Qobuz, TrackId(1))
+            .unwrap()
+            .steps
+            .is_empty()
+    );
+    assert!(!serde_json::to_string(&manifest).unwrap().contains("steps"));
+}
+
+#[test]
+fn album_entries_mut_matches_service_and_directory() {
+    let mut manifest = manifest_with(&[
+        ("Miles Davis", "Kind of Blue"),
+        ("Miles Davis", "Bitches Brew"),
+    ]);
+    let dir = Path::new("Miles Davis/Kind of Blue");
+    assert_eq!(manifest.album_entries_mut(Service::Qobuz, dir).count(), 1);
+    assert_eq!(
+        manifest.album_entries_mut(Service::Bandcamp, dir).count(),
+        0
+    );
+    assert_eq!(
+        manifest
+            .album_entries_mut(Service::Qobuz, Path::new("Miles Davis"))
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn checksums_computed_while_writing_match_the_file() {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join(format!("qoget-manifest-hash-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("track.mp3");
+
+    let mut out = HashingWriter::new(std::fs::File::create(&path).unwrap());
+    out.write_all(b"ID3").unwrap();
+    out.write_all(b" frames").unwrap();
+    let (_, sha256) = out.finish();
+    assert_eq!(sha256, file_sha256(&path).unwrap());
+    assert_eq!(sha256, sha256_hex(b"ID3 frames"));
+
+    // A known checksum is recorded as given rather than read back.
+    let album = make_album("Miles Davis", "Kind of Blue");
+    let track = make_track(1, "Miles Davis");
+    let entry = ManifestEntry::hashed(
+        &album,
+        &track,
+        AudioFormat::Mp3,
+        &dir,
+        &path,
+        Some("feed".to_string()),
+    );
+    assert_eq!(entry.sha256.as_deref(), Some("feed"));
+    assert_eq!(entry.size, Some(10));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+use qoget::config::{JellyfinConfig, NetworkConfig, PlexConfig};
+use qoget::media_server::{endpoint, refresh_jellyfin, refresh_plex, should_refresh};
+use qoget::models::{BandcampSyncResult, Service};
+use qoget::report::{ServiceReport, SyncReport};
+
+fn report(dry_run: bool, downloaded: &[&str]) -> SyncReport {
+    let target = Path::new("/music");
+    let mut report = SyncReport::new(target, dry_run);
+    let mut bandcamp = ServiceReport::new(Service::Bandcamp);
+    bandcamp.add_bandcamp(
+        &BandcampSyncResult {
+            downloaded: downloaded.len(),
+            skipped: 0,
+            would_download: 0,
+            retagged: 0,
+            succeeded: downloaded
+                .iter()
+                .map(|name| (name.to_string(), vec![PathBuf::from("/music/a.m4a")]))
+                .collect(),
+            skipped_items: vec![],
+            sizes: HashMap::new(),
+            failed: vec![],
+        },
+        target,
+    );
+    report.add(bandcamp, &Ok(()));
+    report.finish(&Ok(()));
+    report
+}
+
+/// Answer one request with `status_line`, returning the request's head.
+fn serve_once(status_line: &'static str) -> (String, std::thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+            let n = conn.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed early");
+            request.extend_from_slice(&buf[..n]);
+        }
+        conn.write_all(
+            format!("{status_line}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").as_bytes(),
+        )
+        .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+    (url, server)
+}
+
+#[test]
+fn refreshes_only_after_real_downloads() {
+    assert!(should_refresh(&report(false, &["Low - Hey What"])));
+    assert!(!should_refresh(&report(false, &[])));
+    assert!(!should_refresh(&report(true, &["Low - Hey What"])));
+}
+
+#[test]
+fn endpoint_joins_base_url() {
+    assert_eq!(
+        endpoint("http://nas:8096/", "Library/Refresh"),
+        "http://nas:8096/Library/Refresh"
+    );
+    assert_eq!(
+        endpoint("https://example.com/jellyfin", "Library/Refresh"),
+        "https://example.com/jellyfin/Library/Refresh"
+    );
+}
+
+#[tokio::test]
+async fn jellyfin_refresh_posts_with_api_key() {
+    let (url, server) = serve_once("HTTP/1.1 204 No Content");
+    let cfg = JellyfinConfig {
+        url,
+        api_key: "abc123".to_string(),
+    };
+    refresh_jellyfin(&cfg, &NetworkConfig::default())
+        .await
+        .unwrap();
+    let request = server.join().unwrap().to_ascii_lowercase();
+    assert!(request.starts_with("post /library/refresh "), "{request}");
+    assert!(request.contains("x-emby-token: abc123"), "{request}");
+}
+
+#[tokio::test]
+async fn plex_refresh_reports_rejected_token() {
+    let (url, server) = serve_once("HTTP/1.1 401 Unauthorized");
+    let cfg = PlexConfig {
+        url,
+        token: "wrong".to_string(),
+    };
+    let err = refresh_plex(&cfg, &NetworkConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("401"), "{err:#}");
+    let request = server.join().unwrap().to_ascii_lowercase();
+    assert!(
+        request.starts_with("get /library/sections/all/refresh "),
+        "{request}"
+    );
+    assert!(request.contains("x-plex-token: wrong"), "{request}");
+}
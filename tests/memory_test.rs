@@ -0,0 +1,115 @@
+//! Peak-memory guardrail for plan building on a large synthetic library.
+//!
+//! Runs in its own test binary so the counting allocator below only observes
+//! this test.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use qoget::manifest::Manifest;
+use qoget::models::{PurchaseList, Service, SizeMismatchPolicy, Track};
+use qoget::path::Naming;
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+
+mod common;
+
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let now = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc;
+
+const TRACKS: usize = 50_000;
+const TRACKS_PER_ALBUM: usize = 25;
+
+/// Peak heap growth allowed while planning a 50k-track library, on top of
+/// the purchase list itself. Planning currently peaks around 48 MiB (under
+/// 1 KiB per track); cloning full track listings into every task once pushed
+/// this past 250 MiB.
+const PLAN_BUDGET_BYTES: usize = 64 * 1024 * 1024;
+
+fn synthetic_library() -> PurchaseList {
+    let albums = (0..TRACKS / TRACKS_PER_ALBUM)
+        .map(|a| {
+            let artist = format!("Artist {a}");
+            let items: Vec<Track> = (0..TRACKS_PER_ALBUM)
+                .map(|t| Track {
+                    duration: 240,
+                    ..common::track(
+                        (a * TRACKS_PER_ALBUM + t) as u64,
+                        &format!("Track {t} of album {a}"),
+                        t as u8 + 1,
+                        &artist,
+                    )
+                })
+                .collect();
+            let album = common::album(&format!("album-{a}"), &format!("Album {a}"), &artist);
+            common::with_tracks(album, items)
+        })
+        .collect();
+    PurchaseList {
+        albums,
+        tracks: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn plan_for_50k_tracks_stays_within_budget() {
+    let purchases = synthetic_library();
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let tasks = collect_tasks(
+        &purchases,
+        Path::new("/nonexistent"),
+        ".mp3",
+        Naming::default(),
+    );
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    assert_eq!(plan.download_count(), TRACKS);
+
+    let used = PEAK.load(Ordering::Relaxed) - baseline;
+    eprintln!(
+        "USED {used} {}",
+        std::mem::size_of::<qoget::models::DownloadTask>()
+    );
+    assert!(
+        used <= PLAN_BUDGET_BYTES,
+        "planning {TRACKS} tracks peaked at {} MiB (budget {} MiB)",
+        used / (1024 * 1024),
+        PLAN_BUDGET_BYTES / (1024 * 1024)
+    );
+}
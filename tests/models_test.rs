@@ -1,4 +1,8 @@
-use qoget::models::{Album, AlbumId, FileUrlResponse, LoginResponse, PurchaseResponse, TrackId};
+use qoget::models::{
+    Album, AlbumId, AudioFormat, AudioQuality, BandcampLink, FavoritesResponse, FileUrlResponse,
+    GetItem, GiftFilter, LoginResponse, NameFilter, NamePattern, PurchaseList, PurchaseResponse,
+    QobuzItem, QobuzQuality, Since, TrackId,
+};
 
 #[test]
 fn parse_login_response() {
@@ -123,6 +127,65 @@ fn parse_file_url_response() {
     assert!(resp.url.starts_with("https://"));
     assert_eq!(resp.format_id, 5);
     assert_eq!(resp.mime_type, "audio/mpeg");
+    assert_eq!(resp.quality(), None);
+    assert_eq!(resp.format(), AudioFormat::Mp3);
+    assert!(!resp.sample);
+}
+
+#[test]
+fn file_url_response_flags_previews() {
+    let json = r#"{
+        "track_id": 216020864,
+        "url": "https://example.com/file?uid=test",
+        "format_id": 5,
+        "mime_type": "audio/mpeg",
+        "sample": true
+    }"#;
+    assert!(
+        serde_json::from_str::<FileUrlResponse>(json)
+            .unwrap()
+            .sample
+    );
+}
+
+#[test]
+fn file_url_response_reports_lossless_quality() {
+    let json = r#"{
+        "track_id": 216020864,
+        "url": "https://example.com/file?uid=test",
+        "format_id": 7,
+        "mime_type": "audio/flac",
+        "sampling_rate": 96,
+        "bit_depth": 24
+    }"#;
+
+    let resp = serde_json::from_str::<FileUrlResponse>(json).unwrap();
+    assert_eq!(resp.format(), AudioFormat::Flac);
+    let quality = resp.quality().unwrap();
+    assert_eq!(quality.to_string(), "24-96");
+    assert!(quality.is_hires());
+
+    let cd = AudioQuality {
+        bit_depth: 16,
+        sampling_rate: 44.1,
+    };
+    assert_eq!(cd.to_string(), "16-44.1");
+    assert!(!cd.is_hires());
+}
+
+#[test]
+fn qobuz_quality_falls_back_through_lower_formats() {
+    assert_eq!(QobuzQuality::default(), QobuzQuality::Mp3);
+    assert_eq!(QobuzQuality::Mp3.format_ids(), [5, 6]);
+    assert_eq!(QobuzQuality::Cd.format_ids(), [6, 5]);
+    assert_eq!(
+        "hires-192".parse::<QobuzQuality>().unwrap().format_ids(),
+        [27, 7, 6, 5]
+    );
+    assert_eq!(QobuzQuality::Hires96.format(), AudioFormat::Flac);
+    assert_eq!(QobuzQuality::Hires96.to_string(), "hires-96");
+    let err = "lossless".parse::<QobuzQuality>().unwrap_err();
+    assert!(err.to_string().contains("hires-192"), "{err}");
 }
 
 #[test]
@@ -133,6 +196,58 @@ fn track_id_newtype_deserializes() {
     assert_eq!(format!("{}", id), "216020864");
 }
 
+#[test]
+fn qobuz_item_from_links_and_ids() {
+    let album = |id: &str| QobuzItem::Album(AlbumId(id.to_string()));
+    assert_eq!(
+        "https://www.qobuz.com/us-en/album/kind-of-blue-miles-davis/0886443927087"
+            .parse::<QobuzItem>()
+            .unwrap(),
+        album("0886443927087")
+    );
+    assert_eq!(
+        "https://play.qobuz.com/album/hq3gk0jcoeq3a?utm_source=share"
+            .parse::<QobuzItem>()
+            .unwrap(),
+        album("hq3gk0jcoeq3a")
+    );
+    assert_eq!(
+        "https://open.qobuz.com/track/5966783/"
+            .parse::<QobuzItem>()
+            .unwrap(),
+        QobuzItem::Track(TrackId(5966783))
+    );
+    assert_eq!(
+        " 0886443927087 ".parse::<QobuzItem>().unwrap(),
+        album("0886443927087")
+    );
+    assert!(
+        "https://open.qobuz.com/track/abc"
+            .parse::<QobuzItem>()
+            .is_err()
+    );
+    assert!("kind of blue".parse::<QobuzItem>().is_err());
+}
+
+#[test]
+fn get_item_tells_services_apart() {
+    assert_eq!(
+        "0886443927087".parse::<GetItem>().unwrap(),
+        GetItem::Qobuz(QobuzItem::Album(AlbumId("0886443927087".to_string())))
+    );
+    let page = "https://music.example.org/album/hey-what";
+    assert_eq!(
+        page.parse::<GetItem>().unwrap(),
+        GetItem::Bandcamp(BandcampLink::Page(page.to_string()))
+    );
+    let receipt = "https://bandcamp.com/download?from=receipt&payment_id=9&sig=abc";
+    assert_eq!(
+        receipt.parse::<GetItem>().unwrap(),
+        GetItem::Bandcamp(BandcampLink::Download(receipt.to_string()))
+    );
+    assert!("https://low.bandcamp.com/music".parse::<GetItem>().is_err());
+}
+
 #[test]
 fn album_id_newtype_deserializes() {
     let json = "\"album-789\"";
@@ -140,3 +255,266 @@ fn album_id_newtype_deserializes() {
     assert_eq!(id.0, "album-789");
     assert_eq!(format!("{}", id), "album-789");
 }
+
+#[test]
+fn parse_purchase_metadata() {
+    let json = r#"{
+        "id": "album-123",
+        "title": "Gifted Album",
+        "version": null,
+        "artist": { "id": 99, "name": "Test Artist" },
+        "media_count": 1,
+        "tracks_count": 10,
+        "purchased_at": 1707900000,
+        "purchase_channel": "gift"
+    }"#;
+
+    let album: Album = serde_json::from_str(json).unwrap();
+    assert_eq!(album.purchase.purchased_at, Some(1707900000));
+    assert!(album.purchase.is_gift());
+}
+
+#[test]
+fn parse_album_credits() {
+    let json = r#"{
+        "id": "album-123",
+        "title": "Compilation",
+        "version": null,
+        "artist": { "id": 145383, "name": "Verschiedene Interpreten" },
+        "artists": [
+            { "id": 145383, "name": "Verschiedene Interpreten", "roles": ["main-artist"] },
+            { "id": 42, "name": "Guest", "roles": ["featured-artist"] }
+        ],
+        "media_count": 1,
+        "tracks_count": 10
+    }"#;
+
+    let album: Album = serde_json::from_str(json).unwrap();
+    assert_eq!(album.artist.name, "Verschiedene Interpreten");
+    assert_eq!(album.artists.len(), 2);
+    // Known by its id, whatever the name's language.
+    assert!(album.is_various_artists());
+}
+
+#[test]
+fn purchase_metadata_absent_on_catalog_album() {
+    let json = r#"{
+        "id": "album-123",
+        "title": "Catalog Album",
+        "version": null,
+        "artist": { "id": 99, "name": "Test Artist" },
+        "media_count": 1,
+        "tracks_count": 10
+    }"#;
+
+    let album: Album = serde_json::from_str(json).unwrap();
+    assert!(album.purchase.purchased_at.is_none());
+    assert!(!album.purchase.is_gift());
+}
+
+#[test]
+fn gift_filter_on_purchase_list() {
+    let json = r#"{
+        "albums": {"offset": 0, "limit": 50, "total": 2, "items": [
+            {"id": "a1", "title": "Bought", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1,
+             "purchase_channel": "purchase"},
+            {"id": "a2", "title": "Given", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1,
+             "purchase_channel": "voucher"}
+        ]},
+        "tracks": {"offset": 0, "limit": 50, "total": 0, "items": []}
+    }"#;
+    let resp: PurchaseResponse = serde_json::from_str(json).unwrap();
+    let list = || PurchaseList {
+        albums: resp.albums.items.clone(),
+        tracks: Vec::new(),
+    };
+
+    let mut all = list();
+    all.retain_gifts(GiftFilter::All);
+    assert_eq!(all.albums.len(), 2);
+
+    let mut only = list();
+    only.retain_gifts(GiftFilter::OnlyGifts);
+    assert_eq!(only.albums[0].title, "Given");
+    assert_eq!(only.albums.len(), 1);
+
+    let mut excluded = list();
+    excluded.retain_gifts(GiftFilter::ExcludeGifts);
+    assert_eq!(excluded.albums[0].title, "Bought");
+    assert_eq!(excluded.albums.len(), 1);
+
+    assert_eq!(GiftFilter::from_flags(false, false), GiftFilter::All);
+    assert_eq!(
+        GiftFilter::from_flags(true, false),
+        GiftFilter::ExcludeGifts
+    );
+    assert_eq!(GiftFilter::from_flags(false, true), GiftFilter::OnlyGifts);
+}
+
+fn pattern(s: &str) -> NamePattern {
+    s.parse().unwrap()
+}
+
+#[test]
+fn name_patterns_match_substrings_or_globs() {
+    assert!(pattern("davis").matches("Miles Davis"));
+    assert!(pattern("MILES").matches("Miles Davis"));
+    assert!(!pattern("coltrane").matches("Miles Davis"));
+    // Regex characters in a plain pattern are literal.
+    assert!(pattern("(live)").matches("Kind of Blue (Live)"));
+    assert!(!pattern("a.c").matches("abc"));
+
+    // A glob must match the whole name.
+    assert!(pattern("miles*").matches("Miles Davis"));
+    assert!(!pattern("davis*").matches("Miles Davis"));
+    assert!(pattern("*blue").matches("Kind of Blue"));
+    assert!(pattern("b?ue").matches("Blue"));
+    assert!(pattern("[bc]at").matches("Cat"));
+    assert!(!pattern("[!bc]at").matches("Bat"));
+    assert!(pattern("[!bc]at").matches("Hat"));
+    assert!(pattern("a[b").matches("A[B"));
+
+    assert!("".parse::<NamePattern>().is_err());
+}
+
+#[test]
+fn name_filter_on_purchase_list() {
+    let json = r#"{
+        "albums": {"offset": 0, "limit": 50, "total": 3, "items": [
+            {"id": "a1", "title": "Kind of Blue", "version": null,
+             "artist": {"id": 1, "name": "Miles Davis"}, "media_count": 1, "tracks_count": 1},
+            {"id": "a2", "title": "Bitches Brew", "version": null,
+             "artist": {"id": 1, "name": "Miles Davis"}, "media_count": 1, "tracks_count": 1},
+            {"id": "a3", "title": "Blue Train", "version": null,
+             "artist": {"id": 2, "name": "John Coltrane"}, "media_count": 1, "tracks_count": 1}
+        ]},
+        "tracks": {"offset": 0, "limit": 50, "total": 0, "items": []}
+    }"#;
+    let resp: PurchaseResponse = serde_json::from_str(json).unwrap();
+    let titles = |filter: NameFilter| {
+        let mut list = PurchaseList {
+            albums: resp.albums.items.clone(),
+            tracks: Vec::new(),
+        };
+        list.retain_names(&filter);
+        list.albums.into_iter().map(|a| a.title).collect::<Vec<_>>()
+    };
+
+    assert_eq!(titles(NameFilter::default()).len(), 3);
+    assert_eq!(
+        titles(NameFilter {
+            artist: Some(pattern("miles")),
+            album: None,
+        }),
+        ["Kind of Blue", "Bitches Brew"]
+    );
+    assert_eq!(
+        titles(NameFilter {
+            artist: None,
+            album: Some(pattern("*blue*")),
+        }),
+        ["Kind of Blue", "Blue Train"]
+    );
+    assert_eq!(
+        titles(NameFilter {
+            artist: Some(pattern("coltrane")),
+            album: Some(pattern("blue")),
+        }),
+        ["Blue Train"]
+    );
+}
+
+#[test]
+fn since_parses_dates_and_ages() {
+    let now = 1_700_000_000;
+    let at = |s: &str| Since::parse_at(s, now).map(|since| since.at);
+
+    assert_eq!(at("1970-01-01"), Ok(0));
+    assert_eq!(at("2024-01-01"), Ok(1_704_067_200));
+    assert_eq!(at("2024-02-29"), Ok(1_709_164_800));
+    assert_eq!(at("30d"), Ok(now - 30 * 86_400));
+    assert_eq!(at("2w"), Ok(now - 14 * 86_400));
+    assert_eq!(at("0d"), Ok(now));
+
+    for bad in [
+        "2023-02-29",
+        "2024-13-01",
+        "2024-1-1",
+        "yesterday",
+        "30",
+        "d",
+        "-3d",
+    ] {
+        assert!(at(bad).is_err(), "{bad}");
+    }
+
+    let since = Since::parse_at("30d", now).unwrap();
+    assert_eq!(since.to_string(), "30d");
+    assert!(since.keeps(Some(now)));
+    assert!(!since.keeps(Some(now - 31 * 86_400)));
+    assert!(since.keeps(None));
+}
+
+#[test]
+fn since_filter_on_purchase_list() {
+    let json = r#"{
+        "albums": {"offset": 0, "limit": 50, "total": 3, "items": [
+            {"id": "a1", "title": "Old", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1,
+             "purchased_at": 1600000000},
+            {"id": "a2", "title": "New", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1,
+             "purchased_at": 1700000000},
+            {"id": "a3", "title": "Undated", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1}
+        ]},
+        "tracks": {"offset": 0, "limit": 50, "total": 0, "items": []}
+    }"#;
+    let resp: PurchaseResponse = serde_json::from_str(json).unwrap();
+    let mut list = PurchaseList {
+        albums: resp.albums.items,
+        tracks: Vec::new(),
+    };
+    list.retain_since(&Since::parse_at("2023-06-01", 0).unwrap());
+    let titles: Vec<&str> = list.albums.iter().map(|a| a.title.as_str()).collect();
+    assert_eq!(titles, ["New", "Undated"]);
+}
+
+#[test]
+fn favorites_add_only_what_is_missing() {
+    let json = r#"{
+        "albums": {"offset": 0, "limit": 50, "total": 2, "items": [
+            {"id": "a1", "title": "Bought", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1},
+            {"id": "a2", "title": "Liked", "version": null,
+             "artist": {"id": 1, "name": "X"}, "media_count": 1, "tracks_count": 1}
+        ]}
+    }"#;
+    let albums: FavoritesResponse = serde_json::from_str(json).unwrap();
+    assert!(albums.tracks.is_none());
+    let tracks: FavoritesResponse = serde_json::from_str(
+        r#"{"tracks": {"offset": 0, "limit": 50, "total": 2, "items": [
+            {"id": 11, "title": "Bought Single", "track_number": 1, "media_number": 1,
+             "duration": 200, "performer": {"id": 1, "name": "X"}, "isrc": null},
+            {"id": 12, "title": "Liked Single", "track_number": 1, "media_number": 1,
+             "duration": 200, "performer": {"id": 1, "name": "X"}, "isrc": null}
+        ]}}"#,
+    )
+    .unwrap();
+    let favorites = PurchaseList {
+        albums: albums.albums.unwrap().items,
+        tracks: tracks.tracks.unwrap().items,
+    };
+
+    let mut purchases = PurchaseList {
+        albums: vec![favorites.albums[0].clone()],
+        tracks: vec![favorites.tracks[0].clone()],
+    };
+    assert_eq!(purchases.add_missing(favorites), (1, 1));
+    let albums: Vec<&str> = purchases.albums.iter().map(|a| a.title.as_str()).collect();
+    assert_eq!(albums, ["Bought", "Liked"]);
+    let ids: Vec<TrackId> = purchases.track_ids().collect();
+    assert_eq!(ids, [TrackId(11), TrackId(12)]);
+}
@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+
+use qoget::models::AudioFormat;
+use qoget::musicbrainz::{
+    IsrcLookup, Release, ReleaseList, id_tags, isrc_release, search_query, search_release,
+    tag_album, title_matches, track_position,
+};
+use qoget::tags::{mp4_freeform, mp4_text_items};
+
+const RELEASE: &str = r#"{
+  "id": "a3b4c5d6-0000-4000-8000-000000000001",
+  "title": "Kind of Blue",
+  "date": "1959-08-17",
+  "release-group": {"id": "rg000000-0000-4000-8000-000000000002"},
+  "artist-credit": [{"name": "Miles Davis", "artist": {"id": "561d854a-6a28-4aa7-8c99-323e6ce46c2a", "name": "Miles Davis"}}],
+  "media": [
+    {"position": 1, "tracks": [
+      {"id": "tr000000-0000-4000-8000-000000000011", "number": "1", "position": 1, "title": "So What",
+       "recording": {"id": "rec00000-0000-4000-8000-000000000021", "title": "So What"}},
+      {"id": "tr000000-0000-4000-8000-000000000012", "number": "2", "position": 2, "title": "Freddie Freeloader",
+       "recording": {"id": "rec00000-0000-4000-8000-000000000022", "title": "Freddie Freeloader"}}
+    ]}
+  ]
+}"#;
+
+fn release() -> Release {
+    serde_json::from_str(RELEASE).unwrap()
+}
+
+#[test]
+fn tags_use_picard_names_per_format() {
+    let release = release();
+    let flac = id_tags(AudioFormat::Flac, &release, Some((1, 2)));
+    assert_eq!(
+        flac,
+        [
+            (
+                "MUSICBRAINZ_ALBUMID",
+                "a3b4c5d6-0000-4000-8000-000000000001"
+            ),
+            (
+                "MUSICBRAINZ_RELEASEGROUPID",
+                "rg000000-0000-4000-8000-000000000002"
+            ),
+            (
+                "MUSICBRAINZ_ALBUMARTISTID",
+                "561d854a-6a28-4aa7-8c99-323e6ce46c2a"
+            ),
+            (
+                "MUSICBRAINZ_TRACKID",
+                "rec00000-0000-4000-8000-000000000022"
+            ),
+            (
+                "MUSICBRAINZ_RELEASETRACKID",
+                "tr000000-0000-4000-8000-000000000012"
+            ),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+    );
+
+    // A track that can't be placed still gets the album's IDs.
+    let mp3: Vec<String> = id_tags(AudioFormat::Mp3, &release, Some((2, 1)))
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(
+        mp3,
+        [
+            "MusicBrainz Album Id",
+            "MusicBrainz Release Group Id",
+            "MusicBrainz Album Artist Id"
+        ]
+    );
+}
+
+#[test]
+fn releases_must_match_the_album_title() {
+    let lookup: IsrcLookup = serde_json::from_str(
+        r#"{"recordings": [{"id": "r1", "releases": [
+            {"id": "comp", "title": "The Best of Jazz"},
+            {"id": "album", "title": "Kind of Blue"}
+        ]}]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        isrc_release(&lookup, "Kind Of Blue (Legacy Edition)").map(|r| r.id.as_str()),
+        Some("album")
+    );
+    assert!(isrc_release(&lookup, "Sketches of Spain").is_none());
+
+    let list: ReleaseList = serde_json::from_str(
+        r#"{"releases": [
+            {"id": "weak", "score": 70, "title": "Kind of Blue"},
+            {"id": "other", "score": 100, "title": "Kind of Bleu"},
+            {"id": "good", "score": 95, "title": "Kind of Blue"}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        search_release(&list, "Kind of Blue").map(|r| r.id.as_str()),
+        Some("good")
+    );
+    assert!(!title_matches("", "Kind of Blue"));
+    assert_eq!(
+        search_query("Sunn O)))", r#"Life "Metal""#),
+        r#"release:"Life \"Metal\"" AND artist:"Sunn O)))""#
+    );
+}
+
+#[test]
+fn positions_come_from_file_names() {
+    assert_eq!(
+        track_position(Path::new(
+            "Miles Davis/Kind of Blue/02 - Freddie Freeloader.mp3"
+        )),
+        Some((1, 2))
+    );
+    assert_eq!(
+        track_position(Path::new("Bach/Mass in B minor/Disc 2/11 - Credo.flac")),
+        Some((2, 11))
+    );
+    assert_eq!(track_position(Path::new("Low/Hey What/cover.jpg")), None);
+}
+
+fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+#[tokio::test]
+async fn m4a_tracks_get_ids_and_release_date() {
+    let dir = std::env::temp_dir().join(format!("qoget-mb-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("01 - So What.m4a");
+    let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+    file.extend(atom(b"moov", &atom(b"trak", b"")));
+    file.extend(atom(b"mdat", b"AUDIO"));
+    std::fs::write(&path, &file).unwrap();
+
+    let tracks: Vec<(PathBuf, AudioFormat)> = vec![(path.clone(), AudioFormat::Aac)];
+    let shas = tag_album(&tracks, &release()).await.unwrap();
+    assert_eq!(shas[0], qoget::manifest::file_sha256(&path).unwrap());
+    assert_eq!(
+        mp4_freeform(&path, "MusicBrainz Track Id")
+            .unwrap()
+            .as_deref(),
+        Some("rec00000-0000-4000-8000-000000000021")
+    );
+    let items = mp4_text_items(&path).unwrap();
+    assert!(
+        items.contains(&("\u{a9}day".to_string(), "1959-08-17".to_string())),
+        "{items:?}"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
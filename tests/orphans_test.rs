@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use qoget::orphans::clean;
+
+#[test]
+fn leftover_temp_files_are_removed() {
+    let root = std::env::temp_dir().join(format!("qoget-orphans-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let album = root.join("Low/Words");
+    std::fs::create_dir_all(&album).unwrap();
+    std::fs::create_dir_all(root.join(".qoget-temp/staged")).unwrap();
+    std::fs::create_dir_all(root.join(".qoget-trash/Low")).unwrap();
+    for name in [
+        "01 - Lazy.mp3",
+        "02 - Words.flac.tmp",
+        "03 - Cut.mp3.tmp",
+        "04 - Rope.tagging.tmp",
+        "checksums.sha256.tmp",
+        ".cover.jpg.part",
+        "notes.tmp",
+    ] {
+        std::fs::write(album.join(name), b"x").unwrap();
+    }
+    std::fs::write(root.join(".qoget-temp/staged/01 - Lazy.m4a"), b"x").unwrap();
+    std::fs::write(root.join(".qoget-trash/Low/05 - Old.mp3.tmp"), b"x").unwrap();
+
+    let removed = clean(&root).unwrap();
+    assert_eq!(
+        removed,
+        [
+            ".qoget-temp",
+            "Low/Words/.cover.jpg.part",
+            "Low/Words/02 - Words.flac.tmp",
+            "Low/Words/03 - Cut.mp3.tmp",
+            "Low/Words/04 - Rope.tagging.tmp",
+            "Low/Words/checksums.sha256.tmp",
+        ]
+        .map(PathBuf::from)
+    );
+    assert!(album.join("01 - Lazy.mp3").exists());
+    // Not one of qoget's, and not in a directory it cleans.
+    assert!(album.join("notes.tmp").exists());
+    assert!(root.join(".qoget-trash/Low/05 - Old.mp3.tmp").exists());
+
+    assert!(clean(&root).unwrap().is_empty());
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -1,35 +1,35 @@
 use std::path::Path;
 
-use qoget::models::{Album, AlbumId, Artist, DiscNumber, Track, TrackId, TrackNumber};
-use qoget::path::{sanitize_component, track_path};
+use qoget::models::{Album, Artist, DiscNumber, Track};
+use qoget::path::{
+    MAX_PATH_BYTES, Naming, SanitizeMode, UnicodeForm, is_dated_album_dir, sanitize_component,
+    sanitize_component_with, track_path, track_paths,
+};
+
+mod common;
+
+/// `sanitize_component` with the default naming.
+fn sanitize(s: &str) -> String {
+    sanitize_component(s, Naming::default())
+}
 
 fn make_album(artist: &str, title: &str, media_count: u8) -> Album {
     Album {
-        id: AlbumId("test-album".to_string()),
-        title: title.to_string(),
-        version: None,
-        artist: Artist {
-            id: 1,
-            name: artist.to_string(),
-        },
         media_count,
         tracks_count: 10,
-        tracks: None,
+        ..common::album("test-album", title, artist)
     }
 }
 
 fn make_track(title: &str, number: u8, disc: u8, performer: &str) -> Track {
+    let track = common::track(1000, title, number, performer);
     Track {
-        id: TrackId(1000),
-        title: title.to_string(),
-        track_number: TrackNumber(number),
         media_number: DiscNumber(disc),
-        duration: 200,
         performer: Artist {
             id: 2,
-            name: performer.to_string(),
+            ..track.performer
         },
-        isrc: None,
+        ..track
     }
 }
 
@@ -39,7 +39,7 @@ fn single_disc_album() {
     let track = make_track("Breathe", 2, 1, "Pink Floyd");
     let base = Path::new("/music");
 
-    let path = track_path(base, &album, &track, ".mp3");
+    let path = track_path(base, &album, &track, ".mp3", Naming::default());
     assert_eq!(
         path,
         Path::new("/music/Pink Floyd/The Dark Side of the Moon/02 - Breathe.mp3")
@@ -52,7 +52,7 @@ fn multi_disc_album() {
     let track = make_track("Birthday", 1, 2, "The Beatles");
     let base = Path::new("/music");
 
-    let path = track_path(base, &album, &track, ".mp3");
+    let path = track_path(base, &album, &track, ".mp3", Naming::default());
     assert_eq!(
         path,
         Path::new("/music/The Beatles/White Album/Disc 2/01 - Birthday.mp3")
@@ -65,33 +65,88 @@ fn compilation_album() {
     let track = make_track("So What", 1, 1, "Miles Davis");
     let base = Path::new("/music");
 
-    let path = track_path(base, &album, &track, ".mp3");
+    let path = track_path(base, &album, &track, ".mp3", Naming::default());
     assert_eq!(
         path,
         Path::new("/music/Various Artists/Jazz Classics/01 - Miles Davis - So What.mp3")
     );
 }
 
+#[test]
+fn credited_and_featured_artists_are_not_a_compilation() {
+    let mut album = make_album("Robert Glasper", "Black Radio", 1);
+    album.artists = vec![Artist {
+        id: 7,
+        name: "Erykah Badu".to_string(),
+    }];
+    let base = Path::new("/music");
+
+    // Credited alongside the album artist, or the album artist with a guest.
+    for performer in ["Erykah Badu", "Robert Glasper feat. Lalah Hathaway"] {
+        let track = make_track("Afro Blue", 1, 1, performer);
+        assert_eq!(
+            track_path(base, &album, &track, ".mp3", Naming::default()),
+            Path::new("/music/Robert Glasper/Black Radio/01 - Afro Blue.mp3")
+        );
+    }
+    // Someone the album doesn't credit is still named.
+    let track = make_track("Afro Blue", 1, 1, "Robert Glasperson");
+    assert_eq!(
+        track_path(base, &album, &track, ".mp3", Naming::default()),
+        Path::new("/music/Robert Glasper/Black Radio/01 - Robert Glasperson - Afro Blue.mp3")
+    );
+
+    // Various Artists names every track's artist, even one it credits.
+    let mut various = make_album("Various Artists", "Jazz Classics", 1);
+    various.artists = vec![Artist {
+        id: 2,
+        name: "Miles Davis".to_string(),
+    }];
+    let track = make_track("So What", 1, 1, "Miles Davis");
+    assert_eq!(
+        track_path(base, &various, &track, ".mp3", Naming::default()),
+        Path::new("/music/Various Artists/Jazz Classics/01 - Miles Davis - So What.mp3")
+    );
+}
+
 #[test]
 fn m4a_extension() {
     let album = make_album("Deafheaven", "Sunbather", 1);
     let track = make_track("Dream House", 1, 1, "Deafheaven");
     let base = Path::new("/music");
 
-    let path = track_path(base, &album, &track, ".m4a");
+    let path = track_path(base, &album, &track, ".m4a", Naming::default());
     assert_eq!(
         path,
         Path::new("/music/Deafheaven/Sunbather/01 - Dream House.m4a")
     );
 }
 
+#[test]
+fn unknown_track_number_has_no_prefix() {
+    let album = make_album("Deafheaven", "Sunbather", 1);
+    let track = make_track("Dream House", 0, 1, "Deafheaven");
+
+    let path = track_path(
+        Path::new("/music"),
+        &album,
+        &track,
+        ".m4a",
+        Naming::default(),
+    );
+    assert_eq!(
+        path,
+        Path::new("/music/Deafheaven/Sunbather/Dream House.m4a")
+    );
+}
+
 #[test]
 fn m4a_compilation() {
     let album = make_album("Various Artists", "Bandcamp Compilation", 1);
     let track = make_track("Intro", 1, 1, "Some Band");
     let base = Path::new("/music");
 
-    let path = track_path(base, &album, &track, ".m4a");
+    let path = track_path(base, &album, &track, ".m4a", Naming::default());
     assert_eq!(
         path,
         Path::new("/music/Various Artists/Bandcamp Compilation/01 - Some Band - Intro.m4a")
@@ -100,35 +155,214 @@ fn m4a_compilation() {
 
 #[test]
 fn sanitize_slashes_and_colons() {
-    assert_eq!(sanitize_component("AC/DC"), "AC-DC");
-    assert_eq!(sanitize_component("foo\\bar"), "foo-bar");
-    assert_eq!(sanitize_component("Title: Subtitle"), "Title- Subtitle");
+    assert_eq!(sanitize("AC/DC"), "AC-DC");
+    assert_eq!(sanitize("foo\\bar"), "foo-bar");
+    assert_eq!(sanitize("Title: Subtitle"), "Title- Subtitle");
 }
 
 #[test]
 fn sanitize_removes_forbidden_chars() {
-    assert_eq!(sanitize_component("What?"), "What");
-    assert_eq!(sanitize_component("Star*"), "Star");
-    assert_eq!(sanitize_component("He said \"hello\""), "He said hello");
-    assert_eq!(sanitize_component("<tag>"), "tag");
-    assert_eq!(sanitize_component("a|b"), "ab");
+    assert_eq!(sanitize("What?"), "What");
+    assert_eq!(sanitize("Star*"), "Star");
+    assert_eq!(sanitize("He said \"hello\""), "He said hello");
+    assert_eq!(sanitize("<tag>"), "tag");
+    assert_eq!(sanitize("a|b"), "ab");
 }
 
 #[test]
 fn sanitize_leading_dot() {
-    assert_eq!(sanitize_component(".hidden"), "hidden");
-    assert_eq!(sanitize_component("...dots"), "dots");
+    assert_eq!(sanitize(".hidden"), "hidden");
+    assert_eq!(sanitize("...dots"), "dots");
 }
 
 #[test]
 fn sanitize_consecutive_spaces() {
-    assert_eq!(sanitize_component("a  b   c"), "a b c");
+    assert_eq!(sanitize("a  b   c"), "a b c");
 }
 
 #[test]
 fn sanitize_truncates_to_255_bytes() {
     let long = "a".repeat(300);
-    let result = sanitize_component(&long);
+    let result = sanitize(&long);
     assert!(result.len() <= 255);
     assert_eq!(result.len(), 255);
 }
+
+#[test]
+fn sanitize_strips_control_characters() {
+    assert_eq!(sanitize("Line\none"), "Lineone");
+    assert_eq!(sanitize("tab\there"), "tabhere");
+    assert_eq!(sanitize("nul\0byte"), "nulbyte");
+}
+
+#[test]
+fn windows_reserved_names() {
+    let w = SanitizeMode::Windows;
+    assert_eq!(sanitize_component_with("CON", w), "CON_");
+    assert_eq!(sanitize_component_with("nul", w), "nul_");
+    assert_eq!(sanitize_component_with("Com1", w), "Com1_");
+    assert_eq!(sanitize_component_with("LPT9", w), "LPT9_");
+    assert_eq!(sanitize_component_with("aux.txt", w), "aux_.txt");
+    assert_eq!(sanitize_component_with("Console", w), "Console");
+    assert_eq!(sanitize_component_with("COM10", w), "COM10");
+}
+
+#[test]
+fn windows_trailing_dots_and_spaces() {
+    let w = SanitizeMode::Windows;
+    assert_eq!(sanitize_component_with("Etc.", w), "Etc");
+    assert_eq!(sanitize_component_with("Wait... ", w), "Wait");
+    assert_eq!(sanitize_component_with("CON. ", w), "CON_");
+}
+
+#[test]
+fn portable_mode_keeps_trailing_dots_and_reserved_names() {
+    let p = SanitizeMode::Portable;
+    assert_eq!(sanitize_component_with("Etc.", p), "Etc.");
+    assert_eq!(sanitize_component_with("CON", p), "CON");
+}
+
+#[test]
+fn windows_safe_naming_applies_to_whole_track_paths() {
+    let album = make_album("Con", "Etc.", 1);
+    let track = make_track("Intro", 1, 1, "Con");
+    let naming = Naming {
+        windows_safe: true,
+        ..Naming::default()
+    };
+    let path = track_path(Path::new("/music"), &album, &track, ".flac", naming);
+    assert_eq!(path, Path::new("/music/Con_/Etc/01 - Intro.flac"));
+}
+
+#[test]
+fn windows_truncation_does_not_leave_trailing_dot() {
+    let long = format!("{}.{}", "a".repeat(254), "b".repeat(10));
+    let result = sanitize_component_with(&long, SanitizeMode::Windows);
+    assert_eq!(result, "a".repeat(254));
+}
+
+#[test]
+fn unicode_forms_normalize() {
+    assert_eq!(UnicodeForm::Nfc.normalize("Cafe\u{301}"), "Caf\u{e9}");
+    assert_eq!(UnicodeForm::Nfd.normalize("Caf\u{e9}"), "Cafe\u{301}");
+}
+
+#[test]
+fn long_titles_are_ellipsized_to_fit_the_path() {
+    let album = make_album("Wiener Philharmoniker", "Beethoven: Symphonies", 1);
+    let title = "Symphony No. 9 in D Minor, Op. 125 \"Choral\": IV. Presto – Allegro assai – \
+                 Recitativo: O Freunde, nicht diese Töne! – Allegro assai – "
+        .repeat(4);
+    let track = make_track(&title, 4, 1, "Wiener Philharmoniker");
+
+    let path = track_path(
+        Path::new("/music"),
+        &album,
+        &track,
+        ".flac",
+        Naming::default(),
+    );
+    let name = path.file_name().unwrap().to_str().unwrap();
+    assert!(name.len() <= 255, "{name}");
+    assert!(name.starts_with("04 - Symphony No. 9 in D Minor"), "{name}");
+    assert!(name.ends_with("….flac"), "{name}");
+
+    // A deep target leaves less room than the name limit.
+    let deep = format!(
+        "/music/{}",
+        "Some Very Long Directory Name/".repeat(MAX_PATH_BYTES / 31 - 4)
+    );
+    let short = make_track("Breathe", 2, 1, "Wiener Philharmoniker");
+    let path = track_path(Path::new(&deep), &album, &short, ".flac", Naming::default());
+    assert!(path.ends_with("02 - Breathe.flac"));
+    let path = track_path(Path::new(&deep), &album, &track, ".flac", Naming::default());
+    let name = path.file_name().unwrap().to_str().unwrap();
+    assert!(path.as_os_str().len() < MAX_PATH_BYTES, "{name}");
+    assert!(name.starts_with("04 - Symphony"), "{name}");
+    assert!(name.ends_with("….flac"), "{name}");
+}
+
+#[test]
+fn colliding_track_paths_get_ordinals() {
+    let album = make_album("Low", "Hey What", 1);
+    let tracks = [
+        make_track("Interlude", 3, 1, "Low"),
+        make_track("Days Like These", 4, 1, "Low"),
+        make_track("INTERLUDE", 3, 1, "Low"),
+        make_track("Interlude", 3, 1, "Low"),
+        make_track("", 0, 1, "Low"),
+        make_track("", 0, 1, "Low"),
+    ];
+
+    let paths = track_paths(
+        Path::new("/music"),
+        &album,
+        &tracks,
+        ".flac",
+        Naming::default(),
+    );
+    let names: Vec<_> = paths
+        .iter()
+        .map(|p| p.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "03 - Interlude.flac",
+            "04 - Days Like These.flac",
+            "03 - INTERLUDE (2).flac",
+            "03 - Interlude (3).flac",
+            ".flac",
+            "(2).flac",
+        ]
+    );
+    assert!(paths.iter().all(|p| p.starts_with("/music/Low/Hey What")));
+}
+
+#[test]
+fn track_versions_go_in_file_names_when_enabled() {
+    let album = make_album("Pink Floyd", "The Dark Side of the Moon", 1);
+    let mut remaster = make_track("Breathe", 2, 1, "Pink Floyd");
+    remaster.version = Some("2023 Remaster".to_string());
+    let mut live = make_track("Breathe (Live)", 2, 1, "Pink Floyd");
+    live.version = Some("Live".to_string());
+    let base = Path::new("/music");
+
+    let naming = Naming {
+        track_versions: true,
+        ..Naming::default()
+    };
+    let named = track_path(base, &album, &remaster, ".flac", naming);
+    let unchanged = track_path(base, &album, &live, ".flac", naming);
+    assert!(named.ends_with("02 - Breathe (2023 Remaster).flac"));
+    assert!(unchanged.ends_with("02 - Breathe (Live).flac"));
+    assert!(
+        track_path(base, &album, &remaster, ".flac", Naming::default())
+            .ends_with("02 - Breathe.flac")
+    );
+}
+
+#[test]
+fn album_years_go_before_titles_when_enabled() {
+    let mut album = make_album("Low", "Hey What", 1);
+    album.release_date = Some("2021-09-10".to_string());
+    let undated = make_album("Low", "Words", 1);
+    let track = make_track("White Horses", 1, 1, "Low");
+    let base = Path::new("/music");
+
+    let naming = Naming {
+        album_years: true,
+        ..Naming::default()
+    };
+    let dated = track_path(base, &album, &track, ".flac", naming);
+    let plain = track_path(base, &undated, &track, ".flac", naming);
+    assert_eq!(
+        dated,
+        Path::new("/music/Low/2021 - Hey What/01 - White Horses.flac")
+    );
+    assert_eq!(plain, Path::new("/music/Low/Words/01 - White Horses.flac"));
+
+    assert!(is_dated_album_dir("2021 - Hey What", "Hey What"));
+    assert!(!is_dated_album_dir("Hey What", "Hey What"));
+    assert!(!is_dated_album_dir("Live - Hey What", "Hey What"));
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{Artist, AudioFormat, Playlist, PlaylistTrack, Service, TrackId};
+use qoget::path::Naming;
+use qoget::playlist::{
+    PLAYLISTS_DIR, RECENTLY_ADDED, add_recently_added, append_recent, file_names, render, write,
+};
+
+fn entry(artist: &str, title: &str, path: &str) -> ManifestEntry {
+    ManifestEntry {
+        format: AudioFormat::Mp3,
+        artist: artist.to_string(),
+        album: "Album".to_string(),
+        title: title.to_string(),
+        path: PathBuf::from(path),
+        size: None,
+        sha256: None,
+        quality: None,
+        genre: None,
+        steps: BTreeMap::new(),
+    }
+}
+
+fn track(id: u64, title: &str, performer: Option<&str>) -> PlaylistTrack {
+    PlaylistTrack {
+        id: TrackId(id),
+        title: title.to_string(),
+        duration: 180 + id as u32,
+        performer: performer.map(|name| Artist {
+            id: 1,
+            name: name.to_string(),
+        }),
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-playlist-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn playlist(id: u64, name: &str) -> Playlist {
+    Playlist {
+        id,
+        name: name.to_string(),
+        tracks_count: 0,
+        tracks: None,
+    }
+}
+
+#[test]
+fn render_lists_synced_tracks_in_order_relative_to_playlists_dir() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(2),
+        entry("Band", "Second", "Band/Album/02 - Second.mp3"),
+    );
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Band", "First", "Band/Album/01 - First.mp3"),
+    );
+    let tracks = [
+        track(2, "Second", Some("Singer")),
+        track(3, "Never Synced", None),
+        track(1, "First", None),
+    ];
+
+    let rendered = render(&tracks, &manifest);
+
+    assert_eq!(rendered.found, 2);
+    assert_eq!(rendered.missing, 1);
+    assert_eq!(
+        rendered.contents,
+        "#EXTM3U\n\
+         #EXTINF:182,Singer - Second\n../Band/Album/02 - Second.mp3\n\
+         #EXTINF:181,Band - First\n../Band/Album/01 - First.mp3\n"
+    );
+}
+
+#[test]
+fn render_ignores_bandcamp_tracks_with_the_same_id() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Bandcamp,
+        TrackId(1),
+        entry("Band", "First", "a.m4a"),
+    );
+
+    let rendered = render(&[track(1, "First", None)], &manifest);
+
+    assert_eq!(rendered.found, 0);
+    assert_eq!(rendered.missing, 1);
+}
+
+#[test]
+fn file_names_disambiguate_clashing_names() {
+    let playlists = [
+        playlist(10, "Road Trip"),
+        playlist(11, "Focus: Deep Work"),
+        playlist(12, "road trip"),
+    ];
+
+    assert_eq!(
+        file_names(&playlists, Naming::default()),
+        [
+            "Road Trip (10).m3u8",
+            "Focus- Deep Work.m3u8",
+            "road trip (12).m3u8"
+        ]
+    );
+}
+
+#[test]
+fn write_replaces_an_earlier_export() {
+    let dir = temp_dir("write");
+    write(&dir, "Mix.m3u8", "old").unwrap();
+    let path = write(&dir, "Mix.m3u8", "#EXTM3U\n").unwrap();
+
+    assert_eq!(path, dir.join(PLAYLISTS_DIR).join("Mix.m3u8"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "#EXTM3U\n");
+    assert_eq!(
+        std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+        1
+    );
+}
+
+#[test]
+fn recently_added_keeps_the_newest_tracks() {
+    let a = entry("Band", "A", "Band/Album/01 - A.mp3");
+    let b = entry("Band", "B", "Band/Album/02 - B.mp3");
+    let c = entry("Other", "C", "Other/Album/01 - C.mp3");
+
+    let first = append_recent("", &[&a, &b], 2);
+    assert_eq!(
+        first,
+        "#EXTM3U\n\
+         #EXTINF:-1,Band - A\nBand/Album/01 - A.mp3\n\
+         #EXTINF:-1,Band - B\nBand/Album/02 - B.mp3\n"
+    );
+    // The oldest track falls off the top once the cap is reached.
+    let second = append_recent(&first, &[&c], 2);
+    assert_eq!(
+        second,
+        "#EXTM3U\n\
+         #EXTINF:-1,Band - B\nBand/Album/02 - B.mp3\n\
+         #EXTINF:-1,Other - C\nOther/Album/01 - C.mp3\n"
+    );
+    // A track synced again moves to the end instead of being listed twice.
+    let third = append_recent(&second, &[&b], 5);
+    assert_eq!(third.matches("02 - B.mp3").count(), 1);
+    assert!(third.ends_with("Band/Album/02 - B.mp3\n"));
+}
+
+#[test]
+fn recently_added_is_written_at_the_target_root() {
+    let dir = temp_dir("recent");
+    let a = entry("Band", "A", "Band/Album/01 - A.mp3");
+    let b = entry("Band", "B", "Band/Album/02 - B.mp3");
+    let path = add_recently_added(&dir, &[&a], 10).unwrap();
+    assert_eq!(path, dir.join(RECENTLY_ADDED));
+    add_recently_added(&dir, &[&b], 10).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(contents.matches("#EXTINF").count(), 2);
+    assert!(contents.starts_with("#EXTM3U\n#EXTINF:-1,Band - A\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+
+use indicatif::ProgressBar;
+use qoget::models::Service;
+use qoget::progress::{AlbumLog, DownloadItem, Events, Share, SyncEvents, Transfer};
+
+#[test]
+fn shares_correct_the_estimated_total() {
+    let total = ProgressBar::hidden();
+    total.set_length(300);
+
+    // Estimated at 100, turned out to be 150.
+    let mut bigger = Share::new(&total, 100);
+    bigger.set_size(150);
+    bigger.inc(150);
+    drop(bigger);
+    assert_eq!(total.length(), Some(350));
+
+    // Size unknown up front, and smaller than estimated.
+    let mut smaller = Share::new(&total, 100);
+    smaller.inc(60);
+    drop(smaller);
+    assert_eq!(total.length(), Some(310));
+
+    // Failed part way: what never arrived comes off the total.
+    let mut failed = Share::new(&total, 100);
+    failed.inc(30);
+    drop(failed);
+    assert_eq!(total.length(), Some(240));
+    assert_eq!(total.position(), 240);
+}
+
+#[test]
+fn album_lines_come_out_when_its_last_track_is_done() {
+    let mut log = AlbumLog::new([
+        ("a", "Low - Hey What".to_string()),
+        ("b", "Slint - Spiderland".to_string()),
+        ("a", "Low - Hey What".to_string()),
+    ]);
+    assert_eq!(log.finish(&"a", true), None);
+    log.note(&"b", "Failed: Nosferatu Man: HTTP 500".to_string());
+    assert_eq!(
+        log.finish(&"b", false).as_deref(),
+        Some(
+            "Slint - Spiderland\n  Failed: Nosferatu Man: HTTP 500\n  Done: 0 of 1 tracks, 1 failed"
+        )
+    );
+    log.note(&"a", "MP3 unavailable, got FLAC: White Horses".to_string());
+    assert_eq!(
+        log.finish(&"a", true).as_deref(),
+        Some("Low - Hey What\n  MP3 unavailable, got FLAC: White Horses\n  Done: 2 tracks")
+    );
+}
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<String>>);
+
+impl SyncEvents for Recorder {
+    fn on_track_started(&self, item: &DownloadItem) {
+        self.0.lock().unwrap().push(format!("start {}", item.id));
+    }
+    fn on_bytes(&self, item: &DownloadItem, bytes: u64) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{bytes} of {}", item.id));
+    }
+    fn on_track_done(&self, item: &DownloadItem) {
+        self.0.lock().unwrap().push(format!("done {}", item.id));
+    }
+    fn on_item_failed(&self, item: &DownloadItem, error: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("failed {}: {error}", item.id));
+    }
+}
+
+#[test]
+fn transfers_report_to_the_events_sink() {
+    let recorder = Arc::new(Recorder::default());
+    let events = Events::new(recorder.clone());
+    let item = |id: &str| DownloadItem {
+        service: Service::Qobuz,
+        id: id.to_string(),
+        name: "Low - Words".to_string(),
+    };
+
+    let total = ProgressBar::hidden();
+    total.set_length(100);
+    events.track_started(&item("1"));
+    let mut transfer = Transfer::new(ProgressBar::hidden(), &events, item("1"))
+        .with_share(Share::new(&total, 100));
+    transfer.set_size(80);
+    transfer.inc(50);
+    transfer.inc(30);
+    transfer.finish();
+    drop(transfer);
+    events.track_done(&item("1"));
+    events.item_failed(&item("2"), "HTTP 404");
+
+    assert_eq!(total.position(), 80);
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        [
+            "start 1",
+            "50 of 1",
+            "30 of 1",
+            "done 1",
+            "failed 2: HTTP 404"
+        ]
+    );
+
+    // Nothing listening: the same calls are no-ops.
+    let mut quiet = Transfer::new(ProgressBar::hidden(), &Events::default(), item("3"));
+    quiet.inc(10);
+}
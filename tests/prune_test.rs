@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use qoget::bandcamp::BandcampPurchases;
+use qoget::checksums::CHECKSUM_FILE;
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{AudioFormat, BandcampCollectionItem, PurchaseList, Service, Track, TrackId};
+use qoget::path::Naming;
+use qoget::prune::{Purchased, prune, stale_tracks};
+use qoget::trash::Trash;
+
+mod common;
+
+fn entry(artist: &str, album: &str, title: &str, path: &str) -> ManifestEntry {
+    ManifestEntry {
+        format: AudioFormat::Mp3,
+        artist: artist.to_string(),
+        album: album.to_string(),
+        title: title.to_string(),
+        path: PathBuf::from(path),
+        size: None,
+        sha256: None,
+        quality: None,
+        genre: None,
+        steps: BTreeMap::new(),
+    }
+}
+
+fn track(id: u64) -> Track {
+    common::track(id, &format!("Track {id}"), id as u8, "Band")
+}
+
+fn qobuz_purchases(ids: &[u64]) -> PurchaseList {
+    let tracks: Vec<Track> = ids.iter().map(|&id| track(id)).collect();
+    PurchaseList {
+        albums: vec![common::with_tracks(
+            common::album("a1", "Record", "Band"),
+            tracks,
+        )],
+        tracks: vec![],
+    }
+}
+
+fn bandcamp_item(band: &str, title: &str, item_id: u64) -> BandcampCollectionItem {
+    BandcampCollectionItem {
+        band_name: band.to_string(),
+        item_title: title.to_string(),
+        item_id,
+        item_type: "album".to_string(),
+        sale_item_type: "a".to_string(),
+        sale_item_id: item_id,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: None,
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-prune-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn qobuz_tracks_no_longer_purchased_are_stale() {
+    let mut manifest = Manifest::default();
+    for id in [1, 2] {
+        let path = format!("Band/Record/0{id} - Track {id}.mp3");
+        let title = format!("Track {id}");
+        manifest.record(
+            Service::Qobuz,
+            TrackId(id),
+            entry("Band", "Record", &title, &path),
+        );
+    }
+    // Other services' tracks are never Qobuz's to prune.
+    manifest.record(
+        Service::Bandcamp,
+        TrackId(9),
+        entry("Other", "Album", "Nine", "Other/Album/09 - Nine.m4a"),
+    );
+
+    let purchased = Purchased::qobuz(&qobuz_purchases(&[1]));
+    let stale = stale_tracks(&manifest, Service::Qobuz, &purchased).unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].key, "qobuz:2");
+    assert_eq!(stale[0].title, "Track 2");
+
+    // No purchases at all looks like a bad listing, not a refund of everything.
+    let nothing = Purchased::qobuz(&qobuz_purchases(&[]));
+    assert!(stale_tracks(&manifest, Service::Qobuz, &nothing).is_err());
+}
+
+#[test]
+fn bandcamp_tracks_are_matched_by_album_directory() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Bandcamp,
+        TrackId(1001),
+        entry("Band", "Kept", "One", "Band/Kept/01 - One.m4a"),
+    );
+    manifest.record(
+        Service::Bandcamp,
+        TrackId(2001),
+        entry("Band", "Refunded", "One", "Band/Refunded/01 - One.m4a"),
+    );
+    // Synced with the year its download page gave.
+    manifest.record(
+        Service::Bandcamp,
+        TrackId(3001),
+        entry("Band", "Dated", "One", "Band/2019 - Dated/01 - One.m4a"),
+    );
+    let purchases = BandcampPurchases {
+        items: vec![
+            bandcamp_item("Band", "Kept", 1),
+            bandcamp_item("Band", "Dated", 3),
+        ],
+        redownload_urls: HashMap::new(),
+    };
+    let purchased = Purchased::bandcamp(&purchases, Naming::default());
+    let stale = stale_tracks(&manifest, Service::Bandcamp, &purchased).unwrap();
+    let paths: Vec<_> = stale.iter().map(|s| s.path.clone()).collect();
+    assert_eq!(paths, vec![PathBuf::from("Band/Refunded/01 - One.m4a")]);
+}
+
+#[test]
+fn pruning_trashes_files_and_forgets_them() {
+    let dir = temp_dir("trash");
+    let mut manifest = Manifest::default();
+    for (id, album) in [(1, "Kept"), (2, "Gone")] {
+        let path = format!("Band/{album}/01 - Track {id}.mp3");
+        std::fs::create_dir_all(dir.join(format!("Band/{album}"))).unwrap();
+        std::fs::write(dir.join(&path), b"audio").unwrap();
+        manifest.record(
+            Service::Qobuz,
+            TrackId(id),
+            entry("Band", album, "Track", &path),
+        );
+    }
+    // Left by an earlier sync; it goes with the album.
+    std::fs::write(dir.join("Band/Gone").join(CHECKSUM_FILE), "").unwrap();
+    let purchased = Purchased::qobuz(&qobuz_purchases(&[1]));
+    let stale = stale_tracks(&manifest, Service::Qobuz, &purchased).unwrap();
+
+    let batch = prune(&dir, &mut manifest, &stale).unwrap();
+    assert!(batch.starts_with(Trash::dir(&dir)));
+    assert!(batch.join("Band/Gone/01 - Track 2.mp3").is_file());
+    // The emptied album directory goes; the artist still has an album.
+    assert!(!dir.join("Band/Gone").exists());
+    assert!(dir.join("Band/Kept/01 - Track 1.mp3").is_file());
+    assert!(manifest.get(Service::Qobuz, TrackId(2)).is_none());
+    assert!(manifest.get(Service::Qobuz, TrackId(1)).is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use qoget::models::AudioFormat;
+use qoget::replaygain::{Loudness, gain_tags, measure_args, parse_summary};
+
+/// The end of what `ffmpeg -filter_complex ebur128=peak=sample` logs.
+const SUMMARY: &str = "\
+[Parsed_ebur128_1 @ 0x5581] Summary:
+
+  Integrated loudness:
+    I:         -11.4 LUFS
+    Threshold: -21.6 LUFS
+
+  Loudness range:
+    LRA:         5.3 LU
+    Threshold: -31.7 LUFS
+    LRA low:   -15.2 LUFS
+    LRA high:   -9.9 LUFS
+
+  Sample peak:
+    Peak:       -0.3 dBFS
+";
+
+#[test]
+fn summary_is_parsed() {
+    let log = format!("Input #0, flac, from 'a.flac':\n{SUMMARY}");
+    assert_eq!(
+        parse_summary(&log).unwrap(),
+        Loudness {
+            integrated: -11.4,
+            peak: -0.3
+        }
+    );
+}
+
+#[test]
+fn silence_has_no_peak() {
+    let log = SUMMARY.replace("-0.3 dBFS", "-inf dBFS");
+    let loudness = parse_summary(&log).unwrap();
+    assert_eq!(loudness.linear_peak(), 0.0);
+}
+
+#[test]
+fn missing_summary_is_an_error() {
+    assert!(parse_summary("a.flac: Invalid data found when processing input").is_err());
+    assert!(parse_summary("Summary:\n  Integrated loudness:\n").is_err());
+}
+
+#[test]
+fn replaygain_tags() {
+    let track = Loudness {
+        integrated: -11.4,
+        peak: -0.3,
+    };
+    let album = Loudness {
+        integrated: -20.0,
+        peak: 0.0,
+    };
+    let tags = gain_tags(AudioFormat::Flac, track, album);
+    let get = |key: &str| tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    assert_eq!(get("REPLAYGAIN_TRACK_GAIN"), Some("-6.60 dB"));
+    assert_eq!(get("REPLAYGAIN_TRACK_PEAK"), Some("0.966051"));
+    assert_eq!(get("REPLAYGAIN_ALBUM_GAIN"), Some("+2.00 dB"));
+    assert_eq!(get("REPLAYGAIN_ALBUM_PEAK"), Some("1.000000"));
+}
+
+#[test]
+fn opus_gets_r128_tags() {
+    let track = Loudness {
+        integrated: -11.4,
+        peak: -0.3,
+    };
+    let album = Loudness {
+        integrated: -23.0,
+        peak: -1.0,
+    };
+    let tags = gain_tags(AudioFormat::Opus, track, album);
+    assert_eq!(
+        tags,
+        vec![
+            ("R128_TRACK_GAIN".to_string(), "-2970".to_string()),
+            ("R128_ALBUM_GAIN".to_string(), "0".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn album_is_measured_as_one_stream() {
+    let inputs = [PathBuf::from("01.flac"), PathBuf::from("02.flac")];
+    let args: Vec<String> = measure_args(&inputs)
+        .into_iter()
+        .map(|a| a.into_string().unwrap())
+        .collect();
+    let filter = args.iter().position(|a| a == "-filter_complex").unwrap();
+    assert!(args[filter + 1].starts_with("[0:a][1:a]concat=n=2:v=0:a=1,ebur128"));
+    assert_eq!(args.iter().filter(|a| *a == "-i").count(), 2);
+    assert_eq!(&args[args.len() - 3..], ["-f", "null", "-"]);
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use qoget::config::NetworkConfig;
+use qoget::health::AuthError;
+use qoget::models::{
+    AlbumId, BandcampDownloadError, BandcampSyncResult, FailOn, QobuzItem, Service, SkipReason,
+    SkippedTrack, TrackId,
+};
+use qoget::notify::{WebhookPayload, post_webhook};
+use qoget::report::{DownloadsFailed, FailureKind, RetryList, ServiceReport, SyncReport};
+
+mod common;
+
+fn skipped(title: &str, reason: SkipReason) -> SkippedTrack {
+    SkippedTrack {
+        track: common::track(1, title, 1, "Miles Davis"),
+        target_path: PathBuf::from(format!("/music/Miles Davis/Kind of Blue/01 - {title}.mp3")),
+        reason,
+    }
+}
+
+fn sample_report() -> SyncReport {
+    let target = Path::new("/music");
+    let mut report = SyncReport::new(target, false);
+
+    let mut qobuz = ServiceReport::new(Service::Qobuz);
+    qobuz.add_skipped(&[skipped("So What", SkipReason::AlreadyExists)], target);
+    report.add(qobuz, &Ok(()));
+
+    let mut bandcamp = ServiceReport::new(Service::Bandcamp);
+    bandcamp.add_bandcamp(
+        &BandcampSyncResult {
+            downloaded: 2,
+            skipped: 0,
+            would_download: 0,
+            retagged: 0,
+            succeeded: vec![(
+                "Low - Hey What".to_string(),
+                vec![
+                    PathBuf::from("/music/Low/Hey What/01 - White Horses.m4a"),
+                    PathBuf::from("/music/Low/Hey What/02 - I Can Wait.m4a"),
+                ],
+            )],
+            skipped_items: vec![],
+            sizes: HashMap::new(),
+            failed: vec![BandcampDownloadError {
+                item_id: 1,
+                description: "Grouper - Shade".to_string(),
+                error: "HTTP 500".to_string(),
+            }],
+        },
+        target,
+    );
+    report.add(bandcamp, &Err(DownloadsFailed(Service::Bandcamp).into()));
+
+    report.finish(&Err(anyhow::anyhow!("One or more services failed")));
+    report
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("qoget-report-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn json_report_lists_outcomes_per_service() {
+    let path = temp_path("report.json");
+    sample_report().write(&path).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(json["ok"], false);
+    assert_eq!(json["error"], "One or more services failed");
+    let qobuz = &json["services"][0];
+    assert_eq!(qobuz["service"], "qobuz");
+    assert!(qobuz.get("error").is_none());
+    assert_eq!(qobuz["skipped"][0]["name"], "Miles Davis - So What");
+    assert_eq!(qobuz["skipped"][0]["reason"], "already-synced");
+    assert_eq!(
+        qobuz["skipped"][0]["path"],
+        "Miles Davis/Kind of Blue/01 - So What.mp3"
+    );
+
+    let bandcamp = &json["services"][1];
+    assert_eq!(bandcamp["error"], "Some Bandcamp downloads failed");
+    assert_eq!(bandcamp["succeeded"].as_array().unwrap().len(), 2);
+    assert_eq!(bandcamp["succeeded"][1]["format"], "aac");
+    assert_eq!(bandcamp["failed"][0]["name"], "Grouper - Shade");
+    assert_eq!(bandcamp["failed"][0]["error"], "HTTP 500");
+}
+
+#[test]
+fn failures_file_lists_failed_downloads_until_a_clean_run() {
+    let path = temp_path("state").join("last-failures.json");
+    assert_eq!(sample_report().write_failures(&path).unwrap(), 1);
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(json["target_dir"], "/music");
+    let failure = &json["failures"][0];
+    assert_eq!(failure["service"], "bandcamp");
+    assert_eq!(failure["id"], "1");
+    assert_eq!(failure["name"], "Grouper - Shade");
+    assert_eq!(failure["error"], "HTTP 500");
+
+    let mut clean = SyncReport::new(Path::new("/music"), false);
+    clean.add(ServiceReport::new(Service::Qobuz), &Ok(()));
+    assert_eq!(clean.write_failures(&path).unwrap(), 0);
+    assert!(!path.exists());
+    std::fs::remove_dir(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn retry_list_reads_back_failure_ids() {
+    let retry = RetryList::parse(
+        r#"{"started_at": 0, "target_dir": "/music", "failures": [
+            {"service": "qobuz", "id": "11", "album_id": "abc", "name": "A - One", "error": "HTTP 500"},
+            {"service": "qobuz", "id": "12", "album_id": "abc", "name": "A - Two", "error": "HTTP 500"},
+            {"service": "qobuz", "id": "20", "album_id": "standalone-20", "name": "B - Single", "error": "HTTP 500"},
+            {"service": "bandcamp", "id": "7", "name": "Grouper - Shade", "error": "HTTP 500"},
+            {"service": "bandcamp", "name": "Unknown", "error": "HTTP 500"}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(retry.target_dir, Path::new("/music"));
+    assert_eq!(
+        retry.qobuz_items,
+        [
+            QobuzItem::Album(AlbumId("abc".to_string())),
+            QobuzItem::Track(TrackId(20))
+        ]
+    );
+    assert_eq!(retry.qobuz_tracks.len(), 3);
+    assert_eq!(retry.bandcamp_items.iter().collect::<Vec<_>>(), [&7]);
+    assert_eq!(retry.services(), [Service::Qobuz, Service::Bandcamp]);
+}
+
+#[test]
+fn toml_extension_writes_toml() {
+    let path = temp_path("report.toml");
+    sample_report().write(&path).unwrap();
+    let report: toml::Value = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(report["target_dir"].as_str(), Some("/music"));
+    let services = report["services"].as_array().unwrap();
+    assert_eq!(services[1]["service"].as_str(), Some("bandcamp"));
+    assert_eq!(services[1]["failed"][0]["error"].as_str(), Some("HTTP 500"));
+}
+
+#[test]
+fn fail_on_policy_decides_which_failures_pass() {
+    // Bandcamp: two tracks downloaded, one album failed.
+    let partial = sample_report();
+    assert!(!partial.tolerates(FailOn::Any));
+    assert!(partial.tolerates(FailOn::All));
+    assert!(partial.tolerates(FailOn::None));
+
+    // Every download failed.
+    let target = Path::new("/music");
+    let mut total = SyncReport::new(target, false);
+    let mut bandcamp = ServiceReport::new(Service::Bandcamp);
+    bandcamp.add_bandcamp(
+        &BandcampSyncResult {
+            downloaded: 0,
+            skipped: 0,
+            would_download: 0,
+            retagged: 0,
+            succeeded: vec![],
+            skipped_items: vec![],
+            sizes: HashMap::new(),
+            failed: vec![BandcampDownloadError {
+                item_id: 1,
+                description: "Grouper - Shade".to_string(),
+                error: "HTTP 500".to_string(),
+            }],
+        },
+        target,
+    );
+    total.add(
+        bandcamp,
+        &Err(anyhow::anyhow!("Some Bandcamp downloads failed")),
+    );
+    assert!(!total.tolerates(FailOn::All));
+    assert!(total.tolerates(FailOn::None));
+
+    // A service that never got to download, e.g. a rejected login.
+    let mut broken = SyncReport::new(target, false);
+    broken.add(
+        ServiceReport::new(Service::Qobuz),
+        &Err(anyhow::anyhow!("login failed")),
+    );
+    assert!(!broken.tolerates(FailOn::None));
+
+    assert!(!SyncReport::new(target, false).tolerates(FailOn::None));
+}
+
+#[tokio::test]
+async fn failures_are_classified_per_service() {
+    let partial = sample_report();
+    assert_eq!(partial.services[0].failure, None);
+    assert_eq!(partial.services[1].failure, Some(FailureKind::Downloads));
+    assert_eq!(partial.exit_code(), 3);
+    assert_eq!(partial.failed_services(), "Bandcamp (downloads)");
+    let json = serde_json::to_value(&partial).unwrap();
+    assert_eq!(json["services"][1]["failure"].as_str(), Some("downloads"));
+
+    // Nothing listens on port 1, so this is a connection error.
+    let unreachable = reqwest::get("http://127.0.0.1:1/")
+        .await
+        .map(|_| ())
+        .map_err(|e| anyhow::Error::new(e).context("fetching purchases"));
+    let rejected = Err(AuthError(Service::Bandcamp).into());
+
+    let target = Path::new("/music");
+    let mut report = SyncReport::new(target, false);
+    report.add(ServiceReport::new(Service::Qobuz), &unreachable);
+    report.finish(&Err(anyhow::anyhow!("One or more services failed")));
+    assert_eq!(report.services[0].failure, Some(FailureKind::Network));
+    assert_eq!(report.exit_code(), 4);
+
+    // The most serious failure decides the exit status.
+    report.add(ServiceReport::new(Service::Bandcamp), &rejected);
+    assert_eq!(report.exit_code(), 5);
+    assert_eq!(report.failed_services(), "Qobuz (network), Bandcamp (auth)");
+
+    let mut other = SyncReport::new(target, false);
+    other.finish(&Err(anyhow::anyhow!("Bandcamp is not configured")));
+    assert_eq!(other.exit_code(), 1);
+    let mut fine = SyncReport::new(target, false);
+    fine.finish(&Ok(()));
+    assert_eq!(fine.exit_code(), 0);
+}
+
+#[test]
+fn webhook_payload_counts_outcomes() {
+    let report = sample_report();
+    let payload = serde_json::to_value(WebhookPayload::new(&report)).unwrap();
+
+    assert_eq!(payload["ok"], false);
+    assert_eq!(payload["text"], payload["content"]);
+    let text = payload["text"].as_str().unwrap();
+    assert!(text.starts_with("qoget sync of /music failed"), "{text}");
+    assert!(
+        text.contains("Bandcamp: 2 downloaded, 1 failed, 0 skipped"),
+        "{text}"
+    );
+
+    let qobuz = &payload["services"][0];
+    assert_eq!(qobuz["skipped"], 1);
+    assert_eq!(qobuz["failures"].as_array().unwrap().len(), 0);
+    let bandcamp = &payload["services"][1];
+    assert_eq!(bandcamp["succeeded"], 2);
+    assert_eq!(bandcamp["failed"], 1);
+    assert_eq!(bandcamp["failures"][0]["name"], "Grouper - Shade");
+    assert_eq!(bandcamp["error"], "Some Bandcamp downloads failed");
+}
+
+#[tokio::test]
+async fn webhook_posts_json_summary() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        // Read until the whole JSON body has arrived.
+        while !String::from_utf8_lossy(&request).trim_end().ends_with('}') {
+            let n = conn.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed early");
+            request.extend_from_slice(&buf[..n]);
+        }
+        conn.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    post_webhook(&url, &sample_report(), &NetworkConfig::default())
+        .await
+        .unwrap();
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /hook "), "{request}");
+    assert!(request.contains("application/json"), "{request}");
+    assert!(request.contains("\"target_dir\":\"/music\""), "{request}");
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use qoget::config::RequestsConfig;
+use qoget::models::Service;
+use qoget::retry::{DEFAULT_RETRY_STATUSES, RequestPolicy};
+
+#[test]
+fn backoff_doubles_per_retry() {
+    let policy = RequestPolicy::default_for(Service::Qobuz);
+    let delays: Vec<Duration> = (0..4).map(|retry| policy.delay(retry, 503)).collect();
+    assert_eq!(delays, [1, 2, 4, 8].map(Duration::from_secs));
+    // Qobuz has no separate wait for rate limiting.
+    assert_eq!(policy.delay(2, 429), Duration::from_secs(4));
+}
+
+#[test]
+fn rate_limited_requests_wait_the_fixed_backoff() {
+    let policy = RequestPolicy::default_for(Service::Bandcamp);
+    assert_eq!(policy.delay(0, 429), Duration::from_secs(10));
+    assert_eq!(policy.delay(2, 429), Duration::from_secs(10));
+    assert_eq!(policy.delay(2, 500), Duration::from_secs(4));
+}
+
+#[test]
+fn unconfigured_requests_use_each_services_default() {
+    let requests = RequestsConfig::default();
+    assert_eq!(requests.qobuz, RequestPolicy::default_for(Service::Qobuz));
+    assert_eq!(
+        requests.bandcamp,
+        RequestPolicy::default_for(Service::Bandcamp)
+    );
+}
+
+#[test]
+fn only_listed_statuses_are_retried() {
+    let mut policy = RequestPolicy::default_for(Service::Qobuz);
+    assert_eq!(policy.retry_statuses, DEFAULT_RETRY_STATUSES);
+    assert!(policy.retries(429));
+    assert!(policy.retries(503));
+    assert!(!policy.retries(400));
+    assert!(!policy.retries(401));
+
+    policy.retry_statuses = vec![503];
+    assert!(!policy.retries(429));
+    assert!(policy.retries(503));
+}
+
+#[test]
+fn jitter_adds_at_most_its_share() {
+    let mut policy = RequestPolicy::default_for(Service::Bandcamp);
+    let delay = Duration::from_secs(10);
+    policy.jitter = 0.0;
+    assert_eq!(policy.jittered(delay), delay);
+
+    policy.jitter = 0.5;
+    for _ in 0..100 {
+        let jittered = policy.jittered(delay);
+        assert!(
+            (delay..=Duration::from_secs(15)).contains(&jittered),
+            "{jittered:?}"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+
+use qoget::bandcamp::BandcampPurchases;
+use qoget::list::PurchaseKind;
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{Album, AudioFormat, Service, Track, TrackId};
+use qoget::models::{BandcampCollectionItem, CatalogSearchResponse, PurchaseList};
+use qoget::path::{Naming, track_path};
+use qoget::search::{bandcamp_collection_hits, qobuz_catalog_hits, search};
+use qoget::snapshot::{Snapshot, SnapshotItem};
+
+mod common;
+
+fn make_album(artist: &str, title: &str) -> Album {
+    Album {
+        tracks_count: 1,
+        ..common::album(title, title, artist)
+    }
+}
+
+fn make_track(id: u64, title: &str, artist: &str) -> Track {
+    common::track(id, title, 1, artist)
+}
+
+fn record(manifest: &mut Manifest, service: Service, album: &Album, track: &Track) -> PathBuf {
+    let base = Path::new("/music");
+    let path = track_path(base, album, track, ".mp3", Naming::default());
+    let entry = ManifestEntry::new(album, track, AudioFormat::Mp3, base, &path);
+    manifest.record(service, track.id, entry);
+    path.strip_prefix(base).unwrap().to_path_buf()
+}
+
+fn item(artist: &str, album: &str, track: Option<(&str, u64)>) -> SnapshotItem {
+    SnapshotItem {
+        artist: artist.to_string(),
+        album: album.to_string(),
+        track: track.map(|(t, _)| t.to_string()),
+        track_id: track.map(|(_, id)| TrackId(id)),
+    }
+}
+
+#[test]
+fn remote_hit_marked_synced_when_in_manifest() {
+    let album = make_album("Miles Davis", "Kind of Blue");
+    let so_what = make_track(1, "So What", "Miles Davis");
+    let mut manifest = Manifest::default();
+    let path = record(&mut manifest, Service::Qobuz, &album, &so_what);
+
+    let snapshot = Snapshot {
+        service: Service::Qobuz,
+        fetched_at: 0,
+        items: vec![
+            item("Miles Davis", "Kind of Blue", Some(("So What", 1))),
+            item(
+                "Miles Davis",
+                "Kind of Blue",
+                Some(("Freddie Freeloader", 2)),
+            ),
+        ],
+    };
+
+    let hits = search("kind of blue", &[snapshot], &manifest);
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].local_path, Some(path));
+    assert!(!hits[1].is_synced());
+}
+
+#[test]
+fn album_level_bandcamp_hit_resolves_album_dir() {
+    let album = make_album("Band", "Record");
+    let track = make_track(5, "Song", "Band");
+    let mut manifest = Manifest::default();
+    record(&mut manifest, Service::Bandcamp, &album, &track);
+
+    let snapshot = Snapshot {
+        service: Service::Bandcamp,
+        fetched_at: 0,
+        items: vec![item("Band", "Record", None)],
+    };
+
+    let hits = search("record", &[snapshot], &manifest);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].local_path, Some(PathBuf::from("Band/Record")));
+}
+
+#[test]
+fn local_only_entries_reported_without_snapshot() {
+    let album = make_album("John Coltrane", "Blue Train");
+    let track = make_track(9, "Moment's Notice", "John Coltrane");
+    let mut manifest = Manifest::default();
+    record(&mut manifest, Service::Qobuz, &album, &track);
+
+    let hits = search("coltrane", &[], &manifest);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].service, Service::Qobuz);
+    assert!(hits[0].is_synced());
+    assert!(search("davis", &[], &manifest).is_empty());
+}
+
+#[test]
+fn qobuz_catalog_hits_can_be_passed_to_get() {
+    let resp: CatalogSearchResponse = serde_json::from_str(
+        r#"{
+            "query": "kind of blue",
+            "albums": {"offset": 0, "limit": 20, "total": 1, "items": [{
+                "id": "0886443927087", "title": "Kind of Blue", "version": "Legacy Edition",
+                "artist": {"id": 1, "name": "Miles Davis"},
+                "media_count": 2, "tracks_count": 12
+            }]},
+            "tracks": {"offset": 0, "limit": 20, "total": 1, "items": [{
+                "id": 5966783, "title": "So What", "track_number": 1, "media_number": 1,
+                "duration": 562, "performer": {"id": 1, "name": "Miles Davis"}
+            }]}
+        }"#,
+    )
+    .unwrap();
+    let results = PurchaseList {
+        albums: resp.albums.unwrap().items,
+        tracks: resp.tracks.unwrap().items,
+    };
+    let lines: Vec<String> = qobuz_catalog_hits(&results)
+        .iter()
+        .map(|hit| hit.to_line())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "qobuz\talbum\t0886443927087\tMiles Davis\tKind of Blue (Legacy Edition)",
+            "qobuz\ttrack\thttps://open.qobuz.com/track/5966783\tMiles Davis\tSo What",
+        ]
+    );
+}
+
+fn collection_item(
+    band: &str,
+    title: &str,
+    sale_type: &str,
+    url: Option<&str>,
+) -> BandcampCollectionItem {
+    BandcampCollectionItem {
+        band_name: band.to_string(),
+        item_title: title.to_string(),
+        item_id: 1,
+        item_type: String::new(),
+        sale_item_type: sale_type.to_string(),
+        sale_item_id: 1,
+        token: String::new(),
+        gift_id: None,
+        gift_sender_name: None,
+        item_art_id: None,
+        item_url: url.map(str::to_string),
+    }
+}
+
+#[test]
+fn bandcamp_collection_is_filtered_locally() {
+    let purchases = BandcampPurchases {
+        items: vec![
+            collection_item(
+                "Low",
+                "Hey What",
+                "a",
+                Some("https://low.bandcamp.com/album/hey-what"),
+            ),
+            collection_item("Grouper", "Shade", "a", None),
+            collection_item("Slowdive", "kisses", "t", None),
+        ],
+        redownload_urls: Default::default(),
+    };
+    let hits = bandcamp_collection_hits(&purchases, "LOW");
+    assert_eq!(hits.len(), 2);
+    assert_eq!(
+        hits[0].get.as_deref(),
+        Some("https://low.bandcamp.com/album/hey-what")
+    );
+    assert_eq!(hits[1].kind, PurchaseKind::Track);
+    assert!(hits[1].to_line().contains("\t-\t"));
+}
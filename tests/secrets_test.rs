@@ -0,0 +1,26 @@
+use qoget::secrets::{Secret, keyring_entry};
+
+#[test]
+fn keyring_values_name_an_entry() {
+    assert_eq!(
+        keyring_entry("keyring:", Secret::QobuzPassword),
+        Some("qobuz-password")
+    );
+    assert_eq!(
+        keyring_entry("keyring:", Secret::BandcampIdentity),
+        Some("bandcamp-identity")
+    );
+    assert_eq!(
+        keyring_entry("keyring: work-account", Secret::QobuzPassword),
+        Some("work-account")
+    );
+    assert_eq!(keyring_entry("hunter2", Secret::QobuzPassword), None);
+}
+
+#[test]
+fn secret_names_round_trip() {
+    for secret in [Secret::QobuzPassword, Secret::BandcampIdentity] {
+        assert_eq!(secret.to_string().parse::<Secret>(), Ok(secret));
+    }
+    assert!("qobuz".parse::<Secret>().is_err());
+}
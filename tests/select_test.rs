@@ -0,0 +1,27 @@
+use std::collections::BTreeSet;
+
+use qoget::select::parse_selection;
+
+fn picked(input: &str, count: usize) -> Vec<usize> {
+    parse_selection(input, count).unwrap().into_iter().collect()
+}
+
+#[test]
+fn selections_count_from_one() {
+    assert_eq!(picked("1", 5), [0]);
+    assert_eq!(picked("1,3-5", 5), [0, 2, 3, 4]);
+    assert_eq!(picked(" 2 4 ", 5), [1, 3]);
+    assert_eq!(picked("2-3,3", 5), [1, 2]);
+    assert_eq!(picked("all", 3), [0, 1, 2]);
+    assert_eq!(picked("*", 2), [0, 1]);
+    assert_eq!(parse_selection("", 3).unwrap(), BTreeSet::new());
+}
+
+#[test]
+fn bad_selections_are_rejected() {
+    assert!(parse_selection("0", 3).is_err());
+    assert!(parse_selection("4", 3).is_err());
+    assert!(parse_selection("3-1", 3).is_err());
+    assert!(parse_selection("two", 3).is_err());
+    assert!(parse_selection("1-", 3).is_err());
+}
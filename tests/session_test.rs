@@ -0,0 +1,69 @@
+use qoget::session::{BandcampSession, QobuzSession, SessionFile};
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-session-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn missing_file_means_no_sessions() {
+    let dir = temp_dir("missing");
+    let sessions = SessionFile::load(&dir).unwrap();
+    assert!(sessions.qobuz.is_none());
+    assert!(sessions.bandcamp.is_none());
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let dir = temp_dir("roundtrip");
+    let sessions = SessionFile {
+        qobuz: Some(QobuzSession {
+            app_id: "123".to_string(),
+            app_secret: "secret".to_string(),
+            user_auth_token: "token".to_string(),
+            user_id: 42,
+        }),
+        bandcamp: Some(BandcampSession {
+            identity_cookie: "7%09abc".to_string(),
+            fan_id: 99,
+        }),
+    };
+    sessions.save(&dir).unwrap();
+
+    let loaded = SessionFile::load(&dir).unwrap();
+    assert_eq!(loaded.qobuz, sessions.qobuz);
+    assert_eq!(loaded.bandcamp, sessions.bandcamp);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(SessionFile::path(&dir))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn saving_one_service_keeps_the_other_absent() {
+    let dir = temp_dir("partial");
+    let sessions = SessionFile {
+        qobuz: None,
+        bandcamp: Some(BandcampSession {
+            identity_cookie: "cookie".to_string(),
+            fan_id: 1,
+        }),
+    };
+    sessions.save(&dir).unwrap();
+
+    let json = std::fs::read_to_string(SessionFile::path(&dir)).unwrap();
+    assert!(!json.contains("qobuz"));
+    let loaded = SessionFile::load(&dir).unwrap();
+    assert!(loaded.qobuz.is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,54 @@
+use qoget::models::BandcampDownloadFormat;
+use qoget::space::{available, check, human, parse_size};
+
+fn format(size_mb: &str) -> BandcampDownloadFormat {
+    BandcampDownloadFormat {
+        url: "https://popplers5.bandcamp.com/download/album".to_string(),
+        size_mb: size_mb.to_string(),
+    }
+}
+
+#[test]
+fn bandcamp_sizes_parse_to_bytes() {
+    assert_eq!(format("90MB").size_bytes(), Some(90 * 1024 * 1024));
+    assert_eq!(format("0.5MB").size_bytes(), Some(512 * 1024));
+    assert_eq!(format(" 12 MB ").size_bytes(), Some(12 * 1024 * 1024));
+    assert_eq!(format("12").size_bytes(), Some(12 * 1024 * 1024));
+    assert_eq!(format("").size_bytes(), None);
+    assert_eq!(format("big").size_bytes(), None);
+    assert_eq!(format("-3MB").size_bytes(), None);
+}
+
+#[test]
+fn sizes_read_as_mb_or_gb() {
+    assert_eq!(human(812 * 1024 * 1024), "812 MB");
+    assert_eq!(human(3_650_722_201), "3.4 GB");
+}
+
+#[test]
+fn budgets_parse_with_binary_units() {
+    assert_eq!(parse_size("25GB").unwrap(), 25 << 30);
+    assert_eq!(parse_size("500mb").unwrap(), 500 << 20);
+    assert_eq!(parse_size("1.5 TB").unwrap(), 3 << 39);
+    assert_eq!(parse_size("4096").unwrap(), 4096);
+    assert!(parse_size("lots").is_err());
+    assert!(parse_size("10 parsecs").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn check_fails_only_when_downloads_will_not_fit() {
+    // A directory that doesn't exist yet is measured on its parent.
+    let dir = std::env::temp_dir().join(format!("qoget-space-{}/Music", std::process::id()));
+    let free = available(&dir).expect("free space of the temp filesystem");
+    assert!(free > 0);
+
+    assert!(check("nothing", 0, &dir, false).is_ok());
+    let err = check("42 Qobuz tracks", u64::MAX / 2, &dir, false).unwrap_err();
+    let msg = err.to_string();
+    assert!(msg.starts_with("42 Qobuz tracks: about "), "{msg}");
+    assert!(msg.contains("--ignore-space"), "{msg}");
+
+    assert!(check("42 Qobuz tracks", u64::MAX / 2, &dir, true).is_ok());
+    assert!(!dir.exists());
+}
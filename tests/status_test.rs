@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{Album, AudioFormat, PurchaseList, Service, Track, TrackId};
+use qoget::path::Naming;
+use qoget::status::{SyncState, match_library, scan_library};
+
+mod common;
+
+fn make_track(id: u64, title: &str, number: u8) -> Track {
+    common::track(id, title, number, "Artist")
+}
+
+fn make_album(id: &str, title: &str, tracks: Option<Vec<Track>>) -> Album {
+    let album = common::album(id, title, "Artist");
+    match tracks {
+        Some(tracks) => common::with_tracks(album, tracks),
+        None => album,
+    }
+}
+
+fn paths(list: &[&str]) -> Vec<PathBuf> {
+    list.iter().map(PathBuf::from).collect()
+}
+
+#[test]
+fn classifies_complete_partial_and_missing_albums() {
+    let qobuz = PurchaseList {
+        albums: vec![
+            make_album(
+                "a1",
+                "Complete",
+                Some(vec![make_track(1, "One", 1), make_track(2, "Two", 2)]),
+            ),
+            make_album(
+                "a2",
+                "Partial",
+                Some(vec![make_track(3, "One", 1), make_track(4, "Two", 2)]),
+            ),
+            make_album("a3", "Missing", Some(vec![make_track(5, "One", 1)])),
+        ],
+        tracks: vec![],
+    };
+    let local = paths(&[
+        "Artist/Complete/01 - One.mp3",
+        "Artist/Complete/02 - Two.mp3",
+        "Artist/Partial/02 - Two.mp3",
+        "Someone Else/Ripped CD/01 - Song.flac",
+    ]);
+
+    let status = match_library(
+        &[(Service::Qobuz, &qobuz)],
+        &local,
+        &Manifest::default(),
+        Naming::default(),
+        None,
+    );
+
+    let states: Vec<(&str, SyncState, usize)> = status
+        .albums
+        .iter()
+        .map(|a| (a.title.as_str(), a.state, a.present))
+        .collect();
+    assert_eq!(
+        states,
+        [
+            ("Complete", SyncState::Complete, 2),
+            ("Partial", SyncState::Partial, 1),
+            ("Missing", SyncState::Missing, 0),
+        ]
+    );
+    assert_eq!(
+        status.unmatched,
+        paths(&["Someone Else/Ripped CD/01 - Song.flac"])
+    );
+}
+
+#[test]
+fn manifest_recorded_path_counts_as_present() {
+    let qobuz = PurchaseList {
+        albums: vec![make_album(
+            "a1",
+            "Album",
+            Some(vec![make_track(1, "One", 1)]),
+        )],
+        tracks: vec![],
+    };
+    let flac = PathBuf::from("Artist/Album/01 - One.flac");
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        ManifestEntry::new(
+            &qobuz.albums[0],
+            &make_track(1, "One", 1),
+            AudioFormat::Flac,
+            Path::new(""),
+            &flac,
+        ),
+    );
+
+    let status = match_library(
+        &[(Service::Qobuz, &qobuz)],
+        std::slice::from_ref(&flac),
+        &manifest,
+        Naming::default(),
+        None,
+    );
+    assert_eq!(status.albums[0].state, SyncState::Complete);
+    assert!(status.unmatched.is_empty());
+}
+
+#[test]
+fn albums_without_track_listing_match_by_directory() {
+    let bandcamp = PurchaseList {
+        albums: vec![
+            make_album("bc-1", "Here", None),
+            make_album("bc-2", "Gone", None),
+        ],
+        tracks: vec![],
+    };
+    let local = paths(&["Artist/Here/01 - Intro.m4a", "Artist/Here/02 - Outro.m4a"]);
+
+    let status = match_library(
+        &[(Service::Bandcamp, &bandcamp)],
+        &local,
+        &Manifest::default(),
+        Naming::default(),
+        None,
+    );
+    assert_eq!(status.albums[0].state, SyncState::Complete);
+    assert_eq!(status.albums[0].expected, None);
+    assert_eq!(status.albums[0].present, 2);
+    assert_eq!(status.albums[1].state, SyncState::Missing);
+    assert!(status.unmatched.is_empty());
+}
+
+#[test]
+fn scan_finds_audio_files_recursively() {
+    let root = std::env::temp_dir().join(format!("qoget-status-scan-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("A/B/Disc 1")).unwrap();
+    std::fs::write(root.join("A/B/Disc 1/01 - x.mp3"), b"x").unwrap();
+    std::fs::write(root.join("A/B/cover.jpg"), b"x").unwrap();
+    std::fs::write(root.join("A/02 - y.FLAC"), b"x").unwrap();
+    std::fs::write(root.join(".qoget-manifest.json"), b"{}").unwrap();
+
+    let files = scan_library(&root).unwrap();
+    assert_eq!(files, paths(&["A/02 - y.FLAC", "A/B/Disc 1/01 - x.mp3"]));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
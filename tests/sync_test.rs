@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use qoget::manifest::{Manifest, ManifestEntry};
+use qoget::models::{
+    Album, AudioFormat, PurchaseList, Service, SizeMismatchPolicy, SkipReason, Track, TrackId,
+};
+use qoget::path::{Naming, UnicodeForm};
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+
+mod common;
+
+fn make_track(id: u64, title: &str, number: u8) -> Track {
+    common::track(id, title, number, "Artist")
+}
+
+fn make_album(id: &str, title: &str, tracks: Vec<Track>) -> Album {
+    common::with_tracks(common::album(id, title, "Artist"), tracks)
+}
+
+#[tokio::test]
+async fn plan_groups_tracks_by_album() {
+    let purchases = PurchaseList {
+        albums: vec![
+            make_album(
+                "a1",
+                "First",
+                vec![make_track(2, "Two", 2), make_track(1, "One", 1)],
+            ),
+            make_album("a2", "Second", vec![make_track(3, "Three", 1)]),
+        ],
+        tracks: vec![],
+    };
+
+    let tasks = collect_tasks(
+        &purchases,
+        Path::new("/nonexistent"),
+        ".mp3",
+        Naming::default(),
+    );
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+
+    assert_eq!(plan.total_tracks, 3);
+    assert_eq!(plan.albums.len(), 2);
+    assert_eq!(plan.albums[0].album.title, "First");
+    assert_eq!(plan.albums[1].album.title, "Second");
+
+    let first: Vec<u8> = plan.albums[0]
+        .downloads
+        .iter()
+        .map(|t| t.track.track_number.0)
+        .collect();
+    assert_eq!(first, vec![1, 2]);
+    assert_eq!(plan.download_count(), 3);
+    assert_eq!(plan.downloads().count(), 3);
+    // Three 200-second MP3s at 320 kbps.
+    assert_eq!(plan.estimated_bytes(), 3 * 200 * 40_000);
+}
+
+#[tokio::test]
+async fn budget_defers_whole_albums_in_order() {
+    let purchases = PurchaseList {
+        albums: vec![
+            make_album(
+                "a1",
+                "First",
+                vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+            ),
+            make_album("a2", "Second", vec![make_track(3, "Three", 1)]),
+            make_album("a3", "Third", vec![make_track(4, "Four", 1)]),
+        ],
+        tracks: vec![],
+    };
+    let track_size = 200 * 40_000;
+
+    let plan_with_budget = |budget| {
+        let tasks = collect_tasks(
+            &purchases,
+            Path::new("/nonexistent"),
+            ".mp3",
+            Naming::default(),
+        );
+        async move {
+            let existing = scan_existing(
+                &tasks,
+                Service::Qobuz,
+                &Manifest::default(),
+                Naming::default(),
+            )
+            .await
+            .unwrap();
+            let mut plan = build_sync_plan(
+                tasks,
+                &existing,
+                false,
+                SizeMismatchPolicy::Keep,
+                Naming::default(),
+            );
+            let deferred = plan.defer_beyond(budget);
+            (plan, deferred)
+        }
+    };
+
+    // Room for the first two albums but not the third.
+    let (plan, deferred) = plan_with_budget(3 * track_size + 1).await;
+    assert_eq!(deferred, 1);
+    assert_eq!(plan.download_count(), 3);
+    let skipped: Vec<&str> = plan
+        .skipped()
+        .filter(|s| matches!(s.reason, SkipReason::Deferred))
+        .map(|s| s.track.title.as_str())
+        .collect();
+    assert_eq!(skipped, ["Four"]);
+
+    // The first album goes ahead even when it alone is over budget.
+    let (plan, deferred) = plan_with_budget(1).await;
+    assert_eq!(deferred, 2);
+    assert_eq!(plan.download_count(), 2);
+}
+
+#[tokio::test]
+async fn unselected_albums_are_skipped() {
+    let purchases = PurchaseList {
+        albums: vec![
+            make_album(
+                "a1",
+                "First",
+                vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+            ),
+            make_album("a2", "Second", vec![make_track(3, "Three", 1)]),
+            make_album("a3", "Third", vec![make_track(4, "Four", 1)]),
+        ],
+        tracks: vec![],
+    };
+    let tasks = collect_tasks(
+        &purchases,
+        Path::new("/nonexistent"),
+        ".mp3",
+        Naming::default(),
+    );
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let mut plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+
+    let titles: Vec<&str> = plan
+        .albums_to_download()
+        .map(|a| a.album.title.as_str())
+        .collect();
+    assert_eq!(titles, ["First", "Second", "Third"]);
+
+    let skipped = plan.select_albums(&[0, 2].into_iter().collect());
+    assert_eq!(skipped, 1);
+    assert_eq!(plan.download_count(), 3);
+    let not_selected: Vec<&str> = plan
+        .skipped()
+        .filter(|s| matches!(s.reason, SkipReason::NotSelected))
+        .map(|s| s.track.title.as_str())
+        .collect();
+    assert_eq!(not_selected, ["Three"]);
+}
+
+#[tokio::test]
+async fn tracks_can_be_skipped_by_id() {
+    let purchases = PurchaseList {
+        albums: vec![
+            make_album(
+                "a1",
+                "First",
+                vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+            ),
+            make_album("a2", "Second", vec![make_track(3, "Three", 1)]),
+        ],
+        tracks: vec![],
+    };
+    let ids: HashSet<TrackId> = [TrackId(2), TrackId(3)].into_iter().collect();
+    for dry_run in [false, true] {
+        let tasks = collect_tasks(
+            &purchases,
+            Path::new("/nonexistent"),
+            ".mp3",
+            Naming::default(),
+        );
+        let existing = scan_existing(
+            &tasks,
+            Service::Qobuz,
+            &Manifest::default(),
+            Naming::default(),
+        )
+        .await
+        .unwrap();
+        let mut plan = build_sync_plan(
+            tasks,
+            &existing,
+            dry_run,
+            SizeMismatchPolicy::Keep,
+            Naming::default(),
+        );
+
+        assert_eq!(plan.skip_tracks(&ids, SkipReason::NotEntitled), 2);
+        let not_entitled: Vec<&str> = plan
+            .skipped()
+            .filter(|s| matches!(s.reason, SkipReason::NotEntitled))
+            .map(|s| s.track.title.as_str())
+            .collect();
+        assert_eq!(not_entitled, ["Two", "Three"]);
+        assert_eq!(plan.download_count(), if dry_run { 0 } else { 1 });
+    }
+}
+
+#[tokio::test]
+async fn standalone_duplicate_folds_into_album() {
+    let purchases = PurchaseList {
+        albums: vec![make_album(
+            "a1",
+            "Album",
+            vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+        )],
+        tracks: vec![make_track(1, "One", 1)],
+    };
+
+    let tasks = collect_tasks(
+        &purchases,
+        Path::new("/nonexistent"),
+        ".mp3",
+        Naming::default(),
+    );
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+
+    assert_eq!(plan.total_tracks, 2);
+    assert_eq!(plan.albums.len(), 1);
+    assert_eq!(plan.albums[0].album.id.0, "a1");
+}
+
+#[tokio::test]
+async fn versioned_editions_each_get_their_tracks() {
+    let base = std::env::temp_dir().join(format!("qoget-sync-editions-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let standard = make_album(
+        "a1",
+        "Record",
+        vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+    );
+    let mut deluxe = make_album(
+        "a2",
+        "Record",
+        vec![
+            make_track(1, "One", 1),
+            make_track(2, "Two", 2),
+            make_track(3, "Bonus", 3),
+        ],
+    );
+    deluxe.version = Some("Deluxe Edition".to_string());
+    let purchases = PurchaseList {
+        albums: vec![standard, deluxe],
+        tracks: vec![],
+    };
+
+    let naming = Naming {
+        album_versions: true,
+        ..Naming::default()
+    };
+    let tasks = collect_tasks(&purchases, &base, ".mp3", naming);
+    // The standard edition is synced already, and the manifest knows it.
+    let mut manifest = Manifest::default();
+    for task in tasks.iter().filter(|t| t.album.id.0 == "a1") {
+        std::fs::create_dir_all(task.target_path.parent().unwrap()).unwrap();
+        std::fs::write(&task.target_path, b"ID3").unwrap();
+        let entry = ManifestEntry::new(
+            &task.album,
+            &task.track,
+            AudioFormat::Mp3,
+            &base,
+            &task.target_path,
+        );
+        manifest.record(Service::Qobuz, task.track.id, entry);
+    }
+    let existing = scan_existing(&tasks, Service::Qobuz, &manifest, naming)
+        .await
+        .unwrap();
+    let plan = build_sync_plan(tasks, &existing, false, SizeMismatchPolicy::Keep, naming);
+
+    assert_eq!(plan.total_tracks, 5);
+    assert_eq!(plan.skipped_count(), 2);
+    let downloads: Vec<_> = plan.downloads().map(|t| t.target_path.clone()).collect();
+    assert_eq!(
+        downloads,
+        ["01 - One.mp3", "02 - Two.mp3", "03 - Bonus.mp3"]
+            .map(|name| base.join("Artist/Record (Deluxe Edition)").join(name))
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn dry_run_skips_everything() {
+    let purchases = PurchaseList {
+        albums: vec![make_album("a1", "Album", vec![make_track(1, "One", 1)])],
+        tracks: vec![],
+    };
+
+    let tasks = collect_tasks(
+        &purchases,
+        Path::new("/nonexistent"),
+        ".mp3",
+        Naming::default(),
+    );
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        true,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+
+    assert_eq!(plan.download_count(), 0);
+    assert_eq!(plan.skipped_count(), 1);
+    let (downloads, skipped) = plan.into_parts();
+    assert!(downloads.is_empty());
+    assert_eq!(skipped.len(), 1);
+}
+
+#[tokio::test]
+async fn manifest_format_used_for_existence_check() {
+    let base = std::env::temp_dir().join(format!("qoget-sync-test-{}", std::process::id()));
+    let purchases = PurchaseList {
+        albums: vec![make_album("a1", "Album", vec![make_track(1, "One", 1)])],
+        tracks: vec![],
+    };
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+
+    // Track was downloaded as FLAC (format fallback)
+    let flac_path = tasks[0].target_path.with_extension("flac");
+    std::fs::create_dir_all(flac_path.parent().unwrap()).unwrap();
+    std::fs::write(&flac_path, b"fLaC").unwrap();
+
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    assert_eq!(
+        plan.download_count(),
+        1,
+        "unrecorded fallback is not guessed"
+    );
+
+    let mut manifest = Manifest::default();
+    let entry = ManifestEntry::new(
+        &purchases.albums[0],
+        &make_track(1, "One", 1),
+        AudioFormat::Flac,
+        &base,
+        &flac_path,
+    );
+    manifest.record(Service::Qobuz, TrackId(1), entry);
+    manifest.save(&base).unwrap();
+    let manifest = Manifest::load(&base).unwrap();
+
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    let existing = scan_existing(&tasks, Service::Qobuz, &manifest, Naming::default())
+        .await
+        .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    assert_eq!(plan.download_count(), 0);
+    assert_eq!(plan.skipped_count(), 1);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn normalization_matches_existing_names_in_other_form() {
+    let naming = Naming {
+        unicode_form: Some(UnicodeForm::Nfc),
+        ..Naming::default()
+    };
+    let base = std::env::temp_dir().join(format!("qoget-sync-nfc-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let purchases = PurchaseList {
+        albums: vec![make_album(
+            "a1",
+            "Cafe\u{301}",
+            vec![make_track(1, "E\u{301}te\u{301}", 1)],
+        )],
+        tracks: vec![],
+    };
+    let tasks = collect_tasks(&purchases, &base, ".mp3", naming);
+    assert!(tasks[0].target_path.ends_with("Artist/Café/01 - Été.mp3"));
+
+    // Same track previously synced with decomposed (NFD) names
+    let nfd_path = base.join("Artist/Cafe\u{301}/01 - E\u{301}te\u{301}.mp3");
+    std::fs::create_dir_all(nfd_path.parent().unwrap()).unwrap();
+    std::fs::write(&nfd_path, b"ID3").unwrap();
+
+    let existing = scan_existing(&tasks, Service::Qobuz, &Manifest::default(), naming)
+        .await
+        .unwrap();
+    let plan = build_sync_plan(tasks, &existing, false, SizeMismatchPolicy::Keep, naming);
+    assert_eq!(plan.download_count(), 0);
+    assert_eq!(plan.skipped_count(), 1);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn implausible_size_follows_mismatch_policy() {
+    let base = std::env::temp_dir().join(format!("qoget-sync-size-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let purchases = PurchaseList {
+        albums: vec![make_album(
+            "a1",
+            "Album",
+            vec![make_track(1, "One", 1), make_track(2, "Two", 2)],
+        )],
+        tracks: vec![],
+    };
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    std::fs::create_dir_all(tasks[0].target_path.parent().unwrap()).unwrap();
+    // 200 s at 128 kbps: an old rip where a 320 kbps download is planned
+    let old_rip = std::fs::File::create(&tasks[0].target_path).unwrap();
+    old_rip.set_len(200 * 16_000).unwrap();
+    // 200 s at 320 kbps
+    let good = std::fs::File::create(&tasks[1].target_path).unwrap();
+    good.set_len(200 * 40_000).unwrap();
+    let rip_path = tasks[0].target_path.clone();
+
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    assert_eq!(plan.download_count(), 0);
+    assert_eq!(plan.size_mismatches.len(), 1);
+    assert_eq!(plan.size_mismatches[0].path, rip_path);
+    assert_eq!(plan.size_mismatches[0].actual, 200 * 16_000);
+    let reasons: Vec<bool> = plan
+        .skipped()
+        .map(|s| matches!(s.reason, SkipReason::SizeMismatch))
+        .collect();
+    assert_eq!(reasons, [true, false]);
+
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Overwrite,
+        Naming::default(),
+    );
+    let downloads: Vec<_> = plan.downloads().map(|t| t.target_path.clone()).collect();
+    assert_eq!(downloads, std::slice::from_ref(&rip_path));
+
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    let existing = scan_existing(
+        &tasks,
+        Service::Qobuz,
+        &Manifest::default(),
+        Naming::default(),
+    )
+    .await
+    .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::KeepBoth,
+        Naming::default(),
+    );
+    let downloads: Vec<_> = plan.downloads().map(|t| t.target_path.clone()).collect();
+    assert_eq!(downloads, [rip_path.with_file_name("01 - One (qoget).mp3")]);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn recorded_files_are_not_size_checked() {
+    let base = std::env::temp_dir().join(format!("qoget-sync-trusted-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let purchases = PurchaseList {
+        albums: vec![make_album("a1", "Album", vec![make_track(1, "One", 1)])],
+        tracks: vec![],
+    };
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    std::fs::create_dir_all(tasks[0].target_path.parent().unwrap()).unwrap();
+    std::fs::write(&tasks[0].target_path, b"ID3").unwrap();
+
+    let mut manifest = Manifest::default();
+    let entry = ManifestEntry::new(
+        &purchases.albums[0],
+        &make_track(1, "One", 1),
+        AudioFormat::Mp3,
+        &base,
+        &tasks[0].target_path,
+    );
+    manifest.record(Service::Qobuz, TrackId(1), entry);
+
+    let existing = scan_existing(&tasks, Service::Qobuz, &manifest, Naming::default())
+        .await
+        .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Overwrite,
+        Naming::default(),
+    );
+    assert_eq!(plan.download_count(), 0);
+    assert!(plan.size_mismatches.is_empty());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
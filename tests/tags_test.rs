@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use qoget::manifest::file_sha256;
+use qoget::tags::{mp4_freeform, mp4_genre, set_mp4_freeform, set_mp4_genre};
+
+fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// `moov` with a single chunk offset table pointing at `offset`.
+fn moov(offset: u32) -> Vec<u8> {
+    let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    stco.extend_from_slice(&offset.to_be_bytes());
+    let stbl = atom(b"stbl", &atom(b"stco", &stco));
+    let minf = atom(b"minf", &stbl);
+    let mdia = atom(b"mdia", &minf);
+    atom(b"moov", &atom(b"trak", &mdia))
+}
+
+/// Read the first chunk offset back out of a file built by `moov`.
+fn chunk_offset(file: &[u8]) -> usize {
+    let at = file.windows(4).position(|w| w == b"stco").unwrap();
+    u32::from_be_bytes(file[at + 12..at + 16].try_into().unwrap()) as usize
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qoget-tags-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("01 - Track.m4a");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn genre_is_added_and_audio_offsets_follow() {
+    let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+    let audio = b"AUDIO-FRAMES";
+    // Sample data after the moov, as in streaming-friendly files
+    let offset = (ftyp.len() + moov(0).len() + 8) as u32;
+    let mut file = ftyp.clone();
+    file.extend(moov(offset));
+    file.extend(atom(b"mdat", audio));
+    let path = temp_file("after", &file);
+
+    assert_eq!(mp4_genre(&path).unwrap(), None);
+    let sha256 = set_mp4_genre(&path, "Ambient").unwrap();
+    assert_eq!(mp4_genre(&path).unwrap().as_deref(), Some("Ambient"));
+    assert_eq!(sha256, file_sha256(&path).unwrap());
+
+    let tagged = std::fs::read(&path).unwrap();
+    let at = chunk_offset(&tagged);
+    assert_eq!(&tagged[at..at + audio.len()], audio);
+
+    // Setting it again replaces the genre rather than adding another.
+    set_mp4_genre(&path, "Post-Rock").unwrap();
+    assert_eq!(mp4_genre(&path).unwrap().as_deref(), Some("Post-Rock"));
+    let retagged = std::fs::read(&path).unwrap();
+    assert_eq!(retagged.windows(4).filter(|w| w == b"\xa9gen").count(), 1);
+    let at = chunk_offset(&retagged);
+    assert_eq!(&retagged[at..at + audio.len()], audio);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn offsets_before_moov_are_left_alone() {
+    let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+    let offset = (ftyp.len() + 8) as u32;
+    let mut file = ftyp.clone();
+    file.extend(atom(b"mdat", b"AUDIO"));
+    file.extend(moov(offset));
+    let path = temp_file("before", &file);
+
+    set_mp4_genre(&path, "Jazz").unwrap();
+    let tagged = std::fs::read(&path).unwrap();
+    assert_eq!(chunk_offset(&tagged), offset as usize);
+    assert_eq!(&tagged[..ftyp.len() + 13], &file[..ftyp.len() + 13]);
+    assert_eq!(mp4_genre(&path).unwrap().as_deref(), Some("Jazz"));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn files_without_moov_are_rejected() {
+    let path = temp_file("nomoov", &atom(b"ftyp", b"M4A \0\0\0\0"));
+    assert!(set_mp4_genre(&path, "Jazz").is_err());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn freeform_items_are_replaced_by_name() {
+    let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+    let audio = b"AUDIO-FRAMES";
+    let offset = (ftyp.len() + moov(0).len() + 8) as u32;
+    let mut file = ftyp.clone();
+    file.extend(moov(offset));
+    file.extend(atom(b"mdat", audio));
+    let path = temp_file("freeform", &file);
+
+    set_mp4_genre(&path, "Ambient").unwrap();
+    set_mp4_freeform(
+        &path,
+        &[
+            ("replaygain_track_gain", "-3.20 dB"),
+            ("replaygain_album_gain", "-4.00 dB"),
+        ],
+    )
+    .unwrap();
+    let sha256 = set_mp4_freeform(&path, &[("replaygain_track_gain", "-2.50 dB")]).unwrap();
+    assert_eq!(sha256, file_sha256(&path).unwrap());
+
+    assert_eq!(
+        mp4_freeform(&path, "replaygain_track_gain")
+            .unwrap()
+            .as_deref(),
+        Some("-2.50 dB")
+    );
+    assert_eq!(
+        mp4_freeform(&path, "replaygain_album_gain")
+            .unwrap()
+            .as_deref(),
+        Some("-4.00 dB")
+    );
+    assert_eq!(mp4_freeform(&path, "replaygain_track_peak").unwrap(), None);
+    assert_eq!(mp4_genre(&path).unwrap().as_deref(), Some("Ambient"));
+    let tagged = std::fs::read(&path).unwrap();
+    assert_eq!(tagged.windows(4).filter(|w| w == b"----").count(), 2);
+    let at = chunk_offset(&tagged);
+    assert_eq!(&tagged[at..at + audio.len()], audio);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use qoget::models::AudioFormat;
+use qoget::transcode::{Codec, TranscodeTarget, planned_format};
+
+#[test]
+fn targets_parse_as_codec_and_bitrate() {
+    let target: TranscodeTarget = "opus-128".parse().unwrap();
+    assert_eq!(
+        target,
+        TranscodeTarget {
+            codec: Codec::Opus,
+            bitrate: 128
+        }
+    );
+    assert_eq!(target.to_string(), "opus-128");
+    assert_eq!(target.format().extension(), ".opus");
+
+    let aac: TranscodeTarget = "AAC-256".parse().unwrap();
+    assert_eq!(aac.format(), AudioFormat::Aac);
+    assert_eq!(aac.to_string(), "aac-256");
+
+    for bad in ["opus", "flac-900", "opus-12", "mp3-fast", "mp3-1000"] {
+        assert!(bad.parse::<TranscodeTarget>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn ffmpeg_keeps_tags_and_names_the_muxer() {
+    let target: TranscodeTarget = "mp3-192".parse().unwrap();
+    let args = target.ffmpeg_args(Path::new("in.flac"), Path::new("out.mp3.tmp"));
+    let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap()).collect();
+    let window = |w: &[&str]| args.windows(w.len()).any(|a| a == w);
+    assert!(window(&["-i", "in.flac"]), "{args:?}");
+    assert!(window(&["-map_metadata", "0"]), "{args:?}");
+    assert!(window(&["-c:a", "libmp3lame"]), "{args:?}");
+    assert!(window(&["-b:a", "192k"]), "{args:?}");
+    // The temp name's extension can't tell ffmpeg the format.
+    assert!(window(&["-f", "mp3", "out.mp3.tmp"]), "{args:?}");
+    assert_eq!(args.last(), Some(&"out.mp3.tmp"));
+}
+
+#[test]
+fn planning_uses_the_target_format() {
+    assert_eq!(planned_format(AudioFormat::Mp3, None), AudioFormat::Mp3);
+    let opus = Some("opus-96".parse().unwrap());
+    assert_eq!(planned_format(AudioFormat::Mp3, opus), AudioFormat::Opus);
+    assert_eq!(planned_format(AudioFormat::Aac, opus), AudioFormat::Opus);
+    assert_eq!(planned_format(AudioFormat::Aac, None), AudioFormat::Aac);
+}
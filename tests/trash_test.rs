@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use qoget::trash::{Trash, batch_name, empty, list_batches};
+
+#[test]
+fn batch_names_are_utc_timestamps() {
+    assert_eq!(batch_name(0), "19700101T000000Z");
+    assert_eq!(batch_name(951_782_400), "20000229T000000Z");
+    assert_eq!(batch_name(1_700_000_000), "20231114T221320Z");
+}
+
+#[test]
+fn moved_files_keep_relative_paths() {
+    let root = std::env::temp_dir().join(format!("qoget-trash-move-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Artist/Album")).unwrap();
+    std::fs::write(root.join("Artist/Album/01 - One.mp3"), b"first").unwrap();
+
+    let bin = Trash::with_batch(&root, "20260101T000000Z");
+    let dest = bin
+        .move_file(Path::new("Artist/Album/01 - One.mp3"))
+        .unwrap();
+    assert_eq!(dest, bin.batch_dir().join("Artist/Album/01 - One.mp3"));
+    assert!(!root.join("Artist/Album/01 - One.mp3").exists());
+
+    // Same path trashed again in the same batch, given as an absolute path
+    std::fs::write(root.join("Artist/Album/01 - One.mp3"), b"second").unwrap();
+    let dest = bin
+        .move_file(&root.join("Artist/Album/01 - One.mp3"))
+        .unwrap();
+    assert_eq!(dest, bin.batch_dir().join("Artist/Album/01 - One.mp3.1"));
+    assert_eq!(std::fs::read(&dest).unwrap(), b"second");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn list_and_empty_batches() {
+    let root = std::env::temp_dir().join(format!("qoget-trash-empty-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("A/B")).unwrap();
+    std::fs::write(root.join("A/B/1.mp3"), b"12345").unwrap();
+    std::fs::write(root.join("A/B/2.mp3"), b"123").unwrap();
+    std::fs::write(root.join("A/B/3.mp3"), b"1").unwrap();
+
+    assert!(list_batches(&root).unwrap().is_empty());
+
+    let later = Trash::with_batch(&root, "20260102T000000Z");
+    later.move_file(Path::new("A/B/3.mp3")).unwrap();
+    let earlier = Trash::with_batch(&root, "20260101T000000Z");
+    earlier.move_file(Path::new("A/B/1.mp3")).unwrap();
+    earlier.move_file(Path::new("A/B/2.mp3")).unwrap();
+
+    let batches = list_batches(&root).unwrap();
+    let summary: Vec<(&str, usize, u64)> = batches
+        .iter()
+        .map(|b| (b.name.as_str(), b.files, b.bytes))
+        .collect();
+    assert_eq!(
+        summary,
+        [("20260101T000000Z", 2, 8), ("20260102T000000Z", 1, 1)]
+    );
+
+    let removed = empty(&root).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(!Trash::dir(&root).exists());
+    assert!(list_batches(&root).unwrap().is_empty());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+
+use qoget::checksums::CHECKSUM_FILE;
+use qoget::manifest::{Manifest, ManifestEntry, file_sha256};
+use qoget::models::{
+    Album, AudioFormat, PurchaseList, Service, SizeMismatchPolicy, Track, TrackId,
+};
+use qoget::path::Naming;
+use qoget::sync::{build_sync_plan, collect_tasks, scan_existing};
+use qoget::verify::{
+    Damage, LocalFile, MIN_TRACK_BYTES, VerifyReport, check_checksum_files, check_integrity,
+    check_manifest, classify, scan_files,
+};
+
+mod common;
+
+fn make_track(id: u64, title: &str, number: u8) -> Track {
+    common::track(id, title, number, "Artist")
+}
+
+fn make_album(tracks: Vec<Track>) -> Album {
+    common::with_tracks(common::album("a1", "Album", "Artist"), tracks)
+}
+
+fn entry(path: &str, size: Option<u64>) -> ManifestEntry {
+    let album = make_album(vec![]);
+    let mut e = ManifestEntry::new(
+        &album,
+        &make_track(1, "One", 1),
+        AudioFormat::Mp3,
+        Path::new(""),
+        Path::new(path),
+    );
+    e.size = size;
+    e
+}
+
+fn file(path: &str, size: u64) -> LocalFile {
+    LocalFile {
+        path: PathBuf::from(path),
+        size,
+    }
+}
+
+#[test]
+fn moved_file_found_by_size() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Artist/Album/01 - One.mp3", Some(1000)),
+    );
+    manifest.record(
+        Service::Qobuz,
+        TrackId(2),
+        entry("Artist/Album/02 - Two.mp3", Some(2000)),
+    );
+
+    let local = [
+        file("Artist/Album/02 - Two.mp3", 2000),
+        file("Sorted/Artist - Album/01 One.mp3", 1000),
+        file("Sorted/unrelated.mp3", 1234),
+    ];
+    let report = check_manifest(&manifest, &local);
+
+    assert!(report.missing.is_empty());
+    assert_eq!(report.moved.len(), 1);
+    assert_eq!(report.moved[0].key, "qobuz:1");
+    assert_eq!(
+        report.moved[0].to,
+        PathBuf::from("Sorted/Artist - Album/01 One.mp3")
+    );
+}
+
+#[test]
+fn ambiguous_or_unmatched_files_are_missing() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Artist/Album/01 - One.mp3", Some(1000)),
+    );
+
+    // Two same-size candidates with different names: can't tell which
+    let local = [file("x/a.mp3", 1000), file("y/b.mp3", 1000)];
+    let report = check_manifest(&manifest, &local);
+    assert!(report.moved.is_empty());
+    assert_eq!(report.missing.len(), 1);
+
+    // Same-name candidate breaks the tie
+    let local = [file("x/a.mp3", 1000), file("y/01 - One.mp3", 1000)];
+    let report = check_manifest(&manifest, &local);
+    assert_eq!(report.moved[0].to, PathBuf::from("y/01 - One.mp3"));
+
+    // Different size or extension never matches
+    let local = [file("x/01 - One.mp3", 999), file("y/01 - One.flac", 1000)];
+    let report = check_manifest(&manifest, &local);
+    assert_eq!(
+        report.missing[0].path,
+        PathBuf::from("Artist/Album/01 - One.mp3")
+    );
+}
+
+#[test]
+fn entries_without_size_need_matching_name() {
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Artist/Album/01 - One.mp3", None),
+    );
+
+    let report = check_manifest(&manifest, &[file("elsewhere/other.mp3", 1000)]);
+    assert_eq!(report.missing.len(), 1);
+
+    let report = check_manifest(&manifest, &[file("elsewhere/01 - One.mp3", 1000)]);
+    assert_eq!(report.moved.len(), 1);
+}
+
+#[tokio::test]
+async fn relocated_entry_is_not_redownloaded() {
+    let base = std::env::temp_dir().join(format!("qoget-verify-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let purchases = PurchaseList {
+        albums: vec![make_album(vec![make_track(1, "One", 1)])],
+        tracks: vec![],
+    };
+
+    let moved = base.join("Elsewhere/one.mp3");
+    std::fs::create_dir_all(moved.parent().unwrap()).unwrap();
+    std::fs::write(&moved, b"ID3").unwrap();
+
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        ManifestEntry::new(
+            &purchases.albums[0],
+            &make_track(1, "One", 1),
+            AudioFormat::Mp3,
+            &base,
+            &base.join("Artist/Album/01 - One.mp3"),
+        ),
+    );
+    assert!(manifest.relocate("qobuz:1", PathBuf::from("Elsewhere/one.mp3")));
+    manifest.save(&base).unwrap();
+    let manifest = Manifest::load(&base).unwrap();
+
+    let tasks = collect_tasks(&purchases, &base, ".mp3", Naming::default());
+    let existing = scan_existing(&tasks, Service::Qobuz, &manifest, Naming::default())
+        .await
+        .unwrap();
+    let plan = build_sync_plan(
+        tasks,
+        &existing,
+        false,
+        SizeMismatchPolicy::Keep,
+        Naming::default(),
+    );
+    assert_eq!(plan.download_count(), 0);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn classify_flags_empty_truncated_and_non_audio_files() {
+    let big = MIN_TRACK_BYTES * 10;
+    assert_eq!(classify(0, b"", "mp3"), Some(Damage::Empty));
+    assert_eq!(
+        classify(big, b"<!DOCTYPE html><html><body>Error</body>", "m4a"),
+        Some(Damage::Html)
+    );
+    assert_eq!(
+        classify(1000, b"ID3\x04\x00", "mp3"),
+        Some(Damage::Truncated { size: 1000 })
+    );
+    assert_eq!(
+        classify(big, b"fLaC\x00\x00", "mp3"),
+        Some(Damage::WrongFormat)
+    );
+}
+
+#[test]
+fn classify_accepts_valid_audio_heads() {
+    let big = MIN_TRACK_BYTES * 10;
+    assert_eq!(classify(big, b"ID3\x04\x00\x00", "mp3"), None);
+    assert_eq!(classify(big, b"\xFF\xFB\x90\x00", "mp3"), None);
+    assert_eq!(classify(big, b"fLaC\x00\x00\x00\x22", "FLAC"), None);
+    assert_eq!(classify(big, b"\x00\x00\x00\x20ftypM4A ", "m4a"), None);
+}
+
+#[test]
+fn check_integrity_detects_checksum_mismatch() {
+    let root = std::env::temp_dir().join(format!("qoget-verify-sum-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Artist/Album")).unwrap();
+    let rel = Path::new("Artist/Album/01 - One.mp3");
+    let mut body = b"ID3\x04\x00".to_vec();
+    body.resize(MIN_TRACK_BYTES as usize * 2, 0);
+    std::fs::write(root.join(rel), &body).unwrap();
+
+    let album = make_album(vec![make_track(1, "One", 1)]);
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        ManifestEntry::new(
+            &album,
+            &make_track(1, "One", 1),
+            AudioFormat::Mp3,
+            &root,
+            &root.join(rel),
+        ),
+    );
+
+    let mut report = VerifyReport::default();
+    check_integrity(&root, &manifest, true, &mut report).unwrap();
+    assert!(report.is_clean());
+
+    // Same size, same header, different contents.
+    let last = body.len() - 1;
+    body[last] = 1;
+    std::fs::write(root.join(rel), &body).unwrap();
+
+    let mut report = VerifyReport::default();
+    check_integrity(&root, &manifest, false, &mut report).unwrap();
+    assert!(report.is_clean(), "checksums are opt-in");
+    check_integrity(&root, &manifest, true, &mut report).unwrap();
+    assert_eq!(report.damaged.len(), 1);
+    assert!(matches!(
+        report.damaged[0].damage,
+        Damage::ChecksumMismatch { .. }
+    ));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn checksum_files_cover_files_without_recorded_checksums() {
+    let root = std::env::temp_dir().join(format!("qoget-verify-file-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Artist/Album")).unwrap();
+    let mut body = b"ID3\x04\x00".to_vec();
+    body.resize(MIN_TRACK_BYTES as usize * 2, 0);
+    let names = ["01 - One.mp3", "02 - Two.mp3"];
+    let mut sums = String::new();
+    for name in names {
+        let path = root.join("Artist/Album").join(name);
+        std::fs::write(&path, &body).unwrap();
+        sums += &format!("{}  {name}\n", file_sha256(&path).unwrap());
+    }
+    std::fs::write(root.join("Artist/Album").join(CHECKSUM_FILE), sums).unwrap();
+    // Only the first is in the manifest, without a checksum.
+    let mut manifest = Manifest::default();
+    manifest.record(
+        Service::Qobuz,
+        TrackId(1),
+        entry("Artist/Album/01 - One.mp3", None),
+    );
+
+    let local = scan_files(&root).unwrap();
+    let mut report = VerifyReport::default();
+    check_checksum_files(&root, &manifest, &local, &mut report).unwrap();
+    assert!(report.is_clean());
+
+    let last = body.len() - 1;
+    body[last] = 1;
+    for name in names {
+        std::fs::write(root.join("Artist/Album").join(name), &body).unwrap();
+    }
+    check_checksum_files(&root, &manifest, &local, &mut report).unwrap();
+    let damaged: Vec<(&str, &Path)> = report
+        .damaged
+        .iter()
+        .map(|d| (d.key.as_str(), d.path.as_path()))
+        .collect();
+    assert_eq!(
+        damaged,
+        [
+            ("qobuz:1", Path::new("Artist/Album/01 - One.mp3")),
+            ("", Path::new("Artist/Album/02 - Two.mp3")),
+        ]
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}